@@ -0,0 +1,1092 @@
+//! アセンブリ言語の1命令を機械語に変換するアセンブラと、ラベルを解決して.sファイル全体を変換する2パスのアセンブラ
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::instructions::{Address, Immediate, Instruction, InstructionType, Register};
+use crate::symbols::Symbol;
+
+/// ABIでのレジスタ名（インデックスがレジスタ番号）
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+/// アセンブルエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmError {
+    /// 空の行
+    Empty,
+    /// 未知のニーモニック
+    UnknownMnemonic(String),
+    /// オペランドの数が違う
+    OperandCount { mnemonic: String, expected: usize, found: usize },
+    /// 不正なレジスタ指定
+    InvalidRegister(String),
+    /// 不正な即値
+    InvalidImmediate(String),
+    /// 即値が範囲外
+    ImmediateOutOfRange(i64),
+    /// 定義されていないラベル
+    UndefinedLabel(String),
+    /// 同じ名前のラベルが2回定義されている
+    DuplicateLabel(String),
+    /// ラベルに使えない名前
+    InvalidLabel(String),
+    /// 不正なディレクティブ（内容は説明）
+    InvalidDirective(String),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmError::Empty => write!(f, "命令がありません"),
+            AsmError::UnknownMnemonic(m) => write!(f, "未知の命令です: {}", m),
+            AsmError::OperandCount { mnemonic, expected, found } => {
+                write!(f, "{} のオペランドは {} 個必要ですが {} 個指定されています", mnemonic, expected, found)
+            }
+            AsmError::InvalidRegister(r) => write!(f, "不正なレジスタです: {}", r),
+            AsmError::InvalidImmediate(v) => write!(f, "不正な即値です: {}", v),
+            AsmError::ImmediateOutOfRange(v) => write!(f, "即値が範囲外です: {}", v),
+            AsmError::UndefinedLabel(l) => write!(f, "ラベルが定義されていません: {}", l),
+            AsmError::DuplicateLabel(l) => write!(f, "ラベルが2回定義されています: {}", l),
+            AsmError::InvalidLabel(l) => write!(f, "ラベルに使えない名前です: {}", l),
+            AsmError::InvalidDirective(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// 整数レジスタ名を解析する（$8, $t0, t0 のいずれの形式も受け付ける）
+pub fn parse_register(text: &str) -> Result<Register, AsmError> {
+    let name = text.trim();
+    let name = name.strip_prefix('$').unwrap_or(name);
+    if let Ok(number) = name.parse::<u8>() {
+        if (number as usize) < REGISTER_NAMES.len() {
+            return Ok(number);
+        }
+    }
+    // $s8は$fpの別名
+    if name == "s8" {
+        return Ok(30);
+    }
+    REGISTER_NAMES.iter()
+        .position(|&r| r == name)
+        .map(|i| i as Register)
+        .ok_or_else(|| AsmError::InvalidRegister(text.trim().to_string()))
+}
+
+/// 浮動小数点レジスタ名を解析する（$f0-$f31）
+pub fn parse_fp_register(text: &str) -> Result<Register, AsmError> {
+    let name = text.trim();
+    let name = name.strip_prefix('$').unwrap_or(name);
+    name.strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .filter(|&n| n < 32)
+        .ok_or_else(|| AsmError::InvalidRegister(text.trim().to_string()))
+}
+
+/// 整数を解析する（10進数、0xで始まる16進数、負数に対応）
+pub fn parse_integer(text: &str) -> Result<i64, AsmError> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else {
+        digits.parse::<i64>()
+    }
+    .map_err(|_| AsmError::InvalidImmediate(text.to_string()))?;
+    Ok(if negative { -value } else { value })
+}
+
+/// 16ビット即値を解析する（符号付き・符号なしどちらの範囲も受け付ける）
+fn parse_immediate(text: &str) -> Result<Immediate, AsmError> {
+    let value = parse_integer(text)?;
+    if !(-0x8000..=0xFFFF).contains(&value) {
+        return Err(AsmError::ImmediateOutOfRange(value));
+    }
+    Ok(value as u16 as Immediate)
+}
+
+/// シフト量を解析する
+fn parse_shamt(text: &str) -> Result<u8, AsmError> {
+    let value = parse_integer(text)?;
+    if !(0..32).contains(&value) {
+        return Err(AsmError::ImmediateOutOfRange(value));
+    }
+    Ok(value as u8)
+}
+
+/// ジャンプ先アドレスを解析し、26ビットのaddrフィールドに変換する
+fn parse_jump_target(text: &str) -> Result<u32, AsmError> {
+    let value = parse_integer(text)?;
+    if !(0..=0xFFFFFFFF).contains(&value) {
+        return Err(AsmError::ImmediateOutOfRange(value));
+    }
+    Ok(((value as u32) >> 2) & 0x3FFFFFF)
+}
+
+/// "imm(base)" 形式のメモリオペランドを解析する
+fn parse_memory_operand(text: &str) -> Result<(Immediate, Register), AsmError> {
+    let text = text.trim();
+    let open = text.find('(').ok_or_else(|| AsmError::InvalidImmediate(text.to_string()))?;
+    let close = text.rfind(')').filter(|&c| c > open)
+        .ok_or_else(|| AsmError::InvalidImmediate(text.to_string()))?;
+    let offset = text[..open].trim();
+    let imm = if offset.is_empty() { 0 } else { parse_immediate(offset)? };
+    let base = parse_register(&text[open + 1..close])?;
+    Ok((imm, base))
+}
+
+/// 1行をニーモニック（小文字）とオペランドに分ける（'#' 以降はコメントとして無視する）
+fn split_line(line: &str) -> Result<(String, Vec<&str>), AsmError> {
+    let line = match line.find('#') {
+        Some(pos) => &line[..pos],
+        None => line,
+    }.trim();
+    if line.is_empty() {
+        return Err(AsmError::Empty);
+    }
+
+    let (mnemonic, rest) = match line.find(char::is_whitespace) {
+        Some(pos) => (&line[..pos], line[pos..].trim()),
+        None => (line, ""),
+    };
+    let operands = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(str::trim).collect()
+    };
+    Ok((mnemonic.to_lowercase(), operands))
+}
+
+/// アセンブリ言語の1行を命令に変換する
+/// 行中の '#' 以降はコメントとして無視する
+pub fn parse_instruction(line: &str) -> Result<InstructionType, AsmError> {
+    let (mnemonic, operands) = split_line(line)?;
+    let expect = |count: usize| -> Result<(), AsmError> {
+        if operands.len() == count {
+            Ok(())
+        } else {
+            Err(AsmError::OperandCount { mnemonic: mnemonic.clone(), expected: count, found: operands.len() })
+        }
+    };
+    let reg = |i: usize| parse_register(operands[i]);
+    let freg = |i: usize| parse_fp_register(operands[i]);
+
+    let instruction = match mnemonic.as_str() {
+        "add" | "sub" | "and" | "or" | "slt" => {
+            expect(3)?;
+            let (rd, rs, rt) = (reg(0)?, reg(1)?, reg(2)?);
+            match mnemonic.as_str() {
+                "add" => InstructionType::Add { rd, rs, rt },
+                "sub" => InstructionType::Sub { rd, rs, rt },
+                "and" => InstructionType::And { rd, rs, rt },
+                "or" => InstructionType::Or { rd, rs, rt },
+                _ => InstructionType::Slt { rd, rs, rt },
+            }
+        }
+        "sll" | "srl" => {
+            expect(3)?;
+            let (rd, rt, shamt) = (reg(0)?, reg(1)?, parse_shamt(operands[2])?);
+            if mnemonic == "sll" {
+                InstructionType::Sll { rd, rt, shamt }
+            } else {
+                InstructionType::Srl { rd, rt, shamt }
+            }
+        }
+        "jr" => {
+            expect(1)?;
+            InstructionType::Jr { rs: reg(0)? }
+        }
+        "addi" | "slti" => {
+            expect(3)?;
+            let (rt, rs, imm) = (reg(0)?, reg(1)?, parse_immediate(operands[2])?);
+            if mnemonic == "addi" {
+                InstructionType::Addi { rt, rs, imm }
+            } else {
+                InstructionType::Slti { rt, rs, imm }
+            }
+        }
+        "lui" => {
+            expect(2)?;
+            InstructionType::Lui { rt: reg(0)?, imm: parse_immediate(operands[1])? }
+        }
+        "ori" => {
+            expect(3)?;
+            InstructionType::Ori { rt: reg(0)?, rs: reg(1)?, imm: parse_immediate(operands[2])? }
+        }
+        "lw" | "sw" => {
+            expect(2)?;
+            let rt = reg(0)?;
+            let (imm, rs) = parse_memory_operand(operands[1])?;
+            if mnemonic == "lw" {
+                InstructionType::Lw { rt, rs, imm }
+            } else {
+                InstructionType::Sw { rt, rs, imm }
+            }
+        }
+        "beq" | "bne" => {
+            // 分岐オフセットは命令数単位
+            expect(3)?;
+            let (rs, rt, imm) = (reg(0)?, reg(1)?, parse_immediate(operands[2])?);
+            if mnemonic == "beq" {
+                InstructionType::Beq { rs, rt, imm }
+            } else {
+                InstructionType::Bne { rs, rt, imm }
+            }
+        }
+        "j" | "jal" => {
+            expect(1)?;
+            let addr = parse_jump_target(operands[0])?;
+            if mnemonic == "j" {
+                InstructionType::J { addr }
+            } else {
+                InstructionType::Jal { addr }
+            }
+        }
+        "syscall" => {
+            expect(0)?;
+            InstructionType::Syscall
+        }
+        "eret" => {
+            expect(0)?;
+            InstructionType::Eret
+        }
+        "break" => {
+            if operands.len() > 1 {
+                return Err(AsmError::OperandCount { mnemonic, expected: 1, found: operands.len() });
+            }
+            let code = match operands.first() {
+                Some(text) => parse_integer(text)?,
+                None => 0,
+            };
+            if !(0..0x100000).contains(&code) {
+                return Err(AsmError::ImmediateOutOfRange(code));
+            }
+            InstructionType::Break { code: code as u32 }
+        }
+        "mfc0" | "mtc0" => {
+            // コプロセッサ0のレジスタは番号で指定する（$12 = Status など）
+            expect(2)?;
+            let (rt, rd) = (reg(0)?, reg(1)?);
+            if mnemonic == "mfc0" {
+                InstructionType::Mfc0 { rt, rd }
+            } else {
+                InstructionType::Mtc0 { rt, rd }
+            }
+        }
+        "mfc1" | "mtc1" => {
+            expect(2)?;
+            let (rt, fs) = (reg(0)?, freg(1)?);
+            if mnemonic == "mfc1" {
+                InstructionType::Mfc1 { rt, fs }
+            } else {
+                InstructionType::Mtc1 { rt, fs }
+            }
+        }
+        "cfc1" | "ctc1" => {
+            // 制御レジスタは番号で指定する（$31 = FCSR）
+            expect(2)?;
+            let (rt, fs) = (reg(0)?, reg(1)?);
+            if mnemonic == "cfc1" {
+                InstructionType::Cfc1 { rt, fs }
+            } else {
+                InstructionType::Ctc1 { rt, fs }
+            }
+        }
+        "cvt.s.w" | "cvt.w.s" | "trunc.w.s" | "sqrt.s" | "finv" | "abs.s" | "mov.s" | "neg.s" => {
+            expect(2)?;
+            let (fd, fs) = (freg(0)?, freg(1)?);
+            match mnemonic.as_str() {
+                "cvt.s.w" => InstructionType::CvtSW { fd, fs },
+                "cvt.w.s" => InstructionType::CvtWS { fd, fs },
+                "trunc.w.s" => InstructionType::TruncWS { fd, fs },
+                "sqrt.s" => InstructionType::SqrtS { fd, fs },
+                "finv" => InstructionType::FinvS { fd, fs },
+                "abs.s" => InstructionType::AbsS { fd, fs },
+                "mov.s" => InstructionType::MovS { fd, fs },
+                _ => InstructionType::NegS { fd, fs },
+            }
+        }
+        "add.s" | "sub.s" | "mul.s" | "div.s" => {
+            expect(3)?;
+            let (fd, fs, ft) = (freg(0)?, freg(1)?, freg(2)?);
+            match mnemonic.as_str() {
+                "add.s" => InstructionType::AddS { fd, fs, ft },
+                "sub.s" => InstructionType::SubS { fd, fs, ft },
+                "mul.s" => InstructionType::MulS { fd, fs, ft },
+                _ => InstructionType::DivS { fd, fs, ft },
+            }
+        }
+        #[cfg(feature = "double")]
+        "add.d" | "sub.d" | "mul.d" | "div.d" => {
+            expect(3)?;
+            let (fd, fs, ft) = (freg(0)?, freg(1)?, freg(2)?);
+            match mnemonic.as_str() {
+                "add.d" => InstructionType::AddD { fd, fs, ft },
+                "sub.d" => InstructionType::SubD { fd, fs, ft },
+                "mul.d" => InstructionType::MulD { fd, fs, ft },
+                _ => InstructionType::DivD { fd, fs, ft },
+            }
+        }
+        #[cfg(feature = "double")]
+        "sqrt.d" | "abs.d" | "mov.d" | "neg.d" | "cvt.d.s" | "cvt.s.d" | "cvt.d.w" | "cvt.w.d" | "trunc.w.d" => {
+            expect(2)?;
+            let (fd, fs) = (freg(0)?, freg(1)?);
+            match mnemonic.as_str() {
+                "sqrt.d" => InstructionType::SqrtD { fd, fs },
+                "abs.d" => InstructionType::AbsD { fd, fs },
+                "mov.d" => InstructionType::MovD { fd, fs },
+                "neg.d" => InstructionType::NegD { fd, fs },
+                "cvt.d.s" => InstructionType::CvtDS { fd, fs },
+                "cvt.s.d" => InstructionType::CvtSD { fd, fs },
+                "cvt.d.w" => InstructionType::CvtDW { fd, fs },
+                "cvt.w.d" => InstructionType::CvtWD { fd, fs },
+                _ => InstructionType::TruncWD { fd, fs },
+            }
+        }
+        #[cfg(feature = "double")]
+        "ldc1" | "sdc1" => {
+            expect(2)?;
+            let ft = freg(0)?;
+            let (imm, base) = parse_memory_operand(operands[1])?;
+            if mnemonic == "ldc1" {
+                InstructionType::Ldc1 { ft, base, imm }
+            } else {
+                InstructionType::Sdc1 { ft, base, imm }
+            }
+        }
+        _ => return Err(AsmError::UnknownMnemonic(mnemonic)),
+    };
+    Ok(instruction)
+}
+
+/// 疑似命令の展開に使う一時レジスタ（$at）
+const AT_REGISTER: Register = 1;
+
+/// アセンブリ言語の1行を命令の列に変換する（疑似命令は実際の命令の列に展開する）
+///
+/// 対応する疑似命令: nop, move, li, la, b, blt, bgt, ble, bge と、アドレスを直接書いた lw・sw（$at を使って2命令に展開する）
+/// 分岐のオフセットは疑似命令自身からの命令数で指定し、展開後の分岐命令の位置に合わせて補正する
+pub fn expand_instruction(line: &str) -> Result<Vec<InstructionType>, AsmError> {
+    let (mnemonic, operands) = split_line(line)?;
+    let expect = |count: usize| -> Result<(), AsmError> {
+        if operands.len() == count {
+            Ok(())
+        } else {
+            Err(AsmError::OperandCount { mnemonic: mnemonic.clone(), expected: count, found: operands.len() })
+        }
+    };
+    let reg = |i: usize| parse_register(operands[i]);
+
+    let instructions = match mnemonic.as_str() {
+        "nop" => {
+            expect(0)?;
+            vec![InstructionType::Sll { rd: 0, rt: 0, shamt: 0 }]
+        }
+        "move" => {
+            expect(2)?;
+            vec![InstructionType::Add { rd: reg(0)?, rs: reg(1)?, rt: 0 }]
+        }
+        "li" => {
+            expect(2)?;
+            let rt = reg(0)?;
+            let value = parse_integer(operands[1])?;
+            if !(i32::MIN as i64..=u32::MAX as i64).contains(&value) {
+                return Err(AsmError::ImmediateOutOfRange(value));
+            }
+            let (upper, lower) = ((value as u32 >> 16) as Immediate, value as u32 as u16 as Immediate);
+            if (i16::MIN as i64..=i16::MAX as i64).contains(&value) {
+                vec![InstructionType::Addi { rt, rs: 0, imm: value as Immediate }]
+            } else if (0..=0xFFFF).contains(&value) {
+                vec![InstructionType::Ori { rt, rs: 0, imm: lower }]
+            } else if lower == 0 {
+                vec![InstructionType::Lui { rt, imm: upper }]
+            } else {
+                vec![InstructionType::Lui { rt, imm: upper }, InstructionType::Ori { rt, rs: rt, imm: lower }]
+            }
+        }
+        "la" => {
+            // アドレスの値によらず常に2命令に展開する
+            expect(2)?;
+            let rt = reg(0)?;
+            let address = parse_integer(operands[1])?;
+            if !(0..=u32::MAX as i64).contains(&address) {
+                return Err(AsmError::ImmediateOutOfRange(address));
+            }
+            let address = address as u32;
+            vec![
+                InstructionType::Lui { rt, imm: (address >> 16) as Immediate },
+                InstructionType::Ori { rt, rs: rt, imm: address as u16 as Immediate },
+            ]
+        }
+        "lw" | "sw" if operands.len() == 2 && !operands[1].contains('(') => {
+            // lw $t0, 0x10010004 -> lui $at, 0x1001; lw $t0, 4($at)（下位16ビットは符号拡張されるので上位を補正する）
+            let rt = reg(0)?;
+            let address = parse_integer(operands[1])?;
+            if !(0..=u32::MAX as i64).contains(&address) {
+                return Err(AsmError::ImmediateOutOfRange(address));
+            }
+            let address = address as u32;
+            let (upper, lower) = ((address.wrapping_add(0x8000) >> 16) as Immediate, address as u16 as Immediate);
+            let access = match mnemonic.as_str() {
+                "lw" => InstructionType::Lw { rt, rs: AT_REGISTER, imm: lower },
+                _ => InstructionType::Sw { rt, rs: AT_REGISTER, imm: lower },
+            };
+            vec![InstructionType::Lui { rt: AT_REGISTER, imm: upper }, access]
+        }
+        "b" => {
+            expect(1)?;
+            vec![InstructionType::Beq { rs: 0, rt: 0, imm: parse_immediate(operands[0])? }]
+        }
+        "blt" | "bgt" | "ble" | "bge" => {
+            // slt $at, ... の次の命令が分岐になるので、オフセットを1命令分減らす
+            expect(3)?;
+            let (rs, rt) = (reg(0)?, reg(1)?);
+            let offset = parse_integer(operands[2])? - 1;
+            if !(i16::MIN as i64..=i16::MAX as i64).contains(&offset) {
+                return Err(AsmError::ImmediateOutOfRange(offset + 1));
+            }
+            let imm = offset as Immediate;
+            let compare = match mnemonic.as_str() {
+                "blt" | "bge" => InstructionType::Slt { rd: AT_REGISTER, rs, rt },
+                _ => InstructionType::Slt { rd: AT_REGISTER, rs: rt, rt: rs },
+            };
+            let branch = match mnemonic.as_str() {
+                "blt" | "bgt" => InstructionType::Bne { rs: AT_REGISTER, rt: 0, imm },
+                _ => InstructionType::Beq { rs: AT_REGISTER, rt: 0, imm },
+            };
+            vec![compare, branch]
+        }
+        _ => vec![parse_instruction(line)?],
+    };
+    Ok(instructions)
+}
+
+/// ';' で区切られた複数の命令（疑似命令を含む）をアセンブルして機械語の列を返す
+pub fn assemble_snippet(snippet: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut words = Vec::new();
+    for part in snippet.split(';').filter(|part| !part.trim().is_empty()) {
+        for instruction in expand_instruction(part)? {
+            words.push(instruction.encode().expect("expand_instruction はInvalidを返さない"));
+        }
+    }
+    Ok(words)
+}
+
+/// 行番号の付いたアセンブルエラー
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceError {
+    /// 行番号（1始まり）
+    pub line: usize,
+    /// その行の内容
+    pub text: String,
+    pub error: AsmError,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} 行目: {}: '{}'", self.line, self.error, self.text)
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+/// アセンブルしたプログラム
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledProgram {
+    /// 先頭の命令のアドレス
+    pub origin: Address,
+    /// 機械語の列（.text セクション）
+    pub words: Vec<Instruction>,
+    /// .data セクションの先頭アドレス
+    pub data_address: Address,
+    /// .data セクションの内容（メモリ上の並び）
+    pub data: Vec<u8>,
+    /// 定義したラベルとアドレス（定義した順）
+    pub labels: Vec<(String, Address)>,
+    /// ソースの各行と、そこから生成した命令・データ（リスティング用）
+    pub listing: Vec<ListingLine>,
+}
+
+/// リスティングの1行分（ソースの1行と、そこから生成した命令・データ）
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine {
+    /// ソースの行（末尾の空白は除く）
+    pub text: String,
+    /// 生成した命令・データの先頭アドレス（何も生成しない行は None）
+    pub address: Option<Address>,
+    /// 生成した命令
+    pub words: Vec<Instruction>,
+    /// 疑似命令の展開やラベルの解決で、ソースに書いたものと異なる命令に変換したか
+    pub expanded: bool,
+    /// 生成したデータ（メモリ上の並び）
+    pub data: Vec<u8>,
+}
+
+/// リスティングで1つのディレクティブについて表示するデータの最大の行数
+const LISTING_DATA_ROWS: usize = 8;
+
+impl AssembledProgram {
+    /// .text セクションの終端のアドレス（含まない）
+    pub fn text_end(&self) -> Address {
+        self.origin.wrapping_add(self.words.len() as u32 * 4)
+    }
+
+    /// .data セクションの終端のアドレス（含まない）
+    pub fn data_end(&self) -> Address {
+        self.data_address.wrapping_add(self.data.len() as u32)
+    }
+
+    /// 1行に1命令の16進数のテキストに変換する（逆アセンブルした命令と、その位置のラベルをコメントに付ける）
+    /// .data セクションは .text の直後にある場合だけ、続けてワード単位で書き出せる
+    pub fn to_hex(&self) -> Result<String, String> {
+        if !self.data.is_empty() && self.data_address != self.text_end() {
+            return Err(format!(".data セクション（0x{:08X}）が命令の直後にないので、16進数のテキストには書き出せません", self.data_address));
+        }
+        let label_prefix = |address: Address| -> String {
+            self.labels.iter()
+                .filter(|(_, label_address)| *label_address == address)
+                .map(|(name, _)| format!("{}: ", name))
+                .collect()
+        };
+        let mut text = String::new();
+        for (index, &word) in self.words.iter().enumerate() {
+            let address = self.origin.wrapping_add(index as u32 * 4);
+            text.push_str(&format!("{:08X}  # {}{}\n", word, label_prefix(address), InstructionType::decode(word)));
+        }
+        for (index, chunk) in self.data.chunks(4).enumerate() {
+            let address = self.data_address.wrapping_add(index as u32 * 4);
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            let word = u32::from_le_bytes(word);
+            text.push_str(&format!("{:08X}  # {}.word 0x{:08X}\n", word, label_prefix(address), word));
+        }
+        Ok(text)
+    }
+
+    /// リスティング（ソースの行番号・アドレス・機械語またはデータ・ソース）をテキストにする
+    /// 疑似命令のように別の命令に変換した行は、続けて生成した命令を1つずつ逆アセンブルして表示する
+    pub fn to_listing(&self) -> String {
+        // 見出しは全角文字の幅に合わせて空白を入れてある
+        let mut text = String::from("   行  アドレス  内容         ソース\n");
+        let mut row = |number: Option<usize>, address: Option<Address>, content: &str, source: &str| {
+            let number = number.map(|number| number.to_string()).unwrap_or_default();
+            let address = address.map(|address| format!("{:08X}", address)).unwrap_or_default();
+            text.push_str(format!("{:>5}  {:<8}  {:<11}  {}", number, address, content, source).trim_end());
+            text.push('\n');
+        };
+        for (index, line) in self.listing.iter().enumerate() {
+            let number = Some(index + 1);
+            let Some(address) = line.address else {
+                row(number, None, "", &line.text);
+                continue;
+            };
+            if !line.data.is_empty() {
+                for (chunk_index, chunk) in line.data.chunks(4).take(LISTING_DATA_ROWS).enumerate() {
+                    let bytes: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    let chunk_address = Some(address.wrapping_add(chunk_index as u32 * 4));
+                    if chunk_index == 0 {
+                        row(number, chunk_address, &bytes.join(" "), &line.text);
+                    } else {
+                        row(None, chunk_address, &bytes.join(" "), "");
+                    }
+                }
+                if line.data.len() > LISTING_DATA_ROWS * 4 {
+                    row(None, None, "...", &format!("（全 {} バイト）", line.data.len()));
+                }
+            } else if line.expanded {
+                row(number, Some(address), "", &line.text);
+                for (word_index, &word) in line.words.iter().enumerate() {
+                    row(None, Some(address.wrapping_add(word_index as u32 * 4)), &format!("{:08X}", word),
+                        &format!("    -> {}", InstructionType::decode(word)));
+                }
+            } else {
+                let content: Vec<String> = line.words.iter().map(|word| format!("{:08X}", word)).collect();
+                row(number, Some(address), &content.join(" "), &line.text);
+            }
+        }
+        text
+    }
+
+    /// ラベルをシンボルに変換する（サイズは同じセクションの次の別のアドレスのラベルまたはセクションの終端まで）
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let section_end = |address: Address| {
+            if !self.data.is_empty() && (self.data_address..=self.data_end()).contains(&address) {
+                self.data_end()
+            } else {
+                self.text_end()
+            }
+        };
+        self.labels.iter()
+            .map(|(name, address)| {
+                let end = section_end(*address);
+                let next = self.labels.iter()
+                    .map(|&(_, next)| next)
+                    .filter(|next| next > address && *next <= end)
+                    .min()
+                    .unwrap_or(end);
+                Symbol { name: name.clone(), address: *address, size: Some(next.wrapping_sub(*address)) }
+            })
+            .collect()
+    }
+}
+
+/// ラベルを解決して複数行のソースを機械語に変換する2パスのアセンブラ
+///
+/// 1パス目で各行の大きさ（疑似命令は展開後の命令数、ディレクティブは置くデータの量）からラベルのアドレスを決め、
+/// 2パス目でラベルを値に置き換えて変換する。
+/// ラベルは行頭の `名前:` で定義し、分岐命令（疑似命令を含む）のオフセット、`j`・`jal` のジャンプ先、
+/// `la` のアドレス、`lw`・`sw` のアドレス、`.word` の値に使える。
+/// `.data` 以降はデータのセクションになり、`.word`・`.half`・`.byte`・`.ascii`・`.asciiz`・`.space`・`.align` でデータを置く。
+pub struct Assembler {
+    origin: Address,
+    data_base: Option<Address>,
+}
+
+/// セクション
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Text,
+    Data,
+}
+
+impl Assembler {
+    /// origin から命令を置くアセンブラを作成（.data セクションは命令の直後に置く）
+    pub fn new(origin: Address) -> Self {
+        Self { origin, data_base: None }
+    }
+
+    /// .data セクションを置くアドレスを設定する
+    pub fn with_data_base(mut self, data_base: Option<Address>) -> Self {
+        self.data_base = data_base;
+        self
+    }
+
+    /// ソース全体をアセンブルする
+    pub fn assemble(&self, source: &str) -> Result<AssembledProgram, SourceError> {
+        // 1パス目: 各行のセクション内のオフセットとラベルを決める
+        let mut statements = Vec::new();
+        let mut labels = Vec::new();
+        let mut section = Section::Text;
+        let mut sizes = [0u32; 2];
+        for (index, text) in source.lines().enumerate() {
+            let error = |error: AsmError| SourceError { line: index + 1, text: text.trim().to_string(), error };
+            let (names, statement) = split_labels(text).map_err(error)?;
+            let size = &mut sizes[section as usize];
+            // .word などの揃えはラベルより先に行う（ラベルが揃えたデータを指すように）
+            *size = align_up(*size, statement_alignment(statement).map_err(error)?);
+            for name in names {
+                if labels.iter().any(|(label, _, _)| label == name) {
+                    return Err(error(AsmError::DuplicateLabel(name.to_string())));
+                }
+                labels.push((name.to_string(), section, *size));
+            }
+            if statement.is_empty() {
+                continue;
+            }
+            let (length, next_section) = statement_size(statement, section).map_err(error)?;
+            statements.push((index, statement, section, *size));
+            *size += length;
+            section = next_section;
+        }
+
+        let text_end = self.origin.wrapping_add(sizes[Section::Text as usize]);
+        let data_address = self.data_base.unwrap_or(text_end);
+        let base = |section: Section| match section {
+            Section::Text => self.origin,
+            Section::Data => data_address,
+        };
+        let labels: Vec<(String, Address)> = labels.into_iter()
+            .map(|(name, section, offset)| (name, base(section).wrapping_add(offset)))
+            .collect();
+        let addresses: HashMap<String, Address> = labels.iter().cloned().collect();
+
+        // 2パス目: ラベルを置き換えて機械語とデータにする
+        let mut words = Vec::new();
+        let mut data = vec![0; sizes[Section::Data as usize] as usize];
+        let mut listing: Vec<ListingLine> = source.lines()
+            .map(|text| ListingLine { text: text.trim_end().to_string(), address: None, words: Vec::new(), expanded: false, data: Vec::new() })
+            .collect();
+        for (index, statement, section, offset) in statements {
+            let error = |error: AsmError| SourceError { line: index + 1, text: statement.to_string(), error };
+            let address = base(section).wrapping_add(offset);
+            let listing_line = &mut listing[index];
+            if section == Section::Text {
+                // .align で空けた分は nop（0）で埋める
+                words.resize(offset as usize / 4, 0);
+            }
+            if statement.starts_with('.') {
+                let bytes = directive_bytes(statement, Some(&addresses)).map_err(error)?;
+                if section == Section::Text {
+                    words.extend(bytes.chunks(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())));
+                } else {
+                    data[offset as usize..offset as usize + bytes.len()].copy_from_slice(&bytes);
+                }
+                if !bytes.is_empty() {
+                    listing_line.address = Some(address);
+                    listing_line.data = bytes;
+                }
+                continue;
+            }
+            let line = resolve_labels(statement, address, Some(&addresses)).map_err(error)?;
+            let instructions = expand_instruction(&line).map_err(error)?;
+            listing_line.address = Some(address);
+            listing_line.expanded = line != statement || parse_instruction(&line).ok().as_slice() != instructions.as_slice();
+            for instruction in instructions {
+                let word = instruction.encode().expect("expand_instruction はInvalidを返さない");
+                words.push(word);
+                listing_line.words.push(word);
+            }
+        }
+        words.resize(sizes[Section::Text as usize] as usize / 4, 0);
+        Ok(AssembledProgram { origin: self.origin, words, data_address, data, labels, listing })
+    }
+}
+
+/// value を alignment（2のべき乗）の倍数に切り上げる
+fn align_up(value: u32, alignment: u32) -> u32 {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+/// 文の置く位置の揃え（.word は4バイト、.half は2バイト、.align n は2^nバイト、それ以外は揃えない）
+fn statement_alignment(statement: &str) -> Result<u32, AsmError> {
+    let (directive, operands) = split_directive(statement);
+    Ok(match directive {
+        ".word" => 4,
+        ".half" => 2,
+        ".align" => {
+            let exponent = parse_integer(operands)?;
+            if !(0..=12).contains(&exponent) {
+                return Err(AsmError::ImmediateOutOfRange(exponent));
+            }
+            1 << exponent
+        }
+        _ => 1,
+    })
+}
+
+/// 文が置くバイト数と、その後のセクションを返す（ラベルは仮に0として数える）
+fn statement_size(statement: &str, section: Section) -> Result<(u32, Section), AsmError> {
+    match split_directive(statement).0 {
+        ".text" => Ok((0, Section::Text)),
+        ".data" => Ok((0, Section::Data)),
+        "" => {
+            if section == Section::Data {
+                return Err(AsmError::InvalidDirective(".data セクションには命令を置けません".to_string()));
+            }
+            let count = expand_instruction(&resolve_labels(statement, 0, None)?)?.len();
+            Ok((count as u32 * 4, section))
+        }
+        directive => {
+            let length = directive_bytes(statement, None)?.len() as u32;
+            // 命令の間に置くデータはワード単位にする（命令の位置がずれないように）
+            if section == Section::Text && !length.is_multiple_of(4) {
+                return Err(AsmError::InvalidDirective(format!("{} で .text セクションに置くデータは4バイトの倍数にしてください", directive)));
+            }
+            Ok((length, section))
+        }
+    }
+}
+
+/// ディレクティブ（'.' で始まる文）とオペランドに分ける（ディレクティブでなければ空文字列）
+fn split_directive(statement: &str) -> (&str, &str) {
+    if !statement.starts_with('.') {
+        return ("", statement);
+    }
+    match statement.find(char::is_whitespace) {
+        Some(pos) => (&statement[..pos], statement[pos..].trim()),
+        None => (statement, ""),
+    }
+}
+
+/// ディレクティブが置くバイト列（リトルエンディアン）を返す（.align の埋める分は含まない）
+fn directive_bytes(statement: &str, addresses: Option<&HashMap<String, Address>>) -> Result<Vec<u8>, AsmError> {
+    let (directive, operands) = split_directive(statement);
+    let values = |min: i64, max: i64| -> Result<Vec<i64>, AsmError> {
+        operands.split(',').map(|operand| {
+            let operand = operand.trim();
+            let value = if is_label_name(operand) {
+                match addresses {
+                    None => 0,
+                    Some(addresses) => *addresses.get(operand).ok_or_else(|| AsmError::UndefinedLabel(operand.to_string()))? as i64,
+                }
+            } else {
+                parse_integer(operand)?
+            };
+            if !(min..=max).contains(&value) {
+                return Err(AsmError::ImmediateOutOfRange(value));
+            }
+            Ok(value)
+        }).collect()
+    };
+    Ok(match directive {
+        ".globl" | ".global" | ".text" | ".data" | ".align" => Vec::new(),
+        ".word" => values(i32::MIN as i64, u32::MAX as i64)?.iter().flat_map(|&value| (value as u32).to_le_bytes()).collect(),
+        ".half" => values(i16::MIN as i64, u16::MAX as i64)?.iter().flat_map(|&value| (value as u16).to_le_bytes()).collect(),
+        ".byte" => values(i8::MIN as i64, u8::MAX as i64)?.iter().map(|&value| value as u8).collect(),
+        ".space" => {
+            let length = parse_integer(operands)?;
+            if !(0..=0x1000000).contains(&length) {
+                return Err(AsmError::ImmediateOutOfRange(length));
+            }
+            vec![0; length as usize]
+        }
+        ".ascii" => parse_string(operands)?,
+        ".asciiz" => {
+            let mut bytes = parse_string(operands)?;
+            bytes.push(0);
+            bytes
+        }
+        _ => return Err(AsmError::InvalidDirective(format!("未知のディレクティブです: {}", directive))),
+    })
+}
+
+/// '"' で囲まれた文字列を解析する（\n・\t・\0・\\・\" のエスケープに対応）
+fn parse_string(text: &str) -> Result<Vec<u8>, AsmError> {
+    let error = || AsmError::InvalidDirective(format!("文字列は '\"' で囲んでください: {}", text));
+    let inner = text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')).ok_or_else(error)?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"')) => c,
+                _ => return Err(AsmError::InvalidDirective(format!("未知のエスケープです: {}", text))),
+            },
+            '"' => return Err(error()),
+            c => c,
+        };
+        let mut buffer = [0; 4];
+        bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+    }
+    Ok(bytes)
+}
+
+/// ラベルの名前として正しいか（英字・'_'・'.' で始まり、英数字・'_'・'.' が続く）
+fn is_label_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// 文字列の外にある最初の '#' より前を返す
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (pos, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..pos],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// 行頭のラベルの定義（複数可）と残りの文に分ける（'#' 以降はコメント）
+fn split_labels(line: &str) -> Result<(Vec<&str>, &str), AsmError> {
+    let mut rest = strip_comment(line).trim();
+    let mut names = Vec::new();
+    while let Some((name, statement)) = rest.split_once(':') {
+        let name = name.trim();
+        // ':' を含む文字列のオペランドはラベルではない
+        if name.contains(|c: char| c.is_whitespace() || c == '"') {
+            break;
+        }
+        if !is_label_name(name) {
+            return Err(AsmError::InvalidLabel(name.to_string()));
+        }
+        names.push(name);
+        rest = statement.trim();
+    }
+    Ok((names, rest))
+}
+
+/// ラベルを書けるオペランドのラベルを値に置き換えた行を返す
+/// 分岐命令はその行のアドレスからの命令数に、それ以外はアドレスに置き換える（addresses が None なら仮に0にする）
+fn resolve_labels(statement: &str, address: Address, addresses: Option<&HashMap<String, Address>>) -> Result<String, AsmError> {
+    let (mnemonic, mut operands) = split_line(statement)?;
+    let (position, relative) = match mnemonic.as_str() {
+        "beq" | "bne" | "blt" | "bgt" | "ble" | "bge" => (2, true),
+        "b" => (0, true),
+        "j" | "jal" => (0, false),
+        "la" | "lw" | "sw" => (1, false),
+        _ => return Ok(statement.to_string()),
+    };
+    let value = match operands.get(position) {
+        Some(&name) if is_label_name(name) => match addresses {
+            None => "0".to_string(),
+            Some(addresses) => {
+                let target = *addresses.get(name).ok_or_else(|| AsmError::UndefinedLabel(name.to_string()))?;
+                if relative {
+                    ((target as i64 - address as i64) / 4).to_string()
+                } else {
+                    format!("0x{:08X}", target)
+                }
+            }
+        },
+        _ => return Ok(statement.to_string()),
+    };
+    operands[position] = &value;
+    Ok(format!("{} {}", mnemonic, operands.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_registers() {
+        assert_eq!(parse_register("$t0"), Ok(8));
+        assert_eq!(parse_register("$31"), Ok(31));
+        assert_eq!(parse_register("sp"), Ok(29));
+        assert!(parse_register("$t10").is_err());
+        assert_eq!(parse_fp_register("$f12"), Ok(12));
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        assert_eq!(parse_instruction("addi $t0, $zero, 10"), Ok(InstructionType::Addi { rt: 8, rs: 0, imm: 10 }));
+        assert_eq!(parse_instruction("lw $t1, -4($sp)  # comment"), Ok(InstructionType::Lw { rt: 9, rs: 29, imm: -4 }));
+        assert_eq!(parse_instruction("j 0x00400028"), Ok(InstructionType::J { addr: 0x10000A }));
+        assert_eq!(parse_instruction("mul.s $f2, $f4, $f6"), Ok(InstructionType::MulS { fd: 2, fs: 4, ft: 6 }));
+        assert!(matches!(parse_instruction("addi $t0, 1"), Err(AsmError::OperandCount { .. })));
+        assert!(matches!(parse_instruction("frobnicate"), Err(AsmError::UnknownMnemonic(_))));
+    }
+
+    #[test]
+    fn test_assemble_snippet() {
+        let words = assemble_snippet("add $1, $2, $3; syscall").unwrap();
+        assert_eq!(words, vec![0x00430820, 0x0000000C]);
+    }
+
+    #[test]
+    fn test_expand_pseudo_instructions() {
+        assert_eq!(expand_instruction("li $t0, -5"), Ok(vec![InstructionType::Addi { rt: 8, rs: 0, imm: -5 }]));
+        assert_eq!(expand_instruction("li $t0, 0xFFFF"), Ok(vec![InstructionType::Ori { rt: 8, rs: 0, imm: -1 }]));
+        assert_eq!(assemble_snippet("li $t0, 0x12345678").unwrap(), vec![0x3C081234, 0x35085678]);
+        assert_eq!(assemble_snippet("la $a0, 0x10010000").unwrap(), vec![0x3C041001, 0x34840000]);
+        assert_eq!(assemble_snippet("move $t1, $t0; nop").unwrap(), vec![0x01004820, 0x00000000]);
+        // blt $t0, $t1, 3 -> slt $at, $t0, $t1; bne $at, $zero, 2
+        assert_eq!(assemble_snippet("blt $t0, $t1, 3").unwrap(), vec![0x0109082A, 0x14200002]);
+        assert!(matches!(expand_instruction("li $t0, 0x100000000"), Err(AsmError::ImmediateOutOfRange(_))));
+    }
+
+    #[test]
+    fn test_assembler() {
+        let source = "
+            # 10から1までの和
+            main:   li   $t0, 10
+                    move $t1, $zero
+            loop:   add  $t1, $t1, $t0
+                    addi $t0, $t0, -1
+                    bgt  $t0, $zero, loop
+                    j    done
+                    nop
+            done:   break
+        ";
+        let program = Assembler::new(0x00400000).assemble(source).unwrap();
+        assert_eq!(program.words, vec![
+            0x2008000A, // addi $t0, $zero, 10
+            0x00004820, // add $t1, $zero, $zero
+            0x01284820, // loop: add $t1, $t1, $t0
+            0x2108FFFF, // addi $t0, $t0, -1
+            0x0008082A, // slt $at, $zero, $t0
+            0x1420FFFD, // bne $at, $zero, -3（loop）
+            0x08100008, // j 0x00400020（done）
+            0x00000000, // nop
+            0x0000000D, // done: break
+        ]);
+        assert_eq!(program.labels, vec![
+            ("main".to_string(), 0x00400000),
+            ("loop".to_string(), 0x00400008),
+            ("done".to_string(), 0x00400020),
+        ]);
+        assert_eq!(program.symbols()[1], Symbol { name: "loop".to_string(), address: 0x00400008, size: Some(0x18) });
+        assert_eq!(program.symbols()[2].size, Some(4));
+        assert!(program.to_hex().unwrap().starts_with("2008000A  # main: addi $8, $0, 10\n00004820  # add $9, $0, $0\n"));
+
+        let error = Assembler::new(0).assemble("nop\n  b nowhere").unwrap_err();
+        assert_eq!((error.line, error.error), (2, AsmError::UndefinedLabel("nowhere".to_string())));
+        assert!(matches!(Assembler::new(0).assemble("a: nop\na: nop").unwrap_err().error, AsmError::DuplicateLabel(_)));
+        assert!(matches!(Assembler::new(0).assemble("1x: nop").unwrap_err().error, AsmError::InvalidLabel(_)));
+    }
+
+    #[test]
+    fn test_data_directives() {
+        let source = r#"
+                    .data
+            msg:    .asciiz "a:b#\n"
+            count:  .word 3, table
+            bytes:  .byte 1, -1
+                    .align 2
+            table:  .half 0x1234
+                    .space 2
+                    .text
+            main:   la $a0, msg
+                    lw $t0, count
+                    sw $t0, 0x00008000
+        "#;
+        let program = Assembler::new(0x00400000).with_data_base(Some(0x00500000)).assemble(source).unwrap();
+        assert_eq!(program.data, vec![
+            b'a', b':', b'b', b'#', b'\n', 0, 0, 0, // msg（.word の前を揃える）
+            3, 0, 0, 0, 0x14, 0, 0x50, 0, // count
+            1, 0xFF, 0, 0, // bytes（.align 2 で揃える）
+            0x34, 0x12, 0, 0, // table
+        ]);
+        assert_eq!(program.words, vec![
+            0x3C040050, 0x34840000, // la $a0, msg
+            0x3C010050, 0x8C280008, // lw $t0, count（0x00500008）
+            0x3C010001, 0xAC288000, // sw $t0, 0x00008000（下位は符号拡張されるので上位は1）
+        ]);
+        assert_eq!(program.symbols()[0], Symbol { name: "msg".to_string(), address: 0x00500000, size: Some(8) });
+        assert!(program.to_hex().is_err());
+
+        // .data のアドレスを指定しなければ命令の直後に置き、16進数のテキストにも続けて書き出せる
+        let program = Assembler::new(0).assemble("nop\n.data\nvalue: .word -1").unwrap();
+        assert_eq!((program.data_address, program.labels[0].1), (4, 4));
+        assert_eq!(program.to_hex().unwrap(), "00000000  # sll $0, $0, 0\nFFFFFFFF  # value: .word 0xFFFFFFFF\n");
+
+        let error = |source: &str| Assembler::new(0).assemble(source).unwrap_err().error;
+        assert!(matches!(error(".data\nnop"), AsmError::InvalidDirective(_)));
+        assert!(matches!(error(".byte 1"), AsmError::InvalidDirective(_)));
+        assert!(matches!(error(".data\n.asciiz abc"), AsmError::InvalidDirective(_)));
+        assert!(matches!(error(".data\n.byte 256"), AsmError::ImmediateOutOfRange(256)));
+        assert!(matches!(error(".frobnicate"), AsmError::InvalidDirective(_)));
+    }
+
+    #[test]
+    fn test_listing() {
+        let source = "# 例\nmain: li $t0, 10\n      add $t1, $t0, $t0\nloop: b loop\n.data\nmsg: .asciiz \"hi\"";
+        let program = Assembler::new(0x00400000).assemble(source).unwrap();
+        assert_eq!(program.listing[1].words, vec![0x2008000A]);
+        assert!(program.listing[1].expanded);
+        assert!(!program.listing[2].expanded);
+        assert_eq!(program.listing[5].data, b"hi\0".to_vec());
+        assert_eq!(program.to_listing(), [
+            "   行  アドレス  内容         ソース",
+            "    1                         # 例",
+            "    2  00400000               main: li $t0, 10",
+            "       00400000  2008000A         -> addi $8, $0, 10",
+            "    3  00400004  01084820           add $t1, $t0, $t0",
+            "    4  00400008               loop: b loop",
+            "       00400008  10000000         -> beq $0, $0, 0",
+            "    5                         .data",
+            "    6  0040000C  68 69 00     msg: .asciiz \"hi\"",
+            "",
+        ].join("\n"));
+    }
+}
@@ -0,0 +1,311 @@
+//! 組み込みのマイクロベンチマーク（benchサブコマンド）
+//!
+//! 各カーネルはMIPSのアセンブリで書かれ、0x00400000 から配置される前提でジャンプ先を絶対アドレスで書いている（--program-start によらずそこに置く）。
+//! データ（とスタック）は 0x00800000 以降にプログラム自身が用意し、結果を$a0に入れてexitシステムコールで終了する。
+
+use std::fmt;
+
+use crate::assembler::{self, AsmError};
+use crate::instructions::{Instruction, Word};
+use crate::processor::RunResult;
+
+/// ベンチマークのカーネル
+#[derive(Debug)]
+pub struct Kernel {
+    /// 名前（benchサブコマンドで指定する）
+    pub name: &'static str,
+    /// 説明
+    pub description: &'static str,
+    /// アセンブリ（1行に1命令、'#' 以降はコメント）
+    source: &'static str,
+    /// 終了時の$a0の期待値
+    pub expected: Word,
+}
+
+impl Kernel {
+    /// アセンブルして機械語を返す
+    pub fn assemble(&self) -> Result<Vec<Instruction>, AsmError> {
+        let mut words = Vec::new();
+        for line in self.source.lines() {
+            let code = line.split('#').next().unwrap_or("").trim();
+            if !code.is_empty() {
+                words.extend(assembler::assemble_snippet(code)?);
+            }
+        }
+        Ok(words)
+    }
+}
+
+/// 組み込みのカーネル一覧
+pub const KERNELS: &[Kernel] = &[
+    Kernel {
+        name: "memcpy",
+        description: "2048ワード（8KB）のワード単位コピーとチェックサム",
+        source: MEMCPY_SOURCE,
+        expected: 2047 * 2048 / 2,
+    },
+    Kernel {
+        name: "matmul",
+        description: "8x8の単精度行列積と要素の総和",
+        source: MATMUL_SOURCE,
+        expected: 529536,
+    },
+    Kernel {
+        name: "fib",
+        description: "再帰呼び出しによるfib(15)（スタックと関数呼び出し）",
+        source: FIB_SOURCE,
+        expected: 610,
+    },
+    Kernel {
+        name: "string",
+        description: "4096文字の文字列の長さとバイト値の総和（バイト単位の走査）",
+        source: STRING_SOURCE,
+        expected: 394 * 1024 + 4096,
+    },
+];
+
+/// 名前からカーネルを探す
+pub fn find(name: &str) -> Option<&'static Kernel> {
+    KERNELS.iter().find(|kernel| kernel.name == name)
+}
+
+const MEMCPY_SOURCE: &str = "
+    lui $s0, 0x0080             # コピー元 0x00800000
+    lui $s1, 0x0081             # コピー先 0x00810000
+    addi $s2, $zero, 2048       # ワード数
+    add $t0, $zero, $zero
+    add $t1, $s0, $zero
+    sw $t0, 0($t1)              # コピー元[i] = i
+    addi $t0, $t0, 1
+    addi $t1, $t1, 4
+    bne $t0, $s2, -3
+    add $t0, $zero, $zero
+    add $t1, $s0, $zero
+    add $t2, $s1, $zero
+    lw $t3, 0($t1)              # コピー
+    sw $t3, 0($t2)
+    addi $t1, $t1, 4
+    addi $t2, $t2, 4
+    addi $t0, $t0, 1
+    bne $t0, $s2, -5
+    add $t0, $zero, $zero
+    add $t2, $s1, $zero
+    add $s3, $zero, $zero
+    lw $t3, 0($t2)              # コピー先の総和
+    add $s3, $s3, $t3
+    addi $t2, $t2, 4
+    addi $t0, $t0, 1
+    bne $t0, $s2, -4
+    move $a0, $s3
+    li $v0, 10
+    syscall
+";
+
+const MATMUL_SOURCE: &str = "
+    lui $s0, 0x0080             # A = 0x00800000
+    addi $s1, $s0, 256          # B = A + 256
+    addi $s2, $s0, 512          # C = A + 512
+    addi $s3, $zero, 8          # N
+    addi $s4, $zero, 64         # N * N
+    add $t0, $zero, $zero
+    add $t1, $s0, $zero
+    mtc1 $t0, $f0               # A[k] = B[k] = (float)k
+    cvt.s.w $f0, $f0
+    mfc1 $t2, $f0
+    sw $t2, 0($t1)
+    sw $t2, 256($t1)
+    addi $t0, $t0, 1
+    addi $t1, $t1, 4
+    bne $t0, $s4, -7
+    add $t0, $zero, $zero       # i
+    add $t1, $zero, $zero       # j
+    mtc1 $zero, $f2             # 和 = 0
+    add $t2, $zero, $zero       # k
+    sll $t3, $t0, 5             # &A[i][0]
+    add $t3, $t3, $s0
+    sll $t4, $t1, 2             # &B[0][j]
+    add $t4, $t4, $s1
+    lw $t5, 0($t3)              # 和 += A[i][k] * B[k][j]
+    mtc1 $t5, $f4
+    lw $t5, 0($t4)
+    mtc1 $t5, $f6
+    mul.s $f8, $f4, $f6
+    add.s $f2, $f2, $f8
+    addi $t3, $t3, 4
+    addi $t4, $t4, 32
+    addi $t2, $t2, 1
+    bne $t2, $s3, -9
+    sll $t6, $t0, 3             # C[i][j] = 和
+    add $t6, $t6, $t1
+    sll $t6, $t6, 2
+    add $t6, $t6, $s2
+    mfc1 $t5, $f2
+    sw $t5, 0($t6)
+    addi $t1, $t1, 1
+    bne $t1, $s3, -23
+    addi $t0, $t0, 1
+    bne $t0, $s3, -26
+    mtc1 $zero, $f2             # Cの総和
+    add $t0, $zero, $zero
+    add $t1, $s2, $zero
+    lw $t5, 0($t1)
+    mtc1 $t5, $f4
+    add.s $f2, $f2, $f4
+    addi $t1, $t1, 4
+    addi $t0, $t0, 1
+    bne $t0, $s4, -5
+    cvt.w.s $f2, $f2
+    mfc1 $a0, $f2
+    li $v0, 10
+    syscall
+";
+
+const FIB_SOURCE: &str = "
+    lui $sp, 0x0090             # スタック 0x00900000 から下へ
+    addi $a0, $zero, 15
+    jal 0x00400014              # fib(15)
+    move $a0, $v1
+    j 0x0040005C
+    slti $t0, $a0, 2            # fib: 戻り値は$v1
+    beq $t0, $zero, 3
+    move $v1, $a0
+    jr $ra
+    addi $sp, $sp, -12
+    sw $ra, 0($sp)
+    sw $a0, 4($sp)
+    addi $a0, $a0, -1
+    jal 0x00400014              # fib(n - 1)
+    sw $v1, 8($sp)
+    lw $a0, 4($sp)
+    addi $a0, $a0, -2
+    jal 0x00400014              # fib(n - 2)
+    lw $t0, 8($sp)
+    add $v1, $v1, $t0
+    lw $ra, 0($sp)
+    addi $sp, $sp, 12
+    jr $ra
+    li $v0, 10
+    syscall
+";
+
+const STRING_SOURCE: &str = "
+    lui $s0, 0x0080             # 文字列 0x00800000
+    li $t5, 0x64636261          # \"abcd\"
+    addi $s1, $zero, 1024
+    add $t0, $zero, $zero
+    add $t1, $s0, $zero
+    sw $t5, 0($t1)              # \"abcd\" を1024回並べる
+    addi $t1, $t1, 4
+    addi $t0, $t0, 1
+    bne $t0, $s1, -3
+    sw $zero, 0($t1)            # NUL終端
+    add $t1, $s0, $zero
+    add $s2, $zero, $zero       # バイト値の総和
+    add $s3, $zero, $zero       # 長さ
+    lw $t2, 0($t1)              # 1ワードずつ読み、下位バイトから順に調べる
+    sll $t3, $t2, 24
+    srl $t3, $t3, 24
+    beq $t3, $zero, 19
+    add $s2, $s2, $t3
+    addi $s3, $s3, 1
+    sll $t3, $t2, 16
+    srl $t3, $t3, 24
+    beq $t3, $zero, 14
+    add $s2, $s2, $t3
+    addi $s3, $s3, 1
+    sll $t3, $t2, 8
+    srl $t3, $t3, 24
+    beq $t3, $zero, 9
+    add $s2, $s2, $t3
+    addi $s3, $s3, 1
+    srl $t3, $t2, 24
+    beq $t3, $zero, 5
+    add $s2, $s2, $t3
+    addi $s3, $s3, 1
+    addi $t1, $t1, 4
+    j 0x00400038
+    add $a0, $s2, $s3
+    li $v0, 10
+    syscall
+";
+
+/// 1つのカーネルの実行結果
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub outcome: RunResult,
+    /// 実行命令数
+    pub instructions: u64,
+    /// 見積もりサイクル数
+    pub cycles: u64,
+    /// キャッシュヒット率
+    pub cache_hit_rate: f64,
+    /// 終了時の$a0
+    pub result: Word,
+    /// 期待値
+    pub expected: Word,
+}
+
+impl BenchResult {
+    /// 正常に終了し、結果が期待値と一致したか
+    pub fn passed(&self) -> bool {
+        self.outcome.is_success() && self.result == self.expected
+    }
+
+    /// 1命令あたりのサイクル数
+    pub fn cpi(&self) -> f64 {
+        if self.instructions == 0 {
+            0.0
+        } else {
+            self.cycles as f64 / self.instructions as f64
+        }
+    }
+}
+
+/// ベンチマーク結果の表
+#[derive(Debug, Clone, Default)]
+pub struct BenchTable {
+    pub results: Vec<BenchResult>,
+}
+
+impl BenchTable {
+    /// 失敗したカーネルの数
+    pub fn failure_count(&self) -> usize {
+        self.results.iter().filter(|result| !result.passed()).count()
+    }
+}
+
+impl fmt::Display for BenchTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<8} {:>10} {:>10} {:>6} {:>10} {:>6}", "カーネル", "命令数", "サイクル", "CPI", "ヒット率", "検証")?;
+        for result in &self.results {
+            let status = if result.passed() {
+                "OK".to_string()
+            } else if result.outcome.is_success() {
+                format!("NG（結果 {}、期待値 {}）", result.result, result.expected)
+            } else {
+                format!("NG（{}）", result.outcome.kind())
+            };
+            writeln!(f, "{:<8} {:>10} {:>10} {:>6.3} {:>9.2}% {:>6}",
+                result.name, result.instructions, result.cycles, result.cpi(), result.cache_hit_rate * 100.0, status)?;
+        }
+        let total_instructions: u64 = self.results.iter().map(|r| r.instructions).sum();
+        let total_cycles: u64 = self.results.iter().map(|r| r.cycles).sum();
+        let total_cpi = if total_instructions == 0 { 0.0 } else { total_cycles as f64 / total_instructions as f64 };
+        write!(f, "{:<8} {:>10} {:>10} {:>6.3}", "合計", total_instructions, total_cycles, total_cpi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_assemble() {
+        for kernel in KERNELS {
+            assert!(!kernel.assemble().unwrap().is_empty(), "{}", kernel.name);
+            assert_eq!(find(kernel.name).map(|k| k.name), Some(kernel.name));
+        }
+        assert!(find("nothing").is_none());
+    }
+}
@@ -0,0 +1,448 @@
+//! 条件分岐の予測器
+//!
+//! 条件分岐を実行するたびに予測器に問い合わせ、予測が外れた分をタイミングモデルでペナルティとして数える。
+//! 複数の予測器を同時に評価でき、タイミングには最初の予測器の結果を使う。
+
+use std::fmt;
+
+use crate::memory::MemoryAddress;
+use crate::snapshot::{SnapshotError, SnapshotReader, SnapshotWriter};
+
+/// bimodal・gshareの2ビットカウンタの表の大きさ（エントリ数の2の対数）
+const COUNTER_INDEX_BITS: u32 = 10;
+
+/// gshareの分岐履歴の長さ
+const GSHARE_HISTORY_BITS: u32 = 10;
+
+/// 2ビットカウンタの初期値（弱く不成立）
+const WEAKLY_NOT_TAKEN: u8 = 1;
+
+/// 分岐予測器
+pub trait BranchPredictor: fmt::Debug {
+    /// 予測器の名前
+    fn name(&self) -> &'static str;
+    /// pcにある条件分岐が成立するかを予測する（targetは成立した場合の飛び先）
+    fn predict(&self, pc: MemoryAddress, target: MemoryAddress) -> bool;
+    /// 実際の結果で状態を更新する
+    fn update(&mut self, pc: MemoryAddress, target: MemoryAddress, taken: bool);
+    /// スナップショットに保存する状態（デフォルトは状態を持たない）
+    fn state(&self) -> Vec<u64> {
+        Vec::new()
+    }
+    /// 保存した状態に戻す（長さが合わなければ false）
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        state.is_empty()
+    }
+}
+
+/// 常に不成立と予測する
+#[derive(Debug, Clone, Default)]
+pub struct AlwaysNotTaken;
+
+impl BranchPredictor for AlwaysNotTaken {
+    fn name(&self) -> &'static str {
+        "not-taken"
+    }
+
+    fn predict(&self, _pc: MemoryAddress, _target: MemoryAddress) -> bool {
+        false
+    }
+
+    fn update(&mut self, _pc: MemoryAddress, _target: MemoryAddress, _taken: bool) {}
+}
+
+/// 後方への分岐（ループ）は成立、前方への分岐は不成立と予測する
+#[derive(Debug, Clone, Default)]
+pub struct BackwardTaken;
+
+impl BranchPredictor for BackwardTaken {
+    fn name(&self) -> &'static str {
+        "backward-taken"
+    }
+
+    fn predict(&self, pc: MemoryAddress, target: MemoryAddress) -> bool {
+        target <= pc
+    }
+
+    fn update(&mut self, _pc: MemoryAddress, _target: MemoryAddress, _taken: bool) {}
+}
+
+/// 2ビット飽和カウンタを更新する
+fn train(counter: &mut u8, taken: bool) {
+    *counter = if taken { (*counter + 1).min(3) } else { counter.saturating_sub(1) };
+}
+
+/// 命令アドレスで引く2ビット飽和カウンタの表
+#[derive(Debug, Clone)]
+pub struct Bimodal {
+    counters: Vec<u8>,
+}
+
+impl Default for Bimodal {
+    fn default() -> Self {
+        Self { counters: vec![WEAKLY_NOT_TAKEN; 1 << COUNTER_INDEX_BITS] }
+    }
+}
+
+impl Bimodal {
+    fn index(&self, pc: MemoryAddress) -> usize {
+        (pc >> 2) as usize & (self.counters.len() - 1)
+    }
+}
+
+impl BranchPredictor for Bimodal {
+    fn name(&self) -> &'static str {
+        "bimodal"
+    }
+
+    fn predict(&self, pc: MemoryAddress, _target: MemoryAddress) -> bool {
+        self.counters[self.index(pc)] >= 2
+    }
+
+    fn update(&mut self, pc: MemoryAddress, _target: MemoryAddress, taken: bool) {
+        let index = self.index(pc);
+        train(&mut self.counters[index], taken);
+    }
+
+    fn state(&self) -> Vec<u64> {
+        self.counters.iter().map(|&counter| counter as u64).collect()
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        restore_counters(&mut self.counters, state)
+    }
+}
+
+/// 命令アドレスと直近の分岐結果の履歴のXORで引く2ビット飽和カウンタの表
+#[derive(Debug, Clone)]
+pub struct Gshare {
+    counters: Vec<u8>,
+    /// 直近の分岐結果（最下位ビットが最新、1が成立）
+    history: u32,
+}
+
+impl Default for Gshare {
+    fn default() -> Self {
+        Self { counters: vec![WEAKLY_NOT_TAKEN; 1 << COUNTER_INDEX_BITS], history: 0 }
+    }
+}
+
+impl Gshare {
+    fn index(&self, pc: MemoryAddress) -> usize {
+        ((pc >> 2) ^ self.history) as usize & (self.counters.len() - 1)
+    }
+}
+
+impl BranchPredictor for Gshare {
+    fn name(&self) -> &'static str {
+        "gshare"
+    }
+
+    fn predict(&self, pc: MemoryAddress, _target: MemoryAddress) -> bool {
+        self.counters[self.index(pc)] >= 2
+    }
+
+    fn update(&mut self, pc: MemoryAddress, _target: MemoryAddress, taken: bool) {
+        let index = self.index(pc);
+        train(&mut self.counters[index], taken);
+        self.history = ((self.history << 1) | taken as u32) & ((1 << GSHARE_HISTORY_BITS) - 1);
+    }
+
+    fn state(&self) -> Vec<u64> {
+        std::iter::once(self.history as u64).chain(self.counters.iter().map(|&counter| counter as u64)).collect()
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        match state.split_first() {
+            Some((&history, counters)) if history < 1 << GSHARE_HISTORY_BITS && restore_counters(&mut self.counters, counters) => {
+                self.history = history as u32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 保存した2ビットカウンタの表を書き戻す（長さが合わないか、範囲外の値があれば false）
+fn restore_counters(counters: &mut [u8], state: &[u64]) -> bool {
+    if state.len() != counters.len() || state.iter().any(|&counter| counter > 3) {
+        return false;
+    }
+    for (counter, &saved) in counters.iter_mut().zip(state) {
+        *counter = saved as u8;
+    }
+    true
+}
+
+/// 分岐予測器の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PredictorKind {
+    NotTaken,
+    BackwardTaken,
+    Bimodal,
+    Gshare,
+}
+
+impl PredictorKind {
+    /// 全ての種類
+    pub const ALL: [PredictorKind; 4] = [
+        PredictorKind::NotTaken,
+        PredictorKind::BackwardTaken,
+        PredictorKind::Bimodal,
+        PredictorKind::Gshare,
+    ];
+
+    /// "not-taken"・"backward-taken"・"bimodal"・"gshare" から作成する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::ALL.into_iter()
+            .find(|kind| kind.name() == text)
+            .ok_or_else(|| format!("無効な分岐予測器です: {}", text))
+    }
+
+    /// 名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            PredictorKind::NotTaken => "not-taken",
+            PredictorKind::BackwardTaken => "backward-taken",
+            PredictorKind::Bimodal => "bimodal",
+            PredictorKind::Gshare => "gshare",
+        }
+    }
+
+    /// 予測器を作成
+    pub fn build(&self) -> Box<dyn BranchPredictor> {
+        match self {
+            PredictorKind::NotTaken => Box::new(AlwaysNotTaken),
+            PredictorKind::BackwardTaken => Box::new(BackwardTaken),
+            PredictorKind::Bimodal => Box::new(Bimodal::default()),
+            PredictorKind::Gshare => Box::new(Gshare::default()),
+        }
+    }
+}
+
+/// 1つの予測器の成績
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PredictorStats {
+    /// 予測した条件分岐の数
+    pub branches: u64,
+    /// 予測が当たった数
+    pub correct: u64,
+}
+
+impl PredictorStats {
+    /// 予測が外れた数
+    pub fn mispredictions(&self) -> u64 {
+        self.branches - self.correct
+    }
+
+    /// 正解率
+    pub fn accuracy(&self) -> f64 {
+        if self.branches == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.branches as f64
+        }
+    }
+}
+
+/// 分岐予測器の表と履歴のスナップショット（成績は含まない）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PredictorSnapshot {
+    /// 予測器ごとの名前と状態
+    predictors: Vec<(String, Vec<u64>)>,
+}
+
+impl PredictorSnapshot {
+    /// 保存したときの予測器の名前
+    pub fn names(&self) -> Vec<&str> {
+        self.predictors.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// シミュレータのスナップショットに書き出す
+    pub fn encode(&self, writer: &mut SnapshotWriter) {
+        writer.put_u32(self.predictors.len() as u32);
+        for (name, state) in &self.predictors {
+            writer.put_str(name);
+            writer.put_u32(state.len() as u32);
+            for &value in state {
+                writer.put_u64(value);
+            }
+        }
+    }
+
+    /// シミュレータのスナップショットから読み込む
+    pub fn decode(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        let mut predictors = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.string()?;
+            let mut state = Vec::new();
+            for _ in 0..reader.u32()? {
+                state.push(reader.u64()?);
+            }
+            predictors.push((name, state));
+        }
+        Ok(Self { predictors })
+    }
+}
+
+/// 同時に評価する予測器の組（タイミングには最初の予測器を使う）
+#[derive(Debug, Default)]
+pub struct BranchPredictors {
+    entries: Vec<(Box<dyn BranchPredictor>, PredictorStats)>,
+    /// 成績を数えないなら真（予測器の表と履歴は更新する）
+    stats_paused: bool,
+}
+
+impl BranchPredictors {
+    /// 指定した種類の予測器を作成する
+    pub fn new(kinds: &[PredictorKind]) -> Self {
+        Self { entries: kinds.iter().map(|kind| (kind.build(), PredictorStats::default())).collect(), stats_paused: false }
+    }
+
+    /// 予測器がないか
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 全ての予測器で予測して結果で更新し、最初の予測器が外したかを返す
+    pub fn resolve(&mut self, pc: MemoryAddress, target: MemoryAddress, taken: bool) -> bool {
+        let mut mispredicted = false;
+        for (i, (predictor, stats)) in self.entries.iter_mut().enumerate() {
+            let correct = predictor.predict(pc, target) == taken;
+            predictor.update(pc, target, taken);
+            if !self.stats_paused {
+                stats.branches += 1;
+                stats.correct += correct as u64;
+            }
+            if i == 0 {
+                mispredicted = !correct;
+            }
+        }
+        mispredicted
+    }
+
+    /// 予測器の名前
+    pub fn names(&self) -> Vec<&'static str> {
+        self.entries.iter().map(|(predictor, _)| predictor.name()).collect()
+    }
+
+    /// 予測器の表と履歴を保存する
+    pub fn snapshot(&self) -> PredictorSnapshot {
+        PredictorSnapshot {
+            predictors: self.entries.iter().map(|(predictor, _)| (predictor.name().to_string(), predictor.state())).collect(),
+        }
+    }
+
+    /// 保存した表と履歴に戻す（成績は変えない）
+    /// 名前や状態の長さが合わない予測器と、スナップショットにない予測器（コールドな状態など）は初期状態にする
+    pub fn restore(&mut self, snapshot: &PredictorSnapshot) {
+        for (i, (predictor, _)) in self.entries.iter_mut().enumerate() {
+            let restored = snapshot.predictors.get(i)
+                .is_some_and(|(name, state)| name == predictor.name() && predictor.restore_state(state));
+            if !restored {
+                if let Ok(kind) = PredictorKind::parse(predictor.name()) {
+                    *predictor = kind.build();
+                }
+            }
+        }
+    }
+
+    /// 予測器ごとの成績（名前, 成績）
+    pub fn stats(&self) -> Vec<(&'static str, &PredictorStats)> {
+        self.entries.iter().map(|(predictor, stats)| (predictor.name(), stats)).collect()
+    }
+
+    /// 成績を数えるかを切り替える
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats_paused = !enabled;
+    }
+
+    /// 成績を消去する（予測器の状態は保持する）
+    pub fn reset_stats(&mut self) {
+        for (_, stats) in &mut self.entries {
+            *stats = PredictorStats::default();
+        }
+    }
+}
+
+impl fmt::Display for BranchPredictors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, stats)) in self.stats().into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            // タイミングに使っている予測器に印を付ける
+            let marker = if i == 0 { "*" } else { " " };
+            write!(f, "{} {:<15} 正解率 {:>6.2}% ({} / {}, 予測ミス {})",
+                marker, name, stats.accuracy() * 100.0, stats.correct, stats.branches, stats.mispredictions())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_branch_accuracy() {
+        // 0x400010 から 0x400000 へ戻るループ分岐が9回成立して1回不成立、を3回繰り返す
+        let mut predictors = BranchPredictors::new(&PredictorKind::ALL);
+        for _ in 0..3 {
+            for i in 0..10 {
+                predictors.resolve(0x400010, 0x400000, i < 9);
+            }
+        }
+        let correct: Vec<u64> = predictors.stats().iter().map(|(_, stats)| stats.correct).collect();
+        // not-takenは抜けるときだけ、backward-takenは抜けるとき以外は当たる
+        assert_eq!(correct[0], 3);
+        assert_eq!(correct[1], 27);
+        // bimodalは最初の1回で学習した後は抜けるときだけ外す
+        assert_eq!(correct[2], 26);
+        assert!(predictors.stats().iter().all(|(_, stats)| stats.branches == 30));
+    }
+
+    #[test]
+    fn test_gshare_learns_alternating_pattern() {
+        // 成立・不成立が交互に来る分岐はbimodalでは当たらないが、gshareは履歴で区別できる
+        let mut bimodal = BranchPredictors::new(&[PredictorKind::Bimodal]);
+        let mut gshare = BranchPredictors::new(&[PredictorKind::Gshare]);
+        for i in 0..200 {
+            bimodal.resolve(0x400020, 0x400040, i % 2 == 0);
+            gshare.resolve(0x400020, 0x400040, i % 2 == 0);
+        }
+        assert!(bimodal.stats()[0].1.accuracy() < 0.6);
+        assert!(gshare.stats()[0].1.accuracy() > 0.9);
+        assert_eq!(PredictorKind::parse("gshare"), Ok(PredictorKind::Gshare));
+        assert!(PredictorKind::parse("perceptron").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let kinds = [PredictorKind::Gshare, PredictorKind::Bimodal, PredictorKind::NotTaken];
+        let mut predictors = BranchPredictors::new(&kinds);
+        for i in 0..50 {
+            predictors.resolve(0x400020 + (i % 3) * 4, 0x400000, i % 2 == 0);
+        }
+        let snapshot = predictors.snapshot();
+        let mut writer = SnapshotWriter::new();
+        snapshot.encode(&mut writer);
+        let bytes = writer.into_bytes();
+        let mut reader = SnapshotReader::new(&bytes).unwrap();
+        assert_eq!(PredictorSnapshot::decode(&mut reader), Ok(snapshot.clone()));
+        assert_eq!(reader.finish(), Ok(()));
+
+        // 戻した予測器は保存した後と同じ予測をする
+        let mut restored = BranchPredictors::new(&kinds);
+        restored.restore(&snapshot);
+        assert_eq!(restored.snapshot(), snapshot);
+        for i in 0..20 {
+            let pc = 0x400020 + (i % 3) * 4;
+            assert_eq!(restored.resolve(pc, 0x400000, i % 5 == 0), predictors.resolve(pc, 0x400000, i % 5 == 0));
+        }
+
+        // 空のスナップショットでは初期状態に戻る
+        restored.restore(&PredictorSnapshot::default());
+        assert_eq!(restored.snapshot(), BranchPredictors::new(&kinds).snapshot());
+    }
+}
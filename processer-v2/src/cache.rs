@@ -1,8 +1,14 @@
 //! キャッシュシステムの実装
 
+use std::collections::HashSet;
 use std::fmt;
+use std::ops::Range;
 
+use crate::bus::{BusConfig, BusMonitor, BusStats, TransferKind};
+use crate::coherence::{BusTransaction, SnoopBus};
 use crate::memory::{Memory, MemoryAddress, Word, MemoryError};
+use crate::miss_classifier::{MissClassifier, MissKind};
+use crate::snapshot::{SnapshotError, SnapshotReader, SnapshotWriter};
 
 /// キャッシュラインのサイズ（バイト単位）
 pub const CACHE_LINE_SIZE: usize = 32;
@@ -10,9 +16,402 @@ pub const CACHE_LINE_SIZE: usize = 32;
 /// キャッシュのセット数
 pub const CACHE_SETS: usize = 64;
 
-/// キャッシュの連想度（ウェイ数）
+/// キャッシュの連想度（ウェイ数、二分木の擬似LRUのため2のべき乗）
 pub const CACHE_WAYS: usize = 4;
 
+/// 擬似乱数による置換のデフォルトのシード
+pub const DEFAULT_REPLACEMENT_SEED: u64 = 1;
+
+/// 書き込み方式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WritePolicy {
+    /// ライトバック・ライトアロケート（書き込みはラインだけを更新し、追い出すときに書き戻す）
+    #[default]
+    WriteBack,
+    /// ライトスルー・ライトアロケート（書き込みのたびにメモリも更新し、ミスではラインを読み込む）
+    WriteThrough,
+    /// ライトスルー・ノーライトアロケート（書き込みミスではラインを読み込まずにメモリだけを更新する）
+    NoWriteAllocate,
+}
+
+impl WritePolicy {
+    /// "wb"・"wt"・"wt-noalloc" から作成する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "wb" => Ok(WritePolicy::WriteBack),
+            "wt" => Ok(WritePolicy::WriteThrough),
+            "wt-noalloc" => Ok(WritePolicy::NoWriteAllocate),
+            _ => Err(format!("無効な書き込み方式です: {}", text)),
+        }
+    }
+
+    /// 表示用の名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            WritePolicy::WriteBack => "wb",
+            WritePolicy::WriteThrough => "wt",
+            WritePolicy::NoWriteAllocate => "wt-noalloc",
+        }
+    }
+}
+
+/// 置換方式の種類
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplacementKind {
+    /// 最も長く使われていないラインを追い出す
+    #[default]
+    Lru,
+    /// 最も早く読み込んだラインを追い出す
+    Fifo,
+    /// 擬似乱数（xorshift64）で選んだラインを追い出す
+    Random { seed: u64 },
+    /// 二分木の擬似LRU（実機と同じ追い出し方）
+    TreePlru,
+}
+
+impl ReplacementKind {
+    /// "lru"・"fifo"・"random[:seed]"・"plru" から作成する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.split_once(':') {
+            None if text == "lru" => Ok(ReplacementKind::Lru),
+            None if text == "fifo" => Ok(ReplacementKind::Fifo),
+            None if text == "random" => Ok(ReplacementKind::Random { seed: DEFAULT_REPLACEMENT_SEED }),
+            None if text == "plru" => Ok(ReplacementKind::TreePlru),
+            Some(("random", seed)) => seed.parse()
+                .map(|seed| ReplacementKind::Random { seed })
+                .map_err(|_| format!("無効なシードです: {}", seed)),
+            _ => Err(format!("無効な置換方式です: {}", text)),
+        }
+    }
+
+    /// 表示用の名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReplacementKind::Lru => "lru",
+            ReplacementKind::Fifo => "fifo",
+            ReplacementKind::Random { .. } => "random",
+            ReplacementKind::TreePlru => "plru",
+        }
+    }
+
+    /// 置換方式を作成する
+    pub fn build(&self) -> Box<dyn ReplacementPolicy> {
+        match *self {
+            ReplacementKind::Lru => Box::new(Lru::default()),
+            ReplacementKind::Fifo => Box::new(Fifo::default()),
+            ReplacementKind::Random { seed } => Box::new(RandomReplacement::new(seed)),
+            ReplacementKind::TreePlru => Box::new(TreePlru::default()),
+        }
+    }
+}
+
+/// キャッシュの設定
+#[derive(Debug, Clone, Default)]
+pub struct CacheConfig {
+    /// 書き込み方式
+    pub write_policy: WritePolicy,
+    /// 置換方式
+    pub replacement: ReplacementKind,
+    /// キャッシュを経由せずメモリに直接アクセスする領域（MMIOなど、古い値を読んではいけない領域）
+    pub uncached_regions: Vec<Range<MemoryAddress>>,
+    /// キャッシュを模擬せず、すべてのアクセスをワード単位でメモリに直接行う（アーキテクチャ状態だけを見る高速な機能シミュレーション）
+    pub bypass: bool,
+    /// ヒット・ミスを分けて数えるアドレス範囲（重なっていればそれぞれに数える）
+    pub stats_regions: Vec<StatsRegion>,
+    /// ミスを3C（初期参照・容量・競合）に分類する（影のキャッシュを動かすので、必要なときだけ有効にする）
+    pub classify_misses: bool,
+}
+
+/// 統計を分けて数えるアドレス範囲（テキスト・スタック・シーンデータなど、どこでミスが起きているかを見るため）
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsRegion {
+    /// 名前（統計の表示とJSONのキーに使う）
+    pub name: String,
+    /// 範囲（終了は含まない）
+    pub range: Range<MemoryAddress>,
+}
+
+impl CacheConfig {
+    /// キャッシュを経由しないアドレスか
+    /// 一部だけが領域にかかるラインも、ラインの読み込みや書き戻しで直接書いた値を古い値で上書きしないよう、ライン全体をキャッシュしない
+    pub fn is_uncached(&self, address: MemoryAddress) -> bool {
+        let (start, end) = line_bounds(address);
+        self.uncached_regions.iter().any(|region| (region.start as u64) < end && start < region.end as u64)
+    }
+}
+
+impl fmt::Display for CacheConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.bypass {
+            return write!(f, "なし（メモリに直接アクセス）");
+        }
+        write!(f, "{}KB / {}ウェイ / {}バイトライン / 書き込み方式 {} / 置換方式 {}",
+            CACHE_SETS * CACHE_WAYS * CACHE_LINE_SIZE / 1024, CACHE_WAYS, CACHE_LINE_SIZE,
+            self.write_policy.name(), self.replacement.name())?;
+        for region in &self.uncached_regions {
+            write!(f, " / キャッシュしない領域 0x{:08X}-0x{:08X}", region.start, region.end)?;
+        }
+        Ok(())
+    }
+}
+
+/// キャッシュの置換方式
+/// 無効なラインがあるセットではキャッシュがそれを使うので、victim は全ウェイが有効なときだけ呼ばれる
+pub trait ReplacementPolicy: fmt::Debug {
+    /// 置換方式の名前
+    fn name(&self) -> &'static str;
+    /// ヒットしたラインを記録する
+    fn touch(&mut self, set: usize, way: usize);
+    /// 読み込んだラインを記録する
+    fn insert(&mut self, set: usize, way: usize);
+    /// 追い出すウェイを選ぶ
+    fn victim(&mut self, set: usize) -> usize;
+    /// スナップショットに保存する状態
+    fn state(&self) -> Vec<u64>;
+    /// 保存した状態に戻す（長さが合わなければ false）
+    fn restore_state(&mut self, state: &[u64]) -> bool;
+    /// セット内の各ウェイの古さ（0が最も新しく、大きいほど先に追い出す。順位を持たない方式ならNone）
+    fn ages(&self, _set: usize) -> Option<[usize; CACHE_WAYS]> {
+        None
+    }
+}
+
+/// ラインごとの時刻（LRUは最後にアクセスした時刻、FIFOは読み込んだ時刻）
+#[derive(Debug, Clone)]
+struct LineStamps {
+    stamps: Vec<u64>,
+    counter: u64,
+}
+
+impl Default for LineStamps {
+    fn default() -> Self {
+        Self { stamps: vec![0; CACHE_SETS * CACHE_WAYS], counter: 0 }
+    }
+}
+
+impl LineStamps {
+    fn stamp(&mut self, set: usize, way: usize) {
+        self.stamps[set * CACHE_WAYS + way] = self.counter;
+        self.counter += 1;
+    }
+
+    /// 時刻が最も古いウェイ（同じなら番号の小さいウェイ）
+    fn oldest(&self, set: usize) -> usize {
+        (0..CACHE_WAYS).min_by_key(|&way| self.stamps[set * CACHE_WAYS + way]).unwrap_or(0)
+    }
+
+    /// 時刻が自分より新しいウェイの数
+    fn ages(&self, set: usize) -> [usize; CACHE_WAYS] {
+        let stamps = &self.stamps[set * CACHE_WAYS..(set + 1) * CACHE_WAYS];
+        std::array::from_fn(|way| stamps.iter().filter(|&&stamp| stamp > stamps[way]).count())
+    }
+
+    fn state(&self) -> Vec<u64> {
+        std::iter::once(self.counter).chain(self.stamps.iter().copied()).collect()
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        match state.split_first() {
+            Some((&counter, stamps)) if stamps.len() == self.stamps.len() => {
+                self.counter = counter;
+                self.stamps.copy_from_slice(stamps);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// LRU（Least Recently Used）
+#[derive(Debug, Clone, Default)]
+pub struct Lru(LineStamps);
+
+impl ReplacementPolicy for Lru {
+    fn name(&self) -> &'static str {
+        "lru"
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        self.0.stamp(set, way);
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.0.stamp(set, way);
+    }
+
+    fn victim(&mut self, set: usize) -> usize {
+        self.0.oldest(set)
+    }
+
+    fn state(&self) -> Vec<u64> {
+        self.0.state()
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        self.0.restore_state(state)
+    }
+
+    fn ages(&self, set: usize) -> Option<[usize; CACHE_WAYS]> {
+        Some(self.0.ages(set))
+    }
+}
+
+/// FIFO（ヒットしても順番は変わらない）
+#[derive(Debug, Clone, Default)]
+pub struct Fifo(LineStamps);
+
+impl ReplacementPolicy for Fifo {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn touch(&mut self, _set: usize, _way: usize) {}
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.0.stamp(set, way);
+    }
+
+    fn victim(&mut self, set: usize) -> usize {
+        self.0.oldest(set)
+    }
+
+    fn state(&self) -> Vec<u64> {
+        self.0.state()
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        self.0.restore_state(state)
+    }
+
+    fn ages(&self, set: usize) -> Option<[usize; CACHE_WAYS]> {
+        Some(self.0.ages(set))
+    }
+}
+
+/// 擬似乱数で追い出すウェイを選ぶ（シードが同じなら同じ順に選ぶ）
+#[derive(Debug, Clone)]
+pub struct RandomReplacement {
+    /// 擬似乱数の状態（xorshift64）
+    rng_state: u64,
+}
+
+impl RandomReplacement {
+    pub fn new(seed: u64) -> Self {
+        // xorshiftは状態が0だと0しか出さないので、シードを混ぜて0を避ける
+        let rng_state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Self { rng_state: if rng_state == 0 { 0x9E37_79B9_7F4A_7C15 } else { rng_state } }
+    }
+}
+
+impl ReplacementPolicy for RandomReplacement {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn touch(&mut self, _set: usize, _way: usize) {}
+
+    fn insert(&mut self, _set: usize, _way: usize) {}
+
+    fn victim(&mut self, _set: usize) -> usize {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        (x % CACHE_WAYS as u64) as usize
+    }
+
+    fn state(&self) -> Vec<u64> {
+        vec![self.rng_state]
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        match state {
+            [rng_state] => {
+                self.rng_state = *rng_state;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 二分木の擬似LRU
+/// セットごとにウェイ数 - 1 ビットの二分木を持つ（ノード1が根、ノードnの子は2nと2n+1）。
+/// ビットが0なら左（番号の小さいウェイ側）、1なら右を追い出し候補とし、
+/// アクセスしたウェイへの経路上のビットはそのウェイから離れる向きに設定する。
+#[derive(Debug, Clone)]
+pub struct TreePlru {
+    /// セットごとの木（ビットnがノードn）
+    trees: Vec<u64>,
+}
+
+impl Default for TreePlru {
+    fn default() -> Self {
+        Self { trees: vec![0; CACHE_SETS] }
+    }
+}
+
+impl TreePlru {
+    /// wayへの経路上のビットを、wayから離れる向きに設定する
+    fn point_away(&mut self, set: usize, way: usize) {
+        let tree = &mut self.trees[set];
+        let (mut node, mut low, mut width) = (1, 0, CACHE_WAYS);
+        while width > 1 {
+            width /= 2;
+            if way < low + width {
+                *tree |= 1 << node;
+                node *= 2;
+            } else {
+                *tree &= !(1 << node);
+                node = node * 2 + 1;
+                low += width;
+            }
+        }
+    }
+}
+
+impl ReplacementPolicy for TreePlru {
+    fn name(&self) -> &'static str {
+        "plru"
+    }
+
+    fn touch(&mut self, set: usize, way: usize) {
+        self.point_away(set, way);
+    }
+
+    fn insert(&mut self, set: usize, way: usize) {
+        self.point_away(set, way);
+    }
+
+    fn victim(&mut self, set: usize) -> usize {
+        let tree = self.trees[set];
+        let (mut node, mut low, mut width) = (1, 0, CACHE_WAYS);
+        while width > 1 {
+            width /= 2;
+            if tree & (1 << node) == 0 {
+                node *= 2;
+            } else {
+                node = node * 2 + 1;
+                low += width;
+            }
+        }
+        low
+    }
+
+    fn state(&self) -> Vec<u64> {
+        self.trees.clone()
+    }
+
+    fn restore_state(&mut self, state: &[u64]) -> bool {
+        if state.len() != self.trees.len() {
+            return false;
+        }
+        self.trees.copy_from_slice(state);
+        true
+    }
+}
+
 /// キャッシュライン
 #[derive(Debug, Clone)]
 struct CacheLine {
@@ -20,12 +419,12 @@ struct CacheLine {
     valid: bool,
     /// ダーティビット（書き込み済みかどうか）
     dirty: bool,
+    /// 共有ビット（MESIのShared。他のコアも同じラインを持っている可能性がある）
+    shared: bool,
     /// タグ
     tag: u32,
     /// データ
     data: [u8; CACHE_LINE_SIZE],
-    /// アクセス時刻（LRU用）
-    access_time: u64,
 }
 
 impl CacheLine {
@@ -33,9 +432,9 @@ impl CacheLine {
         Self {
             valid: false,
             dirty: false,
+            shared: false,
             tag: 0,
             data: [0; CACHE_LINE_SIZE],
-            access_time: 0,
         }
     }
 }
@@ -59,6 +458,102 @@ impl CacheSet {
     }
 }
 
+/// キャッシュラインのデータ以外の状態
+#[derive(Debug, Clone, Copy)]
+struct LineState {
+    valid: bool,
+    dirty: bool,
+    tag: u32,
+}
+
+/// キャッシュの状態のスナップショット
+/// タグ・有効ビット・ダーティビット・置換方式の状態だけを持ち、データは持たない（データは復元時にメモリから読み直す）
+#[derive(Debug, Clone)]
+pub struct CacheSnapshot {
+    /// セット順・ウェイ順に並べたラインの状態
+    lines: Vec<LineState>,
+    /// 置換方式の名前
+    replacement: String,
+    /// 置換方式の状態
+    replacement_state: Vec<u64>,
+}
+
+impl CacheSnapshot {
+    /// 有効なライン数
+    pub fn valid_lines(&self) -> usize {
+        self.lines.iter().filter(|line| line.valid).count()
+    }
+
+    /// 保存したときの置換方式の名前
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    /// シミュレータのスナップショットに書き出す
+    pub fn encode(&self, writer: &mut SnapshotWriter) {
+        writer.put_u32(self.lines.len() as u32);
+        for line in &self.lines {
+            writer.put_u8(line.valid as u8 | (line.dirty as u8) << 1);
+            writer.put_u32(line.tag);
+        }
+        writer.put_str(&self.replacement);
+        writer.put_u32(self.replacement_state.len() as u32);
+        for &value in &self.replacement_state {
+            writer.put_u64(value);
+        }
+    }
+
+    /// シミュレータのスナップショットから読み込む
+    pub fn decode(reader: &mut SnapshotReader) -> Result<Self, SnapshotError> {
+        let count = reader.u32()? as usize;
+        if count != CACHE_SETS * CACHE_WAYS {
+            return Err(SnapshotError::Mismatch(format!("キャッシュライン数 {}（現在は {}）", count, CACHE_SETS * CACHE_WAYS)));
+        }
+        let mut lines = Vec::with_capacity(count);
+        for _ in 0..count {
+            let flags = reader.u8()?;
+            lines.push(LineState { valid: flags & 1 != 0, dirty: flags & 2 != 0, tag: reader.u32()? });
+        }
+        let replacement = reader.string()?;
+        let mut replacement_state = Vec::new();
+        for _ in 0..reader.u32()? {
+            replacement_state.push(reader.u64()?);
+        }
+        Ok(Self { lines, replacement, replacement_state })
+    }
+}
+
+/// キャッシュラインの状態（RTLのキャッシュとの比較用）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheLineInfo {
+    pub set: usize,
+    pub way: usize,
+    pub valid: bool,
+    pub dirty: bool,
+    pub tag: u32,
+    /// ラインの先頭アドレス
+    pub address: MemoryAddress,
+    /// 置換方式での古さ（0が最も新しい。LRU・FIFO以外ではNone）
+    pub age: Option<usize>,
+}
+
+/// キャッシュへの1回のアクセス（トレース用。命令フェッチやロード・ストアの1回を1つにまとめる）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheAccess {
+    /// アクセスしたアドレス
+    pub address: MemoryAddress,
+    /// バイト数
+    pub size: u32,
+    /// 命令フェッチか（偽ならデータアクセス）
+    pub fetch: bool,
+    /// 書き込みか
+    pub write: bool,
+    /// ヒットしたか（ラインをまたぐアクセスは、どれかのラインでミスしたらミス）
+    pub hit: bool,
+    /// 追い出した有効なラインの先頭アドレス
+    pub evicted: Option<MemoryAddress>,
+}
+
 /// キャッシュ統計情報
 #[derive(Debug, Clone, Default)]
 pub struct CacheStats {
@@ -66,8 +561,45 @@ pub struct CacheStats {
     pub hits: u64,
     /// ミス数
     pub misses: u64,
-    /// 書き込みバック数
+    /// 書き込みバック数（ダーティラインを追い出したときの書き戻し）
     pub writebacks: u64,
+    /// ライトスルー数（書き込みと同時にメモリへ書いた回数）
+    pub write_throughs: u64,
+    /// 初期参照ミス数（一度も読み込んだことのないライン）
+    pub compulsory_misses: u64,
+    /// 容量ミス数（同じ容量の完全連想キャッシュでもミスする）
+    pub capacity_misses: u64,
+    /// 競合ミス数（同じ容量の完全連想キャッシュならヒットする）
+    pub conflict_misses: u64,
+    /// 他のコアの書き込みで無効にされたライン数
+    pub invalidations: u64,
+    /// コヒーレンスミス数（他のコアの書き込みで無効にされたラインへのミス。3Cには含めない）
+    pub coherence_misses: u64,
+    /// CacheConfig::stats_regions と同じ順の、アドレス範囲ごとのヒット・ミス数
+    pub regions: Vec<RegionStats>,
+    /// ヒット数のうち命令フェッチの分（残りはデータアクセス）
+    pub fetch_hits: u64,
+    /// ミス数のうち命令フェッチの分（残りはデータアクセス）
+    pub fetch_misses: u64,
+}
+
+/// アドレス範囲ごとのヒット・ミス数
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RegionStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl RegionStats {
+    /// ヒット率を計算
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
 }
 
 impl CacheStats {
@@ -80,17 +612,149 @@ impl CacheStats {
             self.hits as f64 / total as f64
         }
     }
+
+    /// 命令フェッチのヒット・ミス数
+    pub fn fetch(&self) -> RegionStats {
+        RegionStats { hits: self.fetch_hits, misses: self.fetch_misses }
+    }
+
+    /// データアクセス（ロード・ストア）のヒット・ミス数
+    pub fn data(&self) -> RegionStats {
+        RegionStats {
+            hits: self.hits.saturating_sub(self.fetch_hits),
+            misses: self.misses.saturating_sub(self.fetch_misses),
+        }
+    }
 }
 
-/// キャッシュシステム
+/// キャッシュのモデル（RTLの振る舞いをそのまま写したモデルなどを、プロセッサを変更せずに差し替えるため）
+/// プロセッサはメモリへのアクセスをすべてこのトレイトを通して行う。
+/// 読み込み・書き込みはメモリの内容と一貫した値を返し、書き戻していないデータは flush でメモリに反映する
+pub trait CacheLike: fmt::Debug {
+    /// モデル名
+    fn name(&self) -> &'static str;
+    /// 設定を変更する（有効なラインはメモリに書き戻して捨てる）
+    fn set_config(&mut self, memory: &mut Memory, config: CacheConfig) -> Result<(), MemoryError>;
+    /// 設定を取得
+    fn config(&self) -> &CacheConfig;
+    /// バイトを読み込む
+    fn read_byte(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<u8, MemoryError>;
+    /// バイトを書き込む
+    fn write_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError>;
+    /// 統計や置換方式の状態を変更せずにバイトを読み込む（デバッガ用）
+    fn peek_byte(&self, memory: &Memory, address: MemoryAddress) -> Result<u8, MemoryError>;
+    /// 統計や置換方式の状態を変更せずに、メモリとキャッシュ上のラインの両方にバイトを書き込む（デバッガ用）
+    fn poke_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError>;
+    /// start から始まるメモリの内容に、書き戻していないデータを上書きする（デバッガ用。デフォルトは何もしない）
+    fn overlay(&self, _start: MemoryAddress, _bytes: &mut [u8]) {}
+    /// ワードを読み込む（デフォルトは4バイトを個別に読み込む）
+    fn read_word(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<Word, MemoryError> {
+        if !memory.is_device(address) && (address as usize).saturating_add(3) >= memory.size() {
+            return Err(MemoryError::AddressOutOfRange(address));
+        }
+        let mut value = 0;
+        for i in 0..4 {
+            value |= (self.read_byte(memory, address + i)? as Word) << (i * 8);
+        }
+        Ok(value)
+    }
+    /// 命令フェッチとしてワードを読み込む（統計を命令フェッチとして数える。デフォルトは read_word と同じ）
+    fn fetch_word(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<Word, MemoryError> {
+        self.read_word(memory, address)
+    }
+    /// ワードを書き込む（デフォルトは4バイトを個別に書き込む）
+    fn write_word(&mut self, memory: &mut Memory, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
+        if !memory.is_device(address) && (address as usize).saturating_add(3) >= memory.size() {
+            return Err(MemoryError::AddressOutOfRange(address));
+        }
+        for i in 0..4 {
+            self.write_byte(memory, address + i, (value >> (i * 8)) as u8)?;
+        }
+        Ok(())
+    }
+    /// 統計情報を取得
+    fn get_stats(&self) -> &CacheStats;
+    /// 統計情報を置き換える
+    fn set_stats(&mut self, stats: CacheStats);
+    /// 統計情報をリセット
+    fn reset_stats(&mut self) {
+        self.set_stats(CacheStats::default());
+    }
+    /// バスの設定を変更（統計はリセットされる）
+    fn set_bus_config(&mut self, config: BusConfig);
+    /// バスの時間を進める
+    fn advance_bus(&mut self, time: u64);
+    /// バス統計を取得
+    fn get_bus_stats(&self) -> &BusStats;
+    /// キャッシュとバスの統計を数えるかを切り替える（デフォルトは常に数える）
+    fn set_stats_enabled(&mut self, _enabled: bool) {}
+    /// メモリを待つ原因になるミスと書き戻しの回数（統計を止めていても数える。デフォルトは統計の値）
+    fn wait_counts(&self) -> (u64, u64) {
+        (self.get_stats().misses, self.get_stats().writebacks)
+    }
+    /// セットの範囲に含まれるラインの状態を返す（デフォルトは空）
+    fn dump(&self, _sets: Range<usize>) -> Vec<CacheLineInfo> {
+        Vec::new()
+    }
+    /// 全てのダーティラインをメモリに書き戻す
+    fn flush(&mut self, memory: &mut Memory) -> Result<(), MemoryError>;
+    /// 全てのラインを書き戻さずに捨てる（デフォルトは何もしない）
+    fn invalidate(&mut self) {}
+    /// 現在の状態を保存する
+    fn snapshot(&self) -> CacheSnapshot;
+    /// スナップショットの状態に戻す（メモリの内容は変えず、ダーティラインは書き戻してから置き換える）
+    fn restore(&mut self, memory: &mut Memory, snapshot: &CacheSnapshot) -> Result<(), MemoryError>;
+    /// アクセスの記録を有効・無効にする（デフォルトは記録しない）
+    fn set_trace_enabled(&mut self, _enabled: bool) {}
+    /// 記録したアクセスを取り出す
+    fn take_trace(&mut self) -> Vec<CacheAccess> {
+        Vec::new()
+    }
+    /// マルチコア実行で、他のコアのキャッシュとコヒーレンスを保つようにする（デフォルトは対応しない）
+    fn enable_coherence(&mut self, _core_id: usize) {}
+    /// スヌープバスを置き換えて元のバスを返す（共有メモリと一緒に受け渡す。対応しなければ受け取らずにそのまま返す）
+    fn replace_snoop_bus(&mut self, bus: Option<SnoopBus>) -> Option<SnoopBus> {
+        bus
+    }
+    /// 他のコアから届いたバスのトランザクションを反映する
+    fn apply_snoops(&mut self) {}
+}
+
+/// キャッシュシステム（デフォルトのモデル）
 #[derive(Debug)]
 pub struct Cache {
     /// キャッシュセット
     sets: [CacheSet; CACHE_SETS],
     /// 統計情報
     stats: CacheStats,
-    /// アクセス時刻カウンタ
-    access_counter: u64,
+    /// 置換方式
+    replacement: Box<dyn ReplacementPolicy>,
+    /// メモリとの間の転送を監視するバスモニタ
+    bus: BusMonitor,
+    /// キャッシュの設定
+    config: CacheConfig,
+    /// 記録中のアクセス（Noneなら記録しない）
+    trace: Option<Vec<CacheAccess>>,
+    /// ミスを3Cに分類する影のキャッシュ（分類しなければNone）
+    classifier: Option<MissClassifier>,
+    /// マルチコア実行でのコア番号
+    core_id: usize,
+    /// スヌープバス（コヒーレンスを保つコアのうち、実行中のコアだけが持つ）
+    snoop_bus: Option<SnoopBus>,
+    /// 他のコアの書き込みで無効にされ、まだアクセスしていないライン
+    invalidated: HashSet<u32>,
+    /// 命令フェッチのアクセス中なら真
+    fetching: bool,
+    /// 統計を数えるなら真
+    stats_enabled: bool,
+    /// 統計を止めていても数える、ミスと書き戻しの回数（サイクル数の見積もりに使う）
+    waits: (u64, u64),
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cache {
@@ -99,10 +763,46 @@ impl Cache {
         Self {
             sets: [(); CACHE_SETS].map(|_| CacheSet::new()),
             stats: CacheStats::default(),
-            access_counter: 0,
+            replacement: ReplacementKind::default().build(),
+            bus: BusMonitor::new(BusConfig::default()),
+            config: CacheConfig::default(),
+            trace: None,
+            classifier: None,
+            core_id: 0,
+            snoop_bus: None,
+            invalidated: HashSet::new(),
+            fetching: false,
+            stats_enabled: true,
+            waits: (0, 0),
         }
     }
 
+    /// 統計を数えていれば更新する
+    fn count(&mut self, update: impl FnOnce(&mut CacheStats)) {
+        if self.stats_enabled {
+            update(&mut self.stats);
+        }
+    }
+
+    /// 統計を数えていればバスの転送を記録する
+    fn record_transfer(&mut self, kind: TransferKind, bytes: u64) {
+        if self.stats_enabled {
+            self.bus.record(kind, bytes);
+        }
+    }
+
+    /// ダーティラインの書き戻しを数える
+    fn count_writeback(&mut self) {
+        self.waits.1 += 1;
+        self.count(|stats| stats.writebacks += 1);
+        self.record_transfer(TransferKind::Writeback, CACHE_LINE_SIZE as u64);
+    }
+
+    /// スヌープバスを持っていれば、自分のコア番号と一緒に返す
+    fn snoop_bus(&mut self) -> Option<(usize, &mut SnoopBus)> {
+        Some((self.core_id, self.snoop_bus.as_mut()?))
+    }
+
     /// アドレスからセットインデックスを計算
     fn get_set_index(&self, address: MemoryAddress) -> usize {
         ((address as usize) / CACHE_LINE_SIZE) % CACHE_SETS
@@ -120,38 +820,21 @@ impl Cache {
 
     /// 指定されたタグのキャッシュラインを検索
     fn find_line(&mut self, set_index: usize, tag: u32) -> Option<usize> {
-        let set = &mut self.sets[set_index];
-        for (i, line) in set.lines.iter_mut().enumerate() {
-            if line.valid && line.tag == tag {
-                line.access_time = self.access_counter;
-                self.access_counter += 1;
-                return Some(i);
-            }
-        }
-        None
+        let way_index = self.sets[set_index].lines.iter().position(|line| line.valid && line.tag == tag)?;
+        self.replacement.touch(set_index, way_index);
+        Some(way_index)
     }
 
-    /// LRUでキャッシュラインを選択
-    fn select_lru_line(&mut self, set_index: usize) -> usize {
-        let set = &mut self.sets[set_index];
-        let mut lru_index = 0;
-        let mut oldest_time = set.lines[0].access_time;
-        
-        for (i, line) in set.lines.iter().enumerate() {
-            if !line.valid {
-                return i; // 無効なラインがあればそれを使用
-            }
-            if line.access_time < oldest_time {
-                oldest_time = line.access_time;
-                lru_index = i;
-            }
+    /// 置換方式で追い出すキャッシュラインを選択
+    fn select_victim(&mut self, set_index: usize) -> usize {
+        // 無効なラインがあればそれを使用
+        match self.sets[set_index].lines.iter().position(|line| !line.valid) {
+            Some(way_index) => way_index,
+            None => self.replacement.victim(set_index),
         }
-        
-        lru_index
     }
 
     /// キャッシュラインをメモリに書き戻す
-    #[allow(dead_code)]
     fn writeback_line(&mut self, memory: &mut Memory, set_index: usize, way_index: usize) -> Result<(), MemoryError> {
         let line = &self.sets[set_index].lines[way_index];
         if !line.dirty {
@@ -163,9 +846,157 @@ impl Cache {
         for i in 0..CACHE_LINE_SIZE {
             memory.write_byte(base_address + i as u32, line.data[i])?;
         }
-        
-        self.stats.writebacks += 1;
-        Ok(())
+        
+        self.count_writeback();
+        Ok(())
+    }
+
+    /// ミスしたラインを読み込むウェイを選び、追い出すラインがダーティなら書き戻してから読み込む
+    /// （ウェイと、有効なラインを追い出した場合はその先頭アドレスを返す）
+    fn allocate(&mut self, memory: &mut Memory, set_index: usize, tag: u32) -> Result<(usize, Option<MemoryAddress>), MemoryError> {
+        let way_index = self.select_victim(set_index);
+        let victim = &self.sets[set_index].lines[way_index];
+        let evicted = victim.valid.then(|| line_address(victim.tag, set_index));
+        if victim.valid && victim.dirty {
+            self.writeback_line(memory, set_index, way_index)?;
+        }
+        if let (Some(address), Some((core_id, bus))) = (evicted, self.snoop_bus()) {
+            bus.evict(core_id, address / CACHE_LINE_SIZE as u32);
+        }
+        self.load_line(memory, set_index, way_index, tag)?;
+        Ok((way_index, evicted))
+    }
+
+    /// アドレスを含むラインをキャッシュしないか（キャッシュしない領域やデバイスの範囲にかかるライン）
+    /// デバイスと同じラインをキャッシュすると、ラインの読み込みでデバイスを読み、書き戻しで古い値をデバイスに書き込んでしまう
+    fn is_line_uncached(&self, memory: &Memory, address: MemoryAddress) -> bool {
+        let start = address & !(CACHE_LINE_SIZE as u32 - 1);
+        self.config.is_uncached(address) || memory.overlaps_device(start, CACHE_LINE_SIZE as u32)
+    }
+
+    /// ワードが1つのラインに収まってキャッシュに載っていれば、そのセットとウェイを返す
+    /// ラインをまたぐ、キャッシュしない領域にかかる、ミスするアクセスはNoneで、バイト単位で処理する
+    fn find_word_line(&mut self, memory: &Memory, address: MemoryAddress) -> Option<(usize, usize)> {
+        if self.get_offset(address) + 4 > CACHE_LINE_SIZE || self.is_line_uncached(memory, address) {
+            return None;
+        }
+        let set_index = self.get_set_index(address);
+        let way_index = self.find_line(set_index, self.get_tag(address))?;
+        Some((set_index, way_index))
+    }
+
+    /// ワード単位のヒットを、バイト単位で4回ヒットした場合と同じ統計になるように、まとめて数えて記録する
+    fn count_word_hit(&mut self, address: MemoryAddress, write: bool) {
+        self.count_access(address, 4, true);
+        self.record_access(address, 4, write, true, None);
+        // 同じラインへの続くアクセスは影のキャッシュを変えないので、1回だけ反映する
+        self.classify(address, true, true);
+    }
+
+    /// ラインの offset から bytes を書き込む（Sharedのラインなら先に他のコアのラインを無効にする）
+    fn write_line(&mut self, set_index: usize, way_index: usize, offset: usize, bytes: &[u8]) {
+        let line = &self.sets[set_index].lines[way_index];
+        if line.shared {
+            let line_number = line_address(line.tag, set_index) / CACHE_LINE_SIZE as u32;
+            if let Some((core_id, bus)) = self.snoop_bus() {
+                bus.read_exclusive(core_id, line_number, true);
+            }
+        }
+        let write_back = self.config.write_policy == WritePolicy::WriteBack;
+        let line = &mut self.sets[set_index].lines[way_index];
+        line.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        line.dirty |= write_back;
+        line.shared = false;
+    }
+
+    /// 書き込んだ値をメモリにも反映する
+    /// ライトスルーでは1バイトずつ数え、コヒーレンスを保つライトバックでは他のコアがメモリから読めるように反映しておく（統計には含めない）
+    fn write_to_memory(&mut self, memory: &mut Memory, address: MemoryAddress, bytes: &[u8]) -> Result<(), MemoryError> {
+        if self.config.write_policy != WritePolicy::WriteBack {
+            for (i, &byte) in bytes.iter().enumerate() {
+                memory.write_byte(address + i as u32, byte)?;
+                self.count(|stats| stats.write_throughs += 1);
+                self.record_transfer(TransferKind::WriteThrough, 1);
+            }
+        } else if self.snoop_bus.is_some() {
+            for (i, &byte) in bytes.iter().enumerate() {
+                memory.write_byte(address + i as u32, byte)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// address から size バイトのヒット・ミスを1バイトずつのアクセスとして数える
+    /// （統計を分けて数える範囲に含まれていれば、含まれるバイト数をその範囲の統計にも数える）
+    fn count_access(&mut self, address: MemoryAddress, size: u32, hit: bool) {
+        let count = size as u64;
+        if !hit {
+            self.waits.0 += count;
+        }
+        if !self.stats_enabled {
+            return;
+        }
+        if hit {
+            self.stats.hits += count;
+        } else {
+            self.stats.misses += count;
+        }
+        if self.fetching {
+            if hit {
+                self.stats.fetch_hits += count;
+            } else {
+                self.stats.fetch_misses += count;
+            }
+        }
+        if self.config.stats_regions.is_empty() {
+            return;
+        }
+        // 統計をリセットした後も数えられるよう、範囲の数に合わせる
+        self.stats.regions.resize(self.config.stats_regions.len(), RegionStats::default());
+        let (start, end) = (address as u64, address as u64 + count);
+        for (region, stats) in self.config.stats_regions.iter().zip(&mut self.stats.regions) {
+            let inside = end.min(region.range.end as u64).saturating_sub(start.max(region.range.start as u64));
+            if hit {
+                stats.hits += inside;
+            } else {
+                stats.misses += inside;
+            }
+        }
+    }
+
+    /// 影のキャッシュにアクセスを反映し、ミスなら3Cに分類して数える（他のコアに無効にされたラインへのミスはコヒーレンスミスに数える）
+    fn classify(&mut self, address: MemoryAddress, hit: bool, allocate: bool) {
+        let line = address / CACHE_LINE_SIZE as u32;
+        let kind = self.classifier.as_mut().and_then(|classifier| classifier.access(line, !hit, allocate));
+        if !hit && !self.invalidated.is_empty() && self.invalidated.remove(&line) {
+            self.count(|stats| stats.coherence_misses += 1);
+            return;
+        }
+        self.count(|stats| match kind {
+            Some(MissKind::Compulsory) => stats.compulsory_misses += 1,
+            Some(MissKind::Capacity) => stats.capacity_misses += 1,
+            Some(MissKind::Conflict) => stats.conflict_misses += 1,
+            None => {}
+        });
+    }
+
+    /// トレースを有効にしていればアクセスを記録する
+    /// ハーフワードやラインをまたぐワードはバイト単位で処理するので、直前の記録に続くバイトは同じアクセスとしてまとめる
+    /// （記録は命令ごとに取り出すので、続くのは同じ命令の同じアクセスのバイトだけ）
+    fn record_access(&mut self, address: MemoryAddress, size: u32, write: bool, hit: bool, evicted: Option<MemoryAddress>) {
+        let fetch = self.fetching;
+        let Some(trace) = &mut self.trace else {
+            return;
+        };
+        if let Some(last) = trace.last_mut() {
+            if last.fetch == fetch && last.write == write && last.address.wrapping_add(last.size) == address {
+                last.size += size;
+                last.hit &= hit;
+                last.evicted = last.evicted.or(evicted);
+                return;
+            }
+        }
+        trace.push(CacheAccess { address, size, fetch, write, hit, evicted });
     }
 
     /// メモリからキャッシュラインを読み込む
@@ -179,83 +1010,198 @@ impl Cache {
         
         line.valid = true;
         line.dirty = false;
+        line.shared = false;
         line.tag = tag;
-        line.access_time = self.access_counter;
-        self.access_counter += 1;
+        self.replacement.insert(set_index, way_index);
+        self.record_transfer(TransferKind::Fill, CACHE_LINE_SIZE as u64);
         
         Ok(())
     }
+}
+
+impl CacheLike for Cache {
+    fn name(&self) -> &'static str {
+        "default"
+    }
+
+    /// キャッシュの設定を変更する（有効なラインはメモリに書き戻して捨てる）
+    fn set_config(&mut self, memory: &mut Memory, config: CacheConfig) -> Result<(), MemoryError> {
+        self.restore(memory, &Cache::new().snapshot())?;
+        self.replacement = config.replacement.build();
+        self.classifier = config.classify_misses.then(|| MissClassifier::new(CACHE_SETS * CACHE_WAYS));
+        self.invalidated.clear();
+        self.config = config;
+        Ok(())
+    }
+
+    /// キャッシュの設定を取得
+    fn config(&self) -> &CacheConfig {
+        &self.config
+    }
 
     /// バイトを読み込む
-    pub fn read_byte(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<u8, MemoryError> {
+    fn read_byte(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<u8, MemoryError> {
+        if self.config.bypass {
+            return memory.load_byte(address);
+        }
+        if self.is_line_uncached(memory, address) {
+            let value = memory.load_byte(address)?;
+            self.record_transfer(TransferKind::Uncached, 1);
+            return Ok(value);
+        }
         let set_index = self.get_set_index(address);
         let tag = self.get_tag(address);
         let offset = self.get_offset(address);
 
         if let Some(way_index) = self.find_line(set_index, tag) {
             // キャッシュヒット
-            self.stats.hits += 1;
+            self.count_access(address, 1, true);
+            self.classify(address, true, true);
+            self.record_access(address, 1, false, true, None);
             Ok(self.sets[set_index].lines[way_index].data[offset])
         } else {
             // キャッシュミス
-            self.stats.misses += 1;
-            let way_index = self.select_lru_line(set_index);
-            
-            // 既存のラインがダーティなら書き戻し
-            if self.sets[set_index].lines[way_index].valid && self.sets[set_index].lines[way_index].dirty {
-                self.writeback_line(memory, set_index, way_index)?;
-            }
-            
-            // メモリからラインを読み込み
-            self.load_line(memory, set_index, way_index, tag)?;
+            self.count_access(address, 1, false);
+            self.classify(address, false, true);
+            let (way_index, evicted) = self.allocate(memory, set_index, tag)?;
+            // 他のコアも持っていればShared、いなければExclusiveで読み込む
+            let shared = self.snoop_bus().is_some_and(|(core_id, bus)| bus.read(core_id, address / CACHE_LINE_SIZE as u32));
+            self.sets[set_index].lines[way_index].shared = shared;
+            self.record_access(address, 1, false, false, evicted);
             Ok(self.sets[set_index].lines[way_index].data[offset])
         }
     }
 
     /// バイトを書き込む
-    pub fn write_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError> {
+    fn write_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError> {
+        if self.config.bypass {
+            return memory.write_byte(address, value);
+        }
+        if self.is_line_uncached(memory, address) {
+            memory.write_byte(address, value)?;
+            self.record_transfer(TransferKind::Uncached, 1);
+            return Ok(());
+        }
         let set_index = self.get_set_index(address);
         let tag = self.get_tag(address);
         let offset = self.get_offset(address);
+        let policy = self.config.write_policy;
 
-        if let Some(way_index) = self.find_line(set_index, tag) {
+        let way_index = match self.find_line(set_index, tag) {
             // キャッシュヒット
-            self.stats.hits += 1;
-            self.sets[set_index].lines[way_index].data[offset] = value;
-            self.sets[set_index].lines[way_index].dirty = true;
-        } else {
-            // キャッシュミス
-            self.stats.misses += 1;
-            let way_index = self.select_lru_line(set_index);
-            
-            // 既存のラインがダーティなら書き戻し
-            if self.sets[set_index].lines[way_index].valid && self.sets[set_index].lines[way_index].dirty {
-                self.writeback_line(memory, set_index, way_index)?;
+            Some(way_index) => {
+                self.count_access(address, 1, true);
+                self.classify(address, true, true);
+                self.record_access(address, 1, true, true, None);
+                Some(way_index)
+            }
+            // キャッシュミス（ノーライトアロケートではラインを読み込まない）
+            None => {
+                self.count_access(address, 1, false);
+                self.classify(address, false, policy != WritePolicy::NoWriteAllocate);
+                if let Some((core_id, bus)) = self.snoop_bus() {
+                    bus.read_exclusive(core_id, address / CACHE_LINE_SIZE as u32, policy != WritePolicy::NoWriteAllocate);
+                }
+                if policy == WritePolicy::NoWriteAllocate {
+                    self.record_access(address, 1, true, false, None);
+                    None
+                } else {
+                    // ラインの残りのバイトを正しく保つため、書き込む前にメモリから読み込む
+                    let (way_index, evicted) = self.allocate(memory, set_index, tag)?;
+                    self.record_access(address, 1, true, false, evicted);
+                    Some(way_index)
+                }
             }
-            
-            // 新しいラインを初期化
-            let line = &mut self.sets[set_index].lines[way_index];
-            line.valid = true;
-            line.dirty = true;
-            line.tag = tag;
-            line.access_time = self.access_counter;
-            self.access_counter += 1;
-            
-            // データを書き込み
+        };
+
+        if let Some(way_index) = way_index {
+            self.write_line(set_index, way_index, offset, &[value]);
+        }
+        self.write_to_memory(memory, address, &[value])
+    }
+
+    /// 統計や置換方式の状態を変更せずにバイトを読み込む（デバッガ用）
+    /// キャッシュに載っていればキャッシュの内容を、なければメモリの内容を返す
+    fn peek_byte(&self, memory: &Memory, address: MemoryAddress) -> Result<u8, MemoryError> {
+        let set_index = self.get_set_index(address);
+        let tag = self.get_tag(address);
+        let offset = self.get_offset(address);
+        match self.sets[set_index].lines.iter().find(|line| line.valid && line.tag == tag) {
+            Some(line) => Ok(line.data[offset]),
+            None => memory.read_byte(address),
+        }
+    }
+
+    /// 統計や置換方式の状態を変更せずにバイトを書き込む（デバッガ用）
+    /// メモリとキャッシュ上のラインの両方を更新して一貫性を保つ
+    fn poke_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError> {
+        memory.write_byte(address, value)?;
+        let set_index = self.get_set_index(address);
+        let tag = self.get_tag(address);
+        let offset = self.get_offset(address);
+        let line = self.sets[set_index].lines.iter_mut().find(|line| line.valid && line.tag == tag);
+        let held = line.is_some();
+        if let Some(line) = line {
             line.data[offset] = value;
+            line.shared = false;
+        }
+        // 他のコアが古い内容を読まないように無効にする
+        if let Some((core_id, bus)) = self.snoop_bus() {
+            bus.read_exclusive(core_id, address / CACHE_LINE_SIZE as u32, held);
         }
-        
         Ok(())
     }
 
+    /// 有効なキャッシュラインの内容をバイト列に上書きする（デバッガ用）
+    /// bytes は start から始まるメモリの内容で、書き戻されていないデータを反映した値になる
+    fn overlay(&self, start: MemoryAddress, bytes: &mut [u8]) {
+        let start = start as usize;
+        let end = start + bytes.len();
+        for (set_index, set) in self.sets.iter().enumerate() {
+            for line in set.lines.iter().filter(|line| line.valid) {
+                let line_start = (line.tag as usize * CACHE_SETS + set_index) * CACHE_LINE_SIZE;
+                let line_end = line_start + CACHE_LINE_SIZE;
+                if line_end <= start || line_start >= end {
+                    continue;
+                }
+                let (from, to) = (line_start.max(start), line_end.min(end));
+                bytes[from - start..to - start].copy_from_slice(&line.data[from - line_start..to - line_start]);
+            }
+        }
+    }
+
+    /// 命令フェッチとしてワードを読み込む
+    fn fetch_word(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<Word, MemoryError> {
+        self.fetching = true;
+        let result = self.read_word(memory, address);
+        self.fetching = false;
+        result
+    }
+
     /// ワードを読み込む
-    pub fn read_word(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<Word, MemoryError> {
+    fn read_word(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<Word, MemoryError> {
+        if self.config.bypass {
+            return memory.load_word(address);
+        }
+        // デバイスへのアクセスはワードのままデバイスに渡す
+        if (0..4).any(|i| memory.is_device(address.wrapping_add(i))) {
+            let value = memory.load_word(address)?;
+            self.record_transfer(TransferKind::Uncached, 4);
+            return Ok(value);
+        }
         // 4バイトの境界チェック
         if (address as usize).saturating_add(3) >= memory.size() {
             return Err(MemoryError::AddressOutOfRange(address));
         }
         
-        // 4バイトを個別に読み込んでワードを構築
+        if let Some((set_index, way_index)) = self.find_word_line(memory, address) {
+            self.count_word_hit(address, false);
+            let offset = self.get_offset(address);
+            let data = &self.sets[set_index].lines[way_index].data[offset..offset + 4];
+            return Ok(Word::from_le_bytes(data.try_into().unwrap()));
+        }
+
+        // ラインをまたぐアクセスやミスは4バイトを個別に読み込んでワードを構築
         let b0 = self.read_byte(memory, address)? as u32;
         let b1 = self.read_byte(memory, address + 1)? as u32;
         let b2 = self.read_byte(memory, address + 2)? as u32;
@@ -265,13 +1211,28 @@ impl Cache {
     }
 
     /// ワードを書き込む
-    pub fn write_word(&mut self, memory: &mut Memory, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
+    fn write_word(&mut self, memory: &mut Memory, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
+        if self.config.bypass {
+            return memory.write_word(address, value);
+        }
+        // デバイスへのアクセスはワードのままデバイスに渡す
+        if (0..4).any(|i| memory.is_device(address.wrapping_add(i))) {
+            memory.write_word(address, value)?;
+            self.record_transfer(TransferKind::Uncached, 4);
+            return Ok(());
+        }
         // 4バイトの境界チェック
         if (address as usize).saturating_add(3) >= memory.size() {
             return Err(MemoryError::AddressOutOfRange(address));
         }
         
-        // ワードを4バイトに分解して個別に書き込み
+        if let Some((set_index, way_index)) = self.find_word_line(memory, address) {
+            self.count_word_hit(address, true);
+            self.write_line(set_index, way_index, self.get_offset(address), &value.to_le_bytes());
+            return self.write_to_memory(memory, address, &value.to_le_bytes());
+        }
+
+        // ラインをまたぐアクセスやミスはワードを4バイトに分解して個別に書き込み
         self.write_byte(memory, address, (value & 0xFF) as u8)?;
         self.write_byte(memory, address + 1, ((value >> 8) & 0xFF) as u8)?;
         self.write_byte(memory, address + 2, ((value >> 16) & 0xFF) as u8)?;
@@ -281,19 +1242,71 @@ impl Cache {
     }
 
     /// 統計情報を取得
-    pub fn get_stats(&self) -> &CacheStats {
+    fn get_stats(&self) -> &CacheStats {
         &self.stats
     }
 
+    /// 統計情報を置き換える（統計を止めている区間の増分を取り消すために使う）
+    fn set_stats(&mut self, stats: CacheStats) {
+        self.stats = stats;
+    }
+
     /// 統計情報をリセット
-    #[allow(dead_code)]
-    pub fn reset_stats(&mut self) {
+    fn reset_stats(&mut self) {
         self.stats = CacheStats::default();
+        self.bus.reset_stats();
+    }
+
+    /// バスの設定を変更（統計はリセットされる）
+    fn set_bus_config(&mut self, config: BusConfig) {
+        self.bus = BusMonitor::new(config);
+    }
+
+    /// バスの時間を進める
+    fn advance_bus(&mut self, time: u64) {
+        self.bus.advance(time);
+    }
+
+    /// バス統計を取得
+    fn get_bus_stats(&self) -> &BusStats {
+        self.bus.get_stats()
+    }
+
+    /// バス統計を置き換える（統計を止めている区間の増分を取り消すために使う）
+    fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats_enabled = enabled;
+    }
+
+    fn wait_counts(&self) -> (u64, u64) {
+        self.waits
+    }
+
+    /// セットの範囲に含まれるすべてのラインの状態を返す（セット順・ウェイ順、範囲外のセットは含めない）
+    fn dump(&self, sets: Range<usize>) -> Vec<CacheLineInfo> {
+        let sets = sets.start.min(CACHE_SETS)..sets.end.min(CACHE_SETS);
+        sets.flat_map(|set_index| {
+            // 古さは有効なラインの間での順位にする
+            let lines = &self.sets[set_index].lines;
+            let ages = self.replacement.ages(set_index).map(|ages| {
+                std::array::from_fn::<usize, CACHE_WAYS, _>(|way| {
+                    (0..CACHE_WAYS).filter(|&other| lines[other].valid && ages[other] < ages[way]).count()
+                })
+            });
+            lines.iter().enumerate().map(move |(way, line)| CacheLineInfo {
+                set: set_index,
+                way,
+                valid: line.valid,
+                dirty: line.dirty,
+                tag: line.tag,
+                address: line_address(line.tag, set_index),
+                age: ages.filter(|_| line.valid).map(|ages| ages[way]),
+            })
+        }).collect()
     }
 
     /// キャッシュをフラッシュ（全てのダーティラインをメモリに書き戻し）
-    #[allow(dead_code)]
-    pub fn flush(&mut self, memory: &mut Memory) -> Result<(), MemoryError> {
+    fn flush(&mut self, memory: &mut Memory) -> Result<(), MemoryError> {
+        let mut writebacks = 0;
         // set_indexを使用し、self.setsの再借用を避ける
         for (set_index, set) in self.sets.iter_mut().enumerate() {
             // set_indexをu32にキャスト
@@ -311,21 +1324,192 @@ impl Cache {
                     }
                     
                     line.dirty = false;
-                    self.stats.writebacks += 1;
+                    writebacks += 1;
+                }
+            }
+        }
+        for _ in 0..writebacks {
+            self.count_writeback();
+        }
+        Ok(())
+    }
+
+    /// キャッシュを無効化（全てのラインをダーティなものも書き戻さずに捨てる）
+    fn invalidate(&mut self) {
+        for set_index in 0..CACHE_SETS {
+            for way_index in 0..CACHE_WAYS {
+                let line = &mut self.sets[set_index].lines[way_index];
+                if !line.valid {
+                    continue;
+                }
+                line.valid = false;
+                line.dirty = false;
+                let address = line_address(line.tag, set_index);
+                if let Some((core_id, bus)) = self.snoop_bus() {
+                    bus.evict(core_id, address / CACHE_LINE_SIZE as u32);
+                }
+            }
+        }
+    }
+
+    /// 現在のキャッシュの状態を保存する
+    fn snapshot(&self) -> CacheSnapshot {
+        CacheSnapshot {
+            lines: self.sets.iter()
+                .flat_map(|set| set.lines.iter())
+                .map(|line| LineState { valid: line.valid, dirty: line.dirty, tag: line.tag })
+                .collect(),
+            replacement: self.replacement.name().to_string(),
+            replacement_state: self.replacement.state(),
+        }
+    }
+
+    /// スナップショットの状態に戻す
+    /// 現在のダーティラインはメモリに書き戻し、復元したラインのデータはメモリから読み直すので、
+    /// メモリの内容（アーキテクチャ状態）は変わらない。この転送は統計とバスの記録に含めない
+    /// 置換方式が異なるスナップショット（コールドな状態など）では、置換方式の状態を初期状態にする
+    fn restore(&mut self, memory: &mut Memory, snapshot: &CacheSnapshot) -> Result<(), MemoryError> {
+        for (set_index, set) in self.sets.iter_mut().enumerate() {
+            for line in set.lines.iter_mut().filter(|line| line.valid && line.dirty) {
+                let base_address = line_address(line.tag, set_index);
+                for (i, byte) in line.data.iter().enumerate() {
+                    memory.write_byte(base_address + i as u32, *byte)?;
+                }
+            }
+        }
+
+        for (index, state) in snapshot.lines.iter().enumerate() {
+            let set_index = index / CACHE_WAYS;
+            let line = &mut self.sets[set_index].lines[index % CACHE_WAYS];
+            line.valid = state.valid;
+            line.dirty = state.dirty;
+            line.tag = state.tag;
+            if state.valid {
+                let base_address = line_address(state.tag, set_index);
+                for (i, byte) in line.data.iter_mut().enumerate() {
+                    *byte = memory.read_byte(base_address + i as u32)?;
                 }
             }
         }
+        if snapshot.replacement != self.replacement.name() || !self.replacement.restore_state(&snapshot.replacement_state) {
+            self.replacement = self.config.replacement.build();
+        }
         Ok(())
     }
+
+    /// アクセスの記録を有効・無効にする
+    fn set_trace_enabled(&mut self, enabled: bool) {
+        self.trace = enabled.then(Vec::new);
+    }
+
+    /// 記録したアクセスを取り出す
+    fn take_trace(&mut self) -> Vec<CacheAccess> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn enable_coherence(&mut self, core_id: usize) {
+        self.core_id = core_id;
+    }
+
+    fn replace_snoop_bus(&mut self, bus: Option<SnoopBus>) -> Option<SnoopBus> {
+        std::mem::replace(&mut self.snoop_bus, bus)
+    }
+
+    fn apply_snoops(&mut self) {
+        let Some((core_id, bus)) = self.snoop_bus() else {
+            return;
+        };
+        for transaction in bus.take_pending(core_id) {
+            let (line_number, exclusive) = match transaction {
+                BusTransaction::Read(line) => (line, false),
+                BusTransaction::ReadExclusive(line) => (line, true),
+            };
+            let address = line_number * CACHE_LINE_SIZE as u32;
+            let set_index = self.get_set_index(address);
+            let tag = self.get_tag(address);
+            let Some(line) = self.sets[set_index].lines.iter_mut().find(|line| line.valid && line.tag == tag) else {
+                continue;
+            };
+            // Modifiedのラインは他のコアにデータを渡すために書き戻す（値はメモリに反映済み）
+            let dirty = std::mem::take(&mut line.dirty);
+            if exclusive {
+                line.valid = false;
+            } else {
+                line.shared = true;
+            }
+            if dirty {
+                self.count_writeback();
+            }
+            if exclusive {
+                self.count(|stats| stats.invalidations += 1);
+                self.invalidated.insert(line_number);
+            }
+        }
+    }
+}
+
+/// アドレスを含むキャッシュラインの範囲（終了は含まない。最後のラインでも溢れないようu64で返す）
+fn line_bounds(address: MemoryAddress) -> (u64, u64) {
+    let start = (address as u64) & !(CACHE_LINE_SIZE as u64 - 1);
+    (start, start + CACHE_LINE_SIZE as u64)
+}
+
+/// タグとセットインデックスからキャッシュラインの先頭アドレスを計算
+fn line_address(tag: u32, set_index: usize) -> MemoryAddress {
+    ((tag * CACHE_SETS as u32 + set_index as u32) * CACHE_LINE_SIZE as u32) as MemoryAddress
+}
+
+/// dumpで得たラインの状態を表形式に整形する（無効なラインは有効ビットだけを示す）
+pub fn format_cache_dump(lines: &[CacheLineInfo]) -> String {
+    let mut result = String::from("セット ウェイ 有効 ダーティ タグ       先頭アドレス 古さ\n");
+    for line in lines {
+        if !line.valid {
+            result.push_str(&format!("{:>6} {:>6} {:>4}\n", line.set, line.way, 0));
+            continue;
+        }
+        let age = line.age.map_or("-".to_string(), |age| age.to_string());
+        result.push_str(&format!("{:>6} {:>6} {:>4} {:>8} 0x{:08X} 0x{:08X}   {:>4}\n",
+            line.set, line.way, 1, line.dirty as u8, line.tag, line.address, age));
+    }
+    result
+}
+
+/// アドレス範囲ごとのヒット・ミス数を表形式に整形する
+pub fn format_region_stats(regions: &[StatsRegion], stats: &[RegionStats]) -> String {
+    // 見出しは全角文字の幅（半角2文字分）に合わせて詰める
+    let mut result = format!("  {:<10} {:<21} {:>8} {:>9} {:>6}", "名前", "範囲", "ヒット数", "ミス数", "ヒット率");
+    for (index, region) in regions.iter().enumerate() {
+        let stats = stats.get(index).copied().unwrap_or_default();
+        result.push_str(&format!("\n  {:<12} 0x{:08X}-0x{:08X} {:>12} {:>12} {:>9.2}%", region.name,
+            region.range.start, region.range.end, stats.hits, stats.misses, stats.hit_rate() * 100.0));
+    }
+    result
 }
 
 impl fmt::Display for CacheStats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "キャッシュ統計:\n")?;
-        write!(f, "  ヒット数: {}\n", self.hits)?;
-        write!(f, "  ミス数: {}\n", self.misses)?;
-        write!(f, "  ヒット率: {:.2}%\n", self.hit_rate() * 100.0)?;
-        write!(f, "  書き込みバック数: {}", self.writebacks)
+        writeln!(f, "キャッシュ統計:")?;
+        writeln!(f, "  ヒット数: {}", self.hits)?;
+        writeln!(f, "  ミス数: {}", self.misses)?;
+        writeln!(f, "  ヒット率: {:.2}%", self.hit_rate() * 100.0)?;
+        writeln!(f, "  ヒット率（命令フェッチ / データ）: {:.2}% / {:.2}%",
+            self.fetch().hit_rate() * 100.0, self.data().hit_rate() * 100.0)?;
+        writeln!(f, "  書き込みバック数: {}", self.writebacks)?;
+        write!(f, "  ライトスルー数: {}", self.write_throughs)?;
+        let share = |count: u64| if self.misses == 0 { 0.0 } else { count as f64 / self.misses as f64 * 100.0 };
+        // 3Cの内訳は分類を有効にしたときだけ数える
+        if self.compulsory_misses + self.capacity_misses + self.conflict_misses > 0 {
+            write!(f, "\n  ミスの内訳: 初期参照 {} ({:.2}%) / 容量 {} ({:.2}%) / 競合 {} ({:.2}%)",
+                self.compulsory_misses, share(self.compulsory_misses),
+                self.capacity_misses, share(self.capacity_misses),
+                self.conflict_misses, share(self.conflict_misses))?;
+        }
+        // コヒーレンスの統計はマルチコア実行でだけ増える
+        if self.invalidations > 0 || self.coherence_misses > 0 {
+            write!(f, "\n  コヒーレンスミス: {} ({:.2}%)\n  無効化されたライン数: {}",
+                self.coherence_misses, share(self.coherence_misses), self.invalidations)?;
+        }
+        Ok(())
     }
 }
 
@@ -343,6 +1527,11 @@ mod tests {
 
     // テスト用のMemory構造体は既にcrate::memoryで定義されているため削除
 
+    /// アセンブリの1行を命令のワードにする
+    fn asm(line: &str) -> crate::instructions::Instruction {
+        crate::assembler::parse_instruction(line).unwrap().encode().unwrap()
+    }
+
 
     #[test]
     fn test_cache_read_write() {
@@ -381,4 +1570,413 @@ mod tests {
         assert_eq!(stats.misses, 1);
         assert_eq!(stats.hits, 2);
     }
+
+    #[test]
+    fn test_bus_transfers() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+
+        // ミスで1ライン分のフィルが発生し、ヒットでは転送は発生しない
+        cache.read_word(&mut memory, 0x1000).unwrap();
+        cache.read_word(&mut memory, 0x1004).unwrap();
+        assert_eq!(cache.get_bus_stats().fill_bytes, CACHE_LINE_SIZE as u64);
+
+        // ダーティラインのフラッシュで1ライン分の書き戻しが発生する
+        cache.write_word(&mut memory, 0x1000, 1).unwrap();
+        cache.flush(&mut memory).unwrap();
+        assert_eq!(cache.get_bus_stats().writeback_bytes, CACHE_LINE_SIZE as u64);
+    }
+
+    #[test]
+    fn test_write_policies() {
+        let cache_with = |write_policy| {
+            let mut memory = Memory::new();
+            memory.write_byte(0x1004, 0x77).unwrap();
+            let mut cache = Cache::new();
+            cache.set_config(&mut memory, CacheConfig { write_policy, ..CacheConfig::default() }).unwrap();
+            cache.write_word(&mut memory, 0x1000, 0x12345678).unwrap();
+            (memory, cache)
+        };
+
+        // ライトバック: 書き込みミスでラインを読み込み、メモリは追い出すまで更新しない
+        let (mut memory, mut cache) = cache_with(WritePolicy::WriteBack);
+        assert_eq!(memory.read_word(0x1000).unwrap(), 0);
+        assert_eq!(cache.read_byte(&mut memory, 0x1004).unwrap(), 0x77);
+        cache.flush(&mut memory).unwrap();
+        assert_eq!((cache.get_stats().writebacks, cache.get_stats().write_throughs), (1, 0));
+        assert_eq!(memory.read_word(0x1000).unwrap(), 0x12345678);
+
+        // ライトスルー: メモリもすぐに更新し、ラインはダーティにならない
+        let (mut memory, mut cache) = cache_with(WritePolicy::WriteThrough);
+        assert_eq!(memory.read_word(0x1000).unwrap(), 0x12345678);
+        assert_eq!(cache.read_byte(&mut memory, 0x1004).unwrap(), 0x77);
+        cache.flush(&mut memory).unwrap();
+        assert_eq!((cache.get_stats().writebacks, cache.get_stats().write_throughs), (0, 4));
+        assert_eq!((cache.get_stats().misses, cache.get_stats().hits), (1, 4));
+        assert_eq!(cache.get_bus_stats().write_through_bytes, 4);
+
+        // ノーライトアロケート: 書き込みミスではラインを読み込まない
+        let (mut memory, mut cache) = cache_with(WritePolicy::NoWriteAllocate);
+        assert_eq!(memory.read_word(0x1000).unwrap(), 0x12345678);
+        assert_eq!((cache.get_stats().misses, cache.get_bus_stats().fill_bytes), (4, 0));
+        assert_eq!(cache.read_word(&mut memory, 0x1000).unwrap(), 0x12345678);
+        assert_eq!(cache.get_stats().misses, 5);
+    }
+
+    #[test]
+    fn test_three_c_misses() {
+        // 同じセットに入る5つのラインを2周読むと、2周目は完全連想キャッシュならヒットするので競合ミス
+        let stride = (CACHE_SETS * CACHE_LINE_SIZE) as MemoryAddress;
+        let mut memory = Memory::new();
+        let config = CacheConfig { classify_misses: true, ..CacheConfig::default() };
+        let mut cache = Cache::new();
+        cache.set_config(&mut memory, config.clone()).unwrap();
+        for _ in 0..2 {
+            for line in 0..5 {
+                cache.read_word(&mut memory, line * stride).unwrap();
+            }
+        }
+        let stats = cache.get_stats();
+        assert_eq!((stats.compulsory_misses, stats.capacity_misses, stats.conflict_misses), (5, 0, 5));
+
+        // 分類を有効にしなければ内訳は数えない
+        let mut cache = Cache::new();
+        cache.read_word(&mut memory, 0).unwrap();
+        let stats = cache.get_stats();
+        assert_eq!((stats.misses, stats.compulsory_misses), (1, 0));
+
+        // 全セットを埋めてもあふれる数のラインを2周読むと、2周目は容量ミス
+        let mut cache = Cache::new();
+        cache.set_config(&mut memory, config).unwrap();
+        let lines = (CACHE_SETS * CACHE_WAYS + CACHE_SETS) as MemoryAddress;
+        for _ in 0..2 {
+            for line in 0..lines {
+                cache.read_byte(&mut memory, line * CACHE_LINE_SIZE as MemoryAddress).unwrap();
+            }
+        }
+        let stats = cache.get_stats();
+        assert_eq!((stats.compulsory_misses, stats.capacity_misses, stats.conflict_misses), (lines as u64, lines as u64, 0));
+    }
+
+    #[test]
+    fn test_cache_dump() {
+        let stride = (CACHE_SETS * CACHE_LINE_SIZE) as MemoryAddress;
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+        cache.read_byte(&mut memory, 0x20).unwrap();
+        cache.read_byte(&mut memory, 0x20 + stride).unwrap();
+        cache.write_byte(&mut memory, 0x20, 1).unwrap();
+
+        let lines = cache.dump(1..2);
+        assert_eq!(lines.len(), CACHE_WAYS);
+        assert_eq!(lines[0], CacheLineInfo { set: 1, way: 0, valid: true, dirty: true, tag: 0, address: 0x20, age: Some(0) });
+        assert_eq!((lines[1].tag, lines[1].address, lines[1].dirty, lines[1].age), (1, 0x20 + stride, false, Some(1)));
+        assert_eq!((lines[2].valid, lines[2].age), (false, None));
+        assert!(format_cache_dump(&lines).contains("     1      0    1        1 0x00000000 0x00000020      0"));
+        assert_eq!(cache.dump(CACHE_SETS - 1..CACHE_SETS + 5).len(), CACHE_WAYS);
+
+        // 擬似LRUは順位を持たない
+        cache.set_config(&mut memory, CacheConfig { replacement: ReplacementKind::TreePlru, ..CacheConfig::default() }).unwrap();
+        cache.read_byte(&mut memory, 0x20).unwrap();
+        assert_eq!(cache.dump(1..2)[0].age, None);
+    }
+
+    #[test]
+    fn test_uncached_regions() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+        let config = CacheConfig { uncached_regions: vec![0x1000..0x1010, 0x2000..0x2004], ..CacheConfig::default() };
+        cache.set_config(&mut memory, config).unwrap();
+        cache.write_word(&mut memory, 0x1000, 0xCAFEBABE).unwrap();
+        // 書き込みはすぐにメモリに届き、キャッシュにもその統計にも残らない
+        assert_eq!(memory.read_word(0x1000).unwrap(), 0xCAFEBABE);
+        memory.write_byte(0x1004, 0x5A).unwrap();
+        assert_eq!(cache.read_byte(&mut memory, 0x1004).unwrap(), 0x5A);
+        assert_eq!((cache.get_stats().hits, cache.get_stats().misses), (0, 0));
+        assert_eq!(cache.dump(0..CACHE_SETS).iter().filter(|line| line.valid).count(), 0);
+        cache.write_byte(&mut memory, 0x2003, 0x11).unwrap();
+        assert_eq!(memory.read_byte(0x2003).unwrap(), 0x11);
+        assert_eq!(cache.get_bus_stats().uncached_bytes, 6);
+        // 領域にかかるラインの残りもキャッシュを経由せず、領域にかからないラインはキャッシュを経由する
+        cache.read_byte(&mut memory, 0x1018).unwrap();
+        assert_eq!(cache.get_stats().misses, 0);
+        cache.read_byte(&mut memory, 0x1020).unwrap();
+        assert_eq!(cache.get_stats().misses, 1);
+    }
+
+    #[test]
+    fn test_word_access_matches_bytes() {
+        // ワード単位の読み書きは、1バイトずつ読み書きした場合と同じ値・統計・トレースになる
+        for write_policy in [WritePolicy::WriteBack, WritePolicy::WriteThrough] {
+            let config = CacheConfig { write_policy, classify_misses: true, ..CacheConfig::default() };
+            let (mut word_memory, mut byte_memory) = (Memory::new(), Memory::new());
+            let (mut word_cache, mut byte_cache) = (Cache::new(), Cache::new());
+            for (cache, memory) in [(&mut word_cache, &mut word_memory), (&mut byte_cache, &mut byte_memory)] {
+                cache.set_config(memory, config.clone()).unwrap();
+                cache.set_trace_enabled(true);
+            }
+            // ミス・整列したヒット・ライン内の整列していないヒット・ラインをまたぐアクセス
+            for address in [0x100, 0x104, 0x109, 0x11E, 0x11E] {
+                word_cache.write_word(&mut word_memory, address, 0x11223344 + address).unwrap();
+                for (i, byte) in (0x11223344 + address).to_le_bytes().into_iter().enumerate() {
+                    byte_cache.write_byte(&mut byte_memory, address + i as u32, byte).unwrap();
+                }
+                let word = word_cache.read_word(&mut word_memory, address).unwrap();
+                let bytes: Vec<u8> = (0..4).map(|i| byte_cache.read_byte(&mut byte_memory, address + i).unwrap()).collect();
+                assert_eq!(word.to_le_bytes().to_vec(), bytes);
+            }
+            let (word_stats, byte_stats) = (word_cache.get_stats(), byte_cache.get_stats());
+            assert_eq!((word_stats.hits, word_stats.misses, word_stats.write_throughs, word_stats.compulsory_misses),
+                (byte_stats.hits, byte_stats.misses, byte_stats.write_throughs, byte_stats.compulsory_misses));
+            assert_eq!(word_cache.get_bus_stats().busy_cycles, byte_cache.get_bus_stats().busy_cycles);
+            assert_eq!(word_cache.take_trace(), byte_cache.take_trace());
+            word_cache.flush(&mut word_memory).unwrap();
+            byte_cache.flush(&mut byte_memory).unwrap();
+            assert_eq!(word_memory.read_word(0x11E).unwrap(), byte_memory.read_word(0x11E).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_trace_one_record_per_access() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+        cache.set_trace_enabled(true);
+        // ワードのミス・命令フェッチのヒット・ラインをまたぐワードは、それぞれ1回のアクセスとして記録する
+        cache.read_word(&mut memory, 0x200).unwrap();
+        assert_eq!(cache.take_trace(), [CacheAccess { address: 0x200, size: 4, fetch: false, write: false, hit: false, evicted: None }]);
+        cache.fetch_word(&mut memory, 0x204).unwrap();
+        assert_eq!(cache.take_trace(), [CacheAccess { address: 0x204, size: 4, fetch: true, write: false, hit: true, evicted: None }]);
+        cache.write_word(&mut memory, 0x21E, 1).unwrap();
+        assert_eq!(cache.take_trace(), [CacheAccess { address: 0x21E, size: 4, fetch: false, write: true, hit: false, evicted: None }]);
+        // 命令フェッチとデータアクセスは続いていても分けて記録する
+        cache.fetch_word(&mut memory, 0x208).unwrap();
+        cache.read_word(&mut memory, 0x20C).unwrap();
+        assert_eq!(cache.take_trace().iter().map(|access| (access.fetch, access.size)).collect::<Vec<_>>(), [(true, 4), (false, 4)]);
+    }
+
+    #[test]
+    fn test_region_stats() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+        let regions = vec![
+            StatsRegion { name: "stack".to_string(), range: 0x1000..0x2000 },
+            StatsRegion { name: "all".to_string(), range: 0..0x4000 },
+        ];
+        cache.set_config(&mut memory, CacheConfig { stats_regions: regions.clone(), ..CacheConfig::default() }).unwrap();
+        cache.read_word(&mut memory, 0x1000).unwrap();
+        cache.write_byte(&mut memory, 0x3000, 1).unwrap();
+        assert_eq!(cache.get_stats().regions, vec![
+            RegionStats { hits: 3, misses: 1 },
+            RegionStats { hits: 3, misses: 2 },
+        ]);
+        // 統計をリセットしても数え直せる
+        cache.reset_stats();
+        cache.read_byte(&mut memory, 0x3000).unwrap();
+        assert_eq!(cache.get_stats().regions, vec![RegionStats::default(), RegionStats { hits: 1, misses: 0 }]);
+        assert!(format_region_stats(&regions, &cache.get_stats().regions).contains("0x00000000-0x00004000"));
+    }
+
+    #[test]
+    fn test_bypass() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+        cache.write_word(&mut memory, 0x100, 0x11223344).unwrap();
+        cache.set_config(&mut memory, CacheConfig { bypass: true, ..CacheConfig::default() }).unwrap();
+        // 設定を変える前のダーティラインは書き戻される
+        assert_eq!(memory.read_word(0x100).unwrap(), 0x11223344);
+        let stats = cache.get_stats().clone();
+        cache.write_word(&mut memory, 0x200, 0xAABBCCDD).unwrap();
+        cache.write_byte(&mut memory, 0x204, 0x55).unwrap();
+        assert_eq!(memory.read_word(0x200).unwrap(), 0xAABBCCDD);
+        assert_eq!(cache.read_byte(&mut memory, 0x204).unwrap(), 0x55);
+        assert_eq!(cache.read_word(&mut memory, 0x100).unwrap(), 0x11223344);
+        assert_eq!((cache.get_stats().hits, cache.get_stats().misses), (stats.hits, stats.misses));
+        assert!(cache.dump(0..CACHE_SETS).iter().all(|line| !line.valid));
+        assert_eq!(cache.config().to_string(), "なし（メモリに直接アクセス）");
+    }
+
+    /// メモリに直接アクセスし、すべてのアクセスをヒットとして数えるモデル
+    #[derive(Debug)]
+    struct CountingModel {
+        config: CacheConfig,
+        stats: CacheStats,
+        bus: BusMonitor,
+    }
+
+    impl CacheLike for CountingModel {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn set_config(&mut self, _memory: &mut Memory, config: CacheConfig) -> Result<(), MemoryError> {
+            self.config = config;
+            Ok(())
+        }
+
+        fn config(&self) -> &CacheConfig {
+            &self.config
+        }
+
+        fn read_byte(&mut self, memory: &mut Memory, address: MemoryAddress) -> Result<u8, MemoryError> {
+            self.stats.hits += 1;
+            memory.read_byte(address)
+        }
+
+        fn write_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError> {
+            self.stats.hits += 1;
+            memory.write_byte(address, value)
+        }
+
+        fn peek_byte(&self, memory: &Memory, address: MemoryAddress) -> Result<u8, MemoryError> {
+            memory.read_byte(address)
+        }
+
+        fn poke_byte(&mut self, memory: &mut Memory, address: MemoryAddress, value: u8) -> Result<(), MemoryError> {
+            memory.write_byte(address, value)
+        }
+
+        fn get_stats(&self) -> &CacheStats {
+            &self.stats
+        }
+
+        fn set_stats(&mut self, stats: CacheStats) {
+            self.stats = stats;
+        }
+
+        fn set_bus_config(&mut self, config: BusConfig) {
+            self.bus = BusMonitor::new(config);
+        }
+
+        fn advance_bus(&mut self, time: u64) {
+            self.bus.advance(time);
+        }
+
+        fn get_bus_stats(&self) -> &BusStats {
+            self.bus.get_stats()
+        }
+
+        fn flush(&mut self, _memory: &mut Memory) -> Result<(), MemoryError> {
+            Ok(())
+        }
+
+        fn snapshot(&self) -> CacheSnapshot {
+            Cache::new().snapshot()
+        }
+
+        fn restore(&mut self, _memory: &mut Memory, _snapshot: &CacheSnapshot) -> Result<(), MemoryError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_cache_model() {
+        use crate::processor::{Processor, RunResult, PC_INITIAL};
+
+        // PC_INITIAL にプログラムを置くため、既定の1MBより大きなメモリにする
+        let mut processor = Processor::with_memory_size(16 * 1024 * 1024);
+        processor.poke_word(0x100, 5).unwrap();
+        processor.set_cache_model(Box::new(CountingModel {
+            config: CacheConfig::default(),
+            stats: CacheStats::default(),
+            bus: BusMonitor::new(BusConfig::default()),
+        }));
+        processor.load_program(&[asm("lw $t0, 0x100($zero)"), asm("sw $t0, 0x104($zero)"), asm("break")], PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.peek_word(0x104), Ok(5));
+        assert_eq!(processor.cache_model().name(), "counting");
+        // 3命令のフェッチとロード・ストアをワードの既定の実装で1バイトずつ数える
+        assert_eq!((processor.get_cache_stats().hits, processor.get_cache_stats().misses), (20, 0));
+    }
+
+    #[test]
+    fn test_replacement_policies() {
+        // 同じセットに入る5つのライン（0〜3を読み込み、0にヒットしてから4を読み込む）
+        let stride = (CACHE_SETS * CACHE_LINE_SIZE) as MemoryAddress;
+        let evicted = |replacement| {
+            let mut memory = Memory::new();
+            let mut cache = Cache::new();
+            cache.set_config(&mut memory, CacheConfig { replacement, ..CacheConfig::default() }).unwrap();
+            for line in [0, 1, 2, 3, 0, 4] {
+                cache.read_byte(&mut memory, line * stride).unwrap();
+            }
+            assert_eq!(cache.get_stats().misses, 5);
+            (0..4).find(|&line| !cache.sets[0].lines.iter().any(|l| l.valid && l.tag == cache.get_tag(line * stride)))
+        };
+        assert_eq!(evicted(ReplacementKind::Lru), Some(1));
+        assert_eq!(evicted(ReplacementKind::Fifo), Some(0));
+        // 0へのヒットで根が右を指し、右の部分木では先に読み込んだ2が追い出される
+        assert_eq!(evicted(ReplacementKind::TreePlru), Some(2));
+        assert_eq!(evicted(ReplacementKind::Random { seed: 7 }), evicted(ReplacementKind::Random { seed: 7 }));
+
+        assert_eq!(ReplacementKind::parse("random:42"), Ok(ReplacementKind::Random { seed: 42 }));
+        assert!(ReplacementKind::parse("mru").is_err());
+
+        // 置換方式の状態もスナップショットで戻る
+        let mut plru = TreePlru::default();
+        for way in 0..CACHE_WAYS {
+            plru.insert(3, way);
+        }
+        let saved = plru.state();
+        plru.touch(3, 0);
+        assert_eq!(plru.victim(3), 2);
+        assert!(plru.restore_state(&saved));
+        assert_eq!(plru.victim(3), 0);
+        assert!(!plru.restore_state(&[0]));
+    }
+
+    #[test]
+    fn test_peek_poke_coherence() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+
+        // キャッシュ上のダーティな値がpeekで見え、pokeがキャッシュ経由の読み込みに反映される
+        cache.write_byte(&mut memory, 0x2000, 0x11).unwrap();
+        assert_eq!(cache.peek_byte(&memory, 0x2000).unwrap(), 0x11);
+        cache.poke_byte(&mut memory, 0x2000, 0x22).unwrap();
+        assert_eq!(cache.read_byte(&mut memory, 0x2000).unwrap(), 0x22);
+
+        // peek/pokeは統計に影響しない
+        let accesses = cache.get_stats().hits + cache.get_stats().misses;
+        cache.peek_byte(&memory, 0x3000).unwrap();
+        cache.poke_byte(&mut memory, 0x3000, 0x33).unwrap();
+        assert_eq!(cache.get_stats().hits + cache.get_stats().misses, accesses);
+    }
+
+    #[test]
+    fn test_overlay_dirty_line() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+
+        // 書き戻し前の値はメモリにはなく、overlayで見えるようになる
+        cache.write_word(&mut memory, 0x201E, 0xCAFEBABE).unwrap();
+        let mut bytes = memory.read_range(0x2018, 16).unwrap();
+        assert_eq!(bytes[6..10], [0, 0, 0, 0]);
+        cache.overlay(0x2018, &mut bytes);
+        assert_eq!(bytes[6..10], 0xCAFEBABEu32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_snapshot_warm_and_cold() {
+        let mut memory = Memory::new();
+        let mut cache = Cache::new();
+        let cold = cache.snapshot();
+
+        cache.read_word(&mut memory, 0x1000).unwrap();
+        let warm = cache.snapshot();
+        assert_eq!(warm.valid_lines(), 1);
+
+        // コールドに戻しても書き戻されていない値はメモリに残り、統計も変わらない
+        cache.write_word(&mut memory, 0x1000, 0x12345678).unwrap();
+        cache.restore(&mut memory, &cold).unwrap();
+        assert_eq!(memory.read_word(0x1000).unwrap(), 0x12345678);
+        assert_eq!(cache.get_stats().writebacks, 0);
+        cache.read_word(&mut memory, 0x1000).unwrap();
+        assert_eq!(cache.get_stats().misses, 2);
+
+        // ウォームに戻すとヒットし、データは現在のメモリの内容になる
+        cache.restore(&mut memory, &cold).unwrap();
+        cache.restore(&mut memory, &warm).unwrap();
+        assert_eq!(cache.read_word(&mut memory, 0x1000).unwrap(), 0x12345678);
+        assert_eq!(cache.get_stats().misses, 2);
+    }
 }
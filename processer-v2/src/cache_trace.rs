@@ -0,0 +1,89 @@
+//! キャッシュアクセスのトレース（オフラインでの解析や、RTLシミュレーションのキャッシュの振る舞いとの比較のため）
+//!
+//! 命令フェッチやロード・ストアの1回を1行にしたCSVで、列は `cycle,pc,kind,address,size,rw,hit,evicted`。
+//! cycle はその命令を始めた時点の見積もりサイクル数、pc・address・evicted は0xを付けない8桁の16進数、
+//! kind は i（命令フェッチ）か d（データアクセス）、size はバイト数、rw は r（読み込み）か w（書き込み）、
+//! hit は1（ヒット）か0（ミス）、evicted は有効なラインを追い出した場合だけ書く。
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::cache::CacheAccess;
+use crate::memory::MemoryAddress;
+
+/// CSVの見出し行
+const CACHE_TRACE_HEADER: &str = "cycle,pc,kind,address,size,rw,hit,evicted";
+
+/// キャッシュアクセスのトレースの書き出し
+#[derive(Debug)]
+pub struct CacheTraceWriter {
+    path: String,
+    writer: BufWriter<File>,
+    /// 書き出したアクセス数
+    records: u64,
+    /// 最初に起きた書き込みエラー（実行は止めず、書き出し終えるときに報告する）
+    error: Option<String>,
+}
+
+impl CacheTraceWriter {
+    /// ファイルを作成し、見出し行を書く
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("キャッシュのトレース '{}' を作成できません: {}", path, e))?;
+        let mut trace = Self { path: path.to_string(), writer: BufWriter::new(file), records: 0, error: None };
+        trace.write_line(CACHE_TRACE_HEADER);
+        Ok(trace)
+    }
+
+    /// 1命令の間のアクセスを書き出す
+    pub fn write(&mut self, cycle: u64, pc: MemoryAddress, accesses: &[CacheAccess]) {
+        for access in accesses {
+            let evicted = access.evicted.map(|address| format!("{:08x}", address)).unwrap_or_default();
+            let line = format!("{},{:08x},{},{:08x},{},{},{},{}",
+                cycle, pc, if access.fetch { 'i' } else { 'd' }, access.address, access.size,
+                if access.write { 'w' } else { 'r' }, access.hit as u8, evicted);
+            self.write_line(&line);
+            self.records += 1;
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.writer, "{}", line) {
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// 書き出し終え、書き出したアクセス数を返す
+    pub fn finish(&mut self) -> Result<u64, String> {
+        if let Err(e) = self.writer.flush() {
+            self.error.get_or_insert(e.to_string());
+        }
+        match &self.error {
+            Some(e) => Err(format!("キャッシュのトレース '{}' に書き込めません: {}", self.path, e)),
+            None => Ok(self.records),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_trace_csv() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_cache_trace_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut trace = CacheTraceWriter::create(path).unwrap();
+        trace.write(12, 0x00400004, &[
+            CacheAccess { address: 0x00400004, size: 4, fetch: true, write: false, hit: true, evicted: None },
+            CacheAccess { address: 0x1000, size: 4, fetch: false, write: false, hit: false, evicted: Some(0x0800) },
+            CacheAccess { address: 0x1006, size: 2, fetch: false, write: true, hit: true, evicted: None },
+        ]);
+        assert_eq!(trace.finish(), Ok(3));
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(text, "cycle,pc,kind,address,size,rw,hit,evicted\n12,00400004,i,00400004,4,r,1,\n\
+            12,00400004,d,00001000,4,r,0,00000800\n12,00400004,d,00001006,2,w,1,\n");
+    }
+}
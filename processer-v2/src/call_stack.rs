@@ -0,0 +1,117 @@
+//! 呼び出し・復帰の追跡によるシャドウコールスタック（エラー時のバックトレースと関数プロファイラで共有する）
+//!
+//! ゲストのスタックは読まず、呼び出し命令の戻りアドレスを積み、その戻りアドレスへの復帰で降ろす。
+//! 対応する呼び出しのない復帰は無視し、深すぎる再帰では古い呼び出しから捨てる。
+//! 呼び出しの判定には命令のデコードが要るので、どちらも使わなければプロセッサは追跡しない。
+
+use std::collections::VecDeque;
+
+use crate::memory::MemoryAddress;
+use crate::profile::CallEvent;
+
+/// 保持する呼び出しの深さの上限
+const CALL_STACK_LIMIT: usize = 4096;
+
+/// 呼び出し中の関数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallFrame {
+    /// 関数の先頭アドレス（呼び出し先）
+    pub entry: MemoryAddress,
+    /// 戻りアドレス
+    pub return_address: MemoryAddress,
+    /// 呼び出した時点の関数プロファイラの命令数とサイクル数（累積を求めるのに使う）
+    pub counts: (u64, u64),
+}
+
+/// シャドウコールスタック
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    /// 呼び出し中の関数（末尾が最も新しい）
+    frames: VecDeque<CallFrame>,
+    /// 上限を超えて捨てた呼び出しの数
+    dropped: usize,
+}
+
+impl CallStack {
+    /// 命令の実行後に呼び出し・復帰を反映し、復帰で降ろした呼び出しを古い順に返す
+    /// （next_pcは実行後のPC、countsは呼び出しに記録する関数プロファイラの命令数とサイクル数）
+    pub fn update(&mut self, event: Option<CallEvent>, next_pc: MemoryAddress, counts: (u64, u64)) -> Vec<CallFrame> {
+        match event {
+            Some(CallEvent::Call { return_address }) => {
+                if self.frames.len() == CALL_STACK_LIMIT {
+                    self.frames.pop_front();
+                    self.dropped += 1;
+                }
+                self.frames.push_back(CallFrame { entry: next_pc, return_address, counts });
+                Vec::new()
+            }
+            Some(CallEvent::Return) => match self.frames.iter().rposition(|frame| frame.return_address == next_pc) {
+                Some(depth) => self.frames.drain(depth..).collect(),
+                None => Vec::new(),
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// 最も新しい呼び出し
+    pub fn current(&self) -> Option<&CallFrame> {
+        self.frames.back()
+    }
+
+    /// 呼び出しの記録を消去する
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.dropped = 0;
+    }
+
+    /// 呼び出し中の関数（古い順）
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &CallFrame> {
+        self.frames.iter()
+    }
+
+    /// pcで止まったときのバックトレース（新しい順、アドレスの表示方法は呼び出し側が決める）
+    pub fn backtrace<F: Fn(MemoryAddress) -> String>(&self, pc: MemoryAddress, format_address: F) -> String {
+        let mut result = format!("バックトレース（新しい順）:\n  #0 {}", format_address(pc));
+        for (i, frame) in self.frames().rev().enumerate() {
+            result.push_str(&format!("\n  #{} {} （{} からの戻り先）",
+                i + 1, format_address(frame.return_address), format_address(frame.entry)));
+        }
+        if self.dropped > 0 {
+            result.push_str(&format!("\n  ... さらに古い呼び出し {} 件は記録していません", self.dropped));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backtrace() {
+        let call = |return_address| Some(CallEvent::Call { return_address });
+        let mut stack = CallStack::default();
+        stack.update(call(0x104), 0x200, (1, 1));
+        stack.update(call(0x208), 0x300, (2, 2));
+        stack.update(None, 0x304, (3, 3));
+        // 対応する呼び出しのない復帰は無視する
+        assert!(stack.update(Some(CallEvent::Return), 0x999, (4, 4)).is_empty());
+        assert_eq!(stack.frames().count(), 2);
+        assert_eq!(stack.current().map(|frame| frame.counts), Some((2, 2)));
+
+        let format = |address| format!("0x{:X}", address);
+        assert_eq!(stack.backtrace(0x304, format),
+            "バックトレース（新しい順）:\n  #0 0x304\n  #1 0x208 （0x300 からの戻り先）\n  #2 0x104 （0x200 からの戻り先）");
+
+        // 外側の呼び出しへの復帰は内側の呼び出しもまとめて降ろす
+        let returned = stack.update(Some(CallEvent::Return), 0x104, (5, 5));
+        assert_eq!(returned.iter().map(|frame| frame.entry).collect::<Vec<_>>(), [0x200, 0x300]);
+        assert_eq!(stack.frames().count(), 0);
+
+        for i in 0..CALL_STACK_LIMIT as u32 + 2 {
+            stack.update(call(i * 4), 0x400, (0, 0));
+        }
+        assert_eq!(stack.frames().next().unwrap().return_address, 8);
+        assert!(stack.backtrace(0, format).ends_with("さらに古い呼び出し 2 件は記録していません"));
+    }
+}
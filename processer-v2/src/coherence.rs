@@ -0,0 +1,104 @@
+//! マルチコアのキャッシュコヒーレンス（MESIプロトコルとスヌープバス）
+//!
+//! ラインの状態は有効・ダーティ・共有の各ビットで表す（Modified = ダーティ、Exclusive = 共有でもダーティでもない、
+//! Shared = 共有）。コアは1つずつ順に実行するので、スヌープバスは共有メモリと一緒に実行中のコアのキャッシュが持ち、
+//! バスに出したトランザクションは他のコアごとの待ち行列に積む。各コアは自分の実行を始めるときにまとめて反映する
+//! （反映するまでそのコアは実行されないので、即座に反映した場合と結果は変わらない）。
+//! 書き込んだ値は共有メモリにもすぐ反映しておき（統計やバスの記録には含めない）、
+//! Modifiedのラインを持つコアがデータを供給する転送はメモリからの読み込みで代える。
+
+use std::collections::HashMap;
+
+/// 他のコアから見えるバスのトランザクション（対象はラインの番号）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusTransaction {
+    /// 読み込みミス（BusRd）。ModifiedとExclusiveのラインはSharedになる
+    Read(u32),
+    /// 書き込みのための読み込みミスか、Sharedのラインへの書き込み（BusRdX・BusUpgr）。ラインは無効になる
+    ReadExclusive(u32),
+}
+
+/// コア間で共有するスヌープバス
+#[derive(Debug, Clone)]
+pub struct SnoopBus {
+    /// ラインを持っている可能性のあるコア（追い出しを待たずに無効にしたラインは含めない）
+    holders: HashMap<u32, Vec<usize>>,
+    /// コアごとのまだ反映していないトランザクション
+    pending: Vec<Vec<BusTransaction>>,
+}
+
+impl SnoopBus {
+    /// cores 個のコアをつなぐバスを作成
+    pub fn new(cores: usize) -> Self {
+        Self { holders: HashMap::new(), pending: vec![Vec::new(); cores] }
+    }
+
+    /// core が line を読み込んだことを知らせ、他のコアも持っていれば真（Sharedで読み込む）を返す
+    pub fn read(&mut self, core: usize, line: u32) -> bool {
+        let holders = self.holders.entry(line).or_default();
+        let shared = holders.iter().any(|&other| other != core);
+        for &other in holders.iter().filter(|&&other| other != core) {
+            self.pending[other].push(BusTransaction::Read(line));
+        }
+        if !holders.contains(&core) {
+            holders.push(core);
+        }
+        shared
+    }
+
+    /// core が line に書き込むことを知らせ、他のコアのラインを無効にする（keep が偽なら core も持たない）
+    pub fn read_exclusive(&mut self, core: usize, line: u32, keep: bool) {
+        let Some(holders) = self.holders.get_mut(&line) else {
+            if keep {
+                self.holders.insert(line, vec![core]);
+            }
+            return;
+        };
+        for &other in holders.iter().filter(|&&other| other != core) {
+            self.pending[other].push(BusTransaction::ReadExclusive(line));
+        }
+        holders.clear();
+        if keep {
+            holders.push(core);
+        }
+    }
+
+    /// core が line を追い出したことを知らせる
+    pub fn evict(&mut self, core: usize, line: u32) {
+        if let Some(holders) = self.holders.get_mut(&line) {
+            holders.retain(|&other| other != core);
+            if holders.is_empty() {
+                self.holders.remove(&line);
+            }
+        }
+    }
+
+    /// core に届いたトランザクションを古い順に取り出す
+    pub fn take_pending(&mut self, core: usize) -> Vec<BusTransaction> {
+        std::mem::take(&mut self.pending[core])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snoop_bus() {
+        let mut bus = SnoopBus::new(3);
+        assert!(!bus.read(0, 7));
+        assert!(bus.read(1, 7));
+        // コア0はコア1の読み込みを、コア1とコア0以外は何も受け取らない
+        assert_eq!(bus.take_pending(0), vec![BusTransaction::Read(7)]);
+        assert!(bus.take_pending(2).is_empty());
+
+        bus.read_exclusive(2, 7, true);
+        assert_eq!(bus.take_pending(0), vec![BusTransaction::ReadExclusive(7)]);
+        assert_eq!(bus.take_pending(1), vec![BusTransaction::ReadExclusive(7)]);
+        assert!(!bus.read(2, 7));
+
+        bus.evict(2, 7);
+        assert!(!bus.read(0, 7));
+        assert!(bus.take_pending(2).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! 命令カバレッジ（実行された命令の種類と到達しなかった命令）の集計
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::instructions::Instruction;
+use crate::memory::MemoryAddress;
+
+/// 命令カバレッジ
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// ニーモニックごとの実行回数
+    counts: BTreeMap<&'static str, u64>,
+    /// 実行された命令のアドレス
+    executed: HashSet<MemoryAddress>,
+    /// ロードされたプログラム（アドレス, 命令）
+    program: Vec<(MemoryAddress, Instruction)>,
+}
+
+impl Coverage {
+    /// ロードされたプログラムを記録する（到達しなかった命令の判定に使う）
+    pub fn set_program(&mut self, start_address: MemoryAddress, program: &[Instruction]) {
+        self.program = program.iter().enumerate()
+            .map(|(i, instruction)| (start_address + (i * 4) as u32, *instruction))
+            .collect();
+    }
+
+    /// 連続していないプログラムを（アドレス, 命令）の列で記録する
+    pub fn set_program_words(&mut self, program: Vec<(MemoryAddress, Instruction)>) {
+        self.program = program;
+    }
+
+    /// ロードされたプログラム（アドレス, 命令）
+    pub fn program(&self) -> &[(MemoryAddress, Instruction)] {
+        &self.program
+    }
+
+    /// 1命令の実行を記録する
+    pub fn record(&mut self, pc: MemoryAddress, mnemonic: &'static str) {
+        *self.counts.entry(mnemonic).or_insert(0) += 1;
+        self.executed.insert(pc);
+    }
+
+    /// 実行の記録を消去する（ロードされたプログラムは保持する）
+    pub fn reset(&mut self) {
+        self.counts.clear();
+        self.executed.clear();
+    }
+
+    /// ニーモニックごとの実行回数（実行回数の多い順）
+    pub fn counts(&self) -> Vec<(&'static str, u64)> {
+        let mut counts: Vec<(&'static str, u64)> = self.counts.iter().map(|(m, c)| (*m, *c)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// ロードされたが一度も実行されなかった命令（アドレス順）
+    pub fn unreached(&self) -> Vec<(MemoryAddress, Instruction)> {
+        self.program.iter()
+            .filter(|(address, _)| !self.executed.contains(address))
+            .copied()
+            .collect()
+    }
+
+    /// 実行された命令のアドレス数
+    pub fn executed_addresses(&self) -> usize {
+        self.executed.len()
+    }
+
+    /// 人が読める形式のレポートを作成する（命令の表示方法は呼び出し側が決める）
+    pub fn report<F: Fn(MemoryAddress, Instruction) -> String>(&self, disassemble: F) -> String {
+        let mut result = String::new();
+        let total: u64 = self.counts.values().sum();
+        result.push_str(&format!("実行された命令の種類: {}\n", self.counts.len()));
+        for (mnemonic, count) in self.counts() {
+            let percent = if total == 0 { 0.0 } else { count as f64 / total as f64 * 100.0 };
+            result.push_str(&format!("  {:<10} {:>10} ({:>6.2}%)\n", mnemonic, count, percent));
+        }
+
+        let unreached = self.unreached();
+        result.push_str(&format!("到達しなかった命令: {} / {}", unreached.len(), self.program.len()));
+        for (address, instruction) in unreached {
+            result.push_str(&format!("\n  0x{:08X}: 0x{:08X} ({})", address, instruction, disassemble(address, instruction)));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage_unreached() {
+        let mut coverage = Coverage::default();
+        coverage.set_program(0x400000, &[0x00430820, 0x0000000C, 0x00430822]);
+        coverage.record(0x400000, "add");
+        coverage.record(0x400000, "add");
+        coverage.record(0x400004, "syscall");
+
+        assert_eq!(coverage.counts(), vec![("add", 2), ("syscall", 1)]);
+        assert_eq!(coverage.unreached(), vec![(0x400008, 0x00430822)]);
+        assert!(coverage.report(|_, word| format!("{:08X}", word)).contains("到達しなかった命令: 1 / 3"));
+    }
+}
@@ -0,0 +1,220 @@
+//! コプロセッサ0（システム制御コプロセッサ）のレジスタ
+
+use std::fmt;
+
+use crate::instructions::{Register, Word};
+
+/// BadVAddr: 最後にアドレスエラーを起こしたアドレス
+pub const CP0_BADVADDR: Register = 8;
+
+/// Count: サイクルごとに増えるカウンタ
+pub const CP0_COUNT: Register = 9;
+
+/// Compare: Countと比較するタイマの値
+pub const CP0_COMPARE: Register = 11;
+
+/// Status: 割り込みマスクや動作モード
+pub const CP0_STATUS: Register = 12;
+
+/// Cause: 直前の例外の原因と保留中の割り込み
+pub const CP0_CAUSE: Register = 13;
+
+/// EPC: 例外から復帰するアドレス
+pub const CP0_EPC: Register = 14;
+
+/// Causeのうちソフトウェアから書き込めるビット（IP1, IP0: ソフトウェア割り込み）
+const CAUSE_WRITABLE_MASK: Word = 0x0000_0300;
+
+/// CauseのExcCodeフィールド（ビット6..2）
+const CAUSE_EXC_CODE_MASK: Word = 0x0000_007C;
+
+/// StatusのIEビット（割り込み許可）
+pub const STATUS_IE: Word = 0x0000_0001;
+
+/// StatusのEXLビット（例外処理中）
+pub const STATUS_EXL: Word = 0x0000_0002;
+
+/// Causeの保留中の割り込み（IP7..IP0）と、同じ位置にあるStatusの割り込みマスク（IM7..IM0）
+const INTERRUPT_MASK: Word = 0x0000_FF00;
+
+/// タイマ割り込みのビット（CauseのIP7、StatusのIM7）
+pub const INTERRUPT_TIMER: Word = 0x0000_8000;
+
+/// 例外の種類（値はCauseのExcCode）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExceptionCode {
+    /// 割り込み
+    Interrupt = 0,
+    /// ロードまたは命令フェッチのアドレスエラー
+    AddressLoad = 4,
+    /// ストアのアドレスエラー
+    AddressStore = 5,
+    /// システムコール
+    Syscall = 8,
+    /// 予約命令（無効な命令）
+    ReservedInstruction = 10,
+    /// 算術オーバーフロー
+    Overflow = 12,
+}
+
+impl ExceptionCode {
+    /// 表示用の名前（MIPSの略称）
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExceptionCode::Interrupt => "Int",
+            ExceptionCode::AddressLoad => "AdEL",
+            ExceptionCode::AddressStore => "AdES",
+            ExceptionCode::Syscall => "Sys",
+            ExceptionCode::ReservedInstruction => "RI",
+            ExceptionCode::Overflow => "Ov",
+        }
+    }
+}
+
+/// コプロセッサ0のレジスタ
+#[derive(Debug, Clone, Default)]
+pub struct Cp0 {
+    pub bad_vaddr: Word,
+    pub count: Word,
+    pub compare: Word,
+    pub status: Word,
+    pub cause: Word,
+    pub epc: Word,
+}
+
+impl Cp0 {
+    /// mfc0: レジスタを読む（未実装のレジスタは0を返す）
+    pub fn read(&self, reg: Register) -> Word {
+        match reg {
+            CP0_BADVADDR => self.bad_vaddr,
+            CP0_COUNT => self.count,
+            CP0_COMPARE => self.compare,
+            CP0_STATUS => self.status,
+            CP0_CAUSE => self.cause,
+            CP0_EPC => self.epc,
+            _ => 0,
+        }
+    }
+
+    /// mtc0: レジスタに書く（BadVAddrと未実装のレジスタへの書き込みは無視し、Causeはソフトウェア割り込みのビットのみ書ける）
+    /// Compareに書くと保留中のタイマ割り込みを取り消す
+    pub fn write(&mut self, reg: Register, value: Word) {
+        match reg {
+            CP0_COUNT => self.count = value,
+            CP0_COMPARE => {
+                self.compare = value;
+                self.cause &= !INTERRUPT_TIMER;
+            }
+            CP0_STATUS => self.status = value,
+            CP0_CAUSE => self.cause = (self.cause & !CAUSE_WRITABLE_MASK) | (value & CAUSE_WRITABLE_MASK),
+            CP0_EPC => self.epc = value,
+            _ => {}
+        }
+    }
+
+    /// 例外処理中（StatusのEXLが立っている）か
+    pub fn in_exception(&self) -> bool {
+        self.status & STATUS_EXL != 0
+    }
+
+    /// 例外を受け付ける: 原因とEPCを記録してEXLを立てる（アドレスエラーではBadVAddrも記録する）
+    pub fn enter_exception(&mut self, code: ExceptionCode, epc: Word, bad_vaddr: Option<Word>) {
+        self.cause = (self.cause & !CAUSE_EXC_CODE_MASK) | ((code as Word) << 2);
+        self.epc = epc;
+        if let Some(address) = bad_vaddr {
+            self.bad_vaddr = address;
+        }
+        self.status |= STATUS_EXL;
+    }
+
+    /// eret: EXLを下ろして復帰先（EPC）を返す
+    pub fn exception_return(&mut self) -> Word {
+        self.status &= !STATUS_EXL;
+        self.epc
+    }
+
+    /// 受け付けるべき割り込みがあるか（IEが立ちEXLが下りていて、マスクされていない割り込みが保留中）
+    pub fn pending_interrupt(&self) -> bool {
+        self.status & (STATUS_IE | STATUS_EXL) == STATUS_IE && self.cause & self.status & INTERRUPT_MASK != 0
+    }
+
+    /// 割り込みを受け付けられる状態か（IEが立ちEXLが下りていて、いずれかの割り込みがマスクされていない）
+    pub fn interrupts_enabled(&self) -> bool {
+        self.status & (STATUS_IE | STATUS_EXL) == STATUS_IE && self.status & INTERRUPT_MASK != 0
+    }
+
+    /// 経過したサイクル数だけCountを進め、CountがCompareに達したらタイマ割り込みを保留にする
+    pub fn advance(&mut self, cycles: u64) {
+        // 1命令で複数サイクル進む場合も、途中でCompareと等しくなればよい
+        let distance = self.compare.wrapping_sub(self.count);
+        if distance != 0 && distance as u64 <= cycles {
+            self.cause |= INTERRUPT_TIMER;
+        }
+        self.count = self.count.wrapping_add(cycles as Word);
+    }
+}
+
+impl fmt::Display for Cp0 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Status:   0x{:08X}", self.status)?;
+        writeln!(f, "Cause:    0x{:08X}", self.cause)?;
+        writeln!(f, "EPC:      0x{:08X}", self.epc)?;
+        writeln!(f, "BadVAddr: 0x{:08X}", self.bad_vaddr)?;
+        writeln!(f, "Count:    {}", self.count)?;
+        write!(f, "Compare:  {}", self.compare)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cp0_write_masks() {
+        let mut cp0 = Cp0::default();
+        cp0.write(CP0_STATUS, 0xFF01);
+        cp0.write(CP0_CAUSE, 0xFFFF_FFFF);
+        cp0.write(CP0_BADVADDR, 0x1234);
+        cp0.write(CP0_COUNT, 0xFFFF_FFFF);
+        cp0.advance(2);
+        assert_eq!(cp0.read(CP0_STATUS), 0xFF01);
+        // Countが0xFFFFFFFFから0（= Compare）に達したのでタイマ割り込みも保留になる
+        assert_eq!(cp0.read(CP0_CAUSE), 0x300 | INTERRUPT_TIMER);
+        assert_eq!(cp0.read(CP0_BADVADDR), 0);
+        assert_eq!(cp0.read(CP0_COUNT), 1);
+        assert_eq!(cp0.read(31), 0);
+    }
+
+    #[test]
+    fn test_timer_interrupt() {
+        let mut cp0 = Cp0::default();
+        cp0.write(CP0_COMPARE, 10);
+        cp0.write(CP0_STATUS, INTERRUPT_TIMER | STATUS_IE);
+        cp0.advance(9);
+        assert!(!cp0.pending_interrupt());
+        // 1命令で数サイクル進んでCompareを飛び越えても割り込む
+        cp0.advance(3);
+        assert!(cp0.pending_interrupt());
+        assert_eq!(cp0.read(CP0_CAUSE) & INTERRUPT_TIMER, INTERRUPT_TIMER);
+        // 例外処理中は受け付けず、Compareに書くと取り消される
+        cp0.enter_exception(ExceptionCode::Interrupt, 0x400000, None);
+        assert!(!cp0.pending_interrupt());
+        cp0.write(CP0_COMPARE, 100);
+        cp0.exception_return();
+        assert!(!cp0.pending_interrupt());
+    }
+
+    #[test]
+    fn test_enter_exception_and_return() {
+        let mut cp0 = Cp0::default();
+        cp0.write(CP0_CAUSE, 0x100);
+        cp0.enter_exception(ExceptionCode::AddressStore, 0x400010, Some(0x1001));
+        assert!(cp0.in_exception());
+        assert_eq!(cp0.read(CP0_CAUSE), 0x100 | (5 << 2));
+        assert_eq!((cp0.read(CP0_EPC), cp0.read(CP0_BADVADDR)), (0x400010, 0x1001));
+        // ハンドラはEPCを書き換えて次の命令へ戻れる
+        cp0.write(CP0_EPC, 0x400014);
+        assert_eq!(cp0.exception_return(), 0x400014);
+        assert!(!cp0.in_exception());
+    }
+}
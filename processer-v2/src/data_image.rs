@@ -0,0 +1,81 @@
+//! 実行前にメモリへ読み込むデータイメージ（シーンファイルや参照テーブルを、ストア命令に埋め込まずに渡すため）
+//!
+//! 拡張子が .hex のファイルはプログラムと同じ16進数のテキスト（1語は8桁までの16進数で、空白で区切って1行に複数書ける。
+//! '#' 以降はコメント）として読み、各語をメモリ上の並び（リトルエンディアン）で置く。
+//! それ以外のファイルはバイナリとしてそのまま置く。
+
+use std::fs;
+use std::path::Path;
+
+use crate::memory::MemoryAddress;
+
+/// メモリに読み込むデータイメージ
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataImage {
+    pub path: String,
+    /// 読み込む先頭アドレス
+    pub address: MemoryAddress,
+}
+
+impl DataImage {
+    /// "<ファイル>@<アドレス>" の形式から作成（アドレスは0xで始まる16進数または10進数）
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (path, address) = text.rsplit_once('@')
+            .filter(|(path, _)| !path.is_empty())
+            .ok_or_else(|| format!("データは <ファイル>@<アドレス> の形式で指定してください: {}", text))?;
+        let address = address.trim();
+        let parsed = match address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => address.parse(),
+        };
+        let address = parsed.map_err(|_| format!("無効なアドレスです: {}", address))?;
+        Ok(Self { path: path.to_string(), address })
+    }
+
+    /// ファイルを読み込み、メモリに置くバイト列を返す
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        let is_hex = Path::new(&self.path).extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hex"));
+        if is_hex {
+            let text = fs::read_to_string(&self.path)
+                .map_err(|e| format!("データ '{}' を開けません: {}", self.path, e))?;
+            parse_hex_words(&text).map_err(|e| format!("データ '{}' の{}", self.path, e))
+        } else {
+            fs::read(&self.path).map_err(|e| format!("データ '{}' を開けません: {}", self.path, e))
+        }
+    }
+}
+
+/// 16進数の語を並べたテキストを、メモリ上の並び（リトルエンディアン）のバイト列に変換する
+fn parse_hex_words(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        for word in line.split_whitespace() {
+            let value = Some(word)
+                .filter(|word| word.len() <= 8)
+                .and_then(|word| u32::from_str_radix(word, 16).ok())
+                .ok_or_else(|| format!(" {} 行目: 8桁までの16進数で指定してください: '{}'", line_num + 1, word))?;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_image() {
+        assert_eq!(DataImage::parse("scene@0x10000").unwrap(), DataImage { path: "scene".to_string(), address: 0x10000 });
+        assert_eq!(DataImage::parse("a@b.bin@4096").unwrap().path, "a@b.bin");
+        assert!(DataImage::parse("scene.bin").is_err());
+        assert!(DataImage::parse("@0x10000").is_err());
+
+        assert_eq!(parse_hex_words("DEADBEEF 1 # comment\n\n# only comment\n00000002").unwrap(),
+            vec![0xEF, 0xBE, 0xAD, 0xDE, 1, 0, 0, 0, 2, 0, 0, 0]);
+        assert!(parse_hex_words("123456789").is_err());
+        assert!(parse_hex_words("0x10").is_err());
+    }
+}
@@ -0,0 +1,445 @@
+//! 表引きによるMIPS命令のデコーダ
+//!
+//! opcode・funct・rs（コプロセッサ命令のサブコード）から命令の種類を引く表を起動時に1度だけ作る。
+//! 独自命令を追加する場合は `DecodeTable::standard()` に `register` してから、最初のデコードより前に `install` する。
+//! MIPS32にあるがこのシミュレータには実装していない命令は、無効な命令と区別できるよう名前だけを表に載せている。
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::instructions::{Address, Immediate, Instruction, InstructionType, Register};
+
+/// 命令語を分解したフィールド
+#[derive(Debug, Clone, Copy)]
+pub struct Fields {
+    /// 命令語全体
+    pub word: Instruction,
+    pub rs: Register,
+    pub rt: Register,
+    pub rd: Register,
+    pub shamt: u8,
+    pub funct: u8,
+    pub imm: Immediate,
+    pub addr: Address,
+}
+
+impl Fields {
+    /// 命令語を各フィールドに分解する
+    pub fn new(word: Instruction) -> Self {
+        Self {
+            word,
+            rs: ((word >> 21) & 0x1F) as u8,
+            rt: ((word >> 16) & 0x1F) as u8,
+            rd: ((word >> 11) & 0x1F) as u8,
+            shamt: ((word >> 6) & 0x1F) as u8,
+            funct: (word & 0x3F) as u8,
+            imm: (word & 0xFFFF) as i16,
+            addr: word & 0x3FFFFFF,
+        }
+    }
+
+    fn opcode(&self) -> u8 {
+        (self.word >> 26) as u8
+    }
+}
+
+/// フィールドから命令を作る関数
+pub type DecodeFn = fn(&Fields) -> InstructionType;
+
+/// 表に登録する位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpcodeKey {
+    /// opcodeだけで決まる命令
+    Primary(u8),
+    /// opcodeとfunctで決まる命令（R形式）
+    Funct { opcode: u8, funct: u8 },
+    /// opcodeとrsで決まる命令（コプロセッサのレジスタ転送）
+    Rs { opcode: u8, rs: u8 },
+    /// opcode・rs・functで決まる命令（コプロセッサの演算）
+    RsFunct { opcode: u8, rs: u8, funct: u8 },
+}
+
+impl fmt::Display for OpcodeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpcodeKey::Primary(opcode) => write!(f, "opcode=0x{:02X}", opcode),
+            OpcodeKey::Funct { opcode, funct } => write!(f, "opcode=0x{:02X}, funct=0x{:02X}", opcode, funct),
+            OpcodeKey::Rs { opcode, rs } => write!(f, "opcode=0x{:02X}, rs=0x{:02X}", opcode, rs),
+            OpcodeKey::RsFunct { opcode, rs, funct } => {
+                write!(f, "opcode=0x{:02X}, rs=0x{:02X}, funct=0x{:02X}", opcode, rs, funct)
+            }
+        }
+    }
+}
+
+/// 表の1要素
+#[derive(Debug, Clone)]
+enum Entry {
+    /// 未定義（無効な命令）
+    Invalid,
+    /// 既知だが未実装の命令（ニーモニック）
+    Unimplemented(&'static str),
+    /// この位置で命令が決まる
+    Leaf(DecodeFn),
+    /// さらにfunctで引く
+    Funct(Box<[Entry; 64]>),
+    /// さらにrsで引く
+    Rs(Box<[Entry; 32]>),
+}
+
+impl Entry {
+    /// 命令を登録できる位置か（未実装の命令の位置には独自命令を登録できる）
+    fn is_free(&self) -> bool {
+        matches!(self, Entry::Invalid | Entry::Unimplemented(_))
+    }
+
+    /// 命令が決まる位置まで表を引く
+    fn lookup(&self, fields: &Fields) -> &Entry {
+        match self {
+            Entry::Funct(table) => table[fields.funct as usize].lookup(fields),
+            Entry::Rs(table) => table[fields.rs as usize].lookup(fields),
+            entry => entry,
+        }
+    }
+}
+
+/// 命令のデコード表
+#[derive(Debug, Clone)]
+pub struct DecodeTable {
+    primary: [Entry; 64],
+}
+
+impl DecodeTable {
+    /// 何も登録されていない表を作成
+    pub fn empty() -> Self {
+        Self { primary: std::array::from_fn(|_| Entry::Invalid) }
+    }
+
+    /// 命令を登録する（既に登録されている位置や、別の引き方をする位置と重なる場合はエラー）
+    pub fn register(&mut self, key: OpcodeKey, decode: DecodeFn) -> Result<(), String> {
+        self.insert(key, Entry::Leaf(decode))
+    }
+
+    /// 既知だが未実装の命令を登録する（デコードすると無効な命令になるが、名前を報告できる）
+    pub fn register_unimplemented(&mut self, key: OpcodeKey, mnemonic: &'static str) -> Result<(), String> {
+        self.insert(key, Entry::Unimplemented(mnemonic))
+    }
+
+    fn insert(&mut self, key: OpcodeKey, leaf: Entry) -> Result<(), String> {
+        let conflict = || Err(format!("デコード表の {} は既に使われています", key));
+        let (opcode, rs, funct) = match key {
+            OpcodeKey::Primary(opcode) => (opcode, None, None),
+            OpcodeKey::Funct { opcode, funct } => (opcode, None, Some(funct)),
+            OpcodeKey::Rs { opcode, rs } => (opcode, Some(rs), None),
+            OpcodeKey::RsFunct { opcode, rs, funct } => (opcode, Some(rs), Some(funct)),
+        };
+        if opcode >= 64 || rs.is_some_and(|rs| rs >= 32) || funct.is_some_and(|funct| funct >= 64) {
+            return Err(format!("デコード表の範囲外です: {}", key));
+        }
+
+        let mut entry = &mut self.primary[opcode as usize];
+        if let Some(rs) = rs {
+            if entry.is_free() {
+                *entry = Entry::Rs(Box::new(std::array::from_fn(|_| Entry::Invalid)));
+            }
+            entry = match entry {
+                Entry::Rs(table) => &mut table[rs as usize],
+                _ => return conflict(),
+            };
+        }
+        if let Some(funct) = funct {
+            if entry.is_free() {
+                *entry = Entry::Funct(Box::new(std::array::from_fn(|_| Entry::Invalid)));
+            }
+            entry = match entry {
+                Entry::Funct(table) => &mut table[funct as usize],
+                _ => return conflict(),
+            };
+        }
+        if !entry.is_free() {
+            return conflict();
+        }
+        *entry = leaf;
+        Ok(())
+    }
+
+    /// 命令をデコードする
+    pub fn decode(&self, instruction: Instruction) -> InstructionType {
+        let fields = Fields::new(instruction);
+        match self.primary[fields.opcode() as usize].lookup(&fields) {
+            Entry::Leaf(decode) => decode(&fields),
+            _ => InstructionType::Invalid,
+        }
+    }
+
+    /// 既知だが未実装の命令であればそのニーモニックを返す
+    pub fn unimplemented_name(&self, instruction: Instruction) -> Option<&'static str> {
+        let fields = Fields::new(instruction);
+        match self.primary[fields.opcode() as usize].lookup(&fields) {
+            Entry::Unimplemented(mnemonic) => Some(mnemonic),
+            _ => None,
+        }
+    }
+
+    /// 命令列に含まれる既知だが未実装の命令をニーモニックごとに数える（出現数の多い順）
+    pub fn count_unimplemented(&self, instructions: &[Instruction]) -> Vec<(&'static str, usize)> {
+        let mut counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+        for &instruction in instructions {
+            if let Some(mnemonic) = self.unimplemented_name(instruction) {
+                *counts.entry(mnemonic).or_insert(0) += 1;
+            }
+        }
+        let mut counts: Vec<(&'static str, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// 標準の命令を登録した表を作成
+    pub fn standard() -> Self {
+        use InstructionType as I;
+        use OpcodeKey::{Funct, Primary, Rs, RsFunct};
+
+        // コプロセッサ1命令は rsフィールドがfmt、rtフィールドがft、rdフィールドがfs、shamtフィールドがfd
+        const COP1: u8 = 0x11;
+        const FMT_S: u8 = 0x10;
+        #[cfg(feature = "double")]
+        const FMT_D: u8 = 0x11;
+        const FMT_W: u8 = 0x14;
+
+        let entries: &[(OpcodeKey, DecodeFn)] = &[
+            // R形式命令
+            (Funct { opcode: 0x00, funct: 0x20 }, |f| I::Add { rd: f.rd, rs: f.rs, rt: f.rt }),
+            (Funct { opcode: 0x00, funct: 0x22 }, |f| I::Sub { rd: f.rd, rs: f.rs, rt: f.rt }),
+            (Funct { opcode: 0x00, funct: 0x24 }, |f| I::And { rd: f.rd, rs: f.rs, rt: f.rt }),
+            (Funct { opcode: 0x00, funct: 0x25 }, |f| I::Or { rd: f.rd, rs: f.rs, rt: f.rt }),
+            (Funct { opcode: 0x00, funct: 0x2A }, |f| I::Slt { rd: f.rd, rs: f.rs, rt: f.rt }),
+            (Funct { opcode: 0x00, funct: 0x00 }, |f| I::Sll { rd: f.rd, rt: f.rt, shamt: f.shamt }),
+            (Funct { opcode: 0x00, funct: 0x02 }, |f| I::Srl { rd: f.rd, rt: f.rt, shamt: f.shamt }),
+            (Funct { opcode: 0x00, funct: 0x08 }, |f| I::Jr { rs: f.rs }),
+            (Funct { opcode: 0x00, funct: 0x0C }, |_| I::Syscall),
+            (Funct { opcode: 0x00, funct: 0x0D }, |f| I::Break { code: (f.word >> 6) & 0xFFFFF }),
+            // I形式・J形式命令
+            (Primary(0x08), |f| I::Addi { rt: f.rt, rs: f.rs, imm: f.imm }),
+            (Primary(0x23), |f| I::Lw { rt: f.rt, rs: f.rs, imm: f.imm }),
+            (Primary(0x2B), |f| I::Sw { rt: f.rt, rs: f.rs, imm: f.imm }),
+            (Primary(0x04), |f| I::Beq { rs: f.rs, rt: f.rt, imm: f.imm }),
+            (Primary(0x05), |f| I::Bne { rs: f.rs, rt: f.rt, imm: f.imm }),
+            (Primary(0x0A), |f| I::Slti { rt: f.rt, rs: f.rs, imm: f.imm }),
+            (Primary(0x0F), |f| I::Lui { rt: f.rt, imm: f.imm }),
+            (Primary(0x0D), |f| I::Ori { rt: f.rt, rs: f.rs, imm: f.imm }),
+            (Primary(0x02), |f| I::J { addr: f.addr }),
+            (Primary(0x03), |f| I::Jal { addr: f.addr }),
+            // コプロセッサ0命令（下位11ビットは0、selは0のみ対応）
+            (Rs { opcode: 0x10, rs: 0x00 }, |f| if f.word & 0x7FF == 0 { I::Mfc0 { rt: f.rt, rd: f.rd } } else { I::Invalid }),
+            (Rs { opcode: 0x10, rs: 0x04 }, |f| if f.word & 0x7FF == 0 { I::Mtc0 { rt: f.rt, rd: f.rd } } else { I::Invalid }),
+            (RsFunct { opcode: 0x10, rs: 0x10, funct: 0x18 }, |f| if f.word == 0x42000018 { I::Eret } else { I::Invalid }),
+            // コプロセッサ1命令
+            (Rs { opcode: COP1, rs: 0x00 }, |f| I::Mfc1 { rt: f.rt, fs: f.rd }),
+            (Rs { opcode: COP1, rs: 0x02 }, |f| I::Cfc1 { rt: f.rt, fs: f.rd }),
+            (Rs { opcode: COP1, rs: 0x04 }, |f| I::Mtc1 { rt: f.rt, fs: f.rd }),
+            (Rs { opcode: COP1, rs: 0x06 }, |f| I::Ctc1 { rt: f.rt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x00 }, |f| I::AddS { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x01 }, |f| I::SubS { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x02 }, |f| I::MulS { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x03 }, |f| I::DivS { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x04 }, |f| I::SqrtS { fd: f.shamt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x05 }, |f| I::AbsS { fd: f.shamt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x06 }, |f| I::MovS { fd: f.shamt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x07 }, |f| I::NegS { fd: f.shamt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x0D }, |f| I::TruncWS { fd: f.shamt, fs: f.rd }),
+            // MIPS IVのrecip.sと同じエンコーディング
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x15 }, |f| I::FinvS { fd: f.shamt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x24 }, |f| I::CvtWS { fd: f.shamt, fs: f.rd }),
+            (RsFunct { opcode: COP1, rs: FMT_W, funct: 0x20 }, |f| I::CvtSW { fd: f.shamt, fs: f.rd }),
+            // 倍精度浮動小数点命令
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x21 }, |f| I::CvtDS { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x00 }, |f| I::AddD { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x01 }, |f| I::SubD { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x02 }, |f| I::MulD { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x03 }, |f| I::DivD { fd: f.shamt, fs: f.rd, ft: f.rt }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x04 }, |f| I::SqrtD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x05 }, |f| I::AbsD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x06 }, |f| I::MovD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x07 }, |f| I::NegD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x0D }, |f| I::TruncWD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x20 }, |f| I::CvtSD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_D, funct: 0x24 }, |f| I::CvtWD { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (RsFunct { opcode: COP1, rs: FMT_W, funct: 0x21 }, |f| I::CvtDW { fd: f.shamt, fs: f.rd }),
+            #[cfg(feature = "double")]
+            (Primary(0x35), |f| I::Ldc1 { ft: f.rt, base: f.rs, imm: f.imm }),
+            #[cfg(feature = "double")]
+            (Primary(0x3D), |f| I::Sdc1 { ft: f.rt, base: f.rs, imm: f.imm }),
+        ];
+
+        // MIPS32にあるが実装していない命令
+        let unimplemented: &[(OpcodeKey, &'static str)] = &[
+            (Funct { opcode: 0x00, funct: 0x03 }, "sra"),
+            (Funct { opcode: 0x00, funct: 0x04 }, "sllv"),
+            (Funct { opcode: 0x00, funct: 0x06 }, "srlv"),
+            (Funct { opcode: 0x00, funct: 0x07 }, "srav"),
+            (Funct { opcode: 0x00, funct: 0x09 }, "jalr"),
+            (Funct { opcode: 0x00, funct: 0x0A }, "movz"),
+            (Funct { opcode: 0x00, funct: 0x0B }, "movn"),
+            (Funct { opcode: 0x00, funct: 0x0F }, "sync"),
+            (Funct { opcode: 0x00, funct: 0x10 }, "mfhi"),
+            (Funct { opcode: 0x00, funct: 0x11 }, "mthi"),
+            (Funct { opcode: 0x00, funct: 0x12 }, "mflo"),
+            (Funct { opcode: 0x00, funct: 0x13 }, "mtlo"),
+            (Funct { opcode: 0x00, funct: 0x18 }, "mult"),
+            (Funct { opcode: 0x00, funct: 0x19 }, "multu"),
+            (Funct { opcode: 0x00, funct: 0x1A }, "div"),
+            (Funct { opcode: 0x00, funct: 0x1B }, "divu"),
+            (Funct { opcode: 0x00, funct: 0x21 }, "addu"),
+            (Funct { opcode: 0x00, funct: 0x23 }, "subu"),
+            (Funct { opcode: 0x00, funct: 0x26 }, "xor"),
+            (Funct { opcode: 0x00, funct: 0x27 }, "nor"),
+            (Funct { opcode: 0x00, funct: 0x2B }, "sltu"),
+            (Funct { opcode: 0x00, funct: 0x34 }, "teq"),
+            // REGIMMはrtフィールドで命令が決まるが、表はrtで引かないためまとめて扱う
+            (Primary(0x01), "bltz/bgez"),
+            (Primary(0x06), "blez"),
+            (Primary(0x07), "bgtz"),
+            (Primary(0x09), "addiu"),
+            (Primary(0x0B), "sltiu"),
+            (Primary(0x0C), "andi"),
+            (Primary(0x0E), "xori"),
+            (Funct { opcode: 0x1C, funct: 0x00 }, "madd"),
+            (Funct { opcode: 0x1C, funct: 0x02 }, "mul"),
+            (Funct { opcode: 0x1C, funct: 0x20 }, "clz"),
+            (Primary(0x20), "lb"),
+            (Primary(0x21), "lh"),
+            (Primary(0x22), "lwl"),
+            (Primary(0x24), "lbu"),
+            (Primary(0x25), "lhu"),
+            (Primary(0x26), "lwr"),
+            (Primary(0x28), "sb"),
+            (Primary(0x29), "sh"),
+            (Primary(0x2A), "swl"),
+            (Primary(0x2E), "swr"),
+            (Primary(0x30), "ll"),
+            (Primary(0x31), "lwc1"),
+            (Primary(0x38), "sc"),
+            (Primary(0x39), "swc1"),
+            (Rs { opcode: COP1, rs: 0x08 }, "bc1f/bc1t"),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x0C }, "round.w.s"),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x0E }, "ceil.w.s"),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x0F }, "floor.w.s"),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x32 }, "c.eq.s"),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x3C }, "c.lt.s"),
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x3E }, "c.le.s"),
+            // 倍精度命令は `--features double` でビルドした場合のみ実装している
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x00 }, "add.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x01 }, "sub.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x02 }, "mul.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x03 }, "div.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x04 }, "sqrt.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x05 }, "abs.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x06 }, "mov.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x07 }, "neg.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x0D }, "trunc.w.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x20 }, "cvt.s.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: 0x11, funct: 0x24 }, "cvt.w.d"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: FMT_S, funct: 0x21 }, "cvt.d.s"),
+            #[cfg(not(feature = "double"))]
+            (RsFunct { opcode: COP1, rs: FMT_W, funct: 0x21 }, "cvt.d.w"),
+            #[cfg(not(feature = "double"))]
+            (Primary(0x35), "ldc1"),
+            #[cfg(not(feature = "double"))]
+            (Primary(0x3D), "sdc1"),
+        ];
+
+        let mut table = Self::empty();
+        for &(key, decode) in entries {
+            table.register(key, decode).expect("標準の命令のエンコーディングが重複しています");
+        }
+        for &(key, mnemonic) in unimplemented {
+            table.register_unimplemented(key, mnemonic).expect("未実装の命令のエンコーディングが重複しています");
+        }
+        table
+    }
+}
+
+/// 実行時に使うデコード表
+static TABLE: OnceLock<DecodeTable> = OnceLock::new();
+
+/// 実行時に使うデコード表（まだなければ標準の表を作る）
+pub fn table() -> &'static DecodeTable {
+    TABLE.get_or_init(DecodeTable::standard)
+}
+
+/// 独自命令を登録した表を実行時に使う表にする（最初のデコードより前にのみ可能）
+pub fn install(table: DecodeTable) -> Result<(), String> {
+    TABLE.set(table).map_err(|_| "デコード表は既に使われているため置き換えられません".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_custom_instruction() {
+        let mut table = DecodeTable::standard();
+        // 空いているopcodeに独自命令を登録できる
+        table.register(OpcodeKey::Primary(0x3F), |f| InstructionType::Addi { rt: f.rt, rs: f.rs, imm: 1 }).unwrap();
+        assert_eq!(table.decode(0xFC220000), InstructionType::Addi { rt: 2, rs: 1, imm: 1 });
+        assert_eq!(table.decode(0x00430820), InstructionType::Add { rd: 1, rs: 2, rt: 3 });
+
+        // 使われている位置や、別の引き方をする位置とは重ならない
+        assert!(table.register(OpcodeKey::Funct { opcode: 0x00, funct: 0x20 }, |_| InstructionType::Syscall).is_err());
+        assert!(table.register(OpcodeKey::Primary(0x00), |_| InstructionType::Syscall).is_err());
+        assert!(table.register(OpcodeKey::Funct { opcode: 0x11, funct: 0x00 }, |_| InstructionType::Syscall).is_err());
+        assert!(table.register(OpcodeKey::Funct { opcode: 0x00, funct: 0x3F }, |_| InstructionType::Syscall).is_ok());
+    }
+
+    #[test]
+    fn test_unregistered_encodings_are_invalid() {
+        let table = DecodeTable::standard();
+        assert_eq!(table.decode(0x0000003F), InstructionType::Invalid); // 未定義のfunct
+        assert_eq!(table.decode(0x46E00000), InstructionType::Invalid); // 未定義のfmt
+        assert_eq!(table.decode(0x40086001), InstructionType::Invalid); // selが0でないmfc0
+        assert_eq!(table.decode(0x40086000), InstructionType::Mfc0 { rt: 8, rd: 12 });
+    }
+
+    #[test]
+    fn test_unimplemented_instructions() {
+        let mut table = DecodeTable::standard();
+        // mul $t0, $t1, $t2 と addu $v0, $a0, $a1 は既知だが未実装、未定義のfunctは名前を持たない
+        let program = [0x712A4002, 0x00851021, 0x712A4002, 0x00430820, 0x0000003F];
+        assert_eq!(table.decode(0x712A4002), InstructionType::Invalid);
+        assert_eq!(table.unimplemented_name(0x712A4002), Some("mul"));
+        assert_eq!(table.unimplemented_name(0x0000003F), None);
+        assert_eq!(table.unimplemented_name(0x00430820), None);
+        assert_eq!(table.count_unimplemented(&program), vec![("mul", 2), ("addu", 1)]);
+
+        // 未実装の命令の位置には独自命令を登録できる
+        table.register(OpcodeKey::Funct { opcode: 0x00, funct: 0x21 }, |f| InstructionType::Add { rd: f.rd, rs: f.rs, rt: f.rt }).unwrap();
+        assert_eq!(table.decode(0x00851021), InstructionType::Add { rd: 2, rs: 4, rt: 5 });
+        assert_eq!(table.unimplemented_name(0x00851021), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! 逆アセンブル（disasmサブコマンド, アセンブラやツールチェーンの出力を実行せずに確かめるため）
+//!
+//! 実行可能なセグメントは1ワードずつアドレス・機械語・命令を表示し、それ以外のセグメントは .word として表示する。
+//! 無効な命令にデコードされるワードには印を付けて数える。
+
+use crate::instructions::InstructionType;
+use crate::loader::{self, LoadImage};
+use crate::processor::Isa;
+use crate::riscv::RvInstruction;
+use crate::symbols::SymbolTable;
+
+/// 逆アセンブルの結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disassembly {
+    /// objdump風のテキスト
+    pub text: String,
+    /// 実行可能なセグメントの命令数
+    pub words: usize,
+    /// そのうち無効な命令にデコードされたワード数
+    pub invalid: usize,
+}
+
+/// イメージ全体を逆アセンブルする
+pub fn disassemble(image: &LoadImage, symbols: &SymbolTable, isa: Isa) -> Disassembly {
+    let mut text = String::new();
+    let (mut words, mut invalid) = (0, 0);
+    if let Some(entry) = image.entry {
+        text.push_str(&format!("エントリポイント: {}\n", symbols.format_address(entry)));
+    }
+    for segment in &image.segments {
+        text.push_str(&format!("\nセグメント 0x{:08X} - 0x{:08X} ({} バイト{})\n", segment.address, segment.end(), segment.data.len(),
+            if segment.executable { ", 実行可能" } else { "" }));
+        for (index, word) in loader::words_from_binary(&segment.data).into_iter().enumerate() {
+            let address = segment.address.wrapping_add(index as u32 * 4);
+            if let Some((symbol, 0)) = symbols.resolve(address) {
+                text.push_str(&format!("\n<{}>:\n", symbol.name));
+            }
+            if !segment.executable {
+                text.push_str(&format!("  {:08X}:  {:08X}  .word 0x{:08X}\n", address, word, word));
+                continue;
+            }
+            words += 1;
+            let (line, is_invalid) = match isa {
+                Isa::Mips => {
+                    let instruction = InstructionType::decode(word);
+                    (instruction.display_at(address, symbols), instruction == InstructionType::Invalid)
+                }
+                Isa::Rv32i => {
+                    let instruction = RvInstruction::decode(word);
+                    (instruction.display_at(address, symbols), instruction == RvInstruction::Invalid)
+                }
+            };
+            if is_invalid {
+                invalid += 1;
+                text.push_str(&format!("  {:08X}:  {:08X}  {:<32} ; ** 無効な命令 **\n", address, word, line));
+            } else {
+                text.push_str(&format!("  {:08X}:  {:08X}  {}\n", address, word, line));
+            }
+        }
+    }
+    Disassembly { text, words, invalid }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::Segment;
+
+    #[test]
+    fn test_disassemble() {
+        let words: [u32; 3] = [0x2008000A, 0x1100FFFF, 0xFC000000];
+        let image = LoadImage {
+            segments: vec![
+                Segment { address: 0x00400000, data: words.iter().flat_map(|word| word.to_le_bytes()).collect(), executable: true },
+                Segment { address: 0x00500000, data: vec![1, 2, 3, 4], executable: false },
+            ],
+            entry: Some(0x00400000),
+            symbols: Vec::new(),
+        };
+        let symbols = SymbolTable::parse("main 0x00400000\ntable 0x00500000 4").unwrap();
+        let result = disassemble(&image, &symbols, Isa::Mips);
+        assert_eq!((result.words, result.invalid), (3, 1));
+        assert_eq!(result.text, [
+            "エントリポイント: 0x00400000 <main>",
+            "",
+            "セグメント 0x00400000 - 0x0040000C (12 バイト, 実行可能)",
+            "",
+            "<main>:",
+            "  00400000:  2008000A  addi $8, $0, 10",
+            "  00400004:  1100FFFF  beq $8, $0, 0x00400000 <main>",
+            "  00400008:  FC000000  invalid                          ; ** 無効な命令 **",
+            "",
+            "セグメント 0x00500000 - 0x00500004 (4 バイト)",
+            "",
+            "<table>:",
+            "  00500000:  04030201  .word 0x04030201",
+            "",
+        ].join("\n"));
+    }
+}
@@ -0,0 +1,206 @@
+//! ELF32の実行ファイルの読み込み（mips-elf-gcc などの出力を objcopy や hexdump で変換せずに実行するため）
+//!
+//! リトルエンディアンの実行ファイル（ET_EXEC）のみに対応する。PT_LOADのセグメントを p_vaddr に置き、
+//! p_memsz が p_filesz より大きい部分（.bss）は0で埋める。シンボル表（.symtab）があれば関数とデータのシンボルを読む。
+
+use crate::loader::{LoadImage, Segment};
+use crate::processor::Isa;
+use crate::symbols::Symbol;
+
+/// ELFヘッダの大きさ
+const ELF_HEADER_SIZE: usize = 52;
+/// プログラムヘッダの種類: 読み込むセグメント
+const PT_LOAD: u32 = 1;
+/// セグメントの属性: 実行可能
+const PF_X: u32 = 1;
+/// セクションの種類: シンボル表
+const SHT_SYMTAB: u32 = 2;
+/// シンボル表の1項目の大きさ
+const SYMBOL_SIZE: usize = 16;
+
+/// ELFのファイルを解析する（e_machine が isa と異なるか、セグメントが大きさ memory_size のメモリに収まらなければエラー）
+pub fn parse(bytes: &[u8], isa: Isa, memory_size: usize) -> Result<LoadImage, String> {
+    let reader = Reader(bytes);
+    if bytes.get(..4) != Some(b"\x7FELF") {
+        return Err("ELFのファイルではありません".to_string());
+    }
+    if bytes.len() < ELF_HEADER_SIZE {
+        return Err("ELFヘッダが途中で終わっています".to_string());
+    }
+    if reader.u8(4)? != 1 {
+        return Err("32ビットのELF（ELFCLASS32）のみに対応しています".to_string());
+    }
+    if reader.u8(5)? != 1 {
+        return Err("リトルエンディアンのELFのみに対応しています（-EL でコンパイルしてください）".to_string());
+    }
+    if reader.u16(16)? != 2 {
+        return Err("実行ファイル（ET_EXEC）のみに対応しています".to_string());
+    }
+    let machine = reader.u16(18)?;
+    let expected = match isa {
+        Isa::Mips => 8,
+        Isa::Rv32i => 243,
+    };
+    if machine != expected {
+        return Err(format!("{} のELFではありません（e_machine: {}）", isa.name(), machine));
+    }
+    let entry = reader.u32(24)?;
+
+    let (phoff, phentsize, phnum) = (reader.u32(28)? as usize, reader.u16(42)? as usize, reader.u16(44)? as usize);
+    let mut segments = Vec::new();
+    for index in 0..phnum {
+        let header = phoff + index * phentsize;
+        if reader.u32(header)? != PT_LOAD {
+            continue;
+        }
+        let (offset, address) = (reader.u32(header + 4)? as usize, reader.u32(header + 8)?);
+        let (file_size, segment_size) = (reader.u32(header + 16)? as usize, reader.u32(header + 20)? as usize);
+        if file_size > segment_size {
+            return Err(format!("セグメント {} のファイル上の大きさがメモリ上の大きさを超えています", index));
+        }
+        // ファイルの p_memsz のままの大きさを確保しないよう、先にメモリに収まるかを確かめる
+        if address as u64 + segment_size as u64 > memory_size as u64 {
+            return Err(format!("セグメント {}（0x{:08X} から {} バイト）がメモリ（{} バイト）に収まりません", index, address, segment_size, memory_size));
+        }
+        let mut data = reader.bytes(offset, file_size)?.to_vec();
+        data.resize(segment_size, 0);
+        let executable = reader.u32(header + 24)? & PF_X != 0;
+        segments.push(Segment { address, data, executable });
+    }
+    if segments.is_empty() {
+        return Err("読み込むセグメント（PT_LOAD）がありません".to_string());
+    }
+
+    Ok(LoadImage { segments, entry: Some(entry), symbols: read_symbols(&reader)? })
+}
+
+/// .symtab から関数・データ・種類なしのシンボルを読む（未定義・セクション・ファイルのシンボルは除く）
+fn read_symbols(reader: &Reader) -> Result<Vec<Symbol>, String> {
+    let (shoff, shentsize, shnum) = (reader.u32(32)? as usize, reader.u16(46)? as usize, reader.u16(48)? as usize);
+    let section = |index: usize| shoff + index * shentsize;
+    let Some(symtab) = (0..shnum).map(section).find(|&header| reader.u32(header + 4) == Ok(SHT_SYMTAB)) else {
+        return Ok(Vec::new());
+    };
+    let strtab = section(reader.u32(symtab + 24)? as usize);
+    let names = reader.bytes(reader.u32(strtab + 16)? as usize, reader.u32(strtab + 20)? as usize)?;
+    let table = reader.bytes(reader.u32(symtab + 16)? as usize, reader.u32(symtab + 20)? as usize)?;
+
+    let mut symbols = Vec::new();
+    for entry in table.chunks_exact(SYMBOL_SIZE) {
+        let entry = Reader(entry);
+        let (name, address, size) = (entry.u32(0)? as usize, entry.u32(4)?, entry.u32(8)?);
+        let kind = entry.u8(12)? & 0xF;
+        if entry.u16(14)? == 0 || kind > 2 || name == 0 {
+            continue;
+        }
+        let name = names.get(name..)
+            .and_then(|rest| rest.split(|&c| c == 0).next())
+            .ok_or_else(|| "シンボルの名前が文字列表の範囲外です".to_string())?;
+        symbols.push(Symbol { name: String::from_utf8_lossy(name).into_owned(), address, size: (size > 0).then_some(size) });
+    }
+    Ok(symbols)
+}
+
+/// リトルエンディアンの値を範囲を確かめながら読む
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, length: usize) -> Result<&[u8], String> {
+        offset.checked_add(length)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or_else(|| format!("ファイルが途中で終わっています（オフセット 0x{:X} から {} バイト）", offset, length))
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8, String> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes(offset, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+}
+
+/// テスト用の最小限のELF（テキストとデータの2セグメント, シンボル main と table）
+#[cfg(test)]
+pub fn test_elf(text: &[u32]) -> Vec<u8> {
+    let text: Vec<u8> = text.iter().flat_map(|word| word.to_le_bytes()).collect();
+    let data = [1u8, 2, 3, 4];
+    let names = b"\0main\0table\0";
+    let (text_offset, data_offset) = (ELF_HEADER_SIZE + 2 * 32, ELF_HEADER_SIZE + 2 * 32 + text.len());
+    let names_offset = data_offset + data.len();
+    let symbols_offset = names_offset + names.len();
+    let sections_offset = symbols_offset + 3 * SYMBOL_SIZE;
+
+    let mut elf = Vec::new();
+    let push = |elf: &mut Vec<u8>, values: &[u32]| elf.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+    elf.extend(b"\x7FELF\x01\x01\x01\0\0\0\0\0\0\0\0\0");
+    elf.extend(2u16.to_le_bytes());
+    elf.extend(8u16.to_le_bytes());
+    push(&mut elf, &[1, 0x00400000, ELF_HEADER_SIZE as u32, sections_offset as u32, 0]);
+    for value in [ELF_HEADER_SIZE as u16, 32, 2, 40, 4, 0] {
+        elf.extend(value.to_le_bytes());
+    }
+    // テキスト（r-x）と、.bssを含むデータ（rw-）
+    push(&mut elf, &[PT_LOAD, text_offset as u32, 0x00400000, 0x00400000, text.len() as u32, text.len() as u32, 5, 4]);
+    push(&mut elf, &[PT_LOAD, data_offset as u32, 0x00500000, 0x00500000, 4, 8, 6, 4]);
+    elf.extend(&text);
+    elf.extend(data);
+    elf.extend(names);
+    push(&mut elf, &[0, 0, 0, 0]);
+    push(&mut elf, &[1, 0x00400000, text.len() as u32, 0x12 | (1 << 16)]);
+    push(&mut elf, &[6, 0x00500000, 8, 0x11 | (2 << 16)]);
+    // セクション: NULL, .text, .strtab, .symtab
+    push(&mut elf, &[0; 10]);
+    push(&mut elf, &[0, 1, 6, 0x00400000, text_offset as u32, text.len() as u32, 0, 0, 4, 0]);
+    push(&mut elf, &[0, 3, 0, 0, names_offset as u32, names.len() as u32, 0, 0, 1, 0]);
+    push(&mut elf, &[0, SHT_SYMTAB, 0, 0, symbols_offset as u32, 3 * SYMBOL_SIZE as u32, 2, 1, 4, SYMBOL_SIZE as u32]);
+    elf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// シミュレータのデフォルトと同じメモリの大きさ
+    const MEMORY: usize = 16 * 1024 * 1024;
+
+    #[test]
+    fn test_parse_elf() {
+        let elf = test_elf(&[0x2008002A, 0x0000000D]);
+        let image = parse(&elf, Isa::Mips, MEMORY).unwrap();
+        assert_eq!(image.entry, Some(0x00400000));
+        assert_eq!(image.segments, vec![
+            Segment { address: 0x00400000, data: vec![0x2A, 0x00, 0x08, 0x20, 0x0D, 0, 0, 0], executable: true },
+            Segment { address: 0x00500000, data: vec![1, 2, 3, 4, 0, 0, 0, 0], executable: false },
+        ]);
+        assert_eq!(image.symbols, vec![
+            Symbol { name: "main".to_string(), address: 0x00400000, size: Some(8) },
+            Symbol { name: "table".to_string(), address: 0x00500000, size: Some(8) },
+        ]);
+
+        assert!(parse(&elf, Isa::Rv32i, MEMORY).unwrap_err().contains("rv32i"));
+        assert!(parse(&elf[..40], Isa::Mips, MEMORY).is_err());
+        assert!(parse(b"0040000D", Isa::Mips, MEMORY).is_err());
+        let mut big_endian = elf.clone();
+        big_endian[5] = 2;
+        assert!(parse(&big_endian, Isa::Mips, MEMORY).is_err());
+    }
+
+    #[test]
+    fn test_segment_outside_memory() {
+        let elf = test_elf(&[0x0000000D]);
+        // データのセグメント（0x00500000 から8バイト）がちょうど収まる大きさ
+        assert!(parse(&elf, Isa::Mips, 0x00500008).is_ok());
+        assert!(parse(&elf, Isa::Mips, 0x00500007).unwrap_err().contains("収まりません"));
+
+        // p_memsz が4GiB近いセグメントは確保する前に断る
+        let p_memsz = ELF_HEADER_SIZE + 32 + 20;
+        let mut huge = elf.clone();
+        huge[p_memsz..p_memsz + 4].copy_from_slice(&0xFFFFFFF0u32.to_le_bytes());
+        assert!(parse(&huge, Isa::Mips, MEMORY).unwrap_err().contains("収まりません"));
+    }
+}
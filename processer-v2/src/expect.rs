@@ -0,0 +1,183 @@
+//! 実行後の最終状態の検査（コンパイラの回帰テストで、期待するレジスタとメモリの値を自動で確かめるため）
+//!
+//! 1行に1項目を "<レジスタ> = <値>" か "[<アドレス>] = <値> [<値>...]" の形式で書く（'#' 以降はコメント）。
+//! レジスタは $t0・$8・$f2 など、値は10進数か0xで始まる16進数で、負数は2の補数とみなす。
+//! 浮動小数点レジスタには 1.5 のように小数点を含む値も書け、単精度のビット列として比べる。
+//! メモリの値を複数並べると、アドレスから連続するワードとして比べる。
+
+use std::fmt;
+
+use crate::assembler::{parse_fp_register, parse_integer, parse_register};
+use crate::instructions::Word;
+use crate::memory::MemoryAddress;
+use crate::pipeline::FP_REGISTER_BASE;
+
+/// 検査する対象
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpectTarget {
+    /// レジスタ（PipelineOpと同じ番号付け, 指定した名前）
+    Register(u8, String),
+    /// メモリのワード
+    Memory(MemoryAddress),
+}
+
+impl fmt::Display for ExpectTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpectTarget::Register(_, name) => f.pad(name),
+            ExpectTarget::Memory(address) => f.pad(&format!("[0x{:08X}]", address)),
+        }
+    }
+}
+
+/// 期待する値
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expectation {
+    pub target: ExpectTarget,
+    pub value: Word,
+    /// ファイルの行番号（1から）
+    pub line: usize,
+}
+
+/// 期待する最終状態
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedState {
+    expectations: Vec<Expectation>,
+}
+
+impl ExpectedState {
+    /// ファイルを読み込む（アドレスの解析は呼び出し側が行い、シンボル名も使えるようにする）
+    pub fn load<R: Fn(&str) -> Result<MemoryAddress, String>>(path: &str, resolve: R) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("期待する状態のファイル '{}' を開けません: {}", path, e))?;
+        Self::parse(&text, resolve).map_err(|e| format!("期待する状態のファイル '{}' の{}", path, e))
+    }
+
+    /// テキストを解析する
+    pub fn parse<R: Fn(&str) -> Result<MemoryAddress, String>>(text: &str, resolve: R) -> Result<Self, String> {
+        let mut expectations = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_num = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| format!(" {} 行目: {}", line_num, message);
+            let (target, values) = line.split_once('=')
+                .ok_or_else(|| error(format!("<レジスタ> = <値> か [<アドレス>] = <値> の形式で書いてください: {}", line)))?;
+            let target = target.trim();
+            let values: Vec<&str> = values.split_whitespace().collect();
+            if values.is_empty() {
+                return Err(error(format!("値がありません: {}", line)));
+            }
+            if let Some(address) = target.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                let address = resolve(address).map_err(error)?;
+                for (i, value) in values.iter().enumerate() {
+                    let value = parse_value(value, false).map_err(error)?;
+                    let address = address.checked_add(i as u32 * 4)
+                        .ok_or_else(|| error(format!("アドレスが範囲を超えます: {}", line)))?;
+                    expectations.push(Expectation { target: ExpectTarget::Memory(address), value, line: line_num });
+                }
+                continue;
+            }
+            let (register, fp) = match parse_register(target) {
+                Ok(register) => (register, false),
+                Err(_) => parse_fp_register(target)
+                    .map(|fp| (fp + FP_REGISTER_BASE, true))
+                    .map_err(|_| error(format!("レジスタが不正です: {}", target)))?,
+            };
+            let [value] = values.as_slice() else {
+                return Err(error(format!("レジスタの値は1つだけ書いてください: {}", line)));
+            };
+            let value = parse_value(value, fp).map_err(error)?;
+            expectations.push(Expectation { target: ExpectTarget::Register(register, target.to_string()), value, line: line_num });
+        }
+        Ok(Self { expectations })
+    }
+
+    /// 実際の値と比べる（actual は対象の値か、読めなかった理由を返す）
+    pub fn compare<A: Fn(&ExpectTarget) -> Result<Word, String>>(&self, actual: A) -> StateComparison {
+        let mismatches = self.expectations.iter()
+            .filter_map(|expectation| {
+                let value = actual(&expectation.target);
+                (value.as_ref() != Ok(&expectation.value)).then(|| (expectation.clone(), value))
+            })
+            .collect();
+        StateComparison { checked: self.expectations.len(), mismatches }
+    }
+}
+
+/// 値を解析する（浮動小数点レジスタには小数点を含む値も書ける）
+fn parse_value(text: &str, fp: bool) -> Result<Word, String> {
+    let hex = text.starts_with("0x") || text.starts_with("0X");
+    if fp && !hex && text.contains(['.', 'e', 'E']) {
+        return text.parse::<f32>().map(f32::to_bits).map_err(|_| format!("無効な値です: {}", text));
+    }
+    parse_integer(text).ok()
+        .filter(|&value| value >= i32::MIN as i64 && value <= u32::MAX as i64)
+        .map(|value| value as Word)
+        .ok_or_else(|| format!("無効な値です: {}", text))
+}
+
+/// 最終状態の検査結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateComparison {
+    /// 検査した項目数
+    pub checked: usize,
+    /// 異なった項目と実際の値
+    pub mismatches: Vec<(Expectation, Result<Word, String>)>,
+}
+
+impl StateComparison {
+    /// すべて一致したかどうか
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for StateComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_match() {
+            return write!(f, "最終状態は期待した状態と一致しました（{} 項目）", self.checked);
+        }
+        write!(f, "最終状態が期待した状態と {} 項目で異なります（{} 項目中）", self.mismatches.len(), self.checked)?;
+        for (expectation, actual) in &self.mismatches {
+            write!(f, "\n  {:<14} 期待: 0x{:08X} ({:>11})  実際: ", expectation.target, expectation.value, expectation.value as i32)?;
+            match actual {
+                Ok(value) => write!(f, "0x{:08X} ({:>11})", value, *value as i32)?,
+                Err(e) => write!(f, "{}", e)?,
+            }
+            write!(f, "  [{} 行目]", expectation.line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_state() {
+        let resolve = |text: &str| match text.trim() {
+            "result" => Ok(0x1000),
+            text => parse_integer(text).map(|value| value as MemoryAddress).map_err(|e| e.to_string()),
+        };
+        let state = ExpectedState::parse("# 期待値\n$v0 = 55\n$t0 = -1\n$f2 = 1.5\n[result] = 1 0x2  # 配列\n", resolve).unwrap();
+        let comparison = state.compare(|target| match target {
+            ExpectTarget::Register(2, _) => Ok(55),
+            ExpectTarget::Register(8, _) => Ok(0xFFFFFFFF),
+            ExpectTarget::Register(_, _) => Ok(1.5f32.to_bits()),
+            ExpectTarget::Memory(0x1000) => Ok(1),
+            ExpectTarget::Memory(address) => Err(format!("0x{:08X} を読めません", address)),
+        });
+        assert_eq!(comparison.checked, 5);
+        assert_eq!(comparison.mismatches.len(), 1);
+        assert!(comparison.to_string().ends_with("[0x00001004]   期待: 0x00000002 (          2)  実際: 0x00001004 を読めません  [5 行目]"));
+
+        assert!(ExpectedState::parse("$q0 = 1", resolve).is_err());
+        assert!(ExpectedState::parse("$t0 = 1 2", resolve).is_err());
+        assert!(ExpectedState::parse("$t0 = 1.5", resolve).is_err());
+        assert!(ExpectedState::parse("[missing] = 1", resolve).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! メモリイメージの書き出し（exportサブコマンド, シミュレータで実行するのと同じイメージをVivadoのシミュレーションやBRAMの初期値に使うため）
+//!
+//! 読み込んだイメージを先頭のアドレスから1行に1ワードの配列にし、セグメントの間は0で埋める。
+//! ワードはメモリ上の並び（リトルエンディアン）の4バイトを1つの32ビットの値にしたもので、シミュレータが命令として読む値と同じになる。
+//! 形式は Xilinx の .coe（Block Memory Generator）、.mem（XPM・updatemem）、Verilog の $readmemh に対応する。
+
+use crate::loader::LoadImage;
+use crate::memory::MemoryAddress;
+
+/// 書き出す形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryFormat {
+    /// Xilinx の COE ファイル
+    Coe,
+    /// Xilinx の MEM ファイル（先頭に @アドレス を付ける）
+    Mem,
+    /// Verilog の $readmemh で読めるテキスト
+    Readmemh,
+}
+
+impl MemoryFormat {
+    /// 名前から作成
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "coe" => Ok(MemoryFormat::Coe),
+            "mem" => Ok(MemoryFormat::Mem),
+            "readmemh" => Ok(MemoryFormat::Readmemh),
+            _ => Err(format!("書き出す形式は coe・mem・readmemh のいずれかで指定してください: {}", text)),
+        }
+    }
+
+    /// 出力ファイルの拡張子から推定する
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "coe" => Some(MemoryFormat::Coe),
+            "mem" => Some(MemoryFormat::Mem),
+            "memh" | "hex" | "dat" | "txt" => Some(MemoryFormat::Readmemh),
+            _ => None,
+        }
+    }
+
+    /// デフォルトの出力ファイルの拡張子
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MemoryFormat::Coe => "coe",
+            MemoryFormat::Mem => "mem",
+            MemoryFormat::Readmemh => "memh",
+        }
+    }
+}
+
+/// 書き出すセグメント
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MemorySection {
+    /// すべてのセグメント
+    #[default]
+    All,
+    /// 実行可能なセグメント（命令メモリ）
+    Text,
+    /// 実行可能でないセグメント（データメモリ）
+    Data,
+}
+
+impl MemorySection {
+    /// 名前から作成
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "all" => Ok(MemorySection::All),
+            "text" => Ok(MemorySection::Text),
+            "data" => Ok(MemorySection::Data),
+            _ => Err(format!("書き出すセグメントは all・text・data のいずれかで指定してください: {}", text)),
+        }
+    }
+
+    fn includes(&self, executable: bool) -> bool {
+        match self {
+            MemorySection::All => true,
+            MemorySection::Text => executable,
+            MemorySection::Data => !executable,
+        }
+    }
+}
+
+/// 1行に1ワードの配列にしたメモリの内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryWords {
+    /// 先頭のワードのアドレス
+    pub base: MemoryAddress,
+    pub words: Vec<u32>,
+}
+
+impl MemoryWords {
+    /// イメージのうち section に当たるセグメントを base（Noneなら最も小さいアドレス）からのワードの配列にする
+    /// depth を指定すると、そのワード数になるまで0で埋める（収まらなければエラー）
+    pub fn from_image(image: &LoadImage, section: MemorySection, base: Option<MemoryAddress>, depth: Option<usize>) -> Result<Self, String> {
+        let segments: Vec<_> = image.segments.iter()
+            .filter(|segment| section.includes(segment.executable) && !segment.data.is_empty())
+            .collect();
+        let Some(lowest) = segments.iter().map(|segment| segment.address).min() else {
+            return Err("書き出すセグメントがありません".to_string());
+        };
+        let base = base.unwrap_or(lowest & !3);
+        if !base.is_multiple_of(4) {
+            return Err(format!("先頭のアドレスは4の倍数にしてください: 0x{:08X}", base));
+        }
+        if lowest < base {
+            return Err(format!("セグメント 0x{:08X} が先頭のアドレス 0x{:08X} より前にあります", lowest, base));
+        }
+        let end = segments.iter().map(|segment| segment.end()).max().unwrap();
+        let length = (end - base as u64).div_ceil(4) as usize;
+        if let Some(depth) = depth {
+            if length > depth {
+                return Err(format!("イメージ（{} ワード）がメモリの深さ（{} ワード）に収まりません", length, depth));
+            }
+        }
+        let mut bytes = vec![0u8; depth.unwrap_or(length) * 4];
+        for segment in segments {
+            let offset = (segment.address - base) as usize;
+            bytes[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+        }
+        let words = bytes.chunks(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect();
+        Ok(Self { base, words })
+    }
+
+    /// format の形式のテキストにする（comment は先頭にコメントとして書く）
+    pub fn to_text(&self, format: MemoryFormat, comment: &str) -> String {
+        let words: Vec<String> = self.words.iter().map(|word| format!("{:08X}", word)).collect();
+        let mut text = String::new();
+        match format {
+            MemoryFormat::Coe => {
+                for line in comment.lines() {
+                    text.push_str(&format!("; {}\n", line));
+                }
+                text.push_str("memory_initialization_radix=16;\n");
+                text.push_str("memory_initialization_vector=\n");
+                text.push_str(&words.join(",\n"));
+                text.push_str(";\n");
+            }
+            MemoryFormat::Mem | MemoryFormat::Readmemh => {
+                for line in comment.lines() {
+                    text.push_str(&format!("// {}\n", line));
+                }
+                if format == MemoryFormat::Mem {
+                    text.push_str("@00000000\n");
+                }
+                for word in words {
+                    text.push_str(&word);
+                    text.push('\n');
+                }
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::Segment;
+
+    #[test]
+    fn test_memory_words() {
+        let image = LoadImage {
+            segments: vec![
+                Segment { address: 0x00400000, data: vec![0x0A, 0x00, 0x08, 0x20, 0x0D, 0, 0, 0], executable: true },
+                Segment { address: 0x00400010, data: vec![1, 2, 3], executable: false },
+            ],
+            entry: None,
+            symbols: Vec::new(),
+        };
+        let all = MemoryWords::from_image(&image, MemorySection::All, None, None).unwrap();
+        assert_eq!(all, MemoryWords { base: 0x00400000, words: vec![0x2008000A, 0x0000000D, 0, 0, 0x00030201] });
+        assert_eq!(all.to_text(MemoryFormat::Coe, "test"), "; test\nmemory_initialization_radix=16;\nmemory_initialization_vector=\n\
+            2008000A,\n0000000D,\n00000000,\n00000000,\n00030201;\n");
+
+        let data = MemoryWords::from_image(&image, MemorySection::Data, Some(0x00400000), Some(8)).unwrap();
+        assert_eq!(data.words, vec![0, 0, 0, 0, 0x00030201, 0, 0, 0]);
+        let text = MemoryWords::from_image(&image, MemorySection::Text, None, None).unwrap();
+        assert_eq!(text.to_text(MemoryFormat::Mem, ""), "@00000000\n2008000A\n0000000D\n");
+        assert_eq!(text.to_text(MemoryFormat::Readmemh, "a\nb"), "// a\n// b\n2008000A\n0000000D\n");
+
+        assert!(MemoryWords::from_image(&image, MemorySection::All, None, Some(4)).unwrap_err().contains("収まりません"));
+        assert!(MemoryWords::from_image(&image, MemorySection::All, Some(0x00400004), None).is_err());
+        assert_eq!(MemoryFormat::from_extension("COE"), Some(MemoryFormat::Coe));
+        assert!(MemoryFormat::parse("mif").is_err());
+    }
+}
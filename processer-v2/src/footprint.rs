@@ -0,0 +1,134 @@
+//! 触れたメモリの集計（ボードのDRAMをプログラム・ヒープ・フレームバッファにどう割り振るかを決めるため）
+//!
+//! 命令フェッチ・ロード・ストアで一度でも触れたページとキャッシュラインを数え、
+//! sbrkで伸ばしたヒープの最大と、$spが最も下がったときのアドレスを記録する。
+
+use std::collections::{HashMap, HashSet};
+
+use crate::cache::CACHE_LINE_SIZE;
+use crate::memory::MemoryAddress;
+
+/// ページの大きさのデフォルト値（バイト）
+pub const DEFAULT_FOOTPRINT_PAGE: u32 = 4096;
+
+/// 触れたページ・ラインとヒープ・スタックの最大
+#[derive(Debug, Clone)]
+pub struct MemoryFootprint {
+    /// ページの大きさ（2のべき乗）
+    page_size: u32,
+    /// ページの番号ごとに、書き込みがあったか
+    pages: HashMap<u32, bool>,
+    /// キャッシュラインの番号
+    lines: HashSet<u32>,
+    /// ヒープの最大（バイト）
+    peak_heap: u32,
+    /// $spの最小値
+    lowest_sp: Option<MemoryAddress>,
+}
+
+impl MemoryFootprint {
+    /// page_size バイトのページ単位で数える集計を作成（page_size は2のべき乗）
+    pub fn new(page_size: u32) -> Self {
+        debug_assert!(page_size.is_power_of_two());
+        Self { page_size, pages: HashMap::new(), lines: HashSet::new(), peak_heap: 0, lowest_sp: None }
+    }
+
+    /// address から bytes バイトへのアクセスを記録する（ページやラインをまたぐアクセスは両方に数える）
+    pub fn record(&mut self, address: MemoryAddress, bytes: u32, write: bool) {
+        let last = address.saturating_add(bytes.max(1) - 1);
+        for page in address / self.page_size..=last / self.page_size {
+            *self.pages.entry(page).or_default() |= write;
+        }
+        let line_size = CACHE_LINE_SIZE as u32;
+        self.lines.extend(address / line_size..=last / line_size);
+    }
+
+    /// ヒープの大きさを記録する
+    pub fn observe_heap(&mut self, bytes: u32) {
+        self.peak_heap = self.peak_heap.max(bytes);
+    }
+
+    /// $spの値を記録する
+    pub fn observe_sp(&mut self, sp: MemoryAddress) {
+        self.lowest_sp = Some(self.lowest_sp.map_or(sp, |lowest| lowest.min(sp)));
+    }
+
+    /// 記録を消去する
+    pub fn reset(&mut self) {
+        self.pages.clear();
+        self.lines.clear();
+        self.peak_heap = 0;
+        self.lowest_sp = None;
+    }
+
+    /// 触れたページ数
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// 触れたページの合計バイト数（実機で常駐させる必要のある量）
+    pub fn resident_bytes(&self) -> u64 {
+        self.pages.len() as u64 * self.page_size as u64
+    }
+
+    /// 触れたキャッシュライン数
+    pub fn lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// 触れたキャッシュラインの合計バイト数
+    pub fn line_bytes(&self) -> u64 {
+        self.lines.len() as u64 * CACHE_LINE_SIZE as u64
+    }
+
+    /// ヒープの最大（バイト）
+    pub fn peak_heap(&self) -> u32 {
+        self.peak_heap
+    }
+
+    /// スタックの最大（$spの初期値から最も下がった量, バイト）
+    pub fn peak_stack(&self, initial_sp: MemoryAddress) -> u32 {
+        self.lowest_sp.map_or(0, |lowest| initial_sp.saturating_sub(lowest))
+    }
+
+    /// 人が読める形式のレポートを作成する
+    pub fn report(&self, initial_sp: MemoryAddress) -> String {
+        let written = self.pages.values().filter(|&&write| write).count();
+        let mut result = format!("触れたページ: {} ({} バイト単位, {} バイト) 書き込みあり {} / 読み込みのみ {}",
+            self.pages(), self.page_size, self.resident_bytes(), written, self.pages() - written);
+        result.push_str(&format!("\n触れたキャッシュライン: {} ({} バイト単位, {} バイト)", self.lines(), CACHE_LINE_SIZE, self.line_bytes()));
+        result.push_str(&format!("\nヒープの最大: {} バイト", self.peak_heap));
+        result.push_str(&format!("\nスタックの最大: {} バイト", self.peak_stack(initial_sp)));
+        if let Some(lowest) = self.lowest_sp {
+            result.push_str(&format!(" ($spの最小値: 0x{:08X})", lowest));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_footprint() {
+        let mut footprint = MemoryFootprint::new(0x1000);
+        footprint.record(0x00400000, 4, false);
+        footprint.record(0x00400004, 4, false);
+        // ページとラインをまたぐ
+        footprint.record(0x10000FFE, 4, true);
+        footprint.observe_heap(64);
+        footprint.observe_heap(32);
+        footprint.observe_sp(0x7FFFFFF0);
+        footprint.observe_sp(0x7FFFFFC0);
+        assert_eq!((footprint.pages(), footprint.resident_bytes()), (3, 0x3000));
+        assert_eq!(footprint.lines(), 3);
+        assert_eq!(footprint.peak_stack(0x7FFFFFFC), 0x3C);
+        assert_eq!(footprint.report(0x7FFFFFFC), [
+            "触れたページ: 3 (4096 バイト単位, 12288 バイト) 書き込みあり 2 / 読み込みのみ 1",
+            "触れたキャッシュライン: 3 (32 バイト単位, 96 バイト)",
+            "ヒープの最大: 64 バイト",
+            "スタックの最大: 60 バイト ($spの最小値: 0x7FFFFFC0)",
+        ].join("\n"));
+    }
+}
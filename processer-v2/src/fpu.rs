@@ -0,0 +1,565 @@
+//! 浮動小数点ユニット（コプロセッサ1）の実装
+
+use std::fmt;
+
+use crate::instructions::Word;
+
+/// 浮動小数点レジスタ数
+pub const FP_REGISTER_COUNT: usize = 32;
+
+/// FCSRのレジスタ番号（cfc1/ctc1で指定する番号）
+pub const FCSR_REGISTER: u8 = 31;
+
+/// 整数に変換できない値（NaN・範囲外）を変換したときの結果
+pub const INVALID_CONVERSION_RESULT: Word = 0x7FFFFFFF;
+
+/// 単精度浮動小数点数の符号ビット
+pub const SIGN_BIT: Word = 0x80000000;
+
+/// 1.0のビット列
+const ONE: Word = 0x3F800000;
+
+/// 単精度浮動小数点数の仮数部のマスク
+const MANTISSA_MASK: Word = 0x007FFFFF;
+
+/// 単精度浮動小数点数の仮数部のビット数
+const MANTISSA_BITS: u32 = 23;
+
+/// 組み込みの近似テーブルのインデックスのビット数
+pub const DEFAULT_TABLE_BITS: u32 = 10;
+
+/// 近似テーブルを使う場合のデフォルトのニュートン法の反復回数
+pub const DEFAULT_NEWTON_ITERATIONS: u32 = 2;
+
+/// 丸めモード（FCSRの下位2ビット）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+    /// 最近接偶数丸め
+    Nearest,
+    /// ゼロ方向への丸め（切り捨て）
+    TowardZero,
+    /// 正の無限大方向への丸め
+    TowardPositive,
+    /// 負の無限大方向への丸め
+    TowardNegative,
+}
+
+impl RoundingMode {
+    /// FCSRの値から丸めモードを取り出す
+    pub fn from_fcsr(fcsr: Word) -> Self {
+        match fcsr & 0x3 {
+            0 => RoundingMode::Nearest,
+            1 => RoundingMode::TowardZero,
+            2 => RoundingMode::TowardPositive,
+            _ => RoundingMode::TowardNegative,
+        }
+    }
+}
+
+/// 単精度浮動小数点数を指定された丸めモードで32ビット符号付き整数に変換する
+/// NaNや表現できない値の場合は INVALID_CONVERSION_RESULT を返す
+pub fn single_to_word(value: f32, mode: RoundingMode) -> Word {
+    // f32からf64への変換は誤差がないため倍精度の変換を共用する
+    double_to_word(value as f64, mode)
+}
+
+/// 倍精度浮動小数点数を指定された丸めモードで32ビット符号付き整数に変換する
+/// NaNや表現できない値の場合は INVALID_CONVERSION_RESULT を返す
+pub fn double_to_word(value: f64, mode: RoundingMode) -> Word {
+    let rounded = match mode {
+        RoundingMode::Nearest => value.round_ties_even(),
+        RoundingMode::TowardZero => value.trunc(),
+        RoundingMode::TowardPositive => value.ceil(),
+        RoundingMode::TowardNegative => value.floor(),
+    };
+
+    // i32::MAX と比較せず 2^31 未満かで判定する（NaNも範囲外として扱われる）
+    if !(-2147483648.0..2147483648.0).contains(&rounded) {
+        return INVALID_CONVERSION_RESULT;
+    }
+    rounded as i32 as Word
+}
+
+/// 浮動小数点演算のモデル
+/// 値はレジスタと同じビット列で受け渡しし、結果をビット単位で再現できるようにする
+pub trait FpuModel: fmt::Debug {
+    /// モデル名
+    fn name(&self) -> &'static str;
+    /// 加算
+    fn add(&self, a: Word, b: Word) -> Word;
+    /// 減算
+    fn sub(&self, a: Word, b: Word) -> Word;
+    /// 乗算
+    fn mul(&self, a: Word, b: Word) -> Word;
+    /// 除算
+    fn div(&self, a: Word, b: Word) -> Word;
+    /// 平方根
+    fn sqrt(&self, a: Word) -> Word;
+    /// 32ビット符号付き整数から単精度への変換
+    fn int_to_single(&self, a: Word) -> Word;
+    /// 逆数（デフォルトは 1.0 / a の除算）
+    fn inv(&self, a: Word) -> Word {
+        self.div(ONE, a)
+    }
+}
+
+/// IEEE 754に準拠したFPU（ホストのf32演算をそのまま使う）
+#[derive(Debug, Clone, Default)]
+pub struct IeeeFpu;
+
+impl FpuModel for IeeeFpu {
+    fn name(&self) -> &'static str {
+        "ieee"
+    }
+
+    fn add(&self, a: Word, b: Word) -> Word {
+        (f32::from_bits(a) + f32::from_bits(b)).to_bits()
+    }
+
+    fn sub(&self, a: Word, b: Word) -> Word {
+        (f32::from_bits(a) - f32::from_bits(b)).to_bits()
+    }
+
+    fn mul(&self, a: Word, b: Word) -> Word {
+        (f32::from_bits(a) * f32::from_bits(b)).to_bits()
+    }
+
+    fn div(&self, a: Word, b: Word) -> Word {
+        (f32::from_bits(a) / f32::from_bits(b)).to_bits()
+    }
+
+    fn sqrt(&self, a: Word) -> Word {
+        f32::from_bits(a).sqrt().to_bits()
+    }
+
+    fn int_to_single(&self, a: Word) -> Word {
+        (a as i32 as f32).to_bits()
+    }
+}
+
+/// 独自FPUの結果の丸め方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CustomRounding {
+    /// 最近接偶数丸め
+    Nearest,
+    /// 仮数の下位ビットを切り捨てる（ゼロ方向への丸め）
+    Truncate,
+}
+
+/// IEEE 754に完全には準拠していないハードウェアFPUのモデル
+/// 演算はf64で行い（単精度同士の積は正確に表現できる）、f64での丸め誤差も含めて設定に従って単精度へ丸める
+#[derive(Debug, Clone)]
+pub struct CustomFpu {
+    /// 結果の丸め方
+    pub rounding: CustomRounding,
+    /// 非正規化数を符号付きゼロとして扱うかどうか（入力・出力の両方）
+    pub flush_to_zero: bool,
+}
+
+impl Default for CustomFpu {
+    fn default() -> Self {
+        // 実機のFPUは仮数の積を切り捨て、非正規化数をサポートしない
+        Self {
+            rounding: CustomRounding::Truncate,
+            flush_to_zero: true,
+        }
+    }
+}
+
+impl CustomFpu {
+    /// 入力の非正規化数をゼロにする
+    fn flush_input(&self, a: Word) -> f64 {
+        let value = f32::from_bits(a);
+        if self.flush_to_zero && value.is_subnormal() {
+            f32::from_bits(a & SIGN_BIT) as f64
+        } else {
+            value as f64
+        }
+    }
+
+    /// f64の結果を設定に従って単精度に丸める
+    /// residual は正確な値と value の差（value + residual が正確な値）。
+    /// f64への丸めと単精度への丸めを続けて行うと、f64で丸めた値がちょうど単精度の境界や中点になったときに結果がずれるので、
+    /// そのときは residual の符号で正確な値がどちら側にあるかを判断する
+    fn round(&self, value: f64, residual: f64) -> Word {
+        let mut result = value as f32;
+        // 0除算などで誤差が求まらなければ、f64の値を正確な値とみなす
+        let residual = if residual.is_finite() { residual } else { 0.0 };
+        if value.is_finite() && value != 0.0 {
+            // 正確な値の絶対値を超えない最大の単精度の値（ゼロ方向への丸め）
+            let inward = residual != 0.0 && residual.is_sign_negative() != value.is_sign_negative();
+            let mut truncated = result;
+            if (result as f64).abs() > value.abs() || (result as f64 == value && inward) {
+                truncated = f32::from_bits(result.to_bits() - 1);
+            }
+            result = match self.rounding {
+                CustomRounding::Truncate => truncated,
+                CustomRounding::Nearest => {
+                    // f64の値がちょうど中点なら、正確な値は residual の向きにある
+                    let outer = f32::from_bits(truncated.to_bits() + 1);
+                    let midpoint = (truncated as f64 + outer as f64) / 2.0;
+                    match value == midpoint && residual != 0.0 {
+                        true if inward => truncated,
+                        true => outer,
+                        false => result,
+                    }
+                }
+            };
+        }
+        if self.flush_to_zero && result.is_subnormal() {
+            result = f32::from_bits(result.to_bits() & SIGN_BIT);
+        }
+        result.to_bits()
+    }
+
+    /// 和とその丸め誤差（TwoSum）
+    fn two_sum(a: f64, b: f64) -> (f64, f64) {
+        let sum = a + b;
+        let b_virtual = sum - a;
+        let a_virtual = sum - b_virtual;
+        (sum, (a - a_virtual) + (b - b_virtual))
+    }
+}
+
+impl FpuModel for CustomFpu {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    fn add(&self, a: Word, b: Word) -> Word {
+        let (sum, residual) = Self::two_sum(self.flush_input(a), self.flush_input(b));
+        self.round(sum, residual)
+    }
+
+    fn sub(&self, a: Word, b: Word) -> Word {
+        let (difference, residual) = Self::two_sum(self.flush_input(a), -self.flush_input(b));
+        self.round(difference, residual)
+    }
+
+    fn mul(&self, a: Word, b: Word) -> Word {
+        // 単精度同士の積はf64で正確に表現できる
+        self.round(self.flush_input(a) * self.flush_input(b), 0.0)
+    }
+
+    fn div(&self, a: Word, b: Word) -> Word {
+        let (a, b) = (self.flush_input(a), self.flush_input(b));
+        let quotient = a / b;
+        // 剰余 a - quotient * b は融合積和で正確に求まり、その符号から正確な商の向きがわかる
+        self.round(quotient, (-quotient).mul_add(b, a) / b)
+    }
+
+    fn sqrt(&self, a: Word) -> Word {
+        let a = self.flush_input(a);
+        let root = a.sqrt();
+        self.round(root, (-root).mul_add(root, a) / (2.0 * root))
+    }
+
+    fn int_to_single(&self, a: Word) -> Word {
+        self.round(a as i32 as f64, 0.0)
+    }
+}
+
+/// finv/fsqrtの初期値を引く近似テーブル
+/// 各エントリは初期値の単精度ビット列で、エントリ数は2のべき乗
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproxTable {
+    entries: Vec<Word>,
+}
+
+impl ApproxTable {
+    /// エントリ列からテーブルを作成
+    pub fn from_entries(entries: Vec<Word>) -> Result<Self, String> {
+        if entries.len() < 2 || !entries.len().is_power_of_two() || entries.len() > (1 << MANTISSA_BITS) {
+            return Err(format!("テーブルのエントリ数は2以上2^{}以下の2のべき乗である必要があります: {}", MANTISSA_BITS, entries.len()));
+        }
+        Ok(Self { entries })
+    }
+
+    /// 逆数の初期値テーブルを生成する（仮数 [1, 2) の各区間の中点の逆数）
+    pub fn generate_inverse(bits: u32) -> Self {
+        let size = 1usize << bits;
+        let entries = (0..size)
+            .map(|i| (1.0 / (1.0 + (i as f64 + 0.5) / size as f64)) as f32)
+            .map(f32::to_bits)
+            .collect();
+        Self { entries }
+    }
+
+    /// 逆平方根の初期値テーブルを生成する
+    /// 上位1ビットが指数の偶奇で、前半が [1, 2)、後半が [2, 4) の区間に対応する
+    pub fn generate_rsqrt(bits: u32) -> Self {
+        let half = 1usize << (bits - 1);
+        let entries = (0..half * 2)
+            .map(|i| {
+                let m = 1.0 + ((i % half) as f64 + 0.5) / half as f64;
+                let m = if i < half { m } else { m * 2.0 };
+                (1.0 / m.sqrt()) as f32
+            })
+            .map(f32::to_bits)
+            .collect();
+        Self { entries }
+    }
+
+    /// テキストからテーブルを読み込む（1行に1エントリの16進数、'#'以降はコメント）
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for (line_num, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            }.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let hex = line.strip_prefix("0x").unwrap_or(line);
+            let entry = u32::from_str_radix(hex, 16)
+                .map_err(|e| format!("{} 行目: '{}' を16進数に変換できません: {}", line_num + 1, line, e))?;
+            entries.push(entry);
+        }
+        Self::from_entries(entries)
+    }
+
+    /// ファイルからテーブルを読み込む
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("テーブルファイル '{}' を開けません: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("テーブルファイル '{}': {}", path, e))
+    }
+
+    /// インデックスのビット数
+    fn index_bits(&self) -> u32 {
+        self.entries.len().trailing_zeros()
+    }
+}
+
+/// 近似テーブルの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproxConfig {
+    /// finv用の逆数テーブル
+    pub inv_table: ApproxTable,
+    /// fsqrt用の逆平方根テーブル
+    pub rsqrt_table: ApproxTable,
+    /// ニュートン法の反復回数
+    pub iterations: u32,
+}
+
+impl Default for ApproxConfig {
+    fn default() -> Self {
+        Self {
+            inv_table: ApproxTable::generate_inverse(DEFAULT_TABLE_BITS),
+            rsqrt_table: ApproxTable::generate_rsqrt(DEFAULT_TABLE_BITS),
+            iterations: DEFAULT_NEWTON_ITERATIONS,
+        }
+    }
+}
+
+/// 逆数と平方根をRTLと同じ「テーブルによる初期値 + ニュートン法」で計算するFPU
+/// ニュートン法の各演算は内側のモデルで行うので、内側の丸め方もそのまま反映される
+#[derive(Debug)]
+pub struct ApproxFpu {
+    inner: Box<dyn FpuModel>,
+    config: ApproxConfig,
+}
+
+impl ApproxFpu {
+    /// 内側のモデルを包んで近似FPUを作成
+    pub fn new(inner: Box<dyn FpuModel>, config: ApproxConfig) -> Self {
+        Self { inner, config }
+    }
+
+    /// 2^exponent（正規化数の範囲）のビット列
+    fn power_of_two(exponent: i32) -> Word {
+        ((exponent + 127) as Word) << MANTISSA_BITS
+    }
+
+    /// 値に2^exponentを掛ける（2^exponentが正規化数で表せない場合は2回に分ける）
+    fn scale(&self, value: Word, exponent: i32) -> Word {
+        if exponent < -126 {
+            let value = self.inner.mul(value, Self::power_of_two(-126));
+            self.inner.mul(value, Self::power_of_two(exponent + 126))
+        } else {
+            self.inner.mul(value, Self::power_of_two(exponent))
+        }
+    }
+}
+
+impl FpuModel for ApproxFpu {
+    fn name(&self) -> &'static str {
+        "approx"
+    }
+
+    fn add(&self, a: Word, b: Word) -> Word {
+        self.inner.add(a, b)
+    }
+
+    fn sub(&self, a: Word, b: Word) -> Word {
+        self.inner.sub(a, b)
+    }
+
+    fn mul(&self, a: Word, b: Word) -> Word {
+        self.inner.mul(a, b)
+    }
+
+    fn div(&self, a: Word, b: Word) -> Word {
+        self.inner.div(a, b)
+    }
+
+    fn int_to_single(&self, a: Word) -> Word {
+        self.inner.int_to_single(a)
+    }
+
+    fn inv(&self, a: Word) -> Word {
+        // ゼロ・無限大・NaN・非正規化数は内側のモデルに任せる
+        if !f32::from_bits(a).is_normal() {
+            return self.inner.inv(a);
+        }
+        let exponent = ((a >> MANTISSA_BITS) & 0xFF) as i32 - 127;
+        let mantissa = a & MANTISSA_MASK;
+        let m = ONE | mantissa;
+
+        // y_{n+1} = y_n * (2 - m * y_n)
+        let table = &self.config.inv_table;
+        let mut y = table.entries[(mantissa >> (MANTISSA_BITS - table.index_bits())) as usize];
+        for _ in 0..self.config.iterations {
+            let t = self.inner.sub(2.0f32.to_bits(), self.inner.mul(m, y));
+            y = self.inner.mul(y, t);
+        }
+        // 1 / (m * 2^e) = (1 / m) * 2^-e
+        self.scale(y, -exponent) | (a & SIGN_BIT)
+    }
+
+    fn sqrt(&self, a: Word) -> Word {
+        // 負数・ゼロ・無限大・NaN・非正規化数は内側のモデルに任せる
+        let value = f32::from_bits(a);
+        if !value.is_normal() || value < 0.0 {
+            return self.inner.sqrt(a);
+        }
+        let exponent = ((a >> MANTISSA_BITS) & 0xFF) as i32 - 127;
+        let mantissa = a & MANTISSA_MASK;
+        // 指数が奇数なら仮数を2倍して指数を偶数にそろえる（m' は [1, 4)）
+        let odd = exponent.rem_euclid(2) as u32;
+        let m = (ONE | mantissa) + (odd << MANTISSA_BITS);
+
+        // 逆平方根を y_{n+1} = y_n * (1.5 - 0.5 * m' * y_n^2) で求める
+        let table = &self.config.rsqrt_table;
+        let bits = table.index_bits();
+        let index = (odd << (bits - 1)) | (mantissa >> (MANTISSA_BITS - (bits - 1)));
+        let mut y = table.entries[index as usize];
+        for _ in 0..self.config.iterations {
+            let t = self.inner.mul(0.5f32.to_bits(), self.inner.mul(m, self.inner.mul(y, y)));
+            y = self.inner.mul(y, self.inner.sub(1.5f32.to_bits(), t));
+        }
+        // sqrt(m' * 2^e') = m' * rsqrt(m') * 2^(e'/2)
+        let root = self.inner.mul(m, y);
+        self.scale(root, (exponent - odd as i32) / 2)
+    }
+}
+
+/// FPUモデルの設定
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FpuConfig {
+    /// IEEE 754準拠
+    #[default]
+    Ieee,
+    /// 独自FPU
+    Custom { rounding: CustomRounding, flush_to_zero: bool },
+}
+
+impl FpuConfig {
+    /// 設定からFPUモデルを作成
+    pub fn build(&self) -> Box<dyn FpuModel> {
+        match self {
+            FpuConfig::Ieee => Box::new(IeeeFpu),
+            FpuConfig::Custom { rounding, flush_to_zero } => Box::new(CustomFpu {
+                rounding: *rounding,
+                flush_to_zero: *flush_to_zero,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding_modes() {
+        assert_eq!(single_to_word(2.5, RoundingMode::Nearest), 2);
+        assert_eq!(single_to_word(3.5, RoundingMode::Nearest), 4);
+        assert_eq!(single_to_word(-2.7, RoundingMode::TowardZero), (-2i32) as Word);
+        assert_eq!(single_to_word(2.1, RoundingMode::TowardPositive), 3);
+        assert_eq!(single_to_word(-2.1, RoundingMode::TowardNegative), (-3i32) as Word);
+    }
+
+    #[test]
+    fn test_invalid_conversion() {
+        assert_eq!(single_to_word(f32::NAN, RoundingMode::Nearest), INVALID_CONVERSION_RESULT);
+        assert_eq!(single_to_word(3.0e9, RoundingMode::TowardZero), INVALID_CONVERSION_RESULT);
+        assert_eq!(single_to_word(-2147483648.0, RoundingMode::TowardZero), 0x80000000);
+    }
+
+    #[test]
+    fn test_custom_truncated_product() {
+        let ieee = IeeeFpu;
+        let custom = CustomFpu::default();
+        // (1.5 + 2^-23)^2 = 2.25 + 1.5ulp + 2^-46 は最近接丸めでは切り上がる
+        let a = 0x3FC00001;
+        assert_eq!(ieee.mul(a, a), 0x40100002);
+        assert_eq!(custom.mul(a, a), 0x40100001);
+        // 正確に表現できる結果は両者で一致する
+        assert_eq!(custom.mul(2.0f32.to_bits(), 3.5f32.to_bits()), 7.0f32.to_bits());
+    }
+
+    #[test]
+    fn test_custom_truncated_sum() {
+        let custom = CustomFpu::default();
+        // 1.0 - 2^-60 はf64でも1.0に丸まるが、切り捨てでは1.0より小さい最大の単精度になる
+        let tiny = 2.0f32.powi(-60).to_bits();
+        assert_eq!(custom.sub(1.0f32.to_bits(), tiny), 0x3F7FFFFF);
+        assert_eq!(custom.add((-1.0f32).to_bits(), tiny), 0xBF7FFFFF);
+        assert_eq!(custom.add(1.0f32.to_bits(), tiny), 1.0f32.to_bits());
+        let nearest = CustomFpu { rounding: CustomRounding::Nearest, ..CustomFpu::default() };
+        assert_eq!(nearest.sub(1.0f32.to_bits(), tiny), 1.0f32.to_bits());
+        // 割り切れない商も切り捨てる（1/3 は最近接丸めでは切り上がる）
+        assert_eq!(custom.div(1.0f32.to_bits(), 3.0f32.to_bits()), 0x3EAAAAAA);
+        assert_eq!(custom.div(0, f32::INFINITY.to_bits()), 0);
+        assert_eq!(custom.sqrt(0), 0);
+    }
+
+    #[test]
+    fn test_approx_inverse_and_sqrt() {
+        let approx = ApproxFpu::new(Box::new(IeeeFpu), ApproxConfig::default());
+        for &x in &[1.0f32, 3.0, 0.1, -7.5, 1.0e20, 1.5e38] {
+            let inv = f32::from_bits(approx.inv(x.to_bits()));
+            assert!(((inv - 1.0 / x) / (1.0 / x)).abs() < 1e-6, "finv({})", x);
+        }
+        for &x in &[1.0f32, 2.0, 10.0, 0.03, 1.0e-20] {
+            let root = f32::from_bits(approx.sqrt(x.to_bits()));
+            assert!(((root - x.sqrt()) / x.sqrt()).abs() < 1e-6, "fsqrt({})", x);
+        }
+        assert!(f32::from_bits(approx.sqrt((-1.0f32).to_bits())).is_nan());
+    }
+
+    #[test]
+    fn test_approx_table_iterations() {
+        // 反復なしではテーブルの初期値がそのまま結果になる
+        let config = ApproxConfig { iterations: 0, ..ApproxConfig::default() };
+        let approx = ApproxFpu::new(Box::new(IeeeFpu), config.clone());
+        assert_eq!(approx.inv(1.0f32.to_bits()), config.inv_table.entries[0]);
+
+        assert!(ApproxTable::parse("3F800000\n3F000000\n3E800000\n").is_err());
+        assert_eq!(ApproxTable::parse("# 初期値\n0x3F800000\n3F000000\n").unwrap().entries.len(), 2);
+    }
+
+    #[test]
+    fn test_custom_flush_to_zero() {
+        let custom = CustomFpu::default();
+        let denormal = f32::from_bits(0x00000001);
+        // 非正規化数の入力はゼロとして扱われる
+        assert_eq!(custom.add(denormal.to_bits(), 0), 0);
+        // 非正規化数になる結果はゼロになる（符号は保たれる）
+        let tiny = f32::MIN_POSITIVE.to_bits();
+        assert_eq!(custom.mul(tiny, (-0.5f32).to_bits()), SIGN_BIT);
+        assert!(f32::from_bits(IeeeFpu.mul(tiny, 0.5f32.to_bits())).is_subnormal());
+    }
+}
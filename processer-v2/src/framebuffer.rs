@@ -0,0 +1,337 @@
+//! メモリマップドのフレームバッファ（レイトレーサの出力を、メモリダンプを手で加工せずに画像として見るため）
+//!
+//! 画素は左上から行ごとに並べ、その直後のワード境界に制御レジスタを置く。
+//! 制御レジスタに書き込むとその時点の内容を画像として書き出し、読み込むとそれまでに書き出したフレーム数を返す。
+//! 画像の形式は書き出し先の拡張子で決まる（.ppm はバイナリのPPM、.png は無圧縮のPNG）。
+
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::memory::MemoryAddress;
+use crate::mmio::MmioDevice;
+
+/// 画素の形式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PixelFormat {
+    /// 1画素1ワード（0x00RRGGBB）
+    #[default]
+    Rgb888,
+    /// 1画素1ハーフワード（上位から赤5ビット・緑6ビット・青5ビット）
+    Rgb565,
+    /// 1画素1バイトのグレースケール
+    Gray8,
+}
+
+impl PixelFormat {
+    /// 名前から作成（rgb888 / rgb565 / gray8）
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "rgb888" => Ok(PixelFormat::Rgb888),
+            "rgb565" => Ok(PixelFormat::Rgb565),
+            "gray8" => Ok(PixelFormat::Gray8),
+            other => Err(format!("無効な画素の形式です: {}（rgb888, rgb565, gray8）", other)),
+        }
+    }
+
+    /// 1画素のバイト数
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb888 => 4,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+
+    /// メモリ上の1画素（リトルエンディアン）をRGBに変換する
+    fn to_rgb(self, bytes: &[u8]) -> [u8; 3] {
+        match self {
+            PixelFormat::Rgb888 => [bytes[2], bytes[1], bytes[0]],
+            PixelFormat::Rgb565 => {
+                let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+                let (r, g, b) = ((value >> 11) & 0x1F, (value >> 5) & 0x3F, value & 0x1F);
+                [(r * 255 / 31) as u8, (g * 255 / 63) as u8, (b * 255 / 31) as u8]
+            }
+            PixelFormat::Gray8 => [bytes[0]; 3],
+        }
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PixelFormat::Rgb888 => "rgb888",
+            PixelFormat::Rgb565 => "rgb565",
+            PixelFormat::Gray8 => "gray8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// フレームバッファの設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramebufferConfig {
+    /// 先頭アドレス
+    pub address: MemoryAddress,
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+    /// 終了時に書き出す画像のファイル（制御レジスタへの書き込みでは <名前>-<フレーム番号>.<拡張子> に書き出す）
+    pub output: String,
+}
+
+impl FramebufferConfig {
+    /// 画素データのバイト数
+    pub fn pixel_bytes(&self) -> usize {
+        self.width * self.height * self.format.bytes_per_pixel()
+    }
+
+    /// 制御レジスタのオフセット（画素データの直後のワード境界）
+    pub fn control_offset(&self) -> usize {
+        self.pixel_bytes().next_multiple_of(4)
+    }
+
+    /// 割り当てるアドレス範囲（画素データと制御レジスタ）
+    pub fn range(&self) -> Result<Range<MemoryAddress>, String> {
+        (self.address as usize).checked_add(self.control_offset() + 4)
+            .filter(|&end| end <= 1 << 32)
+            .map(|end| self.address..end as MemoryAddress)
+            .ok_or_else(|| format!("フレームバッファがアドレス空間に収まりません: 0x{:08X}", self.address))
+    }
+}
+
+/// フレームバッファの内容
+#[derive(Debug)]
+pub struct Framebuffer {
+    config: FramebufferConfig,
+    pixels: Vec<u8>,
+    /// 制御レジスタへの書き込みで書き出したフレーム数
+    frames: u32,
+    /// 最初に起きた書き出しのエラー（実行は止めず、終了時に報告する）
+    error: Option<String>,
+}
+
+impl Framebuffer {
+    pub fn new(config: FramebufferConfig) -> Self {
+        let pixels = vec![0; config.pixel_bytes()];
+        Self { config, pixels, frames: 0, error: None }
+    }
+
+    pub fn config(&self) -> &FramebufferConfig {
+        &self.config
+    }
+
+    /// 制御レジスタへの書き込みで書き出したフレーム数
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// 画素をRGBの並びに変換する
+    fn rgb(&self) -> Vec<u8> {
+        self.pixels.chunks(self.config.format.bytes_per_pixel())
+            .flat_map(|pixel| self.config.format.to_rgb(pixel))
+            .collect()
+    }
+
+    /// 今の内容を画像として書き出す（形式は拡張子で決める）
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        let (width, height, rgb) = (self.config.width, self.config.height, self.rgb());
+        let bytes = match image_format(path)? {
+            ImageFormat::Ppm => encode_ppm(width, height, &rgb),
+            ImageFormat::Png => encode_png(width, height, &rgb),
+        };
+        std::fs::write(path, bytes).map_err(|e| format!("画像 '{}' を書き出せません: {}", path, e))
+    }
+
+    /// 制御レジスタへの書き込みで、フレーム番号を付けたファイルに書き出す
+    fn export_frame(&mut self) {
+        let path = Path::new(&self.config.output);
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("framebuffer");
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("ppm");
+        let frame_path = path.with_file_name(format!("{}-{}.{}", stem, self.frames, extension));
+        if let Err(e) = self.export(&frame_path.to_string_lossy()) {
+            self.error.get_or_insert(e);
+        }
+        self.frames += 1;
+    }
+
+    /// 終了時の内容を書き出す（制御レジスタへの書き込みで起きたエラーがあればそれを返す）
+    pub fn finish(&mut self) -> Result<(), String> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.export(&self.config.output)
+    }
+}
+
+/// メモリに割り当てるフレームバッファのデバイス（内容はシミュレータと共有し、終了時に書き出す）
+#[derive(Debug)]
+pub struct FramebufferDevice(pub Rc<RefCell<Framebuffer>>);
+
+impl MmioDevice for FramebufferDevice {
+    fn name(&self) -> &'static str {
+        "framebuffer"
+    }
+
+    fn read_byte(&mut self, offset: u32) -> u8 {
+        self.peek_byte(offset)
+    }
+
+    fn write_byte(&mut self, offset: u32, value: u8) {
+        let mut framebuffer = self.0.borrow_mut();
+        let offset = offset as usize;
+        let control = framebuffer.config.control_offset();
+        if offset < framebuffer.pixels.len() {
+            framebuffer.pixels[offset] = value;
+        } else if offset == control {
+            // ワードの書き込みは下位バイトから順に届くので、先頭のバイトで1回だけ書き出す
+            framebuffer.export_frame();
+        }
+    }
+
+    fn peek_byte(&self, offset: u32) -> u8 {
+        let framebuffer = self.0.borrow();
+        let offset = offset as usize;
+        let control = framebuffer.config.control_offset();
+        if offset < framebuffer.pixels.len() {
+            framebuffer.pixels[offset]
+        } else if offset >= control {
+            (framebuffer.frames >> ((offset - control) * 8)) as u8
+        } else {
+            0
+        }
+    }
+}
+
+/// 書き出す画像の形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+/// ファイルの拡張子から画像の形式を決める
+fn image_format(path: &str) -> Result<ImageFormat, String> {
+    match Path::new(path).extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("ppm") => Ok(ImageFormat::Ppm),
+        Some("png") => Ok(ImageFormat::Png),
+        _ => Err(format!("画像のファイル名は .ppm か .png で指定してください: {}", path)),
+    }
+}
+
+/// 書き出し先のファイル名を確かめる
+pub fn check_output_path(path: &str) -> Result<(), String> {
+    image_format(path).map(|_| ())
+}
+
+/// バイナリのPPM（P6）に変換する
+fn encode_ppm(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    bytes.extend_from_slice(rgb);
+    bytes
+}
+
+/// 無圧縮のPNG（8ビットRGB）に変換する
+fn encode_png(width: usize, height: usize, rgb: &[u8]) -> Vec<u8> {
+    // 各行の先頭にフィルタなし（0）を置く
+    let mut raw = Vec::with_capacity(rgb.len() + height);
+    for row in rgb.chunks(width * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    // zlibのストアードブロック（1ブロック65535バイトまで）
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        let length = block.len() as u16;
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", header.as_slice()), (b"IDAT", zlib.as_slice()), (b"IEND", &[])] {
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = bytes.len();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(data);
+        let crc = crc32(&bytes[start..]);
+        bytes.extend_from_slice(&crc.to_be_bytes());
+    }
+    bytes
+}
+
+/// PNGのチャンクのCRC-32
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// zlibのAdler-32
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_encoding() {
+        assert_eq!(encode_ppm(2, 1, &[1, 2, 3, 4, 5, 6]), b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+        let png = encode_png(1, 1, &[0xFF, 0, 0]);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x01\0\0\0\x01\x08\x02"));
+        // IENDチャンクのCRCは固定値
+        assert!(png.ends_with(b"IEND\xAE\x42\x60\x82"));
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(PixelFormat::Rgb565.to_rgb(&0xF800u16.to_le_bytes()), [255, 0, 0]);
+    }
+
+    #[test]
+    fn test_framebuffer_device() {
+        let dir = std::env::temp_dir();
+        let output = dir.join(format!("mips_simulator_framebuffer_{}.ppm", std::process::id()));
+        let config = FramebufferConfig {
+            address: 0x10000000,
+            width: 3,
+            height: 1,
+            format: PixelFormat::Gray8,
+            output: output.to_string_lossy().into_owned(),
+        };
+        assert_eq!(config.range(), Ok(0x10000000..0x10000008));
+        let framebuffer = Rc::new(RefCell::new(Framebuffer::new(config)));
+        let mut device = FramebufferDevice(framebuffer.clone());
+        device.write_word(0, 0x00302010);
+        // 制御レジスタへの書き込みで番号付きのフレームを書き出す
+        device.write_word(4, 1);
+        assert_eq!(device.read_word(4), 1);
+        device.write_byte(2, 0xFF);
+        framebuffer.borrow_mut().finish().unwrap();
+
+        let frame = dir.join(format!("mips_simulator_framebuffer_{}-0.ppm", std::process::id()));
+        assert_eq!(std::fs::read(&frame).unwrap(), b"P6\n3 1\n255\n\x10\x10\x10\x20\x20\x20\x30\x30\x30");
+        assert!(std::fs::read(&output).unwrap().ends_with(b"\x20\x20\x20\xFF\xFF\xFF"));
+        std::fs::remove_file(frame).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! ゲストプログラムに渡す設定ブロック（環境変数）
+//!
+//! ブロックは "KEY=VALUE\0" を並べ、最後に空文字列 "\0" を置いた形式で、
+//! ENV_BLOCK_ADDRESS（または設定したアドレス）に配置する。
+
+use std::fs;
+use std::path::Path;
+
+use crate::memory::MemoryAddress;
+
+/// 設定ブロックを配置するデフォルトのアドレス（16MBメモリの末尾64KB）
+pub const ENV_BLOCK_ADDRESS: MemoryAddress = 0x00FF0000;
+
+/// getenv: $a0のキー文字列に対応する値の文字列のアドレスを返す（なければ0）
+pub const SYSCALL_GETENV: u32 = 100;
+
+/// getenv_int: $a0のキー文字列に対応する値を整数として返す（なければ$a1）
+pub const SYSCALL_GETENV_INT: u32 = 101;
+
+/// ゲストプログラムに渡す設定
+#[derive(Debug, Clone, Default)]
+pub struct GuestEnv {
+    entries: Vec<(String, String)>,
+}
+
+impl GuestEnv {
+    /// 設定を追加する（同じキーがあれば上書きする）
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => self.entries.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// "KEY=VALUE" 形式の文字列から設定を追加する
+    pub fn parse_assignment(&mut self, text: &str) -> Result<(), String> {
+        let (key, value) = text.split_once('=')
+            .ok_or_else(|| format!("KEY=VALUE の形式で指定してください: {}", text))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("キーが空です: {}", text));
+        }
+        if key.contains('\0') || value.contains('\0') {
+            return Err(format!("NUL文字は使えません: {}", text));
+        }
+        self.set(key, value.trim());
+        Ok(())
+    }
+
+    /// 1行に1つ "KEY=VALUE" を書いたファイルから設定を読み込む（'#'で始まる行はコメント）
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("設定ファイルを開けません: {}", e))?;
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.parse_assignment(line)
+                .map_err(|e| format!("設定ファイルの {} 行目: {}", line_num + 1, e))?;
+        }
+        Ok(())
+    }
+
+    /// 設定が空かどうか
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// メモリに配置するバイト列に変換する
+    pub fn to_block(&self) -> Vec<u8> {
+        let mut block = Vec::new();
+        for (key, value) in &self.entries {
+            block.extend_from_slice(key.as_bytes());
+            block.push(b'=');
+            block.extend_from_slice(value.as_bytes());
+            block.push(0);
+        }
+        block.push(0);
+        block
+    }
+}
+
+/// ブロックからキーを探し、値の先頭のオフセットを返す
+pub fn find_value(block: &[u8], key: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while offset < block.len() && block[offset] != 0 {
+        let end = block[offset..].iter().position(|&b| b == 0).map_or(block.len(), |p| offset + p);
+        let entry = &block[offset..end];
+        if entry.len() > key.len() && entry.starts_with(key) && entry[key.len()] == b'=' {
+            return Some(offset + key.len() + 1);
+        }
+        offset = end + 1;
+    }
+    None
+}
+
+/// オフセットから NUL までの値を取り出す
+pub fn value_at(block: &[u8], offset: usize) -> &[u8] {
+    let rest = &block[offset.min(block.len())..];
+    let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+    &rest[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_block_lookup() {
+        let mut env = GuestEnv::default();
+        env.parse_assignment("WIDTH=128").unwrap();
+        env.parse_assignment("DEPTH = 0x10").unwrap();
+        env.parse_assignment("WIDTH=256").unwrap();
+        assert!(env.parse_assignment("novalue").is_err());
+
+        let block = env.to_block();
+        assert_eq!(block, b"WIDTH=256\0DEPTH=0x10\0\0");
+        let offset = find_value(&block, b"DEPTH").unwrap();
+        assert_eq!(value_at(&block, offset), b"0x10");
+        // キーの前方一致では見つからない
+        assert_eq!(find_value(&block, b"WID"), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! メモリアクセスのヒートマップ（シーンデータとスタックのどちらがメモリの読み書きの大半を占めるかを見るため）
+//!
+//! ロード・ストアの回数を、境界に揃えた一定の大きさのブロックごとに数える（命令フェッチは含まない）。
+//! アクセスは先頭アドレスを含むブロックに数え、倍精度のロード・ストアは2回に数える。
+
+use std::collections::HashMap;
+
+use crate::memory::MemoryAddress;
+
+/// ブロックの大きさのデフォルト値（バイト）
+pub const DEFAULT_HEATMAP_BLOCK: u32 = 4096;
+
+/// 表示するブロック数のデフォルト値
+pub const DEFAULT_HEATMAP_TOP: usize = 10;
+
+/// CSVの見出し行
+const HEATMAP_CSV_HEADER: &str = "start,end,reads,writes,total";
+
+/// ブロックごとの読み書きの回数
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BlockCounts {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl BlockCounts {
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+}
+
+/// ブロックごとのメモリアクセスの回数
+#[derive(Debug, Clone)]
+pub struct MemoryHeatmap {
+    /// ブロックの大きさ（2のべき乗）
+    block_size: u32,
+    /// ブロックの番号ごとの回数
+    blocks: HashMap<u32, BlockCounts>,
+    /// 記録したアクセス数の合計
+    total: u64,
+}
+
+impl MemoryHeatmap {
+    /// block_size バイトごとに数えるヒートマップを作成（block_size は2のべき乗）
+    pub fn new(block_size: u32) -> Self {
+        debug_assert!(block_size.is_power_of_two());
+        Self { block_size, blocks: HashMap::new(), total: 0 }
+    }
+
+    /// 1回のアクセスを記録する
+    pub fn record(&mut self, address: MemoryAddress, write: bool) {
+        let counts = self.blocks.entry(address / self.block_size).or_default();
+        if write {
+            counts.writes += 1;
+        } else {
+            counts.reads += 1;
+        }
+        self.total += 1;
+    }
+
+    /// 記録を消去する
+    pub fn reset(&mut self) {
+        self.blocks.clear();
+        self.total = 0;
+    }
+
+    /// ブロックの先頭アドレス
+    fn start(&self, block: u32) -> MemoryAddress {
+        block * self.block_size
+    }
+
+    /// アクセスの多い順に上位count件（先頭アドレス, 回数）
+    pub fn top(&self, count: usize) -> Vec<(MemoryAddress, BlockCounts)> {
+        let mut entries: Vec<(MemoryAddress, BlockCounts)> = self.blocks.iter()
+            .map(|(&block, &counts)| (self.start(block), counts))
+            .collect();
+        entries.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then(a.0.cmp(&b.0)));
+        entries.truncate(count);
+        entries
+    }
+
+    /// 人が読める形式のレポートを作成する（ブロックの先頭アドレスの表示方法は呼び出し側が決める）
+    pub fn report<A: Fn(MemoryAddress) -> String>(&self, count: usize, format_address: A) -> String {
+        let mut result = format!("ロード・ストア数: {} (ブロック: {} バイト × {})", self.total, self.block_size, self.blocks.len());
+        for (start, counts) in self.top(count) {
+            let percent = counts.total() as f64 / self.total as f64 * 100.0;
+            result.push_str(&format!("\n  {:>12} ({:>6.2}%) 読み込み {:>12} / 書き込み {:>12}  {}",
+                counts.total(), percent, counts.reads, counts.writes, format_address(start)));
+        }
+        result
+    }
+
+    /// アクセスのあったすべてのブロックをアドレス順にCSVで書き出し、書き出したブロック数を返す
+    pub fn write_csv(&self, path: &str) -> Result<usize, String> {
+        let mut blocks: Vec<(&u32, &BlockCounts)> = self.blocks.iter().collect();
+        blocks.sort_by_key(|(&block, _)| block);
+        let mut text = format!("{}\n", HEATMAP_CSV_HEADER);
+        for (&block, counts) in &blocks {
+            let start = self.start(block) as u64;
+            text.push_str(&format!("0x{:08X},0x{:08X},{},{},{}\n",
+                start, start + self.block_size as u64, counts.reads, counts.writes, counts.total()));
+        }
+        std::fs::write(path, text).map_err(|e| format!("ヒートマップ '{}' を書き出せません: {}", path, e))?;
+        Ok(blocks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_heatmap() {
+        let mut heatmap = MemoryHeatmap::new(0x1000);
+        heatmap.record(0x1004, false);
+        heatmap.record(0x1FFC, true);
+        heatmap.record(0x7FFFF000, true);
+        assert_eq!(heatmap.top(1), vec![(0x1000, BlockCounts { reads: 1, writes: 1 })]);
+        assert!(heatmap.report(2, |address| format!("0x{:08X}", address))
+            .ends_with("( 33.33%) 読み込み            0 / 書き込み            1  0x7FFFF000"));
+
+        let path = std::env::temp_dir().join(format!("mips_simulator_heatmap_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(heatmap.write_csv(path), Ok(2));
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(text, "start,end,reads,writes,total\n0x00001000,0x00002000,1,1,2\n0x7FFFF000,0x80000000,0,1,1\n");
+    }
+}
@@ -0,0 +1,101 @@
+//! 直前に実行した命令の履歴（エラーで止まったときに、そこへ至った経緯を表示する）
+
+use std::collections::VecDeque;
+
+use crate::instructions::{Instruction, Word};
+use crate::memory::MemoryAddress;
+
+/// 履歴に残す命令数のデフォルト値
+pub const DEFAULT_HISTORY_LENGTH: usize = 32;
+
+/// 実行した1命令の記録
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// 命令のアドレス
+    pub pc: MemoryAddress,
+    /// 命令語
+    pub instruction: Instruction,
+    /// 実行前に読んだレジスタの値（レジスタ番号は PipelineOp の sources と同じ番号付け）
+    pub sources: Vec<(u8, Word)>,
+    /// 実行後に書いたレジスタの値（例外で完了しなかった命令はNone）
+    pub dest: Option<(u8, Word)>,
+}
+
+/// 直前に実行した命令のリングバッファ
+#[derive(Debug, Clone)]
+pub struct ExecutionHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl ExecutionHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// 実行する命令を記録する（最も古い記録を捨てる）
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// 最後に記録した命令が書いたレジスタの値を記録する
+    pub fn set_last_dest(&mut self, dest: Option<(u8, Word)>) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.dest = dest;
+        }
+    }
+
+    /// 記録した命令（古い順）
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    /// 記録を消去する
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 人が読める形式のダンプを作成する（命令とレジスタの表示方法は呼び出し側が決める）
+    pub fn report<D, R>(&self, describe: D, register_name: R) -> String
+    where
+        D: Fn(MemoryAddress, Instruction) -> String,
+        R: Fn(u8) -> String,
+    {
+        let mut result = format!("直前に実行した命令（古い順、最大 {} 命令）:", self.capacity);
+        for entry in self.entries() {
+            let mut line = format!("{:<40}", describe(entry.pc, entry.instruction));
+            for (reg, value) in &entry.sources {
+                line.push_str(&format!(" {}=0x{:08X}", register_name(*reg), value));
+            }
+            if let Some((reg, value)) = entry.dest {
+                line.push_str(&format!(" -> {}=0x{:08X}", register_name(reg), value));
+            }
+            result.push_str(&format!("\n  {}", line.trim_end()));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_ring_buffer() {
+        let mut history = ExecutionHistory::new(2);
+        for pc in [0x100, 0x104, 0x108] {
+            history.push(HistoryEntry { pc, instruction: 0, sources: vec![(8, pc)], dest: None });
+            history.set_last_dest(Some((9, pc + 1)));
+        }
+        let pcs: Vec<MemoryAddress> = history.entries().map(|entry| entry.pc).collect();
+        assert_eq!(pcs, vec![0x104, 0x108]);
+
+        let report = history.report(|pc, _| format!("0x{:X}: nop", pc), |reg| format!("${}", reg));
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "直前に実行した命令（古い順、最大 2 命令）:");
+        assert_eq!(lines[2], format!("  0x108: nop{} $8=0x00000108 -> $9=0x00000109", " ".repeat(30)));
+    }
+}
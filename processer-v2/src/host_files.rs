@@ -0,0 +1,124 @@
+//! ファイル入出力システムコール（open/read/write/close）とホストのファイルの橋渡し
+//!
+//! ゲストが開けるのはサンドボックスに指定したディレクトリの中のファイルだけで、
+//! 絶対パスや `..` を含むパスは拒否する。サンドボックスを指定しなければファイルは開けない。
+//! ファイル記述子0〜2（標準入出力）はプロセッサ側で扱い、ここでは3以降を割り当てる。
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::instructions::Word;
+
+/// ゲストのファイルに割り当てる最初のファイル記述子
+const FIRST_FILE_DESCRIPTOR: Word = 3;
+
+/// openのフラグ: 読み込み専用
+pub const OPEN_READ: Word = 0;
+
+/// openのフラグ: 書き込み専用（作成して切り詰める）
+pub const OPEN_WRITE: Word = 1;
+
+/// openのフラグ: 書き込み専用（作成して末尾に追加する）
+pub const OPEN_APPEND: Word = 9;
+
+/// ゲストが開いているホストのファイル
+#[derive(Debug, Default)]
+pub struct HostFiles {
+    /// ファイルを置くディレクトリ（Noneならファイルを開けない）
+    sandbox: Option<PathBuf>,
+    /// ファイル記述子ごとの開いているファイル
+    files: BTreeMap<Word, File>,
+}
+
+impl HostFiles {
+    /// サンドボックスのディレクトリを設定する
+    pub fn set_sandbox(&mut self, sandbox: Option<PathBuf>) {
+        self.sandbox = sandbox;
+    }
+
+    /// ゲストが指定したファイル名をサンドボックス内のパスに変換する
+    fn resolve(&self, name: &[u8]) -> Result<PathBuf, String> {
+        let sandbox = self.sandbox.as_ref()
+            .ok_or("ファイルのサンドボックスが指定されていません（--file-sandbox）")?;
+        let name = std::str::from_utf8(name).map_err(|_| "ファイル名がUTF-8ではありません".to_string())?;
+        let path = Path::new(name);
+        if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(format!("サンドボックスの外は開けません: {}", name));
+        }
+        Ok(sandbox.join(path))
+    }
+
+    /// ファイルを開き、ファイル記述子を返す
+    pub fn open(&mut self, name: &[u8], flags: Word) -> Result<Word, String> {
+        let path = self.resolve(name)?;
+        let mut options = OpenOptions::new();
+        match flags {
+            OPEN_READ => options.read(true),
+            OPEN_WRITE => options.write(true).create(true).truncate(true),
+            OPEN_APPEND => options.append(true).create(true),
+            _ => return Err(format!("未対応のフラグです: {}", flags)),
+        };
+        let file = options.open(&path).map_err(|e| format!("'{}' を開けません: {}", path.display(), e))?;
+        let fd = (FIRST_FILE_DESCRIPTOR..).find(|fd| !self.files.contains_key(fd))
+            .ok_or("ファイル記述子が足りません")?;
+        self.files.insert(fd, file);
+        Ok(fd)
+    }
+
+    fn file(&mut self, fd: Word) -> Result<&mut File, String> {
+        self.files.get_mut(&fd).ok_or_else(|| format!("無効なファイル記述子です: {}", fd))
+    }
+
+    /// 最大max_bytesバイトを読み込む（ファイルの終わりなら空）
+    pub fn read(&mut self, fd: Word, max_bytes: usize) -> Result<Vec<u8>, String> {
+        let mut buffer = Vec::new();
+        self.file(fd)?.take(max_bytes as u64).read_to_end(&mut buffer).map_err(|e| e.to_string())?;
+        Ok(buffer)
+    }
+
+    /// バイト列を書き込む
+    pub fn write(&mut self, fd: Word, bytes: &[u8]) -> Result<(), String> {
+        self.file(fd)?.write_all(bytes).map_err(|e| e.to_string())
+    }
+
+    /// ファイルを閉じる
+    pub fn close(&mut self, fd: Word) -> Result<(), String> {
+        self.files.remove(&fd).map(drop).ok_or_else(|| format!("無効なファイル記述子です: {}", fd))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandboxed_files() {
+        let dir = std::env::temp_dir().join(format!("mips_simulator_files_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut files = HostFiles::default();
+        assert!(files.open(b"result.txt", OPEN_WRITE).is_err());
+        files.set_sandbox(Some(dir.clone()));
+
+        let fd = files.open(b"result.txt", OPEN_WRITE).unwrap();
+        assert_eq!(fd, FIRST_FILE_DESCRIPTOR);
+        files.write(fd, b"1 2 3\n").unwrap();
+        files.close(fd).unwrap();
+        let fd = files.open(b"./result.txt", OPEN_APPEND).unwrap();
+        files.write(fd, b"4\n").unwrap();
+        files.close(fd).unwrap();
+        assert!(files.close(fd).is_err());
+
+        let fd = files.open(b"result.txt", OPEN_READ).unwrap();
+        assert_eq!(files.read(fd, 4).unwrap(), b"1 2 ");
+        assert_eq!(files.read(fd, 100).unwrap(), b"3\n4\n");
+        assert!(files.read(fd, 100).unwrap().is_empty());
+        files.close(fd).unwrap();
+
+        // サンドボックスの外は開けない
+        assert!(files.open(b"../escape.txt", OPEN_WRITE).is_err());
+        assert!(files.open(b"/etc/passwd", OPEN_READ).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+//! ゲストプログラムの入力（システムコールによる入力）の読み込み元
+//!
+//! デフォルトは標準入力で、`--stdin` でファイルに置き換えられる。
+//! 標準入力はステップ実行のコマンドと共有するため、読み込むたびにロックする。
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Write};
+
+/// ゲストプログラムの入力元
+#[derive(Default)]
+pub struct GuestInput {
+    /// 読み込み元（Noneなら標準入力）
+    reader: Option<Box<dyn BufRead>>,
+}
+
+impl fmt::Debug for GuestInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = if self.reader.is_some() { "redirect" } else { "stdin" };
+        f.debug_struct("GuestInput").field("source", &source).finish()
+    }
+}
+
+impl GuestInput {
+    /// ファイルから読み込む
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("入力ファイル '{}' を開けません: {}", path, e))?;
+        Ok(Self { reader: Some(Box::new(BufReader::new(file))) })
+    }
+
+    /// メモリ上のバイト列から読み込む（シミュレータを組み込んで使う場合やテスト用）
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { reader: Some(Box::new(Cursor::new(bytes))) }
+    }
+
+    fn with_reader<T>(&mut self, read: impl FnOnce(&mut dyn BufRead) -> T) -> T {
+        match &mut self.reader {
+            Some(reader) => read(reader.as_mut()),
+            None => {
+                // 入力を促す出力が先に見えるようにする
+                let _ = io::stdout().flush();
+                read(&mut io::stdin().lock())
+            }
+        }
+    }
+
+    /// 1バイト読み込む（入力の終わりならNone）
+    pub fn read_byte(&mut self) -> Option<u8> {
+        self.with_reader(next_byte)
+    }
+
+    /// 改行（含む）までか、最大max_bytesバイトまで読み込む
+    pub fn read_line(&mut self, max_bytes: usize) -> Vec<u8> {
+        self.with_reader(|reader| {
+            let mut line = Vec::new();
+            while line.len() < max_bytes {
+                match next_byte(reader) {
+                    Some(byte) => {
+                        line.push(byte);
+                        if byte == b'\n' {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+            line
+        })
+    }
+
+    /// 1行を読み込んで10進数の整数として解釈する（入力の終わりや整数でなければNone）
+    pub fn read_int(&mut self) -> Option<i32> {
+        let line = self.read_line(usize::MAX);
+        std::str::from_utf8(&line).ok()?.trim().parse().ok()
+    }
+}
+
+fn next_byte(reader: &mut dyn BufRead) -> Option<u8> {
+    let byte = *reader.fill_buf().ok()?.first()?;
+    reader.consume(1);
+    Some(byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_from_bytes() {
+        let mut input = GuestInput::from_bytes(b"-42\nhello world\nxy".to_vec());
+        assert_eq!(input.read_int(), Some(-42));
+        // 長さの上限で止まった場合は残りを次に読む
+        assert_eq!(input.read_line(5), b"hello");
+        assert_eq!(input.read_line(100), b" world\n");
+        assert_eq!(input.read_byte(), Some(b'x'));
+        assert_eq!(input.read_int(), None);
+        assert_eq!(input.read_byte(), None);
+        assert!(input.read_line(10).is_empty());
+    }
+}
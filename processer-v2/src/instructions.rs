@@ -2,6 +2,10 @@
 
 use std::fmt;
 
+use crate::assembler::{self, AsmError};
+use crate::decoder;
+use crate::symbols::SymbolTable;
+
 /// MIPSレジスタ番号（0-31）
 pub type Register = u8;
 
@@ -17,6 +21,58 @@ pub type Immediate = i16;
 /// 26ビットのアドレス
 pub type Address = u32;
 
+/// 命令語をMIPSの各フィールドに分解する（診断表示用）
+pub fn instruction_fields(instruction: Instruction) -> Vec<(&'static str, u32)> {
+    vec![
+        ("opcode", instruction >> 26),
+        ("rs", (instruction >> 21) & 0x1F),
+        ("rt", (instruction >> 16) & 0x1F),
+        ("rd", (instruction >> 11) & 0x1F),
+        ("shamt", (instruction >> 6) & 0x1F),
+        ("funct", instruction & 0x3F),
+    ]
+}
+
+/// 命令の分類（命令ミックスの集計用、ISAに共通）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionCategory {
+    /// 整数演算・比較・即値のロード
+    Alu,
+    /// シフト
+    Shift,
+    /// ロード
+    Load,
+    /// ストア
+    Store,
+    /// 条件分岐
+    Branch,
+    /// ジャンプ（関数の呼び出し・復帰を含む）
+    Jump,
+    /// 浮動小数点（コプロセッサ1）
+    Fp,
+    /// システムコール
+    Syscall,
+    /// その他（コプロセッサ0、break、fence、無効な命令など）
+    Other,
+}
+
+impl InstructionCategory {
+    /// 機械可読な名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstructionCategory::Alu => "alu",
+            InstructionCategory::Shift => "shift",
+            InstructionCategory::Load => "load",
+            InstructionCategory::Store => "store",
+            InstructionCategory::Branch => "branch",
+            InstructionCategory::Jump => "jump",
+            InstructionCategory::Fp => "fp",
+            InstructionCategory::Syscall => "syscall",
+            InstructionCategory::Other => "other",
+        }
+    }
+}
+
 /// MIPS命令の種類
 #[derive(Debug, Clone, PartialEq)]
 pub enum InstructionType {
@@ -37,13 +93,76 @@ pub enum InstructionType {
     Beq { rs: Register, rt: Register, imm: Immediate },
     Bne { rs: Register, rt: Register, imm: Immediate },
     Slti { rt: Register, rs: Register, imm: Immediate },
+    /// 即値のビット列をそのまま上位16ビットに置く
+    Lui { rt: Register, imm: Immediate },
+    /// 即値はゼロ拡張する
+    Ori { rt: Register, rs: Register, imm: Immediate },
     
     // J形式命令
     J { addr: Address },
     Jal { addr: Address },
+
+    // コプロセッサ0命令
+    /// コプロセッサ0のレジスタrdを読む
+    Mfc0 { rt: Register, rd: Register },
+    /// コプロセッサ0のレジスタrdに書く
+    Mtc0 { rt: Register, rd: Register },
+    /// 例外から復帰する（EPCへ戻り、StatusのEXLを下ろす）
+    Eret,
+
+    // 浮動小数点命令（コプロセッサ1）
+    Mfc1 { rt: Register, fs: Register },
+    Mtc1 { rt: Register, fs: Register },
+    Cfc1 { rt: Register, fs: Register },
+    Ctc1 { rt: Register, fs: Register },
+    CvtSW { fd: Register, fs: Register },
+    CvtWS { fd: Register, fs: Register },
+    TruncWS { fd: Register, fs: Register },
+    AddS { fd: Register, fs: Register, ft: Register },
+    SubS { fd: Register, fs: Register, ft: Register },
+    MulS { fd: Register, fs: Register, ft: Register },
+    DivS { fd: Register, fs: Register, ft: Register },
+    SqrtS { fd: Register, fs: Register },
+    FinvS { fd: Register, fs: Register },
+    AbsS { fd: Register, fs: Register },
+    MovS { fd: Register, fs: Register },
+    NegS { fd: Register, fs: Register },
+
+    // 倍精度浮動小数点命令（偶数番号から始まるレジスタ対を使用）
+    #[cfg(feature = "double")]
+    AddD { fd: Register, fs: Register, ft: Register },
+    #[cfg(feature = "double")]
+    SubD { fd: Register, fs: Register, ft: Register },
+    #[cfg(feature = "double")]
+    MulD { fd: Register, fs: Register, ft: Register },
+    #[cfg(feature = "double")]
+    DivD { fd: Register, fs: Register, ft: Register },
+    #[cfg(feature = "double")]
+    SqrtD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    AbsD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    MovD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    NegD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    CvtDS { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    CvtSD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    CvtDW { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    CvtWD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    TruncWD { fd: Register, fs: Register },
+    #[cfg(feature = "double")]
+    Ldc1 { ft: Register, base: Register, imm: Immediate },
+    #[cfg(feature = "double")]
+    Sdc1 { ft: Register, base: Register, imm: Immediate },
     
     // システムコール
     Syscall,
+    Break { code: u32 },
     
     // 無効な命令
     Invalid,
@@ -52,45 +171,240 @@ pub enum InstructionType {
 impl InstructionType {
     /// 32ビット命令から命令をデコードする関数
     pub fn decode(instruction: Instruction) -> Self {
-        let opcode = (instruction >> 26) as u8;
-        let rs = ((instruction >> 21) & 0x1F) as u8;
-        let rt = ((instruction >> 16) & 0x1F) as u8;
-        let rd = ((instruction >> 11) & 0x1F) as u8;
-        let shamt = ((instruction >> 6) & 0x1F) as u8;
-        let funct = (instruction & 0x3F) as u8;
-        let imm = (instruction & 0xFFFF) as i16;
-        let addr = instruction & 0x3FFFFFF;
-
-        match opcode {
-            0x00 => {
-                // R形式命令
-                match funct {
-                    0x20 => InstructionType::Add { rd, rs, rt },
-                    0x22 => InstructionType::Sub { rd, rs, rt },
-                    0x24 => InstructionType::And { rd, rs, rt },
-                    0x25 => InstructionType::Or { rd, rs, rt },
-                    0x2A => InstructionType::Slt { rd, rs, rt },
-                    0x00 => InstructionType::Sll { rd, rt, shamt },
-                    0x02 => InstructionType::Srl { rd, rt, shamt },
-                    0x08 => InstructionType::Jr { rs },
-                    0x0C => InstructionType::Syscall,
-                    _ => InstructionType::Invalid,
-                }
-            }
-            0x08 => InstructionType::Addi { rt, rs, imm },
-            0x23 => InstructionType::Lw { rt, rs, imm },
-            0x2B => InstructionType::Sw { rt, rs, imm },
-            0x04 => InstructionType::Beq { rs, rt, imm },
-            0x05 => InstructionType::Bne { rs, rt, imm },
-            0x0A => InstructionType::Slti { rt, rs, imm },
-            0x02 => InstructionType::J { addr },
-            0x03 => InstructionType::Jal { addr },
-            _ => InstructionType::Invalid,
+        // opcode・funct・rsからの表引き（表は最初のデコードで1度だけ作る）
+        decoder::table().decode(instruction)
+    }
+
+    /// アセンブリ言語の1命令から命令を作成する（レジスタは $8 と $t0 のどちらの形式でもよい）
+    pub fn parse(line: &str) -> Result<Self, AsmError> {
+        assembler::parse_instruction(line)
+    }
+
+    /// 命令を32ビットの機械語にエンコードする（Invalidの場合はNone）
+    pub fn encode(&self) -> Option<Instruction> {
+        fn r_type(rs: Register, rt: Register, rd: Register, shamt: u8, funct: u8) -> Instruction {
+            ((rs as u32) << 21) | ((rt as u32) << 16) | ((rd as u32) << 11) | ((shamt as u32) << 6) | funct as u32
+        }
+        fn i_type(opcode: u8, rs: Register, rt: Register, imm: Immediate) -> Instruction {
+            ((opcode as u32) << 26) | ((rs as u32) << 21) | ((rt as u32) << 16) | (imm as u16 as u32)
+        }
+        fn j_type(opcode: u8, addr: Address) -> Instruction {
+            ((opcode as u32) << 26) | (addr & 0x3FFFFFF)
+        }
+        fn cop1(fmt: u8, ft: Register, fs: Register, fd: Register, funct: u8) -> Instruction {
+            (0x11 << 26) | r_type(fmt, ft, fs, fd, funct)
+        }
+
+        let word = match *self {
+            InstructionType::Add { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x20),
+            InstructionType::Sub { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x22),
+            InstructionType::And { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x24),
+            InstructionType::Or { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x25),
+            InstructionType::Slt { rd, rs, rt } => r_type(rs, rt, rd, 0, 0x2A),
+            InstructionType::Sll { rd, rt, shamt } => r_type(0, rt, rd, shamt, 0x00),
+            InstructionType::Srl { rd, rt, shamt } => r_type(0, rt, rd, shamt, 0x02),
+            InstructionType::Jr { rs } => r_type(rs, 0, 0, 0, 0x08),
+            InstructionType::Addi { rt, rs, imm } => i_type(0x08, rs, rt, imm),
+            InstructionType::Lw { rt, rs, imm } => i_type(0x23, rs, rt, imm),
+            InstructionType::Sw { rt, rs, imm } => i_type(0x2B, rs, rt, imm),
+            InstructionType::Beq { rs, rt, imm } => i_type(0x04, rs, rt, imm),
+            InstructionType::Bne { rs, rt, imm } => i_type(0x05, rs, rt, imm),
+            InstructionType::Slti { rt, rs, imm } => i_type(0x0A, rs, rt, imm),
+            InstructionType::Lui { rt, imm } => i_type(0x0F, 0, rt, imm),
+            InstructionType::Ori { rt, rs, imm } => i_type(0x0D, rs, rt, imm),
+            InstructionType::J { addr } => j_type(0x02, addr),
+            InstructionType::Jal { addr } => j_type(0x03, addr),
+            InstructionType::Mfc0 { rt, rd } => (0x10 << 26) | ((rt as u32) << 16) | ((rd as u32) << 11),
+            InstructionType::Mtc0 { rt, rd } => (0x10 << 26) | (0x04 << 21) | ((rt as u32) << 16) | ((rd as u32) << 11),
+            InstructionType::Eret => 0x42000018,
+            InstructionType::Mfc1 { rt, fs } => cop1(0x00, rt, fs, 0, 0),
+            InstructionType::Cfc1 { rt, fs } => cop1(0x02, rt, fs, 0, 0),
+            InstructionType::Mtc1 { rt, fs } => cop1(0x04, rt, fs, 0, 0),
+            InstructionType::Ctc1 { rt, fs } => cop1(0x06, rt, fs, 0, 0),
+            InstructionType::CvtSW { fd, fs } => cop1(0x14, 0, fs, fd, 0x20),
+            InstructionType::CvtWS { fd, fs } => cop1(0x10, 0, fs, fd, 0x24),
+            InstructionType::TruncWS { fd, fs } => cop1(0x10, 0, fs, fd, 0x0D),
+            InstructionType::AddS { fd, fs, ft } => cop1(0x10, ft, fs, fd, 0x00),
+            InstructionType::SubS { fd, fs, ft } => cop1(0x10, ft, fs, fd, 0x01),
+            InstructionType::MulS { fd, fs, ft } => cop1(0x10, ft, fs, fd, 0x02),
+            InstructionType::DivS { fd, fs, ft } => cop1(0x10, ft, fs, fd, 0x03),
+            InstructionType::SqrtS { fd, fs } => cop1(0x10, 0, fs, fd, 0x04),
+            InstructionType::FinvS { fd, fs } => cop1(0x10, 0, fs, fd, 0x15),
+            InstructionType::AbsS { fd, fs } => cop1(0x10, 0, fs, fd, 0x05),
+            InstructionType::MovS { fd, fs } => cop1(0x10, 0, fs, fd, 0x06),
+            InstructionType::NegS { fd, fs } => cop1(0x10, 0, fs, fd, 0x07),
+            #[cfg(feature = "double")]
+            InstructionType::AddD { fd, fs, ft } => cop1(0x11, ft, fs, fd, 0x00),
+            #[cfg(feature = "double")]
+            InstructionType::SubD { fd, fs, ft } => cop1(0x11, ft, fs, fd, 0x01),
+            #[cfg(feature = "double")]
+            InstructionType::MulD { fd, fs, ft } => cop1(0x11, ft, fs, fd, 0x02),
+            #[cfg(feature = "double")]
+            InstructionType::DivD { fd, fs, ft } => cop1(0x11, ft, fs, fd, 0x03),
+            #[cfg(feature = "double")]
+            InstructionType::SqrtD { fd, fs } => cop1(0x11, 0, fs, fd, 0x04),
+            #[cfg(feature = "double")]
+            InstructionType::AbsD { fd, fs } => cop1(0x11, 0, fs, fd, 0x05),
+            #[cfg(feature = "double")]
+            InstructionType::MovD { fd, fs } => cop1(0x11, 0, fs, fd, 0x06),
+            #[cfg(feature = "double")]
+            InstructionType::NegD { fd, fs } => cop1(0x11, 0, fs, fd, 0x07),
+            #[cfg(feature = "double")]
+            InstructionType::CvtDS { fd, fs } => cop1(0x10, 0, fs, fd, 0x21),
+            #[cfg(feature = "double")]
+            InstructionType::CvtSD { fd, fs } => cop1(0x11, 0, fs, fd, 0x20),
+            #[cfg(feature = "double")]
+            InstructionType::CvtDW { fd, fs } => cop1(0x14, 0, fs, fd, 0x21),
+            #[cfg(feature = "double")]
+            InstructionType::CvtWD { fd, fs } => cop1(0x11, 0, fs, fd, 0x24),
+            #[cfg(feature = "double")]
+            InstructionType::TruncWD { fd, fs } => cop1(0x11, 0, fs, fd, 0x0D),
+            #[cfg(feature = "double")]
+            InstructionType::Ldc1 { ft, base, imm } => i_type(0x35, base, ft, imm),
+            #[cfg(feature = "double")]
+            InstructionType::Sdc1 { ft, base, imm } => i_type(0x3D, base, ft, imm),
+            InstructionType::Syscall => 0x0000000C,
+            InstructionType::Break { code } => ((code & 0xFFFFF) << 6) | 0x0D,
+            InstructionType::Invalid => return None,
+        };
+        Some(word)
+    }
+
+    /// pcに置かれた命令として表示する（分岐・ジャンプ先は即値ではなく絶対アドレスとシンボルで示す）
+    pub fn display_at(&self, pc: Address, symbols: &SymbolTable) -> String {
+        match *self {
+            InstructionType::Beq { rs, rt, imm } => {
+                format!("beq ${}, ${}, {}", rs, rt, symbols.format_address(branch_target(pc, imm)))
+            }
+            InstructionType::Bne { rs, rt, imm } => {
+                format!("bne ${}, ${}, {}", rs, rt, symbols.format_address(branch_target(pc, imm)))
+            }
+            InstructionType::J { addr } => format!("j {}", symbols.format_address(jump_target(pc, addr))),
+            InstructionType::Jal { addr } => format!("jal {}", symbols.format_address(jump_target(pc, addr))),
+            _ => self.to_string(),
+        }
+    }
+
+    /// pcに置かれた分岐・ジャンプ命令の飛び先（レジスタで決まる jr や、分岐・ジャンプでない命令はNone）
+    pub fn target(&self, pc: Address) -> Option<Address> {
+        match *self {
+            InstructionType::Beq { imm, .. } | InstructionType::Bne { imm, .. } => Some(branch_target(pc, imm)),
+            InstructionType::J { addr } | InstructionType::Jal { addr } => Some(jump_target(pc, addr)),
+            _ => None,
+        }
+    }
+
+    /// 乗除算器を使う命令か（浮動小数点の乗除算・平方根・逆数）
+    pub fn is_mul_div(&self) -> bool {
+        match self {
+            InstructionType::MulS { .. } | InstructionType::DivS { .. }
+            | InstructionType::SqrtS { .. } | InstructionType::FinvS { .. } => true,
+            #[cfg(feature = "double")]
+            InstructionType::MulD { .. } | InstructionType::DivD { .. } | InstructionType::SqrtD { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// 命令の分類
+    pub fn category(&self) -> InstructionCategory {
+        match self {
+            InstructionType::Add { .. } | InstructionType::Sub { .. } | InstructionType::And { .. }
+            | InstructionType::Or { .. } | InstructionType::Slt { .. } | InstructionType::Addi { .. }
+            | InstructionType::Slti { .. } | InstructionType::Lui { .. } | InstructionType::Ori { .. } => InstructionCategory::Alu,
+            InstructionType::Sll { .. } | InstructionType::Srl { .. } => InstructionCategory::Shift,
+            InstructionType::Lw { .. } => InstructionCategory::Load,
+            InstructionType::Sw { .. } => InstructionCategory::Store,
+            #[cfg(feature = "double")]
+            InstructionType::Ldc1 { .. } => InstructionCategory::Load,
+            #[cfg(feature = "double")]
+            InstructionType::Sdc1 { .. } => InstructionCategory::Store,
+            InstructionType::Beq { .. } | InstructionType::Bne { .. } => InstructionCategory::Branch,
+            InstructionType::J { .. } | InstructionType::Jal { .. } | InstructionType::Jr { .. } => InstructionCategory::Jump,
+            InstructionType::Syscall => InstructionCategory::Syscall,
+            InstructionType::Mfc0 { .. } | InstructionType::Mtc0 { .. } | InstructionType::Eret
+            | InstructionType::Break { .. } | InstructionType::Invalid => InstructionCategory::Other,
+            // 残りはすべてコプロセッサ1の命令
+            _ => InstructionCategory::Fp,
+        }
+    }
+
+    /// ニーモニック（命令の種類ごとに一意）
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            InstructionType::Add { .. } => "add",
+            InstructionType::Sub { .. } => "sub",
+            InstructionType::And { .. } => "and",
+            InstructionType::Or { .. } => "or",
+            InstructionType::Slt { .. } => "slt",
+            InstructionType::Sll { .. } => "sll",
+            InstructionType::Srl { .. } => "srl",
+            InstructionType::Jr { .. } => "jr",
+            InstructionType::Addi { .. } => "addi",
+            InstructionType::Lw { .. } => "lw",
+            InstructionType::Sw { .. } => "sw",
+            InstructionType::Beq { .. } => "beq",
+            InstructionType::Bne { .. } => "bne",
+            InstructionType::Slti { .. } => "slti",
+            InstructionType::Lui { .. } => "lui",
+            InstructionType::Ori { .. } => "ori",
+            InstructionType::J { .. } => "j",
+            InstructionType::Jal { .. } => "jal",
+            InstructionType::Mfc0 { .. } => "mfc0",
+            InstructionType::Mtc0 { .. } => "mtc0",
+            InstructionType::Eret => "eret",
+            InstructionType::Mfc1 { .. } => "mfc1",
+            InstructionType::Mtc1 { .. } => "mtc1",
+            InstructionType::Cfc1 { .. } => "cfc1",
+            InstructionType::Ctc1 { .. } => "ctc1",
+            InstructionType::CvtSW { .. } => "cvt.s.w",
+            InstructionType::CvtWS { .. } => "cvt.w.s",
+            InstructionType::TruncWS { .. } => "trunc.w.s",
+            InstructionType::AddS { .. } => "add.s",
+            InstructionType::SubS { .. } => "sub.s",
+            InstructionType::MulS { .. } => "mul.s",
+            InstructionType::DivS { .. } => "div.s",
+            InstructionType::SqrtS { .. } => "sqrt.s",
+            InstructionType::FinvS { .. } => "finv",
+            InstructionType::AbsS { .. } => "abs.s",
+            InstructionType::MovS { .. } => "mov.s",
+            InstructionType::NegS { .. } => "neg.s",
+            #[cfg(feature = "double")]
+            InstructionType::AddD { .. } => "add.d",
+            #[cfg(feature = "double")]
+            InstructionType::SubD { .. } => "sub.d",
+            #[cfg(feature = "double")]
+            InstructionType::MulD { .. } => "mul.d",
+            #[cfg(feature = "double")]
+            InstructionType::DivD { .. } => "div.d",
+            #[cfg(feature = "double")]
+            InstructionType::SqrtD { .. } => "sqrt.d",
+            #[cfg(feature = "double")]
+            InstructionType::AbsD { .. } => "abs.d",
+            #[cfg(feature = "double")]
+            InstructionType::MovD { .. } => "mov.d",
+            #[cfg(feature = "double")]
+            InstructionType::NegD { .. } => "neg.d",
+            #[cfg(feature = "double")]
+            InstructionType::CvtDS { .. } => "cvt.d.s",
+            #[cfg(feature = "double")]
+            InstructionType::CvtSD { .. } => "cvt.s.d",
+            #[cfg(feature = "double")]
+            InstructionType::CvtDW { .. } => "cvt.d.w",
+            #[cfg(feature = "double")]
+            InstructionType::CvtWD { .. } => "cvt.w.d",
+            #[cfg(feature = "double")]
+            InstructionType::TruncWD { .. } => "trunc.w.d",
+            #[cfg(feature = "double")]
+            InstructionType::Ldc1 { .. } => "ldc1",
+            #[cfg(feature = "double")]
+            InstructionType::Sdc1 { .. } => "sdc1",
+            InstructionType::Syscall => "syscall",
+            InstructionType::Break { .. } => "break",
+            InstructionType::Invalid => "invalid",
         }
     }
 
     /// 命令のサイズを返す（MIPSは全て4バイト）
-    #[allow(dead_code)]
     pub fn size(&self) -> usize {
         4
     }
@@ -98,6 +412,16 @@ impl InstructionType {
 
 // 命令の詳細を表示する
 
+/// pcにある分岐命令の分岐先（オフセットは分岐命令自身からの命令数）
+pub fn branch_target(pc: Address, imm: Immediate) -> Address {
+    pc.wrapping_add((imm as i32 * 4) as u32)
+}
+
+/// pcにあるジャンプ命令のジャンプ先（上位4ビットはpcから取る）
+pub fn jump_target(pc: Address, addr: Address) -> Address {
+    (pc & 0xF0000000) | (addr << 2)
+}
+
 impl fmt::Display for InstructionType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -129,10 +453,10 @@ impl fmt::Display for InstructionType {
                 write!(f, "addi ${}, ${}, {}", rt, rs, imm)
             }
             InstructionType::Lw { rt, rs, imm } => {
-                write!(f, "lw ${}, {}({})", rt, imm, rs)
+                write!(f, "lw ${}, {}(${})", rt, imm, rs)
             }
             InstructionType::Sw { rt, rs, imm } => {
-                write!(f, "sw ${}, {}({})", rt, imm, rs)
+                write!(f, "sw ${}, {}(${})", rt, imm, rs)
             }
             InstructionType::Beq { rs, rt, imm } => {
                 write!(f, "beq ${}, ${}, {}", rs, rt, imm)
@@ -143,15 +467,141 @@ impl fmt::Display for InstructionType {
             InstructionType::Slti { rt, rs, imm } => {
                 write!(f, "slti ${}, ${}, {}", rt, rs, imm)
             }
+            InstructionType::Lui { rt, imm } => {
+                write!(f, "lui ${}, 0x{:04X}", rt, *imm as u16)
+            }
+            InstructionType::Ori { rt, rs, imm } => {
+                write!(f, "ori ${}, ${}, 0x{:04X}", rt, rs, *imm as u16)
+            }
             InstructionType::J { addr } => {
                 write!(f, "j 0x{:08X}", addr << 2)
             }
             InstructionType::Jal { addr } => {
                 write!(f, "jal 0x{:08X}", addr << 2)
             }
+            InstructionType::Mfc0 { rt, rd } => {
+                write!(f, "mfc0 ${}, ${}", rt, rd)
+            }
+            InstructionType::Mtc0 { rt, rd } => {
+                write!(f, "mtc0 ${}, ${}", rt, rd)
+            }
+            InstructionType::Eret => {
+                write!(f, "eret")
+            }
+            InstructionType::Mfc1 { rt, fs } => {
+                write!(f, "mfc1 ${}, $f{}", rt, fs)
+            }
+            InstructionType::Mtc1 { rt, fs } => {
+                write!(f, "mtc1 ${}, $f{}", rt, fs)
+            }
+            InstructionType::Cfc1 { rt, fs } => {
+                write!(f, "cfc1 ${}, ${}", rt, fs)
+            }
+            InstructionType::Ctc1 { rt, fs } => {
+                write!(f, "ctc1 ${}, ${}", rt, fs)
+            }
+            InstructionType::CvtSW { fd, fs } => {
+                write!(f, "cvt.s.w $f{}, $f{}", fd, fs)
+            }
+            InstructionType::CvtWS { fd, fs } => {
+                write!(f, "cvt.w.s $f{}, $f{}", fd, fs)
+            }
+            InstructionType::TruncWS { fd, fs } => {
+                write!(f, "trunc.w.s $f{}, $f{}", fd, fs)
+            }
+            InstructionType::AddS { fd, fs, ft } => {
+                write!(f, "add.s $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            InstructionType::SubS { fd, fs, ft } => {
+                write!(f, "sub.s $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            InstructionType::MulS { fd, fs, ft } => {
+                write!(f, "mul.s $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            InstructionType::DivS { fd, fs, ft } => {
+                write!(f, "div.s $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            InstructionType::SqrtS { fd, fs } => {
+                write!(f, "sqrt.s $f{}, $f{}", fd, fs)
+            }
+            InstructionType::FinvS { fd, fs } => {
+                write!(f, "finv $f{}, $f{}", fd, fs)
+            }
+            InstructionType::AbsS { fd, fs } => {
+                write!(f, "abs.s $f{}, $f{}", fd, fs)
+            }
+            InstructionType::MovS { fd, fs } => {
+                write!(f, "mov.s $f{}, $f{}", fd, fs)
+            }
+            InstructionType::NegS { fd, fs } => {
+                write!(f, "neg.s $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::AddD { fd, fs, ft } => {
+                write!(f, "add.d $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::SubD { fd, fs, ft } => {
+                write!(f, "sub.d $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::MulD { fd, fs, ft } => {
+                write!(f, "mul.d $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::DivD { fd, fs, ft } => {
+                write!(f, "div.d $f{}, $f{}, $f{}", fd, fs, ft)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::SqrtD { fd, fs } => {
+                write!(f, "sqrt.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::AbsD { fd, fs } => {
+                write!(f, "abs.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::MovD { fd, fs } => {
+                write!(f, "mov.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::NegD { fd, fs } => {
+                write!(f, "neg.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::CvtDS { fd, fs } => {
+                write!(f, "cvt.d.s $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::CvtSD { fd, fs } => {
+                write!(f, "cvt.s.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::CvtDW { fd, fs } => {
+                write!(f, "cvt.d.w $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::CvtWD { fd, fs } => {
+                write!(f, "cvt.w.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::TruncWD { fd, fs } => {
+                write!(f, "trunc.w.d $f{}, $f{}", fd, fs)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::Ldc1 { ft, base, imm } => {
+                write!(f, "ldc1 $f{}, {}(${})", ft, imm, base)
+            }
+            #[cfg(feature = "double")]
+            InstructionType::Sdc1 { ft, base, imm } => {
+                write!(f, "sdc1 $f{}, {}(${})", ft, imm, base)
+            }
             InstructionType::Syscall => {
                 write!(f, "syscall")
             }
+            InstructionType::Break { code } => {
+                write!(f, "break {}", code)
+            }
             InstructionType::Invalid => {
                 write!(f, "invalid")
             }
@@ -194,4 +644,79 @@ mod tests {
             panic!("Expected Addi instruction");
         }
     }
+
+    #[test]
+    fn test_decode_fp_conversions() {
+        // cvt.s.w $f2, $f4
+        assert_eq!(InstructionType::decode(0x468020A0), InstructionType::CvtSW { fd: 2, fs: 4 });
+        // cvt.w.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x460020A4), InstructionType::CvtWS { fd: 2, fs: 4 });
+        // trunc.w.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x4600208D), InstructionType::TruncWS { fd: 2, fs: 4 });
+        // mtc1 $t0, $f4
+        assert_eq!(InstructionType::decode(0x44882000), InstructionType::Mtc1 { rt: 8, fs: 4 });
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let words = [0x00430820u32, 0x20410064, 0x3C081001, 0x3508FFFF, 0x40086000, 0x40887000, 0x8D09FFFC, 0x1100000A, 0x0800000A, 0x46062082, 0x4600208D, 0x0000000C];
+        for word in words {
+            assert_eq!(InstructionType::decode(word).encode(), Some(word));
+        }
+        assert_eq!(InstructionType::Invalid.encode(), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(InstructionType::parse("addi $t0, $zero, 5"), Ok(InstructionType::Addi { rt: 8, rs: 0, imm: 5 }));
+        assert_eq!(InstructionType::parse("addi $8, $0, 5"), InstructionType::parse("addi $t0, $zero, 5"));
+        assert_eq!(InstructionType::parse("add $1, $2, $3").unwrap().encode(), Some(0x00430820));
+        assert!(matches!(InstructionType::parse("addi $t0, $bogus, 5"), Err(AsmError::InvalidRegister(_))));
+    }
+
+    #[test]
+    fn test_display_at() {
+        let symbols = SymbolTable::parse("loop 0x00400008").unwrap();
+        // beq $8, $9, -2 (0x0040000C から)
+        let beq = InstructionType::Beq { rs: 8, rt: 9, imm: -2 };
+        assert_eq!(beq.display_at(0x0040000C, &symbols), "beq $8, $9, 0x00400004");
+        assert_eq!(beq.display_at(0x00400010, &symbols), "beq $8, $9, 0x00400008 <loop>");
+        let j = InstructionType::J { addr: 0x100003 };
+        assert_eq!(j.display_at(0x00400000, &symbols), "j 0x0040000C <loop+0x4>");
+        assert_eq!(InstructionType::Syscall.display_at(0x00400000, &symbols), "syscall");
+    }
+
+    #[test]
+    fn test_decode_fp_arithmetic() {
+        // mul.s $f2, $f4, $f6
+        assert_eq!(InstructionType::decode(0x46062082), InstructionType::MulS { fd: 2, fs: 4, ft: 6 });
+    }
+
+    #[test]
+    fn test_decode_fp_unary() {
+        // sqrt.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x46002084), InstructionType::SqrtS { fd: 2, fs: 4 });
+        // abs.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x46002085), InstructionType::AbsS { fd: 2, fs: 4 });
+        // mov.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x46002086), InstructionType::MovS { fd: 2, fs: 4 });
+        // neg.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x46002087), InstructionType::NegS { fd: 2, fs: 4 });
+        // finv $f2, $f4
+        assert_eq!(InstructionType::decode(0x46002095), InstructionType::FinvS { fd: 2, fs: 4 });
+    }
+
+    #[cfg(feature = "double")]
+    #[test]
+    fn test_decode_double() {
+        // add.d $f2, $f4, $f6
+        assert_eq!(InstructionType::decode(0x46262080), InstructionType::AddD { fd: 2, fs: 4, ft: 6 });
+        // cvt.d.s $f2, $f4
+        assert_eq!(InstructionType::decode(0x460020A1), InstructionType::CvtDS { fd: 2, fs: 4 });
+        // ldc1 $f2, 8($sp)
+        assert_eq!(InstructionType::decode(0xD7A20008), InstructionType::Ldc1 { ft: 2, base: 29, imm: 8 });
+        for word in [0x46262080u32, 0x460020A1, 0xD7A20008, 0xF7A2FFF8, 0x462020A4, 0x468020A1] {
+            assert_eq!(InstructionType::decode(word).encode(), Some(word));
+        }
+    }
 }
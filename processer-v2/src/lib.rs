@@ -0,0 +1,59 @@
+//! MIPSシミュレータのライブラリ（コマンドラインの main.rs もこのクレートを使う）
+//!
+//! 組み込み用のAPIは [`MipsSimulator`] を入り口とする。出力を取り込んだ実行は [`MipsSimulator::run_captured`]、
+//! システムコールの差し替えは [`syscall::SyscallHandler`]、キャッシュモデルの差し替えは [`cache::CacheLike`]、
+//! メモリマップドデバイスは [`mmio::MmioDevice`]、命令デコーダの差し替えは [`decoder::install`] を使う
+
+pub mod instructions;
+pub mod assembler;
+pub mod bench;
+pub mod branch_predictor;
+pub mod memory;
+pub mod cache;
+pub mod cache_trace;
+pub mod bus;
+pub mod call_stack;
+pub mod coherence;
+pub mod coverage;
+pub mod cp0;
+pub mod data_image;
+pub mod expect;
+pub mod elf;
+pub mod export;
+pub mod decoder;
+pub mod disasm;
+pub mod report;
+pub mod footprint;
+pub mod fpu;
+pub mod framebuffer;
+pub mod guest_env;
+pub mod heatmap;
+pub mod history;
+pub mod host_files;
+pub mod input;
+pub mod loader;
+pub mod loop_detector;
+pub mod mem_trace;
+pub mod mmap;
+pub mod miss_classifier;
+pub mod mmio;
+pub mod ooo;
+pub mod output;
+pub mod pipeline;
+pub mod processor;
+pub mod profile;
+pub mod records;
+pub mod register_usage;
+pub mod replay;
+pub mod riscv;
+pub mod scheduler;
+pub mod snapshot;
+pub mod symbols;
+pub mod syscall;
+pub mod timing;
+pub mod undo_log;
+pub mod validate;
+pub mod watchpoint;
+pub mod simulator;
+
+pub use simulator::{parse_address, CapturedRun, MipsSimulator, SimulatorConfig, SimulatorError};
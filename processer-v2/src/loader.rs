@@ -0,0 +1,308 @@
+//! プログラムファイルの形式（ツールチェーンの出力を変換スクリプトなしでそのまま読み込むため）
+//!
+//! hex は1行に1命令の16進数のテキスト（アセンブリ言語の行も混在できる）で、バイト順や1行に複数のワードを書いた場合の順序、
+//! バイト列として書いたテキストにも HexOptions で対応する。
+//! bin はメモリ上の並び（リトルエンディアン）のままのバイナリで、どちらもプログラムの開始アドレスから置く。
+//! elf はPT_LOADのセグメントをそれぞれのアドレスに置き、エントリポイントから実行する。
+//! ihex（Intel HEX）と srec（Motorola S-record）はレコードに埋め込まれたアドレスに置く。
+//! asm はラベルを含むアセンブリ言語のソースで、アセンブルしてプログラムの開始アドレスから置く。
+//! auto はファイルの内容と拡張子から形式を推定する。
+
+use std::path::Path;
+
+use crate::instructions::Instruction;
+use crate::memory::MemoryAddress;
+use crate::symbols::Symbol;
+
+/// プログラムファイルの形式
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ProgramFormat {
+    /// ファイルから推定する
+    #[default]
+    Auto,
+    /// 16進数のテキスト
+    Hex,
+    /// フラットなバイナリ
+    Bin,
+    /// ELF32の実行ファイル
+    Elf,
+    /// Intel HEX
+    IntelHex,
+    /// Motorola S-record
+    Srec,
+    /// アセンブリ言語のソース
+    Asm,
+}
+
+impl ProgramFormat {
+    /// 名前から作成
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "auto" => Ok(ProgramFormat::Auto),
+            "hex" => Ok(ProgramFormat::Hex),
+            "bin" => Ok(ProgramFormat::Bin),
+            "elf" => Ok(ProgramFormat::Elf),
+            "ihex" => Ok(ProgramFormat::IntelHex),
+            "srec" => Ok(ProgramFormat::Srec),
+            "asm" => Ok(ProgramFormat::Asm),
+            _ => Err(format!("プログラムの形式は auto・hex・bin・elf・ihex・srec・asm のいずれかで指定してください: {}", text)),
+        }
+    }
+
+    /// 表示用の名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProgramFormat::Auto => "auto",
+            ProgramFormat::Hex => "hex",
+            ProgramFormat::Bin => "bin",
+            ProgramFormat::Elf => "elf",
+            ProgramFormat::IntelHex => "ihex",
+            ProgramFormat::Srec => "srec",
+            ProgramFormat::Asm => "asm",
+        }
+    }
+
+    /// ファイルの内容と拡張子から形式を推定する
+    /// ELFのマジックナンバー、拡張子（.bin と .s・.asm）、最初の行の ':'（Intel HEX）や "S<数字>"（S-record）の順に調べ、
+    /// どれにも当たらなければ16進数のテキストとみなす
+    pub fn detect(path: &Path, bytes: &[u8]) -> Self {
+        if bytes.starts_with(b"\x7FELF") {
+            return ProgramFormat::Elf;
+        }
+        let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("bin") => return ProgramFormat::Bin,
+            Some("s" | "asm") => return ProgramFormat::Asm,
+            _ => {}
+        }
+        let first_line = bytes.split(|&byte| byte == b'\n')
+            .map(|line| line.trim_ascii())
+            .find(|line| !line.is_empty());
+        match first_line {
+            Some([b':', ..]) => ProgramFormat::IntelHex,
+            Some([b'S', digit, ..]) if digit.is_ascii_digit() => ProgramFormat::Srec,
+            _ => ProgramFormat::Hex,
+        }
+    }
+}
+
+/// メモリに置く連続した領域
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// 先頭アドレス
+    pub address: MemoryAddress,
+    /// 内容（ELFの.bssのようにファイルにない部分は0で埋めてある）
+    pub data: Vec<u8>,
+    /// 命令を含むか（カバレッジの対象にする）
+    pub executable: bool,
+}
+
+impl Segment {
+    /// 終端のアドレス（含まない, 4GBを超える場合があるのでu64）
+    pub fn end(&self) -> u64 {
+        self.address as u64 + self.data.len() as u64
+    }
+}
+
+/// アドレスの付いたプログラムのイメージ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadImage {
+    pub segments: Vec<Segment>,
+    /// 実行を始めるアドレス（Noneならプログラムの開始アドレス）
+    pub entry: Option<MemoryAddress>,
+    /// ファイルに含まれていたシンボル
+    pub symbols: Vec<Symbol>,
+}
+
+/// 読み込んだプログラムファイルの内容
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramFile {
+    /// プログラムの開始アドレスから置く命令の列（hex・bin・asm）と、アセンブラのラベルから作ったシンボル
+    Words { words: Vec<Instruction>, symbols: Vec<Symbol> },
+    /// アドレスの付いたイメージ（elf・ihex・srec）
+    Image(LoadImage),
+}
+
+impl ProgramFile {
+    /// アドレスの付いたイメージに変換する（命令の列は start から置く実行可能なセグメントにする）
+    pub fn into_image(self, start: MemoryAddress) -> LoadImage {
+        match self {
+            ProgramFile::Words { words, symbols } => {
+                let data = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+                LoadImage { segments: vec![Segment { address: start, data, executable: true }], entry: None, symbols }
+            }
+            ProgramFile::Image(image) => image,
+        }
+    }
+}
+
+/// 16進数のテキストの書き方
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HexLayout {
+    /// 1行に1つ（または複数）のワード
+    #[default]
+    Words,
+    /// 行をまたいで続くバイト列（空白で区切っても続けて書いてもよい）
+    Bytes,
+}
+
+/// ワード内のバイト順
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteOrder {
+    /// 先に書いたバイトが上位
+    Big,
+    /// 先に書いたバイトが下位
+    Little,
+}
+
+/// 1行に複数のワードを書いた場合の順序
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WordOrder {
+    /// 先に書いたワードが小さいアドレス
+    #[default]
+    Ascending,
+    /// 最後に書いたワードが小さいアドレス（幅の広いメモリの1行を上位から書いた場合）
+    Descending,
+}
+
+/// 16進数のテキストの読み方（ツールによって異なるバイト順・ワード順に合わせるため）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HexOptions {
+    pub layout: HexLayout,
+    /// Noneなら Words ではそのままの値（ビッグエンディアン）、Bytes ではメモリ上の並び（リトルエンディアン）とみなす
+    pub byte_order: Option<ByteOrder>,
+    pub word_order: WordOrder,
+}
+
+impl HexLayout {
+    /// 名前から作成
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "words" => Ok(HexLayout::Words),
+            "bytes" => Ok(HexLayout::Bytes),
+            _ => Err(format!("16進数のテキストの書き方は words・bytes のいずれかで指定してください: {}", text)),
+        }
+    }
+}
+
+impl ByteOrder {
+    /// 名前から作成
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "big" => Ok(ByteOrder::Big),
+            "little" => Ok(ByteOrder::Little),
+            _ => Err(format!("バイト順は big・little のいずれかで指定してください: {}", text)),
+        }
+    }
+}
+
+impl WordOrder {
+    /// 名前から作成
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "ascending" => Ok(WordOrder::Ascending),
+            "descending" => Ok(WordOrder::Descending),
+            _ => Err(format!("ワード順は ascending・descending のいずれかで指定してください: {}", text)),
+        }
+    }
+}
+
+impl HexOptions {
+    /// Words のとき、1行の16進数（空白で区切った複数のワード、または8桁の倍数の幅の広いワード）を命令語の列に変換する
+    pub fn line_words(&self, text: &str) -> Result<Vec<Instruction>, String> {
+        let mut words = Vec::new();
+        for token in text.split_whitespace() {
+            let chunks: Vec<&str> = if token.len() <= 8 {
+                vec![token]
+            } else if token.len().is_multiple_of(8) {
+                (0..token.len()).step_by(8).map(|i| &token[i..i + 8]).collect()
+            } else {
+                return Err(format!("8桁を超えるワードは8桁の倍数にしてください: '{}'", token));
+            };
+            for chunk in chunks {
+                words.push(u32::from_str_radix(chunk, 16).map_err(|e| format!("文字列から16進数への変換に失敗しました: '{}': {}", chunk, e))?);
+            }
+        }
+        if self.word_order == WordOrder::Descending {
+            words.reverse();
+        }
+        if self.byte_order == Some(ByteOrder::Little) {
+            words.iter_mut().for_each(|word| *word = word.swap_bytes());
+        }
+        Ok(words)
+    }
+
+    /// Bytes のとき、1行の16進数をバイト列に変換する
+    pub fn line_bytes(text: &str) -> Result<Vec<u8>, String> {
+        let digits: String = text.split_whitespace().collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(format!("バイト列の16進数の桁数が奇数です: '{}'", text));
+        }
+        (0..digits.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|e| format!("文字列から16進数への変換に失敗しました: '{}': {}", &digits[i..i + 2], e)))
+            .collect()
+    }
+
+    /// Bytes のとき、バイト列を命令語の列に変換する（末尾の4バイトに満たない部分は0で埋める）
+    pub fn words_from_bytes(&self, bytes: &[u8]) -> Vec<Instruction> {
+        let mut words = words_from_binary(bytes);
+        if self.byte_order == Some(ByteOrder::Big) {
+            words.iter_mut().for_each(|word| *word = word.swap_bytes());
+        }
+        words
+    }
+}
+
+/// フラットなバイナリを命令語の列に変換する（末尾の4バイトに満たない部分は0で埋める）
+pub fn words_from_binary(bytes: &[u8]) -> Vec<Instruction> {
+    bytes.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            Instruction::from_le_bytes(word)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_format() {
+        assert_eq!(ProgramFormat::parse("bin"), Ok(ProgramFormat::Bin));
+        assert_eq!(ProgramFormat::parse("hex").unwrap().name(), "hex");
+        assert!(ProgramFormat::parse("coff").is_err());
+        assert_eq!(words_from_binary(&[0x0D, 0x00, 0x00, 0x00, 0x08, 0x00, 0x09]), vec![0x0000000D, 0x00090008]);
+        assert!(words_from_binary(&[]).is_empty());
+
+        let detect = |path: &str, bytes: &[u8]| ProgramFormat::detect(Path::new(path), bytes);
+        assert_eq!(detect("a.out", b"\x7FELF\x01\x01"), ProgramFormat::Elf);
+        assert_eq!(detect("program.bin", b"\x2A\x00\x08\x20"), ProgramFormat::Bin);
+        assert_eq!(detect("program.hex", b"\n:00000001FF\n"), ProgramFormat::IntelHex);
+        assert_eq!(detect("program.mot", b"S00600004844521B\r\n"), ProgramFormat::Srec);
+        assert_eq!(detect("fibonacci.hex", b"# comment\n2008000A\n"), ProgramFormat::Hex);
+        assert_eq!(detect("program.S", b"main: sw $t0, 0($sp)\n"), ProgramFormat::Asm);
+        assert_eq!(detect("program.txt", b"sw $t0, 0($sp)\n"), ProgramFormat::Hex);
+    }
+
+    #[test]
+    fn test_hex_options() {
+        let words = HexOptions::default();
+        assert_eq!(words.line_words("2008000A"), Ok(vec![0x2008000A]));
+        assert_eq!(words.line_words("2008000A 0000000D"), Ok(vec![0x2008000A, 0x0000000D]));
+        assert!(words.line_words("2008000A0").is_err());
+
+        let little = HexOptions { byte_order: Some(ByteOrder::Little), ..HexOptions::default() };
+        assert_eq!(little.line_words("0A000820"), Ok(vec![0x2008000A]));
+        let wide = HexOptions { word_order: WordOrder::Descending, ..HexOptions::default() };
+        assert_eq!(wide.line_words("0000000D2008000A"), Ok(vec![0x2008000A, 0x0000000D]));
+
+        let bytes = HexOptions { layout: HexLayout::Bytes, ..HexOptions::default() };
+        assert_eq!(HexOptions::line_bytes("0A 00 0820"), Ok(vec![0x0A, 0x00, 0x08, 0x20]));
+        assert!(HexOptions::line_bytes("0A0").is_err());
+        assert_eq!(bytes.words_from_bytes(&[0x0A, 0x00, 0x08, 0x20, 0x0D]), vec![0x2008000A, 0x0000000D]);
+        let big = HexOptions { byte_order: Some(ByteOrder::Big), ..bytes };
+        assert_eq!(big.words_from_bytes(&[0x20, 0x08, 0x00, 0x0A]), vec![0x2008000A]);
+    }
+}
@@ -0,0 +1,104 @@
+//! 無限ループの検出（アーキテクチャ状態を変えずに同じ少数のアドレスを繰り返し実行しているか）
+//!
+//! 状態を変えた命令（レジスタの値を変えた命令、ストア、システムコールなど）を実行するたびに数え直す。
+//! 状態を変えずに window 個以下のアドレスだけを threshold 命令実行したら、抜け出せないループとみなす。
+
+use crate::memory::MemoryAddress;
+
+/// ループとみなすアドレス数のデフォルト値
+pub const DEFAULT_LOOP_WINDOW: usize = 16;
+
+/// ループとみなす命令数のデフォルト値
+pub const DEFAULT_LOOP_THRESHOLD: u64 = 10000;
+
+/// 無限ループの検出の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopDetectionConfig {
+    /// ループとみなすアドレス数の上限
+    pub window: usize,
+    /// 状態を変えずに実行した命令数がこれに達したらループとみなす
+    pub threshold: u64,
+}
+
+impl Default for LoopDetectionConfig {
+    fn default() -> Self {
+        Self { window: DEFAULT_LOOP_WINDOW, threshold: DEFAULT_LOOP_THRESHOLD }
+    }
+}
+
+/// 無限ループの検出器
+#[derive(Debug, Clone)]
+pub struct LoopDetector {
+    config: LoopDetectionConfig,
+    /// 最後に状態が変わってから実行したアドレス（重複なし）
+    addresses: Vec<MemoryAddress>,
+    /// 最後に状態が変わってから実行した命令数
+    instructions: u64,
+}
+
+impl LoopDetector {
+    pub fn new(config: LoopDetectionConfig) -> Self {
+        Self { config, addresses: Vec::new(), instructions: 0 }
+    }
+
+    /// 1命令の実行を記録し、無限ループとみなしたらtrueを返す
+    pub fn record(&mut self, pc: MemoryAddress, changed_state: bool) -> bool {
+        if changed_state {
+            self.reset();
+            return false;
+        }
+        if !self.addresses.contains(&pc) {
+            // 繰り返すアドレスが多すぎる場合はこの命令から数え直す
+            if self.addresses.len() == self.config.window {
+                self.reset();
+            }
+            self.addresses.push(pc);
+        }
+        self.instructions += 1;
+        self.instructions >= self.config.threshold
+    }
+
+    /// 数えている途中の記録を消去する
+    pub fn reset(&mut self) {
+        self.addresses.clear();
+        self.instructions = 0;
+    }
+
+    /// 繰り返しているアドレス（アドレス順）
+    pub fn addresses(&self) -> Vec<MemoryAddress> {
+        let mut addresses = self.addresses.clone();
+        addresses.sort_unstable();
+        addresses
+    }
+
+    /// 状態を変えずに実行した命令数
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_detection() {
+        let mut detector = LoopDetector::new(LoopDetectionConfig { window: 2, threshold: 6 });
+        // 状態を変える命令を含むループは検出しない
+        for _ in 0..10 {
+            assert!(!detector.record(0x100, false));
+            assert!(!detector.record(0x104, true));
+        }
+        // 3個のアドレスを回るループはwindowを超えるので検出しない
+        for _ in 0..10 {
+            for pc in [0x200, 0x204, 0x208] {
+                assert!(!detector.record(pc, false));
+            }
+        }
+        detector.reset();
+        let detected: Vec<bool> = (0..6).map(|i| detector.record(0x304 - (i % 2) * 4, false)).collect();
+        assert_eq!(detected, vec![false, false, false, false, false, true]);
+        assert_eq!(detector.addresses(), vec![0x300, 0x304]);
+        assert_eq!(detector.instructions(), 6);
+    }
+}
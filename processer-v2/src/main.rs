@@ -1,249 +1,1107 @@
-mod instructions;
-mod memory;
-mod cache;
-mod processor;
-
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
 use std::path::Path;
+use std::time::Duration;
+
+use mips_simulator::assembler::Assembler;
+use mips_simulator::bench::{BenchTable, Kernel};
+use mips_simulator::branch_predictor::PredictorKind;
+use mips_simulator::cache::{ReplacementKind, StatsRegion, WritePolicy, CACHE_SETS};
+use mips_simulator::cache_trace::CacheTraceWriter;
+use mips_simulator::data_image::DataImage;
+use mips_simulator::export::{MemoryFormat, MemorySection, MemoryWords};
+use mips_simulator::footprint::DEFAULT_FOOTPRINT_PAGE;
+use mips_simulator::fpu::{ApproxConfig, ApproxTable, CustomRounding, FpuConfig};
+use mips_simulator::framebuffer::{FramebufferConfig, PixelFormat};
+use mips_simulator::memory::{MemoryAddress, MemoryBacking, MemoryDump, Protection, DEFAULT_POISON_BYTE};
+use mips_simulator::heatmap::DEFAULT_HEATMAP_BLOCK;
+use mips_simulator::history::DEFAULT_HISTORY_LENGTH;
+use mips_simulator::undo_log::DEFAULT_UNDO_LIMIT;
+use mips_simulator::watchpoint::{MemoryWatchpoint, Watchpoint};
+use mips_simulator::input::GuestInput;
+use mips_simulator::loader::{ByteOrder, HexLayout, ProgramFormat, WordOrder};
+use mips_simulator::loop_detector::LoopDetectionConfig;
+use mips_simulator::mem_trace::MemTraceWriter;
+use mips_simulator::output::GuestOutput;
+use mips_simulator::profile::DEFAULT_PROFILE_TOP;
+use mips_simulator::replay::ReplayLog;
+use mips_simulator::processor::{Isa, RunResult, INSTRUCTION_LIMIT, PC_INITIAL};
+use mips_simulator::report::{StatsComparison, StatsReport};
+use mips_simulator::scheduler::SchedulePolicy;
+use mips_simulator::symbols::SymbolTable;
+use mips_simulator::timing::TimingModel;
+use mips_simulator::{assembler, bench, disasm, framebuffer, loop_detector};
+use mips_simulator::{parse_address, MipsSimulator, SimulatorConfig};
 
-use instructions::{Instruction, InstructionType};
-use memory::MemoryAddress;
-use processor::{Processor, ProcessorError};
-
-/// シミュレータの設定
-//　ここで定義してdefault()で呼び出せるようにすることで、設定の変更が容易になり、拡張性が上がる
-#[derive(Debug, Clone)]
-pub struct SimulatorConfig {
-    /// メモリサイズ（バイト）
-    pub memory_size: usize,
-    /// プログラムの開始アドレス
-    pub program_start: MemoryAddress,
-    /// デバッグモード
-    pub debug_mode: bool,
-    /// ステップ実行モード
-    pub step_mode: bool,
+/// "<開始>-<終了>" の形式のアドレスの範囲を解析する（終了は含まない）
+fn parse_address_range(text: &str) -> Result<Range<MemoryAddress>, String> {
+    let (start, end) = text.split_once('-')
+        .ok_or_else(|| format!("範囲は <開始>-<終了> の形式で指定してください: {}", text))?;
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if start >= end {
+        return Err(format!("範囲の終了は開始より後にしてください: {}", text));
+    }
+    Ok(start..end)
 }
 
-impl Default for SimulatorConfig {
-    fn default() -> Self {
-        Self {
-            memory_size: 16 * 1024 * 1024, // 16MB
-            program_start: 0x00400000,
-            debug_mode: false,
-            step_mode: false,
-        }
+/// "<アドレス>:<幅>x<高さ>[:<形式>]" の形式のフレームバッファの設定を解析する（書き出し先はデフォルトの framebuffer.ppm）
+fn parse_framebuffer(text: &str) -> Result<FramebufferConfig, String> {
+    let mut parts = text.split(':');
+    let address = parse_address(parts.next().unwrap_or_default())?;
+    let size = parts.next()
+        .ok_or_else(|| format!("フレームバッファは <アドレス>:<幅>x<高さ>[:<形式>] の形式で指定してください: {}", text))?;
+    let (width, height) = size.split_once('x')
+        .and_then(|(width, height)| Some((width.parse::<usize>().ok()?, height.parse::<usize>().ok()?)))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .ok_or_else(|| format!("無効な解像度です: {}", size))?;
+    let format = parts.next().map(PixelFormat::parse).transpose()?.unwrap_or_default();
+    if parts.next().is_some() {
+        return Err(format!("フレームバッファは <アドレス>:<幅>x<高さ>[:<形式>] の形式で指定してください: {}", text));
     }
+    let config = FramebufferConfig { address, width, height, format, output: "framebuffer.ppm".to_string() };
+    config.range()?;
+    Ok(config)
 }
 
-/// MIPSシミュレータ
-#[derive(Debug)]
-pub struct MipsSimulator {
-    processor: Processor,
-    config: SimulatorConfig,
+/// "<開始>:<バイト数>[:<ファイル>]" の形式のメモリダンプの範囲を解析する
+fn parse_memory_dump(text: &str) -> Result<MemoryDump, String> {
+    let mut parts = text.splitn(3, ':');
+    let start = parse_address(parts.next().unwrap_or_default())?;
+    let length = parts.next()
+        .ok_or_else(|| format!("メモリダンプは <開始>:<バイト数>[:<ファイル>] の形式で指定してください: {}", text))?;
+    let length = parse_address(length).ok()
+        .filter(|&length| length > 0 && start.checked_add(length - 1).is_some())
+        .ok_or_else(|| format!("無効なバイト数です: {}", length))?;
+    let output = match parts.next() {
+        Some("") => return Err(format!("メモリダンプのファイル名が空です: {}", text)),
+        output => output.map(str::to_string),
+    };
+    Ok(MemoryDump { start, length: length as usize, output })
 }
 
-impl MipsSimulator {
-    /// 新しいシミュレータを作成
-    pub fn new(config: SimulatorConfig) -> Self {
-        let processor = Processor::with_memory_size(config.memory_size);
-        Self {
-            processor,
-            config,
-        }
-    }
-    /// デフォルト設定でシミュレータを作成
-    pub fn new_default() -> Self {
-        Self::new(SimulatorConfig::default())
-    }
-    /// プログラムをファイルから読み込む
-    // Pというジェネリック型を定義し、「PはPath（ファイルパス）として参照できる型なら何でも良い」という制約（AsRef<Path>）を付けています。
-    // これにより、この関数を呼び出す側は、ファイルパスを様々な形式で渡せるようになり、利用者の使いやすさ（エルゴノミクス）を非常に高めます。
-    // 成功すれば()（中身は空）、失敗すればSimulatorError（エラーの種類を示す列挙型）を返す
-    pub fn load_program_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SimulatorError> {
-        // ?演算子は、Result型に対して以下の処理を自動で行います。
-        // もし結果が成功 (Ok(値)) なら、Okを剥がして中の値だけを取り出す。
-        // もし結果が失敗 (Err(エラー)) なら、その場でこの関数を終了し、中のエラーを関数の呼び出し元に返す
-        let file = File::open(path).map_err(|e| SimulatorError::FileError(format!("ファイルを開けません: {}", e)))?;
-        // Fileから直接1行ずつ読むと、そのたびにOSを呼び出す必要があり、パフォーマンスが低下します。
-        // BufReaderは、最初にある程度の大きさの塊をまとめてメモリ上のバッファに読み込み、その後は高速なメモリから1行ずつ読み出します。
-        // これにより、OSの呼び出し回数が劇的に減り、処理が高速になります。
-        let reader = BufReader::new(file);
-
-        // 命令を格納するベクターを宣言
-        // let mut instructions = vec![];(マクロ呼び出し)と同じ
-        let mut instructions = Vec::new();
-        
-        for (line_num, line) in reader.lines().enumerate() {
-            let line = line.map_err(|e| SimulatorError::FileError(format!("inputfileの {} 行目を読み込めませんでした: {}", line_num + 1, e)))?;
-            let line = line.trim();
-            // 空行またはコメントをスキップ
-            if line.is_empty() || line.starts_with('#') { continue };
-            // 16進数部分のみを抽出（コメントの前まで）
-            // if letで条件が成立した場合のみcomment_posが宣言され、そのスコープはif文内のみである
-            let hex_part = if let Some(comment_pos) = line.find('#') {
-                line[..comment_pos].trim()
-            } else {
-                line
-            };
-            // まだ文字列なので16進数に変換
-            let instruction = u32::from_str_radix(hex_part, 16).map_err(|e| SimulatorError::ParseError(format!("inputfileの {} 行目: 文字列から16進数への変換に失敗しました: '{}': {}", line_num + 1, hex_part, e)))?;
-            instructions.push(instruction);
-        }
-        self.load_program(&instructions)
-    }
-    /// プログラムをメモリにロード
-    pub fn load_program(&mut self, program: &[Instruction]) -> Result<(), SimulatorError> {
-        self.processor.load_program(program, self.config.program_start).map_err(|e| SimulatorError::MemoryError(e))?;
-        Ok(())
-    }
-    /// シミュレータを実行
-    pub fn run(&mut self) -> Result<(), SimulatorError> {
-        if self.config.step_mode {
-            self.run_step_mode()?
-        } else {
-            self.processor.run().map_err(|e| SimulatorError::ProcessorError(e))?;
-        }
-        Ok(())
-    }
-    /// ステップ実行モードで実行
-    fn run_step_mode(&mut self) -> Result<(), SimulatorError> {
-        let mut step_count = 0;
-        loop {
-            println!("\n=== ステップ {} ===", step_count);
-            println!("PC: 0x{:08X}", self.processor.get_pc());
-
-            // 現在の命令を表示
-            let instruction = self.processor.fetch_instruction().map_err(|e| SimulatorError::MemoryError(e))?;
-            let instruction_type = InstructionType::decode(instruction);
-            println!("命令: 0x{:08X} ({})", instruction, instruction_type);
-            
-            // ユーザー入力を待つ
-            print!("実行しますか？ (Enter: 実行, 'q': 終了, 's': 状態表示): ");
-            io::stdout().flush().unwrap();
-            
-            let mut input = String::new();
-            // 標準入力から一行読み込み、inputに格納。.unwrap() は、読み込み中にエラーが発生した場合にプログラムを停止させる
-            io::stdin().read_line(&mut input).unwrap();
-            let input = input.trim();
-            
-            match input {
-                "q" => break,
-                "s" => {
-                    println!("{}", self.processor.dump_state());
-                    continue;
-                }
-                _ => {
-                    // 命令を実行
-                    match self.processor.step() {
-                        Ok(branch_taken) => {
-                            if branch_taken {
-                                println!("分岐が発生しました");
-                            }
-                        }
-                        Err(e) => {
-                            println!("エラー: {}", e);
-                            break;
-                        }
+/// "<開始>-<終了>=<属性>" の形式の、保護属性を設定する領域を解析する
+fn parse_protection(text: &str) -> Result<(Range<MemoryAddress>, Protection), String> {
+    let (range, protection) = text.split_once('=')
+        .ok_or_else(|| format!("保護属性は <開始>-<終了>=<属性> の形式で指定してください: {}", text))?;
+    Ok((parse_address_range(range)?, Protection::parse(protection.trim())?))
+}
+
+/// "<名前>=<開始>-<終了>" の形式の、統計を分けて数えるアドレス範囲を解析する
+fn parse_stats_region(text: &str) -> Result<StatsRegion, String> {
+    let (name, range) = text.split_once('=')
+        .ok_or_else(|| format!("範囲は <名前>=<開始>-<終了> の形式で指定してください: {}", text))?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("範囲の名前は英数字と_で指定してください: {}", name));
+    }
+    Ok(StatsRegion { name: name.to_string(), range: parse_address_range(range)? })
+}
+
+/// コマンドライン引数を解析
+fn parse_args(args: &[String]) -> Result<(SimulatorConfig, Option<String>), String> {
+    let mut config = SimulatorConfig::default();
+    let mut i = 1; // ./mainをスキップ
+    let mut program_file = None;
+    let mut framebuffer_output = None;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--memory-size" | "-m" => {
+                if i + 1 >= args.len() {
+                    return Err("--memory-size には値が必要です".to_string());
+                }
+                config.memory_size = args[i + 1].parse()
+                    .map_err(|_| "無効なメモリサイズです".to_string())?;
+                i += 2;
+            }
+            "--format" => {
+                if i + 1 >= args.len() {
+                    return Err("--format には値が必要です".to_string());
+                }
+                config.program_format = ProgramFormat::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--data-base" => {
+                if i + 1 >= args.len() {
+                    return Err("--data-base には値が必要です".to_string());
+                }
+                config.data_base = Some(parse_address(&args[i + 1])?);
+                i += 2;
+            }
+            "--hex-layout" => {
+                if i + 1 >= args.len() {
+                    return Err("--hex-layout には値が必要です".to_string());
+                }
+                config.hex_options.layout = HexLayout::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--hex-byte-order" => {
+                if i + 1 >= args.len() {
+                    return Err("--hex-byte-order には値が必要です".to_string());
+                }
+                config.hex_options.byte_order = Some(ByteOrder::parse(&args[i + 1])?);
+                i += 2;
+            }
+            "--hex-word-order" => {
+                if i + 1 >= args.len() {
+                    return Err("--hex-word-order には値が必要です".to_string());
+                }
+                config.hex_options.word_order = WordOrder::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--memory-mmap" => {
+                config.memory_backing = MemoryBacking::Anonymous;
+                i += 1;
+            }
+            "--memory-file" => {
+                if i + 1 >= args.len() {
+                    return Err("--memory-file には値が必要です".to_string());
+                }
+                config.memory_backing = MemoryBacking::File(args[i + 1].clone());
+                i += 2;
+            }
+            "--write-policy" => {
+                if i + 1 >= args.len() {
+                    return Err("--write-policy には値が必要です".to_string());
+                }
+                config.cache.write_policy = WritePolicy::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--replacement" => {
+                if i + 1 >= args.len() {
+                    return Err("--replacement には値が必要です".to_string());
+                }
+                config.cache.replacement = ReplacementKind::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--no-cache" => {
+                config.cache.bypass = true;
+                i += 1;
+            }
+            "--miss-classes" => {
+                config.cache.classify_misses = true;
+                i += 1;
+            }
+            "--uncached" => {
+                if i + 1 >= args.len() {
+                    return Err("--uncached には値が必要です".to_string());
+                }
+                config.cache.uncached_regions.push(parse_address_range(&args[i + 1])?);
+                i += 2;
+            }
+            "--protect" => {
+                if i + 1 >= args.len() {
+                    return Err("--protect には値が必要です".to_string());
+                }
+                config.protections.push(parse_protection(&args[i + 1])?);
+                i += 2;
+            }
+            "--cache-region" => {
+                if i + 1 >= args.len() {
+                    return Err("--cache-region には値が必要です".to_string());
+                }
+                let region = parse_stats_region(&args[i + 1])?;
+                if config.cache.stats_regions.iter().any(|other| other.name == region.name) {
+                    return Err(format!("範囲の名前が重複しています: {}", region.name));
+                }
+                config.cache.stats_regions.push(region);
+                i += 2;
+            }
+            "--framebuffer" => {
+                if i + 1 >= args.len() {
+                    return Err("--framebuffer には値が必要です".to_string());
+                }
+                config.framebuffer = Some(parse_framebuffer(&args[i + 1])?);
+                i += 2;
+            }
+            "--data" => {
+                if i + 1 >= args.len() {
+                    return Err("--data には値が必要です".to_string());
+                }
+                config.data_images.push(DataImage::parse(&args[i + 1])?);
+                i += 2;
+            }
+            "--rom" => {
+                if i + 1 >= args.len() {
+                    return Err("--rom には値が必要です".to_string());
+                }
+                config.rom_images.push(DataImage::parse(&args[i + 1])?);
+                i += 2;
+            }
+            "--dump-memory" => {
+                if i + 1 >= args.len() {
+                    return Err("--dump-memory には値が必要です".to_string());
+                }
+                config.memory_dumps.push(parse_memory_dump(&args[i + 1])?);
+                i += 2;
+            }
+            "--framebuffer-output" => {
+                if i + 1 >= args.len() {
+                    return Err("--framebuffer-output には値が必要です".to_string());
+                }
+                framebuffer::check_output_path(&args[i + 1])?;
+                framebuffer_output = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--bus-width" => {
+                if i + 1 >= args.len() {
+                    return Err("--bus-width には値が必要です".to_string());
+                }
+                config.bus.width = args[i + 1].parse()
+                    .map_err(|_| "無効なバス幅です".to_string())?;
+                i += 2;
+            }
+            "--bus-interval" => {
+                if i + 1 >= args.len() {
+                    return Err("--bus-interval には値が必要です".to_string());
+                }
+                config.bus.interval = args[i + 1].parse()
+                    .map_err(|_| "無効な集計区間です".to_string())?;
+                i += 2;
+            }
+            "--fpu" => {
+                if i + 1 >= args.len() {
+                    return Err("--fpu には値が必要です".to_string());
+                }
+                config.fpu = match args[i + 1].as_str() {
+                    "ieee" => FpuConfig::Ieee,
+                    "custom" => FpuConfig::Custom { rounding: CustomRounding::Truncate, flush_to_zero: true },
+                    other => return Err(format!("無効なFPUモデルです: {}", other)),
+                };
+                i += 2;
+            }
+            "--fpu-rounding" => {
+                if i + 1 >= args.len() {
+                    return Err("--fpu-rounding には値が必要です".to_string());
+                }
+                let new_rounding = match args[i + 1].as_str() {
+                    "nearest" => CustomRounding::Nearest,
+                    "truncate" => CustomRounding::Truncate,
+                    other => return Err(format!("無効な丸め方です: {}", other)),
+                };
+                match &mut config.fpu {
+                    FpuConfig::Custom { rounding, .. } => *rounding = new_rounding,
+                    FpuConfig::Ieee => return Err("--fpu-rounding は --fpu custom の後に指定してください".to_string()),
+                }
+                i += 2;
+            }
+            "--fpu-ftz" => {
+                if i + 1 >= args.len() {
+                    return Err("--fpu-ftz には値が必要です".to_string());
+                }
+                let enabled = match args[i + 1].as_str() {
+                    "on" => true,
+                    "off" => false,
+                    other => return Err(format!("--fpu-ftz には on または off を指定してください: {}", other)),
+                };
+                match &mut config.fpu {
+                    FpuConfig::Custom { flush_to_zero, .. } => *flush_to_zero = enabled,
+                    FpuConfig::Ieee => return Err("--fpu-ftz は --fpu custom の後に指定してください".to_string()),
+                }
+                i += 2;
+            }
+            "--fpu-approx" => {
+                config.fpu_approx.get_or_insert_with(ApproxConfig::default);
+                i += 1;
+            }
+            "--finv-table" | "--fsqrt-table" => {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} には値が必要です", args[i]));
+                }
+                let table = ApproxTable::load(&args[i + 1])?;
+                let approx = config.fpu_approx.get_or_insert_with(ApproxConfig::default);
+                if args[i] == "--finv-table" {
+                    approx.inv_table = table;
+                } else {
+                    approx.rsqrt_table = table;
+                }
+                i += 2;
+            }
+            "--approx-iterations" => {
+                if i + 1 >= args.len() {
+                    return Err("--approx-iterations には値が必要です".to_string());
+                }
+                let iterations = args[i + 1].parse()
+                    .map_err(|_| "無効な反復回数です".to_string())?;
+                config.fpu_approx.get_or_insert_with(ApproxConfig::default).iterations = iterations;
+                i += 2;
+            }
+            "--timeout" => {
+                if i + 1 >= args.len() {
+                    return Err("--timeout には値が必要です".to_string());
+                }
+                let seconds: f64 = args[i + 1].parse()
+                    .map_err(|_| "無効な実行時間の上限です".to_string())?;
+                if !seconds.is_finite() || seconds <= 0.0 {
+                    return Err("実行時間の上限は正の値を指定してください".to_string());
+                }
+                config.timeout = Some(Duration::from_secs_f64(seconds));
+                i += 2;
+            }
+            "--max-instructions" => {
+                if i + 1 >= args.len() {
+                    return Err("--max-instructions には値が必要です".to_string());
+                }
+                config.max_instructions = match args[i + 1].as_str() {
+                    "unlimited" => None,
+                    value => Some(value.parse()
+                        .map_err(|_| "無効な命令数の上限です（正の整数かunlimited）".to_string())?),
+                };
+                if config.max_instructions == Some(0) {
+                    return Err("命令数の上限は正の値を指定してください".to_string());
+                }
+                i += 2;
+            }
+            "--stats-json" => {
+                if i + 1 >= args.len() {
+                    return Err("--stats-json には値が必要です".to_string());
+                }
+                config.stats_json_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--load-snapshot" | "--save-snapshot" => {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} には値が必要です", args[i]));
+                }
+                let path = Some(args[i + 1].clone());
+                if args[i] == "--load-snapshot" {
+                    config.load_snapshot_path = path;
+                } else {
+                    config.save_snapshot_path = path;
+                }
+                i += 2;
+            }
+            "--isa" => {
+                if i + 1 >= args.len() {
+                    return Err("--isa には値が必要です".to_string());
+                }
+                config.isa = match args[i + 1].as_str() {
+                    "mips" => Isa::Mips,
+                    "rv32i" => Isa::Rv32i,
+                    other => return Err(format!("無効なISAです: {}", other)),
+                };
+                i += 2;
+            }
+            "--miss-penalty" => {
+                if i + 1 >= args.len() {
+                    return Err("--miss-penalty には値が必要です".to_string());
+                }
+                // --memory-latency の別名。--cache-miss-latency とは独立に設定する
+                config.timing.memory_latency = args[i + 1].parse()
+                    .map_err(|_| "無効なミスペナルティです".to_string())?;
+                i += 2;
+            }
+            "--alu-latency" | "--mul-div-latency" | "--cache-hit-latency" | "--cache-miss-latency" | "--memory-latency"
+            | "--writeback-latency" => {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} には値が必要です", args[i]));
+                }
+                let cycles: u64 = args[i + 1].parse()
+                    .map_err(|_| format!("無効なレイテンシです: {}", args[i + 1]))?;
+                let timing = &mut config.timing;
+                match args[i].as_str() {
+                    "--alu-latency" | "--mul-div-latency" if cycles == 0 => {
+                        return Err(format!("{} には1以上を指定してください", args[i]));
                     }
+                    "--alu-latency" => timing.alu_latency = cycles,
+                    "--mul-div-latency" => timing.mul_div_latency = cycles,
+                    "--cache-hit-latency" => timing.cache_hit_latency = cycles,
+                    "--cache-miss-latency" => timing.cache_miss_latency = cycles,
+                    "--writeback-latency" => timing.writeback_latency = cycles,
+                    _ => timing.memory_latency = cycles,
+                }
+                i += 2;
+            }
+            "--mispredict-penalty" => {
+                if i + 1 >= args.len() {
+                    return Err("--mispredict-penalty には値が必要です".to_string());
+                }
+                config.timing.mispredict_penalty = args[i + 1].parse()
+                    .map_err(|_| "無効な分岐予測ミスのペナルティです".to_string())?;
+                i += 2;
+            }
+            "--branch-predictor" => {
+                if i + 1 >= args.len() {
+                    return Err("--branch-predictor には値が必要です".to_string());
+                }
+                config.branch_predictors = match args[i + 1].as_str() {
+                    "all" => PredictorKind::ALL.to_vec(),
+                    list => list.split(',').map(|name| PredictorKind::parse(name.trim())).collect::<Result<_, _>>()?,
+                };
+                i += 2;
+            }
+            "--timing" => {
+                if i + 1 >= args.len() {
+                    return Err("--timing には値が必要です".to_string());
+                }
+                config.timing.model = TimingModel::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--rob-size" | "--rs-size" | "--issue-width" => {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} には値が必要です", args[i]));
+                }
+                let entries: usize = args[i + 1].parse().ok().filter(|&n| n > 0)
+                    .ok_or_else(|| format!("{} には1以上を指定してください: {}", args[i], args[i + 1]))?;
+                match args[i].as_str() {
+                    "--rob-size" => config.timing.rob_size = entries,
+                    "--rs-size" => config.timing.rs_size = entries,
+                    _ => config.timing.issue_width = entries,
+                }
+                i += 2;
+            }
+            "--mshrs" => {
+                if i + 1 >= args.len() {
+                    return Err("--mshrs には値が必要です".to_string());
+                }
+                config.timing.mshrs = args[i + 1].parse()
+                    .map_err(|_| format!("無効なMSHRの数です: {}", args[i + 1]))?;
+                i += 2;
+            }
+            "--timing-csv" => {
+                if i + 1 >= args.len() {
+                    return Err("--timing-csv には値が必要です".to_string());
+                }
+                config.timing_csv_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--mem-trace" => {
+                if i + 1 >= args.len() {
+                    return Err("--mem-trace には値が必要です".to_string());
+                }
+                config.mem_trace_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--mem-trace-range" => {
+                if i + 1 >= args.len() {
+                    return Err("--mem-trace-range には値が必要です".to_string());
+                }
+                config.mem_trace_filter.ranges.push(parse_address_range(&args[i + 1])?);
+                i += 2;
+            }
+            "--mem-trace-reg" => {
+                if i + 1 >= args.len() {
+                    return Err("--mem-trace-reg には値が必要です".to_string());
+                }
+                let register = assembler::parse_register(&args[i + 1])
+                    .map_err(|_| format!("無効なレジスタです: {}", args[i + 1]))?;
+                config.mem_trace_filter.registers.push(register);
+                i += 2;
+            }
+            "--cache-trace" => {
+                if i + 1 >= args.len() {
+                    return Err("--cache-trace には値が必要です".to_string());
+                }
+                config.cache_trace_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--env" => {
+                if i + 1 >= args.len() {
+                    return Err("--env には値が必要です".to_string());
+                }
+                config.env.parse_assignment(&args[i + 1])?;
+                i += 2;
+            }
+            "--env-file" => {
+                if i + 1 >= args.len() {
+                    return Err("--env-file には値が必要です".to_string());
+                }
+                config.env.load_file(&args[i + 1])?;
+                i += 2;
+            }
+            "--env-address" => {
+                if i + 1 >= args.len() {
+                    return Err("--env-address には値が必要です".to_string());
+                }
+                config.env_address = parse_address(&args[i + 1])?;
+                i += 2;
+            }
+            "--cores" => {
+                if i + 1 >= args.len() {
+                    return Err("--cores には値が必要です".to_string());
+                }
+                config.cores = args[i + 1].parse()
+                    .map_err(|_| "無効なコア数です".to_string())?;
+                if config.cores == 0 {
+                    return Err("コア数は1以上を指定してください".to_string());
+                }
+                i += 2;
+            }
+            "--schedule" => {
+                if i + 1 >= args.len() {
+                    return Err("--schedule には値が必要です".to_string());
+                }
+                config.schedule = SchedulePolicy::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--switch-quantum" => {
+                if i + 1 >= args.len() {
+                    return Err("--switch-quantum には値が必要です".to_string());
+                }
+                config.switch_quantum = args[i + 1].parse()
+                    .map_err(|_| "無効な切り替え間隔です".to_string())?;
+                if config.switch_quantum == 0 {
+                    return Err("切り替え間隔は1以上を指定してください".to_string());
+                }
+                i += 2;
+            }
+            "--output-file" => {
+                if i + 1 >= args.len() {
+                    return Err("--output-file には値が必要です".to_string());
+                }
+                config.output.file_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--expected-output" => {
+                if i + 1 >= args.len() {
+                    return Err("--expected-output には値が必要です".to_string());
+                }
+                config.output.expected_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--expect" => {
+                if i + 1 >= args.len() {
+                    return Err("--expect には値が必要です".to_string());
+                }
+                config.expect_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--file-sandbox" => {
+                if i + 1 >= args.len() {
+                    return Err("--file-sandbox には値が必要です".to_string());
+                }
+                config.file_sandbox = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--stdin" => {
+                if i + 1 >= args.len() {
+                    return Err("--stdin には値が必要です".to_string());
+                }
+                config.stdin_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--record" | "--replay" => {
+                if i + 1 >= args.len() {
+                    return Err(format!("{} には値が必要です", args[i]));
+                }
+                let path = Some(args[i + 1].clone());
+                if args[i] == "--record" {
+                    config.record_path = path;
+                } else {
+                    config.replay_path = path;
+                }
+                i += 2;
+            }
+            "--output-timestamps" => {
+                config.output.timestamps = true;
+                i += 1;
+            }
+            "--symbols" => {
+                if i + 1 >= args.len() {
+                    return Err("--symbols には値が必要です".to_string());
+                }
+                config.symbols_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--guest-trace" => {
+                config.guest_trace = true;
+                i += 1;
+            }
+            "--guest-stats" => {
+                config.guest_stats = true;
+                i += 1;
+            }
+            "--exception-vector" => {
+                if i + 1 >= args.len() {
+                    return Err("--exception-vector には値が必要です".to_string());
+                }
+                config.exception_vector = Some(parse_address(&args[i + 1])?);
+                i += 2;
+            }
+            "--program-start" => {
+                if i + 1 >= args.len() {
+                    return Err("--program-start には値が必要です".to_string());
+                }
+                config.program_start = parse_address(&args[i + 1])?;
+                if !config.program_start.is_multiple_of(4) {
+                    return Err(format!("プログラムの開始アドレスは4の倍数にしてください: 0x{:08X}", config.program_start));
+                }
+                i += 2;
+            }
+            "--initial-sp" => {
+                if i + 1 >= args.len() {
+                    return Err("--initial-sp には値が必要です".to_string());
+                }
+                config.initial_sp = parse_address(&args[i + 1])?;
+                i += 2;
+            }
+            "--initial-gp" => {
+                if i + 1 >= args.len() {
+                    return Err("--initial-gp には値が必要です".to_string());
+                }
+                config.initial_gp = Some(parse_address(&args[i + 1])?);
+                i += 2;
+            }
+            "--halt-address" => {
+                if i + 1 >= args.len() {
+                    return Err("--halt-address には値が必要です".to_string());
+                }
+                config.halt_address = parse_address(&args[i + 1])?;
+                i += 2;
+            }
+            "--dump-cache" => {
+                config.dump_cache = true;
+                i += 1;
+            }
+            "--coverage" => {
+                config.coverage = true;
+                i += 1;
+            }
+            "--profile" => {
+                config.profile_top.get_or_insert(DEFAULT_PROFILE_TOP);
+                i += 1;
+            }
+            "--profile-functions" => {
+                config.profile_functions = true;
+                i += 1;
+            }
+            "--profile-top" => {
+                if i + 1 >= args.len() {
+                    return Err("--profile-top には値が必要です".to_string());
+                }
+                config.profile_top = Some(args[i + 1].parse()
+                    .map_err(|_| "無効な表示件数です".to_string())?);
+                i += 2;
+            }
+            "--heatmap" => {
+                config.heatmap_block.get_or_insert(DEFAULT_HEATMAP_BLOCK);
+                i += 1;
+            }
+            "--heatmap-block" => {
+                if i + 1 >= args.len() {
+                    return Err("--heatmap-block には値が必要です".to_string());
+                }
+                let block_size = parse_address(&args[i + 1])
+                    .ok()
+                    .filter(|size| size.is_power_of_two() && *size >= 4)
+                    .ok_or_else(|| format!("ブロックの大きさは4以上の2のべき乗で指定してください: {}", args[i + 1]))?;
+                config.heatmap_block = Some(block_size);
+                i += 2;
+            }
+            "--heatmap-top" => {
+                if i + 1 >= args.len() {
+                    return Err("--heatmap-top には値が必要です".to_string());
+                }
+                config.heatmap_top = args[i + 1].parse()
+                    .map_err(|_| "無効な表示件数です".to_string())?;
+                config.heatmap_block.get_or_insert(DEFAULT_HEATMAP_BLOCK);
+                i += 2;
+            }
+            "--heatmap-csv" => {
+                if i + 1 >= args.len() {
+                    return Err("--heatmap-csv には値が必要です".to_string());
+                }
+                config.heatmap_csv_path = Some(args[i + 1].clone());
+                config.heatmap_block.get_or_insert(DEFAULT_HEATMAP_BLOCK);
+                i += 2;
+            }
+            "--footprint" => {
+                config.footprint_page.get_or_insert(DEFAULT_FOOTPRINT_PAGE);
+                i += 1;
+            }
+            "--footprint-page" => {
+                if i + 1 >= args.len() {
+                    return Err("--footprint-page には値が必要です".to_string());
+                }
+                let page_size = parse_address(&args[i + 1])
+                    .ok()
+                    .filter(|size| size.is_power_of_two() && *size >= 4)
+                    .ok_or_else(|| format!("ページの大きさは4以上の2のべき乗で指定してください: {}", args[i + 1]))?;
+                config.footprint_page = Some(page_size);
+                i += 2;
+            }
+            "--backtrace" => {
+                config.backtrace = true;
+                i += 1;
+            }
+            "--register-stats" => {
+                config.register_stats = true;
+                i += 1;
+            }
+            "--check-uninit" => {
+                config.check_uninitialized = true;
+                i += 1;
+            }
+            "--poison-memory" => {
+                config.poison_byte.get_or_insert(DEFAULT_POISON_BYTE);
+                i += 1;
+            }
+            "--poison-byte" => {
+                if i + 1 >= args.len() {
+                    return Err("--poison-byte には値が必要です".to_string());
+                }
+                let value = parse_address(&args[i + 1]).ok()
+                    .and_then(|value| u8::try_from(value).ok())
+                    .ok_or_else(|| format!("埋めるバイトは0〜255（0xFF）で指定してください: {}", args[i + 1]))?;
+                config.poison_byte = Some(value);
+                i += 2;
+            }
+            "--check-poison" => {
+                config.check_poison = true;
+                config.poison_byte.get_or_insert(DEFAULT_POISON_BYTE);
+                i += 1;
+            }
+            "--no-check-program" => {
+                config.check_program = false;
+                i += 1;
+            }
+            "--undo-limit" => {
+                if i + 1 >= args.len() {
+                    return Err("--undo-limit には値が必要です".to_string());
+                }
+                config.undo_limit = args[i + 1].parse()
+                    .map_err(|_| "無効な命令数です".to_string())?;
+                i += 2;
+            }
+            "--history" => {
+                if i + 1 >= args.len() {
+                    return Err("--history には値が必要です".to_string());
+                }
+                config.history_length = args[i + 1].parse()
+                    .map_err(|_| "無効な命令数です".to_string())?;
+                i += 2;
+            }
+            "--watch" => {
+                if i + 1 >= args.len() {
+                    return Err("--watch には値が必要です".to_string());
+                }
+                config.watchpoints.push(Watchpoint::parse(&args[i + 1])?);
+                i += 2;
+            }
+            "--watch-mem" => {
+                if i + 1 >= args.len() {
+                    return Err("--watch-mem には値が必要です".to_string());
+                }
+                config.memory_watchpoints.push(MemoryWatchpoint::parse(&args[i + 1])?);
+                i += 2;
+            }
+            "--detect-loops" => {
+                config.loop_detection.get_or_insert_with(LoopDetectionConfig::default);
+                i += 1;
+            }
+            "--loop-window" => {
+                if i + 1 >= args.len() {
+                    return Err("--loop-window には値が必要です".to_string());
                 }
+                let window = args[i + 1].parse()
+                    .map_err(|_| "無効なアドレス数です".to_string())?;
+                if window == 0 {
+                    return Err("ループとみなすアドレス数は1以上を指定してください".to_string());
+                }
+                config.loop_detection.get_or_insert_with(LoopDetectionConfig::default).window = window;
+                i += 2;
+            }
+            "--loop-threshold" => {
+                if i + 1 >= args.len() {
+                    return Err("--loop-threshold には値が必要です".to_string());
+                }
+                let threshold = args[i + 1].parse()
+                    .map_err(|_| "無効な命令数です".to_string())?;
+                if threshold == 0 {
+                    return Err("ループとみなす命令数は1以上を指定してください".to_string());
+                }
+                config.loop_detection.get_or_insert_with(LoopDetectionConfig::default).threshold = threshold;
+                i += 2;
+            }
+            "--debug" | "-d" => {
+                config.debug_mode = true;
+                i += 1;
+            }
+            "--step" | "-s" => {
+                config.step_mode = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                print_usage();
+                std::process::exit(0);
+            }
+            arg => {
+                // オプションでない場合はプログラムファイルとして扱う
+                if arg.starts_with('-') {
+                    return Err(format!("このオプションは定義されていません: {}", arg));
+                }
+                // program_fileがOption型であることを明示するためにSome(arg.to_string())とする
+                program_file = Some(arg.to_string());
+                i += 1;
             }
-            step_count += 1;
         }
-        Ok(())
     }
-
-    /// プロセッサの状態を取得
-    pub fn get_processor_state(&self) -> String {
-        self.processor.dump_state()
+    if config.hex_options.layout == HexLayout::Bytes && config.hex_options.word_order != WordOrder::Ascending {
+        return Err("--hex-word-order は --hex-layout words のときだけ指定できます".to_string());
     }
-
-    /// 統計情報を取得
-    pub fn get_stats(&self) -> &processor::ProcessorStats {
-        self.processor.get_stats()
+    if config.mem_trace_path.is_none() && !config.mem_trace_filter.is_empty() {
+        return Err("--mem-trace-range と --mem-trace-reg は --mem-trace と一緒に指定してください".to_string());
     }
-
-    /// キャッシュ統計を取得
-    pub fn get_cache_stats(&self) -> &cache::CacheStats {
-        self.processor.get_cache_stats()
+    match (&mut config.framebuffer, framebuffer_output) {
+        (Some(framebuffer), Some(output)) => framebuffer.output = output,
+        (None, Some(_)) => return Err("--framebuffer-output は --framebuffer と一緒に指定してください".to_string()),
+        _ => {}
     }
-
-    /// 設定を取得
-    pub fn get_config(&self) -> &SimulatorConfig {
-        &self.config
+    if config.cores > 1 && config.step_mode {
+        return Err("ステップ実行モードはマルチコア実行に対応していません".to_string());
     }
-
-    /// 設定を更新
-    pub fn set_config(&mut self, config: SimulatorConfig) {
-        self.config = config;
+    if config.cores > 1 && (config.load_snapshot_path.is_some() || config.save_snapshot_path.is_some()) {
+        return Err("スナップショットはマルチコア実行に対応していません".to_string());
+    }
+    if config.record_path.is_some() && config.replay_path.is_some() {
+        return Err("--record と --replay は同時に指定できません".to_string());
+    }
+    if config.cores > 1 && (config.record_path.is_some() || config.replay_path.is_some()) {
+        return Err("入力の記録・再生はマルチコア実行に対応していません".to_string());
+    }
+    if let Some(core) = config.schedule.max_core() {
+        if core >= config.cores {
+            return Err(format!("スケジュールに存在しないコア番号があります: {}（コア数: {}）", core, config.cores));
+        }
     }
+    Ok((config, program_file))
 }
 
-/// シミュレータエラー
-//std::fmt::Display	{}	最終ユーザー向け。エラーの「ユーザーフレンドリーな簡潔な説明」を提供します。
-//std::fmt::Debug	{:?}	開発者向け。デバッグ用の「構造的な詳細情報」を提供します。
-#[derive(Debug, Clone)]
-pub enum SimulatorError {
-    FileError(String),
-    ParseError(String),
-    MemoryError(memory::MemoryError),
-    ProcessorError(ProcessorError),
-}
+/// compare-statsサブコマンドのデフォルトの閾値（%）
+const DEFAULT_COMPARE_THRESHOLD: f64 = 5.0;
 
-impl std::fmt::Display for SimulatorError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            SimulatorError::FileError(msg) => write!(f, "ファイルエラー: {}", msg),
-            SimulatorError::ParseError(msg) => write!(f, "解析エラー: {}", msg),
-            SimulatorError::MemoryError(e) => write!(f, "メモリエラー: {}", e),
-            SimulatorError::ProcessorError(e) => write!(f, "プロセッサエラー: {}", e),
+/// compare-statsサブコマンド: 2つの統計JSONを比較して表示する
+/// 閾値を超えて悪化した項目があれば true を返す
+fn run_compare_stats(args: &[String]) -> Result<bool, String> {
+    let mut threshold = DEFAULT_COMPARE_THRESHOLD;
+    let mut files = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--threshold" | "-t" => {
+                if i + 1 >= args.len() {
+                    return Err("--threshold には値が必要です".to_string());
+                }
+                threshold = args[i + 1].parse()
+                    .map_err(|_| "無効な閾値です".to_string())?;
+                i += 2;
+            }
+            file => {
+                files.push(file.to_string());
+                i += 1;
+            }
         }
     }
-}
+    if files.len() != 2 {
+        return Err("比較する統計ファイルを2つ指定してください".to_string());
+    }
 
-//Display トレイト（エラーをユーザーフレンドリーに表示する）の実装が既にあるため、std::error::Error トレイトの実装は形式的なものになっている
-impl std::error::Error for SimulatorError {}
+    let load = |path: &String| -> Result<StatsReport, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("ファイル '{}' を開けません: {}", path, e))?;
+        StatsReport::from_json(&text).map_err(|e| format!("ファイル '{}' の解析に失敗しました: {}", path, e))
+    };
+    let before = load(&files[0])?;
+    let after = load(&files[1])?;
 
-/// コマンドライン引数を解析
-fn parse_args(args: &[String]) -> Result<(SimulatorConfig, Option<String>), String> {
-    let mut config = SimulatorConfig::default();
-    let mut i = 1; // ./mainをスキップ
-    let mut program_file = None;
+    let comparison = StatsComparison::new(&before, &after, threshold);
+    println!("A: {}", files[0]);
+    println!("B: {}", files[1]);
+    println!("{}", comparison);
+    Ok(comparison.regression_count() > 0)
+}
 
+/// asmサブコマンド: アセンブリ言語のソースを16進数のテキストに変換する
+fn run_asm(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut symbols_output = None;
+    let mut listing_output = None;
+    let mut origin = PC_INITIAL;
+    let mut data_base = None;
+    let mut source = None;
+    let mut i = 0;
     while i < args.len() {
         match args[i].as_str() {
-            "--memory-size" | "-m" => {
+            "-o" | "--output" => {
                 if i + 1 >= args.len() {
-                    return Err("--memory-size には値が必要です".to_string());
+                    return Err("--output には値が必要です".to_string());
                 }
-                config.memory_size = args[i + 1].parse()
-                    .map_err(|_| "無効なメモリサイズです".to_string())?;
+                output = Some(args[i + 1].clone());
                 i += 2;
             }
-            "--debug" | "-d" => {
-                config.debug_mode = true;
-                i += 1;
+            "--symbols-out" => {
+                if i + 1 >= args.len() {
+                    return Err("--symbols-out には値が必要です".to_string());
+                }
+                symbols_output = Some(args[i + 1].clone());
+                i += 2;
             }
-            "--step" | "-s" => {
-                config.step_mode = true;
-                i += 1;
+            "-l" | "--listing" => {
+                if i + 1 >= args.len() {
+                    return Err("--listing には値が必要です".to_string());
+                }
+                listing_output = Some(args[i + 1].clone());
+                i += 2;
             }
-            "--help" | "-h" => {
-                print_usage();
-                std::process::exit(0);
+            "--origin" => {
+                if i + 1 >= args.len() {
+                    return Err("--origin には値が必要です".to_string());
+                }
+                origin = parse_address(&args[i + 1])?;
+                if !origin.is_multiple_of(4) {
+                    return Err(format!("先頭のアドレスは4の倍数にしてください: 0x{:08X}", origin));
+                }
+                i += 2;
             }
-            arg => {
-                // オプションでない場合はプログラムファイルとして扱う
-                if arg.starts_with('-') {
-                    return Err(format!("このオプションは定義されていません: {}", arg));
+            "--data-base" => {
+                if i + 1 >= args.len() {
+                    return Err("--data-base には値が必要です".to_string());
                 }
-                // program_fileがOption型であることを明示するためにSome(arg.to_string())とする
-                program_file = Some(arg.to_string());
+                data_base = Some(parse_address(&args[i + 1])?);
+                i += 2;
+            }
+            file if source.is_none() => {
+                source = Some(file.to_string());
                 i += 1;
             }
+            other => return Err(format!("余分な引数です: {}", other)),
         }
     }
-    Ok((config, program_file))
+    let source = source.ok_or_else(|| "アセンブルするファイルを指定してください".to_string())?;
+    let text = std::fs::read_to_string(&source).map_err(|e| format!("'{}' を開けません: {}", source, e))?;
+    let program = Assembler::new(origin).with_data_base(data_base).assemble(&text).map_err(|e| format!("{}: {}", source, e))?;
+    let hex = program.to_hex().map_err(|e| format!("{}（--data-base を指定せずにアセンブルするか、ソースをそのまま実行してください）", e))?;
+
+    let output = output.unwrap_or_else(|| Path::new(&source).with_extension("hex").display().to_string());
+    if output == "-" {
+        print!("{}", hex);
+    } else {
+        std::fs::write(&output, hex).map_err(|e| format!("'{}' に書き込めません: {}", output, e))?;
+        println!("'{}' をアセンブルしました: {} 命令, {} ラベル -> '{}'", source, program.words.len(), program.labels.len(), output);
+    }
+    if let Some(path) = symbols_output {
+        std::fs::write(&path, SymbolTable::from_symbols(program.symbols()).to_text())
+            .map_err(|e| format!("'{}' に書き込めません: {}", path, e))?;
+    }
+    if let Some(path) = listing_output {
+        if path == "-" {
+            print!("{}", program.to_listing());
+        } else {
+            std::fs::write(&path, program.to_listing()).map_err(|e| format!("'{}' に書き込めません: {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// disasmサブコマンド: プログラムファイルを実行せずに逆アセンブルして表示する
+/// 無効な命令にデコードされるワードがあれば true を返す
+fn run_disasm(args: &[String]) -> Result<bool, String> {
+    // "disasm" をプログラム名の位置に置いたまま通常のオプション（--format・--isa・--program-start・--symbols）として解析する
+    let (config, program_file) = parse_args(args)?;
+    let program_file = program_file.ok_or_else(|| "逆アセンブルするプログラムファイルを指定してください".to_string())?;
+    let image = MipsSimulator::read_program_file(&config, &program_file)
+        .map_err(|e| e.to_string())?
+        .into_image(config.program_start);
+    let symbols = match &config.symbols_path {
+        Some(path) => SymbolTable::load_file(path)?,
+        None => SymbolTable::from_symbols(image.symbols.clone()),
+    };
+
+    let result = disasm::disassemble(&image, &symbols, config.isa);
+    println!("{}:", program_file);
+    print!("{}", result.text);
+    if result.invalid > 0 {
+        println!("\n{} 命令中 {} ワードが無効な命令です", result.words, result.invalid);
+    } else {
+        println!("\n{} 命令（無効な命令はありません）", result.words);
+    }
+    Ok(result.invalid > 0)
+}
+
+/// exportサブコマンド: プログラムファイルを読み込んだ（アセンブルした）イメージを、FPGAのメモリの初期値のファイルに書き出す
+fn run_export(args: &[String]) -> Result<(), String> {
+    // exportのオプションを取り除き、残りは "export" をプログラム名の位置に置いたまま通常のオプションとして解析する
+    let mut format = None;
+    let mut output = None;
+    let mut section = MemorySection::All;
+    let mut base = None;
+    let mut depth = None;
+    let mut rest = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let value = |name: &str| args.get(i + 1).cloned().ok_or_else(|| format!("{} には値が必要です", name));
+        match args[i].as_str() {
+            "--to" => format = Some(MemoryFormat::parse(&value("--to")?)?),
+            "-o" | "--output" => output = Some(value("--output")?),
+            "--section" => section = MemorySection::parse(&value("--section")?)?,
+            "--base" => base = Some(parse_address(&value("--base")?)?),
+            "--depth" => {
+                let text = value("--depth")?;
+                depth = Some(text.parse::<usize>().ok().filter(|&depth| depth > 0)
+                    .ok_or_else(|| format!("--depth には1以上のワード数を指定してください: {}", text))?);
+            }
+            other => {
+                rest.push(other.to_string());
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    let format = match (format, &output) {
+        (Some(format), _) => format,
+        (None, Some(output)) => Path::new(output).extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(MemoryFormat::from_extension)
+            .ok_or_else(|| format!("出力ファイルの拡張子から形式が分かりません。--to で指定してください: {}", output))?,
+        (None, None) => return Err("--to で書き出す形式（coe・mem・readmemh）を指定してください".to_string()),
+    };
+
+    let (config, program_file) = parse_args(&rest)?;
+    let program_file = program_file.ok_or_else(|| "書き出すプログラムファイルを指定してください".to_string())?;
+    let image = MipsSimulator::read_program_file(&config, &program_file)
+        .map_err(|e| e.to_string())?
+        .into_image(config.program_start);
+    let memory = MemoryWords::from_image(&image, section, base, depth)?;
+    let comment = format!("{} (0x{:08X} から {} ワード)", program_file, memory.base, memory.words.len());
+    let text = memory.to_text(format, &comment);
+
+    let output = output.unwrap_or_else(|| Path::new(&program_file).with_extension(format.extension()).display().to_string());
+    if output == "-" {
+        print!("{}", text);
+    } else {
+        std::fs::write(&output, text).map_err(|e| format!("'{}' に書き込めません: {}", output, e))?;
+        println!("'{}' を書き出しました: 0x{:08X} から {} ワード -> '{}'", program_file, memory.base, memory.words.len(), output);
+    }
+    Ok(())
+}
+
+/// benchサブコマンド: 組み込みのカーネルを現在の設定で実行し、結果を表にして表示する
+/// 失敗したカーネルがあれば true を返す
+fn run_bench(args: &[String]) -> Result<bool, String> {
+    // "bench" をプログラム名の位置に置いたまま通常のオプションとして解析する
+    let (config, kernel_name) = parse_args(args)?;
+    if config.isa != Isa::Mips {
+        return Err("組み込みのカーネルはMIPSのみに対応しています".to_string());
+    }
+    if config.cores > 1 || config.step_mode {
+        return Err("benchはシングルコアの通常実行のみに対応しています".to_string());
+    }
+    let kernels: Vec<&Kernel> = match &kernel_name {
+        Some(name) => vec![bench::find(name).ok_or_else(|| {
+            let list: Vec<String> = bench::KERNELS.iter().map(|k| format!("  {:<8} {}", k.name, k.description)).collect();
+            format!("カーネル '{}' はありません。使用できるカーネル:\n{}", name, list.join("\n"))
+        })?],
+        None => bench::KERNELS.iter().collect(),
+    };
+
+    println!("タイミング: {}", config.timing);
+    let mut table = BenchTable::default();
+    for kernel in kernels {
+        let mut simulator = MipsSimulator::try_new(config.clone()).map_err(|e| e.to_string())?;
+        table.results.push(simulator.run_kernel(kernel).map_err(|e| e.to_string())?);
+    }
+    println!("{}", table);
+    Ok(table.failure_count() > 0)
 }
 
 /// 使用方法を表示
@@ -254,10 +1112,144 @@ fn print_usage() {
     println!();
     println!("オプション:");
     println!("  -m, --memory-size <サイズ>  メモリサイズを指定（バイト単位）");
+    println!("      --format <形式>         プログラムファイルの形式（hex: 1行に1命令の16進数のテキスト、");
+    println!("                              bin: フラットなバイナリ、elf: ELF32の実行ファイル、ihex: Intel HEX、");
+    println!("                              srec: Motorola S-record、asm: アセンブリ言語のソース、");
+    println!("                              auto: 内容と拡張子から推定、デフォルト: auto）");
+    println!("      --data-base <アドレス>  アセンブリ言語のソースの .data セクションを置くアドレス（デフォルト: 命令の直後）");
+    println!("      --hex-layout <words|bytes>");
+    println!("                              16進数のテキストを1行に1ワード（空白区切りや8桁の倍数の幅で複数も可）として読むか、");
+    println!("                              行をまたいだバイト列として読むか（デフォルト: words）");
+    println!("      --hex-byte-order <big|little>");
+    println!("                              ワード内のバイト順（デフォルト: words はそのままの値、bytes はメモリ上の並び = little）");
+    println!("      --hex-word-order <ascending|descending>");
+    println!("                              1行に複数のワードがある場合に、先頭（ascending）と末尾（descending）のどちらを小さいアドレスにするか");
+    println!("      --memory-mmap           メモリを匿名のメモリマップで確保する（触れたページだけを割り当てるので、");
+    println!("                              数GBのメモリでもすぐに起動できる）");
+    println!("      --memory-file <ファイル> メモリをファイルにマップする（終了時の内容がファイルに残り、");
+    println!("                              次の実行で同じファイルを指定すると引き継げる）");
+    println!("      --isa <mips|rv32i>      実行する命令セット（デフォルト: mips）");
+    println!("      --write-policy <wb|wt|wt-noalloc> キャッシュの書き込み方式（wb: ライトバック、wt: ライトスルー、");
+    println!("                              wt-noalloc: ライトスルー・ノーライトアロケート、デフォルト: wb）");
+    println!("      --replacement <方式>     キャッシュの置換方式（lru, fifo, random[:シード], plru: 二分木の擬似LRU、デフォルト: lru）");
+    println!("      --no-cache              キャッシュを模擬せずメモリに直接アクセスする（アーキテクチャ状態だけを確かめる高速な実行）");
+    println!("      --uncached <開始>-<終了> キャッシュを経由せずメモリに直接アクセスする領域（終了は含まない、複数指定可）");
+    println!("      --protect <開始>-<終了>=<属性> 領域の保護属性（r・w・xの組み合わせかnone、複数指定可で後の指定を優先）");
+    println!("      --cache-region <名前>=<開始>-<終了> キャッシュのヒット・ミスを分けて数えるアドレス範囲（複数指定可）");
+    println!("      --miss-classes          キャッシュミスを3C（初期参照・容量・競合）に分類して表示する");
+    println!("      --bus-width <バイト>    バス幅を指定（帯域統計用、デフォルト: 4）");
+    println!("      --bus-interval <サイクル> 帯域を集計する区間の長さ（デフォルト: 1000）");
+    println!("      --fpu <ieee|custom>     浮動小数点演算のモデル（custom: 実機FPU互換）");
+    println!("      --fpu-rounding <nearest|truncate>  customモデルの丸め方（デフォルト: truncate）");
+    println!("      --fpu-ftz <on|off>      customモデルで非正規化数をゼロにするか（デフォルト: on）");
+    println!("      --fpu-approx            finv/fsqrtを組み込みテーブル + ニュートン法で近似");
+    println!("      --finv-table <ファイル>  finvの初期値テーブルを読み込む（--fpu-approxを含む）");
+    println!("      --fsqrt-table <ファイル> fsqrtの初期値テーブル（逆平方根）を読み込む（--fpu-approxを含む）");
+    println!("      --approx-iterations <n> ニュートン法の反復回数（デフォルト: 2）");
+    println!("      --timeout <秒>          実行時間の上限を指定");
+    println!("      --max-instructions <n|unlimited> 実行する命令数の上限（コアごと、デフォルト: {}）", INSTRUCTION_LIMIT);
+    println!("      --stats-json <ファイル>  実行後に統計情報をJSONで書き出す（終了理由を含む）");
+    println!("      --save-snapshot <ファイル> 実行後にレジスタ・メモリ・キャッシュ・統計をスナップショットに保存");
+    println!("      --load-snapshot <ファイル> プログラムを読み込んだ後、スナップショットの状態から実行を再開");
+    println!("      --miss-penalty <サイクル> キャッシュミス1回あたりのペナルティ（--memory-latency と同じ、デフォルト: 10）");
+    println!("      --alu-latency <サイクル> ALUのレイテンシ（デフォルト: 1）");
+    println!("      --mul-div-latency <サイクル> 浮動小数点の乗除算・平方根・逆数のレイテンシ（デフォルト: 1）");
+    println!("      --cache-hit-latency <サイクル> ロード・ストアのキャッシュアクセスに加わるサイクル数（デフォルト: 0）");
+    println!("      --cache-miss-latency <サイクル> キャッシュミスの判定と置き換えに加わるサイクル数（デフォルト: 0）");
+    println!("      --memory-latency <サイクル> メモリからキャッシュラインを読み出すサイクル数（デフォルト: 10）");
+    println!("      --writeback-latency <サイクル> ダーティラインをメモリに書き戻すサイクル数（デフォルト: 0）");
+    println!("      --timing <simple|pipeline|dual-issue|ooo> サイクル数の見積もり方（pipeline: 5段パイプライン、dual-issue: 2命令同時発行、ooo: アウトオブオーダー実行、デフォルト: simple）");
+    println!("      --mshrs <数>            pipeline・dual-issueモデルのデータキャッシュのMSHRの数（0でブロッキング、デフォルト: 0）");
+    println!("      --rob-size <エントリ数> oooモデルのリオーダーバッファの大きさ（デフォルト: 32）");
+    println!("      --rs-size <エントリ数> oooモデルの予約ステーションの大きさ（デフォルト: 16）");
+    println!("      --issue-width <命令数> oooモデルで1サイクルにフェッチ・発行・コミットできる命令数（デフォルト: 2）");
+    println!("      --branch-predictor <種類[,種類...]|all> 条件分岐を予測し、予測器ごとの正解率を表示する");
+    println!("                              （not-taken, backward-taken, bimodal, gshare。タイミングには最初の予測器を使う）");
+    println!("      --mispredict-penalty <サイクル> 分岐予測ミス1回あたりのペナルティ（simpleモデルのみ、デフォルト: 2）");
+    println!("      --timing-csv <ファイル>  実行後に命令タイミングのヒストグラムとストール要因をCSVで書き出す");
+    println!("      --cache-trace <ファイル> キャッシュへのすべてのアクセス（サイクル, PC, アドレス, 読み書き, ヒット/ミス, 追い出したライン）をCSVで書き出す");
+    println!("      --framebuffer <アドレス>:<幅>x<高さ>[:<形式>] メモリマップドのフレームバッファを置き、終了時に画像として書き出す");
+    println!("                              （形式: rgb888, rgb565, gray8、デフォルト: rgb888。画素の直後のワードに書き込むとその時点のフレームを書き出す）");
+    println!("      --framebuffer-output <ファイル> フレームバッファを書き出す画像（.ppm か .png、デフォルト: framebuffer.ppm）");
+    println!("      --mem-trace <ファイル>   ロード・ストア（PC, アドレス, バイト数, 読み書き, 値）をCSVで書き出す");
+    println!("      --mem-trace-range <開始>-<終了> --mem-trace で書き出すアクセスをこの範囲に重なるものに絞る（複数指定可）");
+    println!("      --mem-trace-reg <レジスタ> --mem-trace で書き出すアクセスをこのベースレジスタを使うものに絞る（複数指定可）");
+    println!("      --env <KEY=VALUE>       ゲストプログラムに渡す設定を追加（複数指定可）");
+    println!("      --env-file <ファイル>    1行に1つ KEY=VALUE を書いたファイルから設定を読み込む");
+    println!("      --env-address <アドレス> 設定ブロックを配置するアドレス（デフォルト: 0x00FF0000）");
+    println!("      --cores <n>             メモリを共有するn個のコアで実行（コア番号はMIPSでは$k0、RV32Iではtpに入る）");
+    println!("      --schedule <方式>        マルチコア実行でのコアの切り替え方式（デフォルト: rr）");
+    println!("                              rr: 番号順, random[:シード]: 擬似乱数, trace:<ファイル>: ファイルに書いたコア番号の順,");
+    println!("                              cycle: 見積もったサイクル数が最も少ないコア");
+    println!("      --switch-quantum <n>    コアを切り替えるまでに実行する命令数（デフォルト: 1）");
+    println!("      --output-file <ファイル> プログラムの出力をコンソールと同時にファイルにも書き出す");
+    println!("      --expected-output <ファイル> プログラムの出力を期待出力と比較し、異なれば終了コード1を返す");
+    println!("      --expect <ファイル>      実行後のレジスタとメモリの値を期待する値（\"$v0 = 55\"、\"[0x10000000] = 1 2 3\"）と比べ、異なれば終了コード1を返す");
+    println!("      --file-sandbox <ディレクトリ> ファイル入出力システムコール（13〜16）で開けるディレクトリ（指定しなければ開けない）");
+    println!("      --stdin <ファイル>       入力システムコール（read_int, read_string, read_char）を標準入力の代わりにファイルから読む");
+    println!("      --record <ファイル>      入力・ファイル操作のシステムコールの結果をファイルに記録する");
+    println!("      --replay <ファイル>      --recordで記録した結果を返して同じ実行を再現する（標準入力やホストのファイルは使わない）");
+    println!("      --output-timestamps     プログラムの出力の各行の先頭に、その行を出力し終えた時点の実行命令数を付ける");
+    println!("      --symbols <ファイル>     1行に1つ \"名前 アドレス [サイズ]\" か \"アドレス 名前\" を書いたシンボルファイル、");
+    println!("                              nm の出力、GNU ld のリンカマップを読み込み、トレースやエラーのPCに名前を付ける");
+    println!("      --guest-trace           実行トレースを止めて開始し、ゲストがシステムコール110/111で有効にした区間だけ表示");
+    println!("      --guest-stats           統計の収集を止めて開始し、ゲストがシステムコール112/113で有効にした区間だけ集める");
+    println!("      --exception-vector <アドレス> オーバーフロー・無効な命令・アドレスエラー・未対応のシステムコールで");
+    println!("                              Cause/EPCを設定してこのアドレスのハンドラへ移る（eretで復帰、MIPSのみ）");
+    println!("      --program-start <アドレス> プログラムをロードして実行を始めるアドレス（デフォルト: 0x00400000）");
+    println!("      --initial-sp <アドレス> スタックポインタ（$sp・sp）の初期値（デフォルト: 0x7FFFFFFC）");
+    println!("      --initial-gp <アドレス> グローバルポインタ（$gp・gp）の初期値（指定しなければ0）");
+    println!("      --halt-address <アドレス> このアドレスへのジャンプを正常終了として扱う（起動時の$ra・raにも入れる、デフォルト: 0xFFFFFFFF）");
+    println!("      --data <ファイル>@<アドレス> 実行前にデータ（.hexは16進数のテキスト、それ以外はバイナリ）をメモリに読み込む（複数指定可）");
+    println!("      --rom <ファイル>@<アドレス> ブートイメージを読み込み、その範囲への書き込みを禁止する（形式は --data と同じ、複数指定可）");
+    println!("      --dump-memory <開始>:<バイト数>[:<ファイル>] 実行後にメモリの16進ダンプを表示（ファイルを指定すると書き出す、複数指定可）");
+    println!("      --dump-cache            実行後にキャッシュの全ラインの状態（有効・ダーティ・タグ・置換の古さ）を表示");
+    println!("      --coverage              実行後に命令カバレッジ（命令の種類ごとの実行回数と到達しなかった命令）を表示");
+    println!("      --profile               実行後に実行回数の多い命令アドレス（ホットスポット）を表示（デフォルト: 上位{}件）", DEFAULT_PROFILE_TOP);
+    println!("      --profile-top <n>       ホットスポットとして表示する件数（--profileを含む）");
+    println!("      --profile-functions     jal/jr $raを追跡し、関数ごとの命令数・サイクル数（自身と累積）を表示（--symbolsで関数名を表示）");
+    println!("      --backtrace             jal/jr $raを追跡し、エラーで止まったときにバックトレースを表示");
+    println!("      --register-stats        実行後にレジスタごとの読み出し・書き込み回数を表示");
+    println!("      --heatmap               実行後にロード・ストアの多いアドレスのブロックを表示");
+    println!("      --heatmap-block <バイト> ヒートマップのブロックの大きさ（2のべき乗、デフォルト: 4096）");
+    println!("      --heatmap-top <n>       ヒートマップに表示するブロック数（デフォルト: 10）");
+    println!("      --heatmap-csv <ファイル> ブロックごとのロード・ストアの回数をCSVで書き出す");
+    println!("      --footprint             実行後に触れたページ・キャッシュラインの合計と、ヒープ・スタックの最大を表示");
+    println!("      --footprint-page <バイト> フットプリントを数えるページの大きさ（2のべき乗、デフォルト: 4096）");
     println!("  -d, --debug                 デバッグモードで実行");
+    println!("      --history <n>           例外で止まったときに直前に実行したn命令を表示（0で記録しない、デフォルト: {}）", DEFAULT_HISTORY_LENGTH);
+    println!("      --watch <条件>          レジスタの値が変わったら停止（\"$s0\"、\"$sp < 0x7FFF0000\" のように条件も書ける、複数指定可）");
+    println!("      --watch-mem <範囲>      メモリへのアクセスで停止（\"0x1000\" でそのワード、\"0x1000-0x1100:rw\" で範囲への読み書き、");
+    println!("                              アクセスは r・w・rw でデフォルトはw、\":log\" を付けると止めずに表示、複数指定可）");
+    println!("      --detect-loops          状態を変えずに同じ少数のアドレスを繰り返していたら無限ループとして停止（マルチコア実行では無効）");
+    println!("      --loop-window <n>       ループとみなすアドレス数の上限（--detect-loopsを含む、デフォルト: {}）", loop_detector::DEFAULT_LOOP_WINDOW);
+    println!("      --loop-threshold <n>    ループとみなす命令数（--detect-loopsを含む、デフォルト: {}）", loop_detector::DEFAULT_LOOP_THRESHOLD);
+    println!("      --check-uninit          未初期化のレジスタ（$zero・$sp以外でリセット後に書き込まれていないもの）の最初の読み出しを警告");
+    println!("      --poison-memory         メモリを0ではなく0xCCで埋めて開始し、初期化し忘れを0で隠さない");
+    println!("      --poison-byte <値>      メモリを埋めるバイト（--poison-memoryを含む）");
+    println!("      --check-poison          埋めたバイトのままのワードの読み込みを未初期化のメモリの読み込みとして警告（--poison-memoryを含む）");
+    println!("      --no-check-program      実行前のプログラムの検査（無効な命令、プログラムの外やデータへの分岐・ジャンプの警告）をしない");
     println!("  -s, --step                  ステップ実行モードで実行");
+    println!("      --undo-limit <n>        ステップ実行モードで 'b' で戻れる命令数（0で記録しない、デフォルト: {}）", DEFAULT_UNDO_LIMIT);
     println!("  -h, --help                  このヘルプを表示");
     println!();
+    println!("サブコマンド:");
+    println!("  compare-stats [-t <閾値%>] <A.json> <B.json>");
+    println!("                              2つの統計JSONを比較し、閾値を超えて悪化した項目を強調表示");
+    println!("  asm [-o <出力>] [--origin <アドレス>] [--data-base <アドレス>] [--symbols-out <ファイル>] [-l <ファイル>] <ソース.s>");
+    println!("                              ラベルを含むアセンブリ言語のソースを1行に1命令の16進数のテキストに変換");
+    println!("                              （デフォルトの出力: 拡張子を .hex にしたファイル、\"-\" で標準出力）");
+    println!("                              --symbols-out でラベルを --symbols で読めるシンボルファイルに書き出す");
+    println!("                              -l, --listing で行番号・アドレス・機械語と疑似命令の展開をリスティングに書き出す");
+    println!("  disasm [オプション] <プログラム>");
+    println!("                              プログラムファイル（--format・--isa・--program-start・--symbols に従う）を実行せずに");
+    println!("                              アドレス・機械語・命令の形で表示（無効な命令があれば終了コード1）");
+    println!("  export --to <coe|mem|readmemh> [-o <出力>] [--section <all|text|data>] [--base <アドレス>] [--depth <ワード数>] [オプション] <プログラム>");
+    println!("                              読み込んだ（アセンブルした）イメージを1行に1ワードのFPGAのメモリの初期値に書き出す");
+    println!("                              （--to を省略すると -o の拡張子から推定、デフォルトの出力: 拡張子を形式に合わせたファイル）");
+    println!("  bench [オプション] [カーネル名]");
+    println!("                              組み込みのカーネル（memcpy, matmul, fib, string）を実行して結果を表で表示");
+    println!();
     println!("例:");
     println!("  {} fibonacci.hex", std::env::args().next().unwrap_or("mips_simulator".to_string()));
     println!("  {} -d -s fibonacci.hex", std::env::args().next().unwrap_or("mips_simulator".to_string()));
@@ -265,7 +1257,53 @@ fn print_usage() {
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(String::as_str) == Some("compare-stats") {
+        match run_compare_stats(&args[2..]) {
+            Ok(regressed) => std::process::exit(if regressed { 1 } else { 0 }),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
     
+    if args.get(1).map(String::as_str) == Some("asm") {
+        if let Err(e) = run_asm(&args[2..]) {
+            eprintln!("エラー: {}", e);
+            std::process::exit(2);
+        }
+        std::process::exit(0);
+    }
+
+    if args.get(1).map(String::as_str) == Some("disasm") {
+        match run_disasm(&args[1..]) {
+            Ok(invalid) => std::process::exit(if invalid { 1 } else { 0 }),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if args.get(1).map(String::as_str) == Some("export") {
+        if let Err(e) = run_export(&args[1..]) {
+            eprintln!("エラー: {}", e);
+            std::process::exit(2);
+        }
+        std::process::exit(0);
+    }
+
+    if args.get(1).map(String::as_str) == Some("bench") {
+        match run_bench(&args[1..]) {
+            Ok(failed) => std::process::exit(if failed { 1 } else { 0 }),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
     let (config, program_file) = match parse_args(&args) {
         Ok((config, program_file)) => (config, program_file),
         Err(e) => {
@@ -284,48 +1322,381 @@ fn main() {
         }
     };
     
-    let mut simulator = MipsSimulator::new(config);
+    let output = match GuestOutput::from_config(&config.output) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let input = match &config.stdin_path {
+        Some(path) => match GuestInput::from_file(path) {
+            Ok(input) => Some(input),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let replay_log = match (&config.replay_path, &config.record_path) {
+        (Some(path), _) => match ReplayLog::load_file(path) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        },
+        (None, Some(_)) => Some(ReplayLog::recording()),
+        (None, None) => None,
+    };
+    let cache_trace = match &config.cache_trace_path {
+        Some(path) => match CacheTraceWriter::create(path) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let mem_trace = match &config.mem_trace_path {
+        Some(path) => match MemTraceWriter::create(path, config.mem_trace_filter.clone()) {
+            Ok(trace) => Some(trace),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let mut simulator = match MipsSimulator::try_new(config) {
+        Ok(simulator) => simulator,
+        Err(e) => {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    };
+    simulator.set_output(output);
+    simulator.set_cache_trace(cache_trace);
+    simulator.set_mem_trace(mem_trace);
+    if let Some(input) = input {
+        simulator.set_input(input);
+    }
+    simulator.set_replay_log(replay_log);
     
     // プログラムを読み込み
-    match simulator.load_program_from_file(&program_file) {
-        Ok(()) => {
+    let program_image = match simulator.load_program_from_file(&program_file) {
+        Ok(image) => {
             if simulator.get_config().debug_mode {
                 println!("プログラム '{}' を読み込みました", program_file);
             }
+            image
         }
         Err(e) => {
             eprintln!("エラー: プログラムの読み込みに失敗しました: {}", e);
             std::process::exit(1);
         }
-    }
+    };
     
-    // シミュレータを実行
-    match simulator.run() {
-        Ok(()) => {
-            println!("{}", simulator.get_processor_state());
+    match simulator.load_data_images() {
+        Ok(loaded) => {
+            if simulator.get_config().debug_mode {
+                for (image, length) in loaded {
+                    println!("データ '{}' を 0x{:08X} に読み込みました: {} バイト", image.path, image.address, length);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    }
+    match simulator.load_rom_images() {
+        Ok(loaded) => {
+            if simulator.get_config().debug_mode {
+                for (image, length) in loaded {
+                    println!("ROM '{}' を 0x{:08X} に読み込みました: {} バイト", image.path, image.address, length);
+                }
+            }
         }
         Err(e) => {
-            eprintln!("エラー: シミュレーション中にエラーが発生しました: {}", e);
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = simulator.get_config().symbols_path.clone() {
+        match simulator.load_symbols_from_file(&path) {
+            Ok(count) => {
+                if simulator.get_config().debug_mode {
+                    println!("シンボルファイル '{}' から {} 個のシンボルを読み込みました", path, count);
+                }
+            }
+            Err(e) => {
+                eprintln!("エラー: シンボルファイルの読み込みに失敗しました: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    // シンボルを読み込んでから検査し、問題の場所をシンボルで表示する
+    if simulator.get_config().check_program {
+        simulator.report_program_issues(&program_image);
+    }
+
+    if let Some(path) = simulator.get_config().load_snapshot_path.clone() {
+        match simulator.load_snapshot(&path) {
+            Ok(()) => println!("スナップショット '{}' から再開します: PC=0x{:08X}", path, simulator.get_processor().get_pc()),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // シミュレータを実行
+    let outcome = simulator.run();
+    let output_comparison = simulator.finish_output();
+    println!("{}", simulator.get_processor_state());
+    println!("終了理由: {}", outcome);
+    if let Some(label) = outcome.pc().and_then(|pc| simulator.get_processor().get_symbols().label(pc)) {
+        println!("停止した場所: {}", label);
+    }
+    for (core_id, core_outcome) in simulator.get_core_outcomes().iter().enumerate() {
+        if let Some(core_outcome) = core_outcome {
+            println!("  コア{}: {}", core_id, core_outcome);
+        }
+    }
+    for dump in simulator.get_config().memory_dumps.clone() {
+        match simulator.write_memory_dump(&dump) {
+            Ok(Some(text)) => print!("\n=== メモリダンプ 0x{:08X} ({} バイト) ===\n{}", dump.start, dump.length, text),
+            Ok(None) => println!("メモリダンプを '{}' に書き出しました: 0x{:08X} から {} バイト", dump.output.unwrap_or_default(), dump.start, dump.length),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if simulator.get_config().dump_cache {
+        print!("\n=== キャッシュの内容 ===\n{}", simulator.get_cache_dump(0..CACHE_SETS));
+    }
+    if simulator.get_config().coverage {
+        println!("\n=== 命令カバレッジ ===\n{}", simulator.get_coverage_report());
+    }
+    if let Some(report) = simulator.get_profile_report() {
+        println!("\n=== ホットスポット ===\n{}", report);
+    }
+    if let Some(report) = simulator.get_function_profile_report() {
+        println!("\n=== 関数プロファイル ===\n{}", report);
+    }
+    if let Some(report) = simulator.get_register_usage_report() {
+        println!("\n=== レジスタ使用回数 ===\n{}", report);
+    }
+    if let Some(report) = simulator.get_heatmap_report() {
+        println!("\n=== メモリアクセスのヒートマップ ===\n{}", report);
+    }
+    if let Some(report) = simulator.get_footprint_report() {
+        println!("\n=== メモリのフットプリント ===\n{}", report);
+    }
+    if let Some(path) = simulator.get_config().heatmap_csv_path.clone() {
+        match simulator.write_heatmap_csv(&path) {
+            Ok(blocks) => println!("ヒートマップを '{}' に書き出しました: {} ブロック", path, blocks),
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = simulator.get_config().record_path.clone() {
+        if let Err(e) = simulator.write_replay_log(&path) {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(remaining) = simulator.get_processor().replay_log().map(ReplayLog::remaining).filter(|&remaining| remaining > 0) {
+        println!("警告: 記録した入力のうち {} 件を再生しませんでした", remaining);
+    }
+    if let Some(path) = simulator.get_config().save_snapshot_path.clone() {
+        if let Err(e) = simulator.save_snapshot(&path) {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = simulator.get_config().stats_json_path.clone() {
+        if let Err(e) = simulator.write_stats_json(&path) {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = simulator.get_config().timing_csv_path.clone() {
+        if let Err(e) = simulator.write_timing_csv(&path) {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+    }
+    match simulator.finish_framebuffer() {
+        Some(Ok((path, frames))) => println!("フレームバッファを '{}' に書き出しました（途中のフレーム: {} 枚）", path, frames),
+        Some(Err(e)) => {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+        None => {}
+    }
+    match simulator.finish_cache_trace() {
+        Some(Ok(records)) => println!("キャッシュアクセスのトレースを書き出しました: {} 件", records),
+        Some(Err(e)) => {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+        None => {}
+    }
+    match simulator.finish_mem_trace() {
+        Some(Ok(records)) => println!("メモリアクセスのトレースを書き出しました: {} 件", records),
+        Some(Err(e)) => {
+            eprintln!("エラー: {}", e);
+            std::process::exit(1);
+        }
+        None => {}
+    }
+    match simulator.finish_memory_file() {
+        Some(Ok(path)) => println!("メモリの内容を '{}' に書き出しました", path),
+        Some(Err(e)) => {
+            eprintln!("エラー: {}", e);
             std::process::exit(1);
         }
+        None => {}
     }
+    if let RunResult::Exception { .. } = outcome {
+        if outcome.kind() == "exception.unimplemented_instruction" {
+            // 次に実装すべき命令が分かるよう、プログラム全体に含まれる未実装の命令をまとめて示す
+            eprintln!("このプログラムに含まれる未実装の命令（出現数）:");
+            for (mnemonic, count) in simulator.get_unimplemented_instructions() {
+                eprintln!("  {:<10} {:>6}", mnemonic, count);
+            }
+        }
+        eprintln!("エラー: シミュレーション中にエラーが発生しました: {}", outcome);
+        std::process::exit(1);
+    }
+    let mut mismatched = false;
+    if let Some(comparison) = output_comparison {
+        println!("{}", comparison);
+        mismatched |= !comparison.is_match();
+    }
+    if let Some(path) = simulator.get_config().expect_path.clone() {
+        match simulator.check_expected_state(&path) {
+            Ok(comparison) => {
+                println!("{}", comparison);
+                mismatched |= !comparison.is_match();
+            }
+            Err(e) => {
+                eprintln!("エラー: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if mismatched {
+        std::process::exit(1);
+    }
+    // テストスクリプトが標準出力を読まずに成否を判定できるよう、ゲストの終了コードを返す
+    std::process::exit(outcome.exit_status());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mips_simulator::instructions::Instruction;
+    use mips_simulator::processor::HALT_ADDRESS;
+
+    /// アセンブリの1行を命令のワードにする
+    fn asm(line: &str) -> Instruction {
+        assembler::parse_instruction(line).unwrap().encode().unwrap()
+    }
+
+    #[test]
+    fn test_max_instructions() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        let (config, _) = parse_args(&args("main --max-instructions unlimited prog.hex")).unwrap();
+        assert_eq!(config.max_instructions, None);
+        assert!(parse_args(&args("main --max-instructions 0 prog.hex")).is_err());
+
+        let (config, _) = parse_args(&args("main --max-instructions 3 prog.hex")).unwrap();
+        let mut simulator = MipsSimulator::new(config);
+        // j 0x00400000 の無限ループ
+        simulator.load_program(&[0x08100000]).unwrap();
+        let outcome = simulator.run();
+        assert_eq!(outcome, RunResult::InstructionLimit { limit: 3 });
+        assert_eq!(outcome.kind(), "instruction_limit");
+    }
+
+    #[test]
+    fn test_program_start_and_initial_pointers() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        let (config, _) = parse_args(&args("main --program-start 0x1000 --initial-sp 0x80000 --initial-gp 0x10000 prog.hex")).unwrap();
+        assert_eq!((config.program_start, config.initial_sp, config.initial_gp), (0x1000, 0x80000, Some(0x10000)));
+        assert!(parse_args(&args("main --program-start 0x1002 prog.hex")).is_err());
+
+        let mut simulator = MipsSimulator::new(config);
+        assert_eq!(simulator.get_processor().get_pc(), 0x1000);
+        simulator.load_program(&[asm("sw $gp, -4($sp)"), asm("jr $ra")]).unwrap();
+        assert_eq!(simulator.run(), RunResult::Halted { pc: HALT_ADDRESS });
+        assert_eq!(simulator.get_processor().peek_word(0x80000 - 4).unwrap(), 0x10000);
+        assert_eq!(simulator.get_processor().get_stats().instructions_executed, 2);
+    }
+
+    #[test]
+    fn test_miss_penalty_order() {
+        // --miss-penalty は --cache-miss-latency を上書きしないので、指定順によらず同じ設定になる
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+        for line in ["main --miss-penalty 20 --cache-miss-latency 5 prog.hex", "main --cache-miss-latency 5 --miss-penalty 20 prog.hex"] {
+            let (config, _) = parse_args(&args(line)).unwrap();
+            assert_eq!((config.timing.cache_miss_latency, config.timing.memory_latency), (5, 20));
+            assert_eq!(config.timing.miss_penalty(), 25);
+        }
+    }
+
+    #[test]
+    fn test_parse_address_range() {
+        assert_eq!(parse_address_range("0xFFFF0000-0xFFFF0010"), Ok(0xFFFF0000..0xFFFF0010));
+        assert_eq!(parse_address_range("16-32"), Ok(16..32));
+        assert!(parse_address_range("0x100-0x100").is_err());
+        assert!(parse_address_range("0x100").is_err());
+    }
+
+    #[test]
+    fn test_parse_stats_region() {
+        assert_eq!(parse_stats_region("scene=0x10000-0x20000"),
+            Ok(StatsRegion { name: "scene".to_string(), range: 0x10000..0x20000 }));
+        assert!(parse_stats_region("0x10000-0x20000").is_err());
+        assert!(parse_stats_region("scene data=0-16").is_err());
+        assert!(parse_stats_region("=0-16").is_err());
+    }
+
+    #[test]
+    fn test_parse_protection() {
+        assert_eq!(parse_protection("0x00400000-0x00500000=rx"),
+            Ok((0x00400000..0x00500000, Protection { read: true, write: false, execute: true })));
+        assert_eq!(parse_protection("0-0x1000=none").unwrap().1.to_string(), "---");
+        assert!(parse_protection("0x00400000-0x00500000").is_err());
+        assert!(parse_protection("0x1000-0x1000=rw").is_err());
+    }
 
     #[test]
-    fn test_simulator_creation() {
-        let config = SimulatorConfig::default();
-        let simulator = MipsSimulator::new(config);
-        assert_eq!(simulator.get_config().memory_size, 16 * 1024 * 1024);
+    fn test_parse_framebuffer() {
+        let config = parse_framebuffer("0x10000000:320x240:rgb565").unwrap();
+        assert_eq!((config.address, config.width, config.height, config.format), (0x10000000, 320, 240, PixelFormat::Rgb565));
+        assert_eq!(parse_framebuffer("0x20000:4x2").unwrap().format, PixelFormat::Rgb888);
+        assert!(parse_framebuffer("0x20000:4x0").is_err());
+        assert!(parse_framebuffer("0x20000:4x2:rgb24").is_err());
+        assert!(parse_framebuffer("0xFFFFFF00:256x256").is_err());
+        assert!(parse_args(&["sim".to_string(), "--framebuffer-output".to_string(), "out.png".to_string()]).is_err());
     }
 
     #[test]
-    fn test_load_program() {
-        let mut simulator = MipsSimulator::new_default();
-        let program = vec![0x00430820u32, 0x0000000Cu32]; // add $1, $2, $3; syscall
-        simulator.load_program(&program).unwrap();
+    fn test_parse_memory_dump() {
+        assert_eq!(parse_memory_dump("0x1000:32").unwrap(), MemoryDump { start: 0x1000, length: 32, output: None });
+        assert_eq!(parse_memory_dump("0x1000:0x10:out/result.txt").unwrap().output.as_deref(), Some("out/result.txt"));
+        assert!(parse_memory_dump("0x1000").is_err());
+        assert!(parse_memory_dump("0x1000:0").is_err());
+        assert!(parse_memory_dump("0xFFFFFFF0:0x20").is_err());
     }
 }
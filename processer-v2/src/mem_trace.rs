@@ -0,0 +1,111 @@
+//! メモリアクセスのトレース（誤った結果の原因を「誰がこのアドレスに最後に書いたか」から追うため）
+//!
+//! 1行1アクセスのCSVで、列は `pc,address,size,rw,value`。
+//! pc・address・value は0xを付けない8桁の16進数、size はバイト数、rw は r（ロード）か w（ストア）。
+//! 倍精度のロード・ストアは4バイトずつ2行になる。
+//! フィルタを指定すると、アクセスする範囲がアドレス範囲のどれかと重なるか、ベースレジスタが指定したもののどれかであるアクセスだけを書き出す。
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+
+use crate::instructions::{Register, Word};
+use crate::memory::MemoryAddress;
+
+/// CSVの見出し行
+const MEM_TRACE_HEADER: &str = "pc,address,size,rw,value";
+
+/// 書き出すアクセスの条件（どちらも空ならすべてのアクセス）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemTraceFilter {
+    /// アドレス範囲（終了は含まない）
+    pub ranges: Vec<Range<MemoryAddress>>,
+    /// ベースレジスタ
+    pub registers: Vec<Register>,
+}
+
+impl MemTraceFilter {
+    /// 条件を指定していなければ真
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.registers.is_empty()
+    }
+
+    /// アクセスが条件に合えば真
+    fn matches(&self, address: MemoryAddress, size: u32, base: Register) -> bool {
+        let end = address as u64 + size as u64;
+        self.is_empty()
+            || self.ranges.iter().any(|range| (range.start as u64) < end && address < range.end)
+            || self.registers.contains(&base)
+    }
+}
+
+/// メモリアクセスのトレースの書き出し
+#[derive(Debug)]
+pub struct MemTraceWriter {
+    path: String,
+    writer: BufWriter<File>,
+    filter: MemTraceFilter,
+    /// 書き出したアクセス数
+    records: u64,
+    /// 最初に起きた書き込みエラー（実行は止めず、書き出し終えるときに報告する）
+    error: Option<String>,
+}
+
+impl MemTraceWriter {
+    /// ファイルを作成し、見出し行を書く
+    pub fn create(path: &str, filter: MemTraceFilter) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("メモリアクセスのトレース '{}' を作成できません: {}", path, e))?;
+        let mut trace = Self { path: path.to_string(), writer: BufWriter::new(file), filter, records: 0, error: None };
+        trace.write_line(MEM_TRACE_HEADER);
+        Ok(trace)
+    }
+
+    /// 1回のアクセスを、条件に合えば書き出す（base はアドレスの計算に使ったベースレジスタ）
+    pub fn record(&mut self, pc: MemoryAddress, address: MemoryAddress, size: u32, write: bool, value: Word, base: Register) {
+        if !self.filter.matches(address, size, base) {
+            return;
+        }
+        let line = format!("{:08x},{:08x},{},{},{:08x}", pc, address, size, if write { 'w' } else { 'r' }, value);
+        self.write_line(&line);
+        self.records += 1;
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.writer, "{}", line) {
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// 書き出し終え、書き出したアクセス数を返す
+    pub fn finish(&mut self) -> Result<u64, String> {
+        if let Err(e) = self.writer.flush() {
+            self.error.get_or_insert(e.to_string());
+        }
+        match &self.error {
+            Some(e) => Err(format!("メモリアクセスのトレース '{}' に書き込めません: {}", self.path, e)),
+            None => Ok(self.records),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_trace_filter() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_mem_trace_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let filter = MemTraceFilter { ranges: vec![0x1000..0x1004, 0x2000..0x2004], registers: vec![29] };
+        let mut trace = MemTraceWriter::create(path, filter).unwrap();
+        trace.record(0x00400000, 0x0FFE, 4, true, 0x12345678, 8); // 範囲と重なる
+        trace.record(0x00400004, 0x1004, 4, false, 1, 8);
+        trace.record(0x00400008, 0x7FFFFFF0, 4, false, 2, 29); // $sp
+        assert_eq!(trace.finish(), Ok(2));
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(text, "pc,address,size,rw,value\n00400000,00000ffe,4,w,12345678\n00400008,7ffffff0,4,r,00000002\n");
+    }
+}
@@ -1,42 +1,312 @@
 //! メモリシステムの実装
 
 use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut, Range};
 
-use crate::Instruction; 
+use crate::mmap::MappedBuffer;
+use crate::mmio::MmioDevice;
+use crate::instructions::Instruction;
 
 /// メモリアドレス（バイト単位）
 pub type MemoryAddress = u32;
 
 /// メモリのサイズ（バイト単位）
-#[allow(dead_code)]
 pub const MEMORY_SIZE: usize = 1024 * 1024; // 1MB
 
+/// 未初期化のメモリを見分けるために埋めるバイトのデフォルト値
+pub const DEFAULT_POISON_BYTE: u8 = 0xCC;
+
 /// メモリシステム
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Memory {
     /// メモリデータ（バイト配列）
-    data: Vec<u8>,
+    data: MemoryData,
+    /// アドレス範囲に割り当てたデバイス
+    devices: Vec<MappedDevice>,
+    /// 保護属性を設定した領域（重なる場合は後に設定したものを優先する）
+    protections: Vec<(Range<MemoryAddress>, Protection)>,
+}
+
+/// メモリの確保方法
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum MemoryBacking {
+    /// 0で埋めた配列
+    #[default]
+    Heap,
+    /// 匿名のメモリマップ（触れたページだけが割り当てられる）
+    Anonymous,
+    /// ファイルのメモリマップ（書き込んだ内容がファイルに残る）
+    File(String),
+}
+
+/// メモリの内容を置く領域
+#[derive(Debug)]
+enum MemoryData {
+    Heap(Vec<u8>),
+    Mapped(MappedBuffer),
+}
+
+impl Deref for MemoryData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MemoryData::Heap(data) => data,
+            MemoryData::Mapped(data) => data,
+        }
+    }
+}
+
+impl DerefMut for MemoryData {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            MemoryData::Heap(data) => data,
+            MemoryData::Mapped(data) => data,
+        }
+    }
+}
+
+/// メモリアクセスの種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Access::Read => "読み込み",
+            Access::Write => "書き込み",
+            Access::Execute => "実行",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Access {
+    /// 命令によるアクセスとしての名前（エラーの表示用）
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Access::Read => "ロード",
+            Access::Write => "ストア",
+            Access::Execute => "命令フェッチ",
+        }
+    }
+}
+
+/// 領域の保護属性（設定していない領域はすべて許可する）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Protection {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Protection {
+    /// ROMの保護属性（読み込みと実行のみ許可する）
+    pub const ROM: Protection = Protection { read: true, write: false, execute: true };
+
+    /// "r"・"w"・"x" の組み合わせか、アクセスを禁止する "none" から作成（例: "rx" は読み込み専用のテキスト）
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut protection = Protection { read: false, write: false, execute: false };
+        if text == "none" {
+            return Ok(protection);
+        }
+        for c in text.chars() {
+            let flag = match c {
+                'r' => &mut protection.read,
+                'w' => &mut protection.write,
+                'x' => &mut protection.execute,
+                _ => return Err(format!("保護属性は r・w・x の組み合わせか none で指定してください: {}", text)),
+            };
+            if std::mem::replace(flag, true) {
+                return Err(format!("保護属性に同じ文字が重複しています: {}", text));
+            }
+        }
+        if text.is_empty() {
+            return Err("保護属性が空です".to_string());
+        }
+        Ok(protection)
+    }
+
+    /// アクセスを許可するか
+    pub fn allows(self, access: Access) -> bool {
+        match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        }
+    }
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |allowed: bool, c: char| if allowed { c } else { '-' };
+        write!(f, "{}{}{}", flag(self.read, 'r'), flag(self.write, 'w'), flag(self.execute, 'x'))
+    }
+}
+
+/// 実行後に書き出すメモリダンプの範囲
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDump {
+    pub start: MemoryAddress,
+    pub length: usize,
+    /// 書き出すファイル（Noneなら標準出力に表示する）
+    pub output: Option<String>,
+}
+
+/// アドレス範囲に割り当てたデバイス
+#[derive(Debug)]
+struct MappedDevice {
+    range: Range<MemoryAddress>,
+    device: Box<dyn MmioDevice>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Memory {
     /// 新しいメモリシステムを作成
-    #[allow(dead_code)]
     pub fn new() -> Self {
-        Self {
-            data: vec![0; MEMORY_SIZE],
-        }
+        Self::with_size(MEMORY_SIZE)
     }
 
     /// 指定されたサイズのメモリを作成
     pub fn with_size(size: usize) -> Self {
         Self {
-            data: vec![0; size],
+            data: MemoryData::Heap(vec![0; size]),
+            devices: Vec::new(),
+            protections: Vec::new(),
+        }
+    }
+
+    /// 指定された方法で確保したメモリを作成（大きさ0のメモリは確保方法によらず配列にする）
+    pub fn with_backing(size: usize, backing: &MemoryBacking) -> io::Result<Self> {
+        let data = match backing {
+            _ if size == 0 => MemoryData::Heap(Vec::new()),
+            MemoryBacking::Heap => MemoryData::Heap(vec![0; size]),
+            MemoryBacking::Anonymous => MemoryData::Mapped(MappedBuffer::anonymous(size)?),
+            MemoryBacking::File(path) => MemoryData::Mapped(MappedBuffer::file(path, size)?),
+        };
+        Ok(Self { data, devices: Vec::new(), protections: Vec::new() })
+    }
+
+    /// ファイルにマップしたメモリなら、書き込んだ内容をファイルに反映する
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.data {
+            MemoryData::Heap(_) => Ok(()),
+            MemoryData::Mapped(data) => data.flush(),
+        }
+    }
+
+    /// 内容を、先頭アドレスとバイト列の組で置き換え、含まれない範囲は0にする（スナップショットの復元用。デバイスの割り当ては変えない）
+    /// 0でないブロックだけを書き換えるので、マップしたメモリでも触れていないページは割り当てない
+    pub fn replace_pages(&mut self, pages: &[(usize, &[u8])]) {
+        for block in self.data.chunks_mut(4096) {
+            if block.iter().any(|&byte| byte != 0) {
+                block.fill(0);
+            }
+        }
+        for &(start, bytes) in pages {
+            self.data[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+    }
+
+    /// アドレス範囲にデバイスを割り当てる（範囲が空か、他のデバイスと重なっていればエラー）
+    /// メモリと重なる範囲ではデバイスが優先する
+    pub fn map_device(&mut self, range: Range<MemoryAddress>, device: Box<dyn MmioDevice>) -> Result<(), MemoryError> {
+        if range.is_empty() || self.devices.iter().any(|mapped| mapped.range.start < range.end && range.start < mapped.range.end) {
+            return Err(MemoryError::InvalidDeviceRange(range));
+        }
+        self.devices.push(MappedDevice { range, device });
+        Ok(())
+    }
+
+    /// 割り当てたデバイスとその範囲
+    pub fn devices(&self) -> impl Iterator<Item = (&Range<MemoryAddress>, &dyn MmioDevice)> {
+        self.devices.iter().map(|mapped| (&mapped.range, mapped.device.as_ref()))
+    }
+
+    /// 領域に保護属性を設定する（既に設定した領域と重なる部分はこちらを優先する）
+    pub fn protect(&mut self, range: Range<MemoryAddress>, protection: Protection) {
+        self.protections.push((range, protection));
+    }
+
+    /// address から length バイトへのアクセスが保護属性で許可されているか確かめる（pc はアクセスした命令のアドレス）
+    pub fn check_access(&self, address: MemoryAddress, length: u32, access: Access, pc: MemoryAddress) -> Result<(), MemoryError> {
+        if self.protections.is_empty() {
+            return Ok(());
+        }
+        for addr in (0..length).map(|i| address.wrapping_add(i)) {
+            let protection = self.protections.iter().rev().find(|(range, _)| range.contains(&addr));
+            if protection.is_some_and(|(_, protection)| !protection.allows(access)) {
+                return Err(MemoryError::ProtectionViolation { addr, access, size: length, pc });
+            }
+        }
+        Ok(())
+    }
+
+    /// アドレスにデバイスが割り当てられていれば真
+    pub fn is_device(&self, address: MemoryAddress) -> bool {
+        self.device_index(address).is_some()
+    }
+
+    /// start から length バイトの範囲にデバイスが割り当てられたアドレスが含まれていれば真
+    pub fn overlaps_device(&self, start: MemoryAddress, length: u32) -> bool {
+        let end = start as u64 + length as u64;
+        self.devices.iter().any(|mapped| (mapped.range.start as u64) < end && start < mapped.range.end)
+    }
+
+    fn device_index(&self, address: MemoryAddress) -> Option<usize> {
+        self.devices.iter().position(|mapped| mapped.range.contains(&address))
+    }
+
+    /// ワード全体が1つのデバイスの範囲に収まっていれば、その番号とオフセットを返す
+    fn word_device(&self, address: MemoryAddress) -> Option<(usize, u32)> {
+        let index = self.device_index(address)?;
+        let range = &self.devices[index].range;
+        (address.checked_add(3).is_some_and(|last| last < range.end)).then(|| (index, address - range.start))
+    }
+
+    /// バイトを読み込む（デバイスの範囲ではデバイスの状態が変わりうる）
+    pub fn load_byte(&mut self, address: MemoryAddress) -> Result<u8, MemoryError> {
+        match self.device_index(address) {
+            Some(index) => {
+                let mapped = &mut self.devices[index];
+                Ok(mapped.device.read_byte(address - mapped.range.start))
+            }
+            None => self.read_byte(address),
         }
     }
 
-    /// メモリにバイトを書き込む
-    #[allow(dead_code)]
+    /// ワードを読み込む（デバイスの範囲ではデバイスの状態が変わりうる）
+    pub fn load_word(&mut self, address: MemoryAddress) -> Result<Word, MemoryError> {
+        if let Some((index, offset)) = self.word_device(address) {
+            return Ok(self.devices[index].device.read_word(offset));
+        }
+        if !(0..4).any(|i| self.is_device(address.wrapping_add(i))) {
+            return self.read_word(address);
+        }
+        let mut value = 0;
+        for i in 0..4 {
+            value |= (self.load_byte(address.wrapping_add(i))? as Word) << (i * 8);
+        }
+        Ok(value)
+    }
+
+    /// メモリにバイトを書き込む（デバイスの範囲ではデバイスに渡す）
     pub fn write_byte(&mut self, address: MemoryAddress, value: u8) -> Result<(), MemoryError> {
+        if let Some(index) = self.device_index(address) {
+            let mapped = &mut self.devices[index];
+            mapped.device.write_byte(address - mapped.range.start, value);
+            return Ok(());
+        }
         if address as usize >= self.data.len() {
             return Err(MemoryError::AddressOutOfRange(address));
         }
@@ -44,17 +314,30 @@ impl Memory {
         Ok(())
     }
 
-    /// メモリからバイトを読み込む
-    #[allow(dead_code)]
+    /// メモリからバイトを読み込む（デバイスの範囲では状態を変えずに読む）
     pub fn read_byte(&self, address: MemoryAddress) -> Result<u8, MemoryError> {
+        if let Some(index) = self.device_index(address) {
+            let mapped = &self.devices[index];
+            return Ok(mapped.device.peek_byte(address - mapped.range.start));
+        }
         if address as usize >= self.data.len() {
             return Err(MemoryError::AddressOutOfRange(address));
         }
         Ok(self.data[address as usize])
     }
 
-    /// メモリにワード（32ビット）を書き込む（リトルエンディアン）
+    /// メモリにワード（32ビット）を書き込む（リトルエンディアン。デバイスの範囲ではデバイスに渡す）
     pub fn write_word(&mut self, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
+        if let Some((index, offset)) = self.word_device(address) {
+            self.devices[index].device.write_word(offset, value);
+            return Ok(());
+        }
+        if (0..4).any(|i| self.is_device(address.wrapping_add(i))) {
+            for i in 0..4 {
+                self.write_byte(address.wrapping_add(i), (value >> (i * 8)) as u8)?;
+            }
+            return Ok(());
+        }
         if (address as usize).saturating_add(3) >= self.data.len() {
             return Err(MemoryError::AddressOutOfRange(address));
         }
@@ -67,9 +350,15 @@ impl Memory {
         Ok(())
     }
 
-    /// メモリからワード（32ビット）を読み込む（リトルエンディアン）
-    #[allow(dead_code)]
+    /// メモリからワード（32ビット）を読み込む（リトルエンディアン。デバイスの範囲では状態を変えずに読む）
     pub fn read_word(&self, address: MemoryAddress) -> Result<Word, MemoryError> {
+        if (0..4).any(|i| self.is_device(address.wrapping_add(i))) {
+            let mut value = 0;
+            for i in 0..4 {
+                value |= (self.read_byte(address.wrapping_add(i))? as Word) << (i * 8);
+            }
+            return Ok(value);
+        }
         if (address as usize).saturating_add(3) >= self.data.len() {
             return Err(MemoryError::AddressOutOfRange(address));
         }
@@ -88,13 +377,11 @@ impl Memory {
     }
 
     /// メモリから命令を読み込む
-    #[allow(dead_code)]
     pub fn read_instruction(&self, address: MemoryAddress) -> Result<Instruction, MemoryError> {
         self.read_word(address)
     }
 
     /// メモリの指定範囲をクリア
-    #[allow(dead_code)]
     pub fn clear_range(&mut self, start: MemoryAddress, end: MemoryAddress) -> Result<(), MemoryError> {
         if start as usize >= self.data.len() || end as usize >= self.data.len() || start > end {
             return Err(MemoryError::AddressOutOfRange(start));
@@ -112,50 +399,109 @@ impl Memory {
     }
 
     /// メモリの内容をダンプ（デバッグ用）
-    #[allow(dead_code)]
     pub fn dump(&self, start: MemoryAddress, length: usize) -> String {
-        let mut result = String::new();
         let end = std::cmp::min(start as usize + length, self.data.len());
-        
-        for i in (start as usize..end).step_by(16) {
-            result.push_str(&format!("{:08X}: ", i));
-            
-            // 16バイト分の16進数表示
-            for j in 0..16 {
-                if i + j < end {
-                    result.push_str(&format!("{:02X} ", self.data[i + j]));
-                } else {
-                    result.push_str("   ");
-                }
+        format_hexdump(start, &self.data[(start as usize).min(end)..end])
+    }
+
+    /// メモリ全体を指定したバイトで埋める（未初期化の領域の読み込みを見つけやすくするため）
+    pub fn fill(&mut self, value: u8) {
+        self.data.fill(value);
+    }
+
+    /// 指定範囲のバイト列をコピーして取得
+    pub fn read_range(&self, start: MemoryAddress, length: usize) -> Result<Vec<u8>, MemoryError> {
+        let end = (start as usize).checked_add(length)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(MemoryError::AddressOutOfRange(start))?;
+        Ok(self.data[start as usize..end].to_vec())
+    }
+
+    /// 指定アドレスからバイト列を書き込む（デバイスの範囲は対象にしない）
+    pub fn write_range(&mut self, start: MemoryAddress, bytes: &[u8]) -> Result<(), MemoryError> {
+        let end = (start as usize).checked_add(bytes.len())
+            .filter(|&end| end <= self.data.len())
+            .ok_or(MemoryError::AddressOutOfRange(start))?;
+        self.data[start as usize..end].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// バイト列を16バイトごとに16進数とASCIIで整形する（先頭アドレスは境界に揃っていなくてよい）
+pub fn format_hexdump(start: MemoryAddress, bytes: &[u8]) -> String {
+    let mut result = String::new();
+
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        result.push_str(&format!("{:08X}: ", start as usize + row * 16));
+
+        // 16バイト分の16進数表示
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(byte) => result.push_str(&format!("{:02X} ", byte)),
+                None => result.push_str("   "),
             }
-            
-            result.push_str(" |");
-            
-            // ASCII文字表示
-            for j in 0..16 {
-                if i + j < end {
-                    let byte = self.data[i + j];
-                    if byte >= 32 && byte <= 126 {
-                        result.push(byte as char);
-                    } else {
-                        result.push('.');
-                    }
-                } else {
-                    result.push(' ');
-                }
+        }
+
+        result.push_str(" |");
+
+        // ASCII文字表示
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(&byte) if (32..=126).contains(&byte) => result.push(byte as char),
+                Some(_) => result.push('.'),
+                None => result.push(' '),
             }
-            
-            result.push_str("|\n");
         }
-        
-        result
+
+        result.push_str("|\n");
+    }
+
+    result
+}
+
+/// バイト列の中からパターンが現れる位置（先頭からのオフセット）をすべて返す
+/// 境界に揃っていない位置や重なり合う出現も含める
+pub fn find_pattern(haystack: &[u8], pattern: &[u8]) -> Vec<usize> {
+    if pattern.is_empty() || pattern.len() > haystack.len() {
+        return Vec::new();
     }
+    haystack.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| *window == pattern)
+        .map(|(offset, _)| offset)
+        .collect()
 }
 
 /// メモリエラー
 #[derive(Debug, Clone, PartialEq)]
 pub enum MemoryError {
     AddressOutOfRange(MemoryAddress),
+    /// 命令による範囲外のアドレスへのアクセス（size はアクセスのバイト数、pc はアクセスした命令のアドレス）
+    AccessOutOfRange { addr: MemoryAddress, access: Access, size: u32, pc: MemoryAddress },
+    /// デバイスを割り当てられない範囲（空か、他のデバイスと重なっている）
+    InvalidDeviceRange(Range<MemoryAddress>),
+    /// 保護属性で禁止されたアクセス（addr は禁止されたバイトのアドレス、size はアクセスのバイト数、pc はアクセスした命令のアドレス）
+    ProtectionViolation { addr: MemoryAddress, access: Access, size: u32, pc: MemoryAddress },
+}
+
+impl MemoryError {
+    /// 命令によるアクセスで起きたエラーに、アクセスの種類・バイト数・命令のアドレスを加える
+    pub fn in_access(self, access: Access, size: u32, pc: MemoryAddress) -> Self {
+        match self {
+            MemoryError::AddressOutOfRange(addr) => MemoryError::AccessOutOfRange { addr, access, size, pc },
+            error => error,
+        }
+    }
+
+    /// エラーの原因になったアドレス（デバイスの割り当てのエラーではNone）
+    pub fn address(&self) -> Option<MemoryAddress> {
+        match self {
+            MemoryError::AddressOutOfRange(addr)
+            | MemoryError::AccessOutOfRange { addr, .. }
+            | MemoryError::ProtectionViolation { addr, .. } => Some(*addr),
+            MemoryError::InvalidDeviceRange(_) => None,
+        }
+    }
 }
 
 impl fmt::Display for MemoryError {
@@ -164,6 +510,15 @@ impl fmt::Display for MemoryError {
             MemoryError::AddressOutOfRange(addr) => {
                 write!(f, "メモリアドレス 0x{:08X} が範囲外です", addr)
             }
+            MemoryError::AccessOutOfRange { addr, access, size, pc } => {
+                write!(f, "PC=0x{:08X} の{}（{} バイト）でメモリアドレス 0x{:08X} が範囲外です", pc, access.operation(), size, addr)
+            }
+            MemoryError::ProtectionViolation { addr, access, size, pc } => {
+                write!(f, "PC=0x{:08X} でのメモリアドレス 0x{:08X} の{}（{} バイト）は保護属性で禁止されています", pc, addr, access, size)
+            }
+            MemoryError::InvalidDeviceRange(range) => {
+                write!(f, "0x{:08X}-0x{:08X} にはデバイスを割り当てられません（空か、他のデバイスと重なっています）", range.start, range.end)
+            }
         }
     }
 }
@@ -195,6 +550,19 @@ mod tests {
         assert_eq!(memory.read_word(0x1000).unwrap(), 0x12345678);
     }
 
+    #[test]
+    fn test_replace_pages() {
+        let mut memory = Memory::with_size(0x3000);
+        memory.write_word(0x10, 0x12345678).unwrap();
+        memory.write_byte(0x2FFF, 0xCD).unwrap();
+
+        // 含まれないページは0に戻り、含まれるページだけが書き込まれる
+        memory.replace_pages(&[(0x1000, &[0xAB, 0xCD])]);
+        assert_eq!(memory.read_word(0x10).unwrap(), 0);
+        assert_eq!(memory.read_byte(0x2FFF).unwrap(), 0);
+        assert_eq!(memory.read_range(0x1000, 3).unwrap(), [0xAB, 0xCD, 0]);
+    }
+
     #[test]
     fn test_memory_address_out_of_range() {
         let memory = Memory::new();
@@ -212,4 +580,75 @@ mod tests {
         memory.write_instruction(0x1000, instruction).unwrap();
         assert_eq!(memory.read_instruction(0x1000).unwrap(), instruction);
     }
+
+    #[test]
+    fn test_find_pattern_misaligned() {
+        let mut memory = Memory::new();
+        memory.write_word(0x1001, 0xDEADBEEF).unwrap();
+        let bytes = memory.read_range(0x1000, 16).unwrap();
+
+        assert_eq!(find_pattern(&bytes, &0xDEADBEEFu32.to_le_bytes()), vec![1]);
+        assert_eq!(find_pattern(&bytes, &[0x00, 0x00]).len(), 10);
+        assert!(format_hexdump(0x1000, &bytes).starts_with("00001000: 00 EF BE AD DE"));
+    }
+
+    /// 書き込んだ値を覚え、読み込むたびに数を増やすレジスタ
+    #[derive(Debug, Default)]
+    struct Counter {
+        registers: [u8; 8],
+        reads: u8,
+    }
+
+    impl MmioDevice for Counter {
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+
+        fn read_byte(&mut self, offset: u32) -> u8 {
+            self.reads += 1;
+            self.registers[offset as usize].wrapping_add(self.reads)
+        }
+
+        fn write_byte(&mut self, offset: u32, value: u8) {
+            self.registers[offset as usize] = value;
+        }
+
+        fn peek_byte(&self, offset: u32) -> u8 {
+            self.registers[offset as usize]
+        }
+    }
+
+    #[test]
+    fn test_protection() {
+        let mut memory = Memory::new();
+        memory.protect(0x1000..0x2000, Protection::parse("rx").unwrap());
+        memory.protect(0x1800..0x1804, Protection::parse("none").unwrap());
+        assert_eq!(memory.check_access(0x1000, 4, Access::Execute, 0x1000), Ok(()));
+        assert_eq!(memory.check_access(0x0FFE, 4, Access::Write, 0x1000),
+            Err(MemoryError::ProtectionViolation { addr: 0x1000, access: Access::Write, size: 4, pc: 0x1000 }));
+        // 後に設定した領域を優先する
+        assert!(memory.check_access(0x1802, 1, Access::Read, 0x1000).is_err());
+        assert_eq!(memory.check_access(0x3000, 4, Access::Write, 0x1000), Ok(()));
+        assert_eq!(Protection::parse("rw").unwrap().to_string(), "rw-");
+        assert!(Protection::parse("rr").is_err() && Protection::parse("").is_err() && Protection::parse("rwz").is_err());
+    }
+
+    #[test]
+    fn test_mapped_device() {
+        let mut memory = Memory::with_size(0x100);
+        memory.map_device(0xFFFF0000..0xFFFF0008, Box::new(Counter::default())).unwrap();
+        assert_eq!(memory.map_device(0xFFFF0004..0xFFFF0010, Box::new(Counter::default())),
+            Err(MemoryError::InvalidDeviceRange(0xFFFF0004..0xFFFF0010)));
+        assert!(memory.map_device(0x10..0x10, Box::new(Counter::default())).is_err());
+
+        // メモリの外の範囲でもデバイスに渡る
+        memory.write_word(0xFFFF0004, 0x40302010).unwrap();
+        assert_eq!(memory.read_word(0xFFFF0004), Ok(0x40302010));
+        assert_eq!(memory.load_byte(0xFFFF0004), Ok(0x11));
+        assert_eq!(memory.load_word(0xFFFF0004), Ok(0x45342312));
+        assert!(memory.is_device(0xFFFF0007) && !memory.is_device(0xFFFF0008));
+        assert_eq!(memory.read_byte(0xFFFF0008), Err(MemoryError::AddressOutOfRange(0xFFFF0008)));
+        assert_eq!(memory.devices().map(|(range, device)| (range.clone(), device.name())).collect::<Vec<_>>(),
+            vec![(0xFFFF0000..0xFFFF0008, "counter")]);
+    }
 }
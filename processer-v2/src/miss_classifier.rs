@@ -0,0 +1,103 @@
+//! キャッシュミスの3C分類（容量と連想度のどちらを増やすべきかを判断するため）
+//!
+//! 実際のキャッシュと並行して、一度でも読み込んだラインを覚える無限の大きさのキャッシュと、
+//! 同じライン数の完全連想LRUキャッシュを影として動かし、実際のキャッシュのミスを次のように分類する。
+//! - 初期参照ミス（compulsory）: 一度も読み込んだことのないライン
+//! - 容量ミス（capacity）: 完全連想キャッシュでもミスする
+//! - 競合ミス（conflict）: 完全連想キャッシュならヒットする
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// ミスの種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissKind {
+    Compulsory,
+    Capacity,
+    Conflict,
+}
+
+/// 影のキャッシュでミスを分類する
+#[derive(Debug, Clone)]
+pub struct MissClassifier {
+    /// 完全連想キャッシュのライン数
+    capacity: usize,
+    /// 一度でも読み込んだライン（無限の大きさのキャッシュ）
+    seen: HashSet<u32>,
+    /// 完全連想キャッシュのラインと最終アクセス時刻
+    stamps: HashMap<u32, u64>,
+    /// 最終アクセス時刻の古い順のライン
+    order: BTreeMap<u64, u32>,
+    /// アクセスの通し番号
+    clock: u64,
+    /// 直前にアクセスしたライン（同じラインへの連続したアクセスを省くため）
+    last_line: Option<u32>,
+}
+
+impl MissClassifier {
+    /// capacity ラインの完全連想キャッシュを影に持つ分類器を作成
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: HashSet::new(),
+            stamps: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+            last_line: None,
+        }
+    }
+
+    /// ラインへのアクセスを影のキャッシュに反映する
+    /// 実際のキャッシュでミスした場合（miss）はその種類を返す。allocate が偽ならラインを読み込まない
+    pub fn access(&mut self, line: u32, miss: bool, allocate: bool) -> Option<MissKind> {
+        if self.last_line == Some(line) && !miss {
+            return None;
+        }
+        let kind = if !self.seen.contains(&line) {
+            MissKind::Compulsory
+        } else if !self.stamps.contains_key(&line) {
+            MissKind::Capacity
+        } else {
+            MissKind::Conflict
+        };
+        if allocate || self.stamps.contains_key(&line) {
+            self.seen.insert(line);
+            self.touch(line);
+            self.last_line = Some(line);
+        }
+        miss.then_some(kind)
+    }
+
+    /// 完全連想キャッシュでラインを最も新しくし、あふれたら最も古いラインを追い出す
+    fn touch(&mut self, line: u32) {
+        self.clock += 1;
+        if let Some(stamp) = self.stamps.insert(line, self.clock) {
+            self.order.remove(&stamp);
+        } else if self.stamps.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.stamps.remove(&oldest);
+            }
+        }
+        self.order.insert(self.clock, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_miss_classification() {
+        let mut classifier = MissClassifier::new(2);
+        assert_eq!(classifier.access(1, true, true), Some(MissKind::Compulsory));
+        assert_eq!(classifier.access(2, true, true), Some(MissKind::Compulsory));
+        // 完全連想キャッシュには1が残っている
+        assert_eq!(classifier.access(1, true, true), Some(MissKind::Conflict));
+        assert_eq!(classifier.access(3, true, true), Some(MissKind::Compulsory));
+        // 2は追い出されている
+        assert_eq!(classifier.access(2, true, true), Some(MissKind::Capacity));
+        assert_eq!(classifier.access(2, false, true), None);
+        // 読み込まないミスは影のキャッシュにも載せない
+        assert_eq!(classifier.access(4, true, false), Some(MissKind::Compulsory));
+        assert_eq!(classifier.access(4, true, false), Some(MissKind::Compulsory));
+    }
+}
@@ -0,0 +1,157 @@
+//! メモリマップした領域（数百MBを超えるメモリを、起動時に確保して0で埋めずに遅延して割り当てるため）
+//!
+//! 匿名のマップはページに初めて触れたときに0のページが割り当てられ、スワップ領域も予約しない。
+//! ファイルのマップは書き込んだ内容がそのままファイルに残るので、次の実行で同じファイルを指定すればメモリの内容を引き継げる。
+//! 外部のクレートを使わず、標準ライブラリがリンクするCライブラリの mmap を直接呼ぶ（x86_64・aarch64のLinuxとmacOSのみ）。
+//! フラグの値はアーキテクチャによって異なる（MIPSやPowerPCのLinuxでは MAP_ANONYMOUS・MAP_NORESERVE が違う）ので、値を確かめたものだけを対象にする。
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+#[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::ffi::{c_int, c_void};
+
+    pub const PROT_READ: c_int = 1;
+    pub const PROT_WRITE: c_int = 2;
+    pub const MAP_SHARED: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+    #[cfg(target_os = "linux")]
+    pub const MAP_ANONYMOUS: c_int = 0x20;
+    #[cfg(target_os = "macos")]
+    pub const MAP_ANONYMOUS: c_int = 0x1000;
+    #[cfg(target_os = "linux")]
+    pub const MAP_NORESERVE: c_int = 0x4000;
+    #[cfg(target_os = "macos")]
+    pub const MAP_NORESERVE: c_int = 0x40;
+    #[cfg(target_os = "linux")]
+    pub const MS_SYNC: c_int = 4;
+    #[cfg(target_os = "macos")]
+    pub const MS_SYNC: c_int = 0x10;
+
+    extern "C" {
+        pub fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        pub fn msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int;
+    }
+}
+
+/// メモリマップした領域（バイト列として読み書きできる）
+pub struct MappedBuffer {
+    pointer: *mut u8,
+    length: usize,
+}
+
+impl MappedBuffer {
+    /// 0で初期化された匿名の領域をマップする
+    pub fn anonymous(length: usize) -> io::Result<Self> {
+        Self::map(length, None)
+    }
+
+    /// ファイルをマップする（ファイルがなければ作成し、lengthより短ければ0で伸ばす）
+    pub fn file<P: AsRef<Path>>(path: P, length: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if file.metadata()?.len() < length as u64 {
+            file.set_len(length as u64)?;
+        }
+        // マップはファイルを閉じても有効なままである
+        Self::map(length, Some(&file))
+    }
+
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos")))]
+    fn map(length: usize, file: Option<&std::fs::File>) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        if length == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "大きさ0の領域はマップできません"));
+        }
+        let (flags, fd) = match file {
+            Some(file) => (sys::MAP_SHARED, file.as_raw_fd()),
+            None => (sys::MAP_PRIVATE | sys::MAP_ANONYMOUS | sys::MAP_NORESERVE, -1),
+        };
+        // SAFETY: 新しい領域をマップするだけで、既存のメモリには触れない
+        let pointer = unsafe { sys::mmap(std::ptr::null_mut(), length, sys::PROT_READ | sys::PROT_WRITE, flags, fd, 0) };
+        if pointer as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { pointer: pointer.cast(), length })
+    }
+
+    #[cfg(not(all(any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos"))))]
+    fn map(_length: usize, _file: Option<&std::fs::File>) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "メモリマップはx86_64・aarch64のLinuxとmacOSでのみ使えます"))
+    }
+
+    /// ファイルをマップしている場合、書き込んだ内容をファイルに反映する
+    pub fn flush(&self) -> io::Result<()> {
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos")))]
+        {
+            // SAFETY: pointerとlengthはmapで得た有効な領域
+            if unsafe { sys::msync(self.pointer.cast(), self.length, sys::MS_SYNC) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Deref for MappedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: pointerからlengthバイトはマップしている間ずっと読み書きできる
+        unsafe { std::slice::from_raw_parts(self.pointer, self.length) }
+    }
+}
+
+impl DerefMut for MappedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: 同上で、&mut selfにより他に参照はない
+        unsafe { std::slice::from_raw_parts_mut(self.pointer, self.length) }
+    }
+}
+
+impl Drop for MappedBuffer {
+    fn drop(&mut self) {
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos")))]
+        // SAFETY: mapで得た領域を一度だけ解放する（ファイルのマップは解放時に書き込んだ内容が残る）
+        unsafe {
+            sys::munmap(self.pointer.cast(), self.length);
+        }
+    }
+}
+
+impl fmt::Debug for MappedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MappedBuffer({} バイト)", self.length)
+    }
+}
+
+#[cfg(all(test, any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapped_buffer() {
+        // 触れたページだけが割り当てられる
+        let mut anonymous = MappedBuffer::anonymous(1 << 30).unwrap();
+        assert_eq!(anonymous[(1 << 29) + 7], 0);
+        anonymous[(1 << 29) + 7] = 0xAB;
+        assert_eq!(anonymous[(1 << 29) + 7], 0xAB);
+        assert!(MappedBuffer::anonymous(0).is_err());
+
+        let path = std::env::temp_dir().join(format!("mips_simulator_mmap_{}.bin", std::process::id()));
+        let mut mapped = MappedBuffer::file(&path, 8192).unwrap();
+        mapped[4096..4100].copy_from_slice(b"MIPS");
+        mapped.flush().unwrap();
+        drop(mapped);
+        let reopened = MappedBuffer::file(&path, 8192).unwrap();
+        assert_eq!(&reopened[4096..4100], b"MIPS");
+        drop(reopened);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 8192);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
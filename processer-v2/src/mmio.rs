@@ -0,0 +1,33 @@
+//! メモリマップドI/Oのデバイス（UART・タイマ・フレームバッファなどを、processor.rsでアドレスを特別扱いせずにモデル化するため）
+//!
+//! Memory::map_device でアドレス範囲にデバイスを割り当てると、その範囲へのロード・ストアはメモリではなくデバイスに渡る。
+//! デバイスの範囲にかかるラインはライン全体がキャッシュを経由せず（バス統計ではキャッシュ非経由に数える）、メモリの大きさの外に置いてもよい。
+//! デバイスに渡すオフセットは範囲の先頭からのバイト数で、ワードはリトルエンディアン。
+
+use std::fmt;
+
+use crate::memory::Word;
+
+/// メモリマップドI/Oのデバイス
+pub trait MmioDevice: fmt::Debug {
+    /// デバイス名
+    fn name(&self) -> &'static str;
+    /// バイトを読み込む（受信バッファなど、読み込みで状態が変わってもよい）
+    fn read_byte(&mut self, offset: u32) -> u8;
+    /// バイトを書き込む
+    fn write_byte(&mut self, offset: u32, value: u8);
+    /// 状態を変更せずにバイトを読み込む（デバッガ用。デフォルトは0を返す）
+    fn peek_byte(&self, _offset: u32) -> u8 {
+        0
+    }
+    /// ワードを読み込む（デフォルトは4バイトを個別に読み込む）
+    fn read_word(&mut self, offset: u32) -> Word {
+        (0..4).fold(0, |value, i| value | (self.read_byte(offset + i) as Word) << (i * 8))
+    }
+    /// ワードを書き込む（デフォルトは4バイトを個別に書き込む）
+    fn write_word(&mut self, offset: u32, value: Word) {
+        for i in 0..4 {
+            self.write_byte(offset + i, (value >> (i * 8)) as u8);
+        }
+    }
+}
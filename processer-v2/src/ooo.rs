@@ -0,0 +1,393 @@
+//! アウトオブオーダー実行（Tomasuloのアルゴリズム）のタイミングモデル
+//!
+//! pipelineモデルと同じく命令は機能シミュレーションで1つずつ実行し、ここでは各命令がフェッチ・ディスパッチ・
+//! 発行・完了・コミットするサイクルを前の命令との関係から求める（RTLで作る前に効果を見積もるためのもの）。
+//! - フェッチ・ディスパッチ（リネームしてROBと予約ステーションに入れる）・コミットはプログラム順で、1サイクルに発行幅の命令数まで
+//! - ディスパッチにはROBと予約ステーションの空きが必要で、ROBのエントリはコミットで、予約ステーションのエントリは発行で空く
+//! - 予約ステーションの命令はオペランドがそろい次第、プログラム順によらず1サイクルに発行幅の命令数まで発行する
+//! - 結果は完了したサイクルに共通データバスで後続の命令に届く（リネームするのでWAR・WAWでは待たない）
+//! - 乗除算器とメモリポートは1つずつで、ロード・ストアはプログラム順にメモリポートを使う（メモリの依存は追わない）
+//! - 予測の外れた分岐（分岐予測器がなければ成立した分岐）は完了するまで、ジャンプはディスパッチするまで後続をフェッチしない
+//! - システムコールなど直列化する命令は先行する命令がすべてコミットしてからディスパッチし、後続はそのコミットを待つ
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+use std::fmt;
+
+use crate::pipeline::{Control, PipelineOp};
+use crate::timing::{InstructionTiming, StallCause, TimingConfig};
+
+/// 依存関係を追うレジスタの数（整数32 + 浮動小数点32）
+const TRACKED_REGISTERS: usize = 64;
+
+/// アウトオブオーダー実行の統計（ストールのサイクル数はタイミング統計のストール要因にも含まれる）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OooStats {
+    /// 流した命令数
+    pub instructions: u64,
+    /// かかったサイクル数
+    pub cycles: u64,
+    /// ROBが満杯でディスパッチが遅れたサイクル数
+    pub rob_full_cycles: u64,
+    /// 予約ステーションが満杯でディスパッチが遅れたサイクル数
+    pub rs_full_cycles: u64,
+    /// 直列化する命令の前後でディスパッチが遅れたサイクル数
+    pub serialize_cycles: u64,
+    /// 同じサイクルに発行幅の命令数をディスパッチ済みで遅れたサイクル数
+    pub dispatch_width_cycles: u64,
+    /// 乗除算器・メモリポートが空くのを待って発行が遅れたサイクル数
+    pub unit_busy_cycles: u64,
+    /// 同じサイクルに発行幅の命令数を発行済みで遅れたサイクル数
+    pub issue_width_cycles: u64,
+    /// ROBに同時に入っていた命令数の最大値
+    pub max_rob_occupancy: usize,
+    /// 予約ステーションに同時に入っていた命令数の最大値
+    pub max_rs_occupancy: usize,
+}
+
+impl OooStats {
+    /// 1サイクルあたりの命令数
+    pub fn ipc(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.instructions as f64 / self.cycles as f64
+        }
+    }
+}
+
+impl fmt::Display for OooStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "IPC: {:.3} ({} 命令 / {} サイクル)", self.ipc(), self.instructions, self.cycles)?;
+        writeln!(f, "最大占有数: ROB {}, 予約ステーション {}", self.max_rob_occupancy, self.max_rs_occupancy)?;
+        writeln!(f, "ディスパッチが遅れたサイクル:")?;
+        writeln!(f, "  ROB満杯:          {:>10}", self.rob_full_cycles)?;
+        writeln!(f, "  予約ST満杯:       {:>10}", self.rs_full_cycles)?;
+        writeln!(f, "  直列化:           {:>10}", self.serialize_cycles)?;
+        writeln!(f, "  ディスパッチ幅:   {:>10}", self.dispatch_width_cycles)?;
+        writeln!(f, "発行が遅れたサイクル:")?;
+        writeln!(f, "  演算器・メモリ:   {:>10}", self.unit_busy_cycles)?;
+        write!(f, "  発行幅:           {:>10}", self.issue_width_cycles)
+    }
+}
+
+/// プログラム順に使い、1サイクルに使える数に上限がある段（フェッチ・ディスパッチ・コミット）
+#[derive(Debug, Clone, Copy, Default)]
+struct InOrderSlots {
+    /// 直前の命令が使ったサイクル
+    cycle: u64,
+    /// そのサイクルに使った命令数
+    used: usize,
+}
+
+impl InOrderSlots {
+    /// earliest 以降で、直前の命令より前でない空いたサイクルを使う
+    fn take(&mut self, earliest: u64, width: usize) -> u64 {
+        if earliest > self.cycle {
+            self.cycle = earliest;
+            self.used = 0;
+        }
+        if self.used == width {
+            self.cycle += 1;
+            self.used = 0;
+        }
+        self.used += 1;
+        self.cycle
+    }
+}
+
+/// アウトオブオーダー実行の状態
+#[derive(Debug, Clone)]
+pub struct OutOfOrderCore {
+    fetch: InOrderSlots,
+    dispatch: InOrderSlots,
+    commit: InOrderSlots,
+    /// サイクルごとに発行した命令数（ディスパッチより前のサイクルは捨てる）
+    issued: BTreeMap<u64, usize>,
+    /// ROBに入っている命令がコミットするサイクル（先頭が最も古い）
+    rob: VecDeque<u64>,
+    /// 予約ステーションに入っている命令が発行するサイクル
+    reservation: BinaryHeap<Reverse<u64>>,
+    /// フェッチが空くサイクル（命令キャッシュのミス中は次をフェッチしない）
+    fetch_free: u64,
+    /// 次の命令をフェッチできる最も早いサイクル（分岐・ジャンプで後続を捨てた場合）
+    redirect: u64,
+    /// 次の命令をディスパッチできる最も早いサイクル（直列化する命令の後）
+    serialize_until: u64,
+    /// 乗除算器が空くサイクル
+    mul_div_free: u64,
+    /// 次のロード・ストアを発行できる最も早いサイクル
+    memory_free: u64,
+    /// 直前の命令がコミットを終えたサイクル
+    last_done: u64,
+    /// 各レジスタの値が共通データバスに出るサイクル
+    ready: [u64; TRACKED_REGISTERS],
+    stats: OooStats,
+    /// 統計を数えるなら真
+    stats_enabled: bool,
+    config: TimingConfig,
+}
+
+impl Default for OutOfOrderCore {
+    fn default() -> Self {
+        Self::new(&TimingConfig::default())
+    }
+}
+
+impl OutOfOrderCore {
+    /// 設定のROB・予約ステーションの大きさと発行幅で空のコアを作成
+    pub fn new(config: &TimingConfig) -> Self {
+        Self {
+            fetch: InOrderSlots::default(),
+            dispatch: InOrderSlots::default(),
+            commit: InOrderSlots::default(),
+            issued: BTreeMap::new(),
+            rob: VecDeque::new(),
+            reservation: BinaryHeap::new(),
+            fetch_free: 0,
+            redirect: 0,
+            serialize_until: 0,
+            mul_div_free: 0,
+            memory_free: 0,
+            last_done: 0,
+            ready: [0; TRACKED_REGISTERS],
+            stats: OooStats::default(),
+            stats_enabled: true,
+            config: config.clone(),
+        }
+    }
+
+    /// 1命令を流し、直前の命令がコミットを終えてからこの命令がコミットを終えるまでのサイクルを返す
+    /// 引数の意味は Pipeline::advance と同じ
+    pub fn advance(&mut self, op: &PipelineOp, flush: bool, fetch_penalty: u64, data_penalty: u64, data_cause: StallCause) -> InstructionTiming {
+        let width = self.config.issue_width.max(1);
+        let accesses_memory = op.is_load || op.is_store;
+
+        // ディスパッチが詰まっている間は次をフェッチしない（フェッチバッファは1サイクル分）
+        let sequential = self.fetch_free.max(self.dispatch.cycle.saturating_sub(1));
+        let flushed = self.redirect.saturating_sub(sequential);
+        let fetch = self.fetch.take(sequential.max(self.redirect), width);
+        self.fetch_free = fetch + fetch_penalty;
+
+        // ディスパッチは直列化・ROB・予約ステーション・ディスパッチ幅の順に空きを待つ
+        let mut earliest = fetch + 1 + fetch_penalty;
+        let serialize = if op.serializing { self.commit.cycle + 1 } else { 0 }.max(self.serialize_until);
+        self.count(|stats| stats.serialize_cycles += serialize.saturating_sub(earliest));
+        earliest = earliest.max(serialize);
+        let before_rob = earliest;
+        while self.rob.front().is_some_and(|&commit| commit <= earliest) {
+            self.rob.pop_front();
+        }
+        if self.rob.len() >= self.config.rob_size.max(1) {
+            earliest = earliest.max(self.rob.pop_front().unwrap_or(0));
+        }
+        self.count(|stats| stats.rob_full_cycles += earliest - before_rob);
+        let before_rs = earliest;
+        while self.reservation.peek().is_some_and(|&Reverse(issue)| issue <= earliest) {
+            self.reservation.pop();
+        }
+        if self.reservation.len() >= self.config.rs_size.max(1) {
+            if let Some(Reverse(issue)) = self.reservation.pop() {
+                earliest = earliest.max(issue);
+            }
+        }
+        self.count(|stats| stats.rs_full_cycles += earliest - before_rs);
+        let in_order = earliest.max(self.dispatch.cycle);
+        let dispatch = self.dispatch.take(earliest, width);
+        self.count(|stats| stats.dispatch_width_cycles += dispatch - in_order);
+        while self.rob.front().is_some_and(|&commit| commit <= dispatch) {
+            self.rob.pop_front();
+        }
+        while self.reservation.peek().is_some_and(|&Reverse(issue)| issue <= dispatch) {
+            self.reservation.pop();
+        }
+
+        // オペランドがそろい、演算器が空き、そのサイクルの発行数に余裕があれば発行する
+        let operands_ready = op.sources.iter().map(|&reg| self.ready[reg as usize]).max().unwrap_or(0);
+        let without_dependency = dispatch + 1;
+        let mut candidate = without_dependency.max(operands_ready);
+        let before_unit = candidate;
+        if op.is_mul_div {
+            candidate = candidate.max(self.mul_div_free);
+        }
+        if accesses_memory {
+            candidate = candidate.max(self.memory_free);
+        }
+        self.count(|stats| stats.unit_busy_cycles += candidate - before_unit);
+        let mut issue = candidate;
+        while self.issued.get(&issue).is_some_and(|&count| count >= width) {
+            issue += 1;
+        }
+        self.count(|stats| stats.issue_width_cycles += issue - candidate);
+        *self.issued.entry(issue).or_insert(0) += 1;
+        self.issued = self.issued.split_off(&dispatch);
+
+        // 演算器のレイテンシの後、ロード・ストアはさらにキャッシュアクセスの後に完了する
+        let ex_cycles = self.config.execute_latency(op.is_mul_div).max(1);
+        let mem_cycles = 1 + if accesses_memory { self.config.cache_hit_latency } else { 0 };
+        let complete = issue + ex_cycles + if accesses_memory { mem_cycles + data_penalty } else { 0 };
+        if op.is_mul_div {
+            self.mul_div_free = issue + ex_cycles;
+        }
+        if accesses_memory {
+            self.memory_free = issue + mem_cycles + data_penalty;
+        }
+        if let Some(dest) = op.dest {
+            self.ready[dest as usize] = complete;
+        }
+        let commit = self.commit.take(complete + 1, width);
+
+        self.rob.push_back(commit);
+        self.reservation.push(Reverse(issue));
+        let occupancy = (self.rob.len(), self.reservation.len());
+        self.count(|stats| {
+            stats.max_rob_occupancy = stats.max_rob_occupancy.max(occupancy.0);
+            stats.max_rs_occupancy = stats.max_rs_occupancy.max(occupancy.1);
+        });
+        self.serialize_until = if op.serializing { commit + 1 } else { 0 };
+        self.redirect = match (flush, op.control) {
+            (true, Control::Jump) => dispatch + 1,
+            (true, Control::Branch) => complete,
+            _ => 0,
+        };
+
+        // 直前の命令からの増分のうち1サイクル（同時にコミットした場合は0）を超える分をストールとして要因に割り振る
+        // 要因が重なった場合は先に挙げたものを優先し、説明できない残り（ROB・予約ステーション・演算器の空き待ちなど）は構造ハザードとする
+        let done = commit + 1;
+        let delta = done.saturating_sub(self.last_done);
+        self.last_done = self.last_done.max(done);
+        self.count(|stats| {
+            stats.instructions += 1;
+            stats.cycles += delta;
+        });
+        let base = delta.min(1);
+        let mut remaining = delta - base;
+        let mut stalls = Vec::new();
+        let candidates = [
+            (StallCause::Fetch, fetch_penalty),
+            (StallCause::ControlHazard, flushed),
+            (data_cause, data_penalty),
+            (StallCause::Dependency, operands_ready.saturating_sub(without_dependency)),
+            (StallCause::MultiCycle, ex_cycles + mem_cycles - 2),
+        ];
+        for (cause, cycles) in candidates {
+            let cycles = cycles.min(remaining);
+            if cycles > 0 {
+                stalls.push((cause, cycles));
+                remaining -= cycles;
+            }
+        }
+        if remaining > 0 {
+            stalls.push((StallCause::Structural, remaining));
+        }
+        InstructionTiming { base, stalls }
+    }
+
+    /// アウトオブオーダー実行の統計
+    pub fn stats(&self) -> &OooStats {
+        &self.stats
+    }
+
+    /// アウトオブオーダー実行の統計を置き換える
+    pub fn set_stats(&mut self, stats: OooStats) {
+        self.stats = stats;
+    }
+
+    /// 統計を数えるかを切り替える（止めている間もタイミングは同じように進める）
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats_enabled = enabled;
+    }
+
+    /// 統計を数えていれば更新する
+    fn count(&mut self, update: impl FnOnce(&mut OooStats)) {
+        if self.stats_enabled {
+            update(&mut self.stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::InstructionType;
+    use crate::pipeline::Pipeline;
+    use crate::timing::TimingModel;
+
+    fn ooo_config(rob_size: usize, rs_size: usize, issue_width: usize) -> TimingConfig {
+        TimingConfig { model: TimingModel::OutOfOrder, rob_size, rs_size, issue_width, ..TimingConfig::default() }
+    }
+
+    #[test]
+    fn test_ooo_hides_miss_latency() {
+        use InstructionType as I;
+        // ミスしたロードの結果を待つ命令の後に、それと独立した乗算が続く
+        let program = [
+            (I::Lw { rt: 8, rs: 0, imm: 0 }, 20),
+            (I::Add { rd: 9, rs: 8, rt: 8 }, 0),
+            (I::MulS { fd: 2, fs: 0, ft: 0 }, 0),
+            (I::MulS { fd: 4, fs: 0, ft: 0 }, 0),
+        ];
+        let config = TimingConfig { mul_div_latency: 8, ..ooo_config(32, 16, 1) };
+        let mut pipeline = Pipeline::new(&TimingConfig { model: TimingModel::Pipeline, ..config.clone() });
+        let mut core = OutOfOrderCore::new(&config);
+        let mut in_order = 0;
+        let mut timings = Vec::new();
+        for (instruction, penalty) in &program {
+            let op = PipelineOp::from_mips(instruction);
+            in_order += pipeline.advance(&op, false, 0, *penalty, StallCause::Load).total();
+            timings.push(core.advance(&op, false, 0, *penalty, StallCause::Load));
+        }
+        let total: u64 = timings.iter().map(InstructionTiming::total).sum();
+
+        // 乗算はミスの間に実行し、コミットだけがロードを待つ
+        assert_eq!(timings[0].total(), 5 + 1 + 20);
+        assert!(timings[1..].iter().all(|timing| timing.total() == 1), "{:?}", timings);
+        assert!(total + 10 < in_order, "{} / {}", total, in_order);
+        assert_eq!((core.stats().instructions, core.stats().cycles), (4, total));
+
+        // 直前の長い演算の結果を待つ分は依存によるストール
+        let mut core = OutOfOrderCore::new(&TimingConfig { mul_div_latency: 8, ..ooo_config(32, 16, 2) });
+        core.advance(&PipelineOp::from_mips(&I::DivS { fd: 2, fs: 0, ft: 0 }), false, 0, 0, StallCause::Load);
+        let timing = core.advance(&PipelineOp::from_mips(&I::DivS { fd: 4, fs: 2, ft: 2 }), false, 0, 0, StallCause::Load);
+        assert_eq!(timing.stalls, vec![(StallCause::Dependency, 7)]);
+    }
+
+    #[test]
+    fn test_ooo_structural_stalls() {
+        use InstructionType as I;
+        let miss = PipelineOp::from_mips(&I::Lw { rt: 8, rs: 0, imm: 0 });
+        let independent = PipelineOp::from_mips(&I::Addi { rt: 9, rs: 0, imm: 1 });
+        let dependent = PipelineOp::from_mips(&I::Add { rd: 10, rs: 8, rt: 0 });
+
+        // ROBが小さいと、ミスしたロードがコミットするまで後続をディスパッチできない
+        let mut small = OutOfOrderCore::new(&ooo_config(4, 16, 2));
+        let mut large = OutOfOrderCore::new(&ooo_config(64, 16, 2));
+        for core in [&mut small, &mut large] {
+            core.advance(&miss, false, 0, 30, StallCause::Load);
+            for _ in 0..16 {
+                core.advance(&independent, false, 0, 0, StallCause::Load);
+            }
+        }
+        assert!(small.stats().rob_full_cycles > 0);
+        assert_eq!(small.stats().max_rob_occupancy, 4);
+        assert_eq!(large.stats().rob_full_cycles, 0);
+        assert!(small.stats().ipc() < large.stats().ipc());
+
+        // ロードの結果を待つ命令で予約ステーションが埋まる
+        let mut core = OutOfOrderCore::new(&ooo_config(64, 2, 2));
+        core.advance(&miss, false, 0, 30, StallCause::Load);
+        for _ in 0..4 {
+            core.advance(&dependent, false, 0, 0, StallCause::Load);
+        }
+        assert!(core.stats().rs_full_cycles > 0);
+        assert_eq!(core.stats().max_rs_occupancy, 2);
+
+        // 同じサイクルには発行幅の数までしかディスパッチできない
+        let mut core = OutOfOrderCore::new(&ooo_config(64, 16, 1));
+        for _ in 0..4 {
+            core.advance(&independent, false, 0, 0, StallCause::Load);
+        }
+        assert_eq!((core.stats().instructions, core.stats().cycles), (4, 8));
+        assert_eq!(core.stats().dispatch_width_cycles, 0);
+    }
+}
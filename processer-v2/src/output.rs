@@ -0,0 +1,273 @@
+//! ゲストプログラムの出力（システムコールによる出力）の書き出し先
+//!
+//! 出力はコンソール、ファイル、期待出力との比較のそれぞれに同時に送られる。
+//! シミュレータを組み込んで使う場合は、コンソールの代わりにメモリ上へバイト列のまま取り込める。
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+/// ゲストプログラムの出力の設定
+#[derive(Debug, Clone, Default)]
+pub struct OutputConfig {
+    /// 出力をコンソールと同時に書き出すファイル
+    pub file_path: Option<String>,
+    /// 出力と比較する期待出力のファイル
+    pub expected_path: Option<String>,
+    /// コンソールとファイルへの出力の各行の先頭に実行命令数を付けるか
+    pub timestamps: bool,
+}
+
+/// 期待出力との比較結果
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputComparison {
+    /// 一致した
+    Match,
+    /// 途中で異なる内容が出力された
+    Mismatch { offset: usize, line: usize, expected: String, actual: String },
+    /// 期待出力の途中で出力が終わった
+    Missing { offset: usize, line: usize, expected: String },
+}
+
+impl OutputComparison {
+    /// 一致したかどうか
+    pub fn is_match(&self) -> bool {
+        matches!(self, OutputComparison::Match)
+    }
+}
+
+impl fmt::Display for OutputComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputComparison::Match => write!(f, "出力は期待出力と一致しました"),
+            OutputComparison::Mismatch { offset, line, expected, actual } => {
+                writeln!(f, "出力が期待出力と {} 行目で異なります（オフセット {}）", line, offset)?;
+                writeln!(f, "  期待: {:?}", expected)?;
+                write!(f, "  実際: {:?}", actual)
+            }
+            OutputComparison::Missing { offset, line, expected } => {
+                writeln!(f, "出力が期待出力の {} 行目で終わりました（オフセット {}）", line, offset)?;
+                write!(f, "  期待: {:?}", expected)
+            }
+        }
+    }
+}
+
+/// 出力を期待出力と逐次比較する
+#[derive(Debug)]
+struct ExpectedOutput {
+    expected: Vec<u8>,
+    /// これまでに比較したバイト数
+    position: usize,
+    /// 現在の行番号（1から）
+    line: usize,
+    /// 現在の行のこれまでの出力
+    current_line: Vec<u8>,
+    /// 最初に異なった位置と、その行の実際の出力
+    mismatch: Option<(usize, usize, Vec<u8>)>,
+    /// 異なった行の出力をまだ集めているか
+    collecting: bool,
+}
+
+impl ExpectedOutput {
+    fn new(expected: Vec<u8>) -> Self {
+        Self { expected, position: 0, line: 1, current_line: Vec::new(), mismatch: None, collecting: false }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if let Some((_, _, actual)) = &mut self.mismatch {
+                // 異なった行の残りだけを集め、以降は比較しない
+                if self.collecting {
+                    if byte == b'\n' {
+                        self.collecting = false;
+                    } else {
+                        actual.push(byte);
+                    }
+                }
+            } else if self.expected.get(self.position) != Some(&byte) {
+                let mut actual = std::mem::take(&mut self.current_line);
+                self.collecting = byte != b'\n';
+                if self.collecting {
+                    actual.push(byte);
+                }
+                self.mismatch = Some((self.position, self.line, actual));
+            } else if byte == b'\n' {
+                self.line += 1;
+                self.current_line.clear();
+            } else {
+                self.current_line.push(byte);
+            }
+            self.position += 1;
+        }
+    }
+
+    /// offsetを含む期待出力の行
+    fn expected_line(&self, offset: usize) -> String {
+        let start = self.expected[..offset.min(self.expected.len())].iter()
+            .rposition(|&b| b == b'\n').map_or(0, |p| p + 1);
+        let end = self.expected[start..].iter().position(|&b| b == b'\n').map_or(self.expected.len(), |p| start + p);
+        String::from_utf8_lossy(&self.expected[start..end]).into_owned()
+    }
+
+    fn result(&self) -> OutputComparison {
+        match &self.mismatch {
+            Some((offset, line, actual)) => OutputComparison::Mismatch {
+                offset: *offset,
+                line: *line,
+                expected: self.expected_line(*offset),
+                actual: String::from_utf8_lossy(actual).into_owned(),
+            },
+            None if self.position < self.expected.len() => OutputComparison::Missing {
+                offset: self.position,
+                line: self.line,
+                expected: self.expected_line(self.position),
+            },
+            None => OutputComparison::Match,
+        }
+    }
+}
+
+/// ゲストプログラムの出力先（デフォルトはコンソールのみ）
+#[derive(Debug, Default)]
+pub struct GuestOutput {
+    /// 出力を書き出すファイル
+    file: Option<BufWriter<File>>,
+    /// 期待出力との比較
+    expected: Option<ExpectedOutput>,
+    /// 各行の先頭に実行命令数を付けるか
+    timestamps: bool,
+    /// 実行命令数を付ける場合の、まだ改行が来ていない行
+    pending: Vec<u8>,
+    /// 取り込んだ出力（取り込み中はコンソールに出力しない）
+    capture: Option<Vec<u8>>,
+}
+
+impl GuestOutput {
+    /// 設定に従って出力先を作成する
+    pub fn from_config(config: &OutputConfig) -> Result<Self, String> {
+        let file = match &config.file_path {
+            Some(path) => Some(BufWriter::new(File::create(path)
+                .map_err(|e| format!("出力ファイル '{}' を作成できません: {}", path, e))?)),
+            None => None,
+        };
+        let expected = match &config.expected_path {
+            Some(path) => Some(ExpectedOutput::new(fs::read(path)
+                .map_err(|e| format!("期待出力ファイル '{}' を開けません: {}", path, e))?)),
+            None => None,
+        };
+        Ok(Self { file, expected, timestamps: config.timestamps, pending: Vec::new(), capture: None })
+    }
+
+    /// コンソールの代わりに出力をバイト列のまま取り込み始める（ファイルや期待出力との比較はそのまま続ける）
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    /// 取り込んだ出力を取り出し、取り込みを終える（取り込んでいなければ None）
+    pub fn take_capture(&mut self) -> Option<Vec<u8>> {
+        self.capture.take()
+    }
+
+    /// 出力する（instructions はその時点の実行命令数）
+    pub fn write(&mut self, bytes: &[u8], instructions: u64) {
+        if let Some(expected) = &mut self.expected {
+            expected.feed(bytes);
+        }
+        // 取り込む出力には実行命令数を付けない
+        if let Some(capture) = &mut self.capture {
+            capture.extend_from_slice(bytes);
+        }
+        if !self.timestamps {
+            self.emit(bytes);
+            return;
+        }
+        // 行がそろってから、その行を出力し終えた時点の命令数を付けて書き出す
+        for &byte in bytes {
+            self.pending.push(byte);
+            if byte == b'\n' {
+                self.emit_pending(instructions);
+            }
+        }
+    }
+
+    /// 書き出していない行を書き出し、期待出力との比較結果を返す（期待出力がなければ None）
+    pub fn finish(&mut self, instructions: u64) -> Option<OutputComparison> {
+        if !self.pending.is_empty() {
+            self.pending.push(b'\n');
+            self.emit_pending(instructions);
+        }
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.flush() {
+                eprintln!("警告: 出力ファイルに書き込めません: {}", e);
+            }
+        }
+        let _ = io::stdout().flush();
+        self.expected.as_ref().map(ExpectedOutput::result)
+    }
+
+    fn emit_pending(&mut self, instructions: u64) {
+        let mut line = format!("[{:>10}] ", instructions).into_bytes();
+        line.append(&mut self.pending);
+        self.emit(&line);
+    }
+
+    /// コンソールとファイルに書き出す
+    fn emit(&mut self, bytes: &[u8]) {
+        if self.capture.is_none() {
+            let _ = io::stdout().write_all(bytes);
+        }
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.write_all(bytes) {
+                // 書き込めなくなったファイルには以降書き出さない
+                eprintln!("警告: 出力ファイルに書き込めません: {}", e);
+                self.file = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_output_comparison() {
+        let mut expected = ExpectedOutput::new(b"55\nhello\nbye\n".to_vec());
+        expected.feed(b"55\nhel");
+        expected.feed(b"p me\nbye\n");
+        assert_eq!(expected.result(), OutputComparison::Mismatch {
+            offset: 6, line: 2, expected: "hello".to_string(), actual: "help me".to_string(),
+        });
+
+        let mut expected = ExpectedOutput::new(b"55\nhello\n".to_vec());
+        expected.feed(b"55\n");
+        assert!(matches!(expected.result(), OutputComparison::Missing { offset: 3, line: 2, .. }));
+        expected.feed(b"hello\n");
+        assert!(expected.result().is_match());
+    }
+
+    #[test]
+    fn test_timestamped_file_output() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_output_{}.txt", std::process::id()));
+        let config = OutputConfig { file_path: Some(path.to_string_lossy().into_owned()), expected_path: None, timestamps: true };
+        let mut output = GuestOutput::from_config(&config).unwrap();
+        output.write(b"a", 3);
+        output.write(b"b\nc", 7);
+        assert_eq!(output.finish(12), None);
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, "[         7] ab\n[        12] c\n");
+    }
+
+    #[test]
+    fn test_capture_binary_output() {
+        let mut output = GuestOutput::default();
+        output.start_capture();
+        output.write(b"P6\n", 1);
+        output.write(&[0x00, 0xFF, 0x80], 2);
+        assert_eq!(output.finish(3), None);
+        assert_eq!(output.take_capture(), Some(vec![b'P', b'6', b'\n', 0x00, 0xFF, 0x80]));
+        assert_eq!(output.take_capture(), None);
+    }
+}
@@ -0,0 +1,720 @@
+//! 5段パイプライン（IF/ID/EX/MEM/WB）のタイミングモデル
+//!
+//! 命令は機能シミュレーションで1つずつ実行し、ここでは各命令が各段に入るサイクルを前の命令との関係から求める。
+//! フォワーディングありのインオーダーパイプラインで、段の間にバッファはなく、後ろの段が空くまで前の段で待つ。
+//! - ロード直後にその結果を使う命令は、ロードがMEMを終えるまでEXに入れない（1サイクルのストール）
+//! - 分岐はEXで確定し、予測が外れた場合は後続の2命令を捨てる（分岐予測器がなければ不成立と予測する）
+//! - ジャンプ（j/jal/jr、RV32Iのjal）はIDで確定して後続の1命令を捨てる
+//! - キャッシュミスのペナルティはIF（フェッチ）またはMEM（データ）の段に留まるサイクルとして加わる
+//! - 演算器のレイテンシだけEXに、キャッシュヒットのレイテンシだけMEMに余分に留まる
+//!
+//! MSHRを設定するとデータキャッシュはノンブロッキングになり、ミスしたロード・ストアはMSHRを1つ確保してすぐにMEMを出る。
+//! ミスしたロードの結果を使う命令だけがデータが届くまでEXに入れず、それ以外の命令は先に進める（ヒットアンダーミス）。
+//! MSHRがすべて使用中なら、ミスした命令は最も早く空くMSHRを待ってMEMに留まる。
+//!
+//! dual-issueモデルでは各段に2命令まで入れ、連続する2命令を同じサイクルに発行（EXに投入）できる。
+//! - 直前の命令の結果を使う・同じレジスタに書く命令は組めない（依存）
+//! - ロード・ストア同士、乗除算同士は組めない（メモリポートと乗除算器は1つ）
+//! - システムコールなど直列化する命令は単独で発行する
+//! - 分岐・ジャンプの後続を捨てた場合はその分岐と組めない（制御）
+//!
+//! レジスタファイルはWBの前半で書いてIDの後半で読むため、書く命令がWBにいるサイクルにIDにいる命令は値を直接読める。
+//! それより近い依存（RAWハザード）は、EXに入るときに書く命令がMEMにいればEX/MEMから、WBにいればMEM/WBからフォワーディングする。
+
+use std::fmt;
+
+use crate::instructions::{InstructionType, Register};
+use crate::riscv::RvInstruction;
+use crate::timing::{InstructionTiming, StallCause, TimingConfig, TimingModel};
+
+/// 段の数
+const STAGE_COUNT: usize = 5;
+
+const IF: usize = 0;
+const ID: usize = 1;
+const EX: usize = 2;
+const MEM: usize = 3;
+const WB: usize = 4;
+/// WBを出たサイクルの位置
+const DONE: usize = 5;
+
+/// 浮動小数点レジスタを区別するために番号に足す値
+pub const FP_REGISTER_BASE: u8 = 32;
+
+/// 依存関係を追うレジスタの数（整数32 + 浮動小数点32）
+const TRACKED_REGISTERS: usize = 64;
+
+/// 制御の移り方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    /// 次の命令へ進む
+    Sequential,
+    /// IDで飛び先が決まる（ジャンプ）
+    Jump,
+    /// EXで成立・不成立が決まる（条件分岐、レジスタ間接ジャンプ）
+    Branch,
+}
+
+/// パイプラインから見た命令の性質
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineOp {
+    /// 読むレジスタ（浮動小数点レジスタは FP_REGISTER_BASE を足した番号、$zeroは含まない）
+    pub sources: Vec<u8>,
+    /// 書くレジスタ
+    pub dest: Option<u8>,
+    /// ロード命令か（結果がMEMの後まで使えない）
+    pub is_load: bool,
+    /// ストア命令か
+    pub is_store: bool,
+    /// 乗除算器を使う命令か
+    pub is_mul_div: bool,
+    /// 他の命令と同時に発行できない命令か（システムコールなど）
+    pub serializing: bool,
+    pub control: Control,
+}
+
+impl PipelineOp {
+    fn new(sources: &[u8], dest: Option<u8>) -> Self {
+        Self {
+            sources: sources.iter().copied().filter(|&reg| reg != 0).collect(),
+            dest: dest.filter(|&reg| reg != 0),
+            is_load: false,
+            is_store: false,
+            is_mul_div: false,
+            serializing: false,
+            control: Control::Sequential,
+        }
+    }
+
+    fn load(mut self) -> Self {
+        self.is_load = true;
+        self
+    }
+
+    fn store(mut self) -> Self {
+        self.is_store = true;
+        self
+    }
+
+    fn serializing(mut self) -> Self {
+        self.serializing = true;
+        self
+    }
+
+    fn control(mut self, control: Control) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// MIPS命令の性質
+    pub fn from_mips(instruction: &InstructionType) -> Self {
+        use InstructionType as I;
+        let fp = |reg: Register| reg + FP_REGISTER_BASE;
+        let mut op = match *instruction {
+            I::Add { rd, rs, rt } | I::Sub { rd, rs, rt } | I::And { rd, rs, rt }
+            | I::Or { rd, rs, rt } | I::Slt { rd, rs, rt } => Self::new(&[rs, rt], Some(rd)),
+            I::Sll { rd, rt, .. } | I::Srl { rd, rt, .. } => Self::new(&[rt], Some(rd)),
+            I::Jr { rs } => Self::new(&[rs], None).control(Control::Jump),
+            I::Addi { rt, rs, .. } | I::Slti { rt, rs, .. } | I::Ori { rt, rs, .. } => Self::new(&[rs], Some(rt)),
+            I::Lui { rt, .. } => Self::new(&[], Some(rt)),
+            I::Lw { rt, rs, .. } => Self::new(&[rs], Some(rt)).load(),
+            I::Sw { rt, rs, .. } => Self::new(&[rs, rt], None).store(),
+            I::Beq { rs, rt, .. } | I::Bne { rs, rt, .. } => Self::new(&[rs, rt], None).control(Control::Branch),
+            I::J { .. } => Self::new(&[], None).control(Control::Jump),
+            I::Jal { .. } => Self::new(&[], Some(31)).control(Control::Jump),
+            I::Mfc0 { rt, .. } | I::Cfc1 { rt, .. } => Self::new(&[], Some(rt)),
+            I::Mtc0 { rt, .. } | I::Ctc1 { rt, .. } => Self::new(&[rt], None).serializing(),
+            I::Eret => Self::new(&[], None).serializing().control(Control::Jump),
+            I::Mfc1 { rt, fs } => Self::new(&[fp(fs)], Some(rt)),
+            I::Mtc1 { rt, fs } => Self::new(&[rt], Some(fp(fs))),
+            I::CvtSW { fd, fs } | I::CvtWS { fd, fs } | I::TruncWS { fd, fs } | I::SqrtS { fd, fs }
+            | I::FinvS { fd, fs } | I::AbsS { fd, fs } | I::MovS { fd, fs } | I::NegS { fd, fs } => {
+                Self::new(&[fp(fs)], Some(fp(fd)))
+            }
+            I::AddS { fd, fs, ft } | I::SubS { fd, fs, ft } | I::MulS { fd, fs, ft } | I::DivS { fd, fs, ft } => {
+                Self::new(&[fp(fs), fp(ft)], Some(fp(fd)))
+            }
+            // 倍精度はレジスタ対の先頭の番号で依存関係を追う
+            #[cfg(feature = "double")]
+            I::SqrtD { fd, fs } | I::AbsD { fd, fs } | I::MovD { fd, fs } | I::NegD { fd, fs } | I::CvtDS { fd, fs }
+            | I::CvtSD { fd, fs } | I::CvtDW { fd, fs } | I::CvtWD { fd, fs } | I::TruncWD { fd, fs } => {
+                Self::new(&[fp(fs)], Some(fp(fd)))
+            }
+            #[cfg(feature = "double")]
+            I::AddD { fd, fs, ft } | I::SubD { fd, fs, ft } | I::MulD { fd, fs, ft } | I::DivD { fd, fs, ft } => {
+                Self::new(&[fp(fs), fp(ft)], Some(fp(fd)))
+            }
+            #[cfg(feature = "double")]
+            I::Ldc1 { ft, base, .. } => Self::new(&[base], Some(fp(ft))).load(),
+            #[cfg(feature = "double")]
+            I::Sdc1 { ft, base, .. } => Self::new(&[base, fp(ft)], None).store(),
+            // システムコールは$v0・$a0〜$a2を読み、$v0に書く
+            I::Syscall => Self::new(&[2, 4, 5, 6], Some(2)).serializing(),
+            I::Break { .. } | I::Invalid => Self::new(&[], None).serializing(),
+        };
+        op.is_mul_div = instruction.is_mul_div();
+        op
+    }
+
+    /// RV32I命令の性質
+    pub fn from_riscv(instruction: &RvInstruction) -> Self {
+        use RvInstruction as R;
+        match *instruction {
+            R::Lui { rd, .. } | R::Auipc { rd, .. } => Self::new(&[], Some(rd)),
+            R::Jal { rd, .. } => Self::new(&[], Some(rd)).control(Control::Jump),
+            R::Jalr { rd, rs1, .. } => Self::new(&[rs1], Some(rd)).control(Control::Branch),
+            R::Branch { rs1, rs2, .. } => Self::new(&[rs1, rs2], None).control(Control::Branch),
+            R::Load { rd, rs1, .. } => Self::new(&[rs1], Some(rd)).load(),
+            R::Store { rs1, rs2, .. } => Self::new(&[rs1, rs2], None).store(),
+            R::OpImm { rd, rs1, .. } => Self::new(&[rs1], Some(rd)),
+            R::Op { rd, rs1, rs2, .. } => Self::new(&[rs1, rs2], Some(rd)),
+            // ecallはa7・a0・a1を読み、a0に書く
+            R::Ecall => Self::new(&[17, 10, 11, 12], Some(10)).serializing(),
+            R::Fence | R::Ebreak | R::Invalid => Self::new(&[], None).serializing(),
+        }
+    }
+}
+
+/// ハザードとフォワーディングの統計（ストールのサイクル数はタイミング統計のストール要因に含まれる）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HazardStats {
+    /// 書く命令がまだWBに達していないレジスタを読んだオペランドの数（RAWハザード）
+    pub raw_hazards: u64,
+    /// EX/MEMからフォワーディングしたオペランドの数
+    pub forwarded_ex_mem: u64,
+    /// MEM/WBからフォワーディングしたオペランドの数
+    pub forwarded_mem_wb: u64,
+    /// ロード直後の使用でストールした回数
+    pub load_use_stalls: u64,
+    /// 分岐（予測が外れたもの）で後続を捨てた回数
+    pub branch_flushes: u64,
+    /// ジャンプで後続を捨てた回数
+    pub jump_flushes: u64,
+    /// 構造ハザード（充填・後続段の詰まり）で遅れた命令の数
+    pub structural_stalls: u64,
+}
+
+impl HazardStats {
+    /// フォワーディングせずにストールしてレジスタファイルから読んだRAWハザードの数
+    pub fn resolved_by_stall(&self) -> u64 {
+        self.raw_hazards - self.forwarded_ex_mem - self.forwarded_mem_wb
+    }
+}
+
+impl fmt::Display for HazardStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "RAWハザード: {} オペランド", self.raw_hazards)?;
+        writeln!(f, "  EX/MEMからフォワーディング: {}", self.forwarded_ex_mem)?;
+        writeln!(f, "  MEM/WBからフォワーディング: {}", self.forwarded_mem_wb)?;
+        writeln!(f, "  ストール後にレジスタから読出: {}", self.resolved_by_stall())?;
+        writeln!(f, "ロード直後の使用によるストール: {} 回", self.load_use_stalls)?;
+        writeln!(f, "分岐による破棄: {} 回", self.branch_flushes)?;
+        writeln!(f, "ジャンプによる破棄: {} 回", self.jump_flushes)?;
+        write!(f, "構造ハザードによる遅れ: {} 命令", self.structural_stalls)
+    }
+}
+
+/// 2命令同時発行の統計（dual-issueモデルのみ）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IssueStats {
+    /// 流した命令数
+    pub instructions: u64,
+    /// かかったサイクル数
+    pub cycles: u64,
+    /// 直前の命令と同じサイクルに発行した命令数（組の数）
+    pub paired: u64,
+    /// 組めなかった理由ごとの命令数（直前の命令が組の先頭だった場合のみ数える）
+    pub blocked_by_dependency: u64,
+    pub blocked_by_structural: u64,
+    pub blocked_by_control: u64,
+    /// ストールやキャッシュミスで発行が遅れたもの
+    pub blocked_by_stall: u64,
+}
+
+impl IssueStats {
+    /// 1サイクルあたりの命令数
+    pub fn ipc(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.instructions as f64 / self.cycles as f64
+        }
+    }
+
+    /// 組になって発行された命令の割合
+    pub fn pairing_rate(&self) -> f64 {
+        if self.instructions == 0 {
+            0.0
+        } else {
+            (self.paired * 2) as f64 / self.instructions as f64
+        }
+    }
+}
+
+impl fmt::Display for IssueStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "IPC: {:.3} ({} 命令 / {} サイクル)", self.ipc(), self.instructions, self.cycles)?;
+        writeln!(f, "同時発行した組: {} (命令の {:.2}%)", self.paired, self.pairing_rate() * 100.0)?;
+        writeln!(f, "組めなかった理由:")?;
+        writeln!(f, "  依存:       {:>10}", self.blocked_by_dependency)?;
+        writeln!(f, "  構造:       {:>10}", self.blocked_by_structural)?;
+        writeln!(f, "  制御:       {:>10}", self.blocked_by_control)?;
+        write!(f, "  ストール:   {:>10}", self.blocked_by_stall)
+    }
+}
+
+/// ノンブロッキングキャッシュの統計（MSHRを設定した場合のみ）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MshrStats {
+    /// MSHRを確保したデータキャッシュのミスの数
+    pub misses: u64,
+    /// MSHRがすべて使用中でMEMに留まったサイクル数
+    pub full_stall_cycles: u64,
+    /// 同時に処理していたミスの最大数
+    pub max_outstanding: usize,
+    /// 同じ命令列をブロッキングキャッシュで実行した場合より減ったサイクル数
+    pub saved_cycles: i64,
+}
+
+impl fmt::Display for MshrStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "MSHRを確保したミス: {}", self.misses)?;
+        writeln!(f, "MSHRが埋まって待ったサイクル: {}", self.full_stall_cycles)?;
+        writeln!(f, "同時に処理したミスの最大数: {}", self.max_outstanding)?;
+        write!(f, "ブロッキングキャッシュと比べて減ったサイクル: {}", self.saved_cycles)
+    }
+}
+
+/// 5段パイプラインの状態
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    /// 同時に発行できる命令数（1または2）
+    width: usize,
+    /// 直前の命令が各段に入ったサイクル（最後はWBを出たサイクル）
+    last: [u64; STAGE_COUNT + 1],
+    /// 2つ前の命令が各段に入ったサイクル
+    before_last: [u64; STAGE_COUNT + 1],
+    /// 直前の命令
+    last_op: Option<PipelineOp>,
+    /// 直前の命令が組の先頭か（次の命令と組める）
+    last_leads: bool,
+    /// 乗除算器が空くサイクル
+    mul_div_free: u64,
+    /// メモリポートが空くサイクル
+    memory_free: u64,
+    /// 次の命令をフェッチできる最も早いサイクル（分岐・ジャンプで後続を捨てた場合）
+    redirect: u64,
+    /// 各レジスタの値を使う命令がEXに入れる最も早いサイクル
+    ready: [u64; TRACKED_REGISTERS],
+    /// ready のうち、書く命令がキャッシュにヒットしていた場合のサイクル（ミスを待った分を区別するため）
+    ready_on_hit: [u64; TRACKED_REGISTERS],
+    /// 各レジスタを最後に書いた命令がMEMとWBに入ったサイクル
+    writer: [(u64, u64); TRACKED_REGISTERS],
+    /// ハザードとフォワーディングの統計
+    hazards: HazardStats,
+    /// 2命令同時発行の統計
+    issue: IssueStats,
+    /// 各MSHRが空くサイクル（MSHRを設定しなければ空）
+    mshr_free: Vec<u64>,
+    /// ノンブロッキングキャッシュの統計
+    mshr: MshrStats,
+    /// 減ったサイクル数を求めるため、同じ命令列を流すブロッキングキャッシュのパイプライン
+    blocking: Option<Box<Pipeline>>,
+    /// 統計を数えるなら真
+    stats_enabled: bool,
+    /// レイテンシの設定
+    config: TimingConfig,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new(&TimingConfig::default())
+    }
+}
+
+impl Pipeline {
+    /// レイテンシの設定に従って空のパイプラインを作成（dual-issueモデルなら2命令同時発行）
+    pub fn new(config: &TimingConfig) -> Self {
+        Self {
+            width: if config.model == TimingModel::DualIssue { 2 } else { 1 },
+            last: [0; STAGE_COUNT + 1],
+            before_last: [0; STAGE_COUNT + 1],
+            last_op: None,
+            last_leads: false,
+            mul_div_free: 0,
+            memory_free: 0,
+            redirect: 0,
+            ready: [0; TRACKED_REGISTERS],
+            ready_on_hit: [0; TRACKED_REGISTERS],
+            writer: [(0, 0); TRACKED_REGISTERS],
+            hazards: HazardStats::default(),
+            issue: IssueStats::default(),
+            mshr_free: vec![0; config.mshrs],
+            mshr: MshrStats::default(),
+            blocking: (config.mshrs > 0).then(|| Box::new(Pipeline::new(&TimingConfig { mshrs: 0, ..config.clone() }))),
+            stats_enabled: true,
+            config: config.clone(),
+        }
+    }
+
+    /// 1命令をパイプラインに流し、直前の命令がWBを出てからこの命令がWBを出るまでのサイクルを返す
+    /// flush は分岐・ジャンプの後にフェッチした命令を捨てるか（成立したジャンプ、予測の外れた分岐）、
+    /// 各ペナルティはその段に余分に留まるサイクル数
+    pub fn advance(&mut self, op: &PipelineOp, flush: bool, fetch_penalty: u64, data_penalty: u64, data_cause: StallCause) -> InstructionTiming {
+        let blocking_cycles = self.blocking.as_mut()
+            .map(|blocking| blocking.advance(op, flush, fetch_penalty, data_penalty, data_cause).total());
+        let prev = self.last;
+        let prev2 = self.before_last;
+        let dual = self.width == 2;
+        let mut enter = [0; STAGE_COUNT + 1];
+        // 段に入れるのは、幅の数だけ前の命令がその段を出て、直前の命令がその段に入った後
+        let stage_free = |stage: usize| if dual { prev2[stage + 1].max(prev[stage]) } else { prev[stage + 1] };
+        let accesses_memory = op.is_load || op.is_store;
+
+        // 直前の命令と同じサイクルに発行できるか（できなければ1サイクル後以降）
+        let flushed = self.redirect.saturating_sub(stage_free(IF));
+        let blocked = self.last_op.as_ref().filter(|_| dual && self.last_leads).map(|last| {
+            let dependent = last.dest.is_some_and(|dest| op.sources.contains(&dest) || op.dest == Some(dest));
+            let structural = op.serializing || last.serializing
+                || (accesses_memory && (last.is_load || last.is_store))
+                || (op.is_mul_div && last.is_mul_div);
+            if flushed > 0 {
+                Some(&mut self.issue.blocked_by_control)
+            } else if dependent {
+                Some(&mut self.issue.blocked_by_dependency)
+            } else if structural {
+                Some(&mut self.issue.blocked_by_structural)
+            } else {
+                None
+            }
+        });
+        let can_pair = matches!(blocked, Some(None));
+
+        // 前の命令がIDに進んで空いたIFに入る（分岐で捨てた場合は飛び先が決まるまで待つ）
+        enter[IF] = stage_free(IF).max(self.redirect);
+        enter[ID] = (enter[IF] + 1 + fetch_penalty).max(stage_free(ID));
+        let operands_ready = op.sources.iter().map(|&reg| self.ready[reg as usize]).max().unwrap_or(0);
+        let operands_ready_on_hit = op.sources.iter().map(|&reg| self.ready_on_hit[reg as usize]).max().unwrap_or(0);
+        let mut ex_without_hazard = (enter[ID] + 1).max(stage_free(EX));
+        if !can_pair {
+            ex_without_hazard = ex_without_hazard.max(prev[EX] + 1);
+        }
+        if op.is_mul_div {
+            ex_without_hazard = ex_without_hazard.max(self.mul_div_free);
+        }
+        enter[EX] = ex_without_hazard.max(operands_ready);
+        let ex_cycles = self.config.execute_latency(op.is_mul_div).max(1);
+        let mem_cycles = 1 + if accesses_memory { self.config.cache_hit_latency } else { 0 };
+        enter[MEM] = (enter[EX] + ex_cycles).max(stage_free(MEM));
+        if accesses_memory {
+            enter[MEM] = enter[MEM].max(self.memory_free);
+        }
+        // ノンブロッキングキャッシュでは、ミスした命令はMSHRを確保できるまでだけMEMに留まる
+        let mut miss_done = None;
+        let mut data_stall = data_penalty;
+        if accesses_memory && data_penalty > 0 && !self.mshr_free.is_empty() {
+            let slot = (0..self.mshr_free.len()).min_by_key(|&slot| self.mshr_free[slot]).unwrap_or(0);
+            data_stall = self.mshr_free[slot].saturating_sub(enter[MEM]);
+            let issued = enter[MEM] + data_stall;
+            let done = issued + mem_cycles + data_penalty;
+            self.mshr_free[slot] = done;
+            if self.stats_enabled {
+                let outstanding = self.mshr_free.iter().filter(|&&free| free > issued).count();
+                self.mshr.misses += 1;
+                self.mshr.full_stall_cycles += data_stall;
+                self.mshr.max_outstanding = self.mshr.max_outstanding.max(outstanding);
+            }
+            miss_done = Some(done);
+        }
+        enter[WB] = (enter[MEM] + mem_cycles + data_stall).max(stage_free(WB));
+        enter[DONE] = enter[WB] + 1;
+        if op.is_mul_div {
+            self.mul_div_free = enter[MEM];
+        }
+        if accesses_memory {
+            self.memory_free = enter[WB];
+        }
+
+        // 組める命令が組めなかった場合は、ストールで発行が遅れたものとして数える
+        let paired = dual && self.last_op.is_some() && enter[EX] == prev[EX];
+        if let Some(reason) = blocked.filter(|_| self.stats_enabled) {
+            match reason {
+                Some(counter) => *counter += 1,
+                None if !paired => self.issue.blocked_by_stall += 1,
+                None => {}
+            }
+        }
+        if paired && self.stats_enabled {
+            self.issue.paired += 1;
+        }
+        self.last_leads = !paired;
+        self.last_op = Some(op.clone());
+
+        // IDで読むときに書く命令がまだWBに達していなければ、EXに入るときの書く命令の位置から転送元を決める
+        if self.stats_enabled {
+            for &reg in &op.sources {
+                let (writer_mem, writer_wb) = self.writer[reg as usize];
+                if writer_wb <= enter[ID] {
+                    continue;
+                }
+                self.hazards.raw_hazards += 1;
+                if (writer_mem..writer_wb).contains(&enter[EX]) {
+                    self.hazards.forwarded_ex_mem += 1;
+                } else if enter[EX] == writer_wb {
+                    self.hazards.forwarded_mem_wb += 1;
+                }
+            }
+            if operands_ready_on_hit > ex_without_hazard {
+                self.hazards.load_use_stalls += 1;
+            }
+            match (flush, op.control) {
+                (true, Control::Jump) => self.hazards.jump_flushes += 1,
+                (true, Control::Branch) => self.hazards.branch_flushes += 1,
+                _ => {}
+            }
+        }
+
+        // ロードの結果はMEMの後（ノンブロッキングキャッシュでミスした場合はデータが届いた後）、
+        // それ以外はEXの後にフォワーディングで使える
+        if let Some(dest) = op.dest {
+            let ready_on_hit = if op.is_load { enter[WB] } else { enter[MEM] };
+            self.ready_on_hit[dest as usize] = ready_on_hit;
+            self.ready[dest as usize] = miss_done.filter(|_| op.is_load).unwrap_or(ready_on_hit);
+            self.writer[dest as usize] = (enter[MEM], enter[WB]);
+        }
+        self.redirect = match (flush, op.control) {
+            (true, Control::Jump) => enter[EX],
+            (true, Control::Branch) => enter[MEM],
+            _ => 0,
+        };
+        self.before_last = prev;
+        self.last = enter;
+
+        // 直前の命令からの増分のうち1サイクル（同時に出た場合は0）を超える分をストールとして要因に割り振る
+        // 要因が重なった場合は先に挙げたものを優先し、説明できない残り（充填・後続段の詰まり）は構造ハザードとする
+        let delta = enter[DONE] - prev[DONE];
+        let base = delta.min(1);
+        let mut remaining = delta - base;
+        if dual && self.stats_enabled {
+            self.issue.instructions += 1;
+            self.issue.cycles += delta;
+        }
+        let mut stalls = Vec::new();
+        let candidates = [
+            (StallCause::Fetch, fetch_penalty),
+            (StallCause::ControlHazard, flushed),
+            (StallCause::LoadUse, operands_ready_on_hit.saturating_sub(ex_without_hazard)),
+            // ノンブロッキングキャッシュで、先行するロードのミスのデータを待った分
+            (StallCause::Load, operands_ready.saturating_sub(ex_without_hazard.max(operands_ready_on_hit))),
+            (StallCause::MultiCycle, ex_cycles + mem_cycles - 2),
+            (data_cause, data_stall),
+        ];
+        for (cause, cycles) in candidates {
+            let cycles = cycles.min(remaining);
+            if cycles > 0 {
+                stalls.push((cause, cycles));
+                remaining -= cycles;
+            }
+        }
+        if remaining > 0 {
+            stalls.push((StallCause::Structural, remaining));
+            if self.stats_enabled {
+                self.hazards.structural_stalls += 1;
+            }
+        }
+        if let Some(blocking_cycles) = blocking_cycles.filter(|_| self.stats_enabled) {
+            self.mshr.saved_cycles += blocking_cycles as i64 - delta as i64;
+        }
+        InstructionTiming { base, stalls }
+    }
+
+    /// ハザードとフォワーディングの統計
+    pub fn hazard_stats(&self) -> &HazardStats {
+        &self.hazards
+    }
+
+    /// ハザードとフォワーディングの統計を置き換える
+    pub fn set_hazard_stats(&mut self, hazards: HazardStats) {
+        self.hazards = hazards;
+    }
+
+    /// 2命令同時発行の統計
+    pub fn issue_stats(&self) -> &IssueStats {
+        &self.issue
+    }
+
+    /// 2命令同時発行の統計を置き換える
+    pub fn set_issue_stats(&mut self, issue: IssueStats) {
+        self.issue = issue;
+    }
+
+    /// ノンブロッキングキャッシュの統計
+    pub fn mshr_stats(&self) -> &MshrStats {
+        &self.mshr
+    }
+
+    /// ノンブロッキングキャッシュの統計を置き換える
+    pub fn set_mshr_stats(&mut self, mshr: MshrStats) {
+        self.mshr = mshr;
+    }
+
+    /// 統計を数えるかを切り替える（止めている間もタイミングは同じように進める）
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats_enabled = enabled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(ops: &[(InstructionType, bool)]) -> (Vec<InstructionTiming>, HazardStats) {
+        let mut pipeline = Pipeline::default();
+        let timings = ops.iter()
+            .map(|(instruction, taken)| pipeline.advance(&PipelineOp::from_mips(instruction), *taken, 0, 0, StallCause::Load))
+            .collect();
+        (timings, pipeline.hazard_stats().clone())
+    }
+
+    #[test]
+    fn test_pipeline_hazards() {
+        use InstructionType as I;
+        let (timings, hazards) = run(&[
+            (I::Addi { rt: 8, rs: 0, imm: 1 }, false),
+            (I::Add { rd: 9, rs: 8, rt: 8 }, false),     // フォワーディングで待たない
+            (I::Lw { rt: 10, rs: 9, imm: 0 }, false),
+            (I::Add { rd: 11, rs: 10, rt: 0 }, false),   // ロード直後の使用で1サイクル
+            (I::Beq { rs: 0, rt: 0, imm: 2 }, true),     // 成立した分岐の後続は2サイクル
+            (I::Addi { rt: 8, rs: 0, imm: 1 }, false),
+            (I::J { addr: 0 }, true),                    // ジャンプの後続は1サイクル
+            (I::Addi { rt: 8, rs: 0, imm: 1 }, false),
+        ]);
+        let totals: Vec<u64> = timings.iter().map(InstructionTiming::total).collect();
+        // 最初の命令はパイプラインの充填で5サイクル
+        assert_eq!(totals, vec![5, 1, 1, 2, 1, 3, 1, 2]);
+        assert_eq!(timings[0].stalls, vec![(StallCause::Structural, 4)]);
+        assert_eq!(timings[3].stalls, vec![(StallCause::LoadUse, 1)]);
+        assert_eq!(timings[5].stalls, vec![(StallCause::ControlHazard, 2)]);
+        assert_eq!(timings[7].stalls, vec![(StallCause::ControlHazard, 1)]);
+        assert_eq!(hazards.load_use_stalls, 1);
+        assert_eq!((hazards.branch_flushes, hazards.jump_flushes, hazards.structural_stalls), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_forwarding_paths() {
+        use InstructionType as I;
+        let (_, hazards) = run(&[
+            (I::Addi { rt: 8, rs: 0, imm: 1 }, false),
+            (I::Add { rd: 9, rs: 8, rt: 8 }, false),     // 直前の結果を2つともEX/MEMから
+            (I::Add { rd: 10, rs: 8, rt: 0 }, false),    // 2つ前の結果はMEM/WBから
+            (I::Add { rd: 11, rs: 8, rt: 0 }, false),    // 3つ前の結果はレジスタファイルから読める
+            (I::Lw { rt: 12, rs: 0, imm: 0 }, false),
+            (I::Add { rd: 13, rs: 12, rt: 9 }, false),   // ロード直後の使用はストールしてMEM/WBから
+        ]);
+        assert_eq!(hazards, HazardStats {
+            raw_hazards: 4,
+            forwarded_ex_mem: 2,
+            forwarded_mem_wb: 2,
+            load_use_stalls: 1,
+            branch_flushes: 0,
+            jump_flushes: 0,
+            structural_stalls: 1,
+        });
+        assert_eq!(hazards.resolved_by_stall(), 0);
+    }
+
+    #[test]
+    fn test_pipeline_miss_penalties() {
+        let add = PipelineOp::from_mips(&InstructionType::Add { rd: 1, rs: 2, rt: 3 });
+        let mut pipeline = Pipeline::default();
+        pipeline.advance(&add, false, 0, 0, StallCause::Load);
+        // データのミスはMEMに留まる分だけ遅れる
+        assert_eq!(pipeline.advance(&add, false, 0, 10, StallCause::Store).stalls, vec![(StallCause::Store, 10)]);
+        // 次の命令のフェッチのミスは前の命令のMEMでのストールと重なるので遅れない
+        assert_eq!(pipeline.advance(&add, false, 10, 0, StallCause::Load).total(), 1);
+        assert_eq!(pipeline.advance(&add, false, 10, 0, StallCause::Load).stalls, vec![(StallCause::Fetch, 10)]);
+    }
+
+    #[test]
+    fn test_nonblocking_cache() {
+        use InstructionType as I;
+        let config = TimingConfig { model: TimingModel::Pipeline, mshrs: 2, ..TimingConfig::default() };
+        let mut pipeline = Pipeline::new(&config);
+        let ops = [
+            (I::Lw { rt: 8, rs: 0, imm: 0 }, 10),
+            (I::Lw { rt: 9, rs: 0, imm: 64 }, 10),
+            (I::Add { rd: 10, rs: 0, rt: 0 }, 0),
+            (I::Add { rd: 11, rs: 8, rt: 9 }, 0),
+        ];
+        let timings: Vec<InstructionTiming> = ops.iter()
+            .map(|(instruction, penalty)| pipeline.advance(&PipelineOp::from_mips(instruction), false, 0, *penalty, StallCause::Load))
+            .collect();
+        // 2つのミスは重なり、結果を使わない命令はミスを待たない
+        assert_eq!(timings[1].total(), 1);
+        assert_eq!(timings[2].total(), 1);
+        // 結果を使う命令は後のロードのデータが届くまで待ち、その分はロードのミスに数える
+        assert_eq!(timings[3].stalls, vec![(StallCause::Load, 10)]);
+        let mshr = pipeline.mshr_stats();
+        assert_eq!((mshr.misses, mshr.full_stall_cycles, mshr.max_outstanding), (2, 0, 2));
+        assert_eq!(mshr.saved_cycles, 10);
+
+        // MSHRが1つなら2つ目のミスは1つ目が終わるまでMEMに留まる
+        let mut pipeline = Pipeline::new(&TimingConfig { mshrs: 1, ..config });
+        for (instruction, penalty) in &ops[..2] {
+            pipeline.advance(&PipelineOp::from_mips(instruction), false, 0, *penalty, StallCause::Load);
+        }
+        assert_eq!(pipeline.mshr_stats().full_stall_cycles, 10);
+    }
+
+    #[test]
+    fn test_pipeline_latencies() {
+        let config = TimingConfig { mul_div_latency: 4, cache_hit_latency: 1, ..TimingConfig::default() };
+        let mut pipeline = Pipeline::new(&config);
+        let mul = PipelineOp::from_mips(&InstructionType::MulS { fd: 2, fs: 0, ft: 1 });
+        let add = PipelineOp::from_mips(&InstructionType::AddS { fd: 3, fs: 2, ft: 2 });
+        let lw = PipelineOp::from_mips(&InstructionType::Lw { rt: 8, rs: 0, imm: 0 });
+        assert!(mul.is_mul_div && !add.is_mul_div && lw.is_load);
+        pipeline.advance(&add, false, 0, 0, StallCause::Load);
+        // 乗算はEXに4サイクル留まり、その結果を使う次の命令もEXが空くまで待つ
+        assert_eq!(pipeline.advance(&mul, false, 0, 0, StallCause::Load).stalls, vec![(StallCause::MultiCycle, 3)]);
+        assert_eq!(pipeline.advance(&add, false, 0, 0, StallCause::Load).total(), 1);
+        // ロードはキャッシュヒットのレイテンシだけMEMに留まる
+        assert_eq!(pipeline.advance(&lw, false, 0, 0, StallCause::Load).stalls, vec![(StallCause::MultiCycle, 1)]);
+    }
+
+    #[test]
+    fn test_dual_issue_pairing() {
+        use InstructionType as I;
+        let config = TimingConfig { model: TimingModel::DualIssue, ..TimingConfig::default() };
+        let mut pipeline = Pipeline::new(&config);
+        let independent = [
+            I::Addi { rt: 8, rs: 0, imm: 1 },
+            I::Addi { rt: 9, rs: 0, imm: 2 },
+            I::Addi { rt: 10, rs: 0, imm: 3 },
+            I::Addi { rt: 11, rs: 0, imm: 4 },
+        ];
+        let totals: Vec<u64> = independent.iter()
+            .map(|instruction| pipeline.advance(&PipelineOp::from_mips(instruction), false, 0, 0, StallCause::Load).total())
+            .collect();
+        // 独立した命令は2つずつ同じサイクルに発行する
+        assert_eq!(totals, vec![5, 0, 1, 0]);
+        assert_eq!(pipeline.issue_stats().paired, 2);
+
+        let rest = [
+            I::Addi { rt: 12, rs: 0, imm: 5 },
+            I::Add { rd: 13, rs: 12, rt: 0 },   // 直前の結果を使うので組めない
+            I::Addi { rt: 14, rs: 0, imm: 6 },
+            I::Lw { rt: 15, rs: 0, imm: 0 },
+            I::Sw { rt: 9, rs: 0, imm: 4 },     // メモリポートは1つなので組めない
+            I::Addi { rt: 16, rs: 0, imm: 7 },
+        ];
+        let totals: Vec<u64> = rest.iter()
+            .map(|instruction| pipeline.advance(&PipelineOp::from_mips(instruction), false, 0, 0, StallCause::Load).total())
+            .collect();
+        assert_eq!(totals, vec![1, 1, 0, 1, 1, 0]);
+        let issue = pipeline.issue_stats();
+        assert_eq!((issue.paired, issue.blocked_by_dependency, issue.blocked_by_structural), (4, 1, 1));
+        assert_eq!((issue.instructions, issue.cycles), (10, 10));
+        assert!((issue.pairing_rate() - 0.8).abs() < 1e-9);
+    }
+}
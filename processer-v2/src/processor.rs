@@ -1,20 +1,114 @@
 //! MIPSプロセッサコアの実装
 
+use std::collections::BTreeMap;
 use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-use crate::instructions::{Instruction, InstructionType, Register, Word};
-use crate::memory::{Memory, MemoryAddress, MemoryError};
-use crate::cache::{Cache, CacheStats};
+use crate::instructions::{self, Instruction, InstructionCategory, InstructionType, Register, Word};
+use crate::memory::{self, Access, Memory, MemoryAddress, MemoryError, Protection};
+use crate::bus::{BusConfig, BusStats};
+use crate::call_stack::CallStack;
+use crate::coverage::Coverage;
+use crate::branch_predictor::{BranchPredictors, PredictorSnapshot};
+use crate::cp0::{Cp0, ExceptionCode};
+use crate::decoder;
+use crate::guest_env::{self, SYSCALL_GETENV, SYSCALL_GETENV_INT};
+use crate::cache::{self, Cache, CacheConfig, CacheLike, CacheLineInfo, CacheSnapshot, CacheStats};
+use crate::cache_trace::CacheTraceWriter;
+use crate::coherence::SnoopBus;
+use crate::host_files::HostFiles;
+use crate::footprint::MemoryFootprint;
+use crate::heatmap::MemoryHeatmap;
+use crate::history::{ExecutionHistory, HistoryEntry};
+use crate::input::GuestInput;
+use crate::loader::{self, Segment};
+use crate::loop_detector::{LoopDetectionConfig, LoopDetector};
+use crate::mem_trace::MemTraceWriter;
+use crate::mmio::MmioDevice;
+use crate::output::{GuestOutput, OutputComparison};
+use crate::profile::{CallEvent, FunctionProfiler, HotSpotProfiler};
+use crate::register_usage::RegisterUsage;
+use crate::replay::{self, ReplayLog};
+use crate::report::StatsReport;
+use crate::riscv::{self, RvInstruction};
+use crate::snapshot::{SnapshotError, SnapshotReader, SnapshotWriter};
+use crate::symbols::SymbolTable;
+use crate::ooo::{OooStats, OutOfOrderCore};
+use crate::pipeline::{HazardStats, IssueStats, MshrStats, Pipeline, PipelineOp, FP_REGISTER_BASE};
+use crate::undo_log::{UndoLog, UndoRecord};
+use crate::syscall::{SpimSyscalls, SyscallContext, SyscallHandler, SyscallResult};
+use crate::watchpoint::{MemoryWatchpoint, Watchpoint};
+use crate::timing::{InstructionTiming, StallCause, TimingConfig, TimingModel, TimingStats};
+use crate::fpu::{self, FpuModel, IeeeFpu, RoundingMode, FCSR_REGISTER, FP_REGISTER_COUNT, SIGN_BIT};
 
 /// MIPSプロセッサのレジスタ数
 pub const REGISTER_COUNT: usize = 32;
 
-/// プログラムカウンタの初期値
+/// プログラムカウンタの初期値（デフォルト、プログラムをロードするとその開始アドレスになる）
 pub const PC_INITIAL: MemoryAddress = 0x00400000;
 
-/// スタックポインタの初期値
+/// スタックポインタの初期値（デフォルト）
 pub const SP_INITIAL: MemoryAddress = 0x7FFFFFFC;
 
+/// このアドレスにジャンプするとプログラムを停止する（デフォルト、起動時に戻りアドレスのレジスタにも入れる）
+pub const HALT_ADDRESS: MemoryAddress = 0xFFFFFFFF;
+
+/// sbrkで確保する領域の境界（バイト）
+const HEAP_ALIGNMENT: MemoryAddress = 8;
+
+/// 実行する命令数の上限（デフォルト）
+pub const INSTRUCTION_LIMIT: u64 = 100000;
+
+/// 無効な命令の診断で直前の有効な命令を探す範囲（ワード数）
+const PRECEDING_SEARCH_LIMIT: u32 = 256;
+
+/// 実行時間の上限を確認する間隔（命令数）
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+/// マルチコア実行でコア番号を渡すレジスタ（$k0）
+pub const CORE_ID_REGISTER: Register = 26;
+
+/// 実行トレースの表示を再開するシステムコール番号
+pub const SYSCALL_TRACE_ON: Word = 110;
+
+/// 実行トレースの表示を止めるシステムコール番号
+pub const SYSCALL_TRACE_OFF: Word = 111;
+
+/// 統計の収集を再開するシステムコール番号
+pub const SYSCALL_STATS_ON: Word = 112;
+
+/// 統計の収集を止めるシステムコール番号
+pub const SYSCALL_STATS_OFF: Word = 113;
+
+/// キャッシュのダーティラインをすべてメモリに書き戻すシステムコール番号
+pub const SYSCALL_CACHE_FLUSH: Word = 120;
+
+/// キャッシュのラインをすべて捨てるシステムコール番号（ダーティラインも書き戻さない）
+pub const SYSCALL_CACHE_INVALIDATE: Word = 121;
+
+/// 命令セットアーキテクチャ
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Isa {
+    /// MIPS（デフォルト）
+    #[default]
+    Mips,
+    /// RISC-V RV32I
+    Rv32i,
+}
+
+impl Isa {
+    /// ISA名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Isa::Mips => "mips",
+            Isa::Rv32i => "rv32i",
+        }
+    }
+}
+
 /// MIPSプロセッサ
 #[derive(Debug)]
 pub struct Processor {
@@ -26,61 +120,350 @@ pub struct Processor {
     hi: Word,
     /// ローレジスタ（乗除算用）
     lo: Word,
+    /// 浮動小数点レジスタ（$f0-$f31、ビット列で保持）
+    fp_registers: [Word; FP_REGISTER_COUNT],
+    /// 浮動小数点制御・状態レジスタ（FCSR）
+    fcsr: Word,
+    /// 浮動小数点演算のモデル
+    fpu: Box<dyn FpuModel>,
+    /// システムコールのハンドラ
+    syscall_handler: Box<dyn SyscallHandler>,
+    /// コプロセッサ0のレジスタ
+    cp0: Cp0,
+    /// 例外ベクタ（Noneなら例外を受け付けず、エラーとして実行を止める）
+    exception_vector: Option<MemoryAddress>,
     /// メモリシステム
     memory: Memory,
-    /// キャッシュシステム
-    cache: Cache,
+    /// キャッシュシステム（set_cache_modelで差し替えられる）
+    cache: Box<dyn CacheLike>,
+    /// キャッシュアクセスのトレースの書き出し先
+    cache_trace: Option<CacheTraceWriter>,
+    /// メモリアクセスのトレースの書き出し先
+    mem_trace: Option<MemTraceWriter>,
     /// 実行統計
     stats: ProcessorStats,
+    /// 実行時間の上限
+    timeout: Option<Duration>,
+    /// 実行する命令数の上限（Noneで無制限）
+    max_instructions: Option<u64>,
+    /// タイミングモデルの設定
+    timing_config: TimingConfig,
+    /// タイミング統計
+    timing: TimingStats,
+    /// 5段パイプラインの状態（pipelineモデルのみ）
+    pipeline: Pipeline,
+    /// アウトオブオーダー実行の状態（oooモデルのみ）
+    ooo: OutOfOrderCore,
+    /// 条件分岐の予測器（なければ予測しない）
+    predictors: BranchPredictors,
+    /// 実行する命令セット
+    isa: Isa,
+    /// 命令カバレッジ
+    coverage: Coverage,
+    /// 命令アドレスごとの実行回数（有効にした場合のみ）
+    profiler: Option<HotSpotProfiler>,
+    /// 関数ごとの命令数・サイクル数（有効にした場合のみ）
+    function_profiler: Option<FunctionProfiler>,
+    /// ブロックごとのロード・ストアの回数（有効にした場合のみ）
+    heatmap: Option<MemoryHeatmap>,
+    /// 触れたページ・ラインとヒープ・スタックの最大（有効にした場合のみ）
+    footprint: Option<MemoryFootprint>,
+    /// レジスタごとの読み出し・書き込み回数（有効にした場合のみ）
+    register_usage: Option<RegisterUsage>,
+    /// 呼び出し・復帰から追跡したシャドウコールスタック（バックトレースか関数プロファイラを有効にした場合のみ）
+    call_stack: Option<CallStack>,
+    /// 未初期化のレジスタの読み出しの検出（有効にした場合のみ）
+    uninitialized: Option<UninitializedRegisters>,
+    /// 未初期化のメモリの読み込みの検出（有効にした場合のみ）
+    poisoned_reads: Option<PoisonedReads>,
+    /// 無限ループの検出（有効にした場合のみ）
+    loop_detector: Option<LoopDetector>,
+    /// 直前に実行した命令の履歴（有効にした場合のみ）
+    history: Option<ExecutionHistory>,
+    /// 逆実行のためのアンドゥログ（有効にした場合のみ）
+    undo_log: Option<UndoLog>,
+    /// レジスタのウォッチポイント
+    watchpoints: Vec<Watchpoint>,
+    /// メモリのウォッチポイント
+    memory_watchpoints: Vec<MemoryWatchpoint>,
+    /// 実行中の命令が最初に該当したメモリのウォッチポイント（命令を完了してから止める）
+    memory_watch_hit: Option<ProcessorError>,
+    /// ホストから値を受け取るシステムコールの記録・再生（有効にした場合のみ）
+    replay: Option<ReplayLog>,
+    /// ゲスト向け設定ブロックの位置（アドレス, バイト数）
+    env_block: Option<(MemoryAddress, usize)>,
+    /// ゲストプログラムの出力先
+    output: GuestOutput,
+    /// ゲストプログラムの入力元
+    input: GuestInput,
+    /// ゲストが開いているホストのファイル
+    files: HostFiles,
+    /// ヒープの先頭（ロードしたプログラムの直後）
+    heap_start: MemoryAddress,
+    /// ヒープの現在の終端（sbrkで伸びる）
+    heap_break: MemoryAddress,
+    /// 実行前に読み込んだデータの範囲（ヒープの先頭に接するものの後ろからヒープを始めるため）
+    data_ranges: Vec<Range<u64>>,
+    /// シンボル表（表示用）
+    symbols: SymbolTable,
+    /// このアドレスにジャンプするとプログラムを停止する
+    halt_address: MemoryAddress,
+    /// スタックポインタの初期値
+    initial_sp: MemoryAddress,
+    /// グローバルポインタの初期値（Noneなら0のまま）
+    initial_gp: Option<MemoryAddress>,
+    /// 実行トレース（実行した命令・分岐・システムコール）を表示するか
+    trace_enabled: bool,
+    /// 取り込んだ実行トレース（取り込み中はコンソールに出力しない）
+    trace_capture: Option<String>,
+    /// 統計（実行統計・キャッシュ統計・タイミング・カバレッジ）を収集するか
+    stats_enabled: bool,
+    /// 実行した命令をカバレッジに記録するか
+    coverage_enabled: bool,
+    /// 命令ごとのサイクル数をタイミングのヒストグラムに記録するか
+    timing_enabled: bool,
+}
+
+/// マイクロアーキテクチャ状態のスナップショット
+/// レジスタやメモリの内容などのアーキテクチャ状態は含まず、同じコード区間をウォーム/コールドで比べるために使う
+#[derive(Debug, Clone)]
+pub struct MicroarchSnapshot {
+    /// キャッシュの状態
+    pub cache: CacheSnapshot,
+    /// 分岐予測器の表と履歴
+    pub predictors: PredictorSnapshot,
+}
+
+impl MicroarchSnapshot {
+    /// 何も載っていない初期状態（コールドスタート）
+    pub fn cold() -> Self {
+        Self { cache: Cache::new().snapshot(), predictors: PredictorSnapshot::default() }
+    }
+}
+
+/// 未初期化のレジスタの読み出しの検出状態
+#[derive(Debug, Clone, Default)]
+struct UninitializedRegisters {
+    /// リセット後に書き込まれたレジスタ（レジスタ番号のビット）
+    written: u32,
+    /// 報告済みのレジスタ（レジスタごとに最初の読み出しだけを報告する）
+    reported: u32,
+    /// 検出した読み出し（命令のアドレス, レジスタ）
+    reads: Vec<(MemoryAddress, Register)>,
+}
+
+/// 未初期化のメモリ（埋めたパターンのままのワード）の読み込みの検出状態
+#[derive(Debug, Clone)]
+struct PoisonedReads {
+    /// 埋めたバイトを4つ並べたワード
+    pattern: Word,
+    /// 検出した読み込み（命令のアドレス, 読み込んだアドレス）。同じ命令は最初の1回だけ記録する
+    reads: Vec<(MemoryAddress, MemoryAddress)>,
 }
 
 /// プロセッサ統計情報
 #[derive(Debug, Clone, Default)]
 pub struct ProcessorStats {
-    /// 実行命令数
+    /// 実行命令数（完了した命令すべて、例外で完了しなかった命令は含まない）
     pub instructions_executed: u64,
     /// 分岐命令数
     pub branches_taken: u64,
+    /// PCが次の命令へ進まなかった回数（成立した分岐・ジャンプ、例外ハンドラへの移動、eret）
+    pub redirections: u64,
     /// ロード命令数
     pub loads_executed: u64,
     /// ストア命令数
     pub stores_executed: u64,
+    /// タイミングモデルで見積もった総サイクル数
+    pub cycles: u64,
+    /// 例外ハンドラへ移った回数（割り込みを含む）
+    pub exceptions: u64,
+    /// そのうち割り込みの回数
+    pub interrupts: u64,
+    /// 命令の分類ごとの実行回数（例外で完了しなかった命令は含まない）
+    pub mix: BTreeMap<InstructionCategory, u64>,
+    /// ニーモニックごとの実行回数（例外で完了しなかった命令は含まない）
+    pub mnemonics: BTreeMap<&'static str, u64>,
+}
+
+impl ProcessorStats {
+    /// 1命令あたりのサイクル数
+    pub fn cpi(&self) -> f64 {
+        if self.instructions_executed == 0 {
+            0.0
+        } else {
+            self.cycles as f64 / self.instructions_executed as f64
+        }
+    }
+
+    /// 完了した1命令を命令ミックスに記録する
+    fn record_mix(&mut self, category: InstructionCategory, mnemonic: &'static str) {
+        *self.mix.entry(category).or_insert(0) += 1;
+        *self.mnemonics.entry(mnemonic).or_insert(0) += 1;
+    }
+
+    /// 命令ミックスのヒストグラム（分類ごと、ニーモニックごとは実行回数の多い順）
+    pub fn mix_report(&self) -> String {
+        let total: u64 = self.mix.values().sum();
+        let percent = |count: u64| if total == 0 { 0.0 } else { count as f64 / total as f64 * 100.0 };
+        let mut result = String::from("分類ごと:");
+        for (category, count) in &self.mix {
+            result.push_str(&format!("\n  {:<10} {:>10} ({:>6.2}%)", category.name(), count, percent(*count)));
+        }
+        let mut mnemonics: Vec<(&'static str, u64)> = self.mnemonics.iter().map(|(m, c)| (*m, *c)).collect();
+        mnemonics.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        result.push_str("\nニーモニックごと:");
+        for (mnemonic, count) in mnemonics {
+            result.push_str(&format!("\n  {:<10} {:>10} ({:>6.2}%)", mnemonic, count, percent(count)));
+        }
+        result
+    }
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Processor {
     /// 新しいプロセッサを作成
-    #[allow(dead_code)]
     pub fn new() -> Self {
         let mut processor = Self {
             registers: [0; REGISTER_COUNT],
             pc: PC_INITIAL,
             hi: 0,
             lo: 0,
+            fp_registers: [0; FP_REGISTER_COUNT],
+            fcsr: 0,
+            fpu: Box::new(IeeeFpu),
+            syscall_handler: Box::new(SpimSyscalls),
+            cp0: Cp0::default(),
+            exception_vector: None,
             memory: Memory::new(),
-            cache: Cache::new(),
+            cache: Box::new(Cache::new()),
+            cache_trace: None,
+            mem_trace: None,
             stats: ProcessorStats::default(),
+            timeout: None,
+            max_instructions: Some(INSTRUCTION_LIMIT),
+            timing_config: TimingConfig::default(),
+            timing: TimingStats::default(),
+            pipeline: Pipeline::default(),
+            ooo: OutOfOrderCore::default(),
+            predictors: BranchPredictors::default(),
+            isa: Isa::Mips,
+            coverage: Coverage::default(),
+            profiler: None,
+            function_profiler: None,
+            heatmap: None,
+            footprint: None,
+            call_stack: None,
+            register_usage: None,
+            uninitialized: None,
+            poisoned_reads: None,
+            loop_detector: None,
+            history: None,
+            undo_log: None,
+            watchpoints: Vec::new(),
+            memory_watchpoints: Vec::new(),
+            memory_watch_hit: None,
+            replay: None,
+            env_block: None,
+            output: GuestOutput::default(),
+            input: GuestInput::default(),
+            files: HostFiles::default(),
+            heap_start: 0,
+            heap_break: 0,
+            data_ranges: Vec::new(),
+            symbols: SymbolTable::default(),
+            halt_address: HALT_ADDRESS,
+            initial_sp: SP_INITIAL,
+            initial_gp: None,
+            trace_enabled: true,
+            trace_capture: None,
+            stats_enabled: true,
+            coverage_enabled: true,
+            timing_enabled: true,
         };
         
         // スタックポインタを初期化
         processor.registers[29] = SP_INITIAL; // $sp
+        // mainからの復帰で停止アドレスに戻るよう、戻りアドレスを初期化
+        processor.registers[31] = HALT_ADDRESS; // $ra
         
         processor
     }
 
     /// 指定されたサイズのメモリでプロセッサを作成
     pub fn with_memory_size(memory_size: usize) -> Self {
+        Self::with_memory(Memory::with_size(memory_size))
+    }
+
+    /// 作成済みのメモリ（メモリマップしたものなど）でプロセッサを作成
+    pub fn with_memory(memory: Memory) -> Self {
         let mut processor = Self {
             registers: [0; REGISTER_COUNT],
             pc: PC_INITIAL,
             hi: 0,
             lo: 0,
-            memory: Memory::with_size(memory_size),
-            cache: Cache::new(),
+            fp_registers: [0; FP_REGISTER_COUNT],
+            fcsr: 0,
+            fpu: Box::new(IeeeFpu),
+            syscall_handler: Box::new(SpimSyscalls),
+            cp0: Cp0::default(),
+            exception_vector: None,
+            memory,
+            cache: Box::new(Cache::new()),
+            cache_trace: None,
+            mem_trace: None,
             stats: ProcessorStats::default(),
+            timeout: None,
+            max_instructions: Some(INSTRUCTION_LIMIT),
+            timing_config: TimingConfig::default(),
+            timing: TimingStats::default(),
+            pipeline: Pipeline::default(),
+            ooo: OutOfOrderCore::default(),
+            predictors: BranchPredictors::default(),
+            isa: Isa::Mips,
+            coverage: Coverage::default(),
+            profiler: None,
+            function_profiler: None,
+            heatmap: None,
+            footprint: None,
+            call_stack: None,
+            register_usage: None,
+            uninitialized: None,
+            poisoned_reads: None,
+            loop_detector: None,
+            history: None,
+            undo_log: None,
+            watchpoints: Vec::new(),
+            memory_watchpoints: Vec::new(),
+            memory_watch_hit: None,
+            replay: None,
+            env_block: None,
+            output: GuestOutput::default(),
+            input: GuestInput::default(),
+            files: HostFiles::default(),
+            heap_start: 0,
+            heap_break: 0,
+            data_ranges: Vec::new(),
+            symbols: SymbolTable::default(),
+            halt_address: HALT_ADDRESS,
+            initial_sp: SP_INITIAL,
+            initial_gp: None,
+            trace_enabled: true,
+            trace_capture: None,
+            stats_enabled: true,
+            coverage_enabled: true,
+            timing_enabled: true,
         };
         
         // スタックポインタを初期化
         processor.registers[29] = SP_INITIAL; // $sp
+        // mainからの復帰で停止アドレスに戻るよう、戻りアドレスを初期化
+        processor.registers[31] = HALT_ADDRESS; // $ra
         
         processor
     }
@@ -98,6 +481,513 @@ impl Processor {
     pub fn set_register(&mut self, reg: Register, value: Word) {
         if reg != 0 {
             self.registers[reg as usize] = value;
+            if let Some(check) = &mut self.uninitialized {
+                check.written |= 1 << reg;
+            }
+        }
+    }
+
+    /// 浮動小数点レジスタの値をビット列として取得
+    pub fn get_fp_register(&self, reg: Register) -> Word {
+        self.fp_registers[reg as usize]
+    }
+
+    /// 浮動小数点レジスタにビット列を設定
+    pub fn set_fp_register(&mut self, reg: Register, value: Word) {
+        self.fp_registers[reg as usize] = value;
+    }
+
+    /// 浮動小数点レジスタの値を単精度浮動小数点数として取得
+    pub fn get_fp_single(&self, reg: Register) -> f32 {
+        f32::from_bits(self.get_fp_register(reg))
+    }
+
+    /// 浮動小数点レジスタに単精度浮動小数点数を設定
+    pub fn set_fp_single(&mut self, reg: Register, value: f32) {
+        self.set_fp_register(reg, value.to_bits());
+    }
+
+    /// レジスタ対（偶数番号が下位ワード）を倍精度として取得
+    #[cfg(feature = "double")]
+    pub fn get_fp_double(&self, reg: Register) -> f64 {
+        let low = self.get_fp_register(reg) as u64;
+        let high = self.get_fp_register(reg + 1) as u64;
+        f64::from_bits((high << 32) | low)
+    }
+
+    /// レジスタ対（偶数番号が下位ワード）に倍精度の値を設定
+    #[cfg(feature = "double")]
+    pub fn set_fp_double(&mut self, reg: Register, value: f64) {
+        let bits = value.to_bits();
+        self.set_fp_register(reg, bits as Word);
+        self.set_fp_register(reg + 1, (bits >> 32) as Word);
+    }
+
+    /// 浮動小数点演算のモデルを設定
+    pub fn set_fpu_model(&mut self, fpu: Box<dyn FpuModel>) {
+        self.fpu = fpu;
+    }
+
+    /// システムコールのハンドラを設定
+    pub fn set_syscall_handler(&mut self, handler: Box<dyn SyscallHandler>) {
+        self.syscall_handler = handler;
+    }
+
+    /// システムコールのハンドラを取得
+    pub fn syscall_handler(&self) -> &dyn SyscallHandler {
+        self.syscall_handler.as_ref()
+    }
+
+    /// 例外ベクタを設定（Noneで例外を受け付けない）
+    pub fn set_exception_vector(&mut self, vector: Option<MemoryAddress>) {
+        self.exception_vector = vector;
+    }
+
+    /// 例外を受け付けるか（例外ベクタはMIPSのコプロセッサ0の機能なのでRV32Iでは使わない）
+    fn exceptions_enabled(&self) -> bool {
+        self.exception_vector.is_some() && self.isa == Isa::Mips
+    }
+
+    /// 例外を受け付ける場合は、ワード境界に揃っていないアクセスをアドレスエラーにする
+    fn check_aligned(&self, address: MemoryAddress, alignment: u32, store: bool) -> Result<(), ProcessorError> {
+        if self.exceptions_enabled() && !address.is_multiple_of(alignment) {
+            return Err(ProcessorError::AddressError { address, store });
+        }
+        Ok(())
+    }
+
+    /// 符号付き加算（例外を受け付ける場合はオーバーフローで例外、そうでなければ桁あふれを無視する）
+    fn add_signed(&self, a: Word, b: Word) -> Result<Word, ProcessorError> {
+        match (a as i32).checked_add(b as i32) {
+            None if self.exceptions_enabled() => Err(ProcessorError::Overflow),
+            _ => Ok(a.wrapping_add(b)),
+        }
+    }
+
+    /// 符号付き減算（オーバーフローの扱いはadd_signedと同じ）
+    fn sub_signed(&self, a: Word, b: Word) -> Result<Word, ProcessorError> {
+        match (a as i32).checked_sub(b as i32) {
+            None if self.exceptions_enabled() => Err(ProcessorError::Overflow),
+            _ => Ok(a.wrapping_sub(b)),
+        }
+    }
+
+    /// errorを例外として例外ベクタへ制御を移す（受け付けられない場合はerrorをそのまま返す）
+    /// EPCには例外を起こした命令のアドレスが入るので、syscallのハンドラはEPCを4進めてからeretで戻る
+    fn take_exception(&mut self, pc: MemoryAddress, error: ProcessorError, store: bool) -> Result<(), ProcessorError> {
+        let vector = match self.exception_vector {
+            Some(vector) if self.exceptions_enabled() => vector,
+            _ => return Err(error),
+        };
+        let address_error = if store { ExceptionCode::AddressStore } else { ExceptionCode::AddressLoad };
+        let (code, bad_vaddr) = match &error {
+            ProcessorError::MemoryError(error @ (MemoryError::AddressOutOfRange(_)
+                | MemoryError::AccessOutOfRange { .. }
+                | MemoryError::ProtectionViolation { .. })) => (address_error, error.address()),
+            ProcessorError::AddressError { address, store } => {
+                (if *store { ExceptionCode::AddressStore } else { ExceptionCode::AddressLoad }, Some(*address))
+            }
+            ProcessorError::InvalidInstruction(_) => (ExceptionCode::ReservedInstruction, None),
+            ProcessorError::Overflow => (ExceptionCode::Overflow, None),
+            ProcessorError::SyscallException { .. } => (ExceptionCode::Syscall, None),
+            _ => return Err(error),
+        };
+        // ハンドラ内での例外はEPCを上書きして戻れなくなるため、実行を止める
+        if self.cp0.in_exception() {
+            println!("例外処理中に例外 {} が発生しました: PC=0x{:08X}", code.name(), pc);
+            return Err(error);
+        }
+        self.trace(|_| format!("例外 {}: EPC=0x{:08X}, ハンドラ 0x{:08X} へ移ります ({})", code.name(), pc, vector, error));
+        self.cp0.enter_exception(code, pc, bad_vaddr);
+        self.pc = vector;
+        self.count(|stats| stats.exceptions += 1);
+        Ok(())
+    }
+
+    /// 保留中の割り込みがあれば、次の命令を実行する前に例外ベクタへ移る（EPCは次に実行するはずだった命令）
+    fn take_interrupt(&mut self) {
+        if let Some(vector) = self.exception_vector.filter(|_| self.exceptions_enabled() && self.cp0.pending_interrupt()) {
+            self.trace(|processor| format!("割り込み: EPC=0x{:08X}, Cause=0x{:08X}, ハンドラ 0x{:08X} へ移ります",
+                                           processor.pc, processor.cp0.cause, vector));
+            self.cp0.enter_exception(ExceptionCode::Interrupt, self.pc, None);
+            self.pc = vector;
+            self.count(|stats| {
+                stats.exceptions += 1;
+                stats.interrupts += 1;
+            });
+        }
+    }
+
+    /// 条件分岐の予測器を設定
+    pub fn set_branch_predictors(&mut self, mut predictors: BranchPredictors) {
+        predictors.set_stats_enabled(self.stats_enabled);
+        self.predictors = predictors;
+    }
+
+    /// 実行する命令セットを設定
+    pub fn set_isa(&mut self, isa: Isa) {
+        // スタックポインタなどのレジスタ番号はISAによって異なる
+        for (reg, _) in self.initial_registers() {
+            self.registers[reg as usize] = 0;
+        }
+        self.isa = isa;
+        for (reg, value) in self.initial_registers() {
+            self.registers[reg as usize] = value;
+        }
+    }
+
+    /// 起動時に値を入れるレジスタとその値（スタックポインタ, 戻りアドレス, 指定していればグローバルポインタ）
+    fn initial_registers(&self) -> Vec<(Register, Word)> {
+        let mut initial = vec![(self.sp_register(), self.initial_sp), (self.ra_register(), self.halt_address)];
+        if let Some(gp) = self.initial_gp {
+            initial.push((self.gp_register(), gp));
+        }
+        initial
+    }
+
+    /// スタックポインタの初期値を設定する（スタックポインタのレジスタも書き換える）
+    pub fn set_initial_sp(&mut self, sp: MemoryAddress) {
+        self.initial_sp = sp;
+        self.registers[self.sp_register() as usize] = sp;
+    }
+
+    /// グローバルポインタの初期値を設定する（グローバルポインタのレジスタも書き換える）
+    pub fn set_initial_gp(&mut self, gp: MemoryAddress) {
+        self.initial_gp = Some(gp);
+        self.registers[self.gp_register() as usize] = gp;
+    }
+
+    /// 停止アドレスを設定する（戻りアドレスのレジスタも停止アドレスにする）
+    pub fn set_halt_address(&mut self, address: MemoryAddress) {
+        self.halt_address = address;
+        self.registers[self.ra_register() as usize] = address;
+    }
+
+    /// 停止アドレスを取得
+    pub fn get_halt_address(&self) -> MemoryAddress {
+        self.halt_address
+    }
+
+    /// 実行する命令セットを取得
+    pub fn get_isa(&self) -> Isa {
+        self.isa
+    }
+
+    /// スタックポインタのレジスタ番号
+    fn sp_register(&self) -> Register {
+        match self.isa {
+            Isa::Mips => 29,
+            Isa::Rv32i => riscv::SP_REGISTER,
+        }
+    }
+
+    /// グローバルポインタのレジスタ番号
+    fn gp_register(&self) -> Register {
+        match self.isa {
+            Isa::Mips => 28,
+            Isa::Rv32i => riscv::GP_REGISTER,
+        }
+    }
+
+    /// 戻りアドレスのレジスタ番号
+    fn ra_register(&self) -> Register {
+        match self.isa {
+            Isa::Mips => 31,
+            Isa::Rv32i => riscv::RA_REGISTER,
+        }
+    }
+
+    /// 直前に実行した命令の履歴に残す命令数を設定する（0で無効）
+    pub fn set_history_length(&mut self, length: usize) {
+        self.history = (length > 0).then(|| ExecutionHistory::new(length));
+    }
+
+    /// 逆実行のために記録する命令数を設定する（0で記録しない）
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.undo_log = (limit > 0).then(|| UndoLog::new(limit));
+    }
+
+    /// レジスタのウォッチポイントを追加する
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// メモリのウォッチポイントを追加する
+    pub fn add_memory_watchpoint(&mut self, watchpoint: MemoryWatchpoint) {
+        self.memory_watchpoints.push(watchpoint);
+    }
+
+    /// 書き込むバイト列がメモリのウォッチポイントの対象なら、書き込む前の値とともに報告する
+    fn watch_memory_write(&mut self, address: MemoryAddress, bytes: &[u8]) {
+        let word = |bytes: &[u8]| bytes.iter().rev().fold(0, |word, &byte| word << 8 | byte as Word);
+        for index in self.matching_memory_watchpoints(address, bytes.len() as u32, true) {
+            // 長い書き込みでは、範囲に入る最初の位置から最大4バイトを示す
+            let offset = (self.memory_watchpoints[index].range.start.saturating_sub(address) as usize).min(bytes.len() - 1);
+            let new = &bytes[offset..(offset + 4).min(bytes.len())];
+            let target = address.wrapping_add(offset as u32);
+            let old = self.peek_range(target, new.len()).unwrap_or_default();
+            self.report_memory_watch(index, target, true, word(&old), word(new));
+        }
+    }
+
+    /// アクセスが対象になるメモリのウォッチポイントの番号
+    fn matching_memory_watchpoints(&self, address: MemoryAddress, size: u32, write: bool) -> Vec<usize> {
+        (0..self.memory_watchpoints.len())
+            .filter(|&index| self.memory_watchpoints[index].matches(address, size, write))
+            .collect()
+    }
+
+    /// メモリのウォッチポイントに該当したアクセスを、表示するか命令の完了後に止めるために記録する
+    fn report_memory_watch(&mut self, index: usize, address: MemoryAddress, write: bool, old: Word, new: Word) {
+        let watchpoint = &self.memory_watchpoints[index];
+        let hit = ProcessorError::MemoryWatchpoint { pc: self.pc, watchpoint: watchpoint.to_string(), address, write, old, new };
+        if watchpoint.log_only {
+            println!("{}", hit);
+        } else if self.memory_watch_hit.is_none() {
+            self.memory_watch_hit = Some(hit);
+        }
+    }
+
+    /// 逆実行で戻せる命令数
+    pub fn undo_depth(&self) -> usize {
+        self.undo_log.as_ref().map_or(0, UndoLog::depth)
+    }
+
+    /// 実行中の命令がメモリを書き換える前の値をアンドゥログに記録する（範囲外のアドレスは書き込みも失敗するので記録しない）
+    fn log_memory_write(&mut self, address: MemoryAddress, length: usize) {
+        if self.undo_log.is_none() {
+            return;
+        }
+        if let Ok(original) = self.peek_range(address, length) {
+            if let Some(undo_log) = &mut self.undo_log {
+                undo_log.record_memory(address, &original);
+            }
+        }
+    }
+
+    /// 直前に実行した命令をcount命令まで取り消し、取り消した命令数を返す
+    /// 統計やキャッシュの状態、ゲストプログラムの入出力は戻さない
+    pub fn rewind(&mut self, count: usize) -> usize {
+        let mut rewound = 0;
+        while rewound < count {
+            let Some(record) = self.undo_log.as_mut().and_then(UndoLog::pop) else {
+                break;
+            };
+            for &(address, byte) in record.memory.iter().rev() {
+                // 記録したときに読めたアドレスなので書き込みは失敗しない
+                let _ = self.cache.poke_byte(&mut self.memory, address, byte);
+            }
+            for &(reg, value) in &record.registers {
+                match reg.checked_sub(FP_REGISTER_BASE) {
+                    Some(fp) => self.fp_registers[fp as usize] = value,
+                    None => self.registers[reg as usize] = value,
+                }
+            }
+            (self.hi, self.lo) = record.hi_lo;
+            self.fcsr = record.fcsr;
+            self.cp0 = record.cp0;
+            self.heap_break = record.heap_break;
+            self.pc = record.pc;
+            rewound += 1;
+        }
+        rewound
+    }
+
+    /// 直前に実行した命令の履歴（履歴が無効ならNone）
+    pub fn history_report(&self) -> Option<String> {
+        self.history.as_ref().map(|history| history.report(
+            |pc, instruction| format!("{}: {}", self.symbols.format_address(pc), self.disassemble_at(instruction, pc)),
+            |reg| self.operand_name(reg)))
+    }
+
+    /// PipelineOpの番号付け（浮動小数点レジスタはFP_REGISTER_BASEを足した番号）でのレジスタの値
+    pub fn operand_value(&self, reg: u8) -> Word {
+        match reg.checked_sub(FP_REGISTER_BASE) {
+            Some(fp) => self.fp_registers[fp as usize],
+            None => self.registers[reg as usize],
+        }
+    }
+
+    /// PipelineOpの番号付けでのレジスタの表示名
+    fn operand_name(&self, reg: u8) -> String {
+        match reg.checked_sub(FP_REGISTER_BASE) {
+            Some(fp) => format!("$f{}", fp),
+            None => self.register_name(reg).to_string(),
+        }
+    }
+
+    /// 無限ループの検出を設定する（Noneで無効）
+    pub fn set_loop_detection(&mut self, config: Option<LoopDetectionConfig>) {
+        self.loop_detector = config.map(LoopDetector::new);
+    }
+
+    /// 無限ループを検出したときの診断を表示し、エラーを作成する
+    fn infinite_loop_error(&self) -> ProcessorError {
+        let detector = self.loop_detector.as_ref().expect("無限ループの検出が無効です");
+        let addresses = detector.addresses();
+        println!("無限ループを検出しました: 状態を変えずに {} 命令のあいだ次の {} 個のアドレスを繰り返しています",
+            detector.instructions(), addresses.len());
+        for &address in &addresses {
+            match self.peek_word(address) {
+                Ok(word) => println!("  {}: {}", self.symbols.format_address(address), self.disassemble_at(word, address)),
+                Err(_) => println!("  {}", self.symbols.format_address(address)),
+            }
+        }
+        ProcessorError::InfiniteLoop { addresses: addresses.len(), instructions: detector.instructions() }
+    }
+
+    /// 未初期化のレジスタ（$zeroと起動時に値を入れるレジスタ以外でリセット後に書き込まれていないもの）の読み出しの検出を有効にする
+    pub fn enable_uninitialized_check(&mut self) {
+        self.uninitialized.get_or_insert_with(UninitializedRegisters::default);
+    }
+
+    /// メモリ全体を指定したバイトで埋める（プログラムを読み込む前に呼ぶ）
+    pub fn poison_memory(&mut self, value: u8) {
+        self.memory.fill(value);
+    }
+
+    /// 埋めたバイトのままのワードの読み込み（未初期化のメモリの読み込みの可能性が高い）の検出を有効にする
+    pub fn enable_poison_check(&mut self, value: u8) {
+        self.poisoned_reads = Some(PoisonedReads { pattern: Word::from_le_bytes([value; 4]), reads: Vec::new() });
+    }
+
+    /// 検出した未初期化のメモリの読み込み（命令のアドレス, 読み込んだアドレス）
+    pub fn poisoned_reads(&self) -> &[(MemoryAddress, MemoryAddress)] {
+        self.poisoned_reads.as_ref().map_or(&[], |check| &check.reads)
+    }
+
+    /// 検出した未初期化のレジスタの読み出し（命令のアドレス, レジスタ）
+    pub fn uninitialized_reads(&self) -> &[(MemoryAddress, Register)] {
+        self.uninitialized.as_ref().map_or(&[], |check| &check.reads)
+    }
+
+    /// pcの命令が未初期化のレジスタを読むなら警告する（レジスタごとに最初の1回だけ）
+    fn check_uninitialized_reads(&mut self, instruction: Instruction, pc: MemoryAddress) {
+        let mut sources: Vec<Register> = match self.category(instruction) {
+            // システムコールの引数は番号によって使うものが異なるので、番号のレジスタだけを確かめる
+            InstructionCategory::Syscall => vec![match self.isa {
+                Isa::Mips => 2,
+                Isa::Rv32i => riscv::SYSCALL_NUMBER_REGISTER,
+            }],
+            _ => {
+                let op = match self.isa {
+                    Isa::Mips => PipelineOp::from_mips(&InstructionType::decode(instruction)),
+                    Isa::Rv32i => PipelineOp::from_riscv(&RvInstruction::decode(instruction)),
+                };
+                op.sources.into_iter().filter(|&reg| (reg as usize) < REGISTER_COUNT).collect()
+            }
+        };
+        sources.sort_unstable();
+        sources.dedup();
+        let known = match &self.uninitialized {
+            Some(check) => self.initial_registers().iter()
+                .fold(check.written | check.reported | 1, |known, &(reg, _)| known | (1 << reg)),
+            None => return,
+        };
+        for reg in sources.into_iter().filter(|&reg| known & (1 << reg) == 0) {
+            println!("警告: 未初期化のレジスタ {} を読みました: PC=0x{:08X} ({})",
+                self.register_name(reg), pc, self.disassemble_at(instruction, pc));
+            if let Some(check) = &mut self.uninitialized {
+                check.reported |= 1 << reg;
+                check.reads.push((pc, reg));
+            }
+        }
+    }
+
+    /// レジスタの表示名（MIPSは$t0などの別名、RV32Iはx0などのABI名）
+    fn register_name(&self, reg: Register) -> &'static str {
+        match reg {
+            _ if self.isa == Isa::Rv32i => riscv::REGISTER_NAMES[reg as usize],
+            0 => "$zero",
+            1 => "$at",
+            2 => "$v0", 3 => "$v1",
+            4 => "$a0", 5 => "$a1", 6 => "$a2", 7 => "$a3",
+            8 => "$t0", 9 => "$t1", 10 => "$t2", 11 => "$t3",
+            12 => "$t4", 13 => "$t5", 14 => "$t6", 15 => "$t7",
+            16 => "$s0", 17 => "$s1", 18 => "$s2", 19 => "$s3",
+            20 => "$s4", 21 => "$s5", 22 => "$s6", 23 => "$s7",
+            24 => "$t8", 25 => "$t9",
+            26 => "$k0", 27 => "$k1",
+            28 => "$gp", 29 => "$sp", 30 => "$fp", 31 => "$ra",
+            _ => "???",
+        }
+    }
+
+    /// addressに置かれた命令として逆アセンブルする（分岐・ジャンプ先は絶対アドレスとシンボルで示す）
+    pub fn disassemble_at(&self, instruction: Instruction, address: MemoryAddress) -> String {
+        match self.isa {
+            Isa::Mips => InstructionType::decode(instruction).display_at(address, &self.symbols),
+            Isa::Rv32i => RvInstruction::decode(instruction).display_at(address, &self.symbols),
+        }
+    }
+
+    /// シンボル表を設定
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    /// シンボル表を取得
+    pub fn get_symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// 現在のISAでのニーモニック
+    fn mnemonic(&self, instruction: Instruction) -> &'static str {
+        match self.isa {
+            Isa::Mips => InstructionType::decode(instruction).mnemonic(),
+            Isa::Rv32i => RvInstruction::decode(instruction).mnemonic(),
+        }
+    }
+
+    /// 現在のISAでの命令の分類
+    fn category(&self, instruction: Instruction) -> InstructionCategory {
+        match self.isa {
+            Isa::Mips => InstructionType::decode(instruction).category(),
+            Isa::Rv32i => RvInstruction::decode(instruction).category(),
+        }
+    }
+
+    /// 現在のISAでの命令の分類とニーモニック（毎命令求めるので、1回のデコードで両方を求める）
+    fn category_and_mnemonic(&self, instruction: Instruction) -> (InstructionCategory, &'static str) {
+        match self.isa {
+            Isa::Mips => {
+                let decoded = InstructionType::decode(instruction);
+                (decoded.category(), decoded.mnemonic())
+            }
+            Isa::Rv32i => {
+                let decoded = RvInstruction::decode(instruction);
+                (decoded.category(), decoded.mnemonic())
+            }
+        }
+    }
+
+    /// ストア命令かどうか
+    fn is_store(&self, instruction: Instruction) -> bool {
+        match self.isa {
+            Isa::Mips => match InstructionType::decode(instruction) {
+                InstructionType::Sw { .. } => true,
+                #[cfg(feature = "double")]
+                InstructionType::Sdc1 { .. } => true,
+                _ => false,
+            },
+            Isa::Rv32i => matches!(RvInstruction::decode(instruction), RvInstruction::Store { .. }),
+        }
+    }
+
+    /// pcにある命令が関数の呼び出し・復帰であるか（$ra・x1・x5を戻りアドレスのレジスタとみなす）
+    fn call_event(&self, instruction: Instruction, pc: MemoryAddress) -> Option<CallEvent> {
+        let call = Some(CallEvent::Call { return_address: pc.wrapping_add(4) });
+        match self.isa {
+            Isa::Mips => match InstructionType::decode(instruction) {
+                InstructionType::Jal { .. } => call,
+                InstructionType::Jr { rs: 31 } => Some(CallEvent::Return),
+                _ => None,
+            },
+            Isa::Rv32i => match RvInstruction::decode(instruction) {
+                RvInstruction::Jal { rd: 1 | 5, .. } | RvInstruction::Jalr { rd: 1 | 5, .. } => call,
+                RvInstruction::Jalr { rd: 0, rs1: 1 | 5, .. } => Some(CallEvent::Return),
+                _ => None,
+            },
         }
     }
 
@@ -107,34 +997,39 @@ impl Processor {
     }
 
     /// プログラムカウンタを設定
-    #[allow(dead_code)]
     pub fn set_pc(&mut self, pc: MemoryAddress) {
         self.pc = pc;
     }
 
     /// メモリから命令を読み込む
     pub fn fetch_instruction(&mut self) -> Result<Instruction, MemoryError> {
-        println!("PC=0x{:08X} から命令をフェッチ", self.pc);
-        let instruction = self.cache.read_word(&mut self.memory, self.pc)?;
-        println!("フェッチした命令: 0x{:08X}", instruction);
+        self.trace(|processor| format!("PC=0x{:08X} から命令をフェッチ", processor.pc));
+        self.check_protection(self.pc, 4, Access::Execute)?;
+        let pc = self.pc;
+        let instruction = self.cache.fetch_word(&mut self.memory, pc)
+            .map_err(|e| e.in_access(Access::Execute, 4, pc))?;
+        if let Some(footprint) = self.footprint.as_mut().filter(|_| self.stats_enabled) {
+            footprint.record(pc, 4, false);
+        }
+        self.trace(|_| format!("フェッチした命令: 0x{:08X}", instruction));
         Ok(instruction)
     }
 
     /// 命令を実行
-    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<bool, ProcessorError> {
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<StepSignal, ProcessorError> {
         let instruction_type = InstructionType::decode(instruction);
         
         match instruction_type {
             InstructionType::Add { rd, rs, rt } => {
                 let rs_val = self.get_register(rs);
                 let rt_val = self.get_register(rt);
-                self.set_register(rd, rs_val.wrapping_add(rt_val));
+                self.set_register(rd, self.add_signed(rs_val, rt_val)?);
             }
             
             InstructionType::Sub { rd, rs, rt } => {
                 let rs_val = self.get_register(rs);
                 let rt_val = self.get_register(rt);
-                self.set_register(rd, rs_val.wrapping_sub(rt_val));
+                self.set_register(rd, self.sub_signed(rs_val, rt_val)?);
             }
             
             InstructionType::And { rd, rs, rt } => {
@@ -168,41 +1063,45 @@ impl Processor {
             InstructionType::Jr { rs } => {
                 let rs_val = self.get_register(rs);
                 self.pc = rs_val;
-                self.stats.branches_taken += 1;
-                return Ok(true); // 分岐が発生
+                self.count(|stats| stats.branches_taken += 1);
+                return Ok(StepSignal::Branch); // 分岐が発生
             }
             
             InstructionType::Addi { rt, rs, imm } => {
-                let rs_val = self.get_register(rs) as i32;
-                let result = rs_val.wrapping_add(imm as i32) as u32;
+                let rs_val = self.get_register(rs);
+                let result = self.add_signed(rs_val, imm as i32 as u32)?;
                 self.set_register(rt, result);
             }
             
             InstructionType::Lw { rt, rs, imm } => {
                 let rs_val = self.get_register(rs);
                 let address = rs_val.wrapping_add(imm as u32);
-                let value = self.cache.read_word(&mut self.memory, address)
-                    .map_err(|e| ProcessorError::MemoryError(e))?;
+                self.check_aligned(address, 4, false)?;
+                let value = self.read_sized(address, 4)
+                    .map_err(ProcessorError::MemoryError)?;
+                self.trace_memory(address, 4, false, value, rs);
                 self.set_register(rt, value);
-                self.stats.loads_executed += 1;
+                self.count(|stats| stats.loads_executed += 1);
             }
             
             InstructionType::Sw { rt, rs, imm } => {
                 let rs_val = self.get_register(rs);
                 let rt_val = self.get_register(rt);
                 let address = rs_val.wrapping_add(imm as u32);
-                self.cache.write_word(&mut self.memory, address, rt_val)
-                    .map_err(|e| ProcessorError::MemoryError(e))?;
-                self.stats.stores_executed += 1;
+                self.check_aligned(address, 4, true)?;
+                self.write_sized(address, rt_val, 4)
+                    .map_err(ProcessorError::MemoryError)?;
+                self.trace_memory(address, 4, true, rt_val, rs);
+                self.count(|stats| stats.stores_executed += 1);
             }
             
             InstructionType::Beq { rs, rt, imm } => {
                 let rs_val = self.get_register(rs);
                 let rt_val = self.get_register(rt);
                 if rs_val == rt_val {
-                    self.pc = self.pc.wrapping_add((imm as i32 * 4) as u32);
-                    self.stats.branches_taken += 1;
-                    return Ok(true); // 分岐が発生
+                    self.pc = instructions::branch_target(self.pc, imm);
+                    self.count(|stats| stats.branches_taken += 1);
+                    return Ok(StepSignal::Branch); // 分岐が発生
                 }
             }
             
@@ -210,9 +1109,9 @@ impl Processor {
                 let rs_val = self.get_register(rs);
                 let rt_val = self.get_register(rt);
                 if rs_val != rt_val {
-                    self.pc = self.pc.wrapping_add((imm as i32 * 4) as u32);
-                    self.stats.branches_taken += 1;
-                    return Ok(true); // 分岐が発生
+                    self.pc = instructions::branch_target(self.pc, imm);
+                    self.count(|stats| stats.branches_taken += 1);
+                    return Ok(StepSignal::Branch); // 分岐が発生
                 }
             }
             
@@ -220,137 +1119,1006 @@ impl Processor {
                 let rs_val = self.get_register(rs) as i32;
                 self.set_register(rt, if rs_val < imm as i32 { 1 } else { 0 });
             }
+
+            InstructionType::Lui { rt, imm } => {
+                self.set_register(rt, (imm as u16 as u32) << 16);
+            }
+
+            InstructionType::Ori { rt, rs, imm } => {
+                let rs_val = self.get_register(rs);
+                self.set_register(rt, rs_val | imm as u16 as u32);
+            }
             
             InstructionType::J { addr } => {
-                println!(
+                self.trace(|processor| format!(
                     "[JUMP] From: 0x{:08X}, To: 0x{:08X} (addr field: 0x{:07X})",
-                    self.pc, (self.pc & 0xF0000000) | (addr << 2), addr
-                );
-                self.pc = (self.pc & 0xF0000000) | (addr << 2);
-                self.stats.branches_taken += 1;
-                return Ok(true); // 分岐が発生
+                    processor.pc, instructions::jump_target(processor.pc, addr), addr
+                ));
+                self.pc = instructions::jump_target(self.pc, addr);
+                self.count(|stats| stats.branches_taken += 1);
+                return Ok(StepSignal::Branch); // 分岐が発生
             }
             
             InstructionType::Jal { addr } => {
                 self.set_register(31, self.pc + 4); // $raに戻りアドレスを保存
-                self.pc = (self.pc & 0xF0000000) | (addr << 2);
-                self.stats.branches_taken += 1;
-                return Ok(true); // 分岐が発生
+                self.pc = instructions::jump_target(self.pc, addr);
+                self.count(|stats| stats.branches_taken += 1);
+                return Ok(StepSignal::Branch); // 分岐が発生
             }
             
-            InstructionType::Syscall => {
-                // システムコールの実装
-                // self.set_register(2, 10); // $v0 = 10 (exit syscall)
-                let syscall_number = self.get_register(2); // $v0レジスタからシステムコール番号を取得
-                println!("Syscall実行: $v0 = {} (syscall番号: {})", self.get_register(2), syscall_number);
-                
-                match syscall_number {
-                    1 => {
-                        // print_int: $a0レジスタの値を整数として出力
-                        let value = self.get_register(4); // $a0レジスタ
-                        println!("{}", value as i32);
-                    }
-                    4 => {
-                        // print_string: $a0レジスタのアドレスから文字列を出力
-                        let address = self.get_register(4); // $a0レジスタ
-                        self.print_string(address)?;
-                    }
-                    10 => {
-                        // exit: プログラム終了
-                        println!("プログラムが終了しました");
-                        return Err(ProcessorError::ProgramEnd); // プログラム終了
-                    }
-                    11 => {
-                        // print_char: $a0レジスタの値を文字として出力
-                        let value = self.get_register(4); // $a0レジスタ
-                        print!("{}", value as u8 as char);
-                    }
-                    _ => {
-                        println!("未対応のシステムコール: {}", syscall_number);
-                        return Err(ProcessorError::InvalidInstruction(instruction));
-                    }
-                }
+            InstructionType::Mfc0 { rt, rd } => {
+                self.set_register(rt, self.cp0.read(rd));
             }
-            
-            InstructionType::Invalid => {
-                return Err(ProcessorError::InvalidInstruction(instruction));
+
+            InstructionType::Mtc0 { rt, rd } => {
+                self.cp0.write(rd, self.get_register(rt));
             }
-        }
-        
-        // 通常の命令の場合、分岐なしを返す
-        Ok(false) // 分岐なし
-    }
 
-    /// 1命令を実行（フェッチ + 実行）
-    pub fn step(&mut self) -> Result<bool, ProcessorError> {
-        let instruction = self.fetch_instruction()
-            .map_err(|e| {
-                println!("命令フェッチエラー: PC=0x{:08X}, エラー={}", self.pc, e);
-                ProcessorError::MemoryError(e)
-            })?;
-        
-        let instruction_type = InstructionType::decode(instruction);
-        println!("実行: 0x{:08X} ({})", instruction, instruction_type);
-        
-        let branch_taken = self.execute_instruction(instruction)
-            .map_err(|e| {
-                println!("命令実行エラー: PC=0x{:08X}, エラー={}", self.pc, e);
-                e
-            })?;
-        
-        // 分岐が発生しなかった場合のみPCを4進める
-        if !branch_taken {
-            self.pc = self.pc.wrapping_add(4);
-            self.stats.instructions_executed += 1;
-        }
-        
-        Ok(branch_taken)
-    }
+            InstructionType::Eret => {
+                self.pc = self.cp0.exception_return();
+                self.count(|stats| stats.branches_taken += 1);
+                return Ok(StepSignal::Branch); // 分岐が発生
+            }
 
-    /// プログラムを実行（無限ループまたはシステムコールまで）
-    pub fn run(&mut self) -> Result<(), ProcessorError> {
-        let mut instruction_count = 0;
-        loop {
-            // 無効なアドレスの場合は終了
-            if self.pc == 0xFFFFFFFF {
-                break;
+            InstructionType::Mfc1 { rt, fs } => {
+                self.set_register(rt, self.get_fp_register(fs));
             }
-            
-            // デバッグ出力
-            if instruction_count < 10 {
-                println!("命令 {}: PC=0x{:08X}", instruction_count, self.pc);
+
+            InstructionType::Mtc1 { rt, fs } => {
+                self.set_fp_register(fs, self.get_register(rt));
             }
-            
-            match self.step()? {
-                true => {
-                    println!("分岐が発生: PC=0x{:08X}", self.pc);
-                    continue; // 分岐が発生した場合
-                }
-                false => {
-                    // システムコールの場合は終了
-                    // println!("self.registers[2] = {}", self.registers[2]);
-                    // エラーでなくループを抜けることによってプログラムを終了させるように修正したい！！
-                    if self.registers[2] == 10 {
-                        println!("システムコールで終了\n\n");
-                        println!("=== 計算結果 ===\n");
-                        println!("{}", self.get_register(4));
-                        break;
-                    }
-                    // 通常の命令の場合は次のループで続行
+
+            InstructionType::Cfc1 { rt, fs } => {
+                // FCSR以外の制御レジスタは未実装のため0を返す
+                let value = if fs == FCSR_REGISTER { self.fcsr } else { 0 };
+                self.set_register(rt, value);
+            }
+
+            InstructionType::Ctc1 { rt, fs } => {
+                if fs == FCSR_REGISTER {
+                    self.fcsr = self.get_register(rt);
                 }
             }
-            
-            instruction_count += 1;
-            
-            // 安全のため、1000命令で強制終了
-            if instruction_count > 100000 {
-                println!("警告: 100000命令を超えました。強制終了します。");
-                break;
+
+            InstructionType::CvtSW { fd, fs } => {
+                let value = self.fpu.int_to_single(self.get_fp_register(fs));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::CvtWS { fd, fs } => {
+                // cvt.w.sはFCSRの丸めモードに従う
+                let mode = RoundingMode::from_fcsr(self.fcsr);
+                let value = fpu::single_to_word(self.get_fp_single(fs), mode);
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::TruncWS { fd, fs } => {
+                // trunc.w.sはFCSRに関係なく常にゼロ方向へ丸める
+                let value = fpu::single_to_word(self.get_fp_single(fs), RoundingMode::TowardZero);
+                self.set_fp_register(fd, value);
+            }
+            
+            InstructionType::AddS { fd, fs, ft } => {
+                let value = self.fpu.add(self.get_fp_register(fs), self.get_fp_register(ft));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::SubS { fd, fs, ft } => {
+                let value = self.fpu.sub(self.get_fp_register(fs), self.get_fp_register(ft));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::MulS { fd, fs, ft } => {
+                let value = self.fpu.mul(self.get_fp_register(fs), self.get_fp_register(ft));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::DivS { fd, fs, ft } => {
+                let value = self.fpu.div(self.get_fp_register(fs), self.get_fp_register(ft));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::SqrtS { fd, fs } => {
+                let value = self.fpu.sqrt(self.get_fp_register(fs));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::FinvS { fd, fs } => {
+                let value = self.fpu.inv(self.get_fp_register(fs));
+                self.set_fp_register(fd, value);
+            }
+
+            InstructionType::AbsS { fd, fs } => {
+                // 符号ビットのみを操作するため、NaNのペイロードも保存される
+                self.set_fp_register(fd, self.get_fp_register(fs) & !SIGN_BIT);
+            }
+
+            InstructionType::MovS { fd, fs } => {
+                self.set_fp_register(fd, self.get_fp_register(fs));
+            }
+
+            InstructionType::NegS { fd, fs } => {
+                self.set_fp_register(fd, self.get_fp_register(fs) ^ SIGN_BIT);
+            }
+
+            // 倍精度演算はFPUモデルを通さずホストのf64で計算する
+            #[cfg(feature = "double")]
+            InstructionType::AddD { fd, fs, ft } => {
+                self.check_fp_pairs(instruction, &[fd, fs, ft])?;
+                self.set_fp_double(fd, self.get_fp_double(fs) + self.get_fp_double(ft));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::SubD { fd, fs, ft } => {
+                self.check_fp_pairs(instruction, &[fd, fs, ft])?;
+                self.set_fp_double(fd, self.get_fp_double(fs) - self.get_fp_double(ft));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::MulD { fd, fs, ft } => {
+                self.check_fp_pairs(instruction, &[fd, fs, ft])?;
+                self.set_fp_double(fd, self.get_fp_double(fs) * self.get_fp_double(ft));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::DivD { fd, fs, ft } => {
+                self.check_fp_pairs(instruction, &[fd, fs, ft])?;
+                self.set_fp_double(fd, self.get_fp_double(fs) / self.get_fp_double(ft));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::SqrtD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fd, fs])?;
+                self.set_fp_double(fd, self.get_fp_double(fs).sqrt());
+            }
+
+            // abs.d/neg.d/mov.dはビット操作のみで行う（符号ビットは奇数番号側の最上位ビット）
+            #[cfg(feature = "double")]
+            InstructionType::AbsD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fd, fs])?;
+                self.set_fp_register(fd, self.get_fp_register(fs));
+                self.set_fp_register(fd + 1, self.get_fp_register(fs + 1) & !SIGN_BIT);
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::MovD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fd, fs])?;
+                self.set_fp_register(fd, self.get_fp_register(fs));
+                self.set_fp_register(fd + 1, self.get_fp_register(fs + 1));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::NegD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fd, fs])?;
+                self.set_fp_register(fd, self.get_fp_register(fs));
+                self.set_fp_register(fd + 1, self.get_fp_register(fs + 1) ^ SIGN_BIT);
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::CvtDS { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fd])?;
+                self.set_fp_double(fd, self.get_fp_single(fs) as f64);
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::CvtSD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fs])?;
+                self.set_fp_register(fd, (self.get_fp_double(fs) as f32).to_bits());
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::CvtDW { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fd])?;
+                self.set_fp_double(fd, self.get_fp_register(fs) as i32 as f64);
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::CvtWD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fs])?;
+                let mode = RoundingMode::from_fcsr(self.fcsr);
+                self.set_fp_register(fd, fpu::double_to_word(self.get_fp_double(fs), mode));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::TruncWD { fd, fs } => {
+                self.check_fp_pairs(instruction, &[fs])?;
+                self.set_fp_register(fd, fpu::double_to_word(self.get_fp_double(fs), RoundingMode::TowardZero));
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::Ldc1 { ft, base, imm } => {
+                // リトルエンディアンなので下位ワードが偶数番号のレジスタに入る
+                self.check_fp_pairs(instruction, &[ft])?;
+                let address = self.get_register(base).wrapping_add(imm as u32);
+                self.check_aligned(address, 8, false)?;
+                self.check_protection(address, 8, Access::Read)?;
+                let low = self.read_sized(address, 4)?;
+                let high = self.read_sized(address.wrapping_add(4), 4)?;
+                self.trace_memory(address, 4, false, low, base);
+                self.trace_memory(address.wrapping_add(4), 4, false, high, base);
+                self.set_fp_register(ft, low);
+                self.set_fp_register(ft + 1, high);
+                self.count(|stats| stats.loads_executed += 1);
+            }
+
+            #[cfg(feature = "double")]
+            InstructionType::Sdc1 { ft, base, imm } => {
+                self.check_fp_pairs(instruction, &[ft])?;
+                let address = self.get_register(base).wrapping_add(imm as u32);
+                self.check_aligned(address, 8, true)?;
+                // 下位ワードだけを書き込んで止まらないよう、先に8バイトとも確かめる
+                self.check_protection(address, 8, Access::Write)?;
+                let high_address = address.wrapping_add(4);
+                if !self.memory.is_device(high_address) && (high_address as usize).saturating_add(3) >= self.memory.size() {
+                    return Err(MemoryError::AddressOutOfRange(high_address).in_access(Access::Write, 4, self.pc).into());
+                }
+                let (low, high) = (self.get_fp_register(ft), self.get_fp_register(ft + 1));
+                self.write_sized(address, low, 4)?;
+                self.write_sized(high_address, high, 4)?;
+                self.trace_memory(address, 4, true, low, base);
+                self.trace_memory(address.wrapping_add(4), 4, true, high, base);
+                self.count(|stats| stats.stores_executed += 1);
+            }
+            
+            InstructionType::Syscall => {
+                // $v0レジスタからシステムコール番号、$a0〜$a2レジスタから引数を取得し、戻り値は$v0に返す
+                let syscall_number = self.get_register(2);
+                self.trace(|processor| format!("Syscall実行: $v0 = {} (syscall番号: {})", processor.get_register(2), syscall_number));
+                let args = [self.get_register(4), self.get_register(5), self.get_register(6)];
+                match self.syscall(syscall_number, args, instruction)? {
+                    SyscallResult::Return(result) => self.set_register(2, result),
+                    SyscallResult::Halt { code } => return Ok(StepSignal::Halt { code }),
+                    SyscallResult::Done => {}
+                }
+            }
+            
+            InstructionType::Break { code } => {
+                return Err(ProcessorError::Break { code });
+            }
+
+            InstructionType::Invalid => {
+                return Err(self.invalid_instruction(instruction));
             }
         }
+        
+        // 通常の命令の場合、分岐なしを返す
+        Ok(StepSignal::Continue)
+    }
+
+    /// システムコールを実行（番号と引数の渡し方はISAごとに呼び出し側で決める）
+    /// 入力の記録・再生を有効にしていれば、ホストから値を受け取るシステムコールの結果を記録し、または記録から返す
+    fn syscall(&mut self, syscall_number: Word, args: [Word; 3], instruction: Instruction) -> Result<SyscallResult, ProcessorError> {
+        let Some(log) = self.replay.as_mut().filter(|_| replay::is_recorded_syscall(syscall_number, args)) else {
+            return self.dispatch_syscall(syscall_number, args, instruction);
+        };
+        if log.is_replaying() {
+            let index = log.position();
+            let event = log.next(syscall_number)
+                .map_err(|expected| ProcessorError::ReplayDivergence { index, expected, actual: syscall_number })?;
+            if let Some((address, bytes)) = &event.data {
+                self.poke_bytes(*address, bytes)?;
+            }
+            return Ok(event.result.map_or(SyscallResult::Done, SyscallResult::Return));
+        }
+        log.begin();
+        let result = self.dispatch_syscall(syscall_number, args, instruction)?;
+        if let Some(log) = &mut self.replay {
+            log.commit(syscall_number, match result {
+                SyscallResult::Return(value) => Some(value),
+                _ => None,
+            });
+        }
+        Ok(result)
+    }
+
+    /// 設定したハンドラでシステムコールを実行する
+    fn dispatch_syscall(&mut self, syscall_number: Word, args: [Word; 3], instruction: Instruction) -> Result<SyscallResult, ProcessorError> {
+        // ハンドラがプロセッサを借用できるよう、実行中は取り出しておく
+        let mut handler = std::mem::replace(&mut self.syscall_handler, Box::new(SpimSyscalls));
+        let result = handler.handle(syscall_number, &mut SyscallContext::new(self, args, instruction));
+        self.syscall_handler = handler;
+        result
+    }
+
+    /// 組み込みのSPIM互換のシステムコール（SpimSyscalls の処理）
+    pub fn builtin_syscall(&mut self, syscall_number: Word, args: [Word; 3], instruction: Instruction) -> Result<SyscallResult, ProcessorError> {
+        let arg = args[0];
+        match syscall_number {
+            1 => {
+                // print_int: 引数を整数として出力
+                self.write_output(format!("{}\n", arg as i32).as_bytes());
+            }
+            4 => {
+                // print_string: 引数のアドレスから文字列を出力
+                self.print_string(arg)?;
+            }
+            10 => {
+                // exit: プログラム終了
+                println!("プログラムが終了しました");
+                return Ok(SyscallResult::Halt { code: 0 });
+            }
+            17 => {
+                // exit2: 引数を終了コードとしてプログラム終了
+                println!("プログラムが終了しました (終了コード: {})", arg as i32);
+                return Ok(SyscallResult::Halt { code: arg as i32 });
+            }
+            11 => {
+                // print_char: 引数を文字として出力
+                self.write_output(&[arg as u8]);
+            }
+            5 => {
+                // read_int: 1行を整数として読む（入力の終わりや整数でなければ0）
+                return Ok(SyscallResult::Return(self.input.read_int().unwrap_or(0) as Word));
+            }
+            8 => {
+                // read_string: 引数のアドレスに最大(第2引数 - 1)バイトを改行まで読み、NUL終端する
+                self.read_string(arg, args[1])?;
+            }
+            9 => {
+                // sbrk: ヒープを引数のバイト数だけ伸ばし、元の終端を返す（伸ばせなければ-1）
+                return Ok(SyscallResult::Return(self.sbrk(arg as i32).unwrap_or(Word::MAX)));
+            }
+            13 => {
+                // open: 第1引数のファイル名を第2引数のフラグで開き、ファイル記述子を返す
+                let name = self.peek_c_string(arg)?;
+                let result = self.files.open(&name, args[1]);
+                return Ok(SyscallResult::Return(self.file_result(result)));
+            }
+            14 => {
+                // read: 第1引数のファイルから第2引数のアドレスへ最大第3引数バイト読み、読んだバイト数を返す
+                let result = match arg {
+                    0 => Ok(self.input.read_line(args[2] as usize)),
+                    fd => self.files.read(fd, args[2] as usize),
+                };
+                let result = match result {
+                    Ok(bytes) => {
+                        self.poke_bytes(args[1], &bytes)?;
+                        Ok(bytes.len() as Word)
+                    }
+                    Err(e) => Err(e),
+                };
+                return Ok(SyscallResult::Return(self.file_result(result)));
+            }
+            15 => {
+                // write: 第2引数のアドレスから第3引数バイトを第1引数のファイルへ書き、書いたバイト数を返す
+                let bytes = self.peek_range(args[1], args[2] as usize)?;
+                let result = match arg {
+                    1 => {
+                        self.write_output(&bytes);
+                        Ok(())
+                    }
+                    2 => std::io::stderr().write_all(&bytes).map_err(|e| e.to_string()),
+                    fd => self.files.write(fd, &bytes),
+                };
+                return Ok(SyscallResult::Return(self.file_result(result.map(|_| args[2]))));
+            }
+            16 => {
+                // close: 第1引数のファイルを閉じ、0を返す
+                let result = self.files.close(arg);
+                return Ok(SyscallResult::Return(self.file_result(result.map(|_| 0))));
+            }
+            12 => {
+                // read_char: 1バイト読む（入力の終わりなら-1）
+                return Ok(SyscallResult::Return(self.input.read_byte().map_or(Word::MAX, Word::from)));
+            }
+            SYSCALL_TRACE_ON | SYSCALL_TRACE_OFF => {
+                // トレースの表示をゲスト自身が関心のある区間だけに絞る
+                self.trace_enabled = syscall_number == SYSCALL_TRACE_ON;
+            }
+            SYSCALL_STATS_ON | SYSCALL_STATS_OFF => {
+                // この命令を数え終えた後に、キャッシュやタイミングモデルと一緒に切り替える（execute_step）
+                self.stats_enabled = syscall_number == SYSCALL_STATS_ON;
+            }
+            SYSCALL_CACHE_FLUSH => {
+                // cache_flush: DMAや自己書き換えコードの前に、実機と同じように書き戻しを強制する
+                self.cache.flush(&mut self.memory)?;
+            }
+            SYSCALL_CACHE_INVALIDATE => {
+                // cache_invalidate: DMAで書き換わったメモリを読み直すために、ラインを書き戻さずに捨てる
+                self.cache.invalidate();
+            }
+            SYSCALL_GETENV => {
+                // getenv: 値の文字列のアドレス（見つからなければ0）
+                let address = self.lookup_env(arg)?.map_or(0, |(address, _)| address);
+                return Ok(SyscallResult::Return(address));
+            }
+            SYSCALL_GETENV_INT => {
+                // getenv_int: 値を整数として解釈する（見つからないか整数でなければ第2引数）
+                let value = match self.lookup_env(arg)? {
+                    Some((_, value)) => std::str::from_utf8(&value).ok()
+                        .and_then(|text| crate::assembler::parse_integer(text).ok()),
+                    None => None,
+                };
+                return Ok(SyscallResult::Return(value.map_or(args[1], |value| value as Word)));
+            }
+            // 例外を受け付ける場合、シミュレータが扱わないシステムコールはゲストのハンドラに任せる
+            _ if self.exceptions_enabled() => {
+                return Err(ProcessorError::SyscallException { number: syscall_number });
+            }
+            _ => {
+                println!("未対応のシステムコール: {}", syscall_number);
+                return Err(self.invalid_instruction(instruction));
+            }
+        }
+        Ok(SyscallResult::Done)
+    }
+
+    /// ゲスト向け設定ブロックをメモリに配置する
+    pub fn load_env_block(&mut self, address: MemoryAddress, block: &[u8]) -> Result<(), MemoryError> {
+        for (i, byte) in block.iter().enumerate() {
+            self.cache.poke_byte(&mut self.memory, address.wrapping_add(i as u32), *byte)?;
+        }
+        self.env_block = Some((address, block.len()));
         Ok(())
     }
 
+    /// 設定ブロックからキー（key_addressのNUL終端文字列）を探し、値のアドレスと内容を返す
+    fn lookup_env(&self, key_address: MemoryAddress) -> Result<Option<(MemoryAddress, Vec<u8>)>, MemoryError> {
+        let (address, size) = match self.env_block {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let key = self.peek_c_string(key_address)?;
+        let block = self.peek_range(address, size)?;
+        Ok(guest_env::find_value(&block, &key)
+            .map(|offset| (address + offset as MemoryAddress, guest_env::value_at(&block, offset).to_vec())))
+    }
+
+    /// NUL終端文字列を読み込む（キャッシュの状態や統計を変更しない）
+    fn peek_c_string(&self, address: MemoryAddress) -> Result<Vec<u8>, MemoryError> {
+        let mut bytes = Vec::new();
+        let mut current = address;
+        loop {
+            let byte = self.cache.peek_byte(&self.memory, current)?;
+            if byte == 0 {
+                return Ok(bytes);
+            }
+            bytes.push(byte);
+            current = current.wrapping_add(1);
+        }
+    }
+
+    /// RV32I命令を実行
+    pub fn execute_riscv(&mut self, instruction: Instruction) -> Result<StepSignal, ProcessorError> {
+        match RvInstruction::decode(instruction) {
+            RvInstruction::Lui { rd, imm } => {
+                self.set_register(rd, imm);
+            }
+
+            RvInstruction::Auipc { rd, imm } => {
+                self.set_register(rd, self.pc.wrapping_add(imm));
+            }
+
+            RvInstruction::Jal { rd, imm } => {
+                let return_address = self.pc.wrapping_add(4);
+                self.pc = self.pc.wrapping_add(imm as u32);
+                self.set_register(rd, return_address);
+                self.count(|stats| stats.branches_taken += 1);
+                return Ok(StepSignal::Branch); // 分岐が発生
+            }
+
+            RvInstruction::Jalr { rd, rs1, imm } => {
+                // rdとrs1が同じ場合に備えて先に飛び先を計算する
+                let target = self.get_register(rs1).wrapping_add(imm as u32) & !1;
+                let return_address = self.pc.wrapping_add(4);
+                self.pc = target;
+                self.set_register(rd, return_address);
+                self.count(|stats| stats.branches_taken += 1);
+                return Ok(StepSignal::Branch); // 分岐が発生
+            }
+
+            RvInstruction::Branch { cond, rs1, rs2, imm } => {
+                if cond.test(self.get_register(rs1), self.get_register(rs2)) {
+                    self.pc = self.pc.wrapping_add(imm as u32);
+                    self.count(|stats| stats.branches_taken += 1);
+                    return Ok(StepSignal::Branch); // 分岐が発生
+                }
+            }
+
+            RvInstruction::Load { width, rd, rs1, imm } => {
+                let address = self.get_register(rs1).wrapping_add(imm as u32);
+                let value = self.read_sized(address, width.bytes())?;
+                self.trace_memory(address, width.bytes(), false, value, rs1);
+                self.set_register(rd, width.extend(value));
+                self.count(|stats| stats.loads_executed += 1);
+            }
+
+            RvInstruction::Store { width, rs1, rs2, imm } => {
+                let address = self.get_register(rs1).wrapping_add(imm as u32);
+                let value = self.get_register(rs2);
+                self.write_sized(address, value, width.bytes())?;
+                self.trace_memory(address, width.bytes(), true, value & (Word::MAX >> (32 - width.bytes() * 8)), rs1);
+                self.count(|stats| stats.stores_executed += 1);
+            }
+
+            RvInstruction::OpImm { op, rd, rs1, imm } => {
+                self.set_register(rd, op.apply(self.get_register(rs1), imm as Word));
+            }
+
+            RvInstruction::Op { op, rd, rs1, rs2 } => {
+                self.set_register(rd, op.apply(self.get_register(rs1), self.get_register(rs2)));
+            }
+
+            RvInstruction::Fence => {
+                // 単一コアでメモリアクセスは逐次的なので何もしない
+            }
+
+            RvInstruction::Ecall => {
+                // a7レジスタからシステムコール番号、a0〜a2レジスタから引数を取得し、戻り値はa0に返す
+                let syscall_number = self.get_register(riscv::SYSCALL_NUMBER_REGISTER);
+                let args = riscv::SYSCALL_ARG_REGISTERS.map(|reg| self.get_register(reg));
+                match self.syscall(syscall_number, args, instruction)? {
+                    SyscallResult::Return(result) => self.set_register(riscv::SYSCALL_RESULT_REGISTER, result),
+                    SyscallResult::Halt { code } => return Ok(StepSignal::Halt { code }),
+                    SyscallResult::Done => {}
+                }
+            }
+
+            RvInstruction::Ebreak => {
+                return Err(ProcessorError::Break { code: 0 });
+            }
+
+            RvInstruction::Invalid => {
+                return Err(self.invalid_instruction(instruction));
+            }
+        }
+
+        Ok(StepSignal::Continue)
+    }
+
+    /// 実行中の命令による address から bytes バイトへのアクセスが保護属性で許可されているか確かめる
+    fn check_protection(&self, address: MemoryAddress, bytes: u32, access: Access) -> Result<(), MemoryError> {
+        self.memory.check_access(address, bytes, access, self.pc)
+    }
+
+    /// 実行中の命令のアクセスを、有効にしていればメモリアクセスのトレースとヒートマップに記録する
+    fn trace_memory(&mut self, address: MemoryAddress, bytes: u32, write: bool, value: Word, base: Register) {
+        if let Some(trace) = &mut self.mem_trace {
+            trace.record(self.pc, address, bytes, write, value, base);
+        }
+        if let Some(heatmap) = self.heatmap.as_mut().filter(|_| self.stats_enabled) {
+            heatmap.record(address, write);
+        }
+        if let Some(footprint) = self.footprint.as_mut().filter(|_| self.stats_enabled) {
+            footprint.record(address, bytes, write);
+        }
+        if !write && bytes == 4 {
+            self.check_poisoned_read(address, value);
+        }
+        if !write && !self.memory_watchpoints.is_empty() {
+            for index in self.matching_memory_watchpoints(address, bytes, false) {
+                self.report_memory_watch(index, address, false, value, value);
+            }
+        }
+    }
+
+    /// 読み込んだワードが埋めたパターンのままなら、未初期化のメモリの読み込みとして警告する（命令ごとに最初の1回だけ）
+    fn check_poisoned_read(&mut self, address: MemoryAddress, value: Word) {
+        let pc = self.pc;
+        match &self.poisoned_reads {
+            Some(check) if check.pattern == value && !check.reads.iter().any(|&(read_pc, _)| read_pc == pc) => {}
+            _ => return,
+        }
+        println!("警告: 未初期化のメモリを読み込んだ可能性があります: PC={} アドレス 0x{:08X} (値 0x{:08X})",
+            self.symbols.format_address(pc), address, value);
+        if let Some(check) = &mut self.poisoned_reads {
+            check.reads.push((pc, address));
+        }
+    }
+
+    /// 指定バイト数（1, 2, 4）をキャッシュ経由で読み込む（リトルエンディアン）
+    /// 範囲外のエラーにはロードのバイト数と実行中の命令のアドレスを加える
+    fn read_sized(&mut self, address: MemoryAddress, bytes: u32) -> Result<Word, MemoryError> {
+        self.check_protection(address, bytes, Access::Read)?;
+        let pc = self.pc;
+        let in_access = |e: MemoryError| e.in_access(Access::Read, bytes, pc);
+        if bytes == 4 {
+            return self.cache.read_word(&mut self.memory, address).map_err(in_access);
+        }
+        let mut value = 0;
+        for i in 0..bytes {
+            value |= (self.cache.read_byte(&mut self.memory, address.wrapping_add(i)).map_err(in_access)? as Word) << (i * 8);
+        }
+        Ok(value)
+    }
+
+    /// 指定バイト数（1, 2, 4）をキャッシュ経由で書き込む（リトルエンディアン）
+    /// 範囲外のエラーにはストアのバイト数と実行中の命令のアドレスを加える
+    fn write_sized(&mut self, address: MemoryAddress, value: Word, bytes: u32) -> Result<(), MemoryError> {
+        self.check_protection(address, bytes, Access::Write)?;
+        self.log_memory_write(address, bytes as usize);
+        if !self.memory_watchpoints.is_empty() {
+            self.watch_memory_write(address, &value.to_le_bytes()[..bytes as usize]);
+        }
+        let pc = self.pc;
+        let in_access = |e: MemoryError| e.in_access(Access::Write, bytes, pc);
+        if bytes == 4 {
+            return self.cache.write_word(&mut self.memory, address, value).map_err(in_access);
+        }
+        for i in 0..bytes {
+            self.cache.write_byte(&mut self.memory, address.wrapping_add(i), (value >> (i * 8)) as u8).map_err(in_access)?;
+        }
+        Ok(())
+    }
+
+    /// 1命令を実行（フェッチ + 実行）
+    pub fn step(&mut self) -> Result<StepSignal, ProcessorError> {
+        if self.watchpoints.is_empty() && self.memory_watchpoints.is_empty() {
+            return self.step_with_undo_log();
+        }
+        let pc = self.pc;
+        let watched: Vec<Word> = self.watchpoints.iter().map(|watchpoint| self.operand_value(watchpoint.register)).collect();
+        self.memory_watch_hit = None;
+        let signal = self.step_with_undo_log();
+        let memory_hit = self.memory_watch_hit.take();
+        let signal = signal?;
+        // 命令は完了しているので、止まった後もそのまま続きを実行できる
+        for (watchpoint, &old) in self.watchpoints.iter().zip(&watched) {
+            let new = self.operand_value(watchpoint.register);
+            if watchpoint.triggered(old, new) {
+                return Err(ProcessorError::Watchpoint { pc, watchpoint: watchpoint.to_string(), old, new });
+            }
+        }
+        match memory_hit {
+            Some(hit) => Err(hit),
+            None => Ok(signal),
+        }
+    }
+
+    /// 1命令を実行し、アンドゥログを有効にしていれば書き換えた状態を記録する
+    fn step_with_undo_log(&mut self) -> Result<StepSignal, ProcessorError> {
+        let Some(undo_log) = &mut self.undo_log else {
+            return self.execute_step();
+        };
+        undo_log.begin(UndoRecord {
+            pc: self.pc,
+            registers: Vec::new(),
+            hi_lo: (self.hi, self.lo),
+            fcsr: self.fcsr,
+            cp0: self.cp0.clone(),
+            heap_break: self.heap_break,
+            memory: Vec::new(),
+        });
+        let (registers, fp_registers) = (self.registers, self.fp_registers);
+        let result = self.execute_step();
+        // 書き換えたレジスタの元の値を、PipelineOpの番号付けで記録する
+        let changed = (0..REGISTER_COUNT).filter(|&i| registers[i] != self.registers[i])
+            .map(|i| (i as u8, registers[i]))
+            .chain((0..FP_REGISTER_COUNT).filter(|&i| fp_registers[i] != self.fp_registers[i])
+                .map(|i| (FP_REGISTER_BASE + i as u8, fp_registers[i])))
+            .collect();
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.commit(changed);
+        }
+        result
+    }
+
+    /// 1命令を実行する（アンドゥログの記録を除く本体）
+    fn execute_step(&mut self) -> Result<StepSignal, ProcessorError> {
+        self.take_interrupt();
+        let pc = self.pc;
+        let (misses_before_fetch, writebacks_before_fetch) = self.cache.wait_counts();
+        let fetched = if self.exceptions_enabled() && !pc.is_multiple_of(4) {
+            Err(ProcessorError::AddressError { address: pc, store: false })
+        } else {
+            self.fetch_instruction().map_err(ProcessorError::MemoryError)
+        };
+        let instruction = match fetched {
+            Ok(instruction) => instruction,
+            Err(error) => {
+                self.take_exception(pc, error, false).map_err(|e| {
+                    println!("命令フェッチエラー: PC={}, エラー={}", self.symbols.format_address(self.pc), e);
+                    if let Some(backtrace) = self.backtrace(pc) {
+                        println!("{}", backtrace);
+                    }
+                    e
+                })?;
+                return Ok(StepSignal::Branch);
+            }
+        };
+        
+        // シンボルがあれば、素の.hexでもどの関数を実行しているかを示す
+        self.trace(|processor| match processor.symbols.label(pc) {
+            Some(label) => format!("実行: 0x{:08X} ({}) <{}>", instruction, processor.disassemble_at(instruction, pc), label),
+            None => format!("実行: 0x{:08X} ({})", instruction, processor.disassemble_at(instruction, pc)),
+        });
+        if self.uninitialized.is_some() {
+            self.check_uninitialized_reads(instruction, pc);
+        }
+        let (category, mnemonic) = self.category_and_mnemonic(instruction);
+        // exitやbreakのように実行が止まる命令も到達したものとして記録する
+        if self.stats_enabled {
+            if self.coverage_enabled {
+                self.coverage.record(pc, mnemonic);
+            }
+            if let Some(profiler) = &mut self.profiler {
+                profiler.record(pc, instruction);
+            }
+        }
+        let (misses_before_execute, writebacks_before_execute) = self.cache.wait_counts();
+        let op = match self.isa {
+            Isa::Mips => PipelineOp::from_mips(&InstructionType::decode(instruction)),
+            Isa::Rv32i => PipelineOp::from_riscv(&RvInstruction::decode(instruction)),
+        };
+        if self.history.is_some() {
+            let sources = op.sources.iter().map(|&reg| (reg, self.operand_value(reg))).collect();
+            if let Some(history) = &mut self.history {
+                history.push(HistoryEntry { pc, instruction, sources, dest: None });
+            }
+        }
+        // 無限ループの検出のため、実行前のレジスタを覚えておく
+        let registers_before = self.loop_detector.as_ref()
+            .map(|_| (self.registers, self.fp_registers, self.hi, self.lo, self.fcsr));
+        
+        let stats_enabled = self.stats_enabled;
+        let result = match self.isa {
+            Isa::Mips => self.execute_instruction(instruction),
+            Isa::Rv32i => self.execute_riscv(instruction),
+        };
+        // stats_on / stats_off による切り替えは、この命令を実行し始めたときの状態で数え終えてから反映する
+        let stats_requested = std::mem::replace(&mut self.stats_enabled, stats_enabled);
+        let completed = result.is_ok();
+        if completed && self.history.is_some() {
+            let dest = op.dest.map(|reg| (reg, self.operand_value(reg)));
+            if let Some(history) = &mut self.history {
+                history.set_last_dest(dest);
+            }
+        }
+        // 例外を受け付けた命令は完了せず、ハンドラへの分岐として扱う
+        let signal = match result {
+            Ok(signal) => {
+                self.count(|stats| {
+                    stats.record_mix(category, mnemonic);
+                    stats.instructions_executed += 1;
+                });
+                if let Some(usage) = self.register_usage.as_mut().filter(|_| self.stats_enabled) {
+                    usage.record(&op);
+                }
+                if self.footprint.is_some() && self.stats_enabled {
+                    let sp = self.get_register(self.sp_register());
+                    if let Some(footprint) = &mut self.footprint {
+                        footprint.observe_sp(sp);
+                    }
+                }
+                signal
+            }
+            Err(error) => {
+                let store = self.is_store(instruction);
+                self.take_exception(pc, error, store).map_err(|e| {
+                    println!("命令実行エラー: PC=0x{:08X}, エラー={}", self.pc, e);
+                    if let Some(backtrace) = self.backtrace(pc) {
+                        println!("{}", backtrace);
+                    }
+                    e
+                })?;
+                StepSignal::Branch
+            }
+        };
+        let branch_taken = signal == StepSignal::Branch;
+        
+        // 分岐が発生しなかった場合のみPCを4進める
+        if branch_taken {
+            self.count(|stats| stats.redirections += 1);
+        } else {
+            self.pc = self.pc.wrapping_add(4);
+        }
+
+        // キャッシュミスとダーティラインの書き戻しの回数から、メモリを待つサイクル数を見積もる
+        let (misses_after_execute, writebacks_after_execute) = self.cache.wait_counts();
+        let fetch_misses = misses_before_execute - misses_before_fetch;
+        let data_misses = misses_after_execute - misses_before_execute;
+        let fetch_writebacks = writebacks_before_execute - writebacks_before_fetch;
+        let data_writebacks = writebacks_after_execute - writebacks_before_execute;
+        let data_cause = if op.is_store { StallCause::Store } else { StallCause::Load };
+        let (penalty, writeback_latency) = (self.timing_config.miss_penalty(), self.timing_config.writeback_latency);
+        let fetch_stall = fetch_misses * penalty + fetch_writebacks * writeback_latency;
+        let data_stall = data_misses * penalty + data_writebacks * writeback_latency;
+        // 条件分岐は予測器に問い合わせ、予測が外れたかを求める（予測器がなければ判定しない）
+        let mispredicted = if self.predictors.is_empty() {
+            None
+        } else {
+            self.conditional_branch_target(instruction, pc).map(|target| self.predictors.resolve(pc, target, branch_taken))
+        };
+        let timing = match self.timing_config.model {
+            TimingModel::Simple => {
+                // 演算器のレイテンシに、ロード・ストアならキャッシュアクセスのレイテンシを加えたものを基本サイクル数とする
+                let mut base = self.timing_config.execute_latency(op.is_mul_div);
+                if op.is_load || op.is_store {
+                    base += self.timing_config.cache_hit_latency;
+                }
+                let mut timing = InstructionTiming { base, stalls: Vec::new() };
+                for (cause, stall) in [(StallCause::Fetch, fetch_stall), (data_cause, data_stall)] {
+                    if stall > 0 {
+                        timing.stalls.push((cause, stall));
+                    }
+                }
+                if mispredicted == Some(true) {
+                    timing.stalls.push((StallCause::BranchMispredict, self.timing_config.mispredict_penalty));
+                }
+                timing
+            }
+            TimingModel::Pipeline | TimingModel::DualIssue => {
+                // 予測器がなければ不成立と予測したものとして、成立した場合に後続を捨てる
+                let flush = mispredicted.unwrap_or(branch_taken);
+                self.pipeline.advance(&op, flush, fetch_stall, data_stall, data_cause)
+            }
+            TimingModel::OutOfOrder => {
+                let flush = mispredicted.unwrap_or(branch_taken);
+                self.ooo.advance(&op, flush, fetch_stall, data_stall, data_cause)
+            }
+        };
+        if let Some(trace) = &mut self.cache_trace {
+            trace.write(self.stats.cycles, pc, &self.cache.take_trace());
+        }
+        if self.stats_enabled {
+            self.stats.cycles += timing.total();
+            self.cache.advance_bus(timing.total());
+            if self.timing_enabled {
+                self.timing.record(pc, instruction, &timing);
+            }
+        }
+        if self.call_stack.is_some() {
+            // 完了した呼び出し・復帰を、バックトレースと関数プロファイラで共有するスタックに反映する
+            let event = if completed { self.call_event(instruction, pc) } else { None };
+            if let Some(call_stack) = &mut self.call_stack {
+                match &mut self.function_profiler {
+                    Some(profiler) if self.stats_enabled => profiler.record(pc, self.pc, timing.total(), event, call_stack),
+                    profiler => {
+                        call_stack.update(event, self.pc, profiler.as_ref().map_or((0, 0), |profiler| profiler.counts()));
+                    }
+                }
+            }
+        }
+        self.cp0.advance(timing.total());
+        if stats_requested != stats_enabled {
+            self.set_stats_enabled(stats_requested);
+        }
+
+        if let Some(before) = registers_before {
+            // 割り込みを待つループや、メモリ・入出力・コプロセッサ0に作用する命令は状態を変えたものとして扱う
+            let changed_state = !completed
+                || before != (self.registers, self.fp_registers, self.hi, self.lo, self.fcsr)
+                || matches!(category,
+                    InstructionCategory::Store | InstructionCategory::Syscall | InstructionCategory::Other)
+                || self.cp0.interrupts_enabled();
+            if self.loop_detector.as_mut().is_some_and(|detector| detector.record(pc, changed_state)) {
+                return Err(self.infinite_loop_error());
+            }
+        }
+        
+        Ok(signal)
+    }
+
+    /// 条件分岐命令であれば、成立した場合の飛び先を返す
+    fn conditional_branch_target(&self, instruction: Instruction, pc: MemoryAddress) -> Option<MemoryAddress> {
+        match self.isa {
+            Isa::Mips => match InstructionType::decode(instruction) {
+                InstructionType::Beq { imm, .. } | InstructionType::Bne { imm, .. } => Some(instructions::branch_target(pc, imm)),
+                _ => None,
+            },
+            Isa::Rv32i => match RvInstruction::decode(instruction) {
+                RvInstruction::Branch { imm, .. } => Some(pc.wrapping_add(imm as u32)),
+                _ => None,
+            },
+        }
+    }
+
+    /// 倍精度命令のレジスタが偶数番号（レジスタ対の先頭）であることを確認する
+    #[cfg(feature = "double")]
+    fn check_fp_pairs(&self, instruction: Instruction, registers: &[Register]) -> Result<(), ProcessorError> {
+        if registers.iter().any(|reg| reg % 2 != 0) {
+            return Err(self.invalid_instruction(instruction));
+        }
+        Ok(())
+    }
+
+    /// 現在のPCで実行しようとした無効な命令のエラーを作成する
+    fn invalid_instruction(&self, instruction: Instruction) -> ProcessorError {
+        let fields = match self.isa {
+            Isa::Mips => crate::instructions::instruction_fields(instruction),
+            Isa::Rv32i => riscv::instruction_fields(instruction),
+        };
+        let unimplemented = match self.isa {
+            Isa::Mips => decoder::table().unimplemented_name(instruction),
+            Isa::Rv32i => None,
+        };
+        ProcessorError::InvalidInstruction(InvalidInstructionInfo {
+            pc: self.pc,
+            instruction,
+            isa: self.isa,
+            fields,
+            preceding_valid: self.find_preceding_valid(self.pc),
+            unimplemented,
+        })
+    }
+
+    /// addressより前にある最も近い有効な命令を探す（アドレス, 命令, 逆アセンブル結果）
+    /// 0のワードは未初期化の領域であることが多いため対象にしない
+    fn find_preceding_valid(&self, address: MemoryAddress) -> Option<(MemoryAddress, Instruction, String)> {
+        (1..=PRECEDING_SEARCH_LIMIT)
+            .map_while(|i| address.checked_sub(i * 4))
+            .filter_map(|candidate| self.peek_word(candidate).ok().map(|word| (candidate, word)))
+            .find(|&(_, word)| word != 0 && self.mnemonic(word) != "invalid")
+            .map(|(candidate, word)| (candidate, word, self.disassemble_at(word, candidate)))
+    }
+
+    /// 実行時間の上限を設定（Noneで無制限）
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// 実行する命令数の上限を設定（Noneで無制限）
+    pub fn set_max_instructions(&mut self, max_instructions: Option<u64>) {
+        self.max_instructions = max_instructions;
+    }
+
+    /// プログラムを実行し、終了理由を返す
+    pub fn run(&mut self) -> RunResult {
+        let start_time = Instant::now();
+        let mut instruction_count: u64 = 0;
+        loop {
+            // 時刻の取得は重いので一定命令数ごとに確認する
+            if let Some(timeout) = self.timeout {
+                if instruction_count.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && start_time.elapsed() >= timeout {
+                    return RunResult::Timeout { elapsed: start_time.elapsed(), instructions: instruction_count };
+                }
+            }
+            
+            // デバッグ出力
+            if instruction_count < 10 && self.pc != self.halt_address {
+                self.trace(|processor| format!("命令 {}: PC=0x{:08X}", instruction_count, processor.pc));
+            }
+
+            // 上限の命令数を実行しても終わらなければ強制終了
+            if let Some(limit) = self.max_instructions {
+                if instruction_count >= limit {
+                    println!("警告: {}命令を超えました。強制終了します。", limit);
+                    return RunResult::InstructionLimit { limit };
+                }
+            }
+
+            if let Some(outcome) = self.run_one() {
+                return outcome;
+            }
+            instruction_count += 1;
+        }
+    }
+
+    /// 1命令を実行し、プログラムが終了した場合はその理由を返す
+    pub fn run_one(&mut self) -> Option<RunResult> {
+        // 停止アドレスに到達した場合は終了（フェッチせずに正常終了として扱う）
+        if self.pc == self.halt_address {
+            return Some(RunResult::Halted { pc: self.pc });
+        }
+
+        // 終了理由に実際に実行した命令のアドレスを記録できるよう、割り込みは先に受け付ける
+        self.take_interrupt();
+        let pc = self.pc;
+        match self.step() {
+            Ok(StepSignal::Branch) => {
+                self.trace(|processor| format!("分岐が発生: PC=0x{:08X}", processor.pc));
+                None
+            }
+            // 通常の命令の場合は次のループで続行
+            Ok(StepSignal::Continue) => None,
+            Ok(StepSignal::Halt { code }) => {
+                println!("システムコールで終了\n\n");
+                if self.isa == Isa::Mips {
+                    println!("=== 計算結果 ===\n");
+                    println!("{}", self.get_register(4));
+                }
+                Some(RunResult::Exited { code })
+            }
+            Err(e) => {
+                let outcome = RunResult::from_error(pc, e);
+                if let (RunResult::Exception { .. }, Some(report)) = (&outcome, self.history_report()) {
+                    println!("{}", report);
+                }
+                Some(outcome)
+            }
+        }
+    }
+
     /// メモリにプログラムをロード
     pub fn load_program(&mut self, program: &[Instruction], start_address: MemoryAddress) -> Result<(), MemoryError> {
         println!("プログラムをロード中: {} 命令", program.len());
@@ -359,101 +2127,966 @@ impl Processor {
             println!("命令 {}: 0x{:08X} をアドレス 0x{:08X} に書き込み", i, instruction, address);
             self.memory.write_instruction(address, *instruction)?;
         }
-        self.pc = start_address;
+        self.coverage.set_program(start_address, program);
+        self.finish_load(start_address as u64 + (program.len() * 4) as u64, start_address);
+        Ok(())
+    }
+
+    /// アドレスの付いたセグメントからなるプログラム（ELFなど）をロードし、PCをentryに設定する
+    /// 実行可能なセグメントの命令をカバレッジの対象にする
+    pub fn load_segments(&mut self, segments: &[Segment], entry: MemoryAddress) -> Result<(), MemoryError> {
+        println!("プログラムをロード中: {} セグメント", segments.len());
+        for segment in segments {
+            println!("セグメント: 0x{:08X} - 0x{:08X} ({} バイト{})", segment.address, segment.end(), segment.data.len(),
+                if segment.executable { ", 実行可能" } else { "" });
+            self.memory.write_range(segment.address, &segment.data)?;
+        }
+        let program = segments.iter()
+            .filter(|segment| segment.executable)
+            .flat_map(|segment| loader::words_from_binary(&segment.data).into_iter().enumerate()
+                .map(|(i, word)| (segment.address.wrapping_add((i * 4) as u32), word)))
+            .collect();
+        self.coverage.set_program_words(program);
+        self.finish_load(segments.iter().map(Segment::end).max().unwrap_or(0), entry);
+        Ok(())
+    }
+
+    /// ロードの後始末（以前の実行の記録を消し、ヒープをプログラムの終端の後ろから始め、PCを設定する）
+    fn finish_load(&mut self, program_end: u64, entry: MemoryAddress) {
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.clear();
+        }
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.clear();
+        }
+        self.move_heap_past(program_end);
+        self.skip_adjacent_data();
+        self.pc = entry;
         println!("PCを 0x{:08X} に設定", self.pc);
+    }
+
+    /// ヒープの先頭が読み込んだ内容の終端 end より前なら、end の後ろに動かす
+    fn move_heap_past(&mut self, end: u64) {
+        if end > self.heap_start as u64 && end <= MemoryAddress::MAX as u64 {
+            self.set_heap_start(end as MemoryAddress);
+        }
+    }
+
+    /// ヒープの先頭に重なるか接するデータがあれば、ヒープをその後ろに動かす
+    /// 続けて置いたデータもまとめて飛ばすが、離れた位置（高いアドレスのROMなど）のデータはヒープの先頭に影響しない
+    fn skip_adjacent_data(&mut self) {
+        loop {
+            let heap_start = self.heap_start as u64;
+            let end = self.data_ranges.iter()
+                .filter(|range| range.start <= heap_start && range.end > heap_start)
+                .map(|range| range.end)
+                .max();
+            match end {
+                Some(end) => self.move_heap_past(end),
+                None => return,
+            }
+            if self.heap_start as u64 == heap_start {
+                return;
+            }
+        }
+    }
+
+    /// 他のコアがロードしたプログラムを共有する（メモリは書き換えず、PCと設定ブロックの位置だけを引き継ぐ）
+    pub fn share_program(&mut self, loaded: &Processor) {
+        self.coverage = loaded.coverage.clone();
+        self.pc = loaded.pc;
+        self.env_block = loaded.env_block;
+        self.heap_start = loaded.heap_start;
+        self.heap_break = loaded.heap_break;
+    }
+
+    /// 実行前にデータをメモリに書き込む（プログラムと同じくキャッシュを経由せず、ヒープの先頭に接するデータならヒープはその後ろから始める）
+    pub fn load_data(&mut self, address: MemoryAddress, bytes: &[u8]) -> Result<(), MemoryError> {
+        self.memory.write_range(address, bytes)?;
+        if !bytes.is_empty() {
+            self.data_ranges.push(address as u64..address as u64 + bytes.len() as u64);
+            self.skip_adjacent_data();
+        }
         Ok(())
     }
 
+    /// 領域に保護属性を設定する（既に設定した領域と重なる部分はこちらを優先する）
+    pub fn protect_memory(&mut self, range: Range<MemoryAddress>, protection: Protection) {
+        self.memory.protect(range, protection);
+    }
+
+    /// アドレス範囲にメモリマップドI/Oのデバイスを割り当てる（その範囲へのロード・ストアはキャッシュを経由せずデバイスに渡る）
+    pub fn map_device(&mut self, range: Range<MemoryAddress>, device: Box<dyn MmioDevice>) -> Result<(), MemoryError> {
+        // 範囲にかかるラインが既にキャッシュにあると、後の書き戻しでデバイスに古い値を書き込むので、先に書き戻して捨てる
+        self.cache.flush(&mut self.memory)?;
+        self.cache.invalidate();
+        self.memory.map_device(range, device)
+    }
+
+    /// キャッシュのダーティラインをメモリに書き戻し、ファイルにマップしたメモリならファイルに反映する
+    pub fn sync_memory(&mut self) -> Result<(), String> {
+        self.cache.flush(&mut self.memory).map_err(|e| e.to_string())?;
+        self.memory.flush().map_err(|e| e.to_string())
+    }
+
+    /// 他のコアとメモリを交換する（マルチコア実行で共有メモリとスヌープバスを受け渡すために使う）
+    pub fn swap_memory(&mut self, other: &mut Processor) {
+        std::mem::swap(&mut self.memory, &mut other.memory);
+        let bus = self.cache.replace_snoop_bus(None);
+        let bus = other.cache.replace_snoop_bus(bus);
+        self.cache.replace_snoop_bus(bus);
+    }
+
+    /// キャッシュを他のコアとMESIプロトコルでコヒーレントに保つ（スヌープバスは共有メモリを持つコアに渡す）
+    pub fn set_coherence(&mut self, core_id: usize, bus: Option<SnoopBus>) {
+        self.cache.enable_coherence(core_id);
+        self.cache.replace_snoop_bus(bus);
+    }
+
+    /// 他のコアのアクセスでまだ反映していないスヌープをキャッシュに反映する
+    pub fn apply_snoops(&mut self) {
+        self.cache.apply_snoops();
+    }
+
+    /// コア番号を設定する（MIPSでは$k0、RV32Iではtpに入る）
+    pub fn set_core_id(&mut self, core_id: usize) {
+        let register = match self.isa {
+            Isa::Mips => CORE_ID_REGISTER,
+            Isa::Rv32i => riscv::CORE_ID_REGISTER,
+        };
+        self.set_register(register, core_id as Word);
+    }
+
     /// メモリからデータを読み込む
-    #[allow(dead_code)]
     pub fn read_memory(&self, address: MemoryAddress) -> Result<Word, MemoryError> {
         self.memory.read_word(address)
     }
 
     /// メモリにデータを書き込む
-    #[allow(dead_code)]
     pub fn write_memory(&mut self, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
         self.memory.write_word(address, value)
     }
 
+    /// デバッガ用にワードを読み込む（キャッシュの状態や統計を変更しない）
+    pub fn peek_word(&self, address: MemoryAddress) -> Result<Word, MemoryError> {
+        if (address as usize).saturating_add(3) >= self.memory.size() {
+            return Err(MemoryError::AddressOutOfRange(address));
+        }
+        let mut word = 0;
+        for i in 0..4 {
+            word |= (self.cache.peek_byte(&self.memory, address + i)? as Word) << (i * 8);
+        }
+        Ok(word)
+    }
+
+    /// デバッガ用にワードを書き込む（キャッシュの状態や統計を変更しない）
+    pub fn poke_word(&mut self, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
+        if (address as usize).saturating_add(3) >= self.memory.size() {
+            return Err(MemoryError::AddressOutOfRange(address));
+        }
+        for i in 0..4 {
+            self.cache.poke_byte(&mut self.memory, address + i, (value >> (i * 8)) as u8)?;
+        }
+        Ok(())
+    }
+
+    /// デバッガ用に指定範囲のバイト列を取得する（キャッシュ上の書き戻されていない内容も反映する）
+    pub fn peek_range(&self, start: MemoryAddress, length: usize) -> Result<Vec<u8>, MemoryError> {
+        let mut bytes = self.memory.read_range(start, length)?;
+        self.cache.overlay(start, &mut bytes);
+        Ok(bytes)
+    }
+
+    /// 指定範囲 [start, end) からバイト列を検索し、見つかったアドレスを返す
+    pub fn find_bytes(&self, pattern: &[u8], start: MemoryAddress, end: MemoryAddress) -> Result<Vec<MemoryAddress>, MemoryError> {
+        if end < start {
+            return Err(MemoryError::AddressOutOfRange(end));
+        }
+        let bytes = self.peek_range(start, (end - start) as usize)?;
+        Ok(memory::find_pattern(&bytes, pattern).into_iter()
+            .map(|offset| start + offset as MemoryAddress)
+            .collect())
+    }
+
+    /// メモリのサイズを取得
+    pub fn memory_size(&self) -> usize {
+        self.memory.size()
+    }
+
     /// プロセッサの状態をダンプ
     pub fn dump_state(&self) -> String {
+        let mut result = self.register_dump();
+
+        result.push_str(&format!("\n=== コプロセッサ0 ===\n{}\n", self.cp0));
+        result.push_str(&format!("\nヒープ: 0x{:08X} - 0x{:08X} ({} バイト)\n",
+            self.heap_start, self.heap_break, self.heap_break - self.heap_start));
+        
+        result.push_str(&format!("\n=== 統計情報 ===\n{}", self.stats));
+        result.push_str(&format!("\n\n=== 命令ミックス ===\n{}\n", self.stats.mix_report()));
+        result.push_str(&format!("\n=== キャッシュ統計 ===\n構成: {}\n{}", self.cache.config(), self.cache.get_stats()));
+        let regions = &self.cache.config().stats_regions;
+        if !regions.is_empty() {
+            result.push_str(&format!("\n=== アドレス範囲ごとのキャッシュ統計 ===\n{}",
+                cache::format_region_stats(regions, &self.cache.get_stats().regions)));
+        }
+        result.push_str(&format!("\n=== バス統計 ===\n{}", self.cache.get_bus_stats()));
+        if self.timing_enabled {
+            result.push_str(&format!("\n=== 命令タイミング ===\n{}", self.timing));
+        }
+        if self.timing_config.model.is_pipelined() {
+            result.push_str(&format!("\n=== パイプラインのハザード ===\n{}", self.pipeline.hazard_stats()));
+        }
+        if self.timing_config.model == TimingModel::DualIssue {
+            result.push_str(&format!("\n=== 2命令同時発行 ===\n{}\n", self.pipeline.issue_stats()));
+        }
+        if self.timing_config.model.is_pipelined() && self.timing_config.mshrs > 0 {
+            result.push_str(&format!("\n=== ノンブロッキングキャッシュ（MSHR {}） ===\n{}\n", self.timing_config.mshrs, self.pipeline.mshr_stats()));
+        }
+        if self.timing_config.model == TimingModel::OutOfOrder {
+            result.push_str(&format!("\n=== アウトオブオーダー実行 ===\n{}\n", self.ooo.stats()));
+        }
+        if !self.predictors.is_empty() {
+            result.push_str(&format!("\n=== 分岐予測 ===\n{}\n", self.predictors));
+        }
+        
+        result
+    }
+
+    /// PC・HI/LOと整数・浮動小数点レジスタのダンプ（マルチコア実行ではコアごとに表示する）
+    pub fn register_dump(&self) -> String {
         let mut result = String::new();
         
         result.push_str("=== プロセッサ状態 ===\n");
+        result.push_str(&format!("ISA: {}\n", self.isa.name()));
         result.push_str(&format!("PC: 0x{:08X}\n", self.pc));
         result.push_str(&format!("HI: 0x{:08X}\n", self.hi));
         result.push_str(&format!("LO: 0x{:08X}\n", self.lo));
         result.push_str("\n=== レジスタ ===\n");
         
         for i in 0..REGISTER_COUNT {
-            let reg_name = match i {
-                0 => "$zero",
-                1 => "$at",
-                2 => "$v0", 3 => "$v1",
-                4 => "$a0", 5 => "$a1", 6 => "$a2", 7 => "$a3",
-                8 => "$t0", 9 => "$t1", 10 => "$t2", 11 => "$t3",
-                12 => "$t4", 13 => "$t5", 14 => "$t6", 15 => "$t7",
-                16 => "$s0", 17 => "$s1", 18 => "$s2", 19 => "$s3",
-                20 => "$s4", 21 => "$s5", 22 => "$s6", 23 => "$s7",
-                24 => "$t8", 25 => "$t9",
-                26 => "$k0", 27 => "$k1",
-                28 => "$gp", 29 => "$sp", 30 => "$fp", 31 => "$ra",
-                _ => "???",
-            };
+            let reg_name = self.register_name(i as Register);
             
             result.push_str(&format!("{}: 0x{:08X} ({})\n", 
                 reg_name, self.registers[i], self.registers[i] as i32));
         }
         
-        result.push_str(&format!("\n=== 統計情報 ===\n{}", self.stats));
-        result.push_str(&format!("\n=== キャッシュ統計 ===\n{}", self.cache.get_stats()));
-        
+        result.push_str("\n=== 浮動小数点レジスタ ===\n");
+        for i in 0..FP_REGISTER_COUNT {
+            result.push_str(&format!("$f{}: 0x{:08X} ({})\n",
+                i, self.fp_registers[i], f32::from_bits(self.fp_registers[i])));
+        }
+        result.push_str(&format!("FCSR: 0x{:08X}\n", self.fcsr));
+        result.push_str(&format!("FPUモデル: {}\n", self.fpu.name()));
+        result.push_str(&format!("システムコールのハンドラ: {}\n", self.syscall_handler.name()));
         result
     }
 
-    /// 統計情報を取得
-    pub fn get_stats(&self) -> &ProcessorStats {
-        &self.stats
+    /// 実行トレースを表示するかを設定（ゲストはシステムコールで切り替えられる）
+    pub fn set_trace_enabled(&mut self, enabled: bool) {
+        self.trace_enabled = enabled;
+    }
+
+    /// 統計を収集するかを設定（ゲストはシステムコールで切り替えられる）
+    /// 止めている間は統計を更新しない（タイミングモデルやキャッシュの状態は同じように進める）
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats_enabled = enabled;
+        self.cache.set_stats_enabled(enabled);
+        self.pipeline.set_stats_enabled(enabled);
+        self.ooo.set_stats_enabled(enabled);
+        self.predictors.set_stats_enabled(enabled);
+    }
+
+    /// 統計を収集していれば更新する
+    fn count(&mut self, update: impl FnOnce(&mut ProcessorStats)) {
+        if self.stats_enabled {
+            update(&mut self.stats);
+        }
+    }
+
+    /// 命令カバレッジを記録するかを設定（デフォルト: 記録する）
+    pub fn set_coverage_enabled(&mut self, enabled: bool) {
+        self.coverage_enabled = enabled;
+    }
+
+    /// 命令タイミングのヒストグラムを記録するかを設定（デフォルト: 記録する）
+    pub fn set_timing_enabled(&mut self, enabled: bool) {
+        self.timing_enabled = enabled;
+    }
+
+    /// 統計情報を取得
+    pub fn get_stats(&self) -> &ProcessorStats {
+        &self.stats
+    }
+
+    /// キャッシュ統計を取得
+    pub fn get_cache_stats(&self) -> &CacheStats {
+        self.cache.get_stats()
+    }
+
+    /// セットの範囲に含まれるキャッシュラインの状態を取得
+    pub fn dump_cache(&self, sets: Range<usize>) -> Vec<CacheLineInfo> {
+        self.cache.dump(sets)
+    }
+
+    /// マイクロアーキテクチャ状態を保存する
+    pub fn save_microarch_state(&self) -> MicroarchSnapshot {
+        MicroarchSnapshot { cache: self.cache.snapshot(), predictors: self.predictors.snapshot() }
+    }
+
+    /// マイクロアーキテクチャ状態を復元する（アーキテクチャ状態と統計は変わらない）
+    pub fn restore_microarch_state(&mut self, snapshot: &MicroarchSnapshot) -> Result<(), MemoryError> {
+        self.cache.restore(&mut self.memory, &snapshot.cache)?;
+        self.predictors.restore(&snapshot.predictors);
+        Ok(())
+    }
+
+    /// アーキテクチャ状態（PC、レジスタ、HI/LO、FCSR、コプロセッサ0、ヒープ、メモリ）と
+    /// キャッシュと分岐予測器の状態、実行統計・キャッシュ統計をスナップショットとして書き出す
+    /// メモリはキャッシュ上の書き戻されていない内容を反映した値を、0だけのページを省いてページごとに保存する
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new();
+        writer.put_str(self.isa.name());
+        writer.put_u32(self.pc);
+        for &value in self.registers.iter().chain(&self.fp_registers) {
+            writer.put_u32(value);
+        }
+        for value in [self.hi, self.lo, self.fcsr] {
+            writer.put_u32(value);
+        }
+        let cp0 = &self.cp0;
+        for value in [cp0.bad_vaddr, cp0.count, cp0.compare, cp0.status, cp0.cause, cp0.epc] {
+            writer.put_u32(value);
+        }
+        writer.put_u32(self.heap_start);
+        writer.put_u32(self.heap_break);
+        let stats = &self.stats;
+        for value in [stats.instructions_executed, stats.branches_taken, stats.redirections, stats.loads_executed,
+                      stats.stores_executed, stats.cycles, stats.exceptions, stats.interrupts] {
+            writer.put_u64(value);
+        }
+        let cache_stats = self.cache.get_stats();
+        for value in [cache_stats.hits, cache_stats.misses, cache_stats.writebacks, cache_stats.write_throughs,
+                      cache_stats.compulsory_misses, cache_stats.capacity_misses, cache_stats.conflict_misses,
+                      cache_stats.fetch_hits, cache_stats.fetch_misses] {
+            writer.put_u64(value);
+        }
+        self.cache.snapshot().encode(&mut writer);
+        self.predictors.snapshot().encode(&mut writer);
+        writer.put_memory(self.memory.size(), |start, length| {
+            self.peek_range(start as MemoryAddress, length).expect("メモリ内のページは読み込める")
+        });
+        writer.into_bytes()
+    }
+
+    /// save_snapshotで書き出した状態に戻す（内容がすべて正しいときだけ状態を変える）
+    /// 命令ミックスやタイミングなどの詳細な統計、ゲストプログラムの入出力の状態は戻さない
+    pub fn restore_snapshot(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        let mut reader = SnapshotReader::new(bytes)?;
+        let isa = reader.string()?;
+        if isa != self.isa.name() {
+            return Err(SnapshotError::Mismatch(format!("命令セット {}（現在は {}）", isa, self.isa.name())));
+        }
+        let pc = reader.u32()?;
+        let mut registers = [0; REGISTER_COUNT];
+        let mut fp_registers = [0; FP_REGISTER_COUNT];
+        for value in registers.iter_mut().chain(fp_registers.iter_mut()) {
+            *value = reader.u32()?;
+        }
+        let (hi, lo, fcsr) = (reader.u32()?, reader.u32()?, reader.u32()?);
+        let cp0 = Cp0 {
+            bad_vaddr: reader.u32()?,
+            count: reader.u32()?,
+            compare: reader.u32()?,
+            status: reader.u32()?,
+            cause: reader.u32()?,
+            epc: reader.u32()?,
+        };
+        let (heap_start, heap_break) = (reader.u32()?, reader.u32()?);
+        let stats = ProcessorStats {
+            instructions_executed: reader.u64()?,
+            branches_taken: reader.u64()?,
+            redirections: reader.u64()?,
+            loads_executed: reader.u64()?,
+            stores_executed: reader.u64()?,
+            cycles: reader.u64()?,
+            exceptions: reader.u64()?,
+            interrupts: reader.u64()?,
+            ..ProcessorStats::default()
+        };
+        let cache_stats = CacheStats {
+            hits: reader.u64()?,
+            misses: reader.u64()?,
+            writebacks: reader.u64()?,
+            write_throughs: reader.u64()?,
+            compulsory_misses: reader.u64()?,
+            capacity_misses: reader.u64()?,
+            conflict_misses: reader.u64()?,
+            fetch_hits: reader.u64()?,
+            fetch_misses: reader.u64()?,
+            ..CacheStats::default()
+        };
+        let cache = CacheSnapshot::decode(&mut reader)?;
+        let replacement = self.cache.config().replacement.name();
+        if cache.replacement() != replacement {
+            return Err(SnapshotError::Mismatch(format!("キャッシュの置換方式 {}（現在は {}）", cache.replacement(), replacement)));
+        }
+        let predictors = PredictorSnapshot::decode(&mut reader)?;
+        if predictors.names() != self.predictors.names() {
+            return Err(SnapshotError::Mismatch(format!("分岐予測器 [{}]（現在は [{}]）",
+                predictors.names().join(","), self.predictors.names().join(","))));
+        }
+        let pages = reader.memory(self.memory.size())?;
+        reader.finish()?;
+
+        // 今のダーティラインを古いメモリへ書き戻してから、メモリを置き換えてラインを読み直す
+        let restore_error = |e: MemoryError| SnapshotError::Corrupted(e.to_string());
+        self.cache.flush(&mut self.memory).map_err(restore_error)?;
+        self.memory.replace_pages(&pages);
+        self.cache.restore(&mut self.memory, &cache).map_err(restore_error)?;
+        self.cache.set_stats(cache_stats);
+        self.predictors.restore(&predictors);
+        (self.pc, self.registers, self.fp_registers) = (pc, registers, fp_registers);
+        (self.hi, self.lo, self.fcsr) = (hi, lo, fcsr);
+        self.cp0 = cp0;
+        (self.heap_start, self.heap_break) = (heap_start, heap_break);
+        self.stats = stats;
+        // 保存したときの呼び出しや直前の命令はわからないので、追跡していた記録は捨てる
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.clear();
+        }
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.clear();
+        }
+        Ok(())
+    }
+
+    /// 統計情報を機械可読なレポートとして取得
+    pub fn stats_report(&self) -> StatsReport {
+        let mut report = StatsReport::new();
+        report.push_count("processor.instructions_executed", self.stats.instructions_executed);
+        report.push_count("processor.branches_taken", self.stats.branches_taken);
+        report.push_count("processor.redirections", self.stats.redirections);
+        report.push_count("processor.loads_executed", self.stats.loads_executed);
+        report.push_count("processor.stores_executed", self.stats.stores_executed);
+        report.push_count("processor.cycles", self.stats.cycles);
+        report.push("processor.cpi", self.stats.cpi());
+        report.push_count("processor.exceptions", self.stats.exceptions);
+        report.push_count("processor.interrupts", self.stats.interrupts);
+        for (category, count) in &self.stats.mix {
+            report.push_count(&format!("mix.{}", category.name()), *count);
+        }
+        for (mnemonic, count) in &self.stats.mnemonics {
+            report.push_count(&format!("mix.mnemonic.{}", mnemonic), *count);
+        }
+        if let Some(usage) = &self.register_usage {
+            for reg in usage.used_registers() {
+                report.push_count(&format!("register.{}.reads", RegisterUsage::key_name(reg)), usage.reads(reg));
+                report.push_count(&format!("register.{}.writes", RegisterUsage::key_name(reg)), usage.writes(reg));
+            }
+        }
+        report.push_count("memory.heap_bytes", (self.heap_break - self.heap_start) as u64);
+        if let Some(footprint) = &self.footprint {
+            report.push_count("memory.touched_pages", footprint.pages() as u64);
+            report.push_count("memory.resident_bytes", footprint.resident_bytes());
+            report.push_count("memory.touched_lines", footprint.lines() as u64);
+            report.push_count("memory.touched_line_bytes", footprint.line_bytes());
+            report.push_count("memory.peak_heap_bytes", footprint.peak_heap() as u64);
+            report.push_count("memory.peak_stack_bytes", footprint.peak_stack(self.initial_sp) as u64);
+        }
+
+        let cache_stats = self.cache.get_stats();
+        report.push_count("cache.hits", cache_stats.hits);
+        report.push_count("cache.misses", cache_stats.misses);
+        report.push_count("cache.writebacks", cache_stats.writebacks);
+        report.push_count("cache.write_throughs", cache_stats.write_throughs);
+        if self.cache.config().classify_misses {
+            report.push_count("cache.compulsory_misses", cache_stats.compulsory_misses);
+            report.push_count("cache.capacity_misses", cache_stats.capacity_misses);
+            report.push_count("cache.conflict_misses", cache_stats.conflict_misses);
+        }
+        report.push("cache.hit_rate", cache_stats.hit_rate());
+        for (kind, stats) in [("fetch", cache_stats.fetch()), ("data", cache_stats.data())] {
+            report.push_count(&format!("cache.{}_hits", kind), stats.hits);
+            report.push_count(&format!("cache.{}_misses", kind), stats.misses);
+            report.push(&format!("cache.{}_hit_rate", kind), stats.hit_rate());
+        }
+        for (index, region) in self.cache.config().stats_regions.iter().enumerate() {
+            let stats = cache_stats.regions.get(index).copied().unwrap_or_default();
+            report.push_count(&format!("cache.region.{}.hits", region.name), stats.hits);
+            report.push_count(&format!("cache.region.{}.misses", region.name), stats.misses);
+            report.push(&format!("cache.region.{}.hit_rate", region.name), stats.hit_rate());
+        }
+
+        let bus_stats = self.cache.get_bus_stats();
+        report.push_count("bus.fill_bytes", bus_stats.fill_bytes);
+        report.push_count("bus.writeback_bytes", bus_stats.writeback_bytes);
+        report.push_count("bus.write_through_bytes", bus_stats.write_through_bytes);
+        report.push_count("bus.uncached_bytes", bus_stats.uncached_bytes);
+        report.push_count("bus.busy_cycles", bus_stats.busy_cycles);
+        report.push("bus.average_bandwidth", bus_stats.average_bandwidth());
+        report.push("bus.peak_bandwidth", bus_stats.peak_bandwidth());
+
+        report.push_count("coverage.mnemonics", self.coverage.counts().len() as u64);
+        report.push_count("coverage.executed_addresses", self.coverage.executed_addresses() as u64);
+        report.push_count("coverage.unreached", self.coverage.unreached().len() as u64);
+
+        report.push_text("timing.model", self.timing_config.model.name());
+        report.push_count("timing.instructions", self.timing.instructions());
+        report.push_count("timing.cycles", self.timing.total_cycles);
+        report.push("timing.cpi", self.timing.cpi());
+        report.push_count("timing.stall_cycles", self.timing.stall_cycles);
+        report.push_count("timing.compute_cycles", self.timing.compute_cycles());
+        report.push_count("timing.memory_stall_cycles", self.timing.memory_stall_cycles());
+        for (cause, cycles) in &self.timing.stalls_by_cause {
+            report.push_count(&format!("timing.stall.{}", cause.name()), *cycles);
+        }
+        if self.timing_config.model.is_pipelined() {
+            let hazards = self.pipeline.hazard_stats();
+            report.push_count("pipeline.raw_hazards", hazards.raw_hazards);
+            report.push_count("pipeline.forwarded_ex_mem", hazards.forwarded_ex_mem);
+            report.push_count("pipeline.forwarded_mem_wb", hazards.forwarded_mem_wb);
+            report.push_count("pipeline.resolved_by_stall", hazards.resolved_by_stall());
+            report.push_count("pipeline.load_use_stalls", hazards.load_use_stalls);
+            report.push_count("pipeline.branch_flushes", hazards.branch_flushes);
+            report.push_count("pipeline.jump_flushes", hazards.jump_flushes);
+            report.push_count("pipeline.structural_stalls", hazards.structural_stalls);
+        }
+        if self.timing_config.model.is_pipelined() && self.timing_config.mshrs > 0 {
+            let mshr = self.pipeline.mshr_stats();
+            report.push_count("mshr.count", self.timing_config.mshrs as u64);
+            report.push_count("mshr.misses", mshr.misses);
+            report.push_count("mshr.full_stall_cycles", mshr.full_stall_cycles);
+            report.push_count("mshr.max_outstanding", mshr.max_outstanding as u64);
+            report.push("mshr.saved_cycles", mshr.saved_cycles as f64);
+        }
+        if self.timing_config.model == TimingModel::DualIssue {
+            let issue = self.pipeline.issue_stats();
+            report.push("dual_issue.ipc", issue.ipc());
+            report.push_count("dual_issue.paired", issue.paired);
+            report.push("dual_issue.pairing_rate", issue.pairing_rate());
+            report.push_count("dual_issue.blocked_by_dependency", issue.blocked_by_dependency);
+            report.push_count("dual_issue.blocked_by_structural", issue.blocked_by_structural);
+            report.push_count("dual_issue.blocked_by_control", issue.blocked_by_control);
+            report.push_count("dual_issue.blocked_by_stall", issue.blocked_by_stall);
+        }
+        if self.timing_config.model == TimingModel::OutOfOrder {
+            let ooo = self.ooo.stats();
+            report.push("ooo.ipc", ooo.ipc());
+            report.push_count("ooo.rob_size", self.timing_config.rob_size as u64);
+            report.push_count("ooo.rs_size", self.timing_config.rs_size as u64);
+            report.push_count("ooo.issue_width", self.timing_config.issue_width as u64);
+            report.push_count("ooo.max_rob_occupancy", ooo.max_rob_occupancy as u64);
+            report.push_count("ooo.max_rs_occupancy", ooo.max_rs_occupancy as u64);
+            report.push_count("ooo.rob_full_cycles", ooo.rob_full_cycles);
+            report.push_count("ooo.rs_full_cycles", ooo.rs_full_cycles);
+            report.push_count("ooo.serialize_cycles", ooo.serialize_cycles);
+            report.push_count("ooo.dispatch_width_cycles", ooo.dispatch_width_cycles);
+            report.push_count("ooo.unit_busy_cycles", ooo.unit_busy_cycles);
+            report.push_count("ooo.issue_width_cycles", ooo.issue_width_cycles);
+        }
+        for (name, stats) in self.predictors.stats() {
+            report.push_count(&format!("branch_predictor.{}.branches", name), stats.branches);
+            report.push_count(&format!("branch_predictor.{}.mispredictions", name), stats.mispredictions());
+            report.push(&format!("branch_predictor.{}.accuracy", name), stats.accuracy());
+        }
+        report
+    }
+
+    /// ロードされたプログラムに含まれる既知だが未実装の命令（ニーモニック, 出現数）
+    pub fn unimplemented_instructions(&self) -> Vec<(&'static str, usize)> {
+        match self.isa {
+            Isa::Mips => {
+                let words: Vec<Instruction> = self.coverage.program().iter().map(|&(_, word)| word).collect();
+                decoder::table().count_unimplemented(&words)
+            }
+            Isa::Rv32i => Vec::new(),
+        }
+    }
+
+    /// 命令カバレッジのレポートを取得
+    pub fn coverage_report(&self) -> String {
+        self.coverage.report(|address, instruction| self.disassemble_at(instruction, address))
+    }
+
+    /// 呼び出し・復帰を追跡し、エラーで止まったときにバックトレースを表示する
+    pub fn enable_backtrace(&mut self) {
+        self.call_stack.get_or_insert_with(CallStack::default);
+    }
+
+    /// pcで止まったときのバックトレース（シャドウコールスタックの戻りアドレスを新しい順に並べる、追跡していなければNone）
+    pub fn backtrace(&self, pc: MemoryAddress) -> Option<String> {
+        self.call_stack.as_ref().map(|call_stack| call_stack.backtrace(pc, |address| self.symbols.format_address(address)))
+    }
+
+    /// 命令アドレスごとの実行回数の集計を有効にする
+    pub fn enable_profiler(&mut self) {
+        self.profiler.get_or_insert_with(HotSpotProfiler::default);
+    }
+
+    /// 実行回数の多い上位count件の命令のレポートを取得（集計が無効ならNone）
+    pub fn profile_report(&self, count: usize) -> Option<String> {
+        self.profiler.as_ref().map(|profiler| profiler.report(count,
+            |address| self.symbols.format_address(address),
+            |address, instruction| self.disassemble_at(instruction, address)))
+    }
+
+    /// 呼び出し・復帰を追跡した関数ごとの集計を有効にする
+    pub fn enable_function_profiler(&mut self) {
+        self.function_profiler.get_or_insert_with(FunctionProfiler::default);
+        self.call_stack.get_or_insert_with(CallStack::default);
+    }
+
+    /// 関数ごとのフラットプロファイルを取得（集計が無効ならNone）
+    pub fn function_profile_report(&self) -> Option<String> {
+        let (profiler, call_stack) = (self.function_profiler.as_ref()?, self.call_stack.as_ref()?);
+        Some(profiler.report(call_stack, |address| self.symbols.format_address(address)))
+    }
+
+    /// block_size バイトのブロックごとのロード・ストアの集計を有効にする
+    pub fn enable_heatmap(&mut self, block_size: u32) {
+        self.heatmap.get_or_insert_with(|| MemoryHeatmap::new(block_size));
+    }
+
+    /// ブロックごとのロード・ストアの回数（集計が無効ならNone）
+    pub fn heatmap(&self) -> Option<&MemoryHeatmap> {
+        self.heatmap.as_ref()
+    }
+
+    /// アクセスの多い上位count件のブロックのレポートを取得（集計が無効ならNone）
+    pub fn heatmap_report(&self, count: usize) -> Option<String> {
+        self.heatmap.as_ref().map(|heatmap| heatmap.report(count, |address| self.symbols.format_address(address)))
+    }
+
+    /// page_size バイトのページ単位で、触れたメモリの集計を有効にする
+    pub fn enable_footprint(&mut self, page_size: u32) {
+        self.footprint.get_or_insert_with(|| MemoryFootprint::new(page_size));
+    }
+
+    /// 触れたメモリの集計（無効ならNone）
+    pub fn footprint(&self) -> Option<&MemoryFootprint> {
+        self.footprint.as_ref()
+    }
+
+    /// 触れたメモリのレポートを取得（集計が無効ならNone）
+    pub fn footprint_report(&self) -> Option<String> {
+        self.footprint.as_ref().map(|footprint| footprint.report(self.initial_sp))
+    }
+
+    /// レジスタごとの読み出し・書き込み回数の集計を有効にする
+    pub fn enable_register_usage(&mut self) {
+        self.register_usage.get_or_insert_with(RegisterUsage::default);
+    }
+
+    /// レジスタごとの読み出し・書き込み回数のレポートを取得（集計が無効ならNone）
+    pub fn register_usage_report(&self) -> Option<String> {
+        self.register_usage.as_ref().map(|usage| usage.report(|reg| self.operand_name(reg)))
+    }
+
+    /// タイミング統計を取得
+    pub fn get_timing_stats(&self) -> &TimingStats {
+        &self.timing
+    }
+
+    /// タイミングモデルの設定を変更
+    pub fn set_timing_config(&mut self, config: TimingConfig) {
+        self.pipeline = Pipeline::new(&config);
+        self.pipeline.set_stats_enabled(self.stats_enabled);
+        self.ooo = OutOfOrderCore::new(&config);
+        self.ooo.set_stats_enabled(self.stats_enabled);
+        self.timing_config = config;
+    }
+
+    /// バス統計を取得
+    pub fn get_bus_stats(&self) -> &BusStats {
+        self.cache.get_bus_stats()
+    }
+
+    /// バスの設定を変更
+    pub fn set_bus_config(&mut self, config: BusConfig) {
+        self.cache.set_bus_config(config);
+    }
+
+    /// キャッシュアクセスのトレースの書き出し先を設定する（Noneで記録しない）
+    pub fn set_cache_trace(&mut self, trace: Option<CacheTraceWriter>) {
+        self.cache.set_trace_enabled(trace.is_some());
+        self.cache_trace = trace;
+    }
+
+    /// キャッシュアクセスのトレースを書き出し終え、書き出したアクセス数を返す（記録していなければNone）
+    pub fn finish_cache_trace(&mut self) -> Option<Result<u64, String>> {
+        self.cache_trace.as_mut().map(CacheTraceWriter::finish)
+    }
+
+    /// メモリアクセスのトレースの書き出し先を設定する（Noneで記録しない）
+    pub fn set_mem_trace(&mut self, trace: Option<MemTraceWriter>) {
+        self.mem_trace = trace;
+    }
+
+    /// メモリアクセスのトレースを書き出し終え、書き出したアクセス数を返す（記録していなければNone）
+    pub fn finish_mem_trace(&mut self) -> Option<Result<u64, String>> {
+        self.mem_trace.as_mut().map(MemTraceWriter::finish)
+    }
+
+    /// キャッシュの設定を変更（有効なラインはメモリに書き戻して捨てる）
+    pub fn set_cache_config(&mut self, config: CacheConfig) {
+        self.cache.set_config(&mut self.memory, config).expect("有効なラインはメモリ内にある");
+    }
+
+    /// キャッシュのモデルを差し替える（今のキャッシュのダーティラインは書き戻し、キャッシュの設定は引き継ぐ）
+    /// バスの設定とコヒーレンスは引き継がないので、差し替えた後に設定し直す
+    pub fn set_cache_model(&mut self, mut cache: Box<dyn CacheLike>) {
+        self.cache.flush(&mut self.memory).expect("有効なラインはメモリ内にある");
+        cache.set_config(&mut self.memory, self.cache.config().clone()).expect("有効なラインはメモリ内にある");
+        cache.set_trace_enabled(self.cache_trace.is_some());
+        cache.set_stats_enabled(self.stats_enabled);
+        self.cache = cache;
+    }
+
+    /// キャッシュのモデル
+    pub fn cache_model(&self) -> &dyn CacheLike {
+        self.cache.as_ref()
+    }
+
+    /// 統計情報をリセット
+    pub fn reset_stats(&mut self) {
+        self.stats = ProcessorStats::default();
+        self.timing = TimingStats::default();
+        self.pipeline.set_hazard_stats(HazardStats::default());
+        self.pipeline.set_issue_stats(IssueStats::default());
+        self.pipeline.set_mshr_stats(MshrStats::default());
+        self.ooo.set_stats(OooStats::default());
+        self.predictors.reset_stats();
+        self.coverage.reset();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset();
+        }
+        if let Some(profiler) = &mut self.function_profiler {
+            profiler.reset();
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.reset();
+        }
+        // ヒープとスタックは、リセットした時点で使っている量から数え直す
+        let (heap, sp) = (self.heap_break - self.heap_start, self.get_register(self.sp_register()));
+        if let Some(footprint) = &mut self.footprint {
+            footprint.reset();
+            footprint.observe_heap(heap);
+            footprint.observe_sp(sp);
+        }
+        if let Some(usage) = &mut self.register_usage {
+            usage.reset();
+        }
+        self.cache.reset_stats();
+    }
+
+    /// 文字列を出力（システムコール用）
+    fn print_string(&mut self, address: MemoryAddress) -> Result<(), MemoryError> {
+        // null文字までをバイト列のまま出力する（キャッシュ上の書き戻されていない内容も反映する）
+        let bytes = self.peek_c_string(address)?;
+        self.write_output(&bytes);
+        Ok(())
+    }
+
+    /// ファイル操作の結果をシステムコールの戻り値にする（失敗したら-1）
+    fn file_result(&self, result: Result<Word, String>) -> Word {
+        result.unwrap_or_else(|e| {
+            println!("ファイル操作エラー: {}", e);
+            Word::MAX
+        })
+    }
+
+    /// ファイルを開けるディレクトリを設定（Noneでファイルを開けない）
+    pub fn set_file_sandbox(&mut self, sandbox: Option<PathBuf>) {
+        self.files.set_sandbox(sandbox);
+    }
+
+    /// バイト列をメモリに書き込む（キャッシュの状態や統計を変更しない）
+    pub fn poke_bytes(&mut self, address: MemoryAddress, bytes: &[u8]) -> Result<(), MemoryError> {
+        self.log_memory_write(address, bytes.len());
+        if !self.memory_watchpoints.is_empty() && !bytes.is_empty() {
+            self.watch_memory_write(address, bytes);
+        }
+        if let Some(log) = &mut self.replay {
+            log.record_data(address, bytes);
+        }
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.cache.poke_byte(&mut self.memory, address.wrapping_add(i as u32), byte)?;
+        }
+        Ok(())
+    }
+
+    /// ヒープの終端をincrementバイト動かして元の終端を返す（終端は8バイト境界に揃える）
+    /// ヒープの先頭より前やメモリの外には動かせない
+    fn sbrk(&mut self, increment: i32) -> Option<MemoryAddress> {
+        let old_break = self.heap_break;
+        let new_break = old_break.checked_add_signed(increment)?
+            .checked_next_multiple_of(HEAP_ALIGNMENT)?;
+        if new_break < self.heap_start || new_break as usize > self.memory.size() {
+            return None;
+        }
+        self.heap_break = new_break;
+        if let Some(footprint) = &mut self.footprint {
+            footprint.observe_heap(new_break - self.heap_start);
+        }
+        Some(old_break)
+    }
+
+    /// ヒープの先頭を設定する（ロードしたプログラムやデータの直後を指定する）
+    pub fn set_heap_start(&mut self, address: MemoryAddress) {
+        self.heap_start = address.next_multiple_of(HEAP_ALIGNMENT);
+        self.heap_break = self.heap_start;
+    }
+
+    /// 入力から1行を読み、NUL終端してメモリに書き込む（書き込みはキャッシュの状態や統計を変更しない）
+    fn read_string(&mut self, address: MemoryAddress, length: Word) -> Result<(), MemoryError> {
+        if length == 0 {
+            return Ok(());
+        }
+        let mut bytes = self.input.read_line(length as usize - 1);
+        bytes.push(0);
+        self.poke_bytes(address, &bytes)
     }
 
-    /// キャッシュ統計を取得
-    pub fn get_cache_stats(&self) -> &CacheStats {
-        self.cache.get_stats()
+    /// ホストから値を受け取るシステムコールの記録・再生を設定する（Noneで無効）
+    pub fn set_replay_log(&mut self, log: Option<ReplayLog>) {
+        self.replay = log;
     }
 
-    /// 統計情報をリセット
-    #[allow(dead_code)]
-    pub fn reset_stats(&mut self) {
-        self.stats = ProcessorStats::default();
-        self.cache.reset_stats();
+    /// 記録・再生しているシステムコールの記録
+    pub fn replay_log(&self) -> Option<&ReplayLog> {
+        self.replay.as_ref()
     }
 
-    /// 文字列を出力（システムコール用）
-    fn print_string(&self, address: MemoryAddress) -> Result<(), MemoryError> {
-        let mut current_addr = address;
-        let mut result = String::new();
-        
-        loop {
-            let byte = self.memory.read_byte(current_addr)?;
-            if byte == 0 {
-                break; // null文字で終了
+    /// ゲストプログラムの入力元を設定
+    pub fn set_input(&mut self, input: GuestInput) {
+        self.input = input;
+    }
+
+    /// ゲストプログラムの出力を書き出す
+    fn write_output(&mut self, bytes: &[u8]) {
+        let instructions = self.timing.instructions();
+        self.output.write(bytes, instructions);
+    }
+
+    /// ゲストプログラムの出力先を設定
+    pub fn set_output(&mut self, output: GuestOutput) {
+        self.output = output;
+    }
+
+    /// 実行トレースが有効なら1行を表示する（取り込み中なら表示せずに取り込む）
+    /// 行の組み立てもトレースが有効なときだけ行う
+    fn trace(&mut self, line: impl FnOnce(&Self) -> String) {
+        if !self.trace_enabled {
+            return;
+        }
+        let line = line(self);
+        match &mut self.trace_capture {
+            Some(capture) => {
+                capture.push_str(&line);
+                capture.push('\n');
             }
-            result.push(byte as char);
-            current_addr += 1;
+            None => println!("{}", line),
         }
-        
-        print!("{}", result);
-        Ok(())
     }
+
+    /// 実行トレースをコンソールの代わりに文字列として取り込み始める
+    pub fn start_trace_capture(&mut self) {
+        self.trace_capture = Some(String::new());
+    }
+
+    /// 取り込んだ実行トレースを取り出す（取り込んでいなければ None）
+    pub fn take_captured_trace(&mut self) -> Option<String> {
+        self.trace_capture.take()
+    }
+
+    /// ゲストプログラムの出力をコンソールの代わりにバイト列として取り込み始める
+    pub fn start_output_capture(&mut self) {
+        self.output.start_capture();
+    }
+
+    /// 取り込んだゲストプログラムの出力を取り出す（取り込んでいなければ None）
+    pub fn take_captured_output(&mut self) -> Option<Vec<u8>> {
+        self.output.take_capture()
+    }
+
+    /// ゲストプログラムの出力を書き出し終え、期待出力との比較結果を返す（期待出力がなければ None）
+    pub fn finish_output(&mut self) -> Option<OutputComparison> {
+        let instructions = self.timing.instructions();
+        self.output.finish(instructions)
+    }
+}
+
+/// 1命令を実行した結果（エラーにならずに実行を終えた場合）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepSignal {
+    /// 次の命令へ進む
+    Continue,
+    /// 分岐・ジャンプ（例外ハンドラへの移動を含む）でPCが変わった
+    Branch,
+    /// exit・exit2システムコールによるプログラムの終了
+    Halt { code: i32 },
 }
 
 /// プロセッサエラー
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProcessorError {
     MemoryError(MemoryError),
-    InvalidInstruction(Instruction),
-    ProgramEnd,
+    InvalidInstruction(InvalidInstructionInfo),
+    /// break命令による停止
+    Break { code: u32 },
+    /// 境界に揃っていないアドレスへのアクセス（例外を受け付ける場合のみ）
+    AddressError { address: MemoryAddress, store: bool },
+    /// 符号付き演算のオーバーフロー（例外を受け付ける場合のみ）
+    Overflow,
+    /// ゲストのハンドラに任せるシステムコール（例外を受け付ける場合のみ）
+    SyscallException { number: Word },
+    /// 状態を変えずに同じアドレスを繰り返している（無限ループの検出を有効にした場合のみ）
+    InfiniteLoop { addresses: usize, instructions: u64 },
+    /// 入力の再生中に、ゲストが記録と異なるシステムコールを呼んだ（expectedがNoneなら記録が尽きた）
+    ReplayDivergence { index: usize, expected: Option<Word>, actual: Word },
+    /// レジスタのウォッチポイントの条件を満たした（pcは値を書き換えた命令のアドレス）
+    Watchpoint { pc: MemoryAddress, watchpoint: String, old: Word, new: Word },
+    /// メモリのウォッチポイントに該当するアクセスがあった（読み込みではoldとnewは読み込んだ値）
+    MemoryWatchpoint { pc: MemoryAddress, watchpoint: String, address: MemoryAddress, write: bool, old: Word, new: Word },
+}
+
+/// 無効な命令の診断情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidInstructionInfo {
+    /// 命令のアドレス
+    pub pc: MemoryAddress,
+    /// 命令語
+    pub instruction: Instruction,
+    /// 実行していたISA
+    pub isa: Isa,
+    /// ISAの形式で分解したフィールド（名前, 値）
+    pub fields: Vec<(&'static str, u32)>,
+    /// 直前にある最も近い有効な命令（アドレス, 命令, 逆アセンブル結果）
+    pub preceding_valid: Option<(MemoryAddress, Instruction, String)>,
+    /// 既知だが未実装の命令であればそのニーモニック
+    pub unimplemented: Option<&'static str>,
+}
+
+impl fmt::Display for InvalidInstructionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:08X} (PC=0x{:08X}, {}:", self.instruction, self.pc, self.isa.name())?;
+        for (name, value) in &self.fields {
+            write!(f, " {}=0x{:02X}", name, value)?;
+        }
+        write!(f, ")")?;
+        match &self.preceding_valid {
+            // 有効な命令から大きく離れていれば、データ領域に飛び込んだ可能性が高い
+            Some((address, instruction, text)) => write!(f, ", 直前の有効な命令: 0x{:08X}: 0x{:08X} ({}) [{} ワード前]",
+                address, instruction, text, self.pc.wrapping_sub(*address) / 4),
+            None => write!(f, ", 直前 {} ワード以内に有効な命令なし", PRECEDING_SEARCH_LIMIT),
+        }
+    }
 }
 
 impl From<MemoryError> for ProcessorError {
@@ -466,20 +3099,177 @@ impl fmt::Display for ProcessorError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ProcessorError::MemoryError(e) => write!(f, "メモリエラー: {}", e),
-            ProcessorError::InvalidInstruction(inst) => write!(f, "無効な命令: 0x{:08X}", inst),
-            ProcessorError::ProgramEnd => write!(f, "プログラムが終了しました"),
+            ProcessorError::InvalidInstruction(info) => match info.unimplemented {
+                Some(mnemonic) => write!(f, "未実装の命令 {}: {}", mnemonic, info),
+                None => write!(f, "無効な命令: {}", info),
+            },
+            ProcessorError::Break { code } => write!(f, "break命令で停止しました (コード: {})", code),
+            ProcessorError::AddressError { address, store } => {
+                write!(f, "アドレスエラー（{}）: 0x{:08X}", if *store { "ストア" } else { "ロード" }, address)
+            }
+            ProcessorError::Overflow => write!(f, "算術オーバーフロー"),
+            ProcessorError::SyscallException { number } => write!(f, "システムコール例外 (番号: {})", number),
+            ProcessorError::InfiniteLoop { addresses, instructions } => {
+                write!(f, "無限ループ ({} 個のアドレスを {} 命令のあいだ状態を変えずに繰り返しています)", addresses, instructions)
+            }
+            ProcessorError::ReplayDivergence { index, expected: Some(expected), actual } => write!(f,
+                "入力の再生が食い違いました: {} 件目の記録はシステムコール {} ですが、システムコール {} が呼ばれました", index + 1, expected, actual),
+            ProcessorError::ReplayDivergence { index, expected: None, actual } => write!(f,
+                "入力の記録が尽きました: {} 件を再生した後にシステムコール {} が呼ばれました", index, actual),
+            ProcessorError::Watchpoint { pc, watchpoint, old, new } => write!(f,
+                "ウォッチポイント '{}' で停止しました: PC=0x{:08X} の命令で 0x{:08X} -> 0x{:08X} に変わりました", watchpoint, pc, old, new),
+            ProcessorError::MemoryWatchpoint { pc, watchpoint, address, write: true, old, new } => write!(f,
+                "メモリのウォッチポイント '{}': PC=0x{:08X} の命令が 0x{:08X} に書き込みました: 0x{:08X} -> 0x{:08X}", watchpoint, pc, address, old, new),
+            ProcessorError::MemoryWatchpoint { pc, watchpoint, address, write: false, new, .. } => write!(f,
+                "メモリのウォッチポイント '{}': PC=0x{:08X} の命令が 0x{:08X} から読み込みました: 0x{:08X}", watchpoint, pc, address, new),
         }
     }
 }
 
 impl std::error::Error for ProcessorError {}
 
+/// プログラムの実行が終了した理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunResult {
+    /// exitシステムコールによる終了
+    Exited { code: i32 },
+    /// 停止アドレスへのジャンプによる終了
+    Halted { pc: MemoryAddress },
+    /// break命令による停止
+    Breakpoint { pc: MemoryAddress, code: u32 },
+    /// 命令数の上限に到達
+    InstructionLimit { limit: u64 },
+    /// 状態を変えずに同じアドレスを繰り返していると判定した（無限ループの検出を有効にした場合のみ）
+    InfiniteLoop { pc: MemoryAddress, addresses: usize, instructions: u64 },
+    /// 実行時間の上限に到達
+    Timeout { elapsed: Duration, instructions: u64 },
+    /// ステップ実行モードでユーザーが終了した
+    UserQuit { pc: MemoryAddress },
+    /// 例外（メモリエラーや無効な命令）による異常終了
+    Exception { pc: MemoryAddress, error: ProcessorError },
+}
+
+impl RunResult {
+    /// step()が返したエラーを終了理由に変換する
+    pub fn from_error(pc: MemoryAddress, error: ProcessorError) -> Self {
+        match error {
+            ProcessorError::Break { code } => RunResult::Breakpoint { pc, code },
+            ProcessorError::InfiniteLoop { addresses, instructions } => RunResult::InfiniteLoop { pc, addresses, instructions },
+            error => RunResult::Exception { pc, error },
+        }
+    }
+
+    /// 止まった位置のPC（PCと結び付かない終了理由ならNone）
+    pub fn pc(&self) -> Option<MemoryAddress> {
+        match self {
+            RunResult::Halted { pc } | RunResult::Breakpoint { pc, .. } | RunResult::InfiniteLoop { pc, .. }
+            | RunResult::UserQuit { pc } | RunResult::Exception { pc, .. } => Some(*pc),
+            RunResult::Exited { .. } | RunResult::InstructionLimit { .. } | RunResult::Timeout { .. } => None,
+        }
+    }
+
+    /// 機械可読な終了理由の種類
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RunResult::Exited { .. } => "exited",
+            RunResult::Halted { .. } => "halted",
+            RunResult::Breakpoint { .. } => "breakpoint",
+            RunResult::InstructionLimit { .. } => "instruction_limit",
+            RunResult::InfiniteLoop { .. } => "infinite_loop",
+            RunResult::Timeout { .. } => "timeout",
+            RunResult::UserQuit { .. } => "user_quit",
+            RunResult::Exception { error, .. } => match error {
+                ProcessorError::MemoryError(MemoryError::ProtectionViolation { .. }) => "exception.protection_violation",
+                ProcessorError::MemoryError(_) => "exception.memory",
+                ProcessorError::InvalidInstruction(info) if info.unimplemented.is_some() => "exception.unimplemented_instruction",
+                ProcessorError::InvalidInstruction(_) => "exception.invalid_instruction",
+                ProcessorError::AddressError { .. } => "exception.address_error",
+                ProcessorError::Overflow => "exception.overflow",
+                ProcessorError::SyscallException { .. } => "exception.syscall",
+                ProcessorError::ReplayDivergence { .. } => "exception.replay_divergence",
+                ProcessorError::Watchpoint { .. } => "exception.watchpoint",
+                ProcessorError::MemoryWatchpoint { .. } => "exception.memory_watchpoint",
+                _ => "exception.other",
+            },
+        }
+    }
+
+    /// ホストのプロセスの終了コード
+    /// exit2で終了した場合はゲストの終了コード、exitや停止アドレスでの終了は0、それ以外（break・例外・上限による打ち切り・中断）は1
+    /// （is_success と同じく、0を返すのは正常終了だけ）
+    /// ホストは下位8ビットしか返さないので、0でない終了コードが256の倍数なら失敗が0に化けないよう1にする
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            RunResult::Exited { code } if *code != 0 && code.rem_euclid(256) == 0 => 1,
+            RunResult::Exited { code } => *code,
+            RunResult::Exception { .. } | RunResult::InstructionLimit { .. } | RunResult::InfiniteLoop { .. }
+            | RunResult::Timeout { .. } | RunResult::Breakpoint { .. } | RunResult::UserQuit { .. } => 1,
+            RunResult::Halted { .. } => 0,
+        }
+    }
+
+    /// 正常終了かどうか
+    pub fn is_success(&self) -> bool {
+        matches!(self, RunResult::Exited { code: 0 } | RunResult::Halted { .. })
+    }
+
+    /// 終了理由をレポートに追加する
+    pub fn append_to_report(&self, report: &mut StatsReport) {
+        report.push_text("outcome.kind", self.kind());
+        match self {
+            RunResult::Exited { code } => report.push("outcome.exit_code", *code),
+            RunResult::Halted { pc } | RunResult::UserQuit { pc } => report.push_count("outcome.pc", *pc as u64),
+            RunResult::Breakpoint { pc, code } => {
+                report.push_count("outcome.pc", *pc as u64);
+                report.push_count("outcome.break_code", *code as u64);
+            }
+            RunResult::InstructionLimit { limit } => report.push_count("outcome.limit", *limit),
+            RunResult::InfiniteLoop { pc, addresses, instructions } => {
+                report.push_count("outcome.pc", *pc as u64);
+                report.push_count("outcome.loop_addresses", *addresses as u64);
+                report.push_count("outcome.instructions", *instructions);
+            }
+            RunResult::Timeout { elapsed, instructions } => {
+                report.push("outcome.elapsed_seconds", elapsed.as_secs_f64());
+                report.push_count("outcome.instructions", *instructions);
+            }
+            RunResult::Exception { pc, error } => {
+                report.push_count("outcome.pc", *pc as u64);
+                report.push_text("outcome.message", &error.to_string());
+            }
+        }
+    }
+}
+
+impl fmt::Display for RunResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunResult::Exited { code } => write!(f, "exitシステムコールで終了しました (終了コード: {})", code),
+            RunResult::Halted { pc } => write!(f, "停止アドレス 0x{:08X} に到達して終了しました", pc),
+            RunResult::Breakpoint { pc, code } => write!(f, "PC=0x{:08X} のbreak命令で停止しました (コード: {})", pc, code),
+            RunResult::InstructionLimit { limit } => write!(f, "命令数の上限 ({}) に到達しました（--max-instructions で変更できます）", limit),
+            RunResult::InfiniteLoop { pc, addresses, instructions } => write!(f,
+                "PC=0x{:08X} で無限ループを検出しました ({} 個のアドレスを {} 命令のあいだ状態を変えずに繰り返しています)",
+                pc, addresses, instructions),
+            RunResult::Timeout { elapsed, instructions } => {
+                write!(f, "実行時間の上限に到達しました ({:.3} 秒, {} 命令)", elapsed.as_secs_f64(), instructions)
+            }
+            RunResult::UserQuit { pc } => write!(f, "PC=0x{:08X} でユーザーが実行を終了しました", pc),
+            RunResult::Exception { pc, error } => write!(f, "PC=0x{:08X} で例外が発生しました: {}", pc, error),
+        }
+    }
+}
+
 impl fmt::Display for ProcessorStats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "実行命令数: {}\n", self.instructions_executed)?;
-        write!(f, "分岐命令数: {}\n", self.branches_taken)?;
-        write!(f, "ロード命令数: {}\n", self.loads_executed)?;
-        write!(f, "ストア命令数: {}", self.stores_executed)
+        writeln!(f, "実行命令数: {}", self.instructions_executed)?;
+        writeln!(f, "分岐命令数: {}", self.branches_taken)?;
+        writeln!(f, "PCの書き換え回数: {}", self.redirections)?;
+        writeln!(f, "ロード命令数: {}", self.loads_executed)?;
+        writeln!(f, "ストア命令数: {}", self.stores_executed)?;
+        writeln!(f, "サイクル数: {}", self.cycles)?;
+        writeln!(f, "CPI: {:.3}", self.cpi())?;
+        write!(f, "例外の発生回数: {} (うち割り込み {})", self.exceptions, self.interrupts)
     }
 }
 
@@ -487,6 +3277,16 @@ impl fmt::Display for ProcessorStats {
 mod tests {
     use super::*;
 
+    /// アセンブリの1行を命令のワードにする
+    fn asm(line: &str) -> Instruction {
+        crate::assembler::parse_instruction(line).unwrap().encode().unwrap()
+    }
+
+    /// PC_INITIAL にプログラムを置けるだけのメモリを持つプロセッサ（既定の1MBでは足りない）
+    fn program_processor() -> Processor {
+        Processor::with_memory_size(16 * 1024 * 1024)
+    }
+
     #[test]
     fn test_processor_creation() {
         let processor = Processor::new();
@@ -519,4 +3319,1029 @@ mod tests {
         processor.execute_instruction(instruction).unwrap();
         assert_eq!(processor.get_register(1), 30);
     }
+
+    #[test]
+    fn test_fp_conversion_instructions() {
+        let mut processor = Processor::new();
+
+        // mtc1 $t0, $f4 ; cvt.s.w $f2, $f4
+        processor.set_register(8, (-7i32) as u32);
+        processor.execute_instruction(0x44882000).unwrap();
+        processor.execute_instruction(0x468020A0).unwrap();
+        assert_eq!(processor.get_fp_single(2), -7.0);
+
+        // trunc.w.s $f2, $f4 は丸めモードに関係なくゼロ方向へ丸める
+        processor.set_fp_single(4, 2.75);
+        processor.execute_instruction(0x4600208D).unwrap();
+        assert_eq!(processor.get_fp_register(2), 2);
+
+        // cvt.w.s $f2, $f4 はFCSRの丸めモード（初期値は最近接偶数丸め）に従う
+        processor.execute_instruction(0x460020A4).unwrap();
+        assert_eq!(processor.get_fp_register(2), 3);
+    }
+
+    #[test]
+    fn test_fp_unary_instructions() {
+        let mut processor = Processor::new();
+        processor.set_fp_single(4, -16.0);
+
+        // neg.s $f2, $f4 ; sqrt.s $f6, $f2
+        processor.execute_instruction(0x46002087).unwrap();
+        processor.execute_instruction(0x46001184).unwrap();
+        assert_eq!(processor.get_fp_single(6), 4.0);
+
+        // abs.s $f2, $f4 ; mov.s $f8, $f2
+        processor.execute_instruction(0x46002085).unwrap();
+        processor.execute_instruction(0x46001206).unwrap();
+        assert_eq!(processor.get_fp_single(8), 16.0);
+    }
+
+    #[cfg(feature = "double")]
+    #[test]
+    fn test_double_instructions() {
+        let mut processor = Processor::new();
+        let bits = 1.5f64.to_bits();
+        processor.write_memory(0x1000, bits as Word).unwrap();
+        processor.write_memory(0x1004, (bits >> 32) as Word).unwrap();
+
+        processor.execute_instruction(asm("ldc1 $f2, 0x1000($zero)")).unwrap();
+        processor.execute_instruction(asm("mul.d $f4, $f2, $f2")).unwrap();
+        processor.execute_instruction(asm("neg.d $f4, $f4")).unwrap();
+        processor.execute_instruction(asm("sdc1 $f4, 0x1008($zero)")).unwrap();
+        processor.execute_instruction(asm("cvt.w.d $f6, $f4")).unwrap();
+
+        assert_eq!(processor.get_fp_double(4), -2.25);
+        assert_eq!(processor.peek_word(0x100C).unwrap(), ((-2.25f64).to_bits() >> 32) as Word);
+        assert_eq!(processor.get_fp_register(6) as i32, -2);
+
+        // 奇数番号のレジスタ対は無効
+        let odd = asm("add.d $f1, $f2, $f4");
+        assert!(matches!(processor.execute_instruction(odd), Err(ProcessorError::InvalidInstruction(_))));
+
+        // 上位ワードがメモリの外なら下位ワードも書き込まない
+        let mut processor = Processor::with_memory_size(0x2000);
+        processor.set_fp_double(4, 0.1);
+        assert!(processor.execute_instruction(asm("sdc1 $f4, 0x1FFC($zero)")).is_err());
+        assert_eq!(processor.peek_word(0x1FFC).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_run_outcomes() {
+        // addi $v0, $zero, 1 ; break 7
+        let mut processor = program_processor();
+        processor.load_program(&[0x20020001, 0x000001CD], PC_INITIAL).unwrap();
+        assert_eq!(processor.run(), RunResult::Breakpoint { pc: PC_INITIAL + 4, code: 7 });
+
+        // 無効な命令は例外として分類される
+        let mut processor = program_processor();
+        processor.load_program(&[0xFC000000], PC_INITIAL).unwrap();
+        assert_eq!(processor.run().kind(), "exception.invalid_instruction");
+
+        // j 0x00400000 の無限ループは命令数の上限で止まる
+        let mut processor = program_processor();
+        processor.load_program(&[0x08100000], PC_INITIAL).unwrap();
+        assert_eq!(processor.run(), RunResult::InstructionLimit { limit: INSTRUCTION_LIMIT });
+        processor.set_max_instructions(Some(5));
+        assert_eq!(processor.run(), RunResult::InstructionLimit { limit: 5 });
+
+        // $v0 がたまたま10になっても終了せず、exitシステムコールでのみ終了する
+        let mut processor = program_processor();
+        let program = [asm("addi $v0, $zero, 10"), asm("addi $a0, $zero, 5"), asm("addi $v0, $zero, 17"), asm("syscall")];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        for _ in 0..3 {
+            assert_eq!(processor.step(), Ok(StepSignal::Continue));
+        }
+        assert_eq!(processor.step(), Ok(StepSignal::Halt { code: 5 }));
+        processor.set_pc(PC_INITIAL);
+        assert_eq!(processor.run(), RunResult::Exited { code: 5 });
+    }
+
+    #[test]
+    fn test_return_to_halt_address() {
+        // mainからjr $raで戻ると、停止アドレスに到達して正常終了する
+        let program = [asm("addi $v0, $zero, 5"), asm("jr $ra")];
+        let mut processor = program_processor();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert_eq!(processor.get_register(31), HALT_ADDRESS);
+        let outcome = processor.run();
+        assert_eq!(outcome, RunResult::Halted { pc: HALT_ADDRESS });
+        assert!(outcome.is_success());
+        assert_eq!(processor.get_stats().instructions_executed, 2);
+
+        // 停止アドレスを変えると$raも変わり、RV32Iではraに入る
+        let mut processor = program_processor();
+        processor.set_halt_address(0x00000100);
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert_eq!(processor.run(), RunResult::Halted { pc: 0x00000100 });
+        processor.set_isa(Isa::Rv32i);
+        assert_eq!((processor.get_register(31), processor.get_register(riscv::RA_REGISTER)), (0, 0x00000100));
+    }
+
+    #[test]
+    fn test_instruction_mix() {
+        let mut processor = program_processor();
+        let program = [
+            asm("addi $t0, $zero, 2"),
+            asm("sll $t1, $t0, 2"),
+            asm("sw $t1, 0x100($zero)"),
+            asm("lw $t2, 0x100($zero)"),
+            asm("addi $t0, $t0, -1"),
+            asm("bne $t0, $zero, -1"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        let stats = processor.get_stats();
+        // 例外で完了しなかったbreakは数えない
+        let mix: Vec<(&str, u64)> = stats.mix.iter().map(|(category, count)| (category.name(), *count)).collect();
+        assert_eq!(mix, vec![("alu", 3), ("shift", 1), ("load", 1), ("store", 1), ("branch", 2)]);
+        assert_eq!(stats.mnemonics.get("bne"), Some(&2));
+        assert!(stats.mix_report().contains("  addi                3 ( 37.50%)"));
+        assert_eq!(processor.stats_report().get("mix.branch"), Some(2.0));
+    }
+
+    #[test]
+    fn test_register_usage() {
+        let mut processor = program_processor();
+        processor.enable_register_usage();
+        let program = [
+            asm("addi $t0, $zero, 3"),
+            asm("add $t1, $t0, $t0"),
+            asm("sw $t1, 0x100($zero)"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        let report = processor.stats_report();
+        assert_eq!(report.get("register.r8.reads"), Some(2.0));
+        assert_eq!(report.get("register.r8.writes"), Some(1.0));
+        assert_eq!(report.get("register.r9.reads"), Some(1.0));
+        assert_eq!(report.get("register.r0.reads"), None);
+        let text = processor.register_usage_report().unwrap();
+        assert!(text.starts_with("命令数: 3, 読み出し: 3"), "{}", text);
+        assert!(text.contains("\n  $t1                 1            1"), "{}", text);
+    }
+
+    #[test]
+    fn test_backtrace_on_error() {
+        let mut processor = program_processor();
+        let program = [
+            0x0C100002, // main: jal f
+            0x0000000D, // break
+            0x0C100004, // f: jal g
+            0x03E00008, // jr $ra
+            0xFC000000, // g: 無効な命令
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        processor.set_symbols(SymbolTable::parse("main 0x00400000\nf 0x00400008\ng 0x00400010").unwrap());
+        processor.enable_backtrace();
+        assert_eq!(processor.run().kind(), "exception.invalid_instruction");
+        let expected = [
+            "バックトレース（新しい順）:",
+            "  #0 0x00400010 <g>",
+            "  #1 0x0040000C <f+0x4> （0x00400010 <g> からの戻り先）",
+            "  #2 0x00400004 <main+0x4> （0x00400008 <f> からの戻り先）",
+        ];
+        assert_eq!(processor.backtrace(processor.get_pc()), Some(expected.join("\n")));
+
+        // 有効にしなければ呼び出しを追跡しない
+        let mut processor = program_processor();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        processor.run();
+        assert_eq!(processor.backtrace(processor.get_pc()), None);
+    }
+
+    #[test]
+    fn test_uninitialized_register_reads() {
+        let mut processor = program_processor();
+        processor.enable_uninitialized_check();
+        let program = [
+            asm("addi $t0, $zero, 1"),
+            asm("add $t1, $t0, $s0"),    // $s0は未初期化
+            asm("add $t2, $s0, $s0"),    // 同じレジスタは1回だけ報告する
+            asm("addi $t3, $sp, -4"),    // $spは初期化済みとみなす
+            asm("addi $v0, $zero, 10"),
+            asm("syscall"),              // システムコールの引数は確かめない
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(processor.run().is_success());
+        assert_eq!(processor.uninitialized_reads(), &[(PC_INITIAL + 4, 16)]);
+    }
+
+    #[test]
+    fn test_poisoned_memory() {
+        let mut processor = program_processor();
+        processor.poison_memory(0xCC);
+        processor.enable_poison_check(0xCC);
+        let program = [
+            asm("addi $t0, $zero, 5"),
+            asm("sw $t0, 0x1000($zero)"),
+            asm("lw $t1, 0x1000($zero)"),  // 書き込んだワードは報告しない
+            asm("lw $t2, 0x1004($zero)"),  // 未初期化
+            asm("lw $t2, 0x1004($zero)"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.get_register(10), 0xCCCCCCCC);
+        assert_eq!(processor.poisoned_reads(), &[(PC_INITIAL + 12, 0x1004), (PC_INITIAL + 16, 0x1004)]);
+    }
+
+    #[test]
+    fn test_infinite_loop_detection() {
+        let config = LoopDetectionConfig { window: 4, threshold: 100 };
+        // 状態を変えながら回るループは検出しない
+        let mut processor = program_processor();
+        processor.set_loop_detection(Some(config.clone()));
+        processor.load_program(&[
+            0x20080200, // addi $t0, $zero, 512
+            0x2108FFFF, // loop: addi $t0, $t0, -1
+            0x1500FFFF, // bne $t0, $zero, loop
+            0x0000000D, // break
+        ], PC_INITIAL).unwrap();
+        assert_eq!(processor.run().kind(), "breakpoint");
+
+        // 状態を変えないループ（slt の結果が毎回同じ）は検出する
+        let mut processor = program_processor();
+        processor.set_loop_detection(Some(config));
+        processor.load_program(&[
+            0x20080001, // addi $t0, $zero, 1
+            0x0100482A, // loop: slt $t1, $t0, $zero
+            0x1120FFFF, // beq $t1, $zero, loop
+            0x0000000D, // break
+        ], PC_INITIAL).unwrap();
+        let outcome = processor.run();
+        assert_eq!(outcome, RunResult::InfiniteLoop { pc: PC_INITIAL + 8, addresses: 2, instructions: 100 });
+        assert_eq!(outcome.exit_status(), 1);
+    }
+
+    #[test]
+    fn test_execution_history() {
+        let mut processor = program_processor();
+        processor.set_history_length(2);
+        processor.load_program(&[
+            0x20080005, // addi $t0, $zero, 5
+            0x01084820, // add $t1, $t0, $t0
+            0x00000000, // sll $zero, $zero, 0
+            0xFC000000, // 無効な命令
+        ], PC_INITIAL).unwrap();
+        assert_eq!(processor.run().kind(), "exception.invalid_instruction");
+        let report = processor.history_report().unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 3, "{}", report);
+        assert!(lines[1].starts_with("  0x00400008: sll"), "{}", report);
+        // 完了しなかった命令は書いたレジスタを表示しない
+        assert_eq!(lines[2], "  0x0040000C: invalid");
+        assert_eq!(Processor::new().history_report(), None);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut processor = program_processor();
+        processor.set_undo_limit(3);
+        processor.load_program(&[
+            asm("addi $t0, $zero, 5"),
+            asm("sw $t0, 0x100($zero)"),
+            asm("mtc1 $t0, $f2"),
+            asm("addi $t0, $t0, 1"),
+        ], PC_INITIAL).unwrap();
+        for _ in 0..4 {
+            processor.step().unwrap();
+        }
+        assert_eq!((processor.get_register(8), processor.get_fp_register(2)), (6, 5));
+        // 記録する命令数の上限を超えた最初の命令は戻せない
+        assert_eq!(processor.undo_depth(), 3);
+
+        assert_eq!(processor.rewind(2), 2);
+        assert_eq!(processor.get_pc(), PC_INITIAL + 8);
+        assert_eq!((processor.get_register(8), processor.get_fp_register(2)), (5, 0));
+        assert_eq!(processor.peek_word(0x100), Ok(5));
+        assert_eq!(processor.rewind(5), 1);
+        assert_eq!(processor.get_pc(), PC_INITIAL + 4);
+        assert_eq!(processor.peek_word(0x100), Ok(0));
+
+        // 戻した位置から実行し直せる
+        processor.step().unwrap();
+        assert_eq!(processor.peek_word(0x100), Ok(5));
+    }
+
+    #[test]
+    fn test_register_watchpoints() {
+        let mut processor = program_processor();
+        processor.add_watchpoint(Watchpoint::parse("$s0").unwrap());
+        processor.add_watchpoint(Watchpoint::parse("$sp < 0x7FFF0000").unwrap());
+        processor.load_program(&[
+            asm("addi $t0, $zero, 1"),
+            asm("addi $s0, $zero, 7"),
+            asm("addi $sp, $sp, -8"),
+            asm("lui $sp, 0x7000"),
+            asm("break"),
+        ], PC_INITIAL).unwrap();
+        let outcome = processor.run();
+        assert_eq!(outcome, RunResult::Exception {
+            pc: PC_INITIAL + 4,
+            error: ProcessorError::Watchpoint { pc: PC_INITIAL + 4, watchpoint: "$s0".to_string(), old: 0, new: 7 },
+        });
+        assert_eq!(outcome.kind(), "exception.watchpoint");
+        // 命令は完了しているので続きから実行でき、$spは条件を満たすまで止まらない
+        assert_eq!(processor.get_register(16), 7);
+        processor.step().unwrap();
+        let error = processor.step().unwrap_err();
+        assert_eq!(error, ProcessorError::Watchpoint {
+            pc: PC_INITIAL + 12, watchpoint: "$sp < 0x7FFF0000".to_string(), old: SP_INITIAL - 8, new: 0x70000000,
+        });
+    }
+
+    #[test]
+    fn test_memory_watchpoints() {
+        let mut processor = program_processor();
+        processor.add_memory_watchpoint(MemoryWatchpoint::parse("0x1000:w").unwrap());
+        processor.add_memory_watchpoint(MemoryWatchpoint::parse("0x2000-0x2010:r:log").unwrap());
+        processor.load_program(&[
+            asm("addi $t0, $zero, 7"),
+            asm("lw $t1, 0x2004($zero)"),  // 表示するだけで止めない
+            asm("sw $t0, 0x1000($zero)"),
+            asm("sw $t0, 0x1000($zero)"),
+            asm("break"),
+        ], PC_INITIAL).unwrap();
+        processor.poke_word(0x1000, 3).unwrap();
+        let outcome = processor.run();
+        assert_eq!(outcome, RunResult::Exception {
+            pc: PC_INITIAL + 8,
+            error: ProcessorError::MemoryWatchpoint {
+                pc: PC_INITIAL + 8, watchpoint: "0x00001000-0x00001004:w".to_string(), address: 0x1000, write: true, old: 3, new: 7,
+            },
+        });
+        assert_eq!(outcome.kind(), "exception.memory_watchpoint");
+        // 命令は完了しているので続きから実行でき、同じ値の書き込みでも止まる
+        assert_eq!(processor.peek_word(0x1000), Ok(7));
+        assert!(matches!(processor.step(), Err(ProcessorError::MemoryWatchpoint { old: 7, new: 7, .. })));
+
+        // システムコールによる長い書き込みは範囲に入る位置からの値を示す
+        processor.add_memory_watchpoint(MemoryWatchpoint::parse("0x3002-0x3003").unwrap());
+        processor.watch_memory_write(0x3000, b"abcd");
+        assert!(matches!(processor.memory_watch_hit, Some(ProcessorError::MemoryWatchpoint { address: 0x3002, old: 0, new: 0x6463, .. })));
+    }
+
+    #[test]
+    fn test_cp0_instructions() {
+        let mut processor = Processor::new();
+        processor.set_register(8, 0x0000_FF01);
+        processor.execute_instruction(asm("mtc0 $8, $12")).unwrap();
+        processor.execute_instruction(asm("mfc0 $9, $12")).unwrap();
+        assert_eq!(processor.get_register(9), 0x0000_FF01);
+
+        // Countは命令を実行するたびにサイクル数だけ進む
+        // 6命令は同じラインに収まるので、最初のフェッチだけがミスし、残りは1サイクルずつかかる
+        let mut processor = program_processor();
+        let program = [
+            asm("addi $8, $zero, 100"),
+            asm("mtc0 $8, $11"),   // Compare = 100
+            asm("mtc0 $zero, $9"), // Count = 0（この命令の1サイクルは書いた後に進む）
+            asm("add $0, $0, $0"),
+            asm("mfc0 $9, $9"),
+            asm("mfc0 $10, $11"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        for _ in 0..program.len() {
+            processor.step().unwrap();
+        }
+        assert_eq!(processor.get_register(9), 2);
+        assert_eq!(processor.get_register(10), 100);
+        assert_eq!(processor.cp0.count, 4);
+        assert_eq!(processor.cp0.compare, 100);
+    }
+
+    #[test]
+    fn test_precise_exceptions() {
+        let mut processor = program_processor();
+        // ハンドラ: 回数を数え、EPCを4進めて戻る
+        let handler = [
+            asm("mfc0 $k0, $14"),
+            asm("addi $k0, $k0, 4"),
+            asm("mtc0 $k0, $14"),
+            asm("addi $s0, $s0, 1"),
+            asm("eret"),
+        ];
+        processor.load_program(&handler, PC_INITIAL + 0x100).unwrap();
+        let program = [
+            asm("lui $t0, 0x7FFF"),
+            asm("ori $t0, $t0, 0xFFFF"),
+            asm("addi $t1, $t0, 1"),      // オーバーフロー
+            asm("lw $t2, 2($zero)"),      // 境界に揃っていないロード
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        processor.set_exception_vector(Some(PC_INITIAL + 0x100));
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // 例外を起こした命令は結果を書かない
+        assert_eq!(processor.get_register(9), 0);
+        assert_eq!(processor.get_register(16), 2);
+        assert_eq!(processor.get_stats().exceptions, 2);
+        assert_eq!((processor.cp0.cause >> 2) & 0x1F, ExceptionCode::AddressLoad as Word);
+        assert_eq!(processor.cp0.bad_vaddr, 2);
+        assert!(!processor.cp0.in_exception());
+
+        // 例外ベクタがなければ従来どおりオーバーフローは無視し、ハンドラ内の例外では止まる
+        let mut processor = Processor::new();
+        processor.set_register(8, 0x7FFF_FFFF);
+        assert!(processor.execute_instruction(asm("addi $t1, $t0, 1")).is_ok());
+        processor.set_exception_vector(Some(0x100));
+        assert_eq!(processor.execute_instruction(asm("addi $t1, $t0, 1")), Err(ProcessorError::Overflow));
+        processor.cp0.enter_exception(ExceptionCode::Syscall, 0, None);
+        assert_eq!(processor.take_exception(0, ProcessorError::Overflow, false), Err(ProcessorError::Overflow));
+    }
+
+    #[test]
+    fn test_timer_interrupt() {
+        let mut processor = program_processor();
+        processor.load_program(&[asm("break")], PC_INITIAL + 0x100).unwrap();
+        let program = [
+            asm("addi $t0, $zero, 50"),
+            asm("mtc0 $t0, $11"),          // Compare = 50
+            asm("ori $t0, $zero, 0x8001"),
+            asm("mtc0 $t0, $12"),          // Status = IM7 | IE
+            asm("addi $s0, $s0, 1"),
+            asm("j 0x00400010"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        processor.set_exception_vector(Some(PC_INITIAL + 0x100));
+        assert_eq!(processor.run(), RunResult::Breakpoint { pc: PC_INITIAL + 0x100, code: 0 });
+        // Countが50に達するまでループを回り、ループ内の命令を指すEPCでハンドラへ移る
+        assert!(processor.get_register(16) > 0);
+        assert!((PC_INITIAL + 0x10..=PC_INITIAL + 0x14).contains(&processor.cp0.epc));
+        assert_eq!((processor.cp0.cause >> 2) & 0x1F, ExceptionCode::Interrupt as Word);
+        assert_eq!(processor.get_stats().interrupts, 1);
+    }
+
+    #[test]
+    fn test_input_syscalls() {
+        let mut processor = program_processor();
+        processor.set_input(GuestInput::from_bytes(b"123\nabcdefgh\n".to_vec()));
+        let program = [
+            asm("addi $v0, $zero, 5"),     // read_int
+            asm("syscall"),
+            asm("add $s0, $v0, $zero"),
+            asm("addi $v0, $zero, 12"),    // read_char
+            asm("syscall"),
+            asm("add $s1, $v0, $zero"),
+            asm("lui $a0, 0x0080"),
+            asm("addi $a1, $zero, 4"),
+            asm("addi $v0, $zero, 8"),     // read_string（3バイトまで）
+            asm("syscall"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.get_register(16), 123);
+        assert_eq!(processor.get_register(17), b'a' as Word);
+        assert_eq!(processor.peek_word(0x0080_0000).unwrap(), u32::from_le_bytes(*b"bcd\0"));
+    }
+
+    #[test]
+    fn test_sbrk() {
+        let mut processor = program_processor();
+        let program = [
+            asm("addi $a0, $zero, 5"),
+            asm("addi $v0, $zero, 9"),     // sbrk(5)
+            asm("syscall"),
+            asm("add $s0, $v0, $zero"),
+            asm("addi $a0, $zero, 16"),
+            asm("addi $v0, $zero, 9"),     // sbrk(16)
+            asm("syscall"),
+            asm("add $s1, $v0, $zero"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // ヒープはプログラムの直後から始まり、終端は8バイト境界に揃う
+        assert_eq!(processor.get_register(16), PC_INITIAL + 40);
+        assert_eq!(processor.get_register(17), PC_INITIAL + 48);
+        assert_eq!(processor.heap_break, PC_INITIAL + 64);
+        // メモリの外やヒープの先頭より前には動かせない
+        assert_eq!(processor.sbrk(16 * 1024 * 1024), None);
+        assert_eq!(processor.sbrk(-100), None);
+        assert_eq!(processor.sbrk(-24), Some(PC_INITIAL + 64));
+
+        // ヒープの先頭から離れたデータではヒープを動かさない
+        processor.load_data(PC_INITIAL + 0x10000, &[1; 10]).unwrap();
+        processor.load_data(PC_INITIAL + 48, &[2; 8]).unwrap();
+        assert_eq!(processor.sbrk(0), Some(PC_INITIAL + 40));
+        // ヒープの先頭に重なるデータの後ろに動かし、そこに接するデータも続けて飛ばす
+        processor.load_data(PC_INITIAL + 36, &[3; 12]).unwrap();
+        assert_eq!(processor.sbrk(0), Some(PC_INITIAL + 56));
+        processor.load_data(PC_INITIAL, &[0; 4]).unwrap();
+        assert_eq!(processor.sbrk(0), Some(PC_INITIAL + 56));
+    }
+
+    #[test]
+    fn test_file_syscalls() {
+        let dir = std::env::temp_dir().join(format!("mips_simulator_syscall_files_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut processor = program_processor();
+        processor.set_file_sandbox(Some(dir.clone()));
+        processor.poke_bytes(0x0080_0000, b"out.txt\0").unwrap();
+        processor.poke_bytes(0x0080_0100, b"hello").unwrap();
+        let program = [
+            asm("lui $t0, 0x0080"),
+            asm("add $a0, $t0, $zero"),
+            asm("addi $a1, $zero, 1"),
+            asm("addi $v0, $zero, 13"),    // open（書き込み）
+            asm("syscall"),
+            asm("add $s0, $v0, $zero"),
+            asm("add $a0, $s0, $zero"),
+            asm("addi $a1, $t0, 0x100"),
+            asm("addi $a2, $zero, 5"),
+            asm("addi $v0, $zero, 15"),    // write
+            asm("syscall"),
+            asm("add $a0, $s0, $zero"),
+            asm("addi $v0, $zero, 16"),    // close
+            asm("syscall"),
+            asm("add $s2, $v0, $zero"),
+            asm("addi $v0, $zero, 16"),    // 閉じたファイルをもう一度close
+            asm("syscall"),
+            asm("add $s3, $v0, $zero"),
+            asm("add $a0, $t0, $zero"),
+            asm("add $a1, $zero, $zero"),
+            asm("addi $v0, $zero, 13"),    // open（読み込み）
+            asm("syscall"),
+            asm("add $a0, $v0, $zero"),
+            asm("addi $a1, $t0, 0x200"),
+            asm("addi $a2, $zero, 100"),
+            asm("addi $v0, $zero, 14"),    // read
+            asm("syscall"),
+            asm("add $s1, $v0, $zero"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        let written = std::fs::read(dir.join("out.txt")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, b"hello");
+        assert_eq!(processor.get_register(16), 3);
+        assert_eq!(processor.get_register(17), 5);
+        assert_eq!((processor.get_register(18), processor.get_register(19)), (0, Word::MAX));
+        assert_eq!(processor.peek_range(0x0080_0200, 5).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_guest_stats_control() {
+        let mut processor = program_processor();
+        let program = [
+            asm("addi $v0, $zero, 113"), // stats_off
+            asm("syscall"),
+            asm("lw $t0, 0x100($zero)"),
+            asm("addi $t1, $zero, 1"),
+            asm("addi $v0, $zero, 112"), // stats_on
+            asm("syscall"),
+            asm("addi $t2, $zero, 2"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // stats_offまでの2命令とstats_onの後の1命令だけが数えられる（stats_onのsyscall自体は止めている間に実行される）
+        assert_eq!(processor.get_stats().instructions_executed, 3);
+        assert_eq!(processor.get_stats().loads_executed, 0);
+        assert_eq!(processor.get_timing_stats().instructions(), 3);
+        // 命令フェッチの最初のミスだけが残り、lwのミスは数えない
+        assert_eq!(processor.get_cache_stats().misses, 1);
+        // バスもlwのラインフィルと止めている間の時間を数えない
+        let bus = processor.get_bus_stats();
+        assert_eq!(bus.fill_bytes, crate::cache::CACHE_LINE_SIZE as u64);
+        assert_eq!(bus.elapsed, processor.get_stats().cycles);
+        assert_eq!(processor.get_register(10), 2);
+
+        // 止めている間に下げた$spはスタックの使用量に数えない
+        let mut processor = program_processor();
+        processor.enable_footprint(4096);
+        let program = [
+            asm("addi $v0, $zero, 113"), // stats_off
+            asm("syscall"),
+            asm("addi $sp, $sp, -64"),
+            asm("addi $sp, $sp, 64"),
+            asm("break"),
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.footprint().unwrap().peak_stack(processor.get_register(29)), 0);
+    }
+
+    #[test]
+    fn test_guest_trace_control() {
+        let program = [
+            asm("j 0x00400008"),
+            asm("break"),
+            asm("addi $v0, $zero, 111"), // trace_off
+            asm("syscall"),
+            asm("j 0x00400018"),
+            asm("break"),
+            asm("addi $v0, $zero, 110"), // trace_on
+            asm("syscall"),
+            asm("addi $t0, $zero, 1"),
+            asm("break"),
+        ];
+        // トレースを止めている間は、フェッチ・分岐・先頭10命令の表示も含めて何も出力せず、trace_onの後の命令から出力する
+        let mut processor = program_processor();
+        processor.set_trace_enabled(false);
+        processor.start_trace_capture();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert!(processor.take_captured_trace().unwrap().starts_with("命令 6: PC=0x00400020\n"));
+
+        // trace_offからtrace_onまでの命令はトレースに現れない
+        let mut processor = program_processor();
+        processor.start_trace_capture();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        let trace = processor.take_captured_trace().unwrap();
+        assert!(trace.contains("[JUMP] From: 0x00400000, To: 0x00400008"));
+        assert!(!trace.contains("0x00400018"));
+        assert!(!trace.contains("PC=0x00400010"));
+        assert!(trace.contains("PC=0x00400020 から命令をフェッチ"));
+    }
+
+    #[test]
+    fn test_mem_trace() {
+        use crate::mem_trace::MemTraceFilter;
+        let program = [
+            asm("addi $t0, $zero, 7"),
+            asm("sw $t0, 0x100($zero)"),
+            asm("sw $t0, 0x200($zero)"),
+            asm("lw $t1, 0x100($zero)"),
+            asm("break"),
+        ];
+        let path = std::env::temp_dir().join(format!("mips_simulator_processor_mem_trace_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let filter = MemTraceFilter { ranges: vec![0x100..0x104, 0x300..0x304], registers: Vec::new() };
+        let mut processor = program_processor();
+        processor.set_mem_trace(Some(MemTraceWriter::create(path, filter).unwrap()));
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.finish_mem_trace(), Some(Ok(2)));
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(text, "pc,address,size,rw,value\n00400004,00000100,4,w,00000007\n0040000c,00000100,4,r,00000007\n");
+    }
+
+    #[test]
+    fn test_memory_protection() {
+        let program = [
+            asm("lui $t0, 0x0040"),
+            asm("lw $t1, 0($t0)"),
+            asm("sw $zero, 4($t0)"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.protect_memory(PC_INITIAL..PC_INITIAL + 0x1000, Protection::parse("rx").unwrap());
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        let outcome = processor.run();
+        assert_eq!(outcome.kind(), "exception.protection_violation");
+        // 書き込みは止められ、テキストは壊れていない
+        assert_eq!(processor.peek_word(PC_INITIAL + 4), Ok(program[1]));
+        assert_eq!(processor.get_register(9), program[0]);
+        assert!(matches!(outcome, RunResult::Exception {
+            error: ProcessorError::MemoryError(MemoryError::ProtectionViolation { addr: 0x00400004, access: Access::Write, size: 4, pc: 0x00400008 }), ..
+        }));
+
+        // 実行を許可しない領域の命令はフェッチできない
+        let mut processor = program_processor();
+        processor.protect_memory(PC_INITIAL..PC_INITIAL + 0x1000, Protection::parse("rw").unwrap());
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert_eq!(processor.run().kind(), "exception.protection_violation");
+    }
+
+    #[test]
+    fn test_out_of_range_access_context() {
+        let program = [
+            asm("lui $t0, 0x0100"),
+            asm("sw $zero, 8($t0)"),
+        ];
+        let mut processor = program_processor();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        let RunResult::Exception { error: ProcessorError::MemoryError(error), .. } = processor.run() else {
+            panic!("範囲外のストアで止まるはず");
+        };
+        assert_eq!(error, MemoryError::AccessOutOfRange { addr: 0x01000008, access: Access::Write, size: 4, pc: 0x00400004 });
+        assert_eq!(error.to_string(), "PC=0x00400004 のストア（4 バイト）でメモリアドレス 0x01000008 が範囲外です");
+
+        // 範囲外へ分岐すると命令フェッチのエラーになる
+        let mut processor = program_processor();
+        processor.load_program(&[asm("lui $t0, 0x0100"), asm("jr $t0")], PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Exception {
+            error: ProcessorError::MemoryError(MemoryError::AccessOutOfRange { addr: 0x01000000, access: Access::Execute, size: 4, pc: 0x01000000 }), ..
+        }));
+    }
+
+    /// オフセット0に書いた下位バイトを送信し、オフセット4で常に送信可能（1）を返すUART
+    #[derive(Debug)]
+    struct TestUart {
+        sent: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+    }
+
+    impl MmioDevice for TestUart {
+        fn name(&self) -> &'static str {
+            "uart"
+        }
+
+        fn read_byte(&mut self, offset: u32) -> u8 {
+            (offset == 4) as u8
+        }
+
+        fn write_byte(&mut self, offset: u32, value: u8) {
+            if offset == 0 {
+                self.sent.borrow_mut().push(value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mmio_device() {
+        let program = [
+            asm("lui $t0, 0xFFFF"),
+            asm("lw $t2, 4($t0)"),
+            asm("addi $t1, $zero, 72"),
+            asm("sw $t1, 0($t0)"),
+            asm("addi $t1, $zero, 105"),
+            asm("sw $t1, 0($t0)"),
+            asm("break"),
+        ];
+        let sent = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut processor = program_processor();
+        processor.map_device(0xFFFF0000..0xFFFF0008, Box::new(TestUart { sent: sent.clone() })).unwrap();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.get_register(10), 1);
+        assert_eq!(*sent.borrow(), b"Hi");
+        // デバイスへのアクセスはキャッシュを経由しない
+        assert_eq!(processor.get_cache_stats().data(), cache::RegionStats::default());
+    }
+
+    #[test]
+    fn test_device_line_not_cached() {
+        // 0x2008 はデバイスと同じラインにあり、そのラインの書き戻しでデバイスに古い値を書き込んではいけない
+        let program = [
+            asm("sw $zero, 0x2008($zero)"),
+            asm("addi $t1, $zero, 72"),
+            asm("sw $t1, 0x2000($zero)"),
+            asm("addi $v0, $zero, 120"), // cache_flush
+            asm("syscall"),
+            asm("break"),
+        ];
+        let sent = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut processor = program_processor();
+        processor.map_device(0x2000..0x2008, Box::new(TestUart { sent: sent.clone() })).unwrap();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(*sent.borrow(), b"H");
+        assert_eq!(processor.get_cache_stats().writebacks, 0);
+    }
+
+    #[test]
+    fn test_cache_flush_invalidate_syscalls() {
+        let program = [
+            asm("addi $t0, $zero, 7"),
+            asm("sw $t0, 0x100($zero)"),
+            asm("addi $v0, $zero, 120"), // cache_flush
+            asm("syscall"),
+            asm("break"),
+            asm("addi $t0, $zero, 9"),
+            asm("sw $t0, 0x100($zero)"),
+            asm("addi $v0, $zero, 121"), // cache_invalidate
+            asm("syscall"),
+            asm("lw $t1, 0x100($zero)"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // 書き戻した値はキャッシュを通さずにメモリから読める
+        assert_eq!(processor.read_memory(0x100), Ok(7));
+        assert_eq!(processor.get_cache_stats().writebacks, 1);
+
+        processor.set_pc(PC_INITIAL + 20);
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // 捨てたダーティラインの値は失われ、メモリから読み直す
+        assert_eq!(processor.get_register(9), 7);
+        assert_eq!(processor.get_cache_stats().writebacks, 1);
+    }
+
+    #[test]
+    fn test_uncached_region_inside_line() {
+        // 0x1004 はキャッシュしない領域と同じラインにあり、フラッシュで 0x1000 に直接書いた値を上書きしてはいけない
+        let program = [
+            asm("addi $t0, $zero, 0x11"),
+            asm("sw $t0, 0x1004($zero)"),
+            asm("addi $t0, $zero, 0x22"),
+            asm("sw $t0, 0x1000($zero)"),
+            asm("addi $v0, $zero, 120"), // cache_flush
+            asm("syscall"),
+            asm("lw $t2, 0x1000($zero)"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        let mut config = CacheConfig::default();
+        config.uncached_regions.push(0x1000..0x1004);
+        processor.set_cache_config(config);
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.get_register(10), 0x22);
+        assert_eq!(processor.read_memory(0x1004), Ok(0x11));
+    }
+
+    #[test]
+    fn test_fetch_and_data_hit_rates() {
+        let program = [
+            asm("lw $t0, 0x100($zero)"),
+            asm("lw $t1, 0x104($zero)"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // 統計はバイト単位で、3命令は同じライン、2つのロードも同じラインに入る
+        let stats = processor.get_cache_stats();
+        assert_eq!(stats.fetch(), cache::RegionStats { hits: 11, misses: 1 });
+        assert_eq!(stats.data(), cache::RegionStats { hits: 7, misses: 1 });
+        assert_eq!(stats.hits, 18);
+    }
+
+    #[test]
+    fn test_writeback_latency() {
+        use crate::timing::DEFAULT_MEMORY_LATENCY;
+        // 0x100と同じセットに入る4つのラインを読み、ダーティな0x100のラインを追い出す
+        let program = [
+            asm("sw $zero, 0x100($zero)"),
+            asm("lw $t0, 0x900($zero)"),
+            asm("lw $t0, 0x1100($zero)"),
+            asm("lw $t0, 0x1900($zero)"),
+            asm("lw $t0, 0x2100($zero)"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.set_timing_config(TimingConfig { writeback_latency: 7, ..TimingConfig::default() });
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(processor.get_cache_stats().writebacks, 1);
+        // 命令フェッチ1回・ストア1回・ロード4回のミスと、1回の書き戻し
+        let timing = processor.get_timing_stats();
+        assert_eq!(timing.memory_stall_cycles(), 6 * DEFAULT_MEMORY_LATENCY + 7);
+        assert_eq!(timing.stalls_by_cause.get(&StallCause::Load), Some(&(4 * DEFAULT_MEMORY_LATENCY + 7)));
+        assert_eq!(timing.compute_cycles(), timing.instructions());
+    }
+
+    #[test]
+    fn test_branch_prediction_penalty() {
+        use crate::branch_predictor::PredictorKind;
+        use crate::timing::DEFAULT_MISPREDICT_PENALTY;
+        // 10回まわるループ: 後方分岐は最後の1回だけ不成立
+        let program = [
+            asm("addi $t0, $zero, 10"),
+            asm("addi $t0, $t0, -1"),
+            asm("bne $t0, $zero, -1"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.set_branch_predictors(BranchPredictors::new(&[PredictorKind::BackwardTaken, PredictorKind::NotTaken]));
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+
+        let stats = processor.predictors.stats();
+        assert_eq!((stats[0].1.branches, stats[0].1.correct), (10, 9));
+        assert_eq!((stats[1].1.branches, stats[1].1.correct), (10, 1));
+        // タイミングには最初の予測器の予測ミス1回分だけが加わる
+        assert_eq!(processor.get_timing_stats().stalls_by_cause.get(&StallCause::BranchMispredict), Some(&DEFAULT_MISPREDICT_PENALTY));
+        assert!(processor.stats_report().to_json().contains("\"branch_predictor.backward-taken.accuracy\": 0.9"));
+    }
+
+    #[test]
+    fn test_microarch_snapshot_predictors() {
+        use crate::branch_predictor::PredictorKind;
+        let program = [
+            asm("addi $t0, $zero, 10"),
+            asm("addi $t0, $t0, -1"),
+            asm("bne $t0, $zero, -1"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.set_branch_predictors(BranchPredictors::new(&[PredictorKind::Gshare, PredictorKind::Bimodal]));
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        let cold = processor.save_microarch_state();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        let warm = processor.save_microarch_state();
+        assert_ne!(warm.predictors, cold.predictors);
+
+        // コールドに戻すと予測器も初期状態になり、ウォームに戻すと学習した表と履歴に戻る
+        processor.restore_microarch_state(&MicroarchSnapshot::cold()).unwrap();
+        assert_eq!(processor.save_microarch_state().predictors, cold.predictors);
+        processor.restore_microarch_state(&warm).unwrap();
+        assert_eq!(processor.save_microarch_state().predictors, warm.predictors);
+
+        // ファイルのスナップショットにも含まれ、予測器の組が異なれば読み込めない
+        let bytes = processor.save_snapshot();
+        let mut restored = program_processor();
+        restored.set_branch_predictors(BranchPredictors::new(&[PredictorKind::Gshare, PredictorKind::Bimodal]));
+        restored.restore_snapshot(&bytes).unwrap();
+        assert_eq!(restored.save_microarch_state().predictors, warm.predictors);
+        let mut other = program_processor();
+        other.set_branch_predictors(BranchPredictors::new(&[PredictorKind::Gshare]));
+        assert!(matches!(other.restore_snapshot(&bytes), Err(SnapshotError::Mismatch(_))));
+    }
+
+    #[test]
+    fn test_taken_branches_are_retired() {
+        let mut processor = program_processor();
+        processor.load_program(&[
+            asm("addi $t0, $zero, 3"),
+            asm("addi $t0, $t0, -1"),
+            asm("bne $t0, $zero, -1"),
+            asm("j 0x00400010"),
+            asm("break"),
+        ], PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // 成立した分岐・ジャンプも完了した命令として数え、PCの書き換えは別に数える（breakは完了しない）
+        let stats = processor.get_stats();
+        assert_eq!(stats.instructions_executed, 1 + 3 * 2 + 1);
+        assert_eq!((stats.branches_taken, stats.redirections), (3, 3));
+        assert_eq!(stats.instructions_executed, processor.get_timing_stats().instructions());
+    }
+
+    #[test]
+    fn test_latency_config_cycles() {
+        let program = [
+            asm("addi $t0, $zero, 7"),
+            asm("sw $t0, 0x1000($zero)"),
+            asm("lw $t1, 0x1000($zero)"),
+            asm("mul.s $f2, $f0, $f0"),
+            asm("break"),
+        ];
+        let mut processor = program_processor();
+        processor.set_timing_config(TimingConfig {
+            mul_div_latency: 5,
+            cache_hit_latency: 2,
+            cache_miss_latency: 3,
+            memory_latency: 20,
+            ..TimingConfig::default()
+        });
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert!(matches!(processor.run(), RunResult::Breakpoint { .. }));
+        // addi: 1 + フェッチミス23、sw: 1 + 2 + ミス23、lw: 1 + 2、mul.s: 5（breakは例外で止まるので数えない）
+        let stats = processor.get_stats();
+        assert_eq!(stats.cycles, 24 + 26 + 3 + 5);
+        assert_eq!(stats.cycles, processor.get_timing_stats().total_cycles);
+        assert!((stats.cpi() - 58.0 / 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_invalid_instruction_diagnostics() {
+        // addi $v0, $zero, 1 ; add $1, $2, $3 ; 0 ; 未定義のopcode 0x3F
+        let mut processor = program_processor();
+        processor.load_program(&[0x20020001, 0x00430820, 0x00000000, 0xFC000000], PC_INITIAL).unwrap();
+        let info = match processor.run() {
+            RunResult::Exception { error: ProcessorError::InvalidInstruction(info), .. } => info,
+            other => panic!("無効な命令の例外になるはず: {:?}", other),
+        };
+
+        assert_eq!(info.pc, PC_INITIAL + 12);
+        assert_eq!(info.fields[0], ("opcode", 0x3F));
+        // 0のワードは飛ばして直前の有効な命令を探す
+        assert_eq!(info.preceding_valid, Some((PC_INITIAL + 4, 0x00430820, "add $1, $2, $3".to_string())));
+        assert!(info.to_string().contains("[2 ワード前]"));
+        assert_eq!(info.unimplemented, None);
+    }
+
+    #[test]
+    fn test_unimplemented_instruction_report() {
+        // addi $t1, $zero, 3 ; mul $t0, $t1, $t1 ; addu $v0, $t0, $t0 ; mul $t0, $t1, $t1
+        let mut processor = program_processor();
+        processor.load_program(&[0x20090003, 0x71294002, 0x01081021, 0x71294002], PC_INITIAL).unwrap();
+        let outcome = processor.run();
+        assert_eq!(outcome.kind(), "exception.unimplemented_instruction");
+        assert!(outcome.to_string().contains("未実装の命令 mul"));
+        assert_eq!(processor.unimplemented_instructions(), vec![("mul", 2), ("addu", 1)]);
+    }
+
+    #[test]
+    fn test_rv32i_program() {
+        let mut processor = program_processor();
+        processor.set_isa(Isa::Rv32i);
+        assert_eq!(processor.get_register(2), SP_INITIAL);
+        let program = [
+            0x00000513, // addi a0, zero, 0
+            0x00500293, // addi t0, zero, 5
+            0x00550533, // loop: add a0, a0, t0
+            0xFFF28293, // addi t0, t0, -1
+            0xFE029CE3, // bne t0, zero, loop
+            0xFFF00313, // addi t1, zero, -1
+            0x10600023, // sb t1, 0x100(zero)
+            0x10004383, // lbu t2, 0x100(zero)
+            0x10000E03, // lb t3, 0x100(zero)
+            0x00100073, // ebreak
+        ];
+        processor.load_program(&program, PC_INITIAL).unwrap();
+
+        assert_eq!(processor.run(), RunResult::Breakpoint { pc: PC_INITIAL + 36, code: 0 });
+        assert_eq!(processor.get_register(10), 15);
+        assert_eq!(processor.get_register(7), 0xFF);
+        assert_eq!(processor.get_register(28), 0xFFFFFFFF);
+    }
 }
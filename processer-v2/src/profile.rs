@@ -0,0 +1,267 @@
+//! 命令アドレスごとの実行回数の集計（手で最適化するループを探すためのホットスポット）と、
+//! 呼び出し・復帰の追跡による関数ごとの命令数・サイクル数の集計
+//!
+//! 関数は呼び出し先のアドレスで区別し、表示するときにシンボル表があればシンボル名を付ける。
+//! 再帰呼び出しでは、スタック上の最も外側の呼び出しだけを累積（inclusive）に数える。
+//! 呼び出し中の関数はバックトレースと同じシャドウコールスタック（call_stack）で追跡する。
+
+use std::collections::HashMap;
+
+use crate::call_stack::{CallFrame, CallStack};
+use crate::instructions::Instruction;
+use crate::memory::MemoryAddress;
+
+/// ホットスポットとして表示する件数のデフォルト値
+pub const DEFAULT_PROFILE_TOP: usize = 20;
+
+/// 命令アドレスごとの実行回数
+#[derive(Debug, Clone, Default)]
+pub struct HotSpotProfiler {
+    /// アドレスごとの（最後に実行した命令, 実行回数）
+    counts: HashMap<MemoryAddress, (Instruction, u64)>,
+    /// 記録した命令数の合計
+    total: u64,
+}
+
+impl HotSpotProfiler {
+    /// 1命令の実行を記録する
+    pub fn record(&mut self, pc: MemoryAddress, instruction: Instruction) {
+        let entry = self.counts.entry(pc).or_insert((instruction, 0));
+        entry.0 = instruction;
+        entry.1 += 1;
+        self.total += 1;
+    }
+
+    /// 記録を消去する
+    pub fn reset(&mut self) {
+        self.counts.clear();
+        self.total = 0;
+    }
+
+    /// 記録した命令数の合計
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// 実行回数の多い順に上位count件（アドレス, 命令, 実行回数）
+    pub fn top(&self, count: usize) -> Vec<(MemoryAddress, Instruction, u64)> {
+        let mut entries: Vec<(MemoryAddress, Instruction, u64)> = self.counts.iter()
+            .map(|(&pc, &(instruction, executions))| (pc, instruction, executions))
+            .collect();
+        entries.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        entries.truncate(count);
+        entries
+    }
+
+    /// 人が読める形式のレポートを作成する（アドレスと命令の表示方法は呼び出し側が決める）
+    pub fn report<A, D>(&self, count: usize, format_address: A, disassemble: D) -> String
+    where
+        A: Fn(MemoryAddress) -> String,
+        D: Fn(MemoryAddress, Instruction) -> String,
+    {
+        let total = self.total();
+        let mut result = format!("実行命令数: {} (アドレス数: {})", total, self.counts.len());
+        for (pc, instruction, executions) in self.top(count) {
+            let percent = executions as f64 / total as f64 * 100.0;
+            result.push_str(&format!("\n  {:>12} ({:>6.2}%) {}: {}",
+                executions, percent, format_address(pc), disassemble(pc, instruction)));
+        }
+        result
+    }
+}
+
+/// 命令が関数の呼び出し・復帰であるか
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallEvent {
+    /// 呼び出し（jal など、戻りアドレスをレジスタに保存する命令）
+    Call { return_address: MemoryAddress },
+    /// 復帰（jr $ra など）
+    Return,
+}
+
+/// 関数ごとの集計
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionStats {
+    /// 呼び出し回数
+    pub calls: u64,
+    /// その関数自身で実行した命令数
+    pub self_instructions: u64,
+    /// その関数自身で実行した命令のサイクル数
+    pub self_cycles: u64,
+    /// 呼び出した関数の分を含む命令数
+    pub inclusive_instructions: u64,
+    /// 呼び出した関数の分を含むサイクル数
+    pub inclusive_cycles: u64,
+}
+
+/// 関数ごとの命令数・サイクル数
+#[derive(Debug, Clone, Default)]
+pub struct FunctionProfiler {
+    /// 関数の先頭アドレスごとの集計（呼び出し中の関数の累積は含まない）
+    functions: HashMap<MemoryAddress, FunctionStats>,
+    /// 最初に実行した命令を含む関数（呼び出されたものとして扱い、スタックには積まない）
+    root: Option<MemoryAddress>,
+    /// 記録した命令数の合計
+    instructions: u64,
+    /// 記録したサイクル数の合計
+    cycles: u64,
+    /// リセットしても戻さない命令数とサイクル数（スタックの呼び出しに記録し、呼び出しからの累積を求める）
+    clock: (u64, u64),
+}
+
+impl FunctionProfiler {
+    /// pcの命令の実行を記録し、呼び出し・復帰をstackに反映する（next_pcは実行後のPC）
+    pub fn record(&mut self, pc: MemoryAddress, next_pc: MemoryAddress, cycles: u64, event: Option<CallEvent>, stack: &mut CallStack) {
+        // 最初に実行した命令を含む関数を呼び出されたものとして扱う
+        let root = *self.root.get_or_insert_with(|| {
+            self.functions.entry(pc).or_default().calls += 1;
+            pc
+        });
+        let current = stack.current().map_or(root, |frame| frame.entry);
+        let stats = self.functions.entry(current).or_default();
+        stats.self_instructions += 1;
+        stats.self_cycles += cycles;
+        self.instructions += 1;
+        self.cycles += cycles;
+        self.clock = (self.clock.0 + 1, self.clock.1 + cycles);
+
+        // 戻り先に対応する呼び出しまでスタックを戻す（対応しない復帰は無視する）
+        let returned = stack.update(event, next_pc, self.clock);
+        if let Some(CallEvent::Call { .. }) = event {
+            self.functions.entry(next_pc).or_default().calls += 1;
+        }
+        // 内側の呼び出しから順に、外側で同じ関数を呼び出し中でなければ累積に数える
+        for (depth, frame) in returned.iter().enumerate().rev() {
+            let nested = self.root == Some(frame.entry)
+                || stack.frames().chain(&returned[..depth]).any(|outer| outer.entry == frame.entry);
+            if !nested {
+                let (instructions, cycles) = self.since(frame);
+                let stats = self.functions.entry(frame.entry).or_default();
+                stats.inclusive_instructions += instructions;
+                stats.inclusive_cycles += cycles;
+            }
+        }
+    }
+
+    /// スタックの呼び出しに記録する命令数とサイクル数
+    pub fn counts(&self) -> (u64, u64) {
+        self.clock
+    }
+
+    /// frameの呼び出しから今までの命令数とサイクル数（リセットより前の分は含めない）
+    fn since(&self, frame: &CallFrame) -> (u64, u64) {
+        ((self.clock.0 - frame.counts.0).min(self.instructions), (self.clock.1 - frame.counts.1).min(self.cycles))
+    }
+
+    /// 記録を消去する（呼び出し中の関数はスタックに残る）
+    pub fn reset(&mut self) {
+        *self = Self { clock: self.clock, ..Self::default() };
+    }
+
+    /// 関数ごとの集計（stackで呼び出し中の関数の累積を含む、自身のサイクル数の多い順）
+    pub fn functions(&self, stack: &CallStack) -> Vec<(MemoryAddress, FunctionStats)> {
+        let mut functions = self.functions.clone();
+        if let Some(root) = self.root {
+            let stats = functions.entry(root).or_default();
+            stats.inclusive_instructions += self.instructions;
+            stats.inclusive_cycles += self.cycles;
+        }
+        let frames: Vec<&CallFrame> = stack.frames().collect();
+        for (depth, frame) in frames.iter().enumerate() {
+            if self.root != Some(frame.entry) && frames[..depth].iter().all(|outer| outer.entry != frame.entry) {
+                let (instructions, cycles) = self.since(frame);
+                let stats = functions.entry(frame.entry).or_default();
+                stats.inclusive_instructions += instructions;
+                stats.inclusive_cycles += cycles;
+            }
+        }
+        let mut functions: Vec<(MemoryAddress, FunctionStats)> = functions.into_iter().collect();
+        functions.sort_by(|a, b| b.1.self_cycles.cmp(&a.1.self_cycles).then(a.0.cmp(&b.0)));
+        functions
+    }
+
+    /// 人が読める形式のフラットプロファイルを作成する（関数の表示方法は呼び出し側が決める）
+    pub fn report<A: Fn(MemoryAddress) -> String>(&self, stack: &CallStack, format_address: A) -> String {
+        let percent = |part: u64, total: u64| if total == 0 { 0.0 } else { part as f64 / total as f64 * 100.0 };
+        let mut result = format!("実行命令数: {}, サイクル数: {}\n", self.instructions, self.cycles);
+        result.push_str(&format!("  {:>10} {:>12} {:>8} {:>12} {:>8} {:>12} {:>8}  関数",
+            "呼び出し", "自身の命令", "%", "自身のサイクル", "%", "累積サイクル", "%"));
+        for (entry, stats) in self.functions(stack) {
+            result.push_str(&format!("\n  {:>10} {:>12} {:>7.2}% {:>12} {:>7.2}% {:>12} {:>7.2}%  {}",
+                stats.calls,
+                stats.self_instructions, percent(stats.self_instructions, self.instructions),
+                stats.self_cycles, percent(stats.self_cycles, self.cycles),
+                stats.inclusive_cycles, percent(stats.inclusive_cycles, self.cycles),
+                format_address(entry)));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_spots() {
+        let mut profiler = HotSpotProfiler::default();
+        profiler.record(0x00400000, 0x11);
+        for _ in 0..3 {
+            profiler.record(0x00400008, 0x22);
+            profiler.record(0x00400004, 0x33);
+        }
+        assert_eq!(profiler.total(), 7);
+        // 実行回数が同じならアドレス順
+        assert_eq!(profiler.top(2), vec![(0x00400004, 0x33, 3), (0x00400008, 0x22, 3)]);
+
+        let report = profiler.report(1, |pc| format!("0x{:08X}", pc), |_, instruction| format!("op{}", instruction));
+        assert_eq!(report, "実行命令数: 7 (アドレス数: 3)\n             3 ( 42.86%) 0x00400004: op51");
+
+        profiler.reset();
+        assert!(profiler.top(10).is_empty());
+    }
+
+    #[test]
+    fn test_function_profile() {
+        // main(0x100) -> f(0x200) -> f(0x200) の再帰呼び出し。命令ごとに2サイクル
+        let call = |return_address| Some(CallEvent::Call { return_address });
+        let mut profiler = FunctionProfiler::default();
+        let mut stack = CallStack::default();
+        profiler.record(0x100, 0x104, 2, None, &mut stack);
+        profiler.record(0x104, 0x200, 2, call(0x108), &mut stack);
+        profiler.record(0x200, 0x204, 2, None, &mut stack);
+        profiler.record(0x204, 0x200, 2, call(0x208), &mut stack);
+        profiler.record(0x200, 0x208, 2, Some(CallEvent::Return), &mut stack);
+        profiler.record(0x208, 0x108, 2, Some(CallEvent::Return), &mut stack);
+        profiler.record(0x108, 0x10C, 2, None, &mut stack);
+
+        let functions = profiler.functions(&stack);
+        assert_eq!(functions[0], (0x200, FunctionStats {
+            calls: 2,
+            self_instructions: 4,
+            self_cycles: 8,
+            inclusive_instructions: 4,
+            inclusive_cycles: 8,
+        }));
+        assert_eq!(functions[1], (0x100, FunctionStats {
+            calls: 1,
+            self_instructions: 3,
+            self_cycles: 6,
+            inclusive_instructions: 7,
+            inclusive_cycles: 14,
+        }));
+
+        // 対応する呼び出しのない復帰では関数を抜けない
+        profiler.record(0x10C, 0x300, 2, Some(CallEvent::Return), &mut stack);
+        profiler.record(0x300, 0x304, 2, None, &mut stack);
+        assert_eq!(profiler.functions(&stack)[0].0, 0x100);
+        assert!(profiler.report(&stack, |entry| format!("0x{:08X}", entry)).ends_with("  0x00000200"));
+
+        // リセットの後は、呼び出し中の関数の累積もリセットの後の分だけを数える
+        profiler.record(0x304, 0x200, 2, call(0x308), &mut stack);
+        profiler.reset();
+        profiler.record(0x200, 0x204, 2, None, &mut stack);
+        let functions = profiler.functions(&stack);
+        assert_eq!(functions.iter().find(|(entry, _)| *entry == 0x200).unwrap().1.inclusive_instructions, 1);
+    }
+}
@@ -0,0 +1,157 @@
+//! Intel HEX と Motorola S-record の読み込み（FPGAの書き込みツールが出力するファイルをそのまま実行するため）
+//!
+//! どちらもレコードに埋め込まれたアドレスにデータを置く。連続するレコードは1つのセグメントにまとめる。
+//! Intel HEX はデータ(00)・終了(01)・拡張セグメントアドレス(02)・開始セグメントアドレス(03)・
+//! 拡張リニアアドレス(04)・開始リニアアドレス(05)の各レコードに、
+//! S-record は S0（ヘッダ）・S1〜S3（データ）・S5/S6（レコード数）・S7〜S9（開始アドレス）に対応する。
+//! 開始アドレスは、読み込んだセグメントのどれかに含まれる場合だけ使う（S9 0000 のような形だけの開始アドレスを無視するため）。
+
+use crate::loader::{LoadImage, Segment};
+use crate::memory::MemoryAddress;
+
+/// Intel HEX を解析する
+pub fn parse_intel_hex(text: &str) -> Result<LoadImage, String> {
+    let mut builder = SegmentBuilder::default();
+    let mut base: MemoryAddress = 0;
+    let mut entry = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("{} 行目: {}: {}", index + 1, message, line);
+        let record = line.strip_prefix(':').ok_or_else(|| error("レコードは ':' で始めてください"))?;
+        let bytes = decode_record(record).map_err(|e| error(&e))?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(error("レコードの長さが不正です"));
+        }
+        if bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+            return Err(error("チェックサムが一致しません"));
+        }
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as MemoryAddress;
+        let data = &bytes[4..bytes.len() - 1];
+        let value = || -> Result<u32, String> {
+            match data.len() {
+                2 => Ok(u16::from_be_bytes([data[0], data[1]]) as u32),
+                4 => Ok(u32::from_be_bytes([data[0], data[1], data[2], data[3]])),
+                _ => Err(error("アドレスのレコードの長さが不正です")),
+            }
+        };
+        match bytes[3] {
+            0x00 => builder.push(base.wrapping_add(offset), data),
+            0x01 => break,
+            0x02 => base = value()? << 4,
+            0x03 => {
+                let start = value()?;
+                entry = Some((start >> 16 << 4).wrapping_add(start & 0xFFFF));
+            }
+            0x04 => base = value()? << 16,
+            0x05 => entry = Some(value()?),
+            kind => return Err(error(&format!("未対応のレコードの種類です: {:02X}", kind))),
+        }
+    }
+    builder.finish(entry)
+}
+
+/// Motorola S-record を解析する
+pub fn parse_srec(text: &str) -> Result<LoadImage, String> {
+    let mut builder = SegmentBuilder::default();
+    let mut entry = None;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("{} 行目: {}: {}", index + 1, message, line);
+        let kind = line.strip_prefix('S')
+            .and_then(|rest| rest.chars().next())
+            .and_then(|kind| kind.to_digit(10))
+            .ok_or_else(|| error("レコードは S0〜S9 で始めてください"))?;
+        let bytes = decode_record(&line[2..]).map_err(|e| error(&e))?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(error("レコードの長さが不正です"));
+        }
+        if bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0xFF {
+            return Err(error("チェックサムが一致しません"));
+        }
+        let address_length = match kind {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(error(&format!("未対応のレコードの種類です: S{}", kind))),
+        };
+        if bytes.len() < address_length + 2 {
+            return Err(error("レコードの長さが不正です"));
+        }
+        let address = bytes[1..=address_length].iter().fold(0, |address: MemoryAddress, &byte| address << 8 | byte as MemoryAddress);
+        match kind {
+            1..=3 => builder.push(address, &bytes[address_length + 1..bytes.len() - 1]),
+            7..=9 => entry = Some(address),
+            _ => {}
+        }
+    }
+    builder.finish(entry)
+}
+
+/// 16進数の文字列をバイト列に変換する
+fn decode_record(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err("16進数の文字が偶数個並んでいません".to_string());
+    }
+    Ok((0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect())
+}
+
+/// レコードのデータを連続するセグメントにまとめる
+#[derive(Default)]
+struct SegmentBuilder {
+    segments: Vec<Segment>,
+}
+
+impl SegmentBuilder {
+    fn push(&mut self, address: MemoryAddress, data: &[u8]) {
+        match self.segments.last_mut() {
+            Some(last) if last.end() == address as u64 => last.data.extend_from_slice(data),
+            _ => self.segments.push(Segment { address, data: data.to_vec(), executable: true }),
+        }
+    }
+
+    fn finish(self, entry: Option<MemoryAddress>) -> Result<LoadImage, String> {
+        if self.segments.is_empty() {
+            return Err("データのレコードがありません".to_string());
+        }
+        let entry = entry.filter(|&entry| self.segments.iter()
+            .any(|segment| segment.address <= entry && (entry as u64) < segment.end()));
+        Ok(LoadImage { segments: self.segments, entry, symbols: Vec::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intel_hex() {
+        let text = ":020000040040BA\n:080000002A0008200D00000099\n:0400000500400000B7\n:00000001FF\n";
+        let image = parse_intel_hex(text).unwrap();
+        assert_eq!(image.segments, vec![
+            Segment { address: 0x00400000, data: vec![0x2A, 0x00, 0x08, 0x20, 0x0D, 0, 0, 0], executable: true },
+        ]);
+        assert_eq!(image.entry, Some(0x00400000));
+        assert!(parse_intel_hex(":080000002A0008200D0000009A").unwrap_err().contains("チェックサム"));
+        assert!(parse_intel_hex("080000002A0008200D00000099").is_err());
+        assert!(parse_intel_hex(":00000001FF").is_err());
+    }
+
+    #[test]
+    fn test_srec() {
+        let text = "S00600004844521B\nS309004000002A00082064\nS309004000040D000000A5\nS70500000000FA\n";
+        let image = parse_srec(text).unwrap();
+        assert_eq!(image.segments, vec![
+            Segment { address: 0x00400000, data: vec![0x2A, 0x00, 0x08, 0x20, 0x0D, 0, 0, 0], executable: true },
+        ]);
+        // 読み込んだ範囲の外を指す開始アドレスは使わない
+        assert_eq!(image.entry, None);
+        assert!(parse_srec("S309004000002A00082065").unwrap_err().contains("チェックサム"));
+        assert!(parse_srec("S4030000FC").is_err());
+    }
+}
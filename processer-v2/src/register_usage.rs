@@ -0,0 +1,113 @@
+//! アーキテクチャレジスタごとの読み出し・書き込み回数の集計
+//! （レジスタファイルのポート数の見積もりや、コンパイラのレジスタ割り付けの確認に使う）
+//!
+//! レジスタは PipelineOp と同じ番号付け（浮動小数点レジスタは FP_REGISTER_BASE を足した番号）で数え、
+//! $zero（x0）への読み書きは数えない。同じ命令が同じレジスタを2回読む場合は2回と数える。
+
+use crate::pipeline::{PipelineOp, FP_REGISTER_BASE};
+
+/// 集計するレジスタ数（整数レジスタ32本と浮動小数点レジスタ32本）
+const OPERAND_COUNT: usize = 64;
+
+/// レジスタごとの読み出し・書き込み回数
+#[derive(Debug, Clone)]
+pub struct RegisterUsage {
+    /// レジスタごとの読み出し回数
+    reads: [u64; OPERAND_COUNT],
+    /// レジスタごとの書き込み回数
+    writes: [u64; OPERAND_COUNT],
+    /// 記録した命令数
+    instructions: u64,
+    /// 1命令で読んだレジスタ数の最大値
+    max_reads: usize,
+}
+
+impl Default for RegisterUsage {
+    fn default() -> Self {
+        Self { reads: [0; OPERAND_COUNT], writes: [0; OPERAND_COUNT], instructions: 0, max_reads: 0 }
+    }
+}
+
+impl RegisterUsage {
+    /// 完了した1命令の読み書きを記録する
+    pub fn record(&mut self, op: &PipelineOp) {
+        for &reg in &op.sources {
+            self.reads[reg as usize] += 1;
+        }
+        if let Some(reg) = op.dest {
+            self.writes[reg as usize] += 1;
+        }
+        self.instructions += 1;
+        self.max_reads = self.max_reads.max(op.sources.len());
+    }
+
+    /// 記録を消去する
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// レジスタの読み出し回数
+    pub fn reads(&self, reg: u8) -> u64 {
+        self.reads[reg as usize]
+    }
+
+    /// レジスタの書き込み回数
+    pub fn writes(&self, reg: u8) -> u64 {
+        self.writes[reg as usize]
+    }
+
+    /// 一度でも読み書きしたレジスタ（番号順）
+    pub fn used_registers(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..OPERAND_COUNT as u8).filter(|&reg| self.reads(reg) > 0 || self.writes(reg) > 0)
+    }
+
+    /// 機械可読なレポートで使うレジスタの名前（整数レジスタは r8、浮動小数点レジスタは f0 など）
+    pub fn key_name(reg: u8) -> String {
+        match reg.checked_sub(FP_REGISTER_BASE) {
+            Some(fp) => format!("f{}", fp),
+            None => format!("r{}", reg),
+        }
+    }
+
+    /// 人が読める形式のレポートを作成する（レジスタの表示方法は呼び出し側が決める）
+    pub fn report<R: Fn(u8) -> String>(&self, register_name: R) -> String {
+        let total_reads: u64 = self.reads.iter().sum();
+        let total_writes: u64 = self.writes.iter().sum();
+        let per_instruction = |count: u64| if self.instructions == 0 { 0.0 } else { count as f64 / self.instructions as f64 };
+        let mut result = format!("命令数: {}, 読み出し: {} (命令あたり {:.2}, 最大 {}), 書き込み: {} (命令あたり {:.2})\n",
+            self.instructions, total_reads, per_instruction(total_reads), self.max_reads,
+            total_writes, per_instruction(total_writes));
+        result.push_str(&format!("  {:<8} {:>12} {:>12}", "レジスタ", "読み出し", "書き込み"));
+        for reg in self.used_registers() {
+            result.push_str(&format!("\n  {:<8} {:>12} {:>12}", register_name(reg), self.reads(reg), self.writes(reg)));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::InstructionType;
+
+    #[test]
+    fn test_register_usage() {
+        let mut usage = RegisterUsage::default();
+        // add $t0, $t1, $t1 と add.s $f2, $f0, $f1
+        usage.record(&PipelineOp::from_mips(&InstructionType::Add { rd: 8, rs: 9, rt: 9 }));
+        usage.record(&PipelineOp::from_mips(&InstructionType::AddS { fd: 2, fs: 0, ft: 1 }));
+        // $zeroへの書き込みは数えない
+        usage.record(&PipelineOp::from_mips(&InstructionType::Addi { rt: 0, rs: 8, imm: 1 }));
+        assert_eq!((usage.reads(9), usage.writes(8), usage.reads(8)), (2, 1, 1));
+        assert_eq!(usage.writes(FP_REGISTER_BASE + 2), 1);
+        assert_eq!(usage.used_registers().collect::<Vec<u8>>(), vec![8, 9, 32, 33, 34]);
+        assert_eq!(RegisterUsage::key_name(34), "f2");
+
+        let report = usage.report(|reg| format!("${}", reg));
+        assert!(report.starts_with("命令数: 3, 読み出し: 5 (命令あたり 1.67, 最大 2), 書き込み: 2 (命令あたり 0.67)"), "{}", report);
+        assert!(report.ends_with("\n  $34                 0            1"), "{}", report);
+
+        usage.reset();
+        assert_eq!(usage.used_registers().count(), 0);
+    }
+}
@@ -0,0 +1,203 @@
+//! 決定的な再実行のための入力の記録と再生（ほかの人が報告した再現しにくい不具合を手元で再現するため）
+//!
+//! ホストから値を受け取るシステムコール（標準入力の読み込み、ホストのファイル操作）の結果を、
+//! 記録時は1件ずつ覚えておき、再生時はホストに問い合わせずに記録した結果を返す。
+//! ゲストが記録と異なるシステムコールを呼んだら、実行が食い違ったものとして止める。
+//!
+//! ファイルは1行1件のテキストで、`<番号> <戻り値|-> [<アドレス>:<16進のバイト列>]` の形式。
+//! アドレスとバイト列は、そのシステムコールがゲストのメモリに書き込んだ内容を表す。
+
+use crate::instructions::Word;
+use crate::memory::MemoryAddress;
+
+/// ファイルの先頭行
+const REPLAY_HEADER: &str = "# mips_simulator replay v1";
+
+/// 記録したシステムコール1件
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayEvent {
+    /// システムコール番号
+    pub syscall: Word,
+    /// 戻り値（戻り値のないシステムコールはNone）
+    pub result: Option<Word>,
+    /// ゲストのメモリに書き込んだ内容（アドレス, バイト列）
+    pub data: Option<(MemoryAddress, Vec<u8>)>,
+}
+
+/// 結果を記録・再生するシステムコールか
+/// （read_int, read_string, read_char, open, read, close と、標準出力・標準エラー出力以外への write）
+pub fn is_recorded_syscall(number: Word, args: [Word; 3]) -> bool {
+    match number {
+        5 | 8 | 12 | 13 | 14 | 16 => true,
+        15 => !matches!(args[0], 1 | 2),
+        _ => false,
+    }
+}
+
+/// 入力の記録または再生
+#[derive(Debug, Clone)]
+pub struct ReplayLog {
+    /// 再生中か（falseなら記録中）
+    replaying: bool,
+    events: Vec<ReplayEvent>,
+    /// 次に再生する記録
+    position: usize,
+    /// 記録中のシステムコールを実行しているか
+    in_syscall: bool,
+    /// 記録中のシステムコールがメモリに書き込んだ内容
+    data: Option<(MemoryAddress, Vec<u8>)>,
+}
+
+impl ReplayLog {
+    /// 空の記録を作成する
+    pub fn recording() -> Self {
+        Self { replaying: false, events: Vec::new(), position: 0, in_syscall: false, data: None }
+    }
+
+    /// 記録したファイルの内容を解析し、再生する記録を作成する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(REPLAY_HEADER) {
+            return Err(format!("入力の記録ファイルではありません（先頭行が '{}' ではありません）", REPLAY_HEADER));
+        }
+        let mut events = Vec::new();
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            events.push(parse_event(line).ok_or_else(|| format!("入力の記録の {} 行目が不正です: '{}'", index + 1, line))?);
+        }
+        Ok(Self { replaying: true, events, ..Self::recording() })
+    }
+
+    /// 記録したファイルを読み込み、再生する記録を作成する
+    pub fn load_file(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("入力の記録 '{}' を読み込めません: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    /// 再生中か
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// 記録するシステムコールの実行を始める
+    pub fn begin(&mut self) {
+        self.in_syscall = true;
+        self.data = None;
+    }
+
+    /// 実行中のシステムコールがメモリに書き込んだ内容を記録する（記録中のシステムコールの実行中でなければ何もしない）
+    pub fn record_data(&mut self, address: MemoryAddress, bytes: &[u8]) {
+        if self.in_syscall {
+            self.data = Some((address, bytes.to_vec()));
+        }
+    }
+
+    /// システムコールの実行を終え、結果を記録する
+    pub fn commit(&mut self, syscall: Word, result: Option<Word>) {
+        self.in_syscall = false;
+        self.events.push(ReplayEvent { syscall, result, data: self.data.take() });
+    }
+
+    /// 次に再生する記録を取り出す（番号が異なるか記録が尽きていれば、再生すべき番号を返す）
+    pub fn next(&mut self, syscall: Word) -> Result<ReplayEvent, Option<Word>> {
+        match self.events.get(self.position) {
+            Some(event) if event.syscall == syscall => {
+                self.position += 1;
+                Ok(event.clone())
+            }
+            event => Err(event.map(|event| event.syscall)),
+        }
+    }
+
+    /// 再生した記録の数
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 再生していない記録の数
+    pub fn remaining(&self) -> usize {
+        self.events.len() - self.position
+    }
+
+    /// ファイルに書き出す形式
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", REPLAY_HEADER);
+        for event in &self.events {
+            text.push_str(&event.syscall.to_string());
+            match event.result {
+                Some(result) => text.push_str(&format!(" {}", result)),
+                None => text.push_str(" -"),
+            }
+            if let Some((address, bytes)) = &event.data {
+                text.push_str(&format!(" 0x{:08X}:", address));
+                for byte in bytes {
+                    text.push_str(&format!("{:02x}", byte));
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn parse_event(line: &str) -> Option<ReplayEvent> {
+    let mut parts = line.split_whitespace();
+    let syscall = parts.next()?.parse().ok()?;
+    let result = match parts.next()? {
+        "-" => None,
+        result => Some(result.parse().ok()?),
+    };
+    let data = match parts.next() {
+        Some(data) => {
+            let (address, hex) = data.split_once(':')?;
+            let address = MemoryAddress::from_str_radix(address.strip_prefix("0x")?, 16).ok()?;
+            if hex.len() % 2 != 0 {
+                return None;
+            }
+            let bytes = (0..hex.len()).step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            Some((address, bytes))
+        }
+        None => None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(ReplayEvent { syscall, result, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_log_round_trip() {
+        let mut log = ReplayLog::recording();
+        // システムコールの実行中でなければ書き込みは記録しない
+        log.record_data(0x100, b"x");
+        log.begin();
+        log.commit(5, Some(42));
+        log.begin();
+        log.record_data(0x00800000, b"hi\n\0");
+        log.commit(8, None);
+        let text = log.to_text();
+        assert_eq!(text, "# mips_simulator replay v1\n5 42\n8 - 0x00800000:68690a00\n");
+
+        let mut replay = ReplayLog::parse(&text).unwrap();
+        assert!(replay.is_replaying());
+        assert_eq!(replay.next(8), Err(Some(5)));
+        assert_eq!(replay.next(5).unwrap().result, Some(42));
+        assert_eq!(replay.next(8).unwrap().data, Some((0x00800000, b"hi\n\0".to_vec())));
+        assert_eq!((replay.position(), replay.remaining()), (2, 0));
+        assert_eq!(replay.next(5), Err(None));
+
+        assert!(ReplayLog::parse("5 42\n").is_err());
+        assert!(ReplayLog::parse("# mips_simulator replay v1\n8 - 0x100:6\n").is_err());
+        assert!(is_recorded_syscall(15, [3, 0, 0]));
+        assert!(!is_recorded_syscall(15, [1, 0, 0]));
+    }
+}
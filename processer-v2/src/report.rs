@@ -0,0 +1,429 @@
+//! 機械可読な統計レポート（JSON入出力と実行間の比較）
+
+use std::fmt;
+
+/// 値が大きいほど良い指標のキー末尾（これ以外は小さいほど良いとみなす）
+const HIGHER_IS_BETTER: [&str; 4] = ["hit_rate", "ipc", "accuracy", "saved_cycles"];
+
+/// レポートの値
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportValue {
+    /// 数値（カウンタや比率）
+    Number(f64),
+    /// 文字列（終了理由など）
+    Text(String),
+}
+
+/// 統計レポート
+/// キーは "processor.instructions_executed" のようにドット区切りで階層を表す
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatsReport {
+    entries: Vec<(String, ReportValue)>,
+}
+
+impl StatsReport {
+    /// 空のレポートを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 値を設定する（同じキーがあれば上書き）
+    fn set(&mut self, key: &str, value: ReportValue) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    /// 数値を追加する
+    pub fn push<V: Into<f64>>(&mut self, key: &str, value: V) {
+        self.set(key, ReportValue::Number(value.into()));
+    }
+
+    /// 文字列を追加する
+    pub fn push_text(&mut self, key: &str, value: &str) {
+        self.set(key, ReportValue::Text(value.to_string()));
+    }
+
+    /// u64のカウンタを追加する
+    pub fn push_count(&mut self, key: &str, value: u64) {
+        self.push(key, value as f64);
+    }
+
+    /// キーに対応する数値を取得
+    pub fn get(&self, key: &str) -> Option<f64> {
+        match self.entries.iter().find(|(k, _)| k == key) {
+            Some((_, ReportValue::Number(v))) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// キーに対応する文字列を取得
+    pub fn get_text(&self, key: &str) -> Option<&str> {
+        match self.entries.iter().find(|(k, _)| k == key) {
+            Some((_, ReportValue::Text(v))) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 全エントリを取得
+    pub fn entries(&self) -> &[(String, ReportValue)] {
+        &self.entries
+    }
+
+    /// 数値のエントリのキーを順に取得
+    fn number_keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().filter(|(_, v)| matches!(v, ReportValue::Number(_))).map(|(k, _)| k)
+    }
+
+    /// JSON文字列に変換する
+    pub fn to_json(&self) -> String {
+        let mut result = String::from("{\n");
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            let separator = if i + 1 < self.entries.len() { "," } else { "" };
+            let value = match value {
+                ReportValue::Number(v) => format_number(*v),
+                ReportValue::Text(v) => format!("\"{}\"", escape_json(v)),
+            };
+            result.push_str(&format!("  \"{}\": {}{}\n", escape_json(key), value, separator));
+        }
+        result.push('}');
+        result
+    }
+
+    /// JSON文字列からレポートを読み込む
+    /// ネストしたオブジェクトはドット区切りのキーに平坦化し、数値と文字列以外の値は無視する
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut parser = JsonParser { chars: text.chars().collect(), pos: 0 };
+        let mut report = StatsReport::new();
+        parser.skip_whitespace();
+        parser.parse_object("", &mut report)?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(format!("{} 文字目以降に余分な文字があります", parser.pos + 1));
+        }
+        Ok(report)
+    }
+}
+
+/// 数値をJSON用に整形する（整数値は小数点なしで出力）
+fn format_number(value: f64) -> String {
+    if !value.is_finite() {
+        "null".to_string()
+    } else if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// 比較表示用に数値を整形する（整数値以外は小数点以下4桁）
+fn format_display(value: f64, signed: bool) -> String {
+    match (value.fract() == 0.0, signed) {
+        (true, false) => format_number(value),
+        (true, true) => format!("{:+}", value as i64),
+        (false, false) => format!("{:.4}", value),
+        (false, true) => format!("{:+.4}", value),
+    }
+}
+
+/// JSON文字列用にエスケープする
+fn escape_json(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// 統計レポートを読むための最小限のJSONパーサ
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("{} 文字目: '{}' が必要です", self.pos + 1, expected))
+        }
+    }
+
+    fn parse_object(&mut self, prefix: &str, report: &mut StatsReport) -> Result<(), String> {
+        self.expect('{')?;
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+            self.expect(':')?;
+            self.parse_value(&key, report)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(format!("{} 文字目: ',' または '}}' が必要です", self.pos + 1)),
+            }
+        }
+    }
+
+    fn parse_value(&mut self, key: &str, report: &mut StatsReport) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(key, report),
+            Some('[') => self.skip_array(),
+            Some('"') => {
+                let value = self.parse_string()?;
+                report.push_text(key, &value);
+                Ok(())
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let value = self.parse_number()?;
+                report.push(key, value);
+                Ok(())
+            }
+            _ => self.parse_literal(),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut result = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escaped = self.peek().ok_or("文字列が途中で終わっています")?;
+                    self.pos += 1;
+                    match escaped {
+                        'n' => result.push('\n'),
+                        't' => result.push('\t'),
+                        'u' => {
+                            let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                            let code = u32::from_str_radix(&hex, 16)
+                                .map_err(|_| format!("無効なエスケープ: \\u{}", hex))?;
+                            result.push(char::from_u32(code).unwrap_or('?'));
+                            self.pos += 4;
+                        }
+                        c => result.push(c),
+                    }
+                }
+                c => result.push(c),
+            }
+        }
+        Err("文字列が閉じられていません".to_string())
+    }
+
+    fn parse_number(&mut self) -> Result<f64, String> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' || c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map_err(|_| format!("無効な数値です: {}", text))
+    }
+
+    fn parse_literal(&mut self) -> Result<(), String> {
+        for literal in ["true", "false", "null"] {
+            let end = self.pos + literal.len();
+            if end <= self.chars.len() && self.chars[self.pos..end].iter().collect::<String>() == literal {
+                self.pos = end;
+                return Ok(());
+            }
+        }
+        Err(format!("{} 文字目: 値が必要です", self.pos + 1))
+    }
+
+    fn skip_array(&mut self) -> Result<(), String> {
+        self.expect('[')?;
+        let mut ignored = StatsReport::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            self.parse_value("", &mut ignored)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(format!("{} 文字目: ',' または ']' が必要です", self.pos + 1)),
+            }
+        }
+    }
+}
+
+/// 2つのレポートの1項目分の比較結果
+#[derive(Debug, Clone)]
+pub struct StatsDelta {
+    pub key: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+    /// 閾値を超えて悪化したかどうか
+    pub regression: bool,
+}
+
+impl StatsDelta {
+    /// 差分（after - before）
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.after? - self.before?)
+    }
+
+    /// 変化率（%）
+    pub fn percent(&self) -> Option<f64> {
+        let before = self.before?;
+        if before == 0.0 {
+            None
+        } else {
+            Some(self.delta()? / before.abs() * 100.0)
+        }
+    }
+}
+
+/// 2つのレポートの比較結果
+#[derive(Debug, Clone)]
+pub struct StatsComparison {
+    pub deltas: Vec<StatsDelta>,
+    /// 悪化と判定する変化率の閾値（%）
+    pub threshold: f64,
+}
+
+impl StatsComparison {
+    /// 2つのレポートを比較する
+    pub fn new(before: &StatsReport, after: &StatsReport, threshold: f64) -> Self {
+        let mut keys: Vec<&String> = before.number_keys().collect();
+        for key in after.number_keys() {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        let deltas = keys.into_iter().map(|key| {
+            let mut delta = StatsDelta {
+                key: key.clone(),
+                before: before.get(key),
+                after: after.get(key),
+                regression: false,
+            };
+            if let Some(diff) = delta.delta() {
+                let higher_is_better = HIGHER_IS_BETTER.iter().any(|suffix| key.ends_with(suffix));
+                let worse = if higher_is_better { diff < 0.0 } else { diff > 0.0 };
+                // 0からの変化は変化率を計算できないので、悪化していれば閾値に関係なく悪化とする
+                delta.regression = worse && delta.percent().is_none_or(|percent| percent.abs() > threshold);
+            }
+            delta
+        }).collect();
+
+        Self { deltas, threshold }
+    }
+
+    /// 悪化した項目数
+    pub fn regression_count(&self) -> usize {
+        self.deltas.iter().filter(|d| d.regression).count()
+    }
+}
+
+impl fmt::Display for StatsComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key_width = self.deltas.iter().map(|d| d.key.len()).max().unwrap_or(0).max(4);
+        let show = |v: Option<f64>| v.map(|v| format_display(v, false)).unwrap_or_else(|| "-".to_string());
+
+        writeln!(f, "{:<width$} {:>16} {:>16} {:>16} {:>10}", "項目", "A", "B", "差分", "変化率", width = key_width)?;
+        for d in &self.deltas {
+            let percent = d.percent().map(|p| format!("{:+.2}%", p)).unwrap_or_else(|| "-".to_string());
+            let delta = d.delta().map(|v| format_display(v, true)).unwrap_or_else(|| "-".to_string());
+            let marker = if d.regression { "  <<< 悪化" } else { "" };
+            writeln!(f, "{:<width$} {:>16} {:>16} {:>16} {:>10}{}",
+                d.key, show(d.before), show(d.after), delta, percent, marker, width = key_width)?;
+        }
+        write!(f, "閾値 {:.2}% を超えて悪化した項目: {}", self.threshold, self.regression_count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut report = StatsReport::new();
+        report.push_count("processor.instructions_executed", 1234);
+        report.push("cache.hit_rate", 0.75);
+        report.push_text("outcome.kind", "exited");
+
+        let parsed = StatsReport::from_json(&report.to_json()).unwrap();
+        assert_eq!(parsed, report);
+    }
+
+    #[test]
+    fn test_nested_json() {
+        let json = r#"{"processor": {"cycles": 10, "name": "x", "ok": true}, "list": [1, 2]}"#;
+        let report = StatsReport::from_json(json).unwrap();
+        assert_eq!(report.get("processor.cycles"), Some(10.0));
+        assert_eq!(report.get_text("processor.name"), Some("x"));
+        assert_eq!(report.entries().len(), 2);
+    }
+
+    #[test]
+    fn test_comparison_regressions() {
+        let mut a = StatsReport::new();
+        a.push_count("processor.instructions_executed", 100);
+        a.push("cache.hit_rate", 0.9);
+        let mut b = StatsReport::new();
+        b.push_count("processor.instructions_executed", 120);
+        b.push("cache.hit_rate", 0.95);
+
+        let comparison = StatsComparison::new(&a, &b, 5.0);
+        assert_eq!(comparison.regression_count(), 1);
+        assert!(comparison.deltas[0].regression);
+        assert!(!comparison.deltas[1].regression);
+    }
+
+    #[test]
+    fn test_comparison_from_zero() {
+        let mut a = StatsReport::new();
+        a.push_count("cache.misses", 0);
+        a.push_count("processor.stalls", 0);
+        let mut b = StatsReport::new();
+        b.push_count("cache.misses", 3);
+        b.push_count("processor.stalls", 0);
+
+        let comparison = StatsComparison::new(&a, &b, 5.0);
+        assert_eq!(comparison.deltas[0].percent(), None);
+        assert!(comparison.deltas[0].regression);
+        assert!(!comparison.deltas[1].regression);
+        assert_eq!(comparison.regression_count(), 1);
+    }
+}
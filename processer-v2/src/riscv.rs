@@ -0,0 +1,453 @@
+//! RISC-V RV32I命令セットの定義とデコード
+
+use std::fmt;
+
+use crate::instructions::{Instruction, InstructionCategory, Register, Word};
+use crate::symbols::SymbolTable;
+
+/// ABIでのレジスタ名（x0-x31）
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// スタックポインタのレジスタ番号
+pub const SP_REGISTER: Register = 2;
+
+/// 戻りアドレスのレジスタ番号（ra）
+pub const RA_REGISTER: Register = 1;
+
+/// グローバルポインタのレジスタ番号（gp）
+pub const GP_REGISTER: Register = 3;
+
+/// マルチコア実行でコア番号を渡すレジスタ（tp）
+pub const CORE_ID_REGISTER: Register = 4;
+
+/// システムコール番号を渡すレジスタ（a7）
+pub const SYSCALL_NUMBER_REGISTER: Register = 17;
+
+/// システムコールの引数を渡すレジスタ（a0, a1, a2）
+pub const SYSCALL_ARG_REGISTERS: [Register; 3] = [10, 11, 12];
+
+/// システムコールの戻り値を返すレジスタ（a0）
+pub const SYSCALL_RESULT_REGISTER: Register = 10;
+
+/// 命令語をRV32Iの各フィールドに分解する（診断表示用）
+pub fn instruction_fields(instruction: Instruction) -> Vec<(&'static str, u32)> {
+    vec![
+        ("opcode", instruction & 0x7F),
+        ("rd", (instruction >> 7) & 0x1F),
+        ("funct3", (instruction >> 12) & 0x7),
+        ("rs1", (instruction >> 15) & 0x1F),
+        ("rs2", (instruction >> 20) & 0x1F),
+        ("funct7", instruction >> 25),
+    ]
+}
+
+/// 整数演算の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+}
+
+impl AluOp {
+    /// 演算を適用する（シフト量は下位5ビットのみ使用）
+    pub fn apply(&self, a: Word, b: Word) -> Word {
+        match self {
+            AluOp::Add => a.wrapping_add(b),
+            AluOp::Sub => a.wrapping_sub(b),
+            AluOp::Sll => a << (b & 0x1F),
+            AluOp::Slt => ((a as i32) < (b as i32)) as Word,
+            AluOp::Sltu => (a < b) as Word,
+            AluOp::Xor => a ^ b,
+            AluOp::Srl => a >> (b & 0x1F),
+            AluOp::Sra => ((a as i32) >> (b & 0x1F)) as Word,
+            AluOp::Or => a | b,
+            AluOp::And => a & b,
+        }
+    }
+
+    /// ニーモニック
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            AluOp::Add => "add",
+            AluOp::Sub => "sub",
+            AluOp::Sll => "sll",
+            AluOp::Slt => "slt",
+            AluOp::Sltu => "sltu",
+            AluOp::Xor => "xor",
+            AluOp::Srl => "srl",
+            AluOp::Sra => "sra",
+            AluOp::Or => "or",
+            AluOp::And => "and",
+        }
+    }
+}
+
+/// 分岐条件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BranchCond {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Ltu,
+    Geu,
+}
+
+impl BranchCond {
+    /// 条件が成立するか
+    pub fn test(&self, a: Word, b: Word) -> bool {
+        match self {
+            BranchCond::Eq => a == b,
+            BranchCond::Ne => a != b,
+            BranchCond::Lt => (a as i32) < (b as i32),
+            BranchCond::Ge => (a as i32) >= (b as i32),
+            BranchCond::Ltu => a < b,
+            BranchCond::Geu => a >= b,
+        }
+    }
+
+    /// ニーモニック
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            BranchCond::Eq => "beq",
+            BranchCond::Ne => "bne",
+            BranchCond::Lt => "blt",
+            BranchCond::Ge => "bge",
+            BranchCond::Ltu => "bltu",
+            BranchCond::Geu => "bgeu",
+        }
+    }
+}
+
+/// ロード・ストアのアクセス幅
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessWidth {
+    /// 符号拡張する1バイト
+    Byte,
+    /// 符号拡張する2バイト
+    Half,
+    /// 4バイト
+    Word,
+    /// ゼロ拡張する1バイト
+    ByteUnsigned,
+    /// ゼロ拡張する2バイト
+    HalfUnsigned,
+}
+
+impl AccessWidth {
+    /// アクセスするバイト数
+    pub fn bytes(&self) -> u32 {
+        match self {
+            AccessWidth::Byte | AccessWidth::ByteUnsigned => 1,
+            AccessWidth::Half | AccessWidth::HalfUnsigned => 2,
+            AccessWidth::Word => 4,
+        }
+    }
+
+    /// 読み込んだ値をレジスタ幅に拡張する
+    pub fn extend(&self, value: Word) -> Word {
+        match self {
+            AccessWidth::Byte => value as u8 as i8 as i32 as Word,
+            AccessWidth::Half => value as u16 as i16 as i32 as Word,
+            _ => value,
+        }
+    }
+
+    /// ロード命令のニーモニック
+    fn load_mnemonic(&self) -> &'static str {
+        match self {
+            AccessWidth::Byte => "lb",
+            AccessWidth::Half => "lh",
+            AccessWidth::Word => "lw",
+            AccessWidth::ByteUnsigned => "lbu",
+            AccessWidth::HalfUnsigned => "lhu",
+        }
+    }
+
+    /// ストア命令のニーモニック
+    fn store_mnemonic(&self) -> &'static str {
+        match self {
+            AccessWidth::Byte | AccessWidth::ByteUnsigned => "sb",
+            AccessWidth::Half | AccessWidth::HalfUnsigned => "sh",
+            AccessWidth::Word => "sw",
+        }
+    }
+}
+
+/// RV32I命令の種類
+#[derive(Debug, Clone, PartialEq)]
+pub enum RvInstruction {
+    Lui { rd: Register, imm: Word },
+    Auipc { rd: Register, imm: Word },
+    Jal { rd: Register, imm: i32 },
+    Jalr { rd: Register, rs1: Register, imm: i32 },
+    Branch { cond: BranchCond, rs1: Register, rs2: Register, imm: i32 },
+    Load { width: AccessWidth, rd: Register, rs1: Register, imm: i32 },
+    Store { width: AccessWidth, rs1: Register, rs2: Register, imm: i32 },
+    OpImm { op: AluOp, rd: Register, rs1: Register, imm: i32 },
+    Op { op: AluOp, rd: Register, rs1: Register, rs2: Register },
+    Fence,
+    Ecall,
+    Ebreak,
+    Invalid,
+}
+
+impl RvInstruction {
+    /// 32ビット命令からRV32I命令をデコードする
+    pub fn decode(instruction: Instruction) -> Self {
+        let opcode = instruction & 0x7F;
+        let rd = ((instruction >> 7) & 0x1F) as Register;
+        let funct3 = (instruction >> 12) & 0x7;
+        let rs1 = ((instruction >> 15) & 0x1F) as Register;
+        let rs2 = ((instruction >> 20) & 0x1F) as Register;
+        let funct7 = instruction >> 25;
+
+        // 即値は形式ごとにビットの並びが異なる（最上位ビットで符号拡張する）
+        let signed = instruction as i32;
+        let imm_i = signed >> 20;
+        let imm_s = ((signed >> 25) << 5) | ((instruction >> 7) & 0x1F) as i32;
+        let imm_b = ((signed >> 31) << 12)
+            | (((instruction >> 7) & 0x1) << 11) as i32
+            | (((instruction >> 25) & 0x3F) << 5) as i32
+            | (((instruction >> 8) & 0xF) << 1) as i32;
+        let imm_u = instruction & 0xFFFFF000;
+        let imm_j = ((signed >> 31) << 20)
+            | (instruction & 0xFF000) as i32
+            | (((instruction >> 20) & 0x1) << 11) as i32
+            | (((instruction >> 21) & 0x3FF) << 1) as i32;
+
+        match opcode {
+            0x37 => RvInstruction::Lui { rd, imm: imm_u },
+            0x17 => RvInstruction::Auipc { rd, imm: imm_u },
+            0x6F => RvInstruction::Jal { rd, imm: imm_j },
+            0x67 if funct3 == 0 => RvInstruction::Jalr { rd, rs1, imm: imm_i },
+            0x63 => {
+                let cond = match funct3 {
+                    0 => BranchCond::Eq,
+                    1 => BranchCond::Ne,
+                    4 => BranchCond::Lt,
+                    5 => BranchCond::Ge,
+                    6 => BranchCond::Ltu,
+                    7 => BranchCond::Geu,
+                    _ => return RvInstruction::Invalid,
+                };
+                RvInstruction::Branch { cond, rs1, rs2, imm: imm_b }
+            }
+            0x03 => {
+                let width = match funct3 {
+                    0 => AccessWidth::Byte,
+                    1 => AccessWidth::Half,
+                    2 => AccessWidth::Word,
+                    4 => AccessWidth::ByteUnsigned,
+                    5 => AccessWidth::HalfUnsigned,
+                    _ => return RvInstruction::Invalid,
+                };
+                RvInstruction::Load { width, rd, rs1, imm: imm_i }
+            }
+            0x23 => {
+                let width = match funct3 {
+                    0 => AccessWidth::Byte,
+                    1 => AccessWidth::Half,
+                    2 => AccessWidth::Word,
+                    _ => return RvInstruction::Invalid,
+                };
+                RvInstruction::Store { width, rs1, rs2, imm: imm_s }
+            }
+            0x13 => {
+                let op = match (funct3, funct7) {
+                    (0, _) => AluOp::Add,
+                    (2, _) => AluOp::Slt,
+                    (3, _) => AluOp::Sltu,
+                    (4, _) => AluOp::Xor,
+                    (6, _) => AluOp::Or,
+                    (7, _) => AluOp::And,
+                    (1, 0x00) => AluOp::Sll,
+                    (5, 0x00) => AluOp::Srl,
+                    (5, 0x20) => AluOp::Sra,
+                    _ => return RvInstruction::Invalid,
+                };
+                // シフト命令の即値はシフト量（下位5ビット）のみ
+                let imm = if matches!(op, AluOp::Sll | AluOp::Srl | AluOp::Sra) { imm_i & 0x1F } else { imm_i };
+                RvInstruction::OpImm { op, rd, rs1, imm }
+            }
+            0x33 => {
+                let op = match (funct3, funct7) {
+                    (0, 0x00) => AluOp::Add,
+                    (0, 0x20) => AluOp::Sub,
+                    (1, 0x00) => AluOp::Sll,
+                    (2, 0x00) => AluOp::Slt,
+                    (3, 0x00) => AluOp::Sltu,
+                    (4, 0x00) => AluOp::Xor,
+                    (5, 0x00) => AluOp::Srl,
+                    (5, 0x20) => AluOp::Sra,
+                    (6, 0x00) => AluOp::Or,
+                    (7, 0x00) => AluOp::And,
+                    _ => return RvInstruction::Invalid,
+                };
+                RvInstruction::Op { op, rd, rs1, rs2 }
+            }
+            0x0F => RvInstruction::Fence,
+            0x73 => match instruction {
+                0x00000073 => RvInstruction::Ecall,
+                0x00100073 => RvInstruction::Ebreak,
+                _ => RvInstruction::Invalid,
+            },
+            _ => RvInstruction::Invalid,
+        }
+    }
+
+    /// pcに置かれた命令として表示する（分岐・ジャンプ先は即値ではなく絶対アドレスとシンボルで示す）
+    pub fn display_at(&self, pc: Word, symbols: &SymbolTable) -> String {
+        match *self {
+            RvInstruction::Jal { rd, imm } => {
+                format!("jal x{}, {}", rd, symbols.format_address(pc.wrapping_add(imm as u32)))
+            }
+            RvInstruction::Branch { cond, rs1, rs2, imm } => {
+                format!("{} x{}, x{}, {}", cond.mnemonic(), rs1, rs2, symbols.format_address(pc.wrapping_add(imm as u32)))
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// pcに置かれた分岐・ジャンプ命令の飛び先（レジスタで決まる jalr や、分岐・ジャンプでない命令はNone）
+    pub fn target(&self, pc: Word) -> Option<Word> {
+        match *self {
+            RvInstruction::Jal { imm, .. } | RvInstruction::Branch { imm, .. } => Some(pc.wrapping_add(imm as u32)),
+            _ => None,
+        }
+    }
+
+    /// 命令の分類
+    pub fn category(&self) -> InstructionCategory {
+        match self {
+            RvInstruction::OpImm { op: AluOp::Sll | AluOp::Srl | AluOp::Sra, .. }
+            | RvInstruction::Op { op: AluOp::Sll | AluOp::Srl | AluOp::Sra, .. } => InstructionCategory::Shift,
+            RvInstruction::Lui { .. } | RvInstruction::Auipc { .. }
+            | RvInstruction::OpImm { .. } | RvInstruction::Op { .. } => InstructionCategory::Alu,
+            RvInstruction::Load { .. } => InstructionCategory::Load,
+            RvInstruction::Store { .. } => InstructionCategory::Store,
+            RvInstruction::Branch { .. } => InstructionCategory::Branch,
+            RvInstruction::Jal { .. } | RvInstruction::Jalr { .. } => InstructionCategory::Jump,
+            RvInstruction::Ecall => InstructionCategory::Syscall,
+            RvInstruction::Fence | RvInstruction::Ebreak | RvInstruction::Invalid => InstructionCategory::Other,
+        }
+    }
+
+    /// ニーモニック（命令の種類ごとに一意）
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            RvInstruction::Lui { .. } => "lui",
+            RvInstruction::Auipc { .. } => "auipc",
+            RvInstruction::Jal { .. } => "jal",
+            RvInstruction::Jalr { .. } => "jalr",
+            RvInstruction::Branch { cond, .. } => cond.mnemonic(),
+            RvInstruction::Load { width, .. } => width.load_mnemonic(),
+            RvInstruction::Store { width, .. } => width.store_mnemonic(),
+            RvInstruction::OpImm { op, .. } => match op {
+                AluOp::Add | AluOp::Sub => "addi",
+                AluOp::Sll => "slli",
+                AluOp::Slt => "slti",
+                AluOp::Sltu => "sltiu",
+                AluOp::Xor => "xori",
+                AluOp::Srl => "srli",
+                AluOp::Sra => "srai",
+                AluOp::Or => "ori",
+                AluOp::And => "andi",
+            },
+            RvInstruction::Op { op, .. } => op.mnemonic(),
+            RvInstruction::Fence => "fence",
+            RvInstruction::Ecall => "ecall",
+            RvInstruction::Ebreak => "ebreak",
+            RvInstruction::Invalid => "invalid",
+        }
+    }
+}
+
+impl fmt::Display for RvInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RvInstruction::Lui { rd, imm } => {
+                write!(f, "lui x{}, 0x{:05X}", rd, imm >> 12)
+            }
+            RvInstruction::Auipc { rd, imm } => {
+                write!(f, "auipc x{}, 0x{:05X}", rd, imm >> 12)
+            }
+            RvInstruction::Jal { rd, imm } => {
+                write!(f, "jal x{}, {}", rd, imm)
+            }
+            RvInstruction::Jalr { rd, rs1, imm } => {
+                write!(f, "jalr x{}, {}(x{})", rd, imm, rs1)
+            }
+            RvInstruction::Branch { cond, rs1, rs2, imm } => {
+                write!(f, "{} x{}, x{}, {}", cond.mnemonic(), rs1, rs2, imm)
+            }
+            RvInstruction::Load { width, rd, rs1, imm } => {
+                write!(f, "{} x{}, {}(x{})", width.load_mnemonic(), rd, imm, rs1)
+            }
+            RvInstruction::Store { width, rs1, rs2, imm } => {
+                write!(f, "{} x{}, {}(x{})", width.store_mnemonic(), rs2, imm, rs1)
+            }
+            RvInstruction::OpImm { rd, rs1, imm, .. } => {
+                write!(f, "{} x{}, x{}, {}", self.mnemonic(), rd, rs1, imm)
+            }
+            RvInstruction::Op { op, rd, rs1, rs2 } => {
+                write!(f, "{} x{}, x{}, x{}", op.mnemonic(), rd, rs1, rs2)
+            }
+            RvInstruction::Fence => {
+                write!(f, "fence")
+            }
+            RvInstruction::Ecall => {
+                write!(f, "ecall")
+            }
+            RvInstruction::Ebreak => {
+                write!(f, "ebreak")
+            }
+            RvInstruction::Invalid => {
+                write!(f, "invalid")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_immediates() {
+        // addi x5, x0, -1
+        assert_eq!(RvInstruction::decode(0xFFF00293), RvInstruction::OpImm { op: AluOp::Add, rd: 5, rs1: 0, imm: -1 });
+        // sw x5, -4(x2)
+        assert_eq!(RvInstruction::decode(0xFE512E23), RvInstruction::Store { width: AccessWidth::Word, rs1: 2, rs2: 5, imm: -4 });
+        // bne x5, x0, -8
+        assert_eq!(RvInstruction::decode(0xFE029CE3), RvInstruction::Branch { cond: BranchCond::Ne, rs1: 5, rs2: 0, imm: -8 });
+        // jal x1, 2048
+        assert_eq!(RvInstruction::decode(0x001000EF), RvInstruction::Jal { rd: 1, imm: 2048 });
+        // lui x10, 0x12345
+        assert_eq!(RvInstruction::decode(0x12345537), RvInstruction::Lui { rd: 10, imm: 0x12345000 });
+    }
+
+    #[test]
+    fn test_decode_shifts_and_system() {
+        // srai x5, x6, 3
+        assert_eq!(RvInstruction::decode(0x40335293), RvInstruction::OpImm { op: AluOp::Sra, rd: 5, rs1: 6, imm: 3 });
+        // sub x5, x6, x7
+        assert_eq!(RvInstruction::decode(0x407302B3), RvInstruction::Op { op: AluOp::Sub, rd: 5, rs1: 6, rs2: 7 });
+        assert_eq!(RvInstruction::decode(0x00000073), RvInstruction::Ecall);
+        assert_eq!(RvInstruction::decode(0x00100073), RvInstruction::Ebreak);
+        assert_eq!(AluOp::Sra.apply(0x80000000, 4), 0xF8000000);
+    }
+}
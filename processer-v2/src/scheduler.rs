@@ -0,0 +1,222 @@
+//! マルチコア実行でどのコアを次に動かすかを決めるスケジューラ
+//!
+//! どの方式も決定的で、同じ設定なら毎回同じ順序でコアを切り替える。
+
+use std::fs;
+
+/// random方式でシードを省略したときのシード
+pub const DEFAULT_SCHEDULE_SEED: u64 = 1;
+
+/// コアを切り替えるまでに実行する命令数のデフォルト値
+pub const DEFAULT_SWITCH_QUANTUM: u64 = 1;
+
+/// コアの切り替え方式
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SchedulePolicy {
+    /// 番号順に切り替える
+    #[default]
+    RoundRobin,
+    /// シード付きの擬似乱数で選ぶ
+    Random { seed: u64 },
+    /// ファイルに書かれたコア番号の順に切り替える（使い切った後はラウンドロビン）
+    Trace(Vec<usize>),
+    /// 見積もったサイクル数が最も少ないコアを選ぶ（コアの時刻をそろえて進める）
+    Cycle,
+}
+
+impl SchedulePolicy {
+    /// "rr", "random[:seed]", "trace:<ファイル>", "cycle" の形式から作成する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (name, arg) = match text.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (text, None),
+        };
+        match (name, arg) {
+            ("rr" | "round-robin", None) => Ok(SchedulePolicy::RoundRobin),
+            ("cycle", None) => Ok(SchedulePolicy::Cycle),
+            ("random", None) => Ok(SchedulePolicy::Random { seed: DEFAULT_SCHEDULE_SEED }),
+            ("random", Some(seed)) => seed.parse()
+                .map(|seed| SchedulePolicy::Random { seed })
+                .map_err(|_| format!("無効なシードです: {}", seed)),
+            ("trace", Some(path)) => {
+                let text = fs::read_to_string(path)
+                    .map_err(|e| format!("スケジュールファイル '{}' を開けません: {}", path, e))?;
+                Self::parse_trace(&text).map(SchedulePolicy::Trace)
+            }
+            _ => Err(format!("無効なスケジュール方式です: {}", text)),
+        }
+    }
+
+    /// コア番号を空白・カンマ・改行で区切って並べたトレースを解析する（'#'以降はコメント）
+    pub fn parse_trace(text: &str) -> Result<Vec<usize>, String> {
+        let mut trace = Vec::new();
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+                let core = token.parse()
+                    .map_err(|_| format!("スケジュールファイルの {} 行目: 無効なコア番号です: {}", line_num + 1, token))?;
+                trace.push(core);
+            }
+        }
+        Ok(trace)
+    }
+
+    /// トレースに現れる最大のコア番号
+    pub fn max_core(&self) -> Option<usize> {
+        match self {
+            SchedulePolicy::Trace(trace) => trace.iter().copied().max(),
+            _ => None,
+        }
+    }
+
+    /// 表示用の名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchedulePolicy::RoundRobin => "round-robin",
+            SchedulePolicy::Random { .. } => "random",
+            SchedulePolicy::Trace(_) => "trace",
+            SchedulePolicy::Cycle => "cycle",
+        }
+    }
+}
+
+/// コアの切り替えを決めるスケジューラ
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    policy: SchedulePolicy,
+    /// 1回の割り当てで実行する命令数
+    quantum: u64,
+    /// 直前に選んだコア
+    last: Option<usize>,
+    /// 擬似乱数の状態（xorshift64）
+    rng_state: u64,
+    /// トレースの読み出し位置
+    trace_position: usize,
+}
+
+impl Scheduler {
+    /// 新しいスケジューラを作成
+    pub fn new(policy: SchedulePolicy, quantum: u64) -> Self {
+        let rng_state = match policy {
+            // xorshiftは状態が0だと0しか出さないので、シードを混ぜて0を避ける
+            SchedulePolicy::Random { seed } => seed ^ 0x9E37_79B9_7F4A_7C15,
+            _ => 0,
+        };
+        Self {
+            policy,
+            quantum: quantum.max(1),
+            last: None,
+            rng_state: if rng_state == 0 { 0x9E37_79B9_7F4A_7C15 } else { rng_state },
+            trace_position: 0,
+        }
+    }
+
+    /// 1回の割り当てで実行する命令数
+    pub fn quantum(&self) -> u64 {
+        self.quantum
+    }
+
+    /// 次に実行するコアを選ぶ（runnable[i] はコアiが実行可能かどうか、cycles[i] はコアiのサイクル数）
+    /// 実行可能なコアがなければ None を返す
+    pub fn next_core(&mut self, runnable: &[bool], cycles: &[u64]) -> Option<usize> {
+        if !runnable.contains(&true) {
+            return None;
+        }
+        let core = match &self.policy {
+            SchedulePolicy::RoundRobin => self.next_round_robin(runnable),
+            SchedulePolicy::Random { .. } => {
+                let candidates: Vec<usize> = (0..runnable.len()).filter(|&i| runnable[i]).collect();
+                candidates[(self.next_random() % candidates.len() as u64) as usize]
+            }
+            SchedulePolicy::Trace(trace) => {
+                // 終了したコアや存在しないコアの番号は読み飛ばす
+                let mut found = None;
+                while self.trace_position < trace.len() {
+                    let core = trace[self.trace_position];
+                    self.trace_position += 1;
+                    if runnable.get(core) == Some(&true) {
+                        found = Some(core);
+                        break;
+                    }
+                }
+                match found {
+                    Some(core) => core,
+                    None => self.next_round_robin(runnable),
+                }
+            }
+            // サイクル数が同じなら番号の小さいコア
+            SchedulePolicy::Cycle => (0..runnable.len())
+                .filter(|&i| runnable[i])
+                .min_by_key(|&i| (cycles.get(i).copied().unwrap_or(0), i))
+                .unwrap_or(0),
+        };
+        self.last = Some(core);
+        Some(core)
+    }
+
+    /// 直前のコアの次から番号順に実行可能なコアを探す
+    fn next_round_robin(&self, runnable: &[bool]) -> usize {
+        let start = self.last.map_or(0, |last| last + 1);
+        (0..runnable.len())
+            .map(|offset| (start + offset) % runnable.len())
+            .find(|&core| runnable[core])
+            .unwrap_or(0)
+    }
+
+    /// xorshift64で次の擬似乱数を得る
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(scheduler: &mut Scheduler, runnable: &[bool], count: usize) -> Vec<usize> {
+        (0..count).map(|_| scheduler.next_core(runnable, &[]).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_round_robin_skips_finished_cores() {
+        let mut scheduler = Scheduler::new(SchedulePolicy::RoundRobin, 1);
+        assert_eq!(schedule(&mut scheduler, &[true, true, true], 4), vec![0, 1, 2, 0]);
+        assert_eq!(schedule(&mut scheduler, &[true, false, true], 3), vec![2, 0, 2]);
+        assert_eq!(scheduler.next_core(&[false, false, false], &[]), None);
+    }
+
+    #[test]
+    fn test_random_is_deterministic() {
+        let runnable = [true; 4];
+        let first = schedule(&mut Scheduler::new(SchedulePolicy::Random { seed: 42 }, 1), &runnable, 32);
+        let second = schedule(&mut Scheduler::new(SchedulePolicy::Random { seed: 42 }, 1), &runnable, 32);
+        let other = schedule(&mut Scheduler::new(SchedulePolicy::Random { seed: 7 }, 1), &runnable, 32);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert!((0..4).all(|core| first.contains(&core)));
+    }
+
+    #[test]
+    fn test_trace_replay() {
+        let trace = SchedulePolicy::parse_trace("1 1, 0 # コメント\n2\n5\n").unwrap();
+        assert_eq!(trace, vec![1, 1, 0, 2, 5]);
+        let mut scheduler = Scheduler::new(SchedulePolicy::Trace(trace), 1);
+        // 5は存在しないので読み飛ばし、使い切った後は2の次からラウンドロビン
+        assert_eq!(schedule(&mut scheduler, &[true, true, true], 6), vec![1, 1, 0, 2, 0, 1]);
+        assert!(SchedulePolicy::parse_trace("0 x").is_err());
+    }
+
+    #[test]
+    fn test_cycle_interleaved() {
+        let mut scheduler = Scheduler::new(SchedulePolicy::parse("cycle").unwrap(), 1);
+        assert_eq!(scheduler.next_core(&[true, true, true], &[30, 10, 20]), Some(1));
+        // 同じサイクル数なら番号の小さいコア、終了したコアは選ばない
+        assert_eq!(scheduler.next_core(&[true, true, true], &[10, 20, 10]), Some(0));
+        assert_eq!(scheduler.next_core(&[false, true, true], &[10, 20, 10]), Some(2));
+    }
+}
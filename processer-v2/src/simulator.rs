@@ -0,0 +1,2029 @@
+//! シミュレータ本体（設定、プログラムの読み込み、実行、ステップ実行モード）
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::assembler::{AsmError, Assembler};
+use crate::bench::{BenchResult, Kernel};
+use crate::branch_predictor::{BranchPredictors, PredictorKind};
+use crate::bus::BusConfig;
+use crate::cache::{CacheConfig, CacheLike, CACHE_SETS};
+use crate::cache_trace::CacheTraceWriter;
+use crate::coherence::SnoopBus;
+use crate::data_image::DataImage;
+use crate::expect::{ExpectTarget, ExpectedState, StateComparison};
+use crate::guest_env::{GuestEnv, ENV_BLOCK_ADDRESS};
+use crate::fpu::{ApproxConfig, ApproxFpu, FpuConfig};
+use crate::framebuffer::{Framebuffer, FramebufferConfig, FramebufferDevice};
+use crate::instructions::{Instruction, Word};
+use crate::memory::{Access, Memory, MemoryAddress, MemoryBacking, MemoryDump, Protection, DEFAULT_POISON_BYTE};
+use crate::mmio::MmioDevice;
+use crate::heatmap::DEFAULT_HEATMAP_TOP;
+use crate::history::DEFAULT_HISTORY_LENGTH;
+use crate::undo_log::DEFAULT_UNDO_LIMIT;
+use crate::watchpoint::{MemoryWatchpoint, Watchpoint};
+use crate::input::GuestInput;
+use crate::loader::{HexLayout, HexOptions, LoadImage, ProgramFile, ProgramFormat, Segment};
+use crate::loop_detector::LoopDetectionConfig;
+use crate::mem_trace::{MemTraceFilter, MemTraceWriter};
+use crate::output::{GuestOutput, OutputComparison, OutputConfig};
+use crate::replay::ReplayLog;
+use crate::snapshot::SnapshotError;
+use crate::syscall::SyscallHandler;
+use crate::processor::{Isa, MicroarchSnapshot, Processor, ProcessorError, RunResult, StepSignal, HALT_ADDRESS, INSTRUCTION_LIMIT, PC_INITIAL, SP_INITIAL};
+use crate::report::{ReportValue, StatsReport};
+use crate::scheduler::{SchedulePolicy, Scheduler, DEFAULT_SWITCH_QUANTUM};
+use crate::symbols::SymbolTable;
+use crate::timing::TimingConfig;
+use crate::{assembler, cache, elf, loader, memory, processor, records, validate};
+
+/// find コマンドで表示する検索結果の最大件数
+const MAX_FIND_RESULTS: usize = 32;
+/// 実行前の検査で1件ずつ表示する問題の最大件数
+const PROGRAM_ISSUES_SHOWN: usize = 10;
+
+/// シミュレータの設定
+//　ここで定義してdefault()で呼び出せるようにすることで、設定の変更が容易になり、拡張性が上がる
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    /// メモリサイズ（バイト）
+    pub memory_size: usize,
+    /// メモリの確保方法（数百MBを超えるメモリはマップして遅延して割り当てる）
+    pub memory_backing: MemoryBacking,
+    /// プログラムの開始アドレス（プログラムをここにロードし、ここから実行する）
+    pub program_start: MemoryAddress,
+    /// プログラムファイルの形式
+    pub program_format: ProgramFormat,
+    /// アセンブリ言語のソースの .data セクションを置くアドレス（Noneなら命令の直後）
+    pub data_base: Option<MemoryAddress>,
+    /// 16進数のテキストのバイト順・ワード順・書き方
+    pub hex_options: HexOptions,
+    /// スタックポインタの初期値
+    pub initial_sp: MemoryAddress,
+    /// グローバルポインタの初期値（Noneなら0のまま）
+    pub initial_gp: Option<MemoryAddress>,
+    /// デバッグモード
+    pub debug_mode: bool,
+    /// ステップ実行モード
+    pub step_mode: bool,
+    /// キャッシュとメモリ間のバス設定
+    pub bus: BusConfig,
+    /// キャッシュの設定
+    pub cache: CacheConfig,
+    /// 浮動小数点演算のモデル
+    pub fpu: FpuConfig,
+    /// finv/fsqrtをテーブル + ニュートン法で近似する場合の設定
+    pub fpu_approx: Option<ApproxConfig>,
+    /// 実行時間の上限
+    pub timeout: Option<Duration>,
+    /// 実行する命令数の上限（コアごと、Noneで無制限）
+    pub max_instructions: Option<u64>,
+    /// 実行後に統計情報をJSONで書き出すファイル
+    pub stats_json_path: Option<String>,
+    /// 実行前に状態を復元するスナップショットのファイル
+    pub load_snapshot_path: Option<String>,
+    /// 実行後に状態を保存するスナップショットのファイル
+    pub save_snapshot_path: Option<String>,
+    /// 実行する命令セット
+    pub isa: Isa,
+    /// タイミングモデルの設定
+    pub timing: TimingConfig,
+    /// 同時に評価する分岐予測器（タイミングには最初のものを使い、空なら予測しない）
+    pub branch_predictors: Vec<PredictorKind>,
+    /// 実行後に命令タイミングのヒストグラムをCSVで書き出すファイル
+    pub timing_csv_path: Option<String>,
+    /// キャッシュへのすべてのアクセスをCSVで書き出すファイル（マルチコア実行ではコア0のキャッシュ）
+    pub cache_trace_path: Option<String>,
+    /// 保護属性を設定する領域（後に指定したものを優先する）
+    pub protections: Vec<(Range<MemoryAddress>, Protection)>,
+    /// ロード・ストアをCSVで書き出すファイル（マルチコア実行ではコア0のアクセス）
+    pub mem_trace_path: Option<String>,
+    /// 書き出すロード・ストアの条件
+    pub mem_trace_filter: MemTraceFilter,
+    /// メモリマップドのフレームバッファ（終了時に画像として書き出す）
+    pub framebuffer: Option<FramebufferConfig>,
+    /// プログラムの後にメモリへ読み込むデータイメージ（後に指定したものを優先する）
+    pub data_images: Vec<DataImage>,
+    /// データイメージの後に読み込み、書き込みを禁止する領域（ブートローダーなど）
+    pub rom_images: Vec<DataImage>,
+    /// 実行後に16進ダンプを書き出すメモリの範囲
+    pub memory_dumps: Vec<MemoryDump>,
+    /// 実行後にキャッシュの全ラインの状態を表示するか
+    pub dump_cache: bool,
+    /// 実行後に命令カバレッジを表示するか
+    pub coverage: bool,
+    /// 実行後に実行回数の多い命令を何件表示するか（Noneなら集計しない）
+    pub profile_top: Option<usize>,
+    /// ロード・ストアの回数を数えるブロックの大きさ（Noneなら集計しない）
+    pub heatmap_block: Option<u32>,
+    /// 実行後にアクセスの多いブロックを何件表示するか
+    pub heatmap_top: usize,
+    /// 実行後にブロックごとのロード・ストアの回数をCSVで書き出すファイル
+    pub heatmap_csv_path: Option<String>,
+    /// 触れたメモリを集計するページの大きさ（Noneなら集計しない）
+    pub footprint_page: Option<u32>,
+    /// 実行後に関数ごとの命令数・サイクル数を表示するか
+    pub profile_functions: bool,
+    /// 呼び出し・復帰を追跡し、エラーで止まったときにバックトレースを表示するか
+    pub backtrace: bool,
+    /// 実行後にレジスタごとの読み出し・書き込み回数を表示するか
+    pub register_stats: bool,
+    /// 未初期化のレジスタの読み出しを警告するか
+    pub check_uninitialized: bool,
+    /// メモリを0の代わりに埋めるバイト（Noneなら0で初期化する）
+    pub poison_byte: Option<u8>,
+    /// 埋めたバイトのままのワードの読み込みを未初期化のメモリの読み込みとして警告するか
+    pub check_poison: bool,
+    /// 実行前にプログラムを検査し、無効な命令やプログラムの外・データへの分岐を警告するか
+    pub check_program: bool,
+    /// 無限ループの検出の設定（Noneなら検出しない、マルチコア実行では無効）
+    pub loop_detection: Option<LoopDetectionConfig>,
+    /// 例外で止まったときに表示する直前の命令数（0なら記録しない）
+    pub history_length: usize,
+    /// ステップ実行モードで逆実行のために記録する命令数（0なら記録しない）
+    pub undo_limit: usize,
+    /// レジスタのウォッチポイント
+    pub watchpoints: Vec<Watchpoint>,
+    /// メモリのウォッチポイント
+    pub memory_watchpoints: Vec<MemoryWatchpoint>,
+    /// ゲストプログラムに渡す設定
+    pub env: GuestEnv,
+    /// 設定ブロックを配置するアドレス
+    pub env_address: MemoryAddress,
+    /// コア数（2以上でメモリを共有するマルチコア実行）
+    pub cores: usize,
+    /// マルチコア実行でのコアの切り替え方式
+    pub schedule: SchedulePolicy,
+    /// コアを切り替えるまでに実行する命令数
+    pub switch_quantum: u64,
+    /// ゲストプログラムの出力先
+    pub output: OutputConfig,
+    /// 実行後に最終状態と比べる、期待するレジスタとメモリの値を書いたファイル
+    pub expect_path: Option<String>,
+    /// シンボルファイル
+    pub symbols_path: Option<String>,
+    /// 実行トレースを止めた状態で開始し、ゲストのシステムコールで有効にした区間だけ表示するか
+    pub guest_trace: bool,
+    /// 統計の収集を止めた状態で開始し、ゲストのシステムコールで有効にした区間だけ集めるか
+    pub guest_stats: bool,
+    /// ファイル入出力システムコールで開けるディレクトリ（Noneならファイルを開けない）
+    pub file_sandbox: Option<String>,
+    /// ゲストプログラムの入力を読み込むファイル（Noneなら標準入力）
+    pub stdin_path: Option<String>,
+    /// ホストから値を受け取るシステムコールの結果を記録するファイル
+    pub record_path: Option<String>,
+    /// 記録した結果を再生するファイル（ホストの入力やファイルを使わずに同じ実行を再現する）
+    pub replay_path: Option<String>,
+    /// 例外ベクタ（指定すると例外でハンドラへ移り、指定しなければ例外で実行を止める）
+    pub exception_vector: Option<MemoryAddress>,
+    /// このアドレスにジャンプすると正常終了する（起動時に戻りアドレスのレジスタにも入れる）
+    pub halt_address: MemoryAddress,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            memory_size: 16 * 1024 * 1024, // 16MB
+            memory_backing: MemoryBacking::Heap,
+            program_start: PC_INITIAL,
+            program_format: ProgramFormat::Auto,
+            data_base: None,
+            hex_options: HexOptions::default(),
+            initial_sp: SP_INITIAL,
+            initial_gp: None,
+            debug_mode: false,
+            step_mode: false,
+            bus: BusConfig::default(),
+            cache: CacheConfig::default(),
+            fpu: FpuConfig::default(),
+            fpu_approx: None,
+            timeout: None,
+            max_instructions: Some(INSTRUCTION_LIMIT),
+            stats_json_path: None,
+            load_snapshot_path: None,
+            save_snapshot_path: None,
+            isa: Isa::default(),
+            timing: TimingConfig::default(),
+            branch_predictors: Vec::new(),
+            timing_csv_path: None,
+            cache_trace_path: None,
+            protections: Vec::new(),
+            mem_trace_path: None,
+            mem_trace_filter: MemTraceFilter::default(),
+            framebuffer: None,
+            data_images: Vec::new(),
+            rom_images: Vec::new(),
+            memory_dumps: Vec::new(),
+            dump_cache: false,
+            coverage: false,
+            profile_top: None,
+            heatmap_block: None,
+            heatmap_top: DEFAULT_HEATMAP_TOP,
+            heatmap_csv_path: None,
+            footprint_page: None,
+            profile_functions: false,
+            backtrace: false,
+            register_stats: false,
+            check_uninitialized: false,
+            poison_byte: None,
+            check_poison: false,
+            check_program: true,
+            loop_detection: None,
+            history_length: DEFAULT_HISTORY_LENGTH,
+            undo_limit: DEFAULT_UNDO_LIMIT,
+            watchpoints: Vec::new(),
+            memory_watchpoints: Vec::new(),
+            env: GuestEnv::default(),
+            env_address: ENV_BLOCK_ADDRESS,
+            cores: 1,
+            schedule: SchedulePolicy::default(),
+            switch_quantum: DEFAULT_SWITCH_QUANTUM,
+            output: OutputConfig::default(),
+            expect_path: None,
+            symbols_path: None,
+            guest_trace: false,
+            guest_stats: false,
+            file_sandbox: None,
+            stdin_path: None,
+            record_path: None,
+            replay_path: None,
+            exception_vector: None,
+            halt_address: HALT_ADDRESS,
+        }
+    }
+}
+
+/// MIPSシミュレータ
+#[derive(Debug)]
+pub struct MipsSimulator {
+    /// コア0（共有メモリは普段このコアが保持する）
+    processor: Processor,
+    /// コア1以降（メモリは実行中だけコア0から受け取る）
+    secondary_cores: Vec<Processor>,
+    /// マルチコア実行のスケジューラ
+    scheduler: Scheduler,
+    /// マルチコア実行での各コアの終了理由
+    core_outcomes: Vec<Option<RunResult>>,
+    config: SimulatorConfig,
+    /// デバッガからのパッチで上書きされた元の内容（アドレス, 元の値）
+    patch_backups: Vec<(MemoryAddress, Word)>,
+    /// 直前の実行の終了理由
+    last_outcome: Option<RunResult>,
+    /// ステップ実行モードで保存したマイクロアーキテクチャ状態（名前順）
+    microarch_snapshots: BTreeMap<String, MicroarchSnapshot>,
+    /// メモリに割り当てたフレームバッファ
+    framebuffer: Option<Rc<RefCell<Framebuffer>>>,
+}
+
+impl MipsSimulator {
+    /// 新しいシミュレータを作成（メモリを確保・マップできなければパニックするので、設定によって失敗しうる場合は try_new を使う）
+    pub fn new(config: SimulatorConfig) -> Self {
+        Self::try_new(config).expect("メモリを確保できません")
+    }
+    /// 新しいシミュレータを作成（メモリをマップできなければエラー）
+    pub fn try_new(config: SimulatorConfig) -> Result<Self, SimulatorError> {
+        let memory = Memory::with_backing(config.memory_size, &config.memory_backing)
+            .map_err(|e| SimulatorError::FileError(match &config.memory_backing {
+                MemoryBacking::File(path) => format!("メモリを '{}' にマップできません: {}", path, e),
+                _ => format!("メモリをマップできません: {}", e),
+            }))?;
+        let mut processor = Self::build_core(&config, memory, 0);
+        if let Some(value) = config.poison_byte {
+            processor.poison_memory(value);
+        }
+        for (range, protection) in &config.protections {
+            processor.protect_memory(range.clone(), *protection);
+        }
+        let framebuffer = config.framebuffer.clone().map(|framebuffer_config| {
+            let range = framebuffer_config.range().expect("範囲は引数の解析で確かめている");
+            let framebuffer = Rc::new(RefCell::new(Framebuffer::new(framebuffer_config)));
+            processor.map_device(range, Box::new(FramebufferDevice(framebuffer.clone())))
+                .expect("フレームバッファは最初に割り当てるデバイス");
+            framebuffer
+        });
+        // コア1以降は自分のメモリを持たず、実行中だけ共有メモリを受け取る
+        let secondary_cores = (1..config.cores.max(1))
+            .map(|core_id| Self::build_core(&config, Memory::with_size(0), core_id))
+            .collect();
+        let mut simulator = Self {
+            processor,
+            secondary_cores,
+            scheduler: Scheduler::new(config.schedule.clone(), config.switch_quantum),
+            core_outcomes: Vec::new(),
+            config,
+            patch_backups: Vec::new(),
+            last_outcome: None,
+            microarch_snapshots: BTreeMap::new(),
+            framebuffer,
+        };
+        simulator.connect_caches();
+        Ok(simulator)
+    }
+    /// マルチコア実行では各コアのキャッシュをスヌープバスでつなぐ（バスは共有メモリを持つコア0に渡す）
+    fn connect_caches(&mut self) {
+        if self.secondary_cores.is_empty() {
+            return;
+        }
+        self.processor.set_coherence(0, Some(SnoopBus::new(self.secondary_cores.len() + 1)));
+        for (index, core) in self.secondary_cores.iter_mut().enumerate() {
+            core.set_coherence(index + 1, None);
+        }
+    }
+    /// 設定に従ってコアを1つ作成
+    fn build_core(config: &SimulatorConfig, memory: Memory, core_id: usize) -> Processor {
+        let mut processor = Processor::with_memory(memory);
+        processor.set_isa(config.isa);
+        processor.set_bus_config(config.bus.clone());
+        processor.set_cache_config(config.cache.clone());
+        processor.set_timeout(config.timeout);
+        processor.set_max_instructions(config.max_instructions);
+        processor.set_timing_config(config.timing.clone());
+        processor.set_branch_predictors(BranchPredictors::new(&config.branch_predictors));
+        let mut fpu = config.fpu.build();
+        if let Some(approx) = &config.fpu_approx {
+            fpu = Box::new(ApproxFpu::new(fpu, approx.clone()));
+        }
+        processor.set_fpu_model(fpu);
+        processor.set_trace_enabled(!config.guest_trace);
+        processor.set_stats_enabled(!config.guest_stats);
+        // キャッシュを模擬しない高速な実行では、カバレッジとタイミングのヒストグラムは表示・書き出しを指定したときだけ記録する
+        if config.cache.bypass {
+            let report = config.stats_json_path.is_some();
+            processor.set_coverage_enabled(config.coverage || report);
+            processor.set_timing_enabled(config.timing_csv_path.is_some() || report);
+        }
+        processor.set_exception_vector(config.exception_vector);
+        processor.set_halt_address(config.halt_address);
+        processor.set_pc(config.program_start);
+        processor.set_initial_sp(config.initial_sp);
+        if let Some(gp) = config.initial_gp {
+            processor.set_initial_gp(gp);
+        }
+        if config.profile_top.is_some() {
+            processor.enable_profiler();
+        }
+        if config.profile_functions {
+            processor.enable_function_profiler();
+        }
+        if config.backtrace {
+            processor.enable_backtrace();
+        }
+        if let Some(block_size) = config.heatmap_block {
+            processor.enable_heatmap(block_size);
+        }
+        if let Some(page_size) = config.footprint_page {
+            processor.enable_footprint(page_size);
+        }
+        if config.register_stats {
+            processor.enable_register_usage();
+        }
+        if config.check_uninitialized {
+            processor.enable_uninitialized_check();
+        }
+        if config.check_poison {
+            processor.enable_poison_check(config.poison_byte.unwrap_or(DEFAULT_POISON_BYTE));
+        }
+        processor.set_history_length(config.history_length);
+        for watchpoint in &config.watchpoints {
+            processor.add_watchpoint(watchpoint.clone());
+        }
+        for watchpoint in &config.memory_watchpoints {
+            processor.add_memory_watchpoint(watchpoint.clone());
+        }
+        // 全命令の記録は大きくなるので、戻る操作のできるステップ実行モードでだけ記録する
+        if config.step_mode {
+            processor.set_undo_limit(config.undo_limit);
+        }
+        // 他のコアが共有メモリを書き換えるのを待つループを誤検出しないよう、マルチコア実行では検出しない
+        if config.cores == 1 {
+            processor.set_loop_detection(config.loop_detection.clone());
+        }
+        processor.set_file_sandbox(config.file_sandbox.as_ref().map(PathBuf::from));
+        if config.cores > 1 {
+            processor.set_core_id(core_id);
+        }
+        processor
+    }
+    /// デフォルト設定でシミュレータを作成
+    pub fn new_default() -> Self {
+        Self::new(SimulatorConfig::default())
+    }
+    /// プログラムをファイルから設定した形式で読み込む（auto なら内容と拡張子から形式を推定する）
+    pub fn load_program_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<LoadImage, SimulatorError> {
+        let program = Self::read_program_file(&self.config, path)?;
+        match &program {
+            ProgramFile::Words { words, symbols } => {
+                self.load_program(words)?;
+                // ラベルをシンボル表にして、トレースやブレークポイントで名前を使えるようにする
+                if !symbols.is_empty() {
+                    self.processor.set_symbols(SymbolTable::from_symbols(symbols.clone()));
+                }
+            }
+            ProgramFile::Image(image) => self.load_image(image)?,
+        }
+        // 読み込んだイメージは実行前の検査に使う
+        Ok(program.into_image(self.config.program_start))
+    }
+    /// 読み込んだイメージを実行前に検査し、問題があれば警告を表示する
+    pub fn report_program_issues(&self, image: &LoadImage) {
+        let isa = self.processor.get_isa();
+        let report = validate::validate(image, isa);
+        if report.issues.is_empty() {
+            return;
+        }
+        println!("警告: 実行前の検査で {} 命令中 {} 件の問題が見つかりました（無効な命令: {} 件）",
+            report.words, report.issues.len(), report.invalid_count());
+        print!("{}", report.format(isa, self.processor.get_symbols(), PROGRAM_ISSUES_SHOWN));
+        if report.looks_byte_swapped() {
+            println!("  無効な命令の多くはバイト順を入れ替えると有効になります。16進数のテキストなら --hex-byte-order を確かめてください");
+        }
+    }
+    /// プログラムファイルを設定した形式で解析する（メモリには置かない）
+    pub fn read_program_file<P: AsRef<Path>>(config: &SimulatorConfig, path: P) -> Result<ProgramFile, SimulatorError> {
+        let path = path.as_ref();
+        if config.program_format == ProgramFormat::Hex {
+            return Ok(ProgramFile::Words { words: Self::read_hex_program(config, path)?, symbols: Vec::new() });
+        }
+        let bytes = std::fs::read(path).map_err(|e| SimulatorError::FileError(format!("ファイルを開けません: {}", e)))?;
+        let format = match config.program_format {
+            ProgramFormat::Auto => ProgramFormat::detect(path, &bytes),
+            format => format,
+        };
+        let text = || std::str::from_utf8(&bytes)
+            .map_err(|e| SimulatorError::ParseError(format!("{} のファイルをテキストとして読めません: {}", format.name(), e)));
+        let image = match format {
+            ProgramFormat::Auto | ProgramFormat::Hex => {
+                return Ok(ProgramFile::Words { words: Self::read_hex_program(config, path)?, symbols: Vec::new() });
+            }
+            ProgramFormat::Bin => return Ok(ProgramFile::Words { words: loader::words_from_binary(&bytes), symbols: Vec::new() }),
+            ProgramFormat::Elf => elf::parse(&bytes, config.isa, config.memory_size),
+            ProgramFormat::IntelHex => records::parse_intel_hex(text()?),
+            ProgramFormat::Srec => records::parse_srec(text()?),
+            ProgramFormat::Asm => {
+                if config.isa != Isa::Mips {
+                    return Err(SimulatorError::ParseError("アセンブラはMIPSのみに対応しています".to_string()));
+                }
+                let program = Assembler::new(config.program_start).with_data_base(config.data_base).assemble(text()?)
+                    .map_err(|e| SimulatorError::ParseError(format!("アセンブルに失敗しました: {}", e)))?;
+                if program.data.is_empty() {
+                    return Ok(ProgramFile::Words { symbols: program.symbols(), words: program.words });
+                }
+                // .data セクションは命令とは別の実行しないセグメントとして置く
+                let symbols = program.symbols();
+                let text = program.words.iter().flat_map(|word| word.to_le_bytes()).collect();
+                return Ok(ProgramFile::Image(LoadImage {
+                    segments: vec![
+                        Segment { address: program.origin, data: text, executable: true },
+                        Segment { address: program.data_address, data: program.data, executable: false },
+                    ],
+                    entry: None,
+                    symbols,
+                }));
+            }
+        };
+        let image = image.map_err(|e| SimulatorError::ParseError(format!("{} のファイルを読み込めません: {}", format.name(), e)))?;
+        Ok(ProgramFile::Image(image))
+    }
+    /// 16進数のテキストのプログラムをファイルから読み込み、命令の列を返す
+    // Pというジェネリック型を定義し、「PはPath（ファイルパス）として参照できる型なら何でも良い」という制約（AsRef<Path>）を付けています。
+    // これにより、この関数を呼び出す側は、ファイルパスを様々な形式で渡せるようになり、利用者の使いやすさ（エルゴノミクス）を非常に高めます。
+    // 成功すれば()（中身は空）、失敗すればSimulatorError（エラーの種類を示す列挙型）を返す
+    fn read_hex_program<P: AsRef<Path>>(config: &SimulatorConfig, path: P) -> Result<Vec<Instruction>, SimulatorError> {
+        // ?演算子は、Result型に対して以下の処理を自動で行います。
+        // もし結果が成功 (Ok(値)) なら、Okを剥がして中の値だけを取り出す。
+        // もし結果が失敗 (Err(エラー)) なら、その場でこの関数を終了し、中のエラーを関数の呼び出し元に返す
+        let file = File::open(path).map_err(|e| SimulatorError::FileError(format!("ファイルを開けません: {}", e)))?;
+        // Fileから直接1行ずつ読むと、そのたびにOSを呼び出す必要があり、パフォーマンスが低下します。
+        // BufReaderは、最初にある程度の大きさの塊をまとめてメモリ上のバッファに読み込み、その後は高速なメモリから1行ずつ読み出します。
+        // これにより、OSの呼び出し回数が劇的に減り、処理が高速になります。
+        let reader = BufReader::new(file);
+
+        // 命令を格納するベクターを宣言
+        // let mut instructions = vec![];(マクロ呼び出し)と同じ
+        let mut instructions = Vec::new();
+        // --hex-layout bytes のときは行をまたいでバイト列を集め、最後にまとめてワードにする
+        let mut bytes = Vec::new();
+        let options = config.hex_options;
+
+        for (line_num, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| SimulatorError::FileError(format!("inputfileの {} 行目を読み込めませんでした: {}", line_num + 1, e)))?;
+            let line = line.trim();
+            // 空行またはコメントをスキップ
+            if line.is_empty() || line.starts_with('#') { continue };
+            // 16進数部分のみを抽出（コメントの前まで）
+            // if letで条件が成立した場合のみcomment_posが宣言され、そのスコープはif文内のみである
+            let hex_part = if let Some(comment_pos) = line.find('#') {
+                line[..comment_pos].trim()
+            } else {
+                line
+            };
+            // 16進数以外の文字を含む行はアセンブリ言語の命令（疑似命令を含む）としてアセンブルする
+            if !hex_part.chars().all(|c| c.is_ascii_hexdigit() || c.is_whitespace()) {
+                if options.layout == HexLayout::Bytes {
+                    return Err(SimulatorError::ParseError(format!("inputfileの {} 行目: バイト列の中にアセンブリ言語の行は書けません: '{}'", line_num + 1, hex_part)));
+                }
+                if config.isa != Isa::Mips {
+                    return Err(SimulatorError::ParseError(format!("inputfileの {} 行目: アセンブラはMIPSのみに対応しています: '{}'", line_num + 1, hex_part)));
+                }
+                let words = assembler::assemble_snippet(hex_part).map_err(|e| SimulatorError::ParseError(format!("inputfileの {} 行目: アセンブルに失敗しました: '{}': {}", line_num + 1, hex_part, e)))?;
+                instructions.extend(words);
+                continue;
+            }
+            // まだ文字列なので16進数に変換（バイト順・ワード順は --hex-* のオプションに従う）
+            let error = |e: String| SimulatorError::ParseError(format!("inputfileの {} 行目: {}", line_num + 1, e));
+            match options.layout {
+                HexLayout::Words => instructions.extend(options.line_words(hex_part).map_err(error)?),
+                HexLayout::Bytes => bytes.extend(HexOptions::line_bytes(hex_part).map_err(error)?),
+            }
+        }
+        if options.layout == HexLayout::Bytes {
+            instructions = options.words_from_bytes(&bytes);
+        }
+        Ok(instructions)
+    }
+    /// 期待する最終状態のファイルを読み込み、コア0のレジスタとメモリの値と比べる
+    pub fn check_expected_state(&self, path: &str) -> Result<StateComparison, SimulatorError> {
+        let expected = ExpectedState::load(path, |text| self.resolve_address(text)).map_err(SimulatorError::ParseError)?;
+        Ok(expected.compare(|target| match target {
+            ExpectTarget::Register(register, _) => Ok(self.processor.operand_value(*register)),
+            ExpectTarget::Memory(address) => self.processor.peek_word(*address).map_err(|e| e.to_string()),
+        }))
+    }
+    /// 設定したデータイメージをメモリに読み込み、（データイメージ, バイト数）を返す
+    pub fn load_data_images(&mut self) -> Result<Vec<(DataImage, usize)>, SimulatorError> {
+        let mut loaded = Vec::new();
+        for image in self.config.data_images.clone() {
+            let bytes = image.read().map_err(SimulatorError::FileError)?;
+            self.processor.load_data(image.address, &bytes).map_err(|e| SimulatorError::FileError(
+                format!("データ '{}' を 0x{:08X} から {} バイト読み込めません: {}", image.path, image.address, bytes.len(), e)))?;
+            loaded.push((image, bytes.len()));
+        }
+        Ok(loaded)
+    }
+    /// 設定したROMのイメージをメモリに読み込んで書き込みを禁止し、（イメージ, バイト数）を返す
+    /// 保護属性はキャッシュより前で確かめるので、ROMの内容も通常どおりキャッシュされる
+    pub fn load_rom_images(&mut self) -> Result<Vec<(DataImage, usize)>, SimulatorError> {
+        let mut loaded = Vec::new();
+        for image in self.config.rom_images.clone() {
+            let bytes = image.read().map_err(SimulatorError::FileError)?;
+            self.processor.load_data(image.address, &bytes).map_err(|e| SimulatorError::FileError(
+                format!("ROM '{}' を 0x{:08X} から {} バイト読み込めません: {}", image.path, image.address, bytes.len(), e)))?;
+            if !bytes.is_empty() {
+                self.processor.protect_memory(image.address..image.address + bytes.len() as MemoryAddress, Protection::ROM);
+            }
+            loaded.push((image, bytes.len()));
+        }
+        Ok(loaded)
+    }
+    /// シンボルファイルを読み込み、シンボルの数を返す
+    pub fn load_symbols_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, SimulatorError> {
+        let symbols = SymbolTable::load_file(path).map_err(SimulatorError::ParseError)?;
+        let count = symbols.len();
+        self.processor.set_symbols(symbols);
+        Ok(count)
+    }
+    /// アドレスを解析する（数値のほか "シンボル名" や "シンボル名+オフセット" も受け付ける）
+    pub fn resolve_address(&self, text: &str) -> Result<MemoryAddress, String> {
+        if let Ok(address) = parse_address(text) {
+            return Ok(address);
+        }
+        let (name, offset) = match text.trim().split_once('+') {
+            Some((name, offset)) => (name.trim(), parse_address(offset)?),
+            None => (text.trim(), 0),
+        };
+        self.processor.get_symbols().lookup(name)
+            .map(|address| address.wrapping_add(offset))
+            .ok_or_else(|| format!("無効なアドレスです: {}", text.trim()))
+    }
+    /// プログラムをメモリにロード
+    pub fn load_program(&mut self, program: &[Instruction]) -> Result<(), SimulatorError> {
+        self.processor.load_program(program, self.config.program_start).map_err(SimulatorError::MemoryError)?;
+        self.finish_load()
+    }
+    /// アドレスの付いたプログラムのイメージ（ELFなど）を読み込む（エントリポイントがなければプログラムの開始アドレスから実行する）
+    /// イメージにシンボルが含まれていれば、シンボル表として使う
+    pub fn load_image(&mut self, image: &LoadImage) -> Result<(), SimulatorError> {
+        let entry = image.entry.unwrap_or(self.config.program_start);
+        self.processor.load_segments(&image.segments, entry).map_err(SimulatorError::MemoryError)?;
+        if !image.symbols.is_empty() {
+            self.processor.set_symbols(SymbolTable::from_symbols(image.symbols.clone()));
+        }
+        self.finish_load()
+    }
+    /// プログラムの後に設定ブロックを置き、他のコアにプログラムを共有する
+    fn finish_load(&mut self) -> Result<(), SimulatorError> {
+        if !self.config.env.is_empty() {
+            self.processor.load_env_block(self.config.env_address, &self.config.env.to_block())
+                .map_err(SimulatorError::MemoryError)?;
+        }
+        for core in &mut self.secondary_cores {
+            core.share_program(&self.processor);
+        }
+        Ok(())
+    }
+    /// 指定アドレスのワードを書き換える（元の値は退避され、revert_patchesで戻せる）
+    pub fn patch_word(&mut self, address: MemoryAddress, value: Word) -> Result<Word, SimulatorError> {
+        let original = self.processor.peek_word(address).map_err(SimulatorError::MemoryError)?;
+        self.processor.poke_word(address, value).map_err(SimulatorError::MemoryError)?;
+        self.patch_backups.push((address, original));
+        Ok(original)
+    }
+
+    /// アセンブリのスニペット（';'区切り）をアセンブルして指定アドレスから書き込む
+    pub fn patch_code(&mut self, address: MemoryAddress, snippet: &str) -> Result<Vec<Instruction>, SimulatorError> {
+        if self.processor.get_isa() != Isa::Mips {
+            return Err(SimulatorError::ParseError("アセンブラはMIPSのみに対応しています".to_string()));
+        }
+        let words = assembler::assemble_snippet(snippet).map_err(SimulatorError::AsmError)?;
+        // 途中で範囲外になって中途半端に書き込まれないよう、先に全体の範囲を確認する
+        let addresses = (0..words.len() as u32)
+            .map(|i| address.checked_add(i * 4).ok_or(memory::MemoryError::AddressOutOfRange(address)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(SimulatorError::MemoryError)?;
+        for &word_address in &addresses {
+            self.processor.peek_word(word_address).map_err(SimulatorError::MemoryError)?;
+        }
+        for (&word_address, &word) in addresses.iter().zip(&words) {
+            self.patch_word(word_address, word)?;
+        }
+        Ok(words)
+    }
+
+    /// パッチを新しいものから順に元に戻し、戻したワード数を返す
+    pub fn revert_patches(&mut self) -> Result<usize, SimulatorError> {
+        let count = self.patch_backups.len();
+        while let Some((address, original)) = self.patch_backups.pop() {
+            self.processor.poke_word(address, original).map_err(SimulatorError::MemoryError)?;
+        }
+        Ok(count)
+    }
+
+    /// シミュレータを実行し、終了理由を返す
+    pub fn run(&mut self) -> RunResult {
+        let outcome = if self.config.step_mode {
+            self.run_step_mode()
+        } else if !self.secondary_cores.is_empty() {
+            self.run_multicore()
+        } else {
+            self.processor.run()
+        };
+        self.last_outcome = Some(outcome.clone());
+        outcome
+    }
+    /// 全コアをスケジューラの決めた順に実行する
+    /// 例外で止まったコアがあればその終了理由を、なければコア0の終了理由を返す
+    fn run_multicore(&mut self) -> RunResult {
+        let start_time = Instant::now();
+        let core_count = self.secondary_cores.len() + 1;
+        let mut outcomes: Vec<Option<RunResult>> = vec![None; core_count];
+        let mut instruction_counts = vec![0u64; core_count];
+
+        loop {
+            if let Some(timeout) = self.config.timeout {
+                if start_time.elapsed() >= timeout {
+                    let timeout = RunResult::Timeout { elapsed: start_time.elapsed(), instructions: instruction_counts.iter().sum() };
+                    for outcome in outcomes.iter_mut().filter(|o| o.is_none()) {
+                        *outcome = Some(timeout.clone());
+                    }
+                    break;
+                }
+            }
+
+            let runnable: Vec<bool> = outcomes.iter().map(Option::is_none).collect();
+            let cycles: Vec<u64> = self.cores().map(|core| core.get_stats().cycles).collect();
+            let core_id = match self.scheduler.next_core(&runnable, &cycles) {
+                Some(core_id) => core_id,
+                None => break,
+            };
+
+            // 共有メモリを選んだコアに渡して割り当て分だけ実行し、コア0に戻す
+            let core = match core_id {
+                0 => &mut self.processor,
+                _ => {
+                    let core = &mut self.secondary_cores[core_id - 1];
+                    core.swap_memory(&mut self.processor);
+                    core
+                }
+            };
+            core.apply_snoops();
+            for _ in 0..self.scheduler.quantum() {
+                if let Some(limit) = self.config.max_instructions {
+                    if instruction_counts[core_id] >= limit {
+                        println!("警告: コア{}が{}命令を超えました。強制終了します。", core_id, limit);
+                        outcomes[core_id] = Some(RunResult::InstructionLimit { limit });
+                        break;
+                    }
+                }
+                if let Some(outcome) = core.run_one() {
+                    outcomes[core_id] = Some(outcome);
+                    break;
+                }
+                instruction_counts[core_id] += 1;
+            }
+            if core_id != 0 {
+                self.secondary_cores[core_id - 1].swap_memory(&mut self.processor);
+            }
+        }
+        self.apply_snoops();
+
+        self.core_outcomes = outcomes;
+        self.core_outcomes.iter().flatten()
+            .find(|outcome| matches!(outcome, RunResult::Exception { .. }))
+            .or(self.core_outcomes[0].as_ref())
+            .cloned()
+            .unwrap_or(RunResult::Halted { pc: self.processor.get_pc() })
+    }
+    /// まだ反映していないスヌープを全コアのキャッシュに反映する（実行後にキャッシュの状態を表示するため）
+    fn apply_snoops(&mut self) {
+        self.processor.apply_snoops();
+        for core in &mut self.secondary_cores {
+            core.swap_memory(&mut self.processor);
+            core.apply_snoops();
+            core.swap_memory(&mut self.processor);
+        }
+    }
+    /// ゲストプログラムの出力先を設定（マルチコア実行ではコア0の出力）
+    pub fn set_output(&mut self, output: GuestOutput) {
+        self.processor.set_output(output);
+    }
+    /// ゲストプログラムの入力元を設定（マルチコア実行ではコア0の入力）
+    pub fn set_input(&mut self, input: GuestInput) {
+        self.processor.set_input(input);
+    }
+    /// システムコールのハンドラを設定する（ハンドラは状態を持てるので、マルチコア実行ではコアごとにmakeで作成する）
+    pub fn set_syscall_handler(&mut self, make: impl Fn() -> Box<dyn SyscallHandler>) {
+        self.processor.set_syscall_handler(make());
+        for core in &mut self.secondary_cores {
+            core.set_syscall_handler(make());
+        }
+    }
+    /// キャッシュのモデルを差し替える（マルチコア実行ではコアごとにmakeで作成する）
+    pub fn set_cache_model(&mut self, make: impl Fn() -> Box<dyn CacheLike>) {
+        for core in std::iter::once(&mut self.processor).chain(&mut self.secondary_cores) {
+            core.set_cache_model(make());
+            core.set_bus_config(self.config.bus.clone());
+        }
+        self.connect_caches();
+    }
+    /// アドレス範囲にメモリマップドI/Oのデバイスを割り当てる（マルチコア実行では共有メモリに割り当て、全コアから見える）
+    pub fn map_device(&mut self, range: Range<MemoryAddress>, device: Box<dyn MmioDevice>) -> Result<(), SimulatorError> {
+        self.processor.map_device(range, device).map_err(SimulatorError::MemoryError)
+    }
+    /// フレームバッファを画像として書き出し、書き出したファイルと制御レジスタへの書き込みで書き出したフレーム数を返す
+    /// （フレームバッファがなければNone）
+    pub fn finish_framebuffer(&mut self) -> Option<Result<(String, u32), SimulatorError>> {
+        let mut framebuffer = self.framebuffer.as_ref()?.borrow_mut();
+        Some(framebuffer.finish()
+            .map(|()| (framebuffer.config().output.clone(), framebuffer.frames()))
+            .map_err(SimulatorError::FileError))
+    }
+    /// キャッシュアクセスのトレースの書き出し先を設定する（マルチコア実行ではコア0のキャッシュ）
+    pub fn set_cache_trace(&mut self, trace: Option<CacheTraceWriter>) {
+        self.processor.set_cache_trace(trace);
+    }
+    /// キャッシュアクセスのトレースを書き出し終え、書き出したアクセス数を返す（記録していなければNone）
+    pub fn finish_cache_trace(&mut self) -> Option<Result<u64, SimulatorError>> {
+        self.processor.finish_cache_trace().map(|result| result.map_err(SimulatorError::FileError))
+    }
+    /// メモリアクセスのトレースの書き出し先を設定する（マルチコア実行ではコア0のアクセス）
+    pub fn set_mem_trace(&mut self, trace: Option<MemTraceWriter>) {
+        self.processor.set_mem_trace(trace);
+    }
+    /// メモリアクセスのトレースを書き出し終え、書き出したアクセス数を返す（記録していなければNone）
+    pub fn finish_mem_trace(&mut self) -> Option<Result<u64, SimulatorError>> {
+        self.processor.finish_mem_trace().map(|result| result.map_err(SimulatorError::FileError))
+    }
+    /// メモリをファイルにマップしていれば、キャッシュの内容も含めてファイルに書き出し、そのファイル名を返す
+    pub fn finish_memory_file(&mut self) -> Option<Result<String, SimulatorError>> {
+        let MemoryBacking::File(path) = &self.config.memory_backing else {
+            return None;
+        };
+        Some(self.processor.sync_memory()
+            .map(|()| path.clone())
+            .map_err(|e| SimulatorError::FileError(format!("メモリを '{}' に書き出せません: {}", path, e))))
+    }
+    /// ホストから値を受け取るシステムコールの記録・再生を設定する（マルチコア実行ではコア0のみ）
+    pub fn set_replay_log(&mut self, log: Option<ReplayLog>) {
+        self.processor.set_replay_log(log);
+    }
+    /// 記録したシステムコールの結果をファイルに書き出す
+    pub fn write_replay_log<P: AsRef<Path>>(&self, path: P) -> Result<(), SimulatorError> {
+        let text = self.processor.replay_log().map(ReplayLog::to_text).unwrap_or_default();
+        std::fs::write(path, text)
+            .map_err(|e| SimulatorError::FileError(format!("入力の記録に書き込めません: {}", e)))
+    }
+    /// ゲストプログラムの出力を書き出し終え、期待出力との比較結果を返す
+    pub fn finish_output(&mut self) -> Option<OutputComparison> {
+        self.processor.finish_output()
+    }
+    /// ゲストプログラムの出力をコンソールに出さずに取り込みながら実行し、終了理由と出力のバイト列を返す
+    /// （組み込んで使うテストハーネスがPPM画像のようなバイナリ出力をファイルを介さずに検証するため）
+    pub fn run_captured(&mut self) -> CapturedRun {
+        self.processor.start_output_capture();
+        let outcome = self.run();
+        self.finish_output();
+        let output = self.processor.take_captured_output().unwrap_or_default();
+        CapturedRun { outcome, output }
+    }
+    /// マルチコア実行での各コアの終了理由
+    pub fn get_core_outcomes(&self) -> &[Option<RunResult>] {
+        &self.core_outcomes
+    }
+    /// ステップ実行モードで実行
+    fn run_step_mode(&mut self) -> RunResult {
+        let mut step_count = 0;
+        loop {
+            let pc = self.processor.get_pc();
+            if pc == self.processor.get_halt_address() {
+                return RunResult::Halted { pc };
+            }
+            println!("\n=== ステップ {} ===", step_count);
+            println!("PC: {}", self.processor.get_symbols().format_address(pc));
+
+            // 現在の命令を表示（表示のための読み込みでキャッシュの状態を変えない）
+            let instruction = match self.processor.peek_word(pc) {
+                Ok(instruction) => instruction,
+                Err(e) => return RunResult::Exception { pc, error: ProcessorError::MemoryError(e.in_access(Access::Execute, 4, pc)) },
+            };
+            println!("命令: 0x{:08X} ({})", instruction, self.processor.disassemble_at(instruction, pc));
+            
+            // ユーザー入力を待つ
+            print!("実行しますか？ (Enter: 実行, 'q': 終了, 's': 状態表示, 'p': パッチ, 'w': 書き込み, 'f': 検索, 'm': メモリ表示, 'u': パッチを戻す, 'k': キャッシュ状態の保存/復元, 'c [セット] [セット]': キャッシュの内容, 'b [n]': n命令戻る): ");
+            io::stdout().flush().unwrap();
+            
+            let mut input = String::new();
+            // 標準入力から一行読み込み、inputに格納。.unwrap() は、読み込み中にエラーが発生した場合にプログラムを停止させる
+            io::stdin().read_line(&mut input).unwrap();
+            let input = input.trim();
+            
+            let (command, command_args) = match input.find(char::is_whitespace) {
+                Some(pos) => (&input[..pos], input[pos..].trim()),
+                None => (input, ""),
+            };
+
+            match command {
+                "q" => return RunResult::UserQuit { pc },
+                "s" => {
+                    println!("{}", self.processor.dump_state());
+                    continue;
+                }
+                "p" => {
+                    // p <アドレス> <命令>[; <命令>...]
+                    if let Err(e) = self.handle_patch_command(command_args) {
+                        println!("エラー: {}", e);
+                    }
+                    continue;
+                }
+                "w" => {
+                    // w <アドレス> <値>
+                    if let Err(e) = self.handle_write_command(command_args) {
+                        println!("エラー: {}", e);
+                    }
+                    continue;
+                }
+                "f" => {
+                    // f <値|"文字列"> [<開始アドレス> <終了アドレス>]
+                    if let Err(e) = self.handle_find_command(command_args) {
+                        println!("エラー: {}", e);
+                    }
+                    continue;
+                }
+                "k" => {
+                    // k save <名前> | k load <名前|cold> | k list
+                    if let Err(e) = self.handle_microarch_command(command_args) {
+                        println!("エラー: {}", e);
+                    }
+                    continue;
+                }
+                "m" => {
+                    // m <アドレス> <バイト数>
+                    match self.handle_memory_command(command_args) {
+                        Ok(dump) => print!("{}", dump),
+                        Err(e) => println!("エラー: {}", e),
+                    }
+                    continue;
+                }
+                "c" => {
+                    // c [<セット> [<最後のセット>]]（省略すると全セット）
+                    match parse_set_range(command_args) {
+                        Ok(sets) => print!("{}", self.get_cache_dump(sets)),
+                        Err(e) => println!("エラー: {}", e),
+                    }
+                    continue;
+                }
+                "b" => {
+                    // b [命令数]（省略すると1命令）
+                    match self.handle_rewind_command(command_args) {
+                        Ok(count) => step_count -= count,
+                        Err(e) => println!("エラー: {}", e),
+                    }
+                    continue;
+                }
+                "u" => {
+                    match self.revert_patches() {
+                        Ok(count) => println!("{} ワードを元に戻しました", count),
+                        Err(e) => println!("エラー: {}", e),
+                    }
+                    continue;
+                }
+                _ => {
+                    // 命令を実行
+                    match self.processor.step() {
+                        Ok(StepSignal::Branch) => println!("分岐が発生しました"),
+                        Ok(StepSignal::Continue) => {}
+                        Ok(StepSignal::Halt { code }) => return RunResult::Exited { code },
+                        // 命令は完了しているので、知らせてステップ実行を続ける
+                        Err(e @ (ProcessorError::Watchpoint { .. } | ProcessorError::MemoryWatchpoint { .. })) => println!("{}", e),
+                        Err(e) => {
+                            println!("エラー: {}", e);
+                            return RunResult::from_error(pc, e);
+                        }
+                    }
+                }
+            }
+            step_count += 1;
+        }
+    }
+
+    /// ステップ実行モードの p コマンドを処理する
+    fn handle_patch_command(&mut self, args: &str) -> Result<(), String> {
+        let (address, snippet) = args.split_once(char::is_whitespace)
+            .ok_or("使用方法: p <アドレス> <命令>[; <命令>...]")?;
+        let address = self.resolve_address(address)?;
+        let words = self.patch_code(address, snippet).map_err(|e| e.to_string())?;
+        for (i, word) in words.iter().enumerate() {
+            let (_, original) = self.patch_backups[self.patch_backups.len() - words.len() + i];
+            println!("0x{:08X}: 0x{:08X} -> 0x{:08X} ({})",
+                address + (i * 4) as u32, original, word, self.processor.disassemble_at(*word, address + (i * 4) as u32));
+        }
+        Ok(())
+    }
+
+    /// ステップ実行モードの b コマンドを処理し、戻った命令数を返す
+    fn handle_rewind_command(&mut self, args: &str) -> Result<usize, String> {
+        let count = match args {
+            "" => 1,
+            count => count.parse().map_err(|_| "使用方法: b [命令数]".to_string())?,
+        };
+        if self.processor.undo_depth() == 0 {
+            return Err("戻れる命令の記録がありません".to_string());
+        }
+        let rewound = self.processor.rewind(count);
+        if rewound < count {
+            println!("記録が残っている {} 命令だけ戻りました（--undo-limit で記録する命令数を変えられます）", rewound);
+        } else {
+            println!("{} 命令戻りました", rewound);
+        }
+        Ok(rewound)
+    }
+
+    /// ステップ実行モードの k コマンドを処理する
+    fn handle_microarch_command(&mut self, args: &str) -> Result<(), String> {
+        const USAGE: &str = "使用方法: k save <名前> | k load <名前|cold> | k list";
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("save"), Some(name)) => {
+                let snapshot = self.processor.save_microarch_state();
+                println!("マイクロアーキテクチャ状態 '{}' を保存しました（有効なキャッシュライン: {}）", name, snapshot.cache.valid_lines());
+                self.microarch_snapshots.insert(name.to_string(), snapshot);
+            }
+            (Some("load"), Some(name)) => {
+                let snapshot = match name {
+                    "cold" => MicroarchSnapshot::cold(),
+                    _ => self.microarch_snapshots.get(name).cloned()
+                        .ok_or_else(|| format!("保存されていない状態です: {}", name))?,
+                };
+                self.processor.restore_microarch_state(&snapshot).map_err(|e| e.to_string())?;
+                println!("マイクロアーキテクチャ状態 '{}' を復元しました", name);
+            }
+            (Some("list"), None) => {
+                for (name, snapshot) in &self.microarch_snapshots {
+                    println!("  {} (有効なキャッシュライン: {})", name, snapshot.cache.valid_lines());
+                }
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+        Ok(())
+    }
+
+    /// ステップ実行モードの w コマンドを処理する
+    fn handle_write_command(&mut self, args: &str) -> Result<(), String> {
+        let (address, value) = args.split_once(char::is_whitespace)
+            .ok_or("使用方法: w <アドレス> <値>")?;
+        let address = self.resolve_address(address)?;
+        let value = assembler::parse_integer(value)
+            .map_err(|e| e.to_string())? as Word;
+        let original = self.patch_word(address, value).map_err(|e| e.to_string())?;
+        println!("0x{:08X}: 0x{:08X} -> 0x{:08X}", address, original, value);
+        Ok(())
+    }
+
+    /// ステップ実行モードの m コマンドを処理し、16進ダンプを返す
+    fn handle_memory_command(&self, args: &str) -> Result<String, String> {
+        let (address, length) = args.split_once(char::is_whitespace)
+            .ok_or("使用方法: m <アドレス> <バイト数>")?;
+        let address = self.resolve_address(address)?;
+        let length = parse_address(length)
+            .map_err(|_| format!("無効なバイト数です: {}", length.trim()))?;
+        self.dump_memory(address, length as usize).map_err(|e| e.to_string())
+    }
+
+    /// メモリの [start, start + length) を16進ダンプに整形する（キャッシュ上の書き戻されていない内容も反映する）
+    pub fn dump_memory(&self, start: MemoryAddress, length: usize) -> Result<String, SimulatorError> {
+        let bytes = self.processor.peek_range(start, length).map_err(SimulatorError::MemoryError)?;
+        Ok(memory::format_hexdump(start, &bytes))
+    }
+
+    /// 設定したメモリダンプを書き出すか、表示する文字列を返す（ファイルに書き出したものはNone）
+    pub fn write_memory_dump(&self, dump: &MemoryDump) -> Result<Option<String>, SimulatorError> {
+        let text = self.dump_memory(dump.start, dump.length)?;
+        match &dump.output {
+            Some(path) => std::fs::write(path, text)
+                .map(|()| None)
+                .map_err(|e| SimulatorError::FileError(format!("メモリダンプ '{}' に書き込めません: {}", path, e))),
+            None => Ok(Some(text)),
+        }
+    }
+
+    /// ステップ実行モードの f コマンドを処理する
+    fn handle_find_command(&self, args: &str) -> Result<(), String> {
+        let (pattern, rest) = parse_search_pattern(args)?;
+        let range: Vec<&str> = rest.split_whitespace().collect();
+        let (start, end) = match range.as_slice() {
+            [] => (0, self.processor.memory_size() as MemoryAddress),
+            [start, end] => (self.resolve_address(start)?, self.resolve_address(end)?),
+            _ => return Err("使用方法: f <値|\"文字列\"> [<開始アドレス> <終了アドレス>]".to_string()),
+        };
+        let matches = self.find_in_memory(&pattern, start, end).map_err(|e| e.to_string())?;
+        println!("{} 件見つかりました", matches.len());
+        for &address in matches.iter().take(MAX_FIND_RESULTS) {
+            // 見つかった位置を含む16バイト境界の行を表示する
+            let row = address & !0xF;
+            let length = 16.min(self.processor.memory_size() - row as usize);
+            let bytes = self.processor.peek_range(row, length).map_err(|e| e.to_string())?;
+            print!("0x{:08X} -> {}", address, memory::format_hexdump(row, &bytes));
+        }
+        if matches.len() > MAX_FIND_RESULTS {
+            println!("（残り {} 件は省略）", matches.len() - MAX_FIND_RESULTS);
+        }
+        Ok(())
+    }
+
+    /// メモリの [start, end) からバイト列を検索する（キャッシュ上の書き戻されていない内容も対象）
+    pub fn find_in_memory(&self, pattern: &[u8], start: MemoryAddress, end: MemoryAddress) -> Result<Vec<MemoryAddress>, SimulatorError> {
+        self.processor.find_bytes(pattern, start, end).map_err(SimulatorError::MemoryError)
+    }
+
+    /// 全コア（コア0から番号順）
+    fn cores(&self) -> impl Iterator<Item = &Processor> {
+        std::iter::once(&self.processor).chain(&self.secondary_cores)
+    }
+
+    /// プロセッサの状態を取得（マルチコア実行ではコア1以降のレジスタとコアごとの統計も含む）
+    pub fn get_processor_state(&self) -> String {
+        let mut state = self.processor.dump_state();
+        if self.secondary_cores.is_empty() {
+            return state;
+        }
+        for (core_id, core) in self.secondary_cores.iter().enumerate() {
+            state.push_str(&format!("\n=== コア{} ===\n{}", core_id + 1, core.register_dump()));
+        }
+        state.push_str("\n=== コアごとの統計 ===\n");
+        state.push_str(&format!("  {:<6} {:>12} {:>12} {:>8} {:>10} {:>8} {:>16}",
+            "コア", "命令数", "サイクル数", "CPI", "ヒット率", "無効化", "コヒーレンスミス"));
+        for (core_id, core) in self.cores().enumerate() {
+            let stats = core.get_stats();
+            let cache_stats = core.get_cache_stats();
+            state.push_str(&format!("\n  {:<6} {:>12} {:>12} {:>8.3} {:>9.2}% {:>8} {:>16}", core_id, stats.instructions_executed,
+                stats.cycles, stats.cpi(), cache_stats.hit_rate() * 100.0, cache_stats.invalidations, cache_stats.coherence_misses));
+        }
+        state
+    }
+
+    /// セットの範囲に含まれるキャッシュラインの状態を表形式で取得（マルチコア実行ではコア0のキャッシュ）
+    pub fn get_cache_dump(&self, sets: Range<usize>) -> String {
+        cache::format_cache_dump(&self.processor.dump_cache(sets))
+    }
+
+    /// 命令カバレッジのレポートを取得
+    pub fn get_coverage_report(&self) -> String {
+        self.processor.coverage_report()
+    }
+
+    /// 実行回数の多い命令のレポートを取得（プロファイルが無効ならNone）
+    pub fn get_profile_report(&self) -> Option<String> {
+        self.processor.profile_report(self.config.profile_top?)
+    }
+
+    /// 関数ごとのフラットプロファイルを取得（関数プロファイルが無効ならNone）
+    pub fn get_function_profile_report(&self) -> Option<String> {
+        self.processor.function_profile_report()
+    }
+
+    /// アクセスの多いブロックのレポートを取得（ヒートマップが無効ならNone）
+    pub fn get_heatmap_report(&self) -> Option<String> {
+        self.processor.heatmap_report(self.config.heatmap_top)
+    }
+
+    /// 触れたメモリとヒープ・スタックの最大のレポートを取得（集計が無効ならNone）
+    pub fn get_footprint_report(&self) -> Option<String> {
+        self.processor.footprint_report()
+    }
+
+    /// ブロックごとのロード・ストアの回数をCSVで書き出し、書き出したブロック数を返す
+    pub fn write_heatmap_csv(&self, path: &str) -> Result<usize, SimulatorError> {
+        let heatmap = self.processor.heatmap()
+            .ok_or_else(|| SimulatorError::FileError("ヒートマップを集計していません".to_string()))?;
+        heatmap.write_csv(path).map_err(SimulatorError::FileError)
+    }
+
+    /// レジスタごとの読み出し・書き込み回数のレポートを取得（集計が無効ならNone）
+    pub fn get_register_usage_report(&self) -> Option<String> {
+        self.processor.register_usage_report()
+    }
+
+    /// ロードしたプログラムに含まれる既知だが未実装の命令（ニーモニック, 出現数）
+    pub fn get_unimplemented_instructions(&self) -> Vec<(&'static str, usize)> {
+        self.processor.unimplemented_instructions()
+    }
+
+    /// 統計情報を取得
+    pub fn get_stats(&self) -> &processor::ProcessorStats {
+        self.processor.get_stats()
+    }
+
+    /// キャッシュ統計を取得
+    pub fn get_cache_stats(&self) -> &cache::CacheStats {
+        self.processor.get_cache_stats()
+    }
+
+    /// 統計情報を機械可読なレポートとして取得（実行済みなら終了理由も含む）
+    pub fn get_stats_report(&self) -> StatsReport {
+        let mut report = StatsReport::new();
+        if let Some(outcome) = &self.last_outcome {
+            outcome.append_to_report(&mut report);
+        }
+        for (key, value) in self.processor.stats_report().entries() {
+            match value {
+                ReportValue::Number(v) => report.push(key, *v),
+                ReportValue::Text(v) => report.push_text(key, v),
+            }
+        }
+        if !self.secondary_cores.is_empty() {
+            for (core_id, core) in self.cores().enumerate() {
+                let stats = core.get_stats();
+                report.push_count(&format!("core.{}.instructions", core_id), stats.instructions_executed);
+                report.push_count(&format!("core.{}.cycles", core_id), stats.cycles);
+                report.push(&format!("core.{}.cpi", core_id), stats.cpi());
+                report.push(&format!("core.{}.cache_hit_rate", core_id), core.get_cache_stats().hit_rate());
+                report.push_count(&format!("core.{}.invalidations", core_id), core.get_cache_stats().invalidations);
+                report.push_count(&format!("core.{}.coherence_misses", core_id), core.get_cache_stats().coherence_misses);
+            }
+        }
+        report
+    }
+
+    /// 統計情報をJSONファイルに書き出す
+    pub fn write_stats_json<P: AsRef<Path>>(&self, path: P) -> Result<(), SimulatorError> {
+        std::fs::write(path, self.get_stats_report().to_json() + "\n")
+            .map_err(|e| SimulatorError::FileError(format!("統計ファイルに書き込めません: {}", e)))
+    }
+
+    /// シミュレータの状態をスナップショットのファイルに保存する（シングルコアのみ）
+    /// 長い実行を中断して再開したり、実機と結果が食い違う位置を二分探索したりするのに使う
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), SimulatorError> {
+        self.check_snapshot_supported()?;
+        std::fs::write(path, self.processor.save_snapshot())
+            .map_err(|e| SimulatorError::FileError(format!("スナップショットに書き込めません: {}", e)))
+    }
+
+    /// スナップショットのファイルからシミュレータの状態を復元する（シングルコアのみ）
+    pub fn load_snapshot<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SimulatorError> {
+        self.check_snapshot_supported()?;
+        let bytes = std::fs::read(path)
+            .map_err(|e| SimulatorError::FileError(format!("スナップショットを読み込めません: {}", e)))?;
+        self.processor.restore_snapshot(&bytes).map_err(SimulatorError::SnapshotError)
+    }
+
+    fn check_snapshot_supported(&self) -> Result<(), SimulatorError> {
+        if self.secondary_cores.is_empty() {
+            Ok(())
+        } else {
+            Err(SimulatorError::SnapshotError(SnapshotError::Mismatch("マルチコア実行には対応していません".to_string())))
+        }
+    }
+
+    /// 命令タイミングのヒストグラムとストール要因をCSVファイルに書き出す
+    pub fn write_timing_csv<P: AsRef<Path>>(&self, path: P) -> Result<(), SimulatorError> {
+        std::fs::write(path, self.processor.get_timing_stats().to_csv())
+            .map_err(|e| SimulatorError::FileError(format!("タイミングファイルに書き込めません: {}", e)))
+    }
+
+    /// ベンチマークのカーネルを読み込んで実行し、結果を返す
+    pub fn run_kernel(&mut self, kernel: &Kernel) -> Result<BenchResult, SimulatorError> {
+        let program = kernel.assemble()
+            .map_err(|e| SimulatorError::ParseError(format!("カーネル '{}' のアセンブルに失敗しました: {}", kernel.name, e)))?;
+        // カーネルはジャンプ先を絶対アドレスで書いているので、--program-start によらず PC_INITIAL に置く
+        self.config.program_start = PC_INITIAL;
+        self.load_program(&program)?;
+        let outcome = self.run();
+        let stats = self.processor.get_stats();
+        Ok(BenchResult {
+            name: kernel.name,
+            outcome,
+            instructions: stats.instructions_executed,
+            cycles: stats.cycles,
+            cache_hit_rate: self.processor.get_cache_stats().hit_rate(),
+            result: self.processor.get_register(4),
+            expected: kernel.expected,
+        })
+    }
+
+    /// 設定を取得
+    pub fn get_config(&self) -> &SimulatorConfig {
+        &self.config
+    }
+
+    /// コア0のプロセッサを取得
+    pub fn get_processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    /// 設定を更新
+    pub fn set_config(&mut self, config: SimulatorConfig) {
+        self.config = config;
+    }
+}
+
+/// 出力を取り込んだ実行の結果
+#[derive(Debug, Clone)]
+pub struct CapturedRun {
+    /// 終了理由
+    pub outcome: RunResult,
+    /// ゲストプログラムの出力（システムコールで出力されたバイト列そのまま）
+    pub output: Vec<u8>,
+}
+
+/// シミュレータエラー
+//std::fmt::Display	{}	最終ユーザー向け。エラーの「ユーザーフレンドリーな簡潔な説明」を提供します。
+//std::fmt::Debug	{:?}	開発者向け。デバッグ用の「構造的な詳細情報」を提供します。
+#[derive(Debug, Clone)]
+pub enum SimulatorError {
+    FileError(String),
+    ParseError(String),
+    MemoryError(memory::MemoryError),
+    ProcessorError(ProcessorError),
+    AsmError(AsmError),
+    SnapshotError(SnapshotError),
+}
+
+impl std::fmt::Display for SimulatorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimulatorError::FileError(msg) => write!(f, "ファイルエラー: {}", msg),
+            SimulatorError::ParseError(msg) => write!(f, "解析エラー: {}", msg),
+            SimulatorError::MemoryError(e) => write!(f, "メモリエラー: {}", e),
+            SimulatorError::ProcessorError(e) => write!(f, "プロセッサエラー: {}", e),
+            SimulatorError::AsmError(e) => write!(f, "アセンブルエラー: {}", e),
+            SimulatorError::SnapshotError(e) => write!(f, "スナップショットエラー: {}", e),
+        }
+    }
+}
+
+//Display トレイト（エラーをユーザーフレンドリーに表示する）の実装が既にあるため、std::error::Error トレイトの実装は形式的なものになっている
+impl std::error::Error for SimulatorError {}
+
+/// アドレスを解析する（0xで始まる16進数または10進数）
+pub fn parse_address(text: &str) -> Result<MemoryAddress, String> {
+    let text = text.trim();
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    value.map_err(|_| format!("無効なアドレスです: {}", text))
+}
+
+/// 検索パターンを解析し、残りの文字列とともに返す
+/// "文字列" はASCIIのバイト列、0xで始まる値は桁数に応じてバイト・ハーフワード・ワードとして
+/// メモリ上の並び（リトルエンディアン）に変換する
+fn parse_search_pattern(text: &str) -> Result<(Vec<u8>, &str), String> {
+    let text = text.trim();
+    if let Some(quoted) = text.strip_prefix('"') {
+        let close = quoted.find('"').ok_or("文字列が閉じられていません")?;
+        if close == 0 {
+            return Err("空の文字列は検索できません".to_string());
+        }
+        return Ok((quoted.as_bytes()[..close].to_vec(), &quoted[close + 1..]));
+    }
+
+    let (token, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let hex = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X"))
+        .ok_or_else(|| format!("検索する値は0xで始まる16進数か\"文字列\"で指定してください: {}", token))?;
+    let value = u32::from_str_radix(hex, 16).map_err(|_| format!("無効な値です: {}", token))?;
+    let width = match hex.len() {
+        1..=2 => 1,
+        3..=4 => 2,
+        _ => 4,
+    };
+    Ok((value.to_le_bytes()[..width].to_vec(), rest))
+}
+
+/// ステップ実行モードの c コマンドのセットの範囲を解析する
+/// 空なら全セット、1つならそのセットだけ、2つならその間のセット（両端を含む）
+fn parse_set_range(text: &str) -> Result<Range<usize>, String> {
+    const USAGE: &str = "使用方法: c [<セット> [<最後のセット>]]";
+    let sets = text.split_whitespace()
+        .map(|set| set.parse::<usize>().ok().filter(|&set| set < CACHE_SETS)
+            .ok_or_else(|| format!("セットは0〜{}で指定してください: {}", CACHE_SETS - 1, set)))
+        .collect::<Result<Vec<usize>, String>>()?;
+    match sets.as_slice() {
+        [] => Ok(0..CACHE_SETS),
+        [set] => Ok(*set..set + 1),
+        [first, last] if first <= last => Ok(*first..last + 1),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench;
+
+    /// アセンブリの1行を命令のワードにする
+    fn asm(line: &str) -> Instruction {
+        assembler::parse_instruction(line).unwrap().encode().unwrap()
+    }
+
+    #[test]
+    fn test_simulator_creation() {
+        let config = SimulatorConfig::default();
+        let simulator = MipsSimulator::new(config);
+        assert_eq!(simulator.get_config().memory_size, 16 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_load_program() {
+        let mut simulator = MipsSimulator::new_default();
+        let program = vec![0x00430820u32, 0x0000000Cu32]; // add $1, $2, $3; syscall
+        simulator.load_program(&program).unwrap();
+    }
+
+    #[test]
+    fn test_patch_and_revert() {
+        let mut simulator = MipsSimulator::new_default();
+        let program = vec![0x00430820u32, 0x0000000Cu32]; // add $1, $2, $3; syscall
+        simulator.load_program(&program).unwrap();
+        let start = simulator.get_config().program_start;
+
+        simulator.patch_code(start, "addi $t0, $zero, 5; sub $1, $2, $3").unwrap();
+        assert_eq!(simulator.processor.peek_word(start).unwrap(), 0x20080005);
+        assert_eq!(simulator.processor.peek_word(start + 4).unwrap(), 0x00430822);
+
+        // アドレス空間の末尾を越えるパッチは何も書き込まずにエラーになる
+        assert!(matches!(simulator.patch_code(0xFFFFFFFC, "nop; nop"), Err(SimulatorError::MemoryError(_))));
+
+        assert_eq!(simulator.revert_patches().unwrap(), 2);
+        assert_eq!(simulator.processor.peek_word(start).unwrap(), 0x00430820);
+        assert_eq!(simulator.processor.peek_word(start + 4).unwrap(), 0x0000000C);
+    }
+
+    #[test]
+    fn test_exit2_status() {
+        let mut simulator = MipsSimulator::new_default();
+        let program = assembler::assemble_snippet("li $a0, 3; li $v0, 17; syscall").unwrap();
+        simulator.load_program(&program).unwrap();
+        let outcome = simulator.run();
+        assert_eq!(outcome, RunResult::Exited { code: 3 });
+        assert_eq!(outcome.exit_status(), 3);
+        assert!(!outcome.is_success());
+        assert_eq!(RunResult::InstructionLimit { limit: 1 }.exit_status(), 1);
+        // ホストで下位8ビットが0になる終了コードも失敗として返す
+        for code in [256, 512, -256] {
+            assert_eq!(RunResult::Exited { code }.exit_status(), 1);
+        }
+        assert_eq!(RunResult::Exited { code: 0 }.exit_status(), 0);
+        // 正常終了でない結果は、is_success と同じく0以外を返す
+        for outcome in [RunResult::Breakpoint { pc: 0, code: 0 }, RunResult::UserQuit { pc: 0 }] {
+            assert!(!outcome.is_success());
+            assert_eq!(outcome.exit_status(), 1);
+        }
+        assert_eq!(RunResult::Halted { pc: 0 }.exit_status(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_resume() {
+        let program = [
+            0x20080005, // addi $t0, $zero, 5
+            0xAC080100, // loop: sw $t0, 0x100($zero)
+            0x2108FFFF, // addi $t0, $t0, -1
+            0x1500FFFE, // bne $t0, $zero, loop
+            0x2002000A, // addi $v0, $zero, 10
+            0x0000000C, // syscall
+        ];
+        let mut uninterrupted = MipsSimulator::new_default();
+        uninterrupted.load_program(&program).unwrap();
+        assert!(uninterrupted.run().is_success());
+
+        // 途中で止めて保存し、別のシミュレータで再開する
+        let path = std::env::temp_dir().join(format!("mips_simulator_snapshot_{}.bin", std::process::id()));
+        let config = SimulatorConfig { max_instructions: Some(6), ..SimulatorConfig::default() };
+        let mut first = MipsSimulator::new(config);
+        first.load_program(&program).unwrap();
+        assert_eq!(first.run(), RunResult::InstructionLimit { limit: 6 });
+        first.save_snapshot(&path).unwrap();
+
+        let mut resumed = MipsSimulator::new_default();
+        resumed.load_program(&program).unwrap();
+        resumed.load_snapshot(&path).unwrap();
+        assert_eq!(resumed.processor.get_pc(), first.processor.get_pc());
+        assert!(resumed.run().is_success());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(resumed.processor.peek_word(0x100), Ok(1));
+        assert_eq!(resumed.processor.get_stats().instructions_executed, uninterrupted.processor.get_stats().instructions_executed);
+        assert_eq!(resumed.processor.get_stats().cycles, uninterrupted.processor.get_stats().cycles);
+        assert_eq!(resumed.processor.get_cache_stats().hits, uninterrupted.processor.get_cache_stats().hits);
+
+        let config = SimulatorConfig { memory_size: 1024 * 1024, ..SimulatorConfig::default() };
+        let mut other = MipsSimulator::new(config);
+        first.save_snapshot(&path).unwrap();
+        let error = other.load_snapshot(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(error, SimulatorError::SnapshotError(SnapshotError::Mismatch(_))), "{}", error);
+    }
+
+    #[test]
+    fn test_record_and_replay_input() {
+        let program = assembler::assemble_snippet(
+            "li $v0, 5; syscall; add $a0, $v0, $zero; li $v0, 1; syscall; li $v0, 10; syscall").unwrap();
+        let mut recorder = MipsSimulator::new_default();
+        recorder.set_input(GuestInput::from_bytes(b"7\n".to_vec()));
+        recorder.set_replay_log(Some(ReplayLog::recording()));
+        recorder.load_program(&program).unwrap();
+        assert_eq!(recorder.run_captured().output, b"7\n");
+        let text = recorder.processor.replay_log().unwrap().to_text();
+
+        // 再生では入力元の内容にかかわらず記録した値を読む
+        let mut replayer = MipsSimulator::new_default();
+        replayer.set_input(GuestInput::from_bytes(b"99\n".to_vec()));
+        replayer.set_replay_log(Some(ReplayLog::parse(&text).unwrap()));
+        replayer.load_program(&program).unwrap();
+        assert_eq!(replayer.run_captured().output, b"7\n");
+        assert_eq!(replayer.processor.replay_log().unwrap().remaining(), 0);
+
+        // 記録と異なるシステムコールを呼んだら止める
+        let program = assembler::assemble_snippet("li $v0, 12; syscall; li $v0, 10; syscall").unwrap();
+        let mut diverged = MipsSimulator::new_default();
+        diverged.set_replay_log(Some(ReplayLog::parse(&text).unwrap()));
+        diverged.load_program(&program).unwrap();
+        assert_eq!(diverged.run().kind(), "exception.replay_divergence");
+    }
+
+    #[test]
+    fn test_profile_report() {
+        let config = SimulatorConfig { profile_top: Some(1), ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            0x20080003, // addi $t0, $zero, 3
+            0x2108FFFF, // loop: addi $t0, $t0, -1
+            0x1500FFFF, // bne $t0, $zero, loop
+            0x2002000A, // addi $v0, $zero, 10
+            0x0000000C, // syscall
+        ]).unwrap();
+        simulator.processor.set_symbols(SymbolTable::parse("loop 0x00400004").unwrap());
+        let outcome = simulator.run();
+        assert!(outcome.is_success(), "{}", outcome);
+        let report = simulator.get_profile_report().unwrap();
+        assert!(report.starts_with("実行命令数: 9 (アドレス数: 5)"), "{}", report);
+        assert!(report.ends_with("3 ( 33.33%) 0x00400004 <loop>: addi $8, $8, -1"), "{}", report);
+        assert_eq!(MipsSimulator::new_default().get_profile_report(), None);
+    }
+
+    #[test]
+    fn test_footprint_report() {
+        let config = SimulatorConfig { footprint_page: Some(0x1000), initial_sp: 0x00F00000, ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            asm("addi $sp, $sp, -32"),
+            asm("sw $zero, 0($sp)"),
+            asm("addi $a0, $zero, 100"),
+            asm("addi $v0, $zero, 9"),     // sbrk(100)
+            asm("syscall"),
+            asm("lw $t0, 0x2000($zero)"),
+            asm("addi $sp, $sp, 32"),
+            asm("addi $v0, $zero, 10"),
+            asm("syscall"),
+        ]).unwrap();
+        assert!(simulator.run().is_success());
+        let footprint = simulator.processor.footprint().unwrap();
+        // テキスト・スタック・0x2000 の3ページ
+        assert_eq!(footprint.resident_bytes(), 3 * 0x1000);
+        assert_eq!(footprint.lines(), 4);
+        assert_eq!(footprint.peak_heap(), 104);
+        let report = simulator.get_footprint_report().unwrap();
+        assert!(report.ends_with("スタックの最大: 32 バイト ($spの最小値: 0x00EFFFE0)"), "{}", report);
+        assert_eq!(MipsSimulator::new_default().get_footprint_report(), None);
+    }
+
+    #[test]
+    fn test_heatmap_report() {
+        let config = SimulatorConfig { heatmap_block: Some(0x100), ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            asm("sw $zero, 0x300($zero)"),
+            asm("sw $zero, 0x100($zero)"),
+            asm("lw $t0, 0x1FC($zero)"),
+            asm("addi $v0, $zero, 10"),
+            asm("syscall"),
+        ]).unwrap();
+        simulator.processor.set_symbols(SymbolTable::parse("scene 0x00000100").unwrap());
+        assert!(simulator.run().is_success());
+        let report = simulator.get_heatmap_report().unwrap();
+        assert!(report.starts_with("ロード・ストア数: 3 (ブロック: 256 バイト × 2)"), "{}", report);
+        assert!(report.contains("2 ( 66.67%) 読み込み            1 / 書き込み            1  0x00000100 <scene>"), "{}", report);
+        assert_eq!(MipsSimulator::new_default().get_heatmap_report(), None);
+    }
+
+    #[test]
+    fn test_function_profile_report() {
+        let config = SimulatorConfig { profile_functions: true, ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            0x0C100003, // main: jal f
+            0x2002000A, // addi $v0, $zero, 10
+            0x0000000C, // syscall
+            0x20080001, // f: addi $t0, $zero, 1
+            0x03E00008, // jr $ra
+        ]).unwrap();
+        simulator.processor.set_symbols(SymbolTable::parse("main 0x00400000\nf 0x0040000C").unwrap());
+        assert!(simulator.run().is_success());
+        let report = simulator.get_function_profile_report().unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4, "{}", report);
+        assert!(lines[0].starts_with("実行命令数: 5,"), "{}", report);
+        // 自身のサイクル数の多い順: main（3命令、累積5命令）、f（2命令）
+        assert!(lines[2].ends_with("0x00400000 <main>"), "{}", report);
+        assert!(lines[3].trim_start().starts_with("1 "), "{}", report);
+        assert!(lines[3].ends_with("0x0040000C <f>"), "{}", report);
+    }
+
+    #[test]
+    fn test_run_captured_binary_output() {
+        let mut simulator = MipsSimulator::new_default();
+        let program = assembler::assemble_snippet(
+            "li $v0, 11; li $a0, 80; syscall; li $a0, 0; syscall; li $a0, 255; syscall; \
+             li $v0, 1; li $a0, -7; syscall; li $v0, 10; syscall").unwrap();
+        simulator.load_program(&program).unwrap();
+        let run = simulator.run_captured();
+        assert!(run.outcome.is_success());
+        assert_eq!(run.output, vec![b'P', 0x00, 0xFF, b'-', b'7', b'\n']);
+    }
+
+    #[test]
+    fn test_guest_env_syscalls() {
+        let mut config = SimulatorConfig::default();
+        config.env.parse_assignment("DEPTH=0x10").unwrap();
+        let mut simulator = MipsSimulator::new(config);
+        let start = simulator.get_config().program_start;
+        simulator.load_program(&[
+            0x20020065, // addi $v0, $zero, 101 (getenv_int)
+            0x20050003, // addi $a1, $zero, 3
+            0x0000000C, // syscall
+            0x0000000D, // break
+        ]).unwrap();
+
+        // キー文字列はプログラムの後ろに置き、アドレスを$a0に直接設定する
+        let key = start + 0x20;
+        simulator.processor.poke_word(key, u32::from_le_bytes(*b"DEPT")).unwrap();
+        simulator.processor.poke_word(key + 4, u32::from_le_bytes(*b"H\0\0\0")).unwrap();
+        simulator.processor.set_register(4, key);
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(2), 0x10);
+
+        // 見つからないキーは$a1の値になる
+        simulator.processor.poke_word(key, u32::from_le_bytes(*b"NONE")).unwrap();
+        simulator.processor.set_pc(start);
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(2), 3);
+    }
+
+    #[test]
+    fn test_dump_memory() {
+        let mut simulator = MipsSimulator::new_default();
+        simulator.patch_word(0x1004, 0x64636261).unwrap();
+        let dump = simulator.handle_memory_command("0x1000 8").unwrap();
+        assert_eq!(dump, "00001000: 00 00 00 00 61 62 63 64                          |....abcd        |\n");
+        assert!(simulator.handle_memory_command("0x1000").is_err());
+        assert!(simulator.dump_memory(0x1000, simulator.processor.memory_size()).is_err());
+    }
+
+    #[test]
+    fn test_parse_set_range() {
+        assert_eq!(parse_set_range(""), Ok(0..CACHE_SETS));
+        assert_eq!(parse_set_range("5"), Ok(5..6));
+        assert_eq!(parse_set_range("2 7"), Ok(2..8));
+        assert!(parse_set_range("7 2").is_err());
+        assert!(parse_set_range(&CACHE_SETS.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_find_in_memory() {
+        assert_eq!(parse_search_pattern("0xBEEF").unwrap(), (vec![0xEF, 0xBE], ""));
+        assert_eq!(parse_search_pattern("\"hi there\" 0 0x100").unwrap(), (b"hi there".to_vec(), " 0 0x100"));
+        assert!(parse_search_pattern("DEADBEEF").is_err());
+
+        let mut simulator = MipsSimulator::new_default();
+        simulator.patch_word(0x1002, 0xDEADBEEF).unwrap();
+        let (pattern, _) = parse_search_pattern("0xDEADBEEF").unwrap();
+        assert_eq!(simulator.find_in_memory(&pattern, 0, 0x2000).unwrap(), vec![0x1002]);
+    }
+
+    #[test]
+    fn test_multicore_schedule() {
+        let config = SimulatorConfig {
+            cores: 3,
+            schedule: SchedulePolicy::Trace(vec![2, 2, 0]),
+            switch_quantum: 2,
+            ..SimulatorConfig::default()
+        };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            0x20080001, // addi $t0, $zero, 1
+            0x011A4020, // add $t0, $t0, $k0
+            0x0000000D, // break
+        ]).unwrap();
+
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.get_core_outcomes().len(), 3);
+        assert!(simulator.get_core_outcomes().iter().all(|o| matches!(o, Some(RunResult::Breakpoint { .. }))));
+        assert_eq!(simulator.processor.get_register(8), 1);
+        assert_eq!(simulator.secondary_cores[0].get_register(8), 2);
+        assert_eq!(simulator.secondary_cores[1].get_register(8), 3);
+        // コア2はトレース通り2回割り当てられ、2回目でbreakに到達している
+        assert_eq!(simulator.secondary_cores[1].get_stats().instructions_executed, 2);
+    }
+
+    #[test]
+    fn test_multicore_per_core_report() {
+        let config = SimulatorConfig { cores: 2, schedule: SchedulePolicy::Cycle, ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            0x20080001, // addi $t0, $zero, 1
+            0x011A4020, // add $t0, $t0, $k0
+            0x0000000D, // break
+        ]).unwrap();
+
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        // サイクル数の少ないコアから動かすので、両方のコアが同じ命令数だけ進む
+        let counts: Vec<u64> = simulator.cores().map(|core| core.get_stats().instructions_executed).collect();
+        assert_eq!(counts, vec![2, 2]);
+        let state = simulator.get_processor_state();
+        assert!(state.contains("=== コア1 ===\n=== プロセッサ状態 ==="), "{}", state);
+        assert!(state.contains("$t0: 0x00000002 (2)"));
+        let json = simulator.get_stats_report().to_json();
+        assert!(json.contains("\"core.1.instructions\": 2"), "{}", json);
+    }
+
+    #[test]
+    fn test_multicore_coherence() {
+        let config = SimulatorConfig { cores: 2, switch_quantum: 4, max_instructions: Some(1000), ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            asm("addi $t1, $zero, 0x1000"),
+            asm("bne $k0, $zero, 4"),
+            // コア0: コア1が書き込むまで待つ
+            asm("lw $t0, 0($t1)"),
+            asm("beq $t0, $zero, -1"),
+            asm("break"),
+            // コア1: 書き込んだ値はダーティなままキャッシュに残る
+            asm("addi $t2, $zero, 42"),
+            asm("sw $t2, 0($t1)"),
+            asm("break"),
+        ]).unwrap();
+
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(8), 42);
+        // コア0が読み込んでいたラインはコア1の書き込みで無効になり、次の読み込みはコヒーレンスミスになる
+        let stats = simulator.processor.get_cache_stats();
+        assert_eq!((stats.invalidations, stats.coherence_misses), (1, 1));
+        // コア1のModifiedのラインはコア0の読み込みで書き戻される
+        assert_eq!(simulator.secondary_cores[0].get_cache_stats().writebacks, 1);
+    }
+
+    #[test]
+    fn test_microarch_snapshot_commands() {
+        let mut simulator = MipsSimulator::new_default();
+        simulator.load_program(&[0x8C080000]).unwrap(); // lw $t0, 0($zero)
+        simulator.handle_microarch_command("save cold_start").unwrap();
+        simulator.processor.step().unwrap();
+        simulator.handle_microarch_command("save warm").unwrap();
+        assert!(simulator.handle_microarch_command("load missing").is_err());
+        assert!(simulator.handle_microarch_command("bogus").is_err());
+
+        // ウォームに戻すと同じ命令がキャッシュミスなしで実行できる
+        let start = simulator.get_config().program_start;
+        for (name, expected_misses) in [("cold", 2), ("warm", 0), ("cold_start", 2)] {
+            simulator.handle_microarch_command(&format!("load {}", name)).unwrap();
+            let misses = simulator.get_cache_stats().misses;
+            simulator.processor.set_pc(start);
+            simulator.processor.step().unwrap();
+            assert_eq!(simulator.get_cache_stats().misses - misses, expected_misses, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_load_assembly_lines() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_asm_{}.hex", std::process::id()));
+        std::fs::write(&path, "li $t0, 0x12345678  # 疑似命令\n00430820\nmove $t1, $t0\nbreak\n").unwrap();
+        let mut simulator = MipsSimulator::new_default();
+        simulator.load_program_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let start = simulator.get_config().program_start;
+        let words: Vec<Word> = (0..5).map(|i| simulator.processor.peek_word(start + i * 4).unwrap()).collect();
+        assert_eq!(words, vec![0x3C081234, 0x35085678, 0x00430820, 0x01004820, 0x0000000D]);
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(9), 0x12345678);
+    }
+
+    #[test]
+    fn test_load_binary_program() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_program_{}.bin", std::process::id()));
+        let program: Vec<u8> = [0x2008002Au32, 0x0000000D].iter().flat_map(|word| word.to_le_bytes()).collect();
+        std::fs::write(&path, program).unwrap();
+        let config = SimulatorConfig { program_format: ProgramFormat::Bin, ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        let loaded = simulator.load_program_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(8), 42);
+    }
+
+    #[test]
+    fn test_load_hex_byte_stream() {
+        // objcopy -O verilog のようなメモリ上の並びのバイト列（コメントと行の区切りは自由）
+        let path = std::env::temp_dir().join(format!("mips_simulator_bytes_{}.hex", std::process::id()));
+        std::fs::write(&path, "# addi $t0, $zero, 42\n2A 00 08 20\n0D00\n0000\n").unwrap();
+        let hex_options = HexOptions { layout: HexLayout::Bytes, ..HexOptions::default() };
+        let mut simulator = MipsSimulator::new(SimulatorConfig { hex_options, ..SimulatorConfig::default() });
+        let loaded = simulator.load_program_from_file(&path);
+        let as_words = MipsSimulator::read_program_file(&SimulatorConfig::default(), &path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(8), 42);
+        // ワード単位で読むと同じファイルでも別の命令列になる
+        assert!(matches!(as_words, Ok(ProgramFile::Words { words, .. }) if words[0] == 0x2A));
+    }
+
+    #[test]
+    fn test_load_elf_program() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_program_{}.elf", std::process::id()));
+        std::fs::write(&path, elf::test_elf(&[
+            0x3C080050, // lui $t0, 0x0050
+            0x8D090000, // lw $t1, 0($t0)
+            0x8D0A0004, // lw $t2, 4($t0)（.bss）
+            0x0000000D, // break
+        ])).unwrap();
+        let config = SimulatorConfig { program_format: ProgramFormat::Elf, ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        let loaded = simulator.load_program_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert_eq!(simulator.processor.get_pc(), 0x00400000);
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(9), 0x04030201);
+        assert_eq!(simulator.processor.get_register(10), 0);
+        // ELFのシンボルで表示でき、ヒープはデータの後ろから始まる
+        assert_eq!(simulator.processor.get_symbols().format_address(0x00400008), "0x00400008 <main+0x8>");
+        assert_eq!(simulator.resolve_address("table+4"), Ok(0x00500004));
+    }
+
+    #[test]
+    fn test_load_assembly_program() {
+        // 拡張子 .s からアセンブリ言語と判断し、ラベルを解決して読み込む
+        let path = std::env::temp_dir().join(format!("mips_simulator_program_{}.s", std::process::id()));
+        std::fs::write(&path, "main: li $t0, 3\nloop: addi $t1, $t1, 2\n  addi $t0, $t0, -1\n  bne $t0, $zero, loop\n  break\n").unwrap();
+        let config = SimulatorConfig { program_start: 0x00001000, ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        let loaded = simulator.load_program_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        // ラベルがシンボルになる
+        assert_eq!(simulator.processor.get_symbols().format_address(0x00001008), "0x00001008 <loop+0x4>");
+        assert_eq!(simulator.resolve_address("loop"), Ok(0x00001004));
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(9), 6);
+    }
+
+    #[test]
+    fn test_load_assembly_data() {
+        // .data セクションを指定したアドレスに置き、lw・la のラベルを解決する
+        let path = std::env::temp_dir().join(format!("mips_simulator_data_{}.s", std::process::id()));
+        std::fs::write(&path, concat!(
+            "        .data\n",
+            "values: .word 40, 2\n",
+            "name:   .asciiz \"fib\"\n",
+            "        .text\n",
+            "main:   lw   $t0, values\n",
+            "        la   $t1, values\n",
+            "        lw   $t2, 4($t1)\n",
+            "        add  $t0, $t0, $t2\n",
+            "        break\n",
+        )).unwrap();
+        let config = SimulatorConfig { data_base: Some(0x00500000), ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        let loaded = simulator.load_program_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(8), 42);
+        assert_eq!(simulator.processor.peek_word(0x00500008), Ok(u32::from_le_bytes(*b"fib\0")));
+        assert_eq!(simulator.resolve_address("name"), Ok(0x00500008));
+    }
+
+    #[test]
+    fn test_load_intel_hex_program() {
+        // 形式を指定しなくても、先頭の ':' から Intel HEX と判断する
+        let path = std::env::temp_dir().join(format!("mips_simulator_program_{}.ihex", std::process::id()));
+        std::fs::write(&path, ":020000040040BA\n:080000002A0008200D00000099\n:00000001FF\n").unwrap();
+        let mut simulator = MipsSimulator::new_default();
+        let loaded = simulator.load_program_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(8), 42);
+
+        let config = SimulatorConfig { program_format: ProgramFormat::Srec, ..SimulatorConfig::default() };
+        let path = std::env::temp_dir().join(format!("mips_simulator_program_{}.srec", std::process::id()));
+        std::fs::write(&path, "S309004000002A00082065\n").unwrap();
+        let loaded = MipsSimulator::new(config).load_program_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(SimulatorError::ParseError(message)) if message.contains("チェックサム")));
+    }
+
+    #[test]
+    fn test_load_data_images() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_data_{}.bin", std::process::id()));
+        std::fs::write(&path, [0x78, 0x56, 0x34, 0x12, 0xFF]).unwrap();
+        let image = DataImage::parse(&format!("{}@0x2000", path.display())).unwrap();
+        let config = SimulatorConfig { data_images: vec![image.clone()], ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            0x8C082000, // lw $t0, 0x2000($zero)
+            0x0000000D, // break
+        ]).unwrap();
+        let loaded = simulator.load_data_images();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), vec![(image, 5)]);
+
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        assert_eq!(simulator.processor.get_register(8), 0x12345678);
+        assert!(simulator.processor.load_data(simulator.processor.memory_size() as MemoryAddress - 2, &[0; 4]).is_err());
+    }
+
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), any(target_os = "linux", target_os = "macos")))]
+    #[test]
+    fn test_memory_file() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_memory_{}.bin", std::process::id()));
+        let backing = MemoryBacking::File(path.display().to_string());
+        let config = SimulatorConfig { memory_size: 8 * 1024 * 1024, memory_backing: backing, ..SimulatorConfig::default() };
+        let mut first = MipsSimulator::try_new(config.clone()).unwrap();
+        first.load_program(&[
+            0x2008002A, // addi $t0, $zero, 42
+            0xAC082000, // sw $t0, 0x2000($zero)
+            0x0000000D, // break
+        ]).unwrap();
+        assert!(matches!(first.run(), RunResult::Breakpoint { .. }));
+        // ストアはキャッシュに残っているので、書き出すときにメモリへ書き戻す
+        assert_eq!(first.finish_memory_file().unwrap().unwrap(), path.display().to_string());
+        drop(first);
+
+        let mut second = MipsSimulator::try_new(config).unwrap();
+        second.load_program(&[
+            0x8C092000, // lw $t1, 0x2000($zero)
+            0x0000000D, // break
+        ]).unwrap();
+        assert!(matches!(second.run(), RunResult::Breakpoint { .. }));
+        let loaded = second.processor.get_register(9);
+        drop(second);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, 42);
+    }
+
+    #[test]
+    fn test_load_rom_images() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_rom_{}.bin", std::process::id()));
+        std::fs::write(&path, 0x12345678u32.to_le_bytes()).unwrap();
+        let image = DataImage::parse(&format!("{}@0x1000", path.display())).unwrap();
+        let config = SimulatorConfig { rom_images: vec![image.clone()], ..SimulatorConfig::default() };
+        let mut simulator = MipsSimulator::new(config);
+        simulator.load_program(&[
+            0x8C081000, // lw $t0, 0x1000($zero)
+            0xAC081004, // sw $t0, 0x1004($zero)（ROMの外）
+            0xAC001000, // sw $zero, 0x1000($zero)
+        ]).unwrap();
+        let loaded = simulator.load_rom_images();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), vec![(image, 4)]);
+
+        let outcome = simulator.run();
+        assert_eq!(outcome.kind(), "exception.protection_violation");
+        assert!(matches!(outcome, RunResult::Exception { pc: 0x00400008, .. }));
+        assert_eq!(simulator.processor.get_register(8), 0x12345678);
+        assert_eq!(simulator.processor.peek_word(0x1000), Ok(0x12345678));
+        assert_eq!(simulator.processor.peek_word(0x1004), Ok(0x12345678));
+    }
+
+    #[test]
+    fn test_check_expected_state() {
+        let path = std::env::temp_dir().join(format!("mips_simulator_expect_{}.txt", std::process::id()));
+        std::fs::write(&path, "$t0 = 42\n$t1 = 0\n[0x2000] = 42 0\n").unwrap();
+        let mut simulator = MipsSimulator::new_default();
+        simulator.load_program(&[
+            0x2008002A, // addi $t0, $zero, 42
+            0xAC082000, // sw $t0, 0x2000($zero)
+            0x20090001, // addi $t1, $zero, 1
+            0x0000000D, // break
+        ]).unwrap();
+        assert!(matches!(simulator.run(), RunResult::Breakpoint { .. }));
+        let comparison = simulator.check_expected_state(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let comparison = comparison.unwrap();
+        assert_eq!(comparison.checked, 4);
+        assert_eq!(comparison.mismatches.len(), 1);
+        assert_eq!(comparison.mismatches[0].1, Ok(1));
+        assert!(simulator.check_expected_state("/nonexistent/expect.txt").is_err());
+    }
+
+    #[test]
+    fn test_bench_kernels() {
+        for kernel in bench::KERNELS {
+            let mut simulator = MipsSimulator::new_default();
+            let result = simulator.run_kernel(kernel).unwrap();
+            assert!(result.passed(), "{}: {:?} {} != {}", kernel.name, result.outcome, result.result, result.expected);
+            assert!(result.cycles >= result.instructions);
+        }
+        // 開始アドレスを変えてもカーネルは決まったアドレスで動く
+        let config = SimulatorConfig { program_start: 0x1000, ..SimulatorConfig::default() };
+        for kernel in bench::KERNELS {
+            let result = MipsSimulator::try_new(config.clone()).unwrap().run_kernel(kernel).unwrap();
+            assert!(result.passed(), "{}: {:?}", kernel.name, result.outcome);
+        }
+    }
+
+    #[test]
+    fn test_no_cache_skips_unrequested_stats() {
+        // --no-cache ではカバレッジとタイミングのヒストグラムを記録しないが、命令数とサイクル数は数える
+        let mut config = SimulatorConfig::default();
+        config.cache.bypass = true;
+        let kernel = &bench::KERNELS[0];
+        let mut simulator = MipsSimulator::try_new(config.clone()).unwrap();
+        let result = simulator.run_kernel(kernel).unwrap();
+        assert!(result.passed() && result.instructions > 0 && result.cycles >= result.instructions);
+        assert_eq!(simulator.processor.get_timing_stats().instructions(), 0);
+        assert!(!simulator.get_processor_state().contains("=== 命令タイミング ==="));
+        let report = simulator.get_stats_report();
+        assert_eq!(report.get("coverage.executed_addresses"), Some(0.0));
+
+        // 統計JSONを書き出すなら記録する
+        config.stats_json_path = Some("stats.json".to_string());
+        let mut simulator = MipsSimulator::try_new(config).unwrap();
+        let result = simulator.run_kernel(kernel).unwrap();
+        assert_eq!(simulator.processor.get_timing_stats().instructions(), result.instructions);
+        assert!(simulator.get_stats_report().get("coverage.executed_addresses").unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_resolve_symbolic_address() {
+        let mut simulator = MipsSimulator::new_default();
+        simulator.processor.set_symbols(SymbolTable::parse("fib 0x00400010 0x20").unwrap());
+        assert_eq!(simulator.resolve_address("0x100"), Ok(0x100));
+        assert_eq!(simulator.resolve_address("fib"), Ok(0x00400010));
+        assert_eq!(simulator.resolve_address("fib+0x8"), Ok(0x00400018));
+        assert!(simulator.resolve_address("main").is_err());
+    }
+
+    #[test]
+    fn test_symbols_file_for_bare_program() {
+        // nm の出力形式のシンボルファイルで、素のプログラムの停止位置に名前を付ける
+        let path = std::env::temp_dir().join(format!("mips_simulator_symbols_{}.sym", std::process::id()));
+        std::fs::write(&path, "00400000 T main\n00400008 T done\n").unwrap();
+        let mut simulator = MipsSimulator::new_default();
+        simulator.load_program(&[0x20080001, 0x20090002, 0x00000000, 0x0000000D]).unwrap();
+        let loaded = simulator.load_symbols_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), 2);
+        let outcome = simulator.run();
+        assert_eq!(outcome.pc(), Some(0x0040000C));
+        assert_eq!(simulator.processor.get_symbols().label(0x0040000C), Some("done+0x4".to_string()));
+        assert_eq!(RunResult::Exited { code: 0 }.pc(), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! シミュレータの状態のスナップショット（長い実行の中断・再開や、実機との食い違いの二分探索に使う）
+//!
+//! 独自のバイナリ形式（数値はリトルエンディアン）で、先頭にマジックとバージョンを置く。
+//! メモリは4KBのページに分け、0以外のバイトを含むページだけを書き出す。
+
+use std::fmt;
+
+/// ファイルの先頭に置くマジック
+const SNAPSHOT_MAGIC: &[u8; 8] = b"MIPSSNAP";
+
+/// 形式のバージョン（互換性のない変更をしたら上げる）
+const SNAPSHOT_VERSION: u32 = 7;
+
+/// メモリを書き出す単位
+const PAGE_SIZE: usize = 4096;
+
+/// スナップショットの読み込みエラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    /// スナップショットのファイルではない
+    BadMagic,
+    /// 対応していないバージョン
+    UnsupportedVersion(u32),
+    /// 途中で終わっている
+    Truncated,
+    /// 内容が壊れている
+    Corrupted(String),
+    /// 保存したときとシミュレータの構成が異なる
+    Mismatch(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::BadMagic => write!(f, "スナップショットのファイルではありません"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "対応していないスナップショットのバージョンです: {}（対応: {}）", version, SNAPSHOT_VERSION)
+            }
+            SnapshotError::Truncated => write!(f, "スナップショットが途中で終わっています"),
+            SnapshotError::Corrupted(msg) => write!(f, "スナップショットが壊れています: {}", msg),
+            SnapshotError::Mismatch(msg) => write!(f, "保存したときと構成が異なります: {}", msg),
+        }
+    }
+}
+
+/// スナップショットの書き出し
+#[derive(Debug, Default)]
+pub struct SnapshotWriter {
+    bytes: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    /// マジックとバージョンを書いた状態で作成する
+    pub fn new() -> Self {
+        let mut writer = Self::default();
+        writer.bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        writer.put_u32(SNAPSHOT_VERSION);
+        writer
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// 長さ付きの文字列
+    pub fn put_str(&mut self, value: &str) {
+        self.put_u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// size バイトのメモリの内容（0だけのページは省く）
+    /// メモリ全体を一度に複製しないよう、read_page でページ（先頭と長さ）ごとに読む
+    pub fn put_memory(&mut self, size: usize, mut read_page: impl FnMut(usize, usize) -> Vec<u8>) {
+        self.put_u64(size as u64);
+        // ページ数は書き出し終えてから埋める
+        let count_position = self.bytes.len();
+        self.put_u32(0);
+        let mut count = 0u32;
+        for index in 0..size.div_ceil(PAGE_SIZE) {
+            let start = index * PAGE_SIZE;
+            let page = read_page(start, PAGE_SIZE.min(size - start));
+            if page.iter().any(|&byte| byte != 0) {
+                self.put_u32(index as u32);
+                self.bytes.extend_from_slice(&page);
+                count += 1;
+            }
+        }
+        self.bytes[count_position..count_position + 4].copy_from_slice(&count.to_le_bytes());
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// スナップショットの読み込み
+#[derive(Debug)]
+pub struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// マジックとバージョンを確かめて作成する
+    pub fn new(bytes: &'a [u8]) -> Result<Self, SnapshotError> {
+        if !bytes.starts_with(SNAPSHOT_MAGIC) {
+            return Err(SnapshotError::BadMagic);
+        }
+        let mut reader = Self { bytes, position: SNAPSHOT_MAGIC.len() };
+        match reader.u32()? {
+            SNAPSHOT_VERSION => Ok(reader),
+            version => Err(SnapshotError::UnsupportedVersion(version)),
+        }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.bytes.len())
+            .ok_or(SnapshotError::Truncated)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn string(&mut self) -> Result<String, SnapshotError> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| SnapshotError::Corrupted("文字列がUTF-8ではありません".to_string()))
+    }
+
+    /// メモリの0でないページ（先頭アドレスと内容。サイズが expected_size と異なればエラー）
+    pub fn memory(&mut self, expected_size: usize) -> Result<Vec<(usize, &'a [u8])>, SnapshotError> {
+        let size = self.u64()? as usize;
+        if size != expected_size {
+            return Err(SnapshotError::Mismatch(format!("メモリサイズ {} バイト（現在は {} バイト）", size, expected_size)));
+        }
+        let mut pages = Vec::new();
+        for _ in 0..self.u32()? {
+            let start = self.u32()? as usize * PAGE_SIZE;
+            if start >= size {
+                return Err(SnapshotError::Corrupted(format!("メモリの範囲外のページです: 0x{:08X}", start)));
+            }
+            let end = (start + PAGE_SIZE).min(size);
+            pages.push((start, self.take(end - start)?));
+        }
+        Ok(pages)
+    }
+
+    /// 最後まで読んだことを確かめる
+    pub fn finish(self) -> Result<(), SnapshotError> {
+        if self.position == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(SnapshotError::Corrupted("末尾に余分なデータがあります".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_encoding() {
+        let mut memory = vec![0; PAGE_SIZE * 3 + 10];
+        memory[PAGE_SIZE + 1] = 0xAB;
+        memory[PAGE_SIZE * 3 + 9] = 0xCD;
+        let mut writer = SnapshotWriter::new();
+        writer.put_u8(7);
+        writer.put_u64(u64::MAX - 1);
+        writer.put_str("mips");
+        writer.put_memory(memory.len(), |start, length| memory[start..start + length].to_vec());
+        let bytes = writer.into_bytes();
+        // 0だけのページは書き出さない
+        assert!(bytes.len() < PAGE_SIZE * 2 + 100);
+
+        let mut reader = SnapshotReader::new(&bytes).unwrap();
+        assert_eq!(reader.u8(), Ok(7));
+        assert_eq!(reader.u64(), Ok(u64::MAX - 1));
+        assert_eq!(reader.string(), Ok("mips".to_string()));
+        let pages = reader.memory(memory.len()).unwrap();
+        assert_eq!(pages.iter().map(|&(start, page)| (start, page.len())).collect::<Vec<_>>(), [(PAGE_SIZE, PAGE_SIZE), (PAGE_SIZE * 3, 10)]);
+        assert_eq!((pages[0].1[1], pages[1].1[9]), (0xAB, 0xCD));
+        assert_eq!(reader.finish(), Ok(()));
+
+        let mut reader = SnapshotReader::new(&bytes).unwrap();
+        reader.take(17).unwrap();
+        assert!(matches!(reader.memory(16), Err(SnapshotError::Mismatch(_))));
+        assert!(matches!(SnapshotReader::new(&bytes[..bytes.len() - 1]).unwrap().take(bytes.len()), Err(SnapshotError::Truncated)));
+        assert!(matches!(SnapshotReader::new(b"NOTASNAP\x01\0\0\0"), Err(SnapshotError::BadMagic)));
+    }
+}
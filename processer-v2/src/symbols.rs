@@ -0,0 +1,218 @@
+//! シンボルファイル（.sym/.map）の読み込みとアドレスの記号表示
+//!
+//! 1行に1つ "名前 アドレス [サイズ]" を書く形式で、'#' 以降はコメントとして無視する。
+//! アドレスとサイズは10進数か0x付きの16進数で書く。
+//! 名前を後ろに書く "アドレス 名前" と nm の出力（"アドレス 種類 名前"）も受け付け、この場合のアドレスは0xがなくても16進数とする。
+//! GNU ld のリンカマップ（-Map の出力）は、"0x<アドレス> <名前>" の行だけをシンボルとして読む。
+
+use std::fs;
+use std::path::Path;
+
+use crate::assembler;
+use crate::memory::MemoryAddress;
+
+/// シンボル
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// 名前
+    pub name: String,
+    /// 先頭アドレス
+    pub address: MemoryAddress,
+    /// サイズ（バイト、不明な場合はNone）
+    pub size: Option<u32>,
+}
+
+/// シンボル表（アドレス順）
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// シンボルファイルの内容を解析する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.contains("Linker script and memory map") {
+            return Ok(Self::parse_linker_map(text));
+        }
+        let mut symbols = Vec::new();
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: &str| format!("シンボルファイルの {} 行目: {}: {}", line_num + 1, message, line);
+            if !(2..=3).contains(&fields.len()) {
+                return Err(error("\"名前 アドレス [サイズ]\" か \"アドレス 名前\" の形式で書いてください"));
+            }
+            // nm の未定義シンボル（"U 名前"）はアドレスがないので読み飛ばす
+            if fields.len() == 2 && matches!(fields[0], "U" | "u" | "w" | "v") {
+                continue;
+            }
+            let number = |text: &str| assembler::parse_integer(text).ok()
+                .filter(|value| (0..=u32::MAX as i64).contains(value))
+                .map(|value| value as u32);
+            let last = fields[fields.len() - 1];
+            if number(last).is_none() {
+                // "アドレス 名前" または "アドレス 種類 名前"
+                let address = parse_hex_address(fields[0]).ok_or_else(|| error("無効なアドレスです"))?;
+                symbols.push(Symbol { name: last.to_string(), address, size: None });
+                continue;
+            }
+            let address = number(fields[1]).ok_or_else(|| error("無効なアドレスです"))?;
+            let size = match fields.get(2) {
+                Some(size) => Some(number(size).ok_or_else(|| error("無効なサイズです"))?),
+                None => None,
+            };
+            symbols.push(Symbol { name: fields[0].to_string(), address, size });
+        }
+        symbols.sort_by_key(|symbol| symbol.address);
+        Ok(Self { symbols })
+    }
+
+    /// GNU ld のリンカマップから "0x<アドレス> <名前>" の行を読む（セクション・代入・入力ファイルの行は読み飛ばす）
+    fn parse_linker_map(text: &str) -> Self {
+        let symbols = text.lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let [address, name] = fields[..] else {
+                    return None;
+                };
+                let address = address.strip_prefix("0x").and_then(parse_hex_address)?;
+                let symbol_name = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$');
+                symbol_name.then(|| Symbol { name: name.to_string(), address, size: None })
+            })
+            .collect();
+        Self::from_symbols(symbols)
+    }
+
+    /// シンボルの列から作成する（ELFのシンボル表など）
+    pub fn from_symbols(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.address);
+        Self { symbols }
+    }
+
+    /// シンボルファイルを読み込む
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("シンボルファイルを開けません: {}", e))?;
+        Self::parse(&text)
+    }
+
+    /// シンボルの数
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// シンボルがないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// 名前からアドレスを探す
+    pub fn lookup(&self, name: &str) -> Option<MemoryAddress> {
+        self.symbols.iter().find(|symbol| symbol.name == name).map(|symbol| symbol.address)
+    }
+
+    /// アドレスを含むシンボルとその先頭からのオフセットを返す
+    /// サイズが不明なシンボルは次のシンボルの手前までを含むものとする
+    pub fn resolve(&self, address: MemoryAddress) -> Option<(&Symbol, u32)> {
+        let index = self.symbols.partition_point(|symbol| symbol.address <= address);
+        let symbol = self.symbols.get(index.checked_sub(1)?)?;
+        let offset = address - symbol.address;
+        match symbol.size {
+            Some(size) if offset >= size => None,
+            _ => Some((symbol, offset)),
+        }
+    }
+
+    /// シンボルファイルの形式（1行に1つ "名前 アドレス [サイズ]"）で書き出す
+    pub fn to_text(&self) -> String {
+        self.symbols.iter()
+            .map(|symbol| match symbol.size {
+                Some(size) => format!("{} 0x{:08X} 0x{:X}\n", symbol.name, symbol.address, size),
+                None => format!("{} 0x{:08X}\n", symbol.name, symbol.address),
+            })
+            .collect()
+    }
+
+    /// アドレスを "fib+0x10" の形式で表す（シンボルがなければNone）
+    pub fn label(&self, address: MemoryAddress) -> Option<String> {
+        match self.resolve(address)? {
+            (symbol, 0) => Some(symbol.name.clone()),
+            (symbol, offset) => Some(format!("{}+0x{:X}", symbol.name, offset)),
+        }
+    }
+
+    /// アドレスを "0x00400010 <fib+0x10>" の形式で表示する（シンボルがなければアドレスのみ）
+    pub fn format_address(&self, address: MemoryAddress) -> String {
+        match self.label(address) {
+            Some(label) => format!("0x{:08X} <{}>", address, label),
+            None => format!("0x{:08X}", address),
+        }
+    }
+}
+
+/// 0xの有無によらず16進数として解析する（64ビットの表記は上位が0の場合だけ受け付ける）
+fn parse_hex_address(text: &str) -> Option<MemoryAddress> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    u64::from_str_radix(digits, 16).ok().and_then(|address| MemoryAddress::try_from(address).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_table() {
+        let table = SymbolTable::parse("# 名前 アドレス サイズ\nfib 0x00400010 0x20\nmain 0x00400000\nbuffer 4096 16\n").unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.lookup("fib"), Some(0x00400010));
+        assert_eq!(table.format_address(0x00400018), "0x00400018 <fib+0x8>");
+        assert_eq!(table.format_address(0x0040000C), "0x0040000C <main+0xC>");
+        assert_eq!(table.format_address(0x00400000), "0x00400000 <main>");
+        // サイズを超えた位置とどのシンボルより前の位置はシンボルなし
+        assert_eq!(table.format_address(0x00400030), "0x00400030");
+        assert_eq!(table.format_address(0x10), "0x00000010");
+        assert_eq!(table.to_text(), "buffer 0x00001000 0x10\nmain 0x00400000\nfib 0x00400010 0x20\n");
+        assert_eq!(SymbolTable::parse(&table.to_text()).unwrap().len(), 3);
+        assert!(SymbolTable::parse("fib").is_err());
+
+        // "アドレス 名前" と nm の出力（アドレスは16進数）
+        let table = SymbolTable::parse("00400000 main\n0x00400010 fib\n00500000 D table\n         U printf\n").unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.label(0x00400014), Some("fib+0x4".to_string()));
+        assert_eq!(table.lookup("table"), Some(0x00500000));
+        assert!(SymbolTable::parse("zzz main").is_err());
+    }
+
+    #[test]
+    fn test_linker_map() {
+        let map = "\
+Memory Configuration
+
+Name             Origin             Length             Attributes
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+LOAD main.o
+                0x0000000000400000                . = 0x400000
+
+.text           0x0000000000400000       0x30
+ *(.text)
+ .text          0x0000000000400000       0x30 main.o
+                0x0000000000400000                main
+                0x0000000000400010                fib
+ *fill*         0x0000000000400030        0x4
+.data           0x0000000000500000        0x8 main.o
+                0x0000000000500000                table
+";
+        let table = SymbolTable::parse(map).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.format_address(0x00400018), "0x00400018 <fib+0x8>");
+        assert_eq!(table.lookup("table"), Some(0x00500000));
+        assert!(SymbolTable::parse("fib zzz").is_err());
+    }
+}
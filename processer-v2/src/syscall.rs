@@ -0,0 +1,161 @@
+//! システムコールのハンドラ（独自のカーネルABIを、プロセッサを変更せずにエミュレートするため）
+//!
+//! ハンドラはシステムコール番号と、レジスタとメモリを読み書きできる SyscallContext を受け取る。
+//! デフォルトの実装は組み込みのSPIM互換のシステムコールで、独自のハンドラは扱わない番号をこれに任せられる。
+
+use std::fmt;
+
+use crate::instructions::{Instruction, Register, Word};
+use crate::memory::{MemoryAddress, MemoryError};
+use crate::processor::{Isa, Processor, ProcessorError};
+
+/// システムコールの結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyscallResult {
+    /// 戻り値なし
+    Done,
+    /// 戻り値をレジスタに返す（MIPSは$v0、RV32Iはa0）
+    Return(Word),
+    /// プログラムを終了する
+    Halt { code: i32 },
+}
+
+/// システムコールのハンドラ
+pub trait SyscallHandler: fmt::Debug {
+    /// ハンドラ名
+    fn name(&self) -> &'static str;
+    /// システムコールを処理する（デフォルトは組み込みのSPIM互換のシステムコール）
+    fn handle(&mut self, number: Word, context: &mut SyscallContext) -> Result<SyscallResult, ProcessorError> {
+        context.spim(number)
+    }
+}
+
+/// 組み込みのSPIM互換のシステムコールだけを扱うハンドラ（デフォルト）
+#[derive(Debug, Clone, Default)]
+pub struct SpimSyscalls;
+
+impl SyscallHandler for SpimSyscalls {
+    fn name(&self) -> &'static str {
+        "spim"
+    }
+}
+
+/// ハンドラに渡すプロセッサの状態
+/// メモリの読み書きはキャッシュ上の書き戻されていない内容を反映し、統計やキャッシュの状態を変えない
+pub struct SyscallContext<'a> {
+    processor: &'a mut Processor,
+    /// 引数（MIPSは$a0〜$a2、RV32Iはa0〜a2）
+    args: [Word; 3],
+    /// 実行中のsyscall命令（未対応の番号を無効な命令として報告するため）
+    instruction: Instruction,
+}
+
+impl<'a> SyscallContext<'a> {
+    pub fn new(processor: &'a mut Processor, args: [Word; 3], instruction: Instruction) -> Self {
+        Self { processor, args, instruction }
+    }
+
+    /// 実行中の命令セット（レジスタの番号付けはこれに従う）
+    pub fn isa(&self) -> Isa {
+        self.processor.get_isa()
+    }
+
+    /// 引数のレジスタの値
+    pub fn args(&self) -> [Word; 3] {
+        self.args
+    }
+
+    /// 汎用レジスタの値
+    pub fn register(&self, reg: Register) -> Word {
+        self.processor.get_register(reg)
+    }
+
+    /// 汎用レジスタに書き込む
+    pub fn set_register(&mut self, reg: Register, value: Word) {
+        self.processor.set_register(reg, value);
+    }
+
+    /// メモリからバイト列を読む
+    pub fn read_bytes(&self, address: MemoryAddress, length: usize) -> Result<Vec<u8>, MemoryError> {
+        self.processor.peek_range(address, length)
+    }
+
+    /// メモリにバイト列を書く（ステップ実行での逆実行と入力の記録に反映される）
+    pub fn write_bytes(&mut self, address: MemoryAddress, bytes: &[u8]) -> Result<(), MemoryError> {
+        self.processor.poke_bytes(address, bytes)
+    }
+
+    /// メモリから1ワードを読む
+    pub fn read_word(&self, address: MemoryAddress) -> Result<Word, MemoryError> {
+        self.processor.peek_word(address)
+    }
+
+    /// メモリに1ワードを書く
+    pub fn write_word(&mut self, address: MemoryAddress, value: Word) -> Result<(), MemoryError> {
+        self.write_bytes(address, &value.to_le_bytes())
+    }
+
+    /// 組み込みのSPIM互換のシステムコールとして処理する
+    pub fn spim(&mut self, number: Word) -> Result<SyscallResult, ProcessorError> {
+        self.processor.builtin_syscall(number, self.args, self.instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{RunResult, PC_INITIAL};
+
+    /// アセンブリの1行を命令のワードにする
+    fn asm(line: &str) -> Instruction {
+        crate::assembler::parse_instruction(line).unwrap().encode().unwrap()
+    }
+
+    /// 200番で$a0のアドレスに$a1を書いて$a1 + 1を返し、ほかの番号は組み込みに任せるハンドラ
+    #[derive(Debug, Default)]
+    struct StoreHandler {
+        calls: usize,
+    }
+
+    impl SyscallHandler for StoreHandler {
+        fn name(&self) -> &'static str {
+            "store"
+        }
+
+        fn handle(&mut self, number: Word, context: &mut SyscallContext) -> Result<SyscallResult, ProcessorError> {
+            if number != 200 {
+                return context.spim(number);
+            }
+            self.calls += 1;
+            let [address, value, _] = context.args();
+            context.write_word(address, value)?;
+            context.set_register(3, context.register(3) + 1); // $v1
+            Ok(SyscallResult::Return(value + 1))
+        }
+    }
+
+    #[test]
+    fn test_custom_syscall_handler() {
+        let program = [
+            asm("addi $a0, $zero, 256"),
+            asm("addi $a1, $zero, 41"),
+            asm("addi $v0, $zero, 200"),
+            asm("syscall"),
+            asm("add $a0, $v0, $v1"),
+            asm("addi $v0, $zero, 17"),
+            asm("syscall"),
+        ];
+        // PC_INITIAL にプログラムを置くため、既定の1MBより大きなメモリにする
+        let mut processor = Processor::with_memory_size(16 * 1024 * 1024);
+        processor.set_syscall_handler(Box::new(StoreHandler::default()));
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert_eq!(processor.run(), RunResult::Exited { code: 43 });
+        assert_eq!(processor.peek_word(256), Ok(41));
+        assert_eq!(processor.syscall_handler().name(), "store");
+
+        // デフォルトのハンドラでは200番は未対応
+        let mut processor = Processor::with_memory_size(16 * 1024 * 1024);
+        processor.load_program(&program, PC_INITIAL).unwrap();
+        assert_eq!(processor.run().kind(), "exception.invalid_instruction");
+    }
+}
@@ -0,0 +1,402 @@
+//! 命令ごとのサイクル数の見積もりとタイミング統計
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::instructions::{Instruction, InstructionType};
+use crate::memory::MemoryAddress;
+
+/// ALU（整数演算など大半の命令）のレイテンシのデフォルト値
+pub const DEFAULT_ALU_LATENCY: u64 = 1;
+
+/// 乗除算器（浮動小数点の乗除算・平方根・逆数）のレイテンシのデフォルト値
+pub const DEFAULT_MUL_DIV_LATENCY: u64 = 1;
+
+/// メモリからキャッシュラインを読み出すサイクル数のデフォルト値
+pub const DEFAULT_MEMORY_LATENCY: u64 = 10;
+
+/// 分岐予測ミス1回あたりのペナルティ（サイクル）のデフォルト値（simpleモデルのみ）
+pub const DEFAULT_MISPREDICT_PENALTY: u64 = 2;
+
+/// リオーダーバッファのエントリ数のデフォルト値（oooモデルのみ）
+pub const DEFAULT_ROB_SIZE: usize = 32;
+
+/// 予約ステーションのエントリ数のデフォルト値（oooモデルのみ）
+pub const DEFAULT_RS_SIZE: usize = 16;
+
+/// 1サイクルにフェッチ・発行・コミットできる命令数のデフォルト値（oooモデルのみ）
+pub const DEFAULT_ISSUE_WIDTH: usize = 2;
+
+/// ストール要因の上位として表示する件数
+pub const TOP_STALL_COUNT: usize = 10;
+
+/// サイクル数の見積もり方
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TimingModel {
+    /// 1命令ごとに基本サイクル数 + キャッシュミスのペナルティ
+    #[default]
+    Simple,
+    /// 5段パイプライン（ハザードによるストールとフラッシュを含む）
+    Pipeline,
+    /// 2命令同時発行のインオーダー5段パイプライン
+    DualIssue,
+    /// Tomasuloのアルゴリズムによるアウトオブオーダー実行
+    OutOfOrder,
+}
+
+impl TimingModel {
+    /// パイプラインで見積もるモデルか
+    pub fn is_pipelined(&self) -> bool {
+        matches!(self, TimingModel::Pipeline | TimingModel::DualIssue)
+    }
+
+    /// "simple"・"pipeline"・"dual-issue"・"ooo" から作成する
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "simple" => Ok(TimingModel::Simple),
+            "pipeline" => Ok(TimingModel::Pipeline),
+            "dual-issue" => Ok(TimingModel::DualIssue),
+            "ooo" => Ok(TimingModel::OutOfOrder),
+            _ => Err(format!("無効なタイミングモデルです: {}", text)),
+        }
+    }
+
+    /// 表示用の名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimingModel::Simple => "simple",
+            TimingModel::Pipeline => "pipeline",
+            TimingModel::DualIssue => "dual-issue",
+            TimingModel::OutOfOrder => "ooo",
+        }
+    }
+}
+
+/// タイミングモデルの設定（各レイテンシはその命令が演算器・キャッシュ・メモリを使うサイクル数）
+#[derive(Debug, Clone)]
+pub struct TimingConfig {
+    /// サイクル数の見積もり方
+    pub model: TimingModel,
+    /// ALUのレイテンシ（simpleモデルでは乗除算以外の命令の基本サイクル数、pipelineモデルではEXの段数）
+    pub alu_latency: u64,
+    /// 乗除算器のレイテンシ
+    pub mul_div_latency: u64,
+    /// データキャッシュへのアクセスに加わるサイクル数（ヒットした場合も含む）
+    pub cache_hit_latency: u64,
+    /// キャッシュミスを判定してラインを置き換えるまでに加わるサイクル数
+    pub cache_miss_latency: u64,
+    /// メモリからキャッシュラインを読み出すサイクル数
+    pub memory_latency: u64,
+    /// ダーティラインをメモリに書き戻すサイクル数（追い出した命令のストールに加わる）
+    pub writeback_latency: u64,
+    /// データキャッシュのMSHRの数（pipeline・dual-issueモデルのみ、0ならミスの間パイプラインを止めるブロッキングキャッシュ）
+    pub mshrs: usize,
+    /// 分岐予測ミス1回あたりのペナルティ（simpleモデルのみ、pipelineモデルでは分岐を確定する段で決まる）
+    pub mispredict_penalty: u64,
+    /// リオーダーバッファのエントリ数（oooモデルのみ）
+    pub rob_size: usize,
+    /// 予約ステーションのエントリ数（oooモデルのみ）
+    pub rs_size: usize,
+    /// 1サイクルにフェッチ・発行・コミットできる命令数（oooモデルのみ）
+    pub issue_width: usize,
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        Self {
+            model: TimingModel::default(),
+            alu_latency: DEFAULT_ALU_LATENCY,
+            mul_div_latency: DEFAULT_MUL_DIV_LATENCY,
+            cache_hit_latency: 0,
+            cache_miss_latency: 0,
+            memory_latency: DEFAULT_MEMORY_LATENCY,
+            writeback_latency: 0,
+            mshrs: 0,
+            mispredict_penalty: DEFAULT_MISPREDICT_PENALTY,
+            rob_size: DEFAULT_ROB_SIZE,
+            rs_size: DEFAULT_RS_SIZE,
+            issue_width: DEFAULT_ISSUE_WIDTH,
+        }
+    }
+}
+
+impl TimingConfig {
+    /// キャッシュミス1回あたりのペナルティ
+    pub fn miss_penalty(&self) -> u64 {
+        self.cache_miss_latency + self.memory_latency
+    }
+
+    /// 命令が演算器を使うサイクル数
+    pub fn execute_latency(&self, is_mul_div: bool) -> u64 {
+        if is_mul_div { self.mul_div_latency } else { self.alu_latency }
+    }
+}
+
+impl fmt::Display for TimingConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / ALU {} / 乗除算 {} / キャッシュヒット +{} / キャッシュミス +{} / メモリ {} / 書き戻し {} サイクル",
+            self.model.name(), self.alu_latency, self.mul_div_latency,
+            self.cache_hit_latency, self.cache_miss_latency, self.memory_latency, self.writeback_latency)?;
+        if self.model.is_pipelined() && self.mshrs > 0 {
+            write!(f, " / MSHR {}", self.mshrs)?;
+        }
+        if self.model == TimingModel::OutOfOrder {
+            write!(f, " / ROB {} / RS {} / 発行幅 {}", self.rob_size, self.rs_size, self.issue_width)?;
+        }
+        Ok(())
+    }
+}
+
+/// ストールの要因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StallCause {
+    /// 命令フェッチでのキャッシュミス
+    Fetch,
+    /// ロードでのキャッシュミス
+    Load,
+    /// ストアでのキャッシュミス
+    Store,
+    /// ロード直後の結果の使用（パイプラインのみ）
+    LoadUse,
+    /// 成立した分岐・ジャンプによる後続命令の破棄（パイプラインのみ）
+    ControlHazard,
+    /// パイプラインの充填や後続段の詰まり（パイプラインのみ）
+    Structural,
+    /// 分岐予測ミス（simpleモデルで分岐予測器を使う場合のみ）
+    BranchMispredict,
+    /// 複数サイクルかかる演算やキャッシュアクセスでEX・MEMに留まる分（パイプラインのみ）
+    MultiCycle,
+    /// 先行する命令の結果を待って発行が遅れた分（oooモデルのみ）
+    Dependency,
+}
+
+impl StallCause {
+    /// 機械可読な名前
+    pub fn name(&self) -> &'static str {
+        match self {
+            StallCause::Fetch => "fetch_miss",
+            StallCause::Load => "load_miss",
+            StallCause::Store => "store_miss",
+            StallCause::LoadUse => "load_use",
+            StallCause::ControlHazard => "branch_flush",
+            StallCause::Structural => "structural",
+            StallCause::BranchMispredict => "branch_mispredict",
+            StallCause::MultiCycle => "multi_cycle",
+            StallCause::Dependency => "dependency",
+        }
+    }
+
+    /// キャッシュミスやダーティラインの書き戻しでメモリを待った分か
+    pub fn is_memory(&self) -> bool {
+        matches!(self, StallCause::Fetch | StallCause::Load | StallCause::Store)
+    }
+}
+
+/// 1命令の実行にかかったサイクルの内訳
+#[derive(Debug, Clone, Default)]
+pub struct InstructionTiming {
+    /// 基本サイクル数
+    pub base: u64,
+    /// 要因ごとのストールサイクル数
+    pub stalls: Vec<(StallCause, u64)>,
+}
+
+impl InstructionTiming {
+    /// 合計サイクル数
+    pub fn total(&self) -> u64 {
+        self.base + self.stall_cycles()
+    }
+
+    /// ストールサイクル数の合計
+    pub fn stall_cycles(&self) -> u64 {
+        self.stalls.iter().map(|(_, cycles)| cycles).sum()
+    }
+}
+
+/// 命令アドレスごとのストール情報
+#[derive(Debug, Clone)]
+pub struct PcStall {
+    /// その番地の命令
+    pub instruction: Instruction,
+    /// 実行回数
+    pub executions: u64,
+    /// ストールサイクル数の合計
+    pub stall_cycles: u64,
+}
+
+/// タイミング統計
+#[derive(Debug, Clone, Default)]
+pub struct TimingStats {
+    /// 総サイクル数
+    pub total_cycles: u64,
+    /// ストールサイクル数の合計
+    pub stall_cycles: u64,
+    /// 1命令あたりのサイクル数の区間ごとの命令数（キーは区間の上限, cycle_bucket を参照）
+    pub histogram: BTreeMap<u64, u64>,
+    /// 要因ごとのストールサイクル数
+    pub stalls_by_cause: BTreeMap<StallCause, u64>,
+    /// 命令アドレスごとのストール情報
+    pub stalls_by_pc: HashMap<MemoryAddress, PcStall>,
+}
+
+impl TimingStats {
+    /// 1命令分のタイミングを記録する
+    pub fn record(&mut self, pc: MemoryAddress, instruction: Instruction, timing: &InstructionTiming) {
+        let cycles = timing.total();
+        let stall = timing.stall_cycles();
+        self.total_cycles += cycles;
+        self.stall_cycles += stall;
+        *self.histogram.entry(cycle_bucket(cycles)).or_insert(0) += 1;
+        for (cause, cycles) in &timing.stalls {
+            *self.stalls_by_cause.entry(*cause).or_insert(0) += cycles;
+        }
+        if stall > 0 {
+            let entry = self.stalls_by_pc.entry(pc).or_insert(PcStall { instruction, executions: 0, stall_cycles: 0 });
+            entry.instruction = instruction;
+            entry.executions += 1;
+            entry.stall_cycles += stall;
+        }
+    }
+
+    /// 記録した命令数
+    pub fn instructions(&self) -> u64 {
+        self.histogram.values().sum()
+    }
+
+    /// メモリを待ったストールサイクル数
+    pub fn memory_stall_cycles(&self) -> u64 {
+        self.stalls_by_cause.iter().filter(|(cause, _)| cause.is_memory()).map(|(_, cycles)| cycles).sum()
+    }
+
+    /// メモリを待った分を除いたサイクル数
+    pub fn compute_cycles(&self) -> u64 {
+        self.total_cycles - self.memory_stall_cycles()
+    }
+
+    /// 1命令あたりのサイクル数
+    pub fn cpi(&self) -> f64 {
+        let instructions = self.instructions();
+        if instructions == 0 {
+            0.0
+        } else {
+            self.total_cycles as f64 / instructions as f64
+        }
+    }
+
+    /// ストールサイクルの多い順に命令アドレスを返す
+    pub fn top_stalls(&self, count: usize) -> Vec<(MemoryAddress, &PcStall)> {
+        let mut entries: Vec<(MemoryAddress, &PcStall)> = self.stalls_by_pc.iter().map(|(pc, s)| (*pc, s)).collect();
+        entries.sort_by(|a, b| b.1.stall_cycles.cmp(&a.1.stall_cycles).then(a.0.cmp(&b.0)));
+        entries.truncate(count);
+        entries
+    }
+
+    /// CSV形式で出力する（列: section,key,value,percent）
+    pub fn to_csv(&self) -> String {
+        let percent = |part: u64, total: u64| if total == 0 { 0.0 } else { part as f64 / total as f64 * 100.0 };
+        let mut result = String::from("section,key,value,percent\n");
+        let instructions = self.instructions();
+        for (bucket, count) in &self.histogram {
+            result.push_str(&format!("cpi_histogram,{},{},{:.4}\n", bucket_label(*bucket), count, percent(*count, instructions)));
+        }
+        for (key, cycles) in [("compute", self.compute_cycles()), ("memory_stall", self.memory_stall_cycles())] {
+            result.push_str(&format!("cycles,{},{},{:.4}\n", key, cycles, percent(cycles, self.total_cycles)));
+        }
+        for (cause, cycles) in &self.stalls_by_cause {
+            result.push_str(&format!("stall_cause,{},{},{:.4}\n", cause.name(), cycles, percent(*cycles, self.stall_cycles)));
+        }
+        for (pc, stall) in self.top_stalls(TOP_STALL_COUNT) {
+            result.push_str(&format!("stall_pc,0x{:08X},{},{:.4}\n", pc, stall.stall_cycles, percent(stall.stall_cycles, self.stall_cycles)));
+        }
+        result
+    }
+}
+
+impl fmt::Display for TimingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instructions = self.instructions();
+        let percent = |part: u64, total: u64| if total == 0 { 0.0 } else { part as f64 / total as f64 * 100.0 };
+
+        writeln!(f, "命令数: {}", instructions)?;
+        writeln!(f, "総サイクル数: {}", self.total_cycles)?;
+        writeln!(f, "CPI: {:.3}", self.cpi())?;
+        writeln!(f, "ストールサイクル数: {} ({:.2}%)", self.stall_cycles, percent(self.stall_cycles, self.total_cycles))?;
+        writeln!(f, "計算サイクル数: {} ({:.2}%) / メモリストールサイクル数: {} ({:.2}%)",
+            self.compute_cycles(), percent(self.compute_cycles(), self.total_cycles),
+            self.memory_stall_cycles(), percent(self.memory_stall_cycles(), self.total_cycles))?;
+        writeln!(f, "サイクル数ごとの命令数:")?;
+        for (bucket, count) in &self.histogram {
+            // ヒストグラムのバーは最大50文字
+            let bar = "#".repeat(((*count as f64 / instructions.max(1) as f64) * 50.0).ceil() as usize);
+            writeln!(f, "  {:>9} サイクル: {:>10} ({:>6.2}%) {}", bucket_label(*bucket), count, percent(*count, instructions), bar)?;
+        }
+        writeln!(f, "ストール要因:")?;
+        for (cause, cycles) in &self.stalls_by_cause {
+            writeln!(f, "  {:<12} {:>10} サイクル ({:>6.2}%)", cause.name(), cycles, percent(*cycles, self.stall_cycles))?;
+        }
+        write!(f, "ストールの多い命令（上位{}件）:", TOP_STALL_COUNT)?;
+        for (pc, stall) in self.top_stalls(TOP_STALL_COUNT) {
+            write!(f, "\n  0x{:08X}: {:>10} サイクル ({:>6.2}%, {} 回) {}",
+                pc, stall.stall_cycles, percent(stall.stall_cycles, self.stall_cycles),
+                stall.executions, InstructionType::decode(stall.instruction))?;
+        }
+        Ok(())
+    }
+}
+
+/// サイクル数をヒストグラムの区間（0, 1, 2, 3-4, 5-8, 9-16, ...）に分け、区間の上限を返す
+/// キャッシュミスのペナルティで命令ごとのサイクル数がばらばらになっても、行数が増えすぎないようにするため
+/// 2命令同時発行で前の命令と同じサイクルに発行した命令は0サイクルなので、1サイクルの命令とは分ける
+pub fn cycle_bucket(cycles: u64) -> u64 {
+    if cycles == 0 {
+        return 0;
+    }
+    cycles.checked_next_power_of_two().unwrap_or(u64::MAX)
+}
+
+/// 区間の上限から表示用のラベルを作る（"0", "1", "2", "3-4", "5-8", ...）
+fn bucket_label(bucket: u64) -> String {
+    if bucket <= 2 {
+        bucket.to_string()
+    } else {
+        format!("{}-{}", bucket / 2 + 1, bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_histogram() {
+        let mut stats = TimingStats::default();
+        let hit = InstructionTiming { base: 1, stalls: Vec::new() };
+        let miss = InstructionTiming { base: 1, stalls: vec![(StallCause::Load, 10)] };
+
+        stats.record(0x400000, 0x00430820, &hit);
+        stats.record(0x400004, 0x8D090000, &miss);
+        stats.record(0x400004, 0x8D090000, &miss);
+
+        assert_eq!(stats.total_cycles, 23);
+        assert_eq!(stats.histogram.get(&1), Some(&1));
+        assert_eq!(stats.histogram.get(&16), Some(&2));
+        assert_eq!(stats.stalls_by_cause.get(&StallCause::Load), Some(&20));
+        assert_eq!((stats.compute_cycles(), stats.memory_stall_cycles()), (3, 20));
+        assert_eq!(stats.top_stalls(1)[0].0, 0x400004);
+        assert!(stats.to_csv().contains("stall_pc,0x00400004,20,100.0000"));
+        assert!(stats.to_csv().contains("cpi_histogram,9-16,2,66.6667"));
+
+        // 同じサイクルに発行した命令は1サイクルの命令と分けて数える
+        stats.record(0x400008, 0x00430820, &InstructionTiming { base: 0, stalls: Vec::new() });
+        assert_eq!((stats.histogram.get(&0), stats.histogram.get(&1)), (Some(&1), Some(&1)));
+        assert!(stats.to_csv().contains("cpi_histogram,0,1,25.0000"));
+    }
+
+    #[test]
+    fn test_cycle_buckets() {
+        let buckets: Vec<u64> = [0, 1, 2, 3, 4, 5, 8, 9, 16, 17].iter().map(|&cycles| cycle_bucket(cycles)).collect();
+        assert_eq!(buckets, vec![0, 1, 2, 4, 4, 8, 8, 16, 16, 32]);
+        let labels: Vec<String> = [0, 1, 2, 4, 8, 32].iter().map(|&bucket| bucket_label(bucket)).collect();
+        assert_eq!(labels, vec!["0", "1", "2", "3-4", "5-8", "17-32"]);
+    }
+}
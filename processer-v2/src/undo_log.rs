@@ -0,0 +1,126 @@
+//! 逆実行のためのアンドゥログ（命令ごとに書き換える前の値を記録し、新しい命令から順に戻す）
+//!
+//! 戻せるのはアーキテクチャ状態（PC、レジスタ、HI/LO、FCSR、コプロセッサ0、メモリ、ヒープの終端）だけで、
+//! 統計やキャッシュの状態、ゲストプログラムの入出力は戻さない。
+
+use std::collections::VecDeque;
+
+use crate::cp0::Cp0;
+use crate::instructions::Word;
+use crate::memory::MemoryAddress;
+
+/// 記録する命令数のデフォルト値
+pub const DEFAULT_UNDO_LIMIT: usize = 10000;
+
+/// 1命令を実行する前の状態（変わったものだけを持つ）
+#[derive(Debug, Clone)]
+pub struct UndoRecord {
+    /// 実行前のPC
+    pub pc: MemoryAddress,
+    /// 書き換えたレジスタの元の値（レジスタ番号は PipelineOp の sources と同じ番号付け）
+    pub registers: Vec<(u8, Word)>,
+    /// 実行前のHI/LO
+    pub hi_lo: (Word, Word),
+    /// 実行前のFCSR
+    pub fcsr: Word,
+    /// 実行前のコプロセッサ0のレジスタ
+    pub cp0: Cp0,
+    /// 実行前のヒープの終端
+    pub heap_break: MemoryAddress,
+    /// 書き換えたメモリの元の値（書き込んだ順、戻すときは逆順に書く）
+    pub memory: Vec<(MemoryAddress, u8)>,
+}
+
+/// 直前に実行した命令のアンドゥログ
+#[derive(Debug, Clone)]
+pub struct UndoLog {
+    /// 記録済みの命令（末尾が最も新しい）
+    records: VecDeque<UndoRecord>,
+    /// 実行中の命令の記録
+    current: Option<UndoRecord>,
+    capacity: usize,
+}
+
+impl UndoLog {
+    pub fn new(capacity: usize) -> Self {
+        Self { records: VecDeque::new(), current: None, capacity }
+    }
+
+    /// 命令の実行を始める（実行前の状態を渡す）
+    pub fn begin(&mut self, record: UndoRecord) {
+        self.current = Some(record);
+    }
+
+    /// 実行中の命令がメモリを書き換える前の値を記録する（命令の実行中でなければ何もしない）
+    pub fn record_memory(&mut self, address: MemoryAddress, original: &[u8]) {
+        if let Some(record) = &mut self.current {
+            for (i, &byte) in original.iter().enumerate() {
+                record.memory.push((address.wrapping_add(i as u32), byte));
+            }
+        }
+    }
+
+    /// 命令の実行を終え、書き換えたレジスタの元の値とともに記録する（最も古い記録を捨てる）
+    pub fn commit(&mut self, registers: Vec<(u8, Word)>) {
+        if let Some(mut record) = self.current.take() {
+            record.registers = registers;
+            if self.records.len() == self.capacity {
+                self.records.pop_front();
+            }
+            self.records.push_back(record);
+        }
+    }
+
+    /// 最も新しい記録を取り出す
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.records.pop_back()
+    }
+
+    /// 戻せる命令数
+    pub fn depth(&self) -> usize {
+        self.records.len()
+    }
+
+    /// 記録を消去する
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.current = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(pc: MemoryAddress) -> UndoRecord {
+        UndoRecord {
+            pc,
+            registers: Vec::new(),
+            hi_lo: (0, 0),
+            fcsr: 0,
+            cp0: Cp0::default(),
+            heap_break: 0,
+            memory: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_undo_log_bound() {
+        let mut log = UndoLog::new(2);
+        // 命令の実行中でなければメモリの書き込みは記録しない
+        log.record_memory(0x100, &[1]);
+        for pc in [0x400, 0x404, 0x408] {
+            log.begin(record(pc));
+            log.record_memory(0x100, &[0xAA, 0xBB]);
+            log.commit(vec![(8, pc)]);
+        }
+        assert_eq!(log.depth(), 2);
+
+        let newest = log.pop().unwrap();
+        assert_eq!(newest.pc, 0x408);
+        assert_eq!(newest.registers, vec![(8, 0x408)]);
+        assert_eq!(newest.memory, vec![(0x100, 0xAA), (0x101, 0xBB)]);
+        assert_eq!(log.pop().unwrap().pc, 0x404);
+        assert!(log.pop().is_none());
+    }
+}
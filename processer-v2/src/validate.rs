@@ -0,0 +1,172 @@
+//! 実行前のプログラムの検査（切り詰められたファイルやバイト順の誤りを、何万命令も実行した後の分かりにくいエラーになる前に見つけるため）
+//!
+//! 実行可能なセグメントの各ワードをデコードし、無効な命令、読み込んだイメージの外への分岐・ジャンプ、
+//! データ（実行可能でないセグメント）への分岐・ジャンプを報告する。レジスタで飛び先が決まる jr・jalr は調べない。
+
+use std::fmt;
+
+use crate::instructions::InstructionType;
+use crate::loader::{self, LoadImage};
+use crate::memory::MemoryAddress;
+use crate::processor::Isa;
+use crate::riscv::RvInstruction;
+use crate::symbols::SymbolTable;
+
+/// 問題の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IssueKind {
+    /// 無効な命令にデコードされる
+    InvalidInstruction,
+    /// 分岐・ジャンプ先が読み込んだイメージの外
+    TargetOutsideImage(MemoryAddress),
+    /// 分岐・ジャンプ先がデータのセグメントの中
+    TargetInData(MemoryAddress),
+}
+
+/// 見つかった問題
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub address: MemoryAddress,
+    pub word: u32,
+    pub kind: IssueKind,
+}
+
+/// 検査の結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+    /// 調べた命令数
+    pub words: usize,
+    /// バイト順を入れ替えると有効な命令になるワードの数（無効な命令のうち）
+    pub swapped_valid: usize,
+}
+
+impl ValidationReport {
+    /// 無効な命令の数
+    pub fn invalid_count(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.kind == IssueKind::InvalidInstruction).count()
+    }
+
+    /// 無効な命令の多くがバイト順を入れ替えると有効になるか（16進数のテキストのバイト順の誤りの可能性が高い）
+    pub fn looks_byte_swapped(&self) -> bool {
+        let invalid = self.invalid_count();
+        invalid * 4 >= self.words && self.swapped_valid * 2 > invalid
+    }
+
+    /// 問題を1件ずつ表示する（limit 件を超える分は件数だけ表示する）
+    pub fn format(&self, isa: Isa, symbols: &SymbolTable, limit: usize) -> String {
+        let mut text = String::new();
+        for issue in self.issues.iter().take(limit) {
+            let instruction = match isa {
+                Isa::Mips => InstructionType::decode(issue.word).display_at(issue.address, symbols),
+                Isa::Rv32i => RvInstruction::decode(issue.word).display_at(issue.address, symbols),
+            };
+            text.push_str(&format!("  {}: {:08X}  {}: {}\n", symbols.format_address(issue.address), issue.word, instruction, issue.kind));
+        }
+        if self.issues.len() > limit {
+            text.push_str(&format!("  ...（ほかに {} 件）\n", self.issues.len() - limit));
+        }
+        text
+    }
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::InvalidInstruction => write!(f, "無効な命令です"),
+            IssueKind::TargetOutsideImage(target) => write!(f, "飛び先 0x{:08X} が読み込んだプログラムの外です", target),
+            IssueKind::TargetInData(target) => write!(f, "飛び先 0x{:08X} がデータの中です", target),
+        }
+    }
+}
+
+/// イメージの実行可能なセグメントを検査する
+pub fn validate(image: &LoadImage, isa: Isa) -> ValidationReport {
+    let segment_at = |address: MemoryAddress| image.segments.iter()
+        .find(|segment| segment.address <= address && (address as u64) < segment.end());
+    let mut report = ValidationReport::default();
+    for segment in image.segments.iter().filter(|segment| segment.executable) {
+        for (index, word) in loader::words_from_binary(&segment.data).into_iter().enumerate() {
+            let address = segment.address.wrapping_add(index as u32 * 4);
+            report.words += 1;
+            let (invalid, target) = match isa {
+                Isa::Mips => {
+                    let instruction = InstructionType::decode(word);
+                    (instruction == InstructionType::Invalid, instruction.target(address))
+                }
+                Isa::Rv32i => {
+                    let instruction = RvInstruction::decode(word);
+                    (instruction == RvInstruction::Invalid, instruction.target(address))
+                }
+            };
+            let kind = if invalid {
+                let swapped_valid = match isa {
+                    Isa::Mips => InstructionType::decode(word.swap_bytes()) != InstructionType::Invalid,
+                    Isa::Rv32i => RvInstruction::decode(word.swap_bytes()) != RvInstruction::Invalid,
+                };
+                if swapped_valid {
+                    report.swapped_valid += 1;
+                }
+                IssueKind::InvalidInstruction
+            } else if let Some(target) = target {
+                match segment_at(target) {
+                    None => IssueKind::TargetOutsideImage(target),
+                    Some(target_segment) if !target_segment.executable => IssueKind::TargetInData(target),
+                    Some(_) => continue,
+                }
+            } else {
+                continue;
+            };
+            report.issues.push(Issue { address, word, kind });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::Segment;
+
+    fn image(words: &[u32]) -> LoadImage {
+        LoadImage {
+            segments: vec![
+                Segment { address: 0x00400000, data: words.iter().flat_map(|word| word.to_le_bytes()).collect(), executable: true },
+                Segment { address: 0x00500000, data: vec![0; 8], executable: false },
+            ],
+            entry: None,
+            symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let report = validate(&image(&[
+            0x2008000A, // addi $t0, $zero, 10
+            0x1100FFFF, // beq $t0, $zero, 0x00400000
+            0x10000100, // beq $zero, $zero, 0x00400408（イメージの外）
+            0x08140000, // j 0x00500000（データ）
+            0xFC000000, // 無効な命令
+            0x0000000D, // break
+        ]), Isa::Mips);
+        assert_eq!(report.words, 6);
+        assert_eq!(report.issues, vec![
+            Issue { address: 0x00400008, word: 0x10000100, kind: IssueKind::TargetOutsideImage(0x00400408) },
+            Issue { address: 0x0040000C, word: 0x08140000, kind: IssueKind::TargetInData(0x00500000) },
+            Issue { address: 0x00400010, word: 0xFC000000, kind: IssueKind::InvalidInstruction },
+        ]);
+        assert!(!report.looks_byte_swapped());
+        let symbols = SymbolTable::parse("main 0x00400000 24").unwrap();
+        assert_eq!(report.format(Isa::Mips, &symbols, 1), "  0x00400008 <main+0x8>: 10000100  beq $0, $0, 0x00400408: \
+            飛び先 0x00400408 が読み込んだプログラムの外です\n  ...（ほかに 2 件）\n");
+    }
+
+    #[test]
+    fn test_validate_byte_swapped() {
+        // ビッグエンディアンのつもりで書いた命令をリトルエンディアンとして読んだ場合
+        let words: Vec<u32> = [0x2108FFFFu32, 0xAC2A0050, 0x1420FFFB].iter().map(|word| word.swap_bytes()).collect();
+        let report = validate(&image(&words), Isa::Mips);
+        assert_eq!(report.invalid_count(), 3);
+        assert!(report.looks_byte_swapped());
+    }
+}
@@ -0,0 +1,215 @@
+//! レジスタとメモリのウォッチポイント
+//!
+//! レジスタは命令を実行するたびに値を確かめ、変わったら止める。
+//! "$s0" のようにレジスタだけを書くと値が変わったときに、"$sp < 0x7FFF0000" のように条件を付けると
+//! 値が変わって新しい値が条件を満たしたときに止める。値は符号なしの32ビット整数として比べる。
+//!
+//! メモリは "0x10000000" や "0x10000000-0x10000100:rw:log" のようにアドレスか範囲（終了は含まない）に
+//! 監視するアクセス（r・w・rw、デフォルトはw）と、止めずに表示だけする "log" を付けて指定する。
+//! ロード・ストア命令とシステムコールによる書き込みを対象にし、命令を完了してから止める。
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::assembler::{parse_fp_register, parse_integer, parse_register};
+use crate::instructions::Word;
+use crate::memory::MemoryAddress;
+use crate::pipeline::FP_REGISTER_BASE;
+
+/// 条件に使える比較演算子（2文字のものを先に調べる）
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Eq),
+    ("!=", Comparison::Ne),
+    ("<=", Comparison::Le),
+    (">=", Comparison::Ge),
+    ("<", Comparison::Lt),
+    (">", Comparison::Gt),
+];
+
+/// 比較演算子
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// value が operand に対して条件を満たすか
+    fn holds(&self, value: Word, operand: Word) -> bool {
+        match self {
+            Comparison::Eq => value == operand,
+            Comparison::Ne => value != operand,
+            Comparison::Lt => value < operand,
+            Comparison::Le => value <= operand,
+            Comparison::Gt => value > operand,
+            Comparison::Ge => value >= operand,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        COMPARISONS.iter().find(|(_, comparison)| comparison == self).map_or("", |(symbol, _)| symbol)
+    }
+}
+
+/// レジスタのウォッチポイント
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watchpoint {
+    /// 監視するレジスタ（PipelineOpと同じ番号付け）
+    pub register: u8,
+    /// 指定した名前（表示用）
+    name: String,
+    /// 新しい値が満たすべき条件（Noneなら値が変わるたびに止める）
+    pub condition: Option<(Comparison, Word)>,
+}
+
+impl Watchpoint {
+    /// "<レジスタ>" または "<レジスタ> <演算子> <値>" の形式から作成する
+    /// （レジスタは $s0・$8・$f2 など、演算子は == != < <= > >=、値は10進数か0xで始まる16進数）
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let (name, condition) = match COMPARISONS.iter().find_map(|(symbol, comparison)| {
+            text.split_once(symbol).map(|(name, value)| (name, comparison, value))
+        }) {
+            Some((name, comparison, value)) => {
+                let value = parse_integer(value)
+                    .ok()
+                    .filter(|&value| value >= i32::MIN as i64 && value <= u32::MAX as i64)
+                    .ok_or_else(|| format!("ウォッチポイントの値が不正です: {}", value.trim()))?;
+                (name.trim(), Some((*comparison, value as Word)))
+            }
+            None => (text, None),
+        };
+        let register = parse_register(name)
+            .or_else(|_| parse_fp_register(name).map(|fp| fp + FP_REGISTER_BASE))
+            .map_err(|_| format!("ウォッチポイントのレジスタが不正です: {}", name))?;
+        if register == 0 {
+            return Err("$zeroは変わらないので監視できません".to_string());
+        }
+        Ok(Self { register, name: name.to_string(), condition })
+    }
+
+    /// 命令の実行前後の値から、止めるかを判定する
+    pub fn triggered(&self, old: Word, new: Word) -> bool {
+        old != new && self.condition.is_none_or(|(comparison, operand)| comparison.holds(new, operand))
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.condition {
+            Some((comparison, value)) => write!(f, "{} {} 0x{:08X}", self.name, comparison.symbol(), value),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// メモリのウォッチポイント
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryWatchpoint {
+    /// 監視する範囲（終了は含まない）
+    pub range: Range<MemoryAddress>,
+    /// 読み込みで止めるか
+    pub read: bool,
+    /// 書き込みで止めるか
+    pub write: bool,
+    /// 止めずにアクセスを表示するだけか
+    pub log_only: bool,
+}
+
+impl MemoryWatchpoint {
+    /// "<アドレス>[:<アクセス>][:log]" または "<開始>-<終了>[:<アクセス>][:log]" の形式から作成する
+    /// （アドレスだけを書くとそのワード、アクセスは r・w・rw）
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.trim().split(':');
+        let address = |text: &str| parse_integer(text).ok()
+            .filter(|&value| (0..=u32::MAX as i64).contains(&value))
+            .map(|value| value as MemoryAddress)
+            .ok_or_else(|| format!("ウォッチポイントのアドレスが不正です: {}", text.trim()));
+        let spec = parts.next().unwrap_or_default();
+        let range = match spec.split_once('-') {
+            Some((start, end)) => address(start)?..address(end)?,
+            None => {
+                let start = address(spec)?;
+                start..start.saturating_add(4)
+            }
+        };
+        if range.is_empty() {
+            return Err(format!("ウォッチポイントの範囲が空です: {}", text.trim()));
+        }
+        let mut watchpoint = Self { range, read: false, write: true, log_only: false };
+        for option in parts {
+            match option.trim() {
+                "r" => (watchpoint.read, watchpoint.write) = (true, false),
+                "w" => (watchpoint.read, watchpoint.write) = (false, true),
+                "rw" => (watchpoint.read, watchpoint.write) = (true, true),
+                "log" => watchpoint.log_only = true,
+                option => return Err(format!("ウォッチポイントのオプションは r・w・rw・log で指定してください: {}", option)),
+            }
+        }
+        Ok(watchpoint)
+    }
+
+    /// address から size バイトのアクセスが対象であれば真
+    pub fn matches(&self, address: MemoryAddress, size: u32, write: bool) -> bool {
+        let end = address as u64 + size as u64;
+        (if write { self.write } else { self.read })
+            && (self.range.start as u64) < end && address < self.range.end
+    }
+}
+
+impl fmt::Display for MemoryWatchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match (self.read, self.write) {
+            (true, true) => "rw",
+            (true, false) => "r",
+            _ => "w",
+        };
+        write!(f, "0x{:08X}-0x{:08X}:{}", self.range.start, self.range.end, access)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchpoint_parse() {
+        let changed = Watchpoint::parse("$s0").unwrap();
+        assert_eq!((changed.register, changed.condition), (16, None));
+        assert!(changed.triggered(1, 2));
+        assert!(!changed.triggered(2, 2));
+
+        let below = Watchpoint::parse("$sp<0x7FFF0000").unwrap();
+        assert_eq!(below.to_string(), "$sp < 0x7FFF0000");
+        assert!(below.triggered(0x7FFF0000, 0x7FFEFFF8));
+        assert!(!below.triggered(0x7FFEFFF8, 0x7FFF0000));
+
+        let fp = Watchpoint::parse("$f2 >= -1").unwrap();
+        assert_eq!(fp.register, FP_REGISTER_BASE + 2);
+        assert_eq!(fp.condition, Some((Comparison::Ge, 0xFFFFFFFF)));
+        assert!(Watchpoint::parse("$zero").is_err());
+        assert!(Watchpoint::parse("$t0 == x").is_err());
+        assert!(Watchpoint::parse("$q1").is_err());
+    }
+
+    #[test]
+    fn test_memory_watchpoint_parse() {
+        let word = MemoryWatchpoint::parse("0x1000").unwrap();
+        assert_eq!(word, MemoryWatchpoint { range: 0x1000..0x1004, read: false, write: true, log_only: false });
+        assert!(word.matches(0x1003, 1, true));
+        assert!(word.matches(0x0FFE, 4, true));
+        assert!(!word.matches(0x1004, 4, true));
+        assert!(!word.matches(0x1000, 4, false));
+
+        let range = MemoryWatchpoint::parse("0x2000-0x2100:rw:log").unwrap();
+        assert!(range.read && range.write && range.log_only);
+        assert_eq!(range.to_string(), "0x00002000-0x00002100:rw");
+        assert!(MemoryWatchpoint::parse("0x2000-0x2000").is_err());
+        assert!(MemoryWatchpoint::parse("0x2000:x").is_err());
+        assert!(MemoryWatchpoint::parse("$sp").is_err());
+    }
+}